@@ -0,0 +1,86 @@
+//! Encode and decode throughput on large buffers
+//!
+//! Run with `cargo bench -p base64k`, and again with
+//! `cargo bench -p base64k --no-default-features` to compare the bulk path
+//! against the scalar one.  Without `--bench` (e.g. under
+//! `cargo test --benches`) each routine runs once as a smoke test.
+//!
+//! This is a smoke harness, not a statistical benchmarking tool: it reports
+//! the median and minimum of a fixed number of samples, so only compare
+//! numbers between runs on the same machine.
+
+use std::{
+    env,
+    hint::black_box,
+    io::prelude::*,
+    time::{Duration, Instant},
+};
+
+use base64k::{Decoder, Encoder};
+
+const SIZES: &[usize] = &[64 * 1024, 16 * 1024 * 1024];
+const SAMPLES: usize = 20;
+
+fn input(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state.to_le_bytes()[0]
+        })
+        .collect()
+}
+
+fn encode(bytes: &[u8]) -> String {
+    let mut enc = Encoder::<String>::default();
+    enc.write_all(bytes).unwrap();
+    enc.finish()
+}
+
+fn decode(s: &str) -> Vec<u8> {
+    let mut out = vec![];
+    Decoder::new(s.chars()).read_to_end(&mut out).unwrap();
+    out
+}
+
+fn run<T>(measure: bool, name: &str, len: usize, mut f: impl FnMut() -> T) {
+    if !measure {
+        black_box(f());
+        println!("{name}/{len}: ok");
+        return;
+    }
+
+    let mut samples: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .collect();
+    samples.sort_unstable();
+
+    let median = samples[samples.len() / 2];
+    #[expect(clippy::cast_precision_loss, reason = "Only used for display")]
+    let mib_s = len as f64 / (1024.0 * 1024.0) / median.as_secs_f64();
+    println!(
+        "{:<24} median {median:>10.3?}  min {:>10.3?}  {mib_s:>8.1} MiB/s",
+        format!("{name}/{len}"),
+        samples[0],
+    );
+}
+
+fn main() {
+    let measure = env::args().any(|a| a == "--bench");
+
+    for &len in SIZES {
+        let bytes = input(len);
+        let encoded = encode(&bytes);
+        assert_eq!(decode(&encoded), bytes);
+
+        run(measure, "encode", len, || encode(black_box(&bytes)));
+        run(measure, "decode", len, || decode(black_box(&encoded)));
+    }
+}
@@ -1,6 +1,8 @@
+#[cfg(all(feature = "bulk", not(miri)))]
+use wide::{i16x8, i32x8};
 use wide::u32x8;
 
-use crate::TRAIL_MASK;
+use crate::{OFFSET, SURROGATE_MASK, TRAIL_MASK};
 
 #[derive(Debug, Default)]
 #[repr(C, align(32))]
@@ -19,33 +21,49 @@ impl ShortArray {
     pub const BYTE_WIDTH: usize = ShortArray::WIDTH * 2;
     pub const WIDTH: usize = 8;
 
+    /// Pack [`BYTE_WIDTH`](Self::BYTE_WIDTH) bytes into an array of
+    /// little-endian byte pairs
+    #[cfg(feature = "bulk")]
+    #[inline]
+    pub fn from_le_pairs(bytes: &[u8; Self::BYTE_WIDTH]) -> Self {
+        let mut arr = [0; Self::WIDTH];
+
+        for (dw, pair) in arr.iter_mut().zip(bytes.chunks_exact(2)) {
+            *dw = u32::from(u16::from_le_bytes([pair[0], pair[1]]));
+        }
+
+        Self(arr)
+    }
+
     /// Bithackery to remap the stored values such that the UTF-16 surrogate
     /// pair range is avoided.  The valid scalar input range for this function
     /// is `0x00..(0x110000 - 0x800)`
     #[cfg(not(miri))]
     #[inline]
     pub fn encode(&self) -> Vector {
-        Vector((u32x8::from(self.0) + u32x8::splat(0x800)) ^ u32x8::splat(0xd800))
+        Vector((u32x8::from(self.0) + u32x8::splat(OFFSET)) ^ u32x8::splat(SURROGATE_MASK))
     }
 
     /// Inverse of [`encode`](Self::encode)
     #[cfg(not(miri))]
     #[inline]
     pub fn decode(&self) -> Vector {
-        Vector((u32x8::from(self.0) ^ u32x8::splat(0xd800)) - u32x8::splat(0x800))
+        Vector((u32x8::from(self.0) ^ u32x8::splat(SURROGATE_MASK)) - u32x8::splat(OFFSET))
     }
 
     #[cfg(any(miri, test))]
     fn encode_miri(&self) -> [u32; ShortArray::WIDTH] {
         let mut arr = self.0;
-        arr.iter_mut().for_each(|i| *i = (*i + 0x800) ^ 0xd800);
+        arr.iter_mut()
+            .for_each(|i| *i = (*i + OFFSET) ^ SURROGATE_MASK);
         arr
     }
 
     #[cfg(any(miri, test))]
     fn decode_miri(&self) -> [u32; ShortArray::WIDTH] {
         let mut arr = self.0;
-        arr.iter_mut().for_each(|i| *i = (*i ^ 0xd800) - 0x800);
+        arr.iter_mut()
+            .for_each(|i| *i = (*i ^ SURROGATE_MASK) - OFFSET);
         arr
     }
 
@@ -65,16 +83,56 @@ impl Vector {
     #[inline]
     pub fn to_array(&self) -> [u32; ShortArray::WIDTH] { self.0.to_array() }
 
+    /// Truncate each value to 16 bits and store the results as little-endian
+    /// byte pairs
+    #[cfg(all(feature = "bulk", not(miri)))]
+    #[inline]
+    pub fn to_le_pairs(&self) -> [u8; ShortArray::BYTE_WIDTH] {
+        #[expect(
+            clippy::cast_possible_wrap,
+            reason = "Only the low 16 bits of each value are kept"
+        )]
+        let words = i16x8::from_i32x8_truncate(i32x8::new(self.to_array().map(|d| d as i32)));
+        let mut bytes = [0; ShortArray::BYTE_WIDTH];
+
+        for (pair, word) in bytes.chunks_exact_mut(2).zip(words.to_array()) {
+            pair.copy_from_slice(&word.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    #[cfg(any(miri, test))]
+    #[cfg(feature = "bulk")]
+    fn to_le_pairs_miri(&self) -> [u8; ShortArray::BYTE_WIDTH] {
+        let mut bytes = [0; ShortArray::BYTE_WIDTH];
+
+        for (pair, dw) in bytes.chunks_exact_mut(2).zip(self.to_array()) {
+            #[expect(
+                clippy::cast_possible_truncation,
+                reason = "dw must be truncated to a u16"
+            )]
+            pair.copy_from_slice(&(dw as u16).to_le_bytes());
+        }
+
+        bytes
+    }
+
     #[cfg(miri)]
     pub fn trail_mask_hint(&self) -> bool { self.0.iter().any(|i| i & TRAIL_MASK != 0) }
 
     #[cfg(miri)]
     pub fn to_array(&self) -> [u32; ShortArray::WIDTH] { self.0 }
+
+    #[cfg(all(feature = "bulk", miri))]
+    pub fn to_le_pairs(&self) -> [u8; ShortArray::BYTE_WIDTH] { self.to_le_pairs_miri() }
 }
 
 #[cfg(test)]
 mod test {
     use super::ShortArray;
+    #[cfg(all(feature = "bulk", not(miri)))]
+    use crate::TRAIL_MASK;
 
     // Sanity check for MIRI-friendly non-vectorized encode
     #[cfg(not(miri))]
@@ -96,4 +154,14 @@ mod test {
             assert_eq!(arr.decode().to_array(), arr.decode_miri());
         }
     }
+
+    // Sanity check for MIRI-friendly non-vectorized byte pair conversion
+    #[cfg(all(feature = "bulk", not(miri)))]
+    #[test]
+    fn test_to_le_pairs_miri() {
+        for i in 0..0xffff {
+            let vec = ShortArray([i, i ^ 0xff, i | TRAIL_MASK, 0, i, 0xffff, i, i]).encode();
+            assert_eq!(vec.to_le_pairs(), vec.to_le_pairs_miri());
+        }
+    }
 }
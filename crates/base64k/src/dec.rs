@@ -11,6 +11,11 @@ pub struct Decoder<I> {
     it: I,
     // TODO: I bet there's a deranged way to use ShortArray for this
     buf: ArrayVec<u8, { ShortArray::BYTE_WIDTH }>,
+    chars_read: usize,
+    bytes_read: usize,
+    trailing_byte: bool,
+    #[cfg(all(test, feature = "bulk"))]
+    scalar: bool,
 }
 
 impl<I: Iterator<Item = char>> Decoder<I> {
@@ -20,10 +25,43 @@ impl<I: Iterator<Item = char>> Decoder<I> {
         Self {
             it: it.into_iter(),
             buf: ArrayVec::default(),
+            chars_read: 0,
+            bytes_read: 0,
+            trailing_byte: false,
+            #[cfg(all(test, feature = "bulk"))]
+            scalar: false,
+        }
+    }
+
+    /// Construct a decoder that never takes the bulk path
+    #[cfg(test)]
+    pub(crate) fn scalar<J: IntoIterator<IntoIter = I>>(it: J) -> Self {
+        Self {
+            #[cfg(feature = "bulk")]
+            scalar: true,
+            ..Self::new(it)
         }
     }
 }
 
+impl<I> Decoder<I> {
+    /// Returns the number of `char`s consumed from the input so far
+    #[inline]
+    #[must_use]
+    pub fn chars_read(&self) -> usize { self.chars_read }
+
+    /// Returns the number of decoded bytes returned to the reader so far
+    #[inline]
+    #[must_use]
+    pub fn bytes_read(&self) -> usize { self.bytes_read }
+
+    /// Returns true if a trailing `char` encoding a single byte has been
+    /// consumed from the input
+    #[inline]
+    #[must_use]
+    pub fn trailing_byte(&self) -> bool { self.trailing_byte }
+}
+
 #[inline]
 unsafe fn split_mut<T>(arr: &mut [T], i: usize) -> (&mut [T], &mut [T]) {
     if cfg!(debug_assertions) {
@@ -55,11 +93,36 @@ fn split_first_chunk_mut<T, const N: usize>(
 }
 
 impl<I: Iterator<Item = char>> io::Read for Decoder<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let nread = self.read_impl(buf)?;
+        self.bytes_read += nread;
+        Ok(nread)
+    }
+}
+
+impl<I: Iterator<Item = char>> Decoder<I> {
+    /// Returns true if decoded chunks should be written with the vectorized
+    /// byte pair conversion
+    #[cfg(feature = "bulk")]
+    #[cfg_attr(
+        not(test),
+        expect(clippy::unused_self, reason = "Only tests can disable the bulk path")
+    )]
+    #[inline]
+    fn use_bulk(&self) -> bool {
+        #[cfg(test)]
+        if self.scalar {
+            return false;
+        }
+
+        true
+    }
+
     #[expect(
         clippy::too_many_lines,
         reason = "This is unfortunately just a very complicated function"
     )]
-    fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
+    fn read_impl(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
         let mut nread = 0;
 
         if !self.buf.is_empty() {
@@ -106,6 +169,7 @@ impl<I: Iterator<Item = char>> io::Read for Decoder<I> {
                 // SAFETY: chunk_len is bounds-checked by the loop condition
                 unsafe { *chunk.0.get_unchecked_mut(chunk_len) = chr.into() };
                 chunk_len += 1;
+                self.chars_read += 1;
             }
 
             let dec = chunk.decode();
@@ -125,6 +189,14 @@ impl<I: Iterator<Item = char>> io::Read for Decoder<I> {
                 },
             }
 
+            nread += ShortArray::BYTE_WIDTH;
+
+            #[cfg(feature = "bulk")]
+            if self.use_bulk() {
+                *dest = dec.to_le_pairs();
+                continue;
+            }
+
             let dec = dec.to_array();
             debug_assert!(dest.len() == 2 * ShortArray::WIDTH);
             // SAFETY: The resulting array length is asserted to be equal to
@@ -143,8 +215,6 @@ impl<I: Iterator<Item = char>> io::Read for Decoder<I> {
                     *bytes = (dw as u16).to_le_bytes();
                 }
             }
-
-            nread += ShortArray::BYTE_WIDTH;
         }
 
         // Ensure that we have 0 < n < WIDTH chars left
@@ -164,8 +234,10 @@ impl<I: Iterator<Item = char>> io::Read for Decoder<I> {
             )]
             let [lo, hi] = (dw as u16).to_le_bytes();
             let has_hi = (dw & TRAIL_MASK) != TRAIL_MASK;
+            self.trailing_byte |= !has_hi;
 
             if !has_hi && self.it.next().is_some() {
+                self.chars_read += 1;
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
                     "Trailing chars found after padding",
@@ -268,15 +340,15 @@ mod test {
         for l in 0..=128 {
             for i in 0..=256 {
                 let arr = vec![255_u8; i];
-                zip_eq(
-                    l,
-                    Decoder::new(arr.clone().chunks(2).map(|c| match *c {
+                let enc = || {
+                    arr.chunks(2).map(|c| match *c {
                         [a, b] => encode2(a, b),
                         [a] => encode1(a),
                         _ => unreachable!(),
-                    })),
-                    arr,
-                );
+                    })
+                };
+                zip_eq(l, Decoder::new(enc()), arr.clone());
+                zip_eq(l, Decoder::scalar(enc()), arr.clone());
             }
         }
     }
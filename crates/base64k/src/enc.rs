@@ -9,8 +9,17 @@ pub struct Encoder<C> {
     curr_byte: usize,
     arr: ShortArray,
     chars: C,
+    bytes_written: usize,
+    chars_written: usize,
+    trailing_byte: bool,
+    #[cfg(all(test, feature = "bulk"))]
+    scalar: bool,
 }
 
+/// Number of bytes encoded per iteration of the bulk path
+#[cfg(feature = "bulk")]
+const BULK_WIDTH: usize = 2 * ShortArray::BYTE_WIDTH;
+
 impl<C: Extend<char>> Encoder<C> {
     // Returns (word_index, high_byte)
     #[inline]
@@ -38,6 +47,7 @@ impl<C: Extend<char>> Encoder<C> {
         // SAFETY: ShortArray::prepare transposes all u32 values that would be
         //         invalid chars into a valid range
         unsafe { self.extend_chars(self.arr.encode().to_array()) };
+        self.chars_written += ShortArray::WIDTH;
         self.curr_byte = 0;
     }
 
@@ -59,6 +69,7 @@ impl<C: Extend<char>> Encoder<C> {
                 // Mark the final word as trailing
                 *dw |= TRAIL_MASK;
             }
+            self.trailing_byte = true;
             idx + 1
         } else {
             idx
@@ -78,8 +89,64 @@ impl<C: Extend<char>> Encoder<C> {
                     .copied(),
             );
         }
+        self.chars_written += idx;
+    }
+
+    /// Encode all whole [`BULK_WIDTH`]-byte blocks at the start of `buf`,
+    /// returning the remaining bytes
+    ///
+    /// This must only be called when the internal buffer is empty.
+    #[cfg(feature = "bulk")]
+    fn write_bulk<'a>(&mut self, buf: &'a [u8]) -> &'a [u8] {
+        debug_assert_eq!(self.curr_byte, 0);
+
+        let mut blocks = buf.chunks_exact(BULK_WIDTH);
+        for block in &mut blocks {
+            for half in block.chunks_exact(ShortArray::BYTE_WIDTH) {
+                let arr =
+                    ShortArray::from_le_pairs(half.try_into().unwrap_or_else(|_| unreachable!()));
+                // SAFETY: ShortArray::prepare transposes all u32 values that
+                //         would be invalid chars into a valid range
+                unsafe { self.extend_chars(arr.encode().to_array()) };
+            }
+
+            self.chars_written += 2 * ShortArray::WIDTH;
+        }
+
+        blocks.remainder()
+    }
+
+    /// Returns true if the bulk path should be used for the next write
+    #[cfg(feature = "bulk")]
+    #[inline]
+    fn use_bulk(&self, len: usize) -> bool {
+        #[cfg(test)]
+        if self.scalar {
+            return false;
+        }
+
+        self.curr_byte == 0 && len >= BULK_WIDTH
     }
 
+    /// Returns the number of bytes written to this encoder so far
+    #[inline]
+    #[must_use]
+    pub fn bytes_written(&self) -> usize { self.bytes_written }
+
+    /// Returns the number of `char`s emitted to the output so far
+    ///
+    /// Bytes still held in the internal buffer are not counted until the
+    /// encoder is flushed.
+    #[inline]
+    #[must_use]
+    pub fn chars_written(&self) -> usize { self.chars_written }
+
+    /// Returns true if a flush has emitted a trailing `char` encoding a single
+    /// byte, i.e. if the data flushed so far had an odd length
+    #[inline]
+    #[must_use]
+    pub fn trailing_byte(&self) -> bool { self.trailing_byte }
+
     /// Flush the internal buffer and return the encoded data
     #[inline]
     #[must_use]
@@ -103,6 +170,11 @@ impl<C: Extend<char>> io::Write for Encoder<C> {
         let buf_len = buf.len();
 
         loop {
+            #[cfg(feature = "bulk")]
+            if self.use_bulk(buf.len()) {
+                buf = self.write_bulk(buf);
+            }
+
             debug_assert!(ShortArray::BYTE_WIDTH > self.curr_byte);
             let len = buf.len().min(ShortArray::BYTE_WIDTH - self.curr_byte);
 
@@ -145,6 +217,7 @@ impl<C: Extend<char>> io::Write for Encoder<C> {
             }
         }
 
+        self.bytes_written += buf_len;
         Ok(buf_len)
     }
 
@@ -155,6 +228,18 @@ impl<C: Extend<char>> io::Write for Encoder<C> {
     }
 }
 
+#[cfg(test)]
+impl<C: Default> Encoder<C> {
+    /// Construct an encoder that never takes the bulk path
+    pub(crate) fn scalar() -> Self {
+        Self {
+            #[cfg(feature = "bulk")]
+            scalar: true,
+            ..Self::default()
+        }
+    }
+}
+
 impl<C: IntoIterator + Extend<char>> IntoIterator for Encoder<C> {
     type IntoIter = <C as IntoIterator>::IntoIter;
     type Item = <C as IntoIterator>::Item;
@@ -170,35 +255,46 @@ mod test {
     use super::Encoder;
     use crate::test::{encode1, encode2};
 
-    fn zip_eq<A: AsRef<[u8]>, B: IntoIterator<Item = char>>(pathological: usize, a: A, b: B)
-    where B::IntoIter: ExactSizeIterator {
-        let mut enc = Encoder::<Vec<char>>::default();
-        if pathological > 0 {
-            let mut slice = a.as_ref();
+    fn zip_eq<A: AsRef<[u8]>, B: IntoIterator<Item = char>>(pathological: usize, a: A, b: B) {
+        let b: Vec<_> = b.into_iter().collect();
 
-            loop {
-                match enc.write(&slice[..pathological.min(slice.len())]).unwrap() {
-                    0 => break,
-                    n => slice = &slice[n..],
+        for scalar in [false, true] {
+            let mut enc = if scalar {
+                Encoder::<Vec<char>>::scalar()
+            } else {
+                Encoder::default()
+            };
+
+            if pathological > 0 {
+                let mut slice = a.as_ref();
+
+                loop {
+                    match enc.write(&slice[..pathological.min(slice.len())]).unwrap() {
+                        0 => break,
+                        n => slice = &slice[n..],
+                    }
                 }
+            } else {
+                enc.write_all(a.as_ref()).unwrap();
             }
-        } else {
-            enc.write_all(a.as_ref()).unwrap();
-        }
 
-        let a = enc.into_iter();
-        let b = b.into_iter();
-        let a_len = a.len();
-        let b_len = b.len();
+            let a = enc.into_iter();
+            let a_len = a.len();
 
-        for (i, (a, b)) in a.map(u32::from).zip(b.map(Into::into)).enumerate() {
-            assert_eq!(a, b, "Mismatch at index {i}: {a:#08x} vs {b:#08x}");
-        }
+            for (i, (a, b)) in a
+                .map(u32::from)
+                .zip(b.iter().map(|&c| u32::from(c)))
+                .enumerate()
+            {
+                assert_eq!(a, b, "Mismatch at index {i}: {a:#08x} vs {b:#08x}");
+            }
 
-        assert_eq!(
-            a_len, b_len,
-            "Length mismatch (pathological = {pathological})"
-        );
+            assert_eq!(
+                a_len,
+                b.len(),
+                "Length mismatch (pathological = {pathological}, scalar = {scalar})"
+            );
+        }
     }
 
     #[test]
@@ -232,6 +328,22 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_counters() {
+        let mut enc = Encoder::<Vec<char>>::default();
+        enc.write_all(b"the quick brown fox").unwrap();
+        assert_eq!(enc.bytes_written(), 19);
+        assert_eq!(enc.chars_written(), 8);
+        assert!(!enc.trailing_byte());
+
+        enc.flush().unwrap();
+        assert_eq!(enc.chars_written(), 10);
+        assert!(enc.trailing_byte());
+
+        let chars = enc.finish();
+        assert_eq!(chars.len(), 10);
+    }
+
     #[test]
     fn test_long() {
         let odd = b"the quick brown fox jumps over the lazy dog";
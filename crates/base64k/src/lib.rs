@@ -1,4 +1,21 @@
 //! A utility crate for converting 16-bit binary data to Unicode code points.
+//!
+//! # Encoding
+//!
+//! Input bytes are read in pairs of [`PAIR_BYTES`], and each pair `[a, b]`
+//! is combined little-endian into the 16-bit value `a | (b << 8)`.  If the
+//! input has an odd length, the final byte `a` is instead combined with
+//! [`TRAIL_MASK`] as `a | TRAIL_MASK`.  Each combined value `v` is then
+//! emitted as the code point `(v + OFFSET) ^ SURROGATE_MASK`, which maps
+//! every possible input to a Unicode scalar value outside the UTF-16
+//! surrogate range.
+//!
+//! Decoding applies the inverse, `(c ^ SURROGATE_MASK) - OFFSET`, to each
+//! code point.  A result with [`TRAIL_MASK`] set yields only its low byte and
+//! must be the last code point of the input.
+//!
+//! See the [`test_vectors`] module for known-answer pairs suitable for
+//! checking other implementations against this one.
 
 #![deny(
     clippy::disallowed_methods,
@@ -14,11 +31,26 @@
 mod arr;
 mod dec;
 mod enc;
+pub mod test_vectors;
 
-// A flag indicating a trailing byte.  All code points are converted from
-// two-byte pairs thus this value exceeds the range of non-trailing code point
-// inputs.
-const TRAIL_MASK: u32 = 0x0001_0000;
+/// The number of input bytes encoded by each output code point
+pub const PAIR_BYTES: usize = 2;
+
+/// A flag indicating a trailing byte.  All code points are converted from
+/// two-byte pairs thus this value exceeds the range of non-trailing code point
+/// inputs.
+pub const TRAIL_MASK: u32 = 0x0001_0000;
+
+/// The offset added to each combined input value before it is masked
+///
+/// Together with [`SURROGATE_MASK`] this moves the range
+/// `0xd000..0xd800`, which would otherwise land on the UTF-16 surrogates,
+/// down to the start of the code space.
+pub const OFFSET: u32 = 0x0800;
+
+/// The mask `XORed` with each offset input value to skip the UTF-16
+/// surrogate range `0xd800..0xe000`
+pub const SURROGATE_MASK: u32 = 0xd800;
 
 pub use dec::Decoder;
 pub use enc::Encoder;
@@ -67,12 +99,36 @@ mod test {
     }
 
     fn assert_roundtrip(inp: &[u8]) {
-        let mut enc = Encoder::<String>::default();
+        for scalar_enc in [false, true] {
+            for scalar_dec in [false, true] {
+                assert_roundtrip_with(inp, scalar_enc, scalar_dec);
+            }
+        }
+    }
+
+    fn assert_roundtrip_with(inp: &[u8], scalar_enc: bool, scalar_dec: bool) {
+        let odd = inp.len() % 2 == 1;
+        let mut enc = if scalar_enc {
+            Encoder::<String>::scalar()
+        } else {
+            Encoder::default()
+        };
         enc.write_all(inp).unwrap();
+        enc.flush().unwrap();
+        assert_eq!(enc.bytes_written(), inp.len());
+        assert_eq!(enc.chars_written(), inp.len().div_ceil(2));
+        assert_eq!(enc.trailing_byte(), odd);
         let s = enc.finish();
-        let mut dec = Decoder::new(s.chars());
+        let mut dec = if scalar_dec {
+            Decoder::scalar(s.chars())
+        } else {
+            Decoder::new(s.chars())
+        };
         let mut out = vec![];
         dec.read_to_end(&mut out).unwrap();
+        assert_eq!(dec.chars_read(), s.chars().count());
+        assert_eq!(dec.bytes_read(), inp.len());
+        assert_eq!(dec.trailing_byte(), odd);
         zip_eq(inp.iter().copied(), out);
     }
 
@@ -0,0 +1,136 @@
+//! Known-answer pairs for checking interoperability with other
+//! implementations
+//!
+//! Each entry of [`VECTORS`] pairs an input with its exact encoding.  The
+//! inputs are chosen to cover the empty and single-byte cases, the edges of
+//! the ranges remapped around the UTF-16 surrogates, trailing bytes, and an
+//! input long enough to exercise this crate's bulk encoding path.
+
+/// A known-answer pair of raw bytes and their encoded form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vector {
+    /// The raw input bytes
+    pub bytes: &'static [u8],
+    /// The encoded string
+    pub encoded: &'static str,
+}
+
+/// Fixed encodings that any conforming implementation must reproduce, in
+/// both directions
+pub const VECTORS: &[Vector] = &[
+    Vector {
+        bytes: b"",
+        encoded: "",
+    },
+    Vector {
+        bytes: b"\x00",
+        encoded: "\u{1d000}",
+    },
+    Vector {
+        bytes: b"\xff",
+        encoded: "\u{1d0ff}",
+    },
+    Vector {
+        bytes: b"\x00\x00",
+        encoded: "\u{d000}",
+    },
+    Vector {
+        bytes: b"\xff\xff",
+        encoded: "\u{1dfff}",
+    },
+    // The first and last pairs remapped below the surrogate range
+    Vector {
+        bytes: b"\x00\xd0",
+        encoded: "\u{0}",
+    },
+    Vector {
+        bytes: b"\xff\xd7",
+        encoded: "\u{7ff}",
+    },
+    Vector {
+        bytes: b"\x00\xd8",
+        encoded: "\u{3800}",
+    },
+    Vector {
+        bytes: b"\xff\xf7",
+        encoded: "\u{27ff}",
+    },
+    Vector {
+        bytes: b"\x00\xf8",
+        encoded: "\u{1d800}",
+    },
+    Vector {
+        bytes: b"hi",
+        encoded: "\u{a968}",
+    },
+    Vector {
+        bytes: b"odd",
+        encoded: "\u{b46f}\u{1d064}",
+    },
+    Vector {
+        bytes: b"base64k",
+        encoded: "\u{b162}\u{b573}\u{e436}\u{1d06b}",
+    },
+    Vector {
+        bytes: &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b,
+            0x1c, 0x1d, 0x1e, 0x1f, 0x20,
+        ],
+        encoded: concat!(
+            "\u{d100}\u{d302}\u{d504}\u{d706}\u{c908}\u{cb0a}\u{cd0c}\u{cf0e}",
+            "\u{c110}\u{c312}\u{c514}\u{c716}\u{f918}\u{fb1a}\u{fd1c}\u{ff1e}",
+            "\u{1d020}",
+        ),
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use std::io::prelude::*;
+
+    use super::VECTORS;
+    use crate::{Decoder, Encoder, OFFSET, PAIR_BYTES, SURROGATE_MASK, TRAIL_MASK};
+
+    #[test]
+    fn known_answers() {
+        for vec in VECTORS {
+            for scalar in [false, true] {
+                let mut enc = if scalar {
+                    Encoder::<String>::scalar()
+                } else {
+                    Encoder::default()
+                };
+                enc.write_all(vec.bytes).unwrap();
+                assert_eq!(enc.finish(), vec.encoded, "Encoding {:?}", vec.bytes);
+            }
+
+            let mut out = vec![];
+            Decoder::new(vec.encoded.chars())
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, vec.bytes, "Decoding {:?}", vec.encoded);
+        }
+    }
+
+    // The specification in the crate docs, written out long-hand
+    #[test]
+    fn spec() {
+        for vec in VECTORS {
+            let expected: String = vec
+                .bytes
+                .chunks(PAIR_BYTES)
+                .map(|c| {
+                    let v = match *c {
+                        [a, b] => u32::from(a) | (u32::from(b) << 8),
+                        [a] => u32::from(a) | TRAIL_MASK,
+                        _ => unreachable!(),
+                    };
+                    char::from_u32((v + OFFSET) ^ SURROGATE_MASK).unwrap()
+                })
+                .collect();
+
+            assert_eq!(expected, vec.encoded);
+        }
+    }
+}
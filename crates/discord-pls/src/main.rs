@@ -13,7 +13,6 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     fmt,
-    sync::Arc,
 };
 
 use rand::prelude::*;
@@ -23,7 +22,6 @@ use serenity::{
     prelude::*,
     utils::MessageBuilder,
 };
-use tracing::Instrument;
 use tracing_subscriber::prelude::*;
 
 #[derive(
@@ -70,33 +68,6 @@ impl FlowType {
     }
 }
 
-#[derive(
-    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
-)]
-enum ResponseType {
-    Message,
-    UpdateMessage,
-    Modal,
-}
-
-impl ResponseType {
-    fn create(self) -> RawResponseType {
-        match self {
-            Self::Message => RawResponseType::ChannelMessageWithSource,
-            Self::UpdateMessage => RawResponseType::UpdateMessage,
-            Self::Modal => RawResponseType::Modal,
-        }
-    }
-
-    fn defer(self) -> Option<RawResponseType> {
-        match self {
-            Self::Message => Some(RawResponseType::DeferredChannelMessageWithSource),
-            Self::UpdateMessage => Some(RawResponseType::DeferredUpdateMessage),
-            Self::Modal => None,
-        }
-    }
-}
-
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
 )]
@@ -108,27 +79,6 @@ enum RawResponseType {
     Modal,
 }
 
-#[derive(
-    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
-)]
-enum CrudOp<T> {
-    Create(T),
-    Defer(T),
-    Edit,
-    Delete,
-}
-
-impl<T> CrudOp<T> {
-    fn map<U>(self, f: impl FnOnce(T) -> U) -> CrudOp<U> {
-        match self {
-            Self::Create(t) => CrudOp::Create(f(t)),
-            Self::Defer(t) => CrudOp::Defer(f(t)),
-            Self::Edit => CrudOp::Edit,
-            Self::Delete => CrudOp::Delete,
-        }
-    }
-}
-
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum CommandOpt {
     String(String, bool),
@@ -173,8 +123,6 @@ impl CommandOpt {
     }
 }
 
-type CrudResults = BTreeMap<(FlowType, Box<[CrudOp<ResponseType>]>), bool>;
-
 #[expect(
     clippy::large_enum_variant,
     reason = "PingPong is zero-size and the rest are large"
@@ -185,11 +133,7 @@ enum TestMode {
         results: BTreeMap<(InteractionType, RawResponseType), bool>,
         modals: bool,
     },
-    CrudBrute {
-        to_try: BTreeSet<Arc<[CrudOp<usize>]>>,
-        untried: BTreeMap<FlowType, Vec<Arc<[CrudOp<usize>]>>>,
-        results: CrudResults,
-    },
+    CrudBrute(paracord::fuzz::Fuzzer),
     PingPong,
     CommandReg {
         untried: BTreeSet<Vec<CommandOpt>>,
@@ -255,16 +199,6 @@ fn sample_create_response(
     }
 }
 
-fn sample_edit_response() -> serenity::builder::EditInteractionResponse {
-    serenity::builder::EditInteractionResponse::new()
-        .content("foo")
-        .components(vec![serenity::builder::CreateActionRow::Buttons(vec![
-            serenity::builder::CreateButton::new("hi")
-                .style(ButtonStyle::Primary)
-                .label("hi"),
-        ])])
-}
-
 async fn create_response<'a>(
     int: &Interaction,
     http: impl CacheHttp,
@@ -292,31 +226,6 @@ async fn create_response<'a>(
 //     }
 // }
 
-async fn edit_response(
-    int: &Interaction,
-    http: impl CacheHttp,
-    res: serenity::builder::EditInteractionResponse,
-) -> serenity::Result<Message> {
-    match int {
-        Interaction::Command(c) => c.edit_response(http, res).await,
-        Interaction::Component(c) => c.edit_response(http, res).await,
-        Interaction::Modal(m) => m.edit_response(http, res).await,
-        _ => unreachable!(),
-    }
-}
-
-async fn delete_response(
-    int: &Interaction,
-    http: impl AsRef<serenity::http::Http>,
-) -> serenity::Result<()> {
-    match int {
-        Interaction::Command(c) => c.delete_response(http).await,
-        Interaction::Component(c) => c.delete_response(http).await,
-        Interaction::Modal(m) => m.delete_response(http).await,
-        _ => unreachable!(),
-    }
-}
-
 async fn create_followup<'a>(
     int: &Interaction,
     http: impl CacheHttp,
@@ -546,46 +455,20 @@ async fn try_cmdreg(
     .ok();
 }
 
-fn pick_random<T, R: IntoIterator<Item = T>>(from: &mut Vec<T>, refill: impl FnOnce() -> R) -> T {
-    if from.is_empty() {
-        from.extend(refill());
-    }
-    let i = rand::thread_rng().gen_range(0..from.len());
-    from.remove(i)
-}
-
-fn print_crud_results(results: &CrudResults) -> MessageBuilder {
-    use std::fmt::Write;
-
-    let mut mb = MessageBuilder::new();
-    mb.push_bold_line("Results:");
-    let mut s = "Results:".to_owned();
-
-    for ((flow, ops), success) in results {
-        let flow = format!("{flow:?}");
-        let ops = format!("{ops:?}");
-        let success = if *success { "  OK" } else { "FAIL" };
-
-        mb.push_mono_safe(&flow)
-            .push(" :: ")
-            .push_mono_safe(&ops)
-            .push(": ")
-            .push_bold_line(success);
-
-        write!(s, "\n{flow:<32} {ops:<62}  {success}").unwrap();
-    }
-
-    tracing::info!("{s}");
-
-    mb
-}
-
 #[async_trait::async_trait]
 impl EventHandler for Handler {
-    #[expect(clippy::too_many_lines, reason = "i mean yea")]
     async fn interaction_create(&self, ctx: Context, int: Interaction) {
         let mut state = self.state.write().await;
 
+        // The fuzzer tags its own modals, so let it identify interactions
+        // itself
+        if let TestMode::CrudBrute(ref mut fuzzer) = *state {
+            if fuzzer.handle(&ctx.http, &int).await.is_some() {
+                tracing::info!("Results:\n{}", fuzzer.matrix());
+            }
+            return;
+        }
+
         let Some(flow) = FlowType::get(&int).unwrap() else {
             tracing::warn!(kind = ?int.kind(), "Unexpected interaction flow");
             return;
@@ -621,121 +504,7 @@ impl EventHandler for Handler {
                     .ok();
                 },
             },
-            TestMode::CrudBrute {
-                ref to_try,
-                ref mut untried,
-                ref mut results,
-            } => {
-                const TYPES: [ResponseType; 3] = [
-                    ResponseType::Message,
-                    ResponseType::UpdateMessage,
-                    ResponseType::Modal,
-                ];
-
-                let untried = untried.entry(flow).or_default();
-                let ops = pick_random(untried, || to_try.iter().cloned());
-
-                let mut types = vec![];
-                let mut rem_types = vec![];
-                let ops = ops
-                    .iter()
-                    .map(|op| {
-                        let defer = matches!(op, CrudOp::Defer(_));
-                        op.map(|i| loop {
-                            if let Some(t) = types.get(i) {
-                                break *t;
-                            }
-
-                            types.push(loop {
-                                let ty = pick_random(&mut rem_types, || TYPES.iter().copied());
-
-                                if !defer || ty.defer().is_some() {
-                                    break ty;
-                                }
-                            });
-                        })
-                    })
-                    .collect::<Vec<_>>()
-                    .into_boxed_slice();
-
-                let mut last_type = None;
-                let mut res = Ok(());
-                for (i, op) in ops.iter().enumerate() {
-                    let ty = match op {
-                        CrudOp::Create(t) | CrudOp::Defer(t) => {
-                            last_type = Some(t);
-                            t
-                        },
-                        CrudOp::Edit | CrudOp::Delete => {
-                            if let Some(t) = last_type {
-                                t
-                            } else {
-                                let t = &ResponseType::Message; // TODO
-
-                                last_type = Some(t);
-                                t
-                            }
-                        },
-                    };
-
-                    let op_res = match op {
-                        CrudOp::Create(_) => {
-                            create_response(
-                                &int,
-                                &ctx.http,
-                                sample_create_response(flow.initial_interaction(), ty.create()),
-                            )
-                            .await
-                        },
-                        CrudOp::Defer(_) => {
-                            create_response(
-                                &int,
-                                &ctx.http,
-                                sample_create_response(
-                                    flow.initial_interaction(),
-                                    ty.defer().unwrap(),
-                                ),
-                            )
-                            .await
-                        },
-                        CrudOp::Edit => edit_response(&int, &ctx.http, sample_edit_response())
-                            .await
-                            .map(|_| ()),
-                        CrudOp::Delete => delete_response(&int, &ctx.http).await,
-                    };
-
-                    if let Err(e) = op_res {
-                        res = Err((e, *op, i));
-                        break;
-                    }
-                }
-
-                let span = tracing::error_span!("try_crud", ?flow, ?ops);
-                async move {
-                    match res {
-                        Ok(()) => tracing::info!("Success!"),
-                        Err((ref e, ref op, i)) => tracing::error!(%e, ?op, i, "Error"),
-                    }
-
-                    let ok = res.is_ok();
-                    let last_ok = results.insert((flow, ops), ok);
-                    assert!(last_ok.map_or(true, |o| ok == o));
-
-                    let mut mb = print_crud_results(results);
-
-                    create_followup(
-                        &int,
-                        &ctx.http,
-                        serenity::builder::CreateInteractionResponseFollowup::new()
-                            .content(mb.build()),
-                    )
-                    .await
-                    .map_err(|err| tracing::warn!(%err, "Error sending followup"))
-                    .ok();
-                }
-                .instrument(span)
-                .await;
-            },
+            TestMode::CrudBrute(_) => unreachable!(),
 
             TestMode::PingPong => {
                 create_response(
@@ -881,33 +650,7 @@ async fn main() {
                 modals,
             }
         },
-        Subcommand::CrudBrute => {
-            use CrudOp::{Create, Defer, Delete, Edit};
-
-            let op_lists = [
-                vec![Edit],
-                vec![Delete],
-                vec![Create(0), Edit, Edit],
-                vec![Defer(0), Edit, Edit],
-                vec![Defer(0), Delete],
-                // vec![Defer(0), Delete, Create(0)],
-                vec![Defer(0), Defer(0)],
-                vec![Delete, Create(0)],
-                // vec![Create(0), Delete, Create(1)],
-                // vec![Create(0), Create(1)],
-            ];
-
-            let to_try = op_lists
-                .into_iter()
-                .map(|v| v.into_boxed_slice().into())
-                .collect();
-
-            TestMode::CrudBrute {
-                to_try,
-                untried: BTreeMap::default(),
-                results: BTreeMap::default(),
-            }
-        },
+        Subcommand::CrudBrute => TestMode::CrudBrute(paracord::fuzz::Fuzzer::default()),
         Subcommand::PingPong => TestMode::PingPong,
         Subcommand::CommandReg => {
             use CommandOpt as Opt;
@@ -17,9 +17,14 @@ pub use image;
 use image::{
     buffer::ConvertBuffer,
     codecs::jpeg::{JpegDecoder, JpegEncoder},
-    ColorType, DynamicImage, ExtendedColorType, ImageBuffer, ImageDecoder, ImageError, ImageResult,
-    Pixel, PixelWithColorType,
+    ColorType, DynamicImage, ExtendedColorType, ImageBuffer, ImageDecoder, ImageError, ImageFormat,
+    ImageReader, Pixel, PixelWithColorType,
 };
+pub use metadata::Metadata;
+pub use quality::{estimate_quality, Quality};
+
+mod metadata;
+mod quality;
 
 /// An error arising from JPEG-ing pixels
 #[derive(Debug, thiserror::Error)]
@@ -30,12 +35,69 @@ pub enum Error {
     /// A [`ColorType`] was encountered that was not supported
     #[error("Unsupported color type {0:?}")]
     UnsupportedColorType(ColorType),
+    /// The image is empty or larger than the JPEG format allows
+    #[error("Invalid image dimensions {width}x{height}")]
+    Dimensions {
+        /// The width of the image
+        width: u32,
+        /// The height of the image
+        height: u32,
+    },
+    /// The decoded image would not fit in the memory budget
+    #[error("Decoded image size of {size} bytes exceeds memory budget of {budget} bytes")]
+    TooLarge {
+        /// The size of the decoded image, in bytes
+        size: u64,
+        /// The memory budget, in bytes
+        budget: usize,
+    },
+    /// The pixel buffer did not match the dimensions and color type given
+    #[error("Pixel buffer has length {actual}, expected {expected}")]
+    BufferSize {
+        /// The length implied by the image dimensions and color type
+        expected: usize,
+        /// The length of the buffer provided
+        actual: usize,
+    },
+}
+
+/// A reasonable default memory budget for the `jpeg_*` functions, in bytes
+pub const DEFAULT_MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+/// The largest width or height the JPEG format can represent
+pub const MAX_DIMENSION: u32 = u16::MAX as u32;
+
+/// Check the dimensions of an image against the JPEG format limits and the
+/// given memory budget, returning the size of its decoded pixel data
+fn decoded_size(
+    width: u32,
+    height: u32,
+    color_type: ExtendedColorType,
+    budget: usize,
+) -> Result<usize, Error> {
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(Error::Dimensions { width, height });
+    }
+
+    let size =
+        (u64::from(width) * u64::from(height) * u64::from(color_type.bits_per_pixel())).div_ceil(8);
+
+    usize::try_from(size)
+        .ok()
+        .filter(|&s| s <= budget)
+        .ok_or(Error::TooLarge { size, budget })
 }
 
 /// Apply JPEG compression to the given pixel buffer
 ///
+/// Images whose decoded pixel data would be larger than `budget` bytes are
+/// rejected up front.  The pixel and JPEG buffers are reused across
+/// iterations, so peak memory use stays at roughly twice the decoded size
+/// regardless of the number of iterations.
+///
 /// # Errors
-/// This function returns an error if the JPEG transcoder fails
+/// This function returns an error if the image is too large or the pixel
+/// buffer does not match its dimensions, or if the JPEG transcoder fails
 pub fn jpeg_pixels(
     pixels: Vec<u8>,
     width: u32,
@@ -43,7 +105,16 @@ pub fn jpeg_pixels(
     color_type: ExtendedColorType,
     iterations: usize,
     quality: u8,
-) -> ImageResult<Vec<u8>> {
+    budget: usize,
+) -> Result<Vec<u8>, Error> {
+    let size = decoded_size(width, height, color_type, budget)?;
+    if pixels.len() != size {
+        return Err(Error::BufferSize {
+            expected: size,
+            actual: pixels.len(),
+        });
+    }
+
     let mut decoded_data = pixels;
     let mut encoded_data = Vec::new();
 
@@ -51,15 +122,17 @@ pub fn jpeg_pixels(
         encoded_data.clear();
         let mut encoder = JpegEncoder::new_with_quality(&mut encoded_data, quality);
         encoder.encode(&decoded_data, width, height, color_type)?;
-        decoded_data.clear();
         let decoder = JpegDecoder::new(Cursor::new(&*encoded_data))?;
-        decoded_data.resize_with(
-            decoder
-                .total_bytes()
-                .try_into()
-                .unwrap_or_else(|_| unreachable!()),
-            Default::default,
-        );
+
+        // The decoder should always reproduce the input dimensions, but guard
+        // against writing past the buffer just in case
+        if decoder.total_bytes() != size as u64 {
+            return Err(Error::BufferSize {
+                expected: size,
+                actual: usize::try_from(decoder.total_bytes()).unwrap_or(usize::MAX),
+            });
+        }
+
         decoder.read_image(&mut decoded_data)?;
     }
 
@@ -68,8 +141,11 @@ pub fn jpeg_pixels(
 
 /// Apply JPEG compression to the given image buffer
 ///
+/// See [`jpeg_pixels`] for details on the memory budget.
+///
 /// # Errors
-/// This function returns an error if the JPEG transcoder fails
+/// This function returns an error if the image is too large or the JPEG
+/// transcoder fails
 ///
 /// # Panics
 /// This function panics if the JPEG transcoder produces an invalid buffer
@@ -77,7 +153,8 @@ pub fn jpeg_buffer<P>(
     image: ImageBuffer<P, Vec<u8>>,
     iterations: usize,
     quality: u8,
-) -> ImageResult<ImageBuffer<P, Vec<u8>>>
+    budget: usize,
+) -> Result<ImageBuffer<P, Vec<u8>>, Error>
 where
     P: PixelWithColorType + Pixel<Subpixel = u8>,
 {
@@ -89,25 +166,187 @@ where
         color_type,
         iterations,
         quality,
+        budget,
     )?;
     Ok(ImageBuffer::from_vec(width, height, data).expect("Wrong buffer size?"))
 }
 
 /// Apply JPEG compression to the given [`DynamicImage`]
 ///
+/// Images with an alpha channel are flattened before compression, and the
+/// original is freed before the JPEG buffers are allocated.  See
+/// [`jpeg_pixels`] for details on the memory budget.
+///
 /// # Errors
-/// This function returns an error if the JPEG transcoder fails
+/// This function returns an error if the image is too large or has an
+/// unsupported color type, or if the JPEG transcoder fails
 pub fn jpeg_dynamic_image(
     image: DynamicImage,
     iterations: usize,
     quality: u8,
+    budget: usize,
 ) -> Result<DynamicImage, Error> {
     use DynamicImage::{ImageLuma8, ImageLumaA8, ImageRgb8, ImageRgba8};
+
+    let color_type = match image {
+        ImageLuma8(_) | ImageLumaA8(_) => ExtendedColorType::L8,
+        ImageRgb8(_) | ImageRgba8(_) => ExtendedColorType::Rgb8,
+        ref image => return Err(Error::UnsupportedColorType(image.color())),
+    };
+    decoded_size(image.width(), image.height(), color_type, budget)?;
+
     Ok(match image {
-        ImageLuma8(image) => ImageLuma8(jpeg_buffer(image, iterations, quality)?),
-        ImageLumaA8(image) => ImageLuma8(jpeg_buffer(image.convert(), iterations, quality)?),
-        ImageRgb8(image) => ImageRgb8(jpeg_buffer(image, iterations, quality)?),
-        ImageRgba8(image) => ImageRgb8(jpeg_buffer(image.convert(), iterations, quality)?),
-        image => return Err(Error::UnsupportedColorType(image.color())),
+        ImageLuma8(image) => ImageLuma8(jpeg_buffer(image, iterations, quality, budget)?),
+        ImageLumaA8(image) => {
+            let luma = image.convert();
+            drop(image);
+            ImageLuma8(jpeg_buffer(luma, iterations, quality, budget)?)
+        },
+        ImageRgb8(image) => ImageRgb8(jpeg_buffer(image, iterations, quality, budget)?),
+        ImageRgba8(image) => {
+            let rgb = image.convert();
+            drop(image);
+            ImageRgb8(jpeg_buffer(rgb, iterations, quality, budget)?)
+        },
+        _ => unreachable!(),
     })
 }
+
+/// Decode an image, rotating and flipping it upright according to its EXIF
+/// orientation
+///
+/// Without this, re-encoding an image drops the orientation tag its viewer
+/// relied on, so photos taken sideways come out sideways.  The orientation
+/// tag in the returned [`Metadata`] is reset to match the new pixels.  Images
+/// whose decoded pixel data would be larger than `budget` bytes are rejected
+/// before decoding.
+///
+/// # Errors
+/// This function returns an error if the image is too large or cannot be
+/// decoded
+pub fn decode(
+    data: &[u8],
+    format: ImageFormat,
+    budget: usize,
+) -> Result<(DynamicImage, Metadata), Error> {
+    let mut decoder = ImageReader::with_format(Cursor::new(data), format).into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    decoded_size(width, height, decoder.color_type().into(), budget)?;
+
+    let icc_profile = decoder.icc_profile()?;
+    let exif = decoder.exif_metadata()?;
+    let orientation = decoder.orientation()?;
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    Ok((image, Metadata::new(icc_profile, exif)))
+}
+
+/// Encode an image as a JPEG, including the given metadata
+///
+/// Pass [`Metadata::default`] to strip all metadata from the output.
+///
+/// # Errors
+/// This function returns an error if the JPEG encoder fails
+pub fn encode(image: &DynamicImage, quality: u8, metadata: &Metadata) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(image)?;
+
+    Ok(metadata.write(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use image::{
+        codecs::jpeg::JpegDecoder, metadata::Orientation, GrayImage, ImageDecoder, ImageFormat,
+        Luma,
+    };
+
+    use super::{decode, encode, metadata, DynamicImage, Metadata, DEFAULT_MEMORY_BUDGET};
+
+    /// A 16x8 image which is dark on the left and light on the right
+    fn landscape() -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(16, 8, |x, _| {
+            Luma([if x < 8 { 0 } else { 255 }])
+        }))
+    }
+
+    /// Encode a fixture with the given EXIF orientation
+    fn oriented(orientation: Orientation) -> Vec<u8> {
+        let meta = metadata::test::raw(
+            None,
+            Some(metadata::test::exif(orientation.to_exif().into(), false)),
+        );
+
+        encode(&landscape(), 100, &meta).unwrap()
+    }
+
+    fn luma(image: &DynamicImage, x: u32, y: u32) -> u8 { image.to_luma8().get_pixel(x, y).0[0] }
+
+    #[test]
+    fn orientation() {
+        let data = oriented(Orientation::Rotate90);
+        assert_eq!(
+            JpegDecoder::new(Cursor::new(&data))
+                .unwrap()
+                .orientation()
+                .unwrap(),
+            Orientation::Rotate90
+        );
+
+        // Rotating clockwise moves the dark left half to the top
+        let (image, meta) = decode(&data, ImageFormat::Jpeg, DEFAULT_MEMORY_BUDGET).unwrap();
+        assert_eq!((image.width(), image.height()), (8, 16));
+        assert!(luma(&image, 4, 2) < 64);
+        assert!(luma(&image, 4, 13) > 192);
+        assert_eq!(meta.exif(), Some(&*metadata::test::exif(1, false)));
+
+        let (image, _) = decode(
+            &oriented(Orientation::Rotate180),
+            ImageFormat::Jpeg,
+            DEFAULT_MEMORY_BUDGET,
+        )
+        .unwrap();
+        assert_eq!((image.width(), image.height()), (16, 8));
+        assert!(luma(&image, 2, 4) > 192);
+        assert!(luma(&image, 13, 4) < 64);
+
+        let (image, meta) = decode(
+            &encode(&landscape(), 100, &Metadata::default()).unwrap(),
+            ImageFormat::Jpeg,
+            DEFAULT_MEMORY_BUDGET,
+        )
+        .unwrap();
+        assert_eq!((image.width(), image.height()), (16, 8));
+        assert!(meta.is_empty());
+    }
+
+    #[test]
+    fn metadata_roundtrip() {
+        let meta = metadata::test::raw(
+            Some((0..=255).cycle().take(100_000).collect()),
+            Some(metadata::test::exif(1, true)),
+        );
+
+        let data = encode(&landscape(), 90, &meta).unwrap();
+        let (_, roundtripped) = decode(&data, ImageFormat::Jpeg, DEFAULT_MEMORY_BUDGET).unwrap();
+        assert_eq!(roundtripped, meta);
+
+        let stripped = encode(&landscape(), 90, &Metadata::default()).unwrap();
+        let mut decoder = JpegDecoder::new(Cursor::new(&stripped)).unwrap();
+        assert_eq!(decoder.exif_metadata().unwrap(), None);
+        assert_eq!(decoder.icc_profile().unwrap(), None);
+    }
+
+    #[test]
+    fn decode_budget() {
+        // The encoder writes grayscale images with three channels
+        let size = 16 * 8 * 3;
+        let data = encode(&landscape(), 90, &Metadata::default()).unwrap();
+        assert!(decode(&data, ImageFormat::Jpeg, size - 1).is_err());
+        assert!(decode(&data, ImageFormat::Jpeg, size).is_ok());
+    }
+}
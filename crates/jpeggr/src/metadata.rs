@@ -0,0 +1,245 @@
+//! Metadata carried over from an input image to its JPEG-ed output
+
+use image::metadata::Orientation;
+
+const SOI: [u8; 2] = [0xff, 0xd8];
+const APP0: [u8; 2] = [0xff, 0xe0];
+const APP1: u8 = 0xe1;
+const APP2: u8 = 0xe2;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const ICC_HEADER: &[u8] = b"ICC_PROFILE\0";
+/// The largest payload of a JPEG marker segment, excluding its length
+const MAX_SEGMENT: usize = u16::MAX as usize - 2;
+const ORIENTATION_TAG: u16 = 0x0112;
+/// TIFF field type of a 16-bit unsigned integer
+const SHORT: u16 = 3;
+
+/// Color profile and EXIF data read from an input image
+///
+/// The default value contains no metadata, and can be passed to
+/// [`encode`](crate::encode) to strip all metadata from the output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    icc_profile: Option<Vec<u8>>,
+    exif: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    /// Construct metadata for an image whose pixels have already been
+    /// oriented upright, resetting any orientation tag in the EXIF data
+    pub(crate) fn new(icc_profile: Option<Vec<u8>>, mut exif: Option<Vec<u8>>) -> Self {
+        if let Some(ref mut exif) = exif {
+            set_orientation(exif, Orientation::NoTransforms);
+        }
+
+        Self { icc_profile, exif }
+    }
+
+    /// Get the ICC color profile, if any
+    #[inline]
+    #[must_use]
+    pub fn icc_profile(&self) -> Option<&[u8]> { self.icc_profile.as_deref() }
+
+    /// Get the raw EXIF data, if any
+    #[inline]
+    #[must_use]
+    pub fn exif(&self) -> Option<&[u8]> { self.exif.as_deref() }
+
+    /// Returns true if there is no metadata to write
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.icc_profile.is_none() && self.exif.is_none() }
+
+    /// Insert this metadata into an encoded JPEG
+    ///
+    /// Metadata too large to fit in the JPEG marker segments is dropped.
+    pub(crate) fn write(&self, jpeg: Vec<u8>) -> Vec<u8> {
+        if self.is_empty() || !jpeg.starts_with(&SOI) {
+            return jpeg;
+        }
+
+        let mut segments = vec![];
+
+        if let Some(ref exif) = self.exif {
+            if EXIF_HEADER.len() + exif.len() <= MAX_SEGMENT {
+                push_segment(&mut segments, APP1, &[EXIF_HEADER, exif]);
+            }
+        }
+
+        if let Some(ref icc) = self.icc_profile {
+            let chunks: Vec<_> = icc.chunks(MAX_SEGMENT - ICC_HEADER.len() - 2).collect();
+
+            if let Ok(count @ 1..) = u8::try_from(chunks.len()) {
+                for (seq, chunk) in (1..=count).zip(chunks) {
+                    push_segment(&mut segments, APP2, &[ICC_HEADER, &[seq, count], chunk]);
+                }
+            }
+        }
+
+        // Keep the JFIF header immediately after the start-of-image marker,
+        // as some readers require
+        let mut at = SOI.len();
+        if jpeg[at..].starts_with(&APP0) {
+            if let Some(len) = jpeg.get(at + 2..at + 4) {
+                at += 2 + usize::from(u16::from_be_bytes([len[0], len[1]]));
+            }
+        }
+
+        let mut out = Vec::with_capacity(jpeg.len() + segments.len());
+        out.extend_from_slice(&jpeg[..at]);
+        out.extend_from_slice(&segments);
+        out.extend_from_slice(&jpeg[at..]);
+        out
+    }
+}
+
+fn push_segment(buf: &mut Vec<u8>, marker: u8, parts: &[&[u8]]) {
+    let len: usize = parts.iter().map(|p| p.len()).sum();
+    let len = u16::try_from(len + 2).unwrap_or_else(|_| unreachable!());
+
+    buf.extend_from_slice(&[0xff, marker]);
+    buf.extend_from_slice(&len.to_be_bytes());
+    for part in parts {
+        buf.extend_from_slice(part);
+    }
+}
+
+/// Locate the value of the orientation tag in the first IFD of a TIFF-format
+/// EXIF block, returning its offset and whether the block is big-endian
+fn find_orientation(exif: &[u8]) -> Option<(usize, bool)> {
+    let big_endian = match exif.get(..4)? {
+        [0x49, 0x49, 42, 0] => false,
+        [0x4d, 0x4d, 0, 42] => true,
+        _ => return None,
+    };
+
+    let read_u16 = |at: usize| {
+        let b = exif.get(at..at.checked_add(2)?)?;
+        let b = [b[0], b[1]];
+        Some(if big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    };
+    let read_u32 = |at: usize| {
+        let b = exif.get(at..at.checked_add(4)?)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Some(if big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    };
+
+    let ifd = usize::try_from(read_u32(4)?).ok()?;
+    let entries = read_u16(ifd)?;
+
+    (0..usize::from(entries))
+        .map(|i| ifd + 2 + i * 12)
+        .find_map(|entry| {
+            let value = entry + 8;
+            (read_u16(entry)? == ORIENTATION_TAG
+                && read_u16(entry + 2)? == SHORT
+                && read_u32(entry + 4)? == 1
+                && read_u16(value).is_some())
+            .then_some((value, big_endian))
+        })
+}
+
+/// Read the orientation tag of a TIFF-format EXIF block
+#[cfg(test)]
+fn orientation(exif: &[u8]) -> Option<Orientation> {
+    let (at, big_endian) = find_orientation(exif)?;
+    let value = if big_endian { exif[at + 1] } else { exif[at] };
+    Orientation::from_exif(value)
+}
+
+/// Overwrite the orientation tag of a TIFF-format EXIF block, returning false
+/// if it has none
+fn set_orientation(exif: &mut [u8], orientation: Orientation) -> bool {
+    let Some((at, big_endian)) = find_orientation(exif) else {
+        return false;
+    };
+
+    let value = u16::from(orientation.to_exif());
+    exif[at..at + 2].copy_from_slice(&if big_endian {
+        value.to_be_bytes()
+    } else {
+        value.to_le_bytes()
+    });
+    true
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use image::metadata::Orientation;
+
+    use super::{orientation, set_orientation, Metadata};
+
+    /// Construct metadata as-is, without resetting its orientation
+    pub(crate) fn raw(icc_profile: Option<Vec<u8>>, exif: Option<Vec<u8>>) -> Metadata {
+        Metadata { icc_profile, exif }
+    }
+
+    /// Build a minimal EXIF block containing only an orientation tag
+    pub(crate) fn exif(value: u16, big_endian: bool) -> Vec<u8> {
+        let u16_bytes = |v: u16| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let u32_bytes = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+
+        let mut exif = if big_endian {
+            b"MM\0*".to_vec()
+        } else {
+            b"II*\0".to_vec()
+        };
+        exif.extend(u32_bytes(8));
+        exif.extend(u16_bytes(1));
+        exif.extend(u16_bytes(0x0112));
+        exif.extend(u16_bytes(3));
+        exif.extend(u32_bytes(1));
+        exif.extend(u16_bytes(value));
+        exif.extend([0, 0]);
+        exif.extend(u32_bytes(0));
+        exif
+    }
+
+    #[test]
+    fn orientation_tag() {
+        for big_endian in [false, true] {
+            let mut data = exif(6, big_endian);
+            assert_eq!(orientation(&data), Some(Orientation::Rotate90));
+
+            assert!(set_orientation(&mut data, Orientation::NoTransforms));
+            assert_eq!(orientation(&data), Some(Orientation::NoTransforms));
+            assert_eq!(data, exif(1, big_endian));
+        }
+
+        let mut truncated = exif(6, false);
+        truncated.truncate(12);
+        assert_eq!(orientation(&truncated), None);
+        assert!(!set_orientation(&mut truncated, Orientation::NoTransforms));
+        assert!(!set_orientation(&mut [], Orientation::NoTransforms));
+    }
+
+    #[test]
+    fn new_resets_orientation() {
+        let meta = Metadata::new(None, Some(exif(8, true)));
+        assert_eq!(
+            orientation(meta.exif().unwrap()),
+            Some(Orientation::NoTransforms)
+        );
+        assert!(Metadata::default().is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+//! Estimation of the quality setting an existing JPEG was encoded with
+
+const SOI: [u8; 2] = [0xff, 0xd8];
+const DQT: u8 = 0xdb;
+const SOS: u8 = 0xda;
+const EOI: u8 = 0xd9;
+const TEM: u8 = 0x01;
+const RST0: u8 = 0xd0;
+const RST7: u8 = 0xd7;
+
+/// Luminance quantization table from Annex K of the JPEG standard, in
+/// natural order
+#[rustfmt::skip]
+const STD_LUMA_QTABLE: [u16; 64] = [
+    16, 11, 10, 16,  24,  40,  51,  61,
+    12, 12, 14, 19,  26,  58,  60,  55,
+    14, 13, 16, 24,  40,  57,  69,  56,
+    14, 17, 22, 29,  51,  87,  80,  62,
+    18, 22, 37, 56,  68, 109, 103,  77,
+    24, 35, 55, 64,  81, 104, 113,  92,
+    49, 64, 78, 87, 103, 121, 120, 101,
+    72, 92, 95, 98, 112, 100, 103,  99,
+];
+
+/// Chrominance quantization table from Annex K of the JPEG standard, in
+/// natural order
+#[rustfmt::skip]
+const STD_CHROMA_QTABLE: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99,
+    18, 21, 26, 66, 99, 99, 99, 99,
+    24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/// The natural-order index of each coefficient of a table stored in zigzag
+/// order
+#[rustfmt::skip]
+const UNZIGZAG: [u8; 64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// An estimate of the quality setting an existing JPEG was encoded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quality {
+    /// The quality setting, from 1 to 100, whose scaled standard tables most
+    /// closely match the image's quantization tables
+    pub quality: u8,
+    /// True if the image's tables are exactly the scaled standard tables,
+    /// i.e. it was most likely saved by an encoder derived from libjpeg
+    pub exact: bool,
+}
+
+/// A quantization table read from a JPEG, in natural order
+#[derive(Debug, Clone, Copy)]
+struct Table {
+    values: [u16; 64],
+    /// The largest value allowed by the table's precision
+    max: u16,
+}
+
+/// Read the first two quantization tables of a JPEG, which by convention are
+/// used for luminance and chrominance respectively
+fn read_tables(data: &[u8]) -> Option<[Option<Table>; 2]> {
+    let mut tables = [None; 2];
+    let mut at = SOI.len();
+
+    if !data.starts_with(&SOI) {
+        return None;
+    }
+
+    loop {
+        // Markers may be preceded by any number of fill bytes
+        if *data.get(at)? != 0xff {
+            return None;
+        }
+        while *data.get(at)? == 0xff {
+            at += 1;
+        }
+        let marker = data[at];
+        at += 1;
+
+        match marker {
+            SOS | EOI => break,
+            TEM | RST0..=RST7 => continue,
+            _ => (),
+        }
+
+        let len = data.get(at..at + 2)?;
+        let len = usize::from(u16::from_be_bytes([len[0], len[1]]));
+        let segment = data.get(at.checked_add(2).filter(|_| len >= 2)?..at + len)?;
+        at += len;
+
+        if marker == DQT {
+            read_dqt(segment, &mut tables)?;
+        }
+    }
+
+    tables.iter().any(Option::is_some).then_some(tables)
+}
+
+/// Read the tables of a single DQT segment, keeping those with IDs 0 and 1
+fn read_dqt(mut segment: &[u8], tables: &mut [Option<Table>; 2]) -> Option<()> {
+    while let Some((&info, rest)) = segment.split_first() {
+        let (wide, id) = (info >> 4 != 0, usize::from(info & 0xf));
+        let size = if wide { 128 } else { 64 };
+        let body = rest.get(..size)?;
+        segment = &rest[size..];
+
+        let mut values = [0; 64];
+        for (i, &natural) in UNZIGZAG.iter().enumerate() {
+            values[usize::from(natural)] = if wide {
+                u16::from_be_bytes([body[2 * i], body[2 * i + 1]])
+            } else {
+                u16::from(body[i])
+            };
+        }
+
+        if let Some(slot) = tables.get_mut(id) {
+            *slot = Some(Table {
+                values,
+                max: if wide { 32767 } else { 255 },
+            });
+        }
+    }
+
+    Some(())
+}
+
+/// Scale a standard table to the given quality, as libjpeg does
+fn scale(base: &[u16; 64], quality: u8, max: u16) -> impl Iterator<Item = u16> + '_ {
+    let quality = u32::from(quality.clamp(1, 100));
+    let factor = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+
+    base.iter().map(move |&v| {
+        let v = ((u32::from(v) * factor + 50) / 100).clamp(1, u32::from(max));
+        u16::try_from(v).unwrap_or_else(|_| unreachable!())
+    })
+}
+
+/// Sum the absolute differences between a table and a scaled standard table
+fn distance(table: &Table, base: &[u16; 64], quality: u8) -> u32 {
+    table
+        .values
+        .iter()
+        .zip(scale(base, quality, table.max))
+        .map(|(&a, b)| u32::from(a.abs_diff(b)))
+        .sum()
+}
+
+/// Estimate the quality setting an existing JPEG was encoded with from its
+/// quantization tables
+///
+/// The estimate is the quality at which the standard tables, scaled the way
+/// libjpeg and most encoders derived from it do, come closest to the image's
+/// own tables.  Images saved by encoders using other tables still get the
+/// nearest equivalent quality, with [`Quality::exact`] unset.
+///
+/// Returns `None` if the data is not a JPEG or has no quantization tables
+/// before its first scan.
+#[must_use]
+pub fn estimate_quality(data: &[u8]) -> Option<Quality> {
+    let [luma, chroma] = read_tables(data)?;
+
+    let (quality, dist) = (1..=100)
+        .rev()
+        .map(|q| {
+            let dist = luma.map_or(0, |t| distance(&t, &STD_LUMA_QTABLE, q))
+                + chroma.map_or(0, |t| distance(&t, &STD_CHROMA_QTABLE, q));
+            (q, dist)
+        })
+        .min_by_key(|&(_, d)| d)?;
+
+    Some(Quality {
+        quality,
+        exact: dist == 0,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use image::{DynamicImage, RgbImage};
+
+    use super::{estimate_quality, Quality};
+    use crate::{encode, Metadata};
+
+    fn encoded(quality: u8) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([x, y, 8].map(|v| u8::try_from(v * 16).unwrap()))
+        }));
+
+        encode(&image, quality, &Metadata::default()).unwrap()
+    }
+
+    #[test]
+    fn standard_tables() {
+        for quality in [1, 10, 25, 50, 75, 90, 100] {
+            assert_eq!(
+                estimate_quality(&encoded(quality)),
+                Some(Quality {
+                    quality,
+                    exact: true
+                }),
+                "quality {quality}"
+            );
+        }
+    }
+
+    #[test]
+    fn custom_tables() {
+        let mut data = encoded(80);
+
+        // Nudge the first coefficient of the first table off the standard
+        let dqt = data.windows(2).position(|w| w == [0xff, 0xdb]).unwrap();
+        data[dqt + 5] += 1;
+
+        let est = estimate_quality(&data).unwrap();
+        assert!(!est.exact);
+        assert!((78..=82).contains(&est.quality));
+    }
+
+    #[test]
+    fn not_jpeg() {
+        assert_eq!(estimate_quality(b""), None);
+        assert_eq!(estimate_quality(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(estimate_quality(&[0xff, 0xd8, 0xff, 0xd9]), None);
+
+        let data = encoded(50);
+        assert_eq!(estimate_quality(&data[..20]), None);
+    }
+}
@@ -0,0 +1,397 @@
+//! Size-capped, format-checked downloading of message attachments
+//!
+//! Handlers accepting user files should download them through a
+//! [`Downloader`] rather than calling [`Attachment::download`] or an HTTP
+//! client directly.  Downloads are streamed into memory, stopping as soon as
+//! the size cap is exceeded, and the file's format is identified from its
+//! magic bytes rather than trusting the uploader's content type or filename.
+
+use std::time::Duration;
+
+use reqwest::header;
+use serenity::model::channel::Attachment;
+use url::Url;
+
+/// Number of leading bytes needed to identify every supported format
+const SNIFF_LEN: usize = 16;
+/// Default size cap for downloads
+const DEFAULT_MAX_SIZE: u64 = 8 * 1024 * 1024;
+/// Default time limit for downloads
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A media format identifiable from a file's leading bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// PNG image
+    Png,
+    /// JPEG image
+    Jpeg,
+    /// GIF image
+    Gif,
+    /// WebP image
+    Webp,
+    /// Windows bitmap image
+    Bmp,
+    /// TIFF image
+    Tiff,
+    /// AVIF image
+    Avif,
+    /// Ogg container, usually Vorbis or Opus audio
+    Ogg,
+    /// MP3 audio
+    Mp3,
+    /// WAVE audio
+    Wav,
+    /// FLAC audio
+    Flac,
+}
+
+impl Format {
+    /// All supported audio formats
+    pub const AUDIO: &'static [Self] = &[Self::Ogg, Self::Mp3, Self::Wav, Self::Flac];
+    /// All supported image formats
+    pub const IMAGES: &'static [Self] = &[
+        Self::Png,
+        Self::Jpeg,
+        Self::Gif,
+        Self::Webp,
+        Self::Bmp,
+        Self::Tiff,
+        Self::Avif,
+    ];
+
+    /// Identify the format of a file from its leading bytes
+    #[must_use]
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        let riff = |kind: &[u8]| data.starts_with(b"RIFF") && data.get(8..12) == Some(kind);
+
+        Some(match data {
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Self::Png,
+            [0xff, 0xd8, 0xff, ..] => Self::Jpeg,
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Self::Gif,
+            _ if riff(b"WEBP") => Self::Webp,
+            [b'B', b'M', ..] => Self::Bmp,
+            [b'I', b'I', 42, 0, ..] | [b'M', b'M', 0, 42, ..] => Self::Tiff,
+            [_, _, _, _, b'f', b't', b'y', b'p', b'a', b'v', b'i', b'f' | b's', ..] => Self::Avif,
+            [b'O', b'g', b'g', b'S', ..] => Self::Ogg,
+            [b'I', b'D', b'3', ..] => Self::Mp3,
+            [0xff, b, ..] if b & 0xe0 == 0xe0 => Self::Mp3,
+            _ if riff(b"WAVE") => Self::Wav,
+            [b'f', b'L', b'a', b'C', ..] => Self::Flac,
+            _ => return None,
+        })
+    }
+
+    /// Get the MIME type of this format
+    #[must_use]
+    pub fn mime(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+            Self::Bmp => "image/bmp",
+            Self::Tiff => "image/tiff",
+            Self::Avif => "image/avif",
+            Self::Ogg => "audio/ogg",
+            Self::Mp3 => "audio/mpeg",
+            Self::Wav => "audio/wav",
+            Self::Flac => "audio/flac",
+        }
+    }
+
+    /// Get the conventional file extension for this format
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Gif => "gif",
+            Self::Webp => "webp",
+            Self::Bmp => "bmp",
+            Self::Tiff => "tiff",
+            Self::Avif => "avif",
+            Self::Ogg => "ogg",
+            Self::Mp3 => "mp3",
+            Self::Wav => "wav",
+            Self::Flac => "flac",
+        }
+    }
+}
+
+/// An error arising from downloading a file
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadError {
+    /// The file is larger than the downloader's size cap
+    #[error("File exceeds the size limit of {0} bytes")]
+    TooLarge(u64),
+    /// The file's format could not be identified
+    #[error("File format not recognized")]
+    Unrecognized,
+    /// The file's format was identified, but is not one the downloader
+    /// accepts
+    #[error("File format {} not accepted", .0.mime())]
+    Rejected(Format),
+    /// The server responded with an error status
+    #[error("Server responded with {0}")]
+    Status(reqwest::StatusCode),
+    /// The download did not complete within the downloader's time limit
+    #[error("Download timed out")]
+    Timeout,
+    /// An HTTP error occurred
+    #[error("HTTP error")]
+    Http(#[source] reqwest::Error),
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Self::Timeout
+        } else {
+            Self::Http(err)
+        }
+    }
+}
+
+/// A downloaded file of a verified format
+#[derive(Debug, Clone)]
+pub struct Download {
+    format: Format,
+    data: Vec<u8>,
+    filename: Option<String>,
+}
+
+impl Download {
+    /// Get the format of this file
+    #[inline]
+    #[must_use]
+    pub fn format(&self) -> Format { self.format }
+
+    /// Get the contents of this file
+    #[inline]
+    #[must_use]
+    pub fn data(&self) -> &[u8] { &self.data }
+
+    /// Take ownership of the contents of this file
+    #[inline]
+    #[must_use]
+    pub fn into_data(self) -> Vec<u8> { self.data }
+
+    /// Get the name the file was uploaded with, if it came from an attachment
+    #[inline]
+    #[must_use]
+    pub fn filename(&self) -> Option<&str> { self.filename.as_deref() }
+}
+
+/// Downloads attachments and linked files subject to a size cap, time limit
+/// and set of accepted formats
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    client: reqwest::Client,
+    accept: &'static [Format],
+    max_size: u64,
+    timeout: Duration,
+}
+
+impl Downloader {
+    /// Construct a new downloader using the given HTTP client, accepting the
+    /// given formats
+    #[inline]
+    #[must_use]
+    pub fn new(client: reqwest::Client, accept: &'static [Format]) -> Self {
+        Self {
+            client,
+            accept,
+            max_size: DEFAULT_MAX_SIZE,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Set the largest file, in bytes, this downloader will accept
+    #[inline]
+    #[must_use]
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Set the longest a single download may take
+    #[inline]
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Get the largest file, in bytes, this downloader will accept
+    #[inline]
+    #[must_use]
+    pub fn max_size(&self) -> u64 { self.max_size }
+
+    fn check(&self, data: &[u8]) -> Result<Format, DownloadError> {
+        let format = Format::sniff(data).ok_or(DownloadError::Unrecognized)?;
+
+        if self.accept.contains(&format) {
+            Ok(format)
+        } else {
+            Err(DownloadError::Rejected(format))
+        }
+    }
+
+    /// Download a message attachment
+    ///
+    /// # Errors
+    /// This method returns an error if the attachment is too large, not of an
+    /// accepted format, or could not be downloaded in time.
+    #[inline]
+    pub async fn download(&self, attachment: &Attachment) -> Result<Download, DownloadError> {
+        self.download_with_progress(attachment, |_, _| ()).await
+    }
+
+    /// Download a message attachment, calling the given closure with the
+    /// number of bytes received so far and the expected total after each
+    /// chunk
+    ///
+    /// # Errors
+    /// This method returns an error if the attachment is too large, not of an
+    /// accepted format, or could not be downloaded in time.
+    pub async fn download_with_progress(
+        &self,
+        attachment: &Attachment,
+        progress: impl FnMut(u64, Option<u64>) + Send,
+    ) -> Result<Download, DownloadError> {
+        // Reject oversized attachments before making any request
+        if u64::from(attachment.size) > self.max_size {
+            return Err(DownloadError::TooLarge(self.max_size));
+        }
+
+        let url = Url::parse(&attachment.url).map_err(|_| DownloadError::Unrecognized)?;
+        let mut download = self.fetch_with_progress(url, progress).await?;
+        download.filename = Some(attachment.filename.clone());
+
+        Ok(download)
+    }
+
+    /// Download the file at the given URL
+    ///
+    /// # Errors
+    /// This method returns an error if the response is an error, too large,
+    /// not of an accepted format, or could not be downloaded in time.
+    #[inline]
+    pub async fn fetch(&self, url: Url) -> Result<Download, DownloadError> {
+        self.fetch_with_progress(url, |_, _| ()).await
+    }
+
+    /// Download the file at the given URL, calling the given closure with the
+    /// number of bytes received so far and the expected total after each
+    /// chunk
+    ///
+    /// # Errors
+    /// This method returns an error if the response is an error, too large,
+    /// not of an accepted format, or could not be downloaded in time.
+    pub async fn fetch_with_progress(
+        &self,
+        url: Url,
+        mut progress: impl FnMut(u64, Option<u64>) + Send,
+    ) -> Result<Download, DownloadError> {
+        let accept = self
+            .accept
+            .iter()
+            .map(|f| f.mime())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let fetch = async {
+            let mut res = self
+                .client
+                .get(url)
+                .header(header::ACCEPT, accept)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                return Err(DownloadError::Status(res.status()));
+            }
+
+            let total = res.content_length();
+            if total.is_some_and(|l| l > self.max_size) {
+                return Err(DownloadError::TooLarge(self.max_size));
+            }
+
+            let mut data = vec![];
+            let mut format = None;
+            while let Some(chunk) = res.chunk().await? {
+                if (data.len() + chunk.len()) as u64 > self.max_size {
+                    return Err(DownloadError::TooLarge(self.max_size));
+                }
+
+                data.extend_from_slice(&chunk);
+                progress(data.len() as u64, total);
+
+                // Bail out early rather than downloading a whole file only to
+                // reject it
+                if format.is_none() && data.len() >= SNIFF_LEN {
+                    format = Some(self.check(&data)?);
+                }
+            }
+
+            let format = match format {
+                Some(f) => f,
+                None => self.check(&data)?,
+            };
+
+            Ok(Download {
+                format,
+                data,
+                filename: None,
+            })
+        };
+
+        tokio::time::timeout(self.timeout, fetch)
+            .await
+            .map_err(|_| DownloadError::Timeout)?
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DownloadError, Downloader, Format};
+
+    #[test]
+    fn sniff() {
+        let cases: &[(&[u8], _)] = &[
+            (b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", Some(Format::Png)),
+            (b"\xff\xd8\xff\xe0\0\x10JFIF", Some(Format::Jpeg)),
+            (b"GIF89a\x01\0\x01\0", Some(Format::Gif)),
+            (b"RIFF\0\0\0\0WEBPVP8 ", Some(Format::Webp)),
+            (b"RIFF\0\0\0\0WAVEfmt ", Some(Format::Wav)),
+            (b"\0\0\0\x1cftypavif", Some(Format::Avif)),
+            (b"OggS\0\x02", Some(Format::Ogg)),
+            (b"ID3\x04\0", Some(Format::Mp3)),
+            (b"\xff\xfb\x90\x64", Some(Format::Mp3)),
+            (b"fLaC\0\0\0\x22", Some(Format::Flac)),
+            (b"RIFF\0\0\0\0AVI ", None),
+            (b"{\"version\": 1}", None),
+            (b"", None),
+        ];
+
+        for &(data, expected) in cases {
+            assert_eq!(Format::sniff(data), expected, "{data:?}");
+        }
+    }
+
+    #[test]
+    fn check() {
+        let dl = Downloader::new(reqwest::Client::new(), Format::IMAGES);
+
+        assert_eq!(dl.check(b"GIF87a").unwrap(), Format::Gif);
+        assert!(matches!(
+            dl.check(b"OggS"),
+            Err(DownloadError::Rejected(Format::Ogg))
+        ));
+        assert!(matches!(
+            dl.check(b"hello"),
+            Err(DownloadError::Unrecognized)
+        ));
+    }
+}
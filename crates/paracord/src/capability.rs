@@ -0,0 +1,287 @@
+//! Detection of the gateway intents and caches available to a client
+//!
+//! Much of the data handlers need, such as a guild's member count or a user's
+//! voice channel, is only present in the cache if the client was started with
+//! the matching gateway intents.  The lookups in this module check which
+//! [`Capabilities`] the client has and fall back to fetching the data over
+//! HTTP when the cache cannot hold it.  Data with no HTTP equivalent instead
+//! fails with a [`CapabilityMissing`] error naming the intent to enable.
+//!
+//! A [`Registry`](crate::interaction::Registry) stores the capabilities it
+//! was configured with in the client's data so lookups can retrieve them with
+//! [`get`].  Until then, every intent is assumed to be enabled.
+
+use std::fmt;
+
+use serenity::{
+    http::GuildPagination,
+    model::{
+        gateway::GatewayIntents,
+        guild::Member,
+        id::{ChannelId, GuildId, UserId},
+        Colour,
+    },
+    prelude::TypeMapKey,
+};
+
+use crate::client::Context;
+
+/// Maximum number of guilds returned by a single request for the current
+/// user's guilds
+const GUILD_PAGE_SIZE: u64 = 200;
+
+/// A category of gateway data that may or may not be cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Guilds, along with their channels and roles
+    Guilds,
+    /// The members of each guild
+    Members,
+    /// The voice channel each guild member is connected to
+    VoiceStates,
+}
+
+impl Capability {
+    /// The gateway intents required to receive this data
+    #[must_use]
+    pub fn intents(self) -> GatewayIntents {
+        match self {
+            Self::Guilds => GatewayIntents::GUILDS,
+            Self::Members => GatewayIntents::GUILDS | GatewayIntents::GUILD_MEMBERS,
+            Self::VoiceStates => GatewayIntents::GUILDS | GatewayIntents::GUILD_VOICE_STATES,
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Guilds => "guild",
+            Self::Members => "guild member",
+            Self::VoiceStates => "voice state",
+        })
+    }
+}
+
+/// An error indicating data was requested that the client cannot receive
+/// with its current intents
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Missing {capability} data, requiring the {:?} gateway intents", capability.intents())]
+pub struct CapabilityMissing {
+    /// The unavailable data
+    pub capability: Capability,
+}
+
+/// An error arising from looking up gateway data
+#[derive(Debug, thiserror::Error)]
+pub enum LookupError {
+    /// The data cannot be received with the client's intents and has no HTTP
+    /// equivalent
+    #[error(transparent)]
+    Missing(#[from] CapabilityMissing),
+    /// The guild has not yet been received from the gateway
+    #[error("Guild {0} is not cached")]
+    Uncached(GuildId),
+    /// An error occurred fetching the data over HTTP
+    #[error("Error fetching data from Discord")]
+    Http(#[from] serenity::Error),
+}
+
+/// The set of gateway intents a client was started with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    intents: GatewayIntents,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self { Self::new(GatewayIntents::all()) }
+}
+
+impl Capabilities {
+    /// Construct a new set of capabilities from the client's intents
+    #[inline]
+    #[must_use]
+    pub const fn new(intents: GatewayIntents) -> Self { Self { intents } }
+
+    /// Get the intents the client was started with
+    #[inline]
+    #[must_use]
+    pub const fn intents(self) -> GatewayIntents { self.intents }
+
+    /// Returns true if the client receives the given data from the gateway
+    #[inline]
+    #[must_use]
+    pub fn has(self, capability: Capability) -> bool { self.intents.contains(capability.intents()) }
+
+    /// Check that the client receives the given data from the gateway
+    ///
+    /// # Errors
+    /// This method returns an error if the client's intents do not cover the
+    /// given data.
+    #[inline]
+    pub fn require(self, capability: Capability) -> Result<(), CapabilityMissing> {
+        if self.has(capability) {
+            Ok(())
+        } else {
+            Err(CapabilityMissing { capability })
+        }
+    }
+
+    /// Returns true if the given data can be read from the cache of the given
+    /// client
+    #[must_use]
+    pub fn cached(self, ctx: &Context, capability: Capability) -> bool {
+        self.has(capability) && ctx.cache.settings().cache_guilds
+    }
+}
+
+struct CapabilitiesKey;
+
+impl TypeMapKey for CapabilitiesKey {
+    type Value = Capabilities;
+}
+
+/// Store a set of capabilities in the client's data for retrieval with
+/// [`get`]
+pub(crate) async fn install(ctx: &Context, caps: Capabilities) {
+    ctx.data.write().await.insert::<CapabilitiesKey>(caps);
+}
+
+/// Retrieve the capabilities of this client, assuming every intent is
+/// enabled if none have been stored
+pub async fn get(ctx: &Context) -> Capabilities {
+    ctx.data
+        .read()
+        .await
+        .get::<CapabilitiesKey>()
+        .copied()
+        .unwrap_or_default()
+}
+
+/// List the guilds the current user is in, from the cache if possible
+///
+/// # Errors
+/// This function returns an error if the guilds are not cached and cannot be
+/// fetched.
+pub async fn guilds(ctx: &Context) -> Result<Vec<GuildId>, LookupError> {
+    if get(ctx).await.cached(ctx, Capability::Guilds) {
+        return Ok(ctx.cache.guilds());
+    }
+
+    let mut ids = vec![];
+    loop {
+        let page = ctx
+            .http
+            .get_guilds(
+                ids.last().copied().map(GuildPagination::After),
+                Some(GUILD_PAGE_SIZE),
+            )
+            .await?;
+        let done = (page.len() as u64) < GUILD_PAGE_SIZE;
+        ids.extend(page.into_iter().map(|g| g.id));
+
+        if done {
+            break Ok(ids);
+        }
+    }
+}
+
+/// Get the number of members in a guild, from the cache if possible
+///
+/// Fetched counts are approximate.
+///
+/// # Errors
+/// This function returns an error if the guild is not cached and cannot be
+/// fetched.
+pub async fn member_count(ctx: &Context, guild: GuildId) -> Result<u64, LookupError> {
+    if get(ctx).await.cached(ctx, Capability::Members) {
+        if let Some(count) = ctx.cache.guild(guild).map(|g| g.member_count) {
+            return Ok(count);
+        }
+    }
+
+    Ok(guild
+        .to_partial_guild_with_counts(&ctx.http)
+        .await?
+        .approximate_member_count
+        .unwrap_or_default())
+}
+
+/// Get the display colour of a guild member, from the cache if possible
+///
+/// # Errors
+/// This function returns an error if the member's roles are not cached and
+/// cannot be fetched.
+pub async fn member_colour(ctx: &Context, member: &Member) -> Result<Option<Colour>, LookupError> {
+    if get(ctx).await.cached(ctx, Capability::Guilds) && ctx.cache.guild(member.guild_id).is_some()
+    {
+        return Ok(member.colour(&ctx.cache));
+    }
+
+    let roles = member.guild_id.roles(&ctx.http).await?;
+    Ok(member
+        .roles
+        .iter()
+        .filter_map(|r| roles.get(r))
+        .filter(|r| r.colour.0 != 0)
+        .max_by_key(|r| (r.position, r.id))
+        .map(|r| r.colour))
+}
+
+/// Get the voice channel a user is connected to in a guild
+///
+/// Discord provides no way to fetch voice states over HTTP, so this requires
+/// the [`VoiceStates`](Capability::VoiceStates) capability.
+///
+/// # Errors
+/// This function returns an error if the client does not receive voice
+/// states, or if the guild has not yet been cached.
+pub async fn voice_channel(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+) -> Result<Option<ChannelId>, LookupError> {
+    let caps = get(ctx).await;
+    caps.require(Capability::VoiceStates)?;
+
+    if !caps.cached(ctx, Capability::VoiceStates) {
+        return Err(CapabilityMissing {
+            capability: Capability::VoiceStates,
+        }
+        .into());
+    }
+
+    let guild = ctx.cache.guild(guild).ok_or(LookupError::Uncached(guild))?;
+    Ok(guild.voice_states.get(&user).and_then(|s| s.channel_id))
+}
+
+#[cfg(test)]
+mod test {
+    use serenity::model::gateway::GatewayIntents;
+
+    use super::{Capabilities, Capability};
+
+    #[test]
+    fn detection() {
+        let all = Capabilities::default();
+        let minimal = Capabilities::new(GatewayIntents::GUILDS);
+        let none = Capabilities::new(GatewayIntents::empty());
+
+        for cap in [
+            Capability::Guilds,
+            Capability::Members,
+            Capability::VoiceStates,
+        ] {
+            assert!(all.has(cap));
+            assert!(!none.has(cap));
+            assert_eq!(none.require(cap).unwrap_err().capability, cap);
+        }
+
+        assert!(minimal.has(Capability::Guilds));
+        assert!(!minimal.has(Capability::Members));
+        assert!(minimal.require(Capability::VoiceStates).is_err());
+
+        let voice = Capabilities::new(GatewayIntents::GUILD_VOICE_STATES);
+        assert!(!voice.has(Capability::VoiceStates));
+    }
+}
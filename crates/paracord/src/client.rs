@@ -0,0 +1,108 @@
+//! Client state shared with interaction handlers, with or without a gateway
+//! connection
+
+use std::{fmt, sync::Arc};
+
+use serenity::{
+    cache::Cache,
+    client,
+    http::{CacheHttp, Http},
+    model::id::ShardId,
+    prelude::TypeMap,
+};
+use tokio::sync::RwLock;
+
+/// The client state available to interaction handlers
+///
+/// Interactions received over the gateway carry the context of the shard that
+/// received them, which can be retrieved with [`Self::gateway`].  Interactions
+/// received over HTTP have no shard, so their context is built from just an
+/// HTTP client, a cache, and the client's data.
+#[derive(Clone)]
+pub struct Context {
+    /// Arbitrary data shared by all handlers
+    pub data: Arc<RwLock<TypeMap>>,
+    /// The HTTP client used to talk to Discord
+    pub http: Arc<Http>,
+    /// The client's cache, which is only filled by gateway events
+    pub cache: Arc<Cache>,
+    /// The ID of the shard that received the interaction, or shard 0 if there
+    /// is no gateway connection
+    pub shard_id: ShardId,
+    gateway: Option<client::Context>,
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("shard_id", &self.shard_id)
+            .field("gateway", &self.gateway.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Context {
+    /// Construct a context for a client with no gateway connection
+    #[inline]
+    #[must_use]
+    pub fn new(http: Arc<Http>, cache: Arc<Cache>, data: Arc<RwLock<TypeMap>>) -> Self {
+        Self {
+            data,
+            http,
+            cache,
+            shard_id: ShardId(0),
+            gateway: None,
+        }
+    }
+
+    /// Get the context of the shard that received the interaction, or `None`
+    /// if the client has no gateway connection
+    #[inline]
+    #[must_use]
+    pub fn gateway(&self) -> Option<&client::Context> { self.gateway.as_ref() }
+}
+
+impl From<client::Context> for Context {
+    fn from(ctx: client::Context) -> Self {
+        Self {
+            data: Arc::clone(&ctx.data),
+            http: Arc::clone(&ctx.http),
+            cache: Arc::clone(&ctx.cache),
+            shard_id: ctx.shard_id,
+            gateway: Some(ctx),
+        }
+    }
+}
+
+impl From<&client::Context> for Context {
+    #[inline]
+    fn from(ctx: &client::Context) -> Self { ctx.clone().into() }
+}
+
+impl CacheHttp for Context {
+    #[inline]
+    fn http(&self) -> &Http { &self.http }
+
+    #[inline]
+    fn cache(&self) -> Option<&Arc<Cache>> { Some(&self.cache) }
+}
+
+impl AsRef<Http> for Context {
+    #[inline]
+    fn as_ref(&self) -> &Http { &self.http }
+}
+
+impl AsRef<Arc<Http>> for Context {
+    #[inline]
+    fn as_ref(&self) -> &Arc<Http> { &self.http }
+}
+
+impl AsRef<Cache> for Context {
+    #[inline]
+    fn as_ref(&self) -> &Cache { &self.cache }
+}
+
+impl AsRef<Arc<Cache>> for Context {
+    #[inline]
+    fn as_ref(&self) -> &Arc<Cache> { &self.cache }
+}
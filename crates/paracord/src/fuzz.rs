@@ -0,0 +1,681 @@
+//! Developer utility for checking Discord's interaction response rules
+//! against a live test guild
+//!
+//! A [`Fuzzer`] answers each interaction it receives with the next untried
+//! [`Sequence`] of response calls for that kind of interaction, and records
+//! whether Discord accepted every call.  Once enough interactions have been
+//! triggered, [`Fuzzer::matrix`] summarizes which sequences work for which
+//! interactions, so the rules documented in
+//! [`responder`](crate::interaction::response::responder) can be re-checked as
+//! Discord changes.
+//!
+//! Every response message includes a button, and every modal is tagged with
+//! the interaction that opened it, so clicking through the fuzzer's own
+//! responses exercises component and modal submit interactions as well.
+//!
+//! Interactions must be acknowledged within
+//! [`INITIAL_RESPONSE_WINDOW`] and their tokens expire after
+//! [`TOKEN_LIFETIME`].  A call that would be made after its deadline, or that
+//! fails once the deadline has passed, is recorded as
+//! [`Outcome::TimedOut`] rather than as a failure, and its sequence is retried
+//! on a later interaction.
+//!
+//! This module requires the `fuzz` feature.  It creates and deletes messages
+//! freely, and should only be used in a guild set aside for testing.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+};
+
+use chrono::{DateTime, Utc};
+use serenity::{
+    builder::{
+        CreateActionRow, CreateButton, CreateInputText, CreateInteractionResponse,
+        CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateModal,
+        EditInteractionResponse,
+    },
+    http::Http,
+    model::application::{ButtonStyle, CommandType, InputTextStyle, Interaction},
+    utils::MessageBuilder,
+};
+
+use crate::interaction::context::{INITIAL_RESPONSE_WINDOW, TOKEN_LIFETIME};
+
+/// Custom ID of the button attached to fuzzer messages
+const BUTTON_ID: &str = "paracord-fuzz";
+/// Prefix of the custom ID of fuzzer modals, followed by the initiating
+/// interaction kind
+const MODAL_PREFIX: &str = "paracord-fuzz:";
+
+/// A kind of interaction that can initiate a response flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Kind {
+    /// A chat input (slash) command
+    SlashCommand,
+    /// A user context menu command
+    UserCommand,
+    /// A message context menu command
+    MessageCommand,
+    /// A message component, such as a button
+    Component,
+}
+
+impl Kind {
+    const ALL: [Self; 4] = [
+        Self::SlashCommand,
+        Self::UserCommand,
+        Self::MessageCommand,
+        Self::Component,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::SlashCommand => "slash",
+            Self::UserCommand => "user",
+            Self::MessageCommand => "message",
+            Self::Component => "component",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> { Self::ALL.into_iter().find(|k| k.as_str() == s) }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SlashCommand => "slash command",
+            Self::UserCommand => "user command",
+            Self::MessageCommand => "message command",
+            Self::Component => "component",
+        })
+    }
+}
+
+/// The context an interaction was received in, which determines the
+/// responses it accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Flow {
+    /// An interaction triggered directly by a user
+    TopLevel(Kind),
+    /// A modal submitted in response to an interaction of the given kind
+    ModalSubmit(Kind),
+}
+
+impl Flow {
+    /// Identify the flow of a received interaction, or `None` if it is not
+    /// one the fuzzer can respond to
+    #[must_use]
+    pub fn of(int: &Interaction) -> Option<Self> {
+        match int {
+            Interaction::Command(c) => match c.data.kind {
+                CommandType::ChatInput => Some(Kind::SlashCommand),
+                CommandType::User => Some(Kind::UserCommand),
+                CommandType::Message => Some(Kind::MessageCommand),
+                _ => None,
+            }
+            .map(Self::TopLevel),
+            Interaction::Component(_) => Some(Self::TopLevel(Kind::Component)),
+            Interaction::Modal(m) => m
+                .data
+                .custom_id
+                .strip_prefix(MODAL_PREFIX)
+                .and_then(Kind::from_str)
+                .map(Self::ModalSubmit),
+            _ => None,
+        }
+    }
+
+    /// The kind of the interaction that started this flow
+    #[inline]
+    #[must_use]
+    pub fn initiator(self) -> Kind {
+        match self {
+            Self::TopLevel(k) | Self::ModalSubmit(k) => k,
+        }
+    }
+}
+
+impl fmt::Display for Flow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TopLevel(k) => k.fmt(f),
+            Self::ModalSubmit(k) => write!(f, "modal submit (from {k})"),
+        }
+    }
+}
+
+/// The type of response a create or defer call acknowledges an interaction
+/// with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ResponseKind {
+    /// Send a new message
+    Message,
+    /// Edit the message the interaction was triggered from
+    UpdateMessage,
+    /// Open a modal
+    Modal,
+}
+
+impl ResponseKind {
+    /// All response kinds
+    pub const ALL: [Self; 3] = [Self::Message, Self::UpdateMessage, Self::Modal];
+
+    /// Returns true if a response of this kind can be deferred
+    #[inline]
+    #[must_use]
+    pub fn can_defer(self) -> bool { !matches!(self, Self::Modal) }
+}
+
+/// A single interaction response call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Op {
+    /// Acknowledge the interaction with a response
+    Create(ResponseKind),
+    /// Acknowledge the interaction with a deferred response
+    ///
+    /// Modals cannot be deferred, so `Defer(ResponseKind::Modal)` always
+    /// fails without making a call.
+    Defer(ResponseKind),
+    /// Edit the original response
+    Edit,
+    /// Delete the original response
+    Delete,
+    /// Create a followup message
+    Followup,
+}
+
+impl Op {
+    /// Returns true if this call acknowledges the interaction
+    #[inline]
+    #[must_use]
+    pub fn acknowledges(self) -> bool { matches!(self, Self::Create(_) | Self::Defer(_)) }
+
+    fn deadline(self, created: DateTime<Utc>) -> DateTime<Utc> {
+        created
+            + if self.acknowledges() {
+                INITIAL_RESPONSE_WINDOW
+            } else {
+                TOKEN_LIFETIME
+            }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Create(k) => write!(f, "Create({k:?})"),
+            Self::Defer(k) => write!(f, "Defer({k:?})"),
+            Self::Edit => f.write_str("Edit"),
+            Self::Delete => f.write_str("Delete"),
+            Self::Followup => f.write_str("Followup"),
+        }
+    }
+}
+
+/// A series of response calls made for a single interaction
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sequence(Box<[Op]>);
+
+impl Sequence {
+    /// Construct a sequence from a list of calls
+    #[inline]
+    #[must_use]
+    pub fn new(ops: impl IntoIterator<Item = Op>) -> Self { Self(ops.into_iter().collect()) }
+
+    /// Get the calls in this sequence
+    #[inline]
+    #[must_use]
+    pub fn ops(&self) -> &[Op] { &self.0 }
+
+    /// The sequences tried by [`Fuzzer::default`], covering each response
+    /// kind along with the edit, delete and followup rules documented in
+    /// [`responder`](crate::interaction::response::responder)
+    #[must_use]
+    pub fn defaults() -> Vec<Self> {
+        use Op::{Create, Defer, Delete, Edit, Followup};
+
+        let mut out = vec![
+            Self::new([Edit]),
+            Self::new([Delete]),
+            Self::new([Followup]),
+            Self::new([Delete, Create(ResponseKind::Message)]),
+        ];
+
+        for kind in ResponseKind::ALL {
+            out.extend([
+                Self::new([Create(kind)]),
+                Self::new([Create(kind), Edit, Edit]),
+                Self::new([Create(kind), Delete, Followup]),
+                Self::new([Create(kind), Create(kind)]),
+            ]);
+
+            if kind.can_defer() {
+                out.extend([
+                    Self::new([Defer(kind), Edit, Edit]),
+                    Self::new([Defer(kind), Delete]),
+                    Self::new([Defer(kind), Delete, Edit]),
+                    Self::new([Defer(kind), Defer(kind)]),
+                    Self::new([Followup, Defer(kind)]),
+                ]);
+            }
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, op) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" -> ")?;
+            }
+            op.fmt(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of running a [`Sequence`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every call succeeded
+    Ok,
+    /// A call was rejected by Discord
+    Failed {
+        /// The index of the rejected call
+        step: usize,
+        /// The error returned for the call
+        error: String,
+    },
+    /// A call was not made, or failed, after its deadline had passed
+    TimedOut {
+        /// The index of the call
+        step: usize,
+    },
+}
+
+impl Outcome {
+    /// The number of calls that succeeded before this outcome was reached,
+    /// or `None` if all of them did
+    #[must_use]
+    pub fn stopped_at(&self) -> Option<usize> {
+        match *self {
+            Self::Ok => None,
+            Self::Failed { step, .. } | Self::TimedOut { step } => Some(step),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ok => f.write_str("OK"),
+            Self::Failed { step, error } => write!(f, "FAIL at step {step}: {error}"),
+            Self::TimedOut { step } => write!(f, "TIMEOUT at step {step}"),
+        }
+    }
+}
+
+/// The recorded outcome of each sequence for each interaction flow
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Matrix(BTreeMap<Flow, BTreeMap<Sequence, Outcome>>);
+
+impl Matrix {
+    /// Get the outcome recorded for a sequence in a flow, if it has been
+    /// tried
+    #[must_use]
+    pub fn get(&self, flow: Flow, seq: &Sequence) -> Option<&Outcome> {
+        self.0.get(&flow)?.get(seq)
+    }
+
+    /// Iterate over every recorded outcome, ordered by flow and sequence
+    pub fn iter(&self) -> impl Iterator<Item = (Flow, &Sequence, &Outcome)> {
+        self.0
+            .iter()
+            .flat_map(|(&f, s)| s.iter().map(move |(s, o)| (f, s, o)))
+    }
+
+    fn insert(&mut self, flow: Flow, seq: Sequence, outcome: Outcome) {
+        self.0.entry(flow).or_default().insert(seq, outcome);
+    }
+}
+
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<_> = self
+            .iter()
+            .map(|(f, s, o)| (f.to_string(), s.to_string(), o))
+            .collect();
+        let flow_width = rows.iter().map(|(f, ..)| f.len()).max().unwrap_or(0);
+        let seq_width = rows.iter().map(|(_, s, _)| s.len()).max().unwrap_or(0);
+
+        for (i, (flow, seq, outcome)) in rows.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{flow:<flow_width$}  {seq:<seq_width$}  {outcome}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn create_response(flow: Flow, kind: ResponseKind) -> CreateInteractionResponse {
+    match kind {
+        ResponseKind::Message => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content("Created by paracord fuzz")
+                .components(vec![CreateActionRow::Buttons(vec![CreateButton::new(
+                    BUTTON_ID,
+                )
+                .style(ButtonStyle::Primary)
+                .label("Next")])]),
+        ),
+        ResponseKind::UpdateMessage => CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new().content("Updated by paracord fuzz"),
+        ),
+        ResponseKind::Modal => CreateInteractionResponse::Modal(
+            CreateModal::new(
+                format!("{MODAL_PREFIX}{}", flow.initiator().as_str()),
+                "paracord fuzz",
+            )
+            .components(vec![CreateActionRow::InputText(
+                CreateInputText::new(InputTextStyle::Short, "Anything", "value").required(false),
+            )]),
+        ),
+    }
+}
+
+fn defer_response(kind: ResponseKind) -> Option<CreateInteractionResponse> {
+    match kind {
+        ResponseKind::Message => Some(CreateInteractionResponse::Defer(
+            CreateInteractionResponseMessage::new(),
+        )),
+        ResponseKind::UpdateMessage => Some(CreateInteractionResponse::Acknowledge),
+        ResponseKind::Modal => None,
+    }
+}
+
+fn edit_response() -> EditInteractionResponse {
+    EditInteractionResponse::new()
+        .content("Edited by paracord fuzz")
+        .components(vec![CreateActionRow::Buttons(vec![CreateButton::new(
+            BUTTON_ID,
+        )
+        .style(ButtonStyle::Primary)
+        .label("Next")])])
+}
+
+macro_rules! each_interaction {
+    ($int:expr, $i:ident => $body:expr) => {
+        match $int {
+            Interaction::Command($i) => $body,
+            Interaction::Component($i) => $body,
+            Interaction::Modal($i) => $body,
+            _ => unreachable!(),
+        }
+    };
+}
+
+async fn call(http: &Http, int: &Interaction, flow: Flow, op: Op) -> Result<(), String> {
+    let res = match op {
+        Op::Create(kind) => {
+            each_interaction!(int, i => i.create_response(http, create_response(flow, kind)).await)
+        },
+        Op::Defer(kind) => {
+            let Some(res) = defer_response(kind) else {
+                return Err("Response kind cannot be deferred".into());
+            };
+            each_interaction!(int, i => i.create_response(http, res).await)
+        },
+        Op::Edit => {
+            each_interaction!(int, i => i.edit_response(http, edit_response()).await.map(|_| ()))
+        },
+        Op::Delete => each_interaction!(int, i => i.delete_response(http).await),
+        Op::Followup => each_interaction!(int, i => i
+            .create_followup(
+                http,
+                CreateInteractionResponseFollowup::new().content("Followup from paracord fuzz"),
+            )
+            .await
+            .map(|_| ())),
+    };
+
+    res.map_err(|e| e.to_string())
+}
+
+/// Classify the result of a call given when it finished relative to its
+/// deadline
+fn classify(step: usize, res: Result<(), String>, expired: bool) -> Option<Outcome> {
+    match res {
+        Ok(()) => None,
+        Err(_) if expired => Some(Outcome::TimedOut { step }),
+        Err(error) => Some(Outcome::Failed { step, error }),
+    }
+}
+
+/// Run a sequence of calls against an interaction, stopping at the first
+/// failure
+async fn run(http: &Http, int: &Interaction, flow: Flow, seq: &Sequence) -> Outcome {
+    let created = *int.id().created_at();
+
+    for (step, &op) in seq.ops().iter().enumerate() {
+        let deadline = op.deadline(created);
+        if Utc::now() >= deadline {
+            return Outcome::TimedOut { step };
+        }
+
+        let res = call(http, int, flow, op).await;
+        if let Some(outcome) = classify(step, res, Utc::now() >= deadline) {
+            return outcome;
+        }
+    }
+
+    Outcome::Ok
+}
+
+/// Responds to interactions with untried response sequences and records the
+/// results
+#[derive(Debug)]
+pub struct Fuzzer {
+    sequences: Vec<Sequence>,
+    untried: BTreeMap<Flow, VecDeque<Sequence>>,
+    matrix: Matrix,
+}
+
+impl Default for Fuzzer {
+    fn default() -> Self { Self::new(Sequence::defaults()) }
+}
+
+impl Fuzzer {
+    /// Construct a fuzzer that tries each of the given sequences once per
+    /// interaction flow
+    #[must_use]
+    pub fn new(sequences: impl IntoIterator<Item = Sequence>) -> Self {
+        Self {
+            sequences: sequences.into_iter().collect(),
+            untried: BTreeMap::new(),
+            matrix: Matrix::default(),
+        }
+    }
+
+    /// Get the outcomes recorded so far
+    #[inline]
+    #[must_use]
+    pub fn matrix(&self) -> &Matrix { &self.matrix }
+
+    /// Get the number of sequences not yet tried for the given flow
+    #[must_use]
+    pub fn remaining(&self, flow: Flow) -> usize {
+        self.untried
+            .get(&flow)
+            .map_or(self.sequences.len(), VecDeque::len)
+    }
+
+    fn next(&mut self, flow: Flow) -> Option<Sequence> {
+        self.untried
+            .entry(flow)
+            .or_insert_with(|| self.sequences.iter().cloned().collect())
+            .pop_front()
+    }
+
+    /// Respond to an interaction with the next untried sequence for its
+    /// flow, then report the outcome in the channel
+    ///
+    /// Returns `None` without responding if the interaction is not one the
+    /// fuzzer handles, or if every sequence has been tried for its flow.
+    pub async fn handle(
+        &mut self,
+        http: &Http,
+        int: &Interaction,
+    ) -> Option<(Flow, Sequence, Outcome)> {
+        let flow = Flow::of(int)?;
+        let Some(seq) = self.next(flow) else {
+            tracing::warn!(%flow, "No untried sequences left");
+            return None;
+        };
+
+        let outcome = run(http, int, flow, &seq).await;
+        match outcome {
+            Outcome::Ok => tracing::info!(%flow, %seq, "Sequence succeeded"),
+            Outcome::Failed { step, ref error } => {
+                tracing::warn!(%flow, %seq, step, error, "Sequence failed");
+            },
+            Outcome::TimedOut { step } => {
+                tracing::warn!(%flow, %seq, step, "Sequence timed out, will retry");
+                if let Some(untried) = self.untried.get_mut(&flow) {
+                    untried.push_back(seq.clone());
+                }
+            },
+        }
+
+        if !matches!(outcome, Outcome::TimedOut { .. }) {
+            self.matrix.insert(flow, seq.clone(), outcome.clone());
+        }
+
+        self.report(http, int, flow, &seq, &outcome).await;
+
+        Some((flow, seq, outcome))
+    }
+
+    /// Post the outcome of a sequence, on a best-effort basis
+    async fn report(
+        &self,
+        http: &Http,
+        int: &Interaction,
+        flow: Flow,
+        seq: &Sequence,
+        outcome: &Outcome,
+    ) {
+        let done = outcome.stopped_at().unwrap_or(seq.ops().len());
+        let acked = seq.ops()[..done].iter().any(|o| o.acknowledges());
+
+        let remaining = self.remaining(flow);
+        let mut mb = MessageBuilder::new();
+        mb.push_bold_safe(flow.to_string())
+            .push(": ")
+            .push_mono_safe(seq.to_string())
+            .push(" ")
+            .push_bold_line_safe(outcome.to_string());
+        if remaining == 0 {
+            mb.push("All sequences tried for this interaction.");
+        } else {
+            mb.push(format!(
+                "{remaining} sequence(s) left for this interaction."
+            ));
+        }
+        let content = mb.build();
+
+        let res = if acked {
+            each_interaction!(int, i => i
+                .create_followup(http, CreateInteractionResponseFollowup::new().content(content))
+                .await
+                .map(|_| ()))
+        } else if Utc::now() < *int.id().created_at() + INITIAL_RESPONSE_WINDOW {
+            each_interaction!(int, i => i
+                .create_response(
+                    http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content(content),
+                    ),
+                )
+                .await)
+        } else {
+            return;
+        };
+
+        if let Err(err) = res {
+            tracing::warn!(%err, "Error reporting fuzz outcome");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{classify, Flow, Kind, Matrix, Op, Outcome, ResponseKind, Sequence};
+
+    #[test]
+    fn sequences() {
+        let defaults = Sequence::defaults();
+        assert!(!defaults
+            .iter()
+            .flat_map(Sequence::ops)
+            .any(|&o| o == Op::Defer(ResponseKind::Modal)));
+
+        let mut sorted = defaults.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), defaults.len());
+
+        let seq = Sequence::new([Op::Defer(ResponseKind::Message), Op::Edit]);
+        assert_eq!(seq.to_string(), "Defer(Message) -> Edit");
+
+        for kind in Kind::ALL {
+            assert_eq!(Kind::from_str(kind.as_str()), Some(kind));
+        }
+    }
+
+    #[test]
+    fn outcomes() {
+        assert_eq!(classify(0, Ok(()), true), None);
+        assert_eq!(
+            classify(1, Err("Unknown interaction".into()), true),
+            Some(Outcome::TimedOut { step: 1 })
+        );
+        assert_eq!(
+            classify(2, Err("Unknown message".into()), false),
+            Some(Outcome::Failed {
+                step: 2,
+                error: "Unknown message".into()
+            })
+        );
+
+        let mut matrix = Matrix::default();
+        let seq = Sequence::new([Op::Create(ResponseKind::Modal)]);
+        matrix.insert(Flow::TopLevel(Kind::SlashCommand), seq.clone(), Outcome::Ok);
+        matrix.insert(
+            Flow::ModalSubmit(Kind::Component),
+            seq.clone(),
+            Outcome::Failed {
+                step: 0,
+                error: "Invalid form body".into(),
+            },
+        );
+
+        assert_eq!(
+            matrix.get(Flow::TopLevel(Kind::SlashCommand), &seq),
+            Some(&Outcome::Ok)
+        );
+        assert_eq!(
+            matrix.to_string(),
+            [
+                "slash command                  Create(Modal)  OK",
+                "modal submit (from component)  Create(Modal)  FAIL at step 0: Invalid form body",
+            ]
+            .join("\n")
+        );
+    }
+}
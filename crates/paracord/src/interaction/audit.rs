@@ -0,0 +1,268 @@
+//! Audit logging for changes made to registered application commands
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serenity::model::id::{ApplicationId, CommandId, GuildId};
+
+use super::{
+    command::{self, CommandInfo},
+    ring::RingLog,
+};
+
+/// The kind of change made to a registered command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MutationKind {
+    /// A new command was registered
+    Create,
+    /// An existing command was edited in-place
+    Update,
+    /// An existing command was unregistered
+    Delete,
+}
+
+impl fmt::Display for MutationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Create => "created",
+            Self::Update => "updated",
+            Self::Delete => "deleted",
+        })
+    }
+}
+
+/// A record of a single change made to a registered command
+#[derive(Debug, Clone)]
+pub struct CommandMutation {
+    /// The time the change was made
+    pub time: DateTime<Utc>,
+    /// The ID of the application that made the change
+    pub actor: ApplicationId,
+    /// The guild the command is registered in, or `None` for global commands
+    pub guild: Option<GuildId>,
+    /// The ID of the affected command
+    pub id: CommandId,
+    /// The kind of change made
+    pub kind: MutationKind,
+    /// The command as it was before the change, if it existed
+    pub old: Option<CommandInfo>,
+    /// The command as it was after the change, if it still exists
+    pub new: Option<CommandInfo>,
+}
+
+impl CommandMutation {
+    /// Get the name of the affected command, preferring the name after the
+    /// change if it was renamed
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.new
+            .as_ref()
+            .or(self.old.as_ref())
+            .map_or("<unknown>", |i| i.name())
+    }
+
+    /// Compute the fields changed by an update
+    ///
+    /// Creations and deletions produce an empty diff.
+    #[must_use]
+    pub fn diff(&self) -> Vec<command::Change> {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => command::diff(old, new),
+            _ => vec![],
+        }
+    }
+}
+
+impl fmt::Display for CommandMutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            time,
+            actor,
+            guild,
+            id,
+            kind,
+            ..
+        } = self;
+
+        write!(
+            f,
+            "[{}] {kind} {:?} (ID {id}) by app {actor}",
+            time.format("%Y-%m-%d %H:%M:%S"),
+            self.name(),
+        )?;
+
+        if let Some(guild) = guild {
+            write!(f, " in guild {guild}")?;
+        }
+
+        for change in self.diff() {
+            write!(f, "\n  {change}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A summary of the guild-scoped commands found and removed in a single guild
+/// when reconciling it with the command handlers
+#[derive(Debug, Clone)]
+pub struct GuildReconciliation {
+    /// The time the reconciliation finished
+    pub time: DateTime<Utc>,
+    /// The guild that was reconciled
+    pub guild: GuildId,
+    /// The names of orphaned commands left in place because they were
+    /// allowlisted
+    pub kept: Vec<String>,
+    /// The names of orphaned commands that were deleted
+    pub deleted: Vec<String>,
+    /// The names of orphaned commands left in place because deletion was
+    /// skipped, either by a dry run or by a sync gate
+    pub skipped: Vec<String>,
+}
+
+impl GuildReconciliation {
+    /// Returns true if no guild commands were found
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.kept.is_empty() && self.deleted.is_empty() && self.skipped.is_empty()
+    }
+}
+
+impl fmt::Display for GuildReconciliation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            time,
+            guild,
+            kept,
+            deleted,
+            skipped,
+        } = self;
+
+        write!(
+            f,
+            "[{}] reconciled guild {guild}: {} deleted, {} kept, {} skipped",
+            time.format("%Y-%m-%d %H:%M:%S"),
+            deleted.len(),
+            kept.len(),
+            skipped.len(),
+        )?;
+
+        for (sigil, names) in [('-', deleted), ('=', kept), ('?', skipped)] {
+            for name in names {
+                write!(f, "\n  {sigil} {name:?}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A destination for command mutation records
+pub trait AuditSink: fmt::Debug + Send + Sync {
+    /// Record a change made to a registered command
+    fn record(&self, mutation: CommandMutation);
+
+    /// Record the outcome of reconciling a guild's commands
+    ///
+    /// Any deletions made are also passed to [`record`](Self::record)
+    /// individually.  The default implementation discards the summary.
+    #[inline]
+    fn reconciled(&self, _summary: GuildReconciliation) {}
+}
+
+/// An [`AuditSink`] retaining a fixed number of the most recent mutations and
+/// guild reconciliations in memory
+#[derive(Debug)]
+pub struct MemoryAuditLog {
+    mutations: RingLog<CommandMutation>,
+    reconciliations: RingLog<GuildReconciliation>,
+}
+
+impl MemoryAuditLog {
+    /// Construct a new audit log retaining at most `cap` mutations and `cap`
+    /// reconciliations
+    #[must_use]
+    pub fn new(cap: usize) -> Self {
+        Self {
+            mutations: RingLog::new(cap),
+            reconciliations: RingLog::new(cap),
+        }
+    }
+
+    /// Get up to `n` of the most recent mutations, oldest first
+    ///
+    /// # Panics
+    /// This method panics if the log's lock is poisoned.
+    #[must_use]
+    pub fn recent(&self, n: usize) -> Vec<CommandMutation> { self.mutations.recent(n) }
+
+    /// Get up to `n` of the most recent guild reconciliations, oldest first
+    ///
+    /// # Panics
+    /// This method panics if the log's lock is poisoned.
+    #[must_use]
+    pub fn reconciliations(&self, n: usize) -> Vec<GuildReconciliation> {
+        self.reconciliations.recent(n)
+    }
+
+    /// Render up to `n` of the most recent mutations as human-readable text,
+    /// oldest first
+    ///
+    /// # Panics
+    /// This method panics if the log's lock is poisoned.
+    #[must_use]
+    pub fn render(&self, n: usize) -> String {
+        self.recent(n)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl AuditSink for MemoryAuditLog {
+    fn record(&self, mutation: CommandMutation) { self.mutations.push(mutation); }
+
+    fn reconciled(&self, summary: GuildReconciliation) { self.reconciliations.push(summary); }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Utc};
+    use serenity::model::id::GuildId;
+
+    use super::{AuditSink, GuildReconciliation, MemoryAuditLog};
+
+    fn summary(guild: u64) -> GuildReconciliation {
+        GuildReconciliation {
+            time: DateTime::<Utc>::UNIX_EPOCH,
+            guild: GuildId::new(guild),
+            kept: vec!["pinned".into()],
+            deleted: vec!["old".into(), "older".into()],
+            skipped: vec![],
+        }
+    }
+
+    #[test]
+    fn reconciliation_display() {
+        assert_eq!(
+            summary(1).to_string(),
+            "[1970-01-01 00:00:00] reconciled guild 1: 2 deleted, 1 kept, 0 skipped\n  - \
+             \"old\"\n  - \"older\"\n  = \"pinned\"",
+        );
+    }
+
+    #[test]
+    fn log_keeps_reconciliations() {
+        let log = MemoryAuditLog::new(2);
+        for guild in 1..=3 {
+            log.reconciled(summary(guild));
+        }
+
+        let guilds: Vec<_> = log.reconciliations(10).into_iter().map(|r| r.guild).collect();
+        assert_eq!(guilds, [2, 3].map(GuildId::new));
+        assert!(log.recent(10).is_empty());
+    }
+}
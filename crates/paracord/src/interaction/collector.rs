@@ -0,0 +1,373 @@
+//! One-off waits for component interactions and reactions on a message
+//!
+//! Persistent components are routed by [`RpcHandler`](super::handler::RpcHandler)
+//! keys, which survive restarts but must be registered ahead of time.  For
+//! short-lived flows such as "click to confirm", a handler can instead send a
+//! message and wait inline for the next matching interaction or reaction on
+//! it with [`Collectors::await_component`] or [`Collectors::await_reaction`].
+//!
+//! A [`Registry`](super::Registry) offers every component interaction to its
+//! collectors before routing it, and stores its collectors in the client's
+//! data so handlers can retrieve them with [`get`].  Reactions are not
+//! interactions, so the event handler must pass them to
+//! [`Registry::offer_reaction`](super::Registry::offer_reaction) itself.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serenity::{
+    model::{
+        application::ComponentInteraction,
+        channel::Reaction,
+        id::{GuildId, MessageId},
+    },
+    prelude::TypeMapKey,
+};
+use tokio::sync::oneshot;
+
+use crate::client::Context;
+
+/// The default limit on concurrent collectors per guild
+pub const DEFAULT_MAX_PER_GUILD: usize = 25;
+
+/// Error returned when a guild already has the maximum number of active
+/// collectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Too many collectors are active in this server")]
+pub struct TooManyCollectors;
+
+type Filter<T> = Box<dyn Fn(&T) -> bool + Send + Sync>;
+
+struct Waiter<T> {
+    id: u64,
+    filter: Filter<T>,
+    tx: oneshot::Sender<T>,
+}
+
+/// Waiters for a single kind of event, keyed by the message they watch
+struct Waiters<T>(HashMap<MessageId, Vec<Waiter<T>>>);
+
+impl<T> Default for Waiters<T> {
+    fn default() -> Self { Self(HashMap::new()) }
+}
+
+impl<T> Waiters<T> {
+    fn push(&mut self, message: MessageId, waiter: Waiter<T>) {
+        self.0.entry(message).or_default().push(waiter);
+    }
+
+    /// Hand an event to the oldest live waiter whose filter accepts it,
+    /// returning the event if there is none
+    fn offer(&mut self, message: MessageId, item: T) -> Result<(), T> {
+        let Some(list) = self.0.get_mut(&message) else {
+            return Err(item);
+        };
+
+        list.retain(|w| !w.tx.is_closed());
+        let waiter = list
+            .iter()
+            .position(|w| (w.filter)(&item))
+            .map(|i| list.remove(i));
+        if list.is_empty() {
+            self.0.remove(&message);
+        }
+
+        match waiter {
+            Some(w) => w.tx.send(item),
+            None => Err(item),
+        }
+    }
+
+    fn remove(&mut self, message: MessageId, id: u64) {
+        let Some(list) = self.0.get_mut(&message) else {
+            return;
+        };
+
+        list.retain(|w| w.id != id);
+        if list.is_empty() {
+            self.0.remove(&message);
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    components: Waiters<ComponentInteraction>,
+    reactions: Waiters<Reaction>,
+    guilds: HashMap<Option<GuildId>, usize>,
+}
+
+type Select<T> = fn(&mut State) -> &mut Waiters<T>;
+
+/// The set of active collectors for a client
+pub struct Collectors {
+    max_per_guild: usize,
+    state: Mutex<State>,
+}
+
+impl std::fmt::Debug for Collectors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Collectors")
+            .field("max_per_guild", &self.max_per_guild)
+            .field("active", &self.active())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Collectors {
+    fn default() -> Self { Self::new(DEFAULT_MAX_PER_GUILD) }
+}
+
+/// A registered waiter, removed when dropped
+struct Registration<'a, T> {
+    collectors: &'a Collectors,
+    select: Select<T>,
+    guild: Option<GuildId>,
+    message: MessageId,
+    id: u64,
+}
+
+impl<T> Drop for Registration<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.collectors.state.lock().unwrap();
+        (self.select)(&mut state).remove(self.message, self.id);
+
+        if let Some(count) = state.guilds.get_mut(&self.guild) {
+            *count -= 1;
+            if *count == 0 {
+                state.guilds.remove(&self.guild);
+            }
+        }
+    }
+}
+
+impl Collectors {
+    /// Construct a new set of collectors, allowing at most `max_per_guild`
+    /// to wait at once in any one guild
+    ///
+    /// Collectors in direct messages share a single limit.
+    #[inline]
+    #[must_use]
+    pub fn new(max_per_guild: usize) -> Self {
+        Self {
+            max_per_guild,
+            state: Mutex::default(),
+        }
+    }
+
+    /// The number of collectors currently waiting
+    ///
+    /// # Panics
+    /// This method panics if the collectors' lock is poisoned.
+    #[must_use]
+    pub fn active(&self) -> usize { self.state.lock().unwrap().guilds.values().sum() }
+
+    fn register<T>(
+        &self,
+        select: Select<T>,
+        guild: Option<GuildId>,
+        message: MessageId,
+        filter: Filter<T>,
+    ) -> Result<(Registration<'_, T>, oneshot::Receiver<T>), TooManyCollectors> {
+        let mut state = self.state.lock().unwrap();
+        let count = state.guilds.entry(guild).or_default();
+        if *count >= self.max_per_guild {
+            tracing::warn!(?guild, "Too many active collectors");
+            return Err(TooManyCollectors);
+        }
+        *count += 1;
+
+        let id = state.next_id;
+        state.next_id += 1;
+        let (tx, rx) = oneshot::channel();
+        select(&mut state).push(message, Waiter { id, filter, tx });
+
+        Ok((
+            Registration {
+                collectors: self,
+                select,
+                guild,
+                message,
+                id,
+            },
+            rx,
+        ))
+    }
+
+    async fn wait<T>(
+        &self,
+        select: Select<T>,
+        guild: Option<GuildId>,
+        message: MessageId,
+        filter: Filter<T>,
+        timeout: Duration,
+    ) -> Result<Option<T>, TooManyCollectors> {
+        let (reg, mut rx) = self.register(select, guild, message, filter)?;
+
+        if let Ok(res) = tokio::time::timeout(timeout, &mut rx).await {
+            return Ok(res.ok());
+        }
+
+        // An event may have been handed over just as the timeout elapsed
+        drop(reg);
+        Ok(rx.try_recv().ok())
+    }
+
+    /// Wait for the next component interaction on a message accepted by
+    /// `filter`, returning `None` if none arrives within `timeout`
+    ///
+    /// The interaction is not routed to any other handler, so the caller must
+    /// respond to it within three seconds of receiving it.
+    ///
+    /// # Errors
+    /// This method returns an error if the guild already has the maximum
+    /// number of active collectors.
+    pub async fn await_component(
+        &self,
+        guild: Option<GuildId>,
+        message: MessageId,
+        filter: impl Fn(&ComponentInteraction) -> bool + Send + Sync + 'static,
+        timeout: Duration,
+    ) -> Result<Option<ComponentInteraction>, TooManyCollectors> {
+        self.wait(
+            |s| &mut s.components,
+            guild,
+            message,
+            Box::new(filter),
+            timeout,
+        )
+        .await
+    }
+
+    /// Wait for the next reaction added to a message accepted by `filter`,
+    /// returning `None` if none arrives within `timeout`
+    ///
+    /// # Errors
+    /// This method returns an error if the guild already has the maximum
+    /// number of active collectors.
+    pub async fn await_reaction(
+        &self,
+        guild: Option<GuildId>,
+        message: MessageId,
+        filter: impl Fn(&Reaction) -> bool + Send + Sync + 'static,
+        timeout: Duration,
+    ) -> Result<Option<Reaction>, TooManyCollectors> {
+        self.wait(
+            |s| &mut s.reactions,
+            guild,
+            message,
+            Box::new(filter),
+            timeout,
+        )
+        .await
+    }
+
+    /// Hand a component interaction to a waiting collector, returning it if
+    /// no collector accepted it
+    pub(super) fn offer_component(
+        &self,
+        mc: ComponentInteraction,
+    ) -> Result<(), ComponentInteraction> {
+        let message = mc.message.id;
+        self.state.lock().unwrap().components.offer(message, mc)
+    }
+
+    /// Hand a reaction to a waiting collector, returning true if one accepted
+    /// it
+    ///
+    /// # Panics
+    /// This method panics if the collectors' lock is poisoned.
+    pub fn offer_reaction(&self, reaction: &Reaction) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .reactions
+            .offer(reaction.message_id, reaction.clone())
+            .is_ok()
+    }
+}
+
+struct CollectorsKey;
+
+impl TypeMapKey for CollectorsKey {
+    type Value = Arc<Collectors>;
+}
+
+/// Store a set of collectors in the client's data for retrieval with [`get`]
+pub(super) async fn install(ctx: &Context, collectors: &Arc<Collectors>) {
+    ctx.data
+        .write()
+        .await
+        .insert::<CollectorsKey>(Arc::clone(collectors));
+}
+
+/// Retrieve the collectors of the [`Registry`](super::Registry) handling
+/// interactions for this client
+///
+/// This returns `None` until the registry has been initialized.
+pub async fn get(ctx: &Context) -> Option<Arc<Collectors>> {
+    ctx.data.read().await.get::<CollectorsKey>().map(Arc::clone)
+}
+
+#[cfg(test)]
+mod test {
+    use serenity::model::id::{GuildId, MessageId};
+
+    use super::{Collectors, TooManyCollectors, Waiter, Waiters};
+
+    #[test]
+    fn offer() {
+        let msg = MessageId::new(1);
+        let mut waiters = Waiters::<u32>::default();
+        let mut waiter = |id, f: fn(&u32) -> bool| {
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            waiters.push(msg, Waiter {
+                id,
+                filter: Box::new(f),
+                tx,
+            });
+            rx
+        };
+
+        let mut even = waiter(0, |n| n % 2 == 0);
+        let mut any = waiter(1, |_| true);
+        drop(waiter(2, |_| true));
+
+        assert_eq!(waiters.offer(MessageId::new(2), 4), Err(4));
+        assert_eq!(waiters.offer(msg, 3), Ok(()));
+        assert_eq!(any.try_recv(), Ok(3));
+        assert_eq!(waiters.offer(msg, 5), Err(5));
+        assert_eq!(waiters.offer(msg, 6), Ok(()));
+        assert_eq!(even.try_recv(), Ok(6));
+        assert!(waiters.0.is_empty());
+    }
+
+    #[test]
+    fn limits() {
+        let collectors = Collectors::new(1);
+        let (guild, msg) = (Some(GuildId::new(1)), MessageId::new(1));
+        let register = |guild| {
+            collectors
+                .register(|s| &mut s.reactions, guild, msg, Box::new(|_| true))
+                .map(|(reg, _rx)| reg)
+        };
+
+        let first = register(guild).unwrap();
+        assert_eq!(register(guild).err(), Some(TooManyCollectors));
+        let dm = register(None).unwrap();
+        assert_eq!(collectors.active(), 2);
+
+        drop(first);
+        assert_eq!(collectors.active(), 1);
+        assert!(!collectors.state.lock().unwrap().reactions.0.is_empty());
+        drop(dm);
+        assert_eq!(collectors.active(), 0);
+        assert!(collectors.state.lock().unwrap().reactions.0.is_empty());
+
+        drop(register(guild).unwrap());
+    }
+}
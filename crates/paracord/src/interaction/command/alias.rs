@@ -0,0 +1,362 @@
+use std::{fmt, str::FromStr};
+
+use async_trait::async_trait;
+use ordered_float::OrderedFloat;
+use serde_json::{json, Value};
+use serenity::model::application::{CommandDataOption, CommandInteraction, CommandOptionType};
+
+use super::{info::Data, ArgType, CommandInfo, Trie};
+use crate::client::Context;
+
+/// A shorthand for invoking a command with preset arguments, such as
+/// `jpeg quality:5`
+///
+/// Aliases are parsed from a command name, followed by the names of any
+/// subcommands and then any number of `name:value` arguments, all separated
+/// by whitespace.  Argument values cannot contain whitespace.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alias {
+    command: String,
+    path: Vec<String>,
+    args: Vec<(String, String)>,
+}
+
+impl FromStr for Alias {
+    type Err = AliasError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let command = words
+            .next()
+            .ok_or(AliasError::Parse("Expected a command name"))?
+            .to_owned();
+        let mut path = vec![];
+        let mut args = vec![];
+
+        for word in words {
+            match word.split_once(':') {
+                Some((name, value)) if !name.is_empty() && !value.is_empty() => {
+                    args.push((name.to_owned(), value.to_owned()));
+                },
+                Some(_) => {
+                    return Err(AliasError::Parse(
+                        "Expected arguments of the form name:value",
+                    ))
+                },
+                None if args.is_empty() => path.push(word.to_owned()),
+                None => return Err(AliasError::Parse("Subcommands must come before arguments")),
+            }
+        }
+
+        Ok(Self {
+            command,
+            path,
+            args,
+        })
+    }
+}
+
+impl fmt::Display for Alias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.command)?;
+
+        for sub in &self.path {
+            write!(f, " {sub}")?;
+        }
+
+        for (name, value) in &self.args {
+            write!(f, " {name}:{value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Alias {
+    /// Get the name of the command this alias invokes
+    #[inline]
+    #[must_use]
+    pub fn command(&self) -> &str { &self.command }
+}
+
+/// An error arising from parsing or expanding an [`Alias`]
+#[derive(Debug, thiserror::Error)]
+pub enum AliasError {
+    /// The alias could not be parsed
+    #[error("Invalid alias: {0}")]
+    Parse(&'static str),
+    /// No alias exists with the given name
+    #[error("There is no alias named {0:?}")]
+    Unknown(String),
+    /// The aliased command does not exist
+    #[error("There is no command named {0:?}")]
+    UnknownCommand(String),
+    /// The aliased command is not a slash command
+    #[error("Command {0:?} cannot be aliased")]
+    NotSlash(String),
+    /// The alias names a subcommand that does not exist
+    #[error("There is no subcommand named {0:?}")]
+    UnknownSubcommand(String),
+    /// The alias names a command with subcommands without choosing one
+    #[error("The alias must choose a subcommand")]
+    MissingSubcommand,
+    /// The alias gives an argument the command does not take
+    #[error("There is no argument named {0:?}")]
+    UnknownArg(String),
+    /// The alias omits an argument the command requires
+    #[error("The required argument {0:?} is missing")]
+    MissingArg(String),
+    /// The alias gives an argument a value the command does not accept
+    #[error("Invalid value {value:?} for argument {name:?}")]
+    BadValue {
+        /// The name of the argument
+        name: String,
+        /// The value given for it
+        value: String,
+    },
+    /// The alias could not be looked up
+    #[error("Error looking up alias")]
+    Backend(#[source] anyhow::Error),
+}
+
+/// Lookup of the alias invoked by a command interaction, if any
+///
+/// A [`Registry`](crate::interaction::Registry) with a resolver consults it
+/// before dispatching each command.  Interactions that invoke an alias are
+/// rewritten into the aliased command with its preset arguments, then
+/// dispatched to that command's handler instead.
+#[async_trait]
+pub trait AliasResolver: fmt::Debug + Send + Sync {
+    /// Look up the alias invoked by the given interaction
+    ///
+    /// Returns `Ok(None)` for interactions that do not invoke an alias, which
+    /// are dispatched unchanged.
+    async fn resolve(
+        &self,
+        ctx: &Context,
+        aci: &CommandInteraction,
+    ) -> Result<Option<Alias>, AliasError>;
+}
+
+fn bad_value(name: &str, value: &str) -> AliasError {
+    AliasError::BadValue {
+        name: name.into(),
+        value: value.into(),
+    }
+}
+
+/// Convert a preset argument to the raw value of a command option
+fn arg_value(
+    name: &str,
+    value: &str,
+    ty: &ArgType,
+) -> Result<(CommandOptionType, Value), AliasError> {
+    let bad = || bad_value(name, value);
+
+    Ok(match ty {
+        ArgType::String {
+            min_len, max_len, ..
+        } => {
+            let len = value.chars().count();
+            if min_len.is_some_and(|m| len < usize::from(m))
+                || max_len.is_some_and(|m| len > usize::from(m))
+            {
+                return Err(bad());
+            }
+
+            (CommandOptionType::String, json!(value))
+        },
+        ArgType::StringChoice(choices) => {
+            if !choices.iter().any(|c| c.val == value) {
+                return Err(bad());
+            }
+
+            (CommandOptionType::String, json!(value))
+        },
+        ArgType::Int { min, max, .. } => {
+            let n: i64 = value.parse().map_err(|_| bad())?;
+            if min.is_some_and(|m| n < m) || max.is_some_and(|m| n > m) {
+                return Err(bad());
+            }
+
+            (CommandOptionType::Integer, json!(n))
+        },
+        ArgType::IntChoice(choices) => {
+            let n: i64 = value.parse().map_err(|_| bad())?;
+            if !choices.iter().any(|c| c.val == n) {
+                return Err(bad());
+            }
+
+            (CommandOptionType::Integer, json!(n))
+        },
+        ArgType::Real { min, max, .. } => {
+            let n: f64 = value
+                .parse()
+                .ok()
+                .filter(|n: &f64| n.is_finite())
+                .ok_or_else(bad)?;
+            if min.is_some_and(|m| n < *m) || max.is_some_and(|m| n > *m) {
+                return Err(bad());
+            }
+
+            (CommandOptionType::Number, json!(n))
+        },
+        ArgType::RealChoice(choices) => {
+            let n: f64 = value.parse().map_err(|_| bad())?;
+            if !choices.iter().any(|c| c.val == OrderedFloat(n)) {
+                return Err(bad());
+            }
+
+            (CommandOptionType::Number, json!(n))
+        },
+        ArgType::Bool => (
+            CommandOptionType::Boolean,
+            json!(value.parse::<bool>().map_err(|_| bad())?),
+        ),
+        ArgType::User
+        | ArgType::Channel(_)
+        | ArgType::Role
+        | ArgType::Mention
+        | ArgType::Attachment => return Err(bad()),
+    })
+}
+
+impl CommandInfo {
+    /// Produce the options of an invocation of this command equivalent to the
+    /// given alias
+    ///
+    /// # Errors
+    /// This method returns an error if the alias does not name this command,
+    /// or its subcommands or arguments do not match this command's.
+    pub fn expand_alias(&self, alias: &Alias) -> Result<Vec<CommandDataOption>, AliasError> {
+        if alias.command != self.name {
+            return Err(AliasError::UnknownCommand(alias.command.clone()));
+        }
+
+        let Data::Slash { ref trie, .. } = self.data else {
+            return Err(AliasError::NotSlash(self.name.clone()));
+        };
+
+        let mut node = trie;
+        for sub in &alias.path {
+            let Trie::Branch { children, .. } = node else {
+                return Err(AliasError::UnknownSubcommand(sub.clone()));
+            };
+
+            node = &children
+                .get(sub)
+                .ok_or_else(|| AliasError::UnknownSubcommand(sub.clone()))?
+                .node;
+        }
+
+        let Trie::Leaf { args, arg_order } = node else {
+            return Err(AliasError::MissingSubcommand);
+        };
+
+        let mut opts = alias
+            .args
+            .iter()
+            .map(|(name, value)| {
+                let arg = args
+                    .get(name)
+                    .ok_or_else(|| AliasError::UnknownArg(name.clone()))?;
+                let (ty, value) = arg_value(name, value, &arg.ty)?;
+                Ok(json!({ "name": name, "type": ty, "value": value }))
+            })
+            .collect::<Result<Vec<_>, AliasError>>()?;
+
+        if let Some(missing) = arg_order
+            .iter()
+            .find(|a| args[*a].required && !alias.args.iter().any(|(n, _)| n == *a))
+        {
+            return Err(AliasError::MissingArg(missing.clone()));
+        }
+
+        // Wrap the arguments in their subcommand, then any subcommand group
+        for (i, sub) in alias.path.iter().enumerate().rev() {
+            let ty = if i + 1 == alias.path.len() {
+                CommandOptionType::SubCommand
+            } else {
+                CommandOptionType::SubCommandGroup
+            };
+            opts = vec![json!({ "name": sub, "type": ty, "options": opts })];
+        }
+
+        opts.into_iter()
+            .map(|o| {
+                serde_json::from_value(o).map_err(|e| AliasError::Backend(anyhow::Error::new(e)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serenity::model::application::CommandDataOptionValue;
+
+    use super::{Alias, AliasError};
+    use crate::interaction::command::CommandInfo;
+
+    fn info() -> CommandInfo {
+        CommandInfo::build_slash("jpeg", "JPEG an image", |a| {
+            a.build_subcmd("url", "JPEG an image from a link", |a| {
+                a.string("url", "The link", true, 1..=100)
+                    .int("quality", "The quality", false, 1..=100)
+                    .bool("strip", "Strip metadata", false)
+            })
+            .build_subcmd("last", "JPEG the last image", |a| {
+                a.int("quality", "The quality", false, 1..=100)
+            })
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let alias: Alias = "jpeg last quality:5".parse().unwrap();
+        assert_eq!(alias.command(), "jpeg");
+        assert_eq!(alias.to_string(), "jpeg last quality:5");
+
+        assert!("".parse::<Alias>().is_err());
+        assert!("jpeg quality:5 last".parse::<Alias>().is_err());
+        assert!("jpeg quality:".parse::<Alias>().is_err());
+    }
+
+    #[test]
+    fn expand() {
+        let info = info();
+        let opts = info
+            .expand_alias(&"jpeg last quality:5".parse().unwrap())
+            .unwrap();
+        assert_eq!(opts.len(), 1);
+        assert_eq!(opts[0].name, "last");
+        let CommandDataOptionValue::SubCommand(ref args) = opts[0].value else {
+            panic!("Expected a subcommand, got {:?}", opts[0].value);
+        };
+        assert_eq!(args[0].name, "quality");
+        assert_eq!(args[0].value, CommandDataOptionValue::Integer(5));
+
+        let opts = info
+            .expand_alias(&"jpeg url url:x strip:true".parse().unwrap())
+            .unwrap();
+        let CommandDataOptionValue::SubCommand(ref args) = opts[0].value else {
+            panic!("Expected a subcommand, got {:?}", opts[0].value);
+        };
+        assert_eq!(args[1].value, CommandDataOptionValue::Boolean(true));
+
+        let err = |s: &str| info.expand_alias(&s.parse().unwrap()).unwrap_err();
+        assert!(matches!(err("jpg last"), AliasError::UnknownCommand(_)));
+        assert!(matches!(err("jpeg"), AliasError::MissingSubcommand));
+        assert!(matches!(err("jpeg next"), AliasError::UnknownSubcommand(_)));
+        assert!(matches!(err("jpeg url"), AliasError::MissingArg(_)));
+        assert!(matches!(err("jpeg last size:5"), AliasError::UnknownArg(_)));
+        assert!(matches!(
+            err("jpeg last quality:500"),
+            AliasError::BadValue { .. }
+        ));
+        assert!(matches!(
+            err("jpeg url url:x strip:maybe"),
+            AliasError::BadValue { .. }
+        ));
+    }
+}
@@ -13,7 +13,7 @@ use serenity::{
 use super::{try_from_value::TryFromValue, TryFromError};
 
 /// Metadata for a chat input command parameter
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Arg {
     pub(super) desc: String,
     pub(super) required: bool,
@@ -126,7 +126,7 @@ impl Arg {
 }
 
 /// Metadata describing the type of a chat input command parameter
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ArgType {
     /// A freeform string
     String {
@@ -289,7 +289,7 @@ type Choices<T> = BTreeSet<Choice<T>>;
 
 /// Metadata for an option for one of the `...Choice` [parameter types](ArgType)
 // TODO: are choices unique on name, value, both, or neither?
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Choice<T> {
     pub(super) name: String,
     pub(super) val: T,
@@ -9,16 +9,79 @@ use super::{Arg, ArgType, Args, Choice, Subcommand, Trie, TryFromError};
 // TODO: sort through all imports
 
 /// Helper for constructing chat input command parameters and/or subcommands
+///
+/// Builders are normally used through a [`TypedArgBuilder`], which tracks
+/// whether parameters or subcommands have been added and rejects mixing the
+/// two at compile time:
+///
+/// ```
+/// # use paracord::interaction::command::{prelude::*, CommandInfo};
+/// CommandInfo::build_slash("roll", "Roll some dice", |a| {
+///     a.string("dice", "Dice to roll", true, ..)
+///         .bool("private", "Only show the result to you", false)
+///         .autocomplete(true, ["dice"])
+/// })
+/// .unwrap();
+/// ```
+///
+/// Parameters cannot be added after subcommands:
+///
+/// ```compile_fail
+/// # use paracord::interaction::command::{prelude::*, CommandInfo};
+/// CommandInfo::build_slash("roll", "Roll some dice", |a| {
+///     a.build_subcmd("d6", "Roll a six-sided die", |a| a)
+///         .bool("private", "Only show the result to you", false)
+/// });
+/// ```
+///
+/// Subcommands cannot be added after parameters:
+///
+/// ```compile_fail
+/// # use paracord::interaction::command::{prelude::*, CommandInfo};
+/// CommandInfo::build_slash("roll", "Roll some dice", |a| {
+///     a.string("dice", "Dice to roll", true, ..)
+///         .build_subcmd("d6", "Roll a six-sided die", |a| a)
+/// });
+/// ```
+///
+/// Autocompletion requires at least one parameter:
+///
+/// ```compile_fail
+/// # use paracord::interaction::command::{prelude::*, CommandInfo};
+/// CommandInfo::build_slash("roll", "Roll some dice", |a| {
+///     a.autocomplete(true, ["dice"])
+/// });
+/// ```
+///
+/// Errors that depend on the values given, such as duplicate names, are
+/// reported when the builder is converted to [`Args`].
 #[derive(Debug, Default)]
-pub struct ArgBuilder(ArgBuilderState);
+pub struct ArgBuilder {
+    args: Vec<(String, Arg)>,
+    subcmds: Vec<(String, String, Child)>,
+    autocomplete: Vec<(String, bool)>,
+}
 
-#[derive(Debug, Default)]
-enum ArgBuilderState {
-    #[default]
-    Default,
-    Leaf(BTreeMap<String, Arg>, Vec<String>),
-    Branch(NonZeroU8, BTreeMap<String, Subcommand>),
-    Error(&'static str),
+/// Marker types for the states of a [`TypedArgBuilder`]
+pub mod arg_state {
+    /// No parameters or subcommands have been added
+    #[derive(Debug, Clone, Copy)]
+    pub struct Empty;
+
+    /// At least one parameter has been added
+    #[derive(Debug, Clone, Copy)]
+    pub struct Leaf;
+
+    /// At least one subcommand has been added
+    #[derive(Debug, Clone, Copy)]
+    pub struct Branch;
+}
+
+/// A subcommand, either already validated or still to be built
+#[derive(Debug)]
+enum Child {
+    Args(Args),
+    Builder(ArgBuilder),
 }
 
 impl ArgBuilder {
@@ -34,77 +97,82 @@ impl ArgBuilder {
         self.arg(name, Arg { desc, required, ty });
     }
 
-    fn insert_subcommand(&mut self, name: impl Into<String>, cmd: Subcommand) {
-        let name = name.into();
+    fn build_leaf(
+        args: Vec<(String, Arg)>,
+        autocomplete: Vec<(String, bool)>,
+    ) -> Result<Trie, TryFromError> {
+        let mut map = BTreeMap::new();
+        let mut arg_order = Vec::with_capacity(args.len());
 
-        let height = match &mut self.0 {
-            s @ ArgBuilderState::Default => {
-                *s = ArgBuilderState::Branch(
-                    NonZeroU8::new(1).unwrap_or_else(|| unreachable!()),
-                    [(name, cmd)].into_iter().collect(),
-                );
-                1
-            },
-            s @ ArgBuilderState::Leaf(..) => {
-                *s = ArgBuilderState::Error("Attempted to add subcommand after adding arguments");
-                return;
-            },
-            ArgBuilderState::Branch(h, c) => {
-                *h = (*h).max(
-                    cmd.node
-                        .height()
-                        .checked_add(1)
-                        .and_then(NonZeroU8::new)
-                        .unwrap_or_else(|| unreachable!()),
-                );
-
-                if c.insert(name, cmd).is_some() {
-                    self.0 = ArgBuilderState::Error("Duplicate subcommand name added");
-                    return;
-                }
+        for (name, arg) in args {
+            if map.insert(name.clone(), arg).is_some() {
+                return Err(TryFromError("Duplicate argument name added"));
+            }
+            arg_order.push(name);
+        }
 
-                (*h).into()
-            },
-            ArgBuilderState::Error(_) => return,
-        };
+        for (key, enable) in autocomplete {
+            let Some(Arg {
+                ty:
+                    ArgType::String { autocomplete, .. }
+                    | ArgType::Int { autocomplete, .. }
+                    | ArgType::Real { autocomplete, .. },
+                ..
+            }) = map.get_mut(&key)
+            else {
+                return Err(TryFromError("Invalid argument for autocomplete"));
+            };
+
+            *autocomplete = enable;
+        }
+
+        Ok(Trie::Leaf {
+            args: map,
+            arg_order,
+        })
+    }
+
+    fn build_branch(subcmds: Vec<(String, String, Child)>) -> Result<Trie, TryFromError> {
+        let mut height = 1;
+        let mut children = BTreeMap::new();
+
+        for (name, desc, child) in subcmds {
+            let Args(node) = match child {
+                Child::Args(a) => a,
+                Child::Builder(b) => b.try_into()?,
+            };
 
-        if height > 2 {
-            self.0 = ArgBuilderState::Error("Maximum subcommand nesting depth exceeded");
+            height = height.max(node.height() + 1);
+            if height > 2 {
+                return Err(TryFromError("Maximum subcommand nesting depth exceeded"));
+            }
+
+            if children.insert(name, Subcommand { desc, node }).is_some() {
+                return Err(TryFromError("Duplicate subcommand name added"));
+            }
         }
+
+        let height = NonZeroU8::new(height).unwrap_or_else(|| unreachable!());
+        Ok(Trie::Branch { height, children })
     }
 }
 
-#[builder(trait_name = ArgBuilderExt)]
+#[builder(trait_name = ArgBuilderExt, typestate = TypedArgBuilder)]
 /// Helper methods for mutating [`ArgBuilder`]
 impl ArgBuilder {
     /// Add a new parameter to this (sub)command
     ///
-    /// **NOTE:** The builder state will become invalid if this (sub)command has
-    /// child subcommands already registered.
+    /// **NOTE:** Converting the builder will fail if this (sub)command also
+    /// has child subcommands.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn arg(&mut self, name: impl Into<String>, arg: Arg) {
-        let name = name.into();
-        match &mut self.0 {
-            s @ ArgBuilderState::Default => {
-                *s = ArgBuilderState::Leaf([(name.clone(), arg)].into_iter().collect(), vec![name]);
-            },
-            ArgBuilderState::Leaf(m, v) => {
-                if m.insert(name.clone(), arg).is_some() {
-                    self.0 = ArgBuilderState::Error("Duplicate argument name added");
-                    return;
-                }
-                v.push(name);
-            },
-            s @ ArgBuilderState::Branch(..) => {
-                *s = ArgBuilderState::Error("Attempted to add argument after adding subcommands");
-                return;
-            },
-            ArgBuilderState::Error(_) => (),
-        }
+        self.args.push((name.into(), arg));
     }
 
     /// Add a new string parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn string(
         &mut self,
         name: impl Into<String>,
@@ -123,6 +191,7 @@ impl ArgBuilder {
     /// Add a new string choice parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn string_choice<C: IntoIterator>(
         &mut self,
         name: impl Into<String>,
@@ -139,6 +208,7 @@ impl ArgBuilder {
     /// Add a new integer parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn int(
         &mut self,
         name: impl Into<String>,
@@ -157,6 +227,7 @@ impl ArgBuilder {
     /// Add a new integer choice parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn int_choice<C: IntoIterator>(
         &mut self,
         name: impl Into<String>,
@@ -173,6 +244,7 @@ impl ArgBuilder {
     /// Add a new Boolean parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn bool(&mut self, name: impl Into<String>, desc: impl Into<String>, required: bool) {
         self.arg_parts(name, desc, required, ArgType::Bool);
     }
@@ -180,6 +252,7 @@ impl ArgBuilder {
     /// Add a new user handle parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn user(&mut self, name: impl Into<String>, desc: impl Into<String>, required: bool) {
         self.arg_parts(name, desc, required, ArgType::User);
     }
@@ -187,6 +260,7 @@ impl ArgBuilder {
     /// Add a new channel handle parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn channel(
         &mut self,
         name: impl Into<String>,
@@ -201,6 +275,7 @@ impl ArgBuilder {
     /// Add a new role handle parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn role(&mut self, name: impl Into<String>, desc: impl Into<String>, required: bool) {
         self.arg_parts(name, desc, required, ArgType::Role);
     }
@@ -208,6 +283,7 @@ impl ArgBuilder {
     /// Add a new user or role handle parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn mention(&mut self, name: impl Into<String>, desc: impl Into<String>, required: bool) {
         self.arg_parts(name, desc, required, ArgType::Mention);
     }
@@ -215,33 +291,37 @@ impl ArgBuilder {
     /// Add a new real (decimal) numeric parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    ///
+    /// # Errors
+    /// This method returns an error if either bound of `range` is NaN.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn real(
         &mut self,
         name: impl Into<String>,
         desc: impl Into<String>,
         required: bool,
         range: impl BuildRange<f64>,
-    ) {
+    ) -> Result<(), TryFromError> {
         let (min, max) = range.build_range().into_inner();
-        let Ok((min, max)) = min
-            .map(NotNan::new)
-            .transpose()
-            .and_then(|r| max.map(NotNan::new).transpose().map(|s| (r, s)))
-        else {
-            self.0 = ArgBuilderState::Error("NaN given for real argument bound");
-            return;
+        let bound = |b: Option<f64>| {
+            b.map(NotNan::new)
+                .transpose()
+                .map_err(|_| TryFromError("NaN given for real argument bound"))
         };
+        let (min, max) = (bound(min)?, bound(max)?);
 
         self.arg_parts(name, desc, required, ArgType::Real {
             autocomplete: false,
             min,
             max,
         });
+        Ok(())
     }
 
     /// Add a new real (decimal) numeric choice parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn real_choice<C: IntoIterator>(
         &mut self,
         name: impl Into<String>,
@@ -268,73 +348,55 @@ impl ArgBuilder {
     /// Add a new file upload parameter to this (sub)command
     ///
     /// See [`arg`](Self::arg) for more details.
+    #[typestate(arg_state::Empty | arg_state::Leaf => arg_state::Leaf)]
     pub fn attachment(&mut self, name: impl Into<String>, desc: impl Into<String>, required: bool) {
         self.arg_parts(name, desc, required, ArgType::Attachment);
     }
 
     /// Set whether the named parameters should send autocomplete interactions
-    pub fn autocomplete<'a, Q: Ord + ?Sized + 'a>(
-        &mut self,
-        enable: bool,
-        keys: impl IntoIterator<Item = &'a Q>,
-    ) where
-        String: std::borrow::Borrow<Q>,
-    {
-        match &mut self.0 {
-            s @ ArgBuilderState::Default => {
-                *s = ArgBuilderState::Error("No argument present to set autocomplete");
-            },
-            ArgBuilderState::Leaf(m, _) => {
-                for key in keys {
-                    if let Some(Arg {
-                        ty:
-                            ArgType::String { autocomplete, .. }
-                            | ArgType::Int { autocomplete, .. }
-                            | ArgType::Real { autocomplete, .. },
-                        ..
-                    }) = m.get_mut(key)
-                    {
-                        *autocomplete = enable;
-                    } else {
-                        self.0 = ArgBuilderState::Error("Invalid argument for autocomplete");
-                        return;
-                    }
-                }
-            },
-            s @ ArgBuilderState::Branch(..) => {
-                *s = ArgBuilderState::Error("Cannot set autocomplete on a subcommand");
-            },
-            ArgBuilderState::Error(_) => (),
-        }
+    ///
+    /// **NOTE:** Converting the builder will fail if any of the keys do not
+    /// name a string, integer or real parameter.
+    #[typestate(arg_state::Leaf => arg_state::Leaf)]
+    pub fn autocomplete<'a>(&mut self, enable: bool, keys: impl IntoIterator<Item = &'a str>) {
+        self.autocomplete
+            .extend(keys.into_iter().map(|k| (k.to_owned(), enable)));
     }
 
     /// Add a new subcommand to this (sub)command
     ///
-    /// **NOTE:** The builder state will become invalid if this (sub)command has
-    /// parameters already registered.
+    /// **NOTE:** Converting the builder will fail if this (sub)command also
+    /// has parameters.
+    #[typestate(arg_state::Empty | arg_state::Branch => arg_state::Branch)]
     pub fn subcmd(&mut self, name: impl Into<String>, desc: impl Into<String>, args: Args) {
-        let name = name.into();
-        let desc = desc.into();
-        let Args(node) = args;
-        self.insert_subcommand(name, Subcommand { desc, node });
+        self.subcmds
+            .push((name.into(), desc.into(), Child::Args(args)));
     }
 
     /// Add a new subcommand to this (sub)command using the given closure
     ///
     /// See [`subcmd`](Self::subcmd) for more details.
     #[inline]
-    pub fn build_subcmd(
+    #[typestate(arg_state::Empty | arg_state::Branch => arg_state::Branch)]
+    pub fn build_subcmd<S>(
         &mut self,
         name: impl Into<String>,
         desc: impl Into<String>,
-        f: impl FnOnce(ArgBuilder) -> ArgBuilder,
+        f: impl FnOnce(TypedArgBuilder<arg_state::Empty>) -> TypedArgBuilder<S>,
     ) {
-        match f(ArgBuilder::default()).try_into() {
-            Ok(a) => {
-                self.subcmd(name, desc, a);
-            },
-            Err(TryFromError(e)) => self.0 = ArgBuilderState::Error(e),
-        }
+        let child = f(ArgBuilder::typed()).into_inner();
+        self.subcmds
+            .push((name.into(), desc.into(), Child::Builder(child)));
+    }
+}
+
+impl ArgBuilder {
+    /// Construct a new empty builder which rejects invalid sequences of
+    /// parameters and subcommands at compile time
+    #[inline]
+    #[must_use]
+    pub fn typed() -> TypedArgBuilder<arg_state::Empty> {
+        TypedArgBuilder::new_unchecked(Self::default())
     }
 }
 
@@ -342,11 +404,150 @@ impl TryFrom<ArgBuilder> for Args {
     type Error = TryFromError;
 
     fn try_from(value: ArgBuilder) -> Result<Self, Self::Error> {
-        Ok(Self(match value.0 {
-            ArgBuilderState::Default => Trie::default(),
-            ArgBuilderState::Leaf(args, arg_order) => Trie::Leaf { args, arg_order },
-            ArgBuilderState::Branch(height, children) => Trie::Branch { height, children },
-            ArgBuilderState::Error(e) => return Err(TryFromError(e)),
+        let ArgBuilder {
+            args,
+            subcmds,
+            autocomplete,
+        } = value;
+
+        Ok(Self(match (args.is_empty(), subcmds.is_empty()) {
+            (true, true) if autocomplete.is_empty() => Trie::default(),
+            (true, true) => return Err(TryFromError("No argument present to set autocomplete")),
+            (_, true) => ArgBuilder::build_leaf(args, autocomplete)?,
+            (true, false) if autocomplete.is_empty() => ArgBuilder::build_branch(subcmds)?,
+            (true, false) => return Err(TryFromError("Cannot set autocomplete on a subcommand")),
+            (false, false) => {
+                return Err(TryFromError(
+                    "Cannot add both arguments and subcommands to a command",
+                ))
+            },
         }))
     }
 }
+
+impl<S> TryFrom<TypedArgBuilder<S>> for Args {
+    type Error = TryFromError;
+
+    #[inline]
+    fn try_from(value: TypedArgBuilder<S>) -> Result<Self, Self::Error> {
+        value.into_inner().try_into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        arg_state, Arg, ArgBuilder, ArgBuilderExt, ArgType, Args, Trie, TryFromError,
+        TypedArgBuilder,
+    };
+
+    fn build<S>(
+        f: impl FnOnce(TypedArgBuilder<arg_state::Empty>) -> TypedArgBuilder<S>,
+    ) -> Result<Args, TryFromError> {
+        f(ArgBuilder::typed()).try_into()
+    }
+
+    fn err<S>(
+        f: impl FnOnce(TypedArgBuilder<arg_state::Empty>) -> TypedArgBuilder<S>,
+    ) -> &'static str {
+        build(f).unwrap_err().0
+    }
+
+    #[test]
+    fn leaf() {
+        let Args(Trie::Leaf { args, arg_order }) = build(|a| {
+            a.string("b", "B", true, ..)
+                .bool("a", "A", false)
+                .autocomplete(true, ["b"])
+        })
+        .unwrap() else {
+            panic!("Expected a leaf");
+        };
+
+        assert_eq!(arg_order, ["b", "a"]);
+        assert!(matches!(args["b"], Arg {
+            ty: ArgType::String {
+                autocomplete: true,
+                ..
+            },
+            ..
+        }));
+
+        assert_eq!(
+            err(|a| a.bool("a", "A", false).bool("a", "A", true)),
+            "Duplicate argument name added",
+        );
+        assert_eq!(
+            err(|a| a.bool("a", "A", false).autocomplete(true, ["a"])),
+            "Invalid argument for autocomplete",
+        );
+        assert_eq!(
+            err(|a| a.bool("a", "A", false).autocomplete(true, ["b"])),
+            "Invalid argument for autocomplete",
+        );
+    }
+
+    #[test]
+    fn fallible_setter() {
+        assert_eq!(
+            ArgBuilder::typed()
+                .real("x", "X", false, f64::NAN..)
+                .unwrap_err()
+                .0,
+            "NaN given for real argument bound",
+        );
+
+        let a = ArgBuilder::typed().real("x", "X", false, 0.0..=1.0).unwrap();
+        assert!(Args::try_from(a).is_ok());
+    }
+
+    #[test]
+    fn branch() {
+        let Args(Trie::Branch { height, children }) =
+            build(|a| a.build_subcmd("a", "A", |a| a).build_subcmd("b", "B", |a| a)).unwrap()
+        else {
+            panic!("Expected a branch");
+        };
+
+        assert_eq!(height.get(), 1);
+        assert_eq!(children.keys().collect::<Vec<_>>(), ["a", "b"]);
+
+        let Args(Trie::Branch { height, .. }) = build(|a| {
+            a.build_subcmd("group", "Group", |a| a.build_subcmd("a", "A", |a| a))
+        })
+        .unwrap() else {
+            panic!("Expected a branch");
+        };
+        assert_eq!(height.get(), 2);
+
+        assert_eq!(
+            err(|a| a.build_subcmd("a", "A", |a| a).build_subcmd("a", "A", |a| a)),
+            "Duplicate subcommand name added",
+        );
+        assert_eq!(
+            err(|a| {
+                a.build_subcmd("x", "X", |a| {
+                    a.build_subcmd("y", "Y", |a| a.build_subcmd("z", "Z", |a| a))
+                })
+            }),
+            "Maximum subcommand nesting depth exceeded",
+        );
+    }
+
+    #[test]
+    fn untyped() {
+        let mixed = ArgBuilder::default()
+            .bool("a", "A", false)
+            .subcmd("b", "B", Args::default());
+        assert_eq!(
+            Args::try_from(mixed).unwrap_err().0,
+            "Cannot add both arguments and subcommands to a command",
+        );
+
+        let orphan = ArgBuilder::default().autocomplete(true, ["a"]);
+        assert_eq!(
+            Args::try_from(orphan).unwrap_err().0,
+            "No argument present to set autocomplete",
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::BTreeMap, fmt};
+
+use super::{Arg, CommandInfo, Data, Subcommand, Trie};
+
+/// A single field that differs between two command descriptors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Dotted path to the changed field
+    pub path: String,
+    /// The old value of the field, or `None` if it was added
+    pub old: Option<String>,
+    /// The new value of the field, or `None` if it was removed
+    pub new: Option<String>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { path, old, new } = self;
+        match (old, new) {
+            (Some(old), Some(new)) => write!(f, "{path}: {old} -> {new}"),
+            (Some(old), None) => write!(f, "-{path}: {old}"),
+            (None, Some(new)) => write!(f, "+{path}: {new}"),
+            (None, None) => write!(f, "{path}"),
+        }
+    }
+}
+
+/// Compute the list of fields that differ between two command descriptors
+#[inline]
+#[must_use]
+pub fn diff(l: &CommandInfo, r: &CommandInfo) -> Vec<Change> {
+    let mut out = vec![];
+    l.diff(r, "", &mut out);
+    out
+}
+
+#[inline]
+pub(super) fn join(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.into()
+    } else {
+        format!("{path}.{field}")
+    }
+}
+
+fn leaf<T: fmt::Debug + PartialEq + ?Sized>(l: &T, r: &T, path: String, out: &mut Vec<Change>) {
+    if l != r {
+        out.push(Change {
+            path,
+            old: Some(format!("{l:?}")),
+            new: Some(format!("{r:?}")),
+        });
+    }
+}
+
+trait Diff {
+    fn diff(&self, rhs: &Self, path: &str, out: &mut Vec<Change>);
+}
+
+impl<V: Diff + fmt::Debug> Diff for BTreeMap<String, V> {
+    fn diff(&self, rhs: &Self, path: &str, out: &mut Vec<Change>) {
+        for (key, l) in self {
+            if let Some(r) = rhs.get(key) {
+                l.diff(r, &join(path, key), out);
+            } else {
+                out.push(Change {
+                    path: join(path, key),
+                    old: Some(format!("{l:?}")),
+                    new: None,
+                });
+            }
+        }
+
+        for (key, r) in rhs {
+            if !self.contains_key(key) {
+                out.push(Change {
+                    path: join(path, key),
+                    old: None,
+                    new: Some(format!("{r:?}")),
+                });
+            }
+        }
+    }
+}
+
+impl Diff for CommandInfo {
+    fn diff(&self, rhs: &Self, path: &str, out: &mut Vec<Change>) {
+        let Self {
+            name: l_name,
+            can_dm: l_dm,
+            integration_types: l_types,
+            contexts: l_ctx,
+            data: l_data,
+        } = self;
+        let Self {
+            name: r_name,
+            can_dm: r_dm,
+            integration_types: r_types,
+            contexts: r_ctx,
+            data: r_data,
+        } = rhs;
+
+        leaf(l_name, r_name, join(path, "name"), out);
+        leaf(l_dm, r_dm, join(path, "can_dm"), out);
+        leaf(l_types, r_types, join(path, "integration_types"), out);
+        leaf(l_ctx, r_ctx, join(path, "contexts"), out);
+        l_data.diff(r_data, path, out);
+    }
+}
+
+impl Data {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Slash { .. } => "slash",
+            Self::User => "user",
+            Self::Message => "message",
+        }
+    }
+}
+
+impl Diff for Data {
+    fn diff(&self, rhs: &Self, path: &str, out: &mut Vec<Change>) {
+        match (self, rhs) {
+            (
+                Self::Slash {
+                    desc: l_desc,
+                    trie: l_trie,
+                },
+                Self::Slash {
+                    desc: r_desc,
+                    trie: r_trie,
+                },
+            ) => {
+                leaf(l_desc, r_desc, join(path, "desc"), out);
+                l_trie.diff(r_trie, &join(path, "opts"), out);
+            },
+            (l, r) => leaf(l.kind(), r.kind(), join(path, "kind"), out),
+        }
+    }
+}
+
+impl Diff for Trie {
+    fn diff(&self, rhs: &Self, path: &str, out: &mut Vec<Change>) {
+        match (self, rhs) {
+            (
+                Self::Branch {
+                    height: l_height,
+                    children: l_chld,
+                },
+                Self::Branch {
+                    height: r_height,
+                    children: r_chld,
+                },
+            ) => {
+                leaf(l_height, r_height, join(path, "height"), out);
+                l_chld.diff(r_chld, path, out);
+            },
+            (
+                Self::Leaf {
+                    args: l_args,
+                    arg_order: l_order,
+                },
+                Self::Leaf {
+                    args: r_args,
+                    arg_order: r_order,
+                },
+            ) => {
+                l_args.diff(r_args, path, out);
+                leaf(l_order, r_order, join(path, "order"), out);
+            },
+            (l, r) => leaf(l, r, path.into(), out),
+        }
+    }
+}
+
+impl Diff for Subcommand {
+    fn diff(&self, rhs: &Self, path: &str, out: &mut Vec<Change>) {
+        let Self {
+            desc: l_desc,
+            node: l_node,
+        } = self;
+        let Self {
+            desc: r_desc,
+            node: r_node,
+        } = rhs;
+
+        leaf(l_desc, r_desc, join(path, "desc"), out);
+        l_node.diff(r_node, path, out);
+    }
+}
+
+impl Diff for Arg {
+    fn diff(&self, rhs: &Self, path: &str, out: &mut Vec<Change>) {
+        let Self {
+            desc: l_desc,
+            required: l_req,
+            ty: l_ty,
+        } = self;
+        let Self {
+            desc: r_desc,
+            required: r_req,
+            ty: r_ty,
+        } = rhs;
+
+        leaf(l_desc, r_desc, join(path, "desc"), out);
+        leaf(l_req, r_req, join(path, "required"), out);
+        leaf(l_ty, r_ty, join(path, "type"), out);
+    }
+}
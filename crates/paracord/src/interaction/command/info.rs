@@ -1,22 +1,40 @@
-use std::{collections::BTreeMap, num::NonZeroU8};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::NonZeroU8,
+};
 
 use qcore::builder;
 use serenity::{
     builder::{CreateCommand, CreateCommandOption},
-    model::application::{CommandOption, CommandOptionType, CommandType},
+    model::application::{
+        CommandOption, CommandOptionType, CommandType, InstallationContext, InteractionContext,
+    },
 };
 
-use super::{Arg, ArgBuilder, ArgType, TryFromError};
+use super::{arg_state, Arg, ArgBuilder, ArgType, TryFromError, TypedArgBuilder};
 
 /// Metadata for an application command
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CommandInfo {
     pub(super) name: String,
     pub(super) can_dm: bool,
+    pub(super) integration_types: BTreeSet<InstallationContext>,
+    pub(super) contexts: Option<BTreeSet<InteractionContext>>,
     pub(super) data: Data,
 }
 
 impl CommandInfo {
+    #[inline]
+    fn new(name: String, data: Data) -> Self {
+        Self {
+            name,
+            can_dm: true,
+            integration_types: [InstallationContext::Guild].into(),
+            contexts: None,
+            data,
+        }
+    }
+
     /// Construct a new description of a chat input command
     // TODO: do descriptions support markdown?
     #[inline]
@@ -24,11 +42,7 @@ impl CommandInfo {
         let name = name.into();
         let desc = desc.into();
         let Args(trie) = args;
-        Self {
-            name,
-            data: Data::Slash { desc, trie },
-            can_dm: true,
-        }
+        Self::new(name, Data::Slash { desc, trie })
     }
 
     /// Construct a new description of a chat input command using the given
@@ -38,45 +52,98 @@ impl CommandInfo {
     /// This method returns an error if invoking the closure results in an
     /// [`ArgBuilder`] with an invalid state.
     #[inline]
-    pub fn build_slash(
+    pub fn build_slash<S>(
         name: impl Into<String>,
         desc: impl Into<String>,
-        // TODO: can these be removed from the API surface?
-        f: impl FnOnce(ArgBuilder) -> ArgBuilder,
+        f: impl FnOnce(TypedArgBuilder<arg_state::Empty>) -> TypedArgBuilder<S>,
     ) -> Result<Self, TryFromError> {
-        Ok(Self::slash(
-            name,
-            desc,
-            f(ArgBuilder::default()).try_into()?,
-        ))
+        Ok(Self::slash(name, desc, f(ArgBuilder::typed()).try_into()?))
     }
 
     /// Construct a new description of a user context menu command
     #[inline]
-    pub fn user(name: impl Into<String>) -> Self {
-        let name = name.into();
-        Self {
-            name,
-            data: Data::User,
-            can_dm: true,
-        }
-    }
+    pub fn user(name: impl Into<String>) -> Self { Self::new(name.into(), Data::User) }
 
     /// Construct a new description of a message context menu command
     #[inline]
-    pub fn message(name: impl Into<String>) -> Self {
-        let name = name.into();
-        Self {
-            name,
-            data: Data::Message,
-            can_dm: true,
-        }
-    }
+    pub fn message(name: impl Into<String>) -> Self { Self::new(name.into(), Data::Message) }
 
     /// Get the unique, non-localized name of this command
     #[inline]
     #[must_use]
     pub fn name(&self) -> &String { &self.name }
+
+    /// Get the type of this command
+    ///
+    /// Command names only need to be unique among commands of the same type.
+    #[inline]
+    #[must_use]
+    pub fn kind(&self) -> CommandType {
+        match self.data {
+            Data::Slash { .. } => CommandType::ChatInput,
+            Data::User => CommandType::User,
+            Data::Message => CommandType::Message,
+        }
+    }
+
+    /// Prepend the given string to the name of this command
+    #[inline]
+    pub(in super::super) fn prefix_name(&mut self, prefix: &str) {
+        self.name.insert_str(0, prefix);
+    }
+
+    /// Construct a new description of a chat input command with each of the
+    /// given chat input commands as one of its subcommands
+    ///
+    /// The resulting command is only usable in DMs if all of its subcommands
+    /// are.
+    pub(in super::super) fn nest(
+        name: impl Into<String>,
+        desc: impl Into<String>,
+        commands: impl IntoIterator<Item = CommandInfo>,
+    ) -> Result<Self, TryFromError> {
+        let mut can_dm = true;
+        let mut height = 1;
+        let mut children = BTreeMap::new();
+
+        for cmd in commands {
+            let CommandInfo {
+                name,
+                can_dm: cmd_dm,
+                data,
+                ..
+            } = cmd;
+            let Data::Slash { desc, trie } = data else {
+                return Err(TryFromError("Only chat input commands can be nested"));
+            };
+
+            height = height.max(trie.height() + 1);
+            if height > 2 {
+                return Err(TryFromError("Maximum subcommand nesting depth exceeded"));
+            }
+
+            can_dm &= cmd_dm;
+
+            if children
+                .insert(name, Subcommand { desc, node: trie })
+                .is_some()
+            {
+                return Err(TryFromError("Duplicate subcommand name added"));
+            }
+        }
+
+        if children.is_empty() {
+            return Err(TryFromError("Attempted to nest an empty set of commands"));
+        }
+
+        let height = NonZeroU8::new(height).unwrap_or_else(|| unreachable!());
+        let mut info = Self::new(name.into(), Data::Slash {
+            desc: desc.into(),
+            trie: Trie::Branch { height, children },
+        });
+        info.can_dm = can_dm;
+        Ok(info)
+    }
 }
 
 #[builder(trait_name = CommandInfoExt)]
@@ -85,12 +152,51 @@ impl CommandInfo {
     /// Set whether this command should be usable in DM (i.e. non-guild)
     /// channels
     pub fn can_dm(&mut self, can_dm: bool) { self.can_dm = can_dm; }
+
+    /// Set the installation types through which this command is available
+    ///
+    /// By default commands are only available when the app is installed to a
+    /// guild.
+    pub fn integration_types(&mut self, types: impl IntoIterator<Item = InstallationContext>) {
+        self.integration_types = types.into_iter().collect();
+    }
+
+    /// Set the contexts in which this command can be invoked
+    ///
+    /// If this is never called, Discord infers the contexts from the installation
+    /// types and [`can_dm`](Self::can_dm).
+    pub fn contexts(&mut self, contexts: impl IntoIterator<Item = InteractionContext>) {
+        self.contexts = Some(contexts.into_iter().collect());
+    }
+
+    /// Make this command available to users who install the app to their
+    /// account, in any guild or DM they can use it from
+    pub fn user_installable(&mut self) {
+        self.integration_types([InstallationContext::Guild, InstallationContext::User]);
+        self.contexts([
+            InteractionContext::Guild,
+            InteractionContext::BotDm,
+            InteractionContext::PrivateChannel,
+        ]);
+    }
 }
 
 impl From<CommandInfo> for CreateCommand {
     fn from(value: CommandInfo) -> Self {
-        let CommandInfo { name, can_dm, data } = value;
-        let cmd = Self::new(name).dm_permission(can_dm);
+        let CommandInfo {
+            name,
+            can_dm,
+            integration_types,
+            contexts,
+            data,
+        } = value;
+        let mut cmd = Self::new(name)
+            .dm_permission(can_dm)
+            .integration_types(integration_types.into_iter().collect());
+
+        if let Some(contexts) = contexts {
+            cmd = cmd.contexts(contexts.into_iter().collect());
+        }
 
         match data {
             Data::Slash { desc, trie } => {
@@ -124,7 +230,7 @@ impl From<CommandInfo> for CreateCommand {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(super) enum Data {
     Slash { desc: String, trie: Trie },
     User,
@@ -135,7 +241,7 @@ pub(super) enum Data {
 #[derive(Debug, Default)]
 pub struct Args(pub(super) Trie);
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(super) enum Trie {
     Branch {
         height: NonZeroU8,
@@ -284,7 +390,7 @@ impl Trie {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(super) struct Subcommand {
     pub(super) desc: String,
     pub(super) node: Trie,
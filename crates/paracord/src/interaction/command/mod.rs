@@ -1,18 +1,27 @@
 //! Types for constructing command descriptions to be registered or inspecting
 //! already-registered command metadata
 
+mod alias;
 mod arg;
 mod arg_builder;
+mod diff;
 mod info;
+mod plan;
 mod registered;
 mod sim;
+mod snapshot;
 mod try_from_value;
 
+pub use alias::{Alias, AliasError, AliasResolver};
 pub use arg::*;
 pub use arg_builder::*;
+pub use diff::*;
 pub use info::*;
+pub(super) use plan::pair;
+pub use plan::{CommandPlan, PlannedChange, SyncGate};
 pub(super) use registered::*;
 pub use sim::*;
+pub use snapshot::*;
 
 /// Helper traits for working with command metadata
 pub mod prelude {
@@ -0,0 +1,290 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap},
+    fmt,
+};
+
+use async_trait::async_trait;
+use ordered_float::OrderedFloat;
+use serenity::model::id::CommandId;
+
+use super::{diff, CommandInfo, RegisteredCommand, SimExplanation, SimWeights};
+use crate::client::Context;
+
+/// A single change needed to bring the registered commands in line with the
+/// command handlers
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlannedChange {
+    /// A new command will be registered
+    Create {
+        /// The command to register
+        new: CommandInfo,
+    },
+    /// An existing command will be edited to match a new one
+    Update {
+        /// The ID of the existing command
+        id: CommandId,
+        /// The existing command
+        old: CommandInfo,
+        /// The command it will be replaced with
+        new: CommandInfo,
+        /// Why the two commands were paired
+        sim: SimExplanation,
+    },
+    /// An existing command will be deleted
+    Delete {
+        /// The ID of the existing command
+        id: CommandId,
+        /// The existing command
+        old: CommandInfo,
+    },
+}
+
+impl fmt::Display for PlannedChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Create { new } => write!(f, "+ {:?}", new.name()),
+            Self::Update { id, old, new, sim } => {
+                write!(f, "~ {:?} (ID {id}): {sim}", new.name())?;
+                for change in diff(old, new) {
+                    write!(f, "\n    {change}")?;
+                }
+                Ok(())
+            },
+            Self::Delete { id, old } => write!(f, "- {:?} (ID {id})", old.name()),
+        }
+    }
+}
+
+/// The changes needed to bring the registered commands in line with the
+/// command handlers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandPlan {
+    /// The number of registered commands that already match a handler
+    pub unchanged: usize,
+    /// The changes to make, in the order they will be made
+    pub changes: Vec<PlannedChange>,
+}
+
+impl CommandPlan {
+    /// Returns true if no changes are needed
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.changes.is_empty() }
+}
+
+impl fmt::Display for CommandPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count =
+            |pred: fn(&PlannedChange) -> bool| self.changes.iter().filter(|c| pred(c)).count();
+
+        write!(
+            f,
+            "{} unchanged, {} updated, {} created, {} deleted",
+            self.unchanged,
+            count(|c| matches!(c, PlannedChange::Update { .. })),
+            count(|c| matches!(c, PlannedChange::Create { .. })),
+            count(|c| matches!(c, PlannedChange::Delete { .. })),
+        )?;
+
+        for change in &self.changes {
+            write!(f, "\n{change}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A check consulted before registered commands are deleted on startup
+///
+/// A build missing some of its handlers, for example from a misconfigured
+/// feature set, would otherwise unregister the corresponding commands as soon
+/// as it starts.  A gate can hold up the deletions until someone confirms the
+/// plan is intended.
+#[async_trait]
+pub trait SyncGate: fmt::Debug + Send + Sync {
+    /// Decide whether the deletions in the given plan may be made
+    ///
+    /// This is only called for plans containing at least one deletion, after
+    /// the plan's other changes have been made and its handlers are already
+    /// answering interactions.  If it returns false, the commands to be
+    /// deleted are left registered.
+    async fn approve(&self, ctx: &Context, plan: &CommandPlan) -> bool;
+}
+
+/// The result of pairing registered commands with the descriptors of the
+/// current command handlers, given by index
+#[derive(Debug, Default)]
+pub(in super::super) struct Pairing<'a> {
+    pub(in super::super) unchanged: Vec<(&'a RegisteredCommand, usize)>,
+    pub(in super::super) updates: Vec<(&'a RegisteredCommand, usize, SimExplanation)>,
+    pub(in super::super) creates: Vec<usize>,
+    pub(in super::super) deletes: Vec<&'a RegisteredCommand>,
+}
+
+impl Pairing<'_> {
+    /// Describe the changes this pairing calls for
+    pub(in super::super) fn plan(&self, new: &[CommandInfo]) -> CommandPlan {
+        let updates = self
+            .updates
+            .iter()
+            .map(|&(reg, i, sim)| PlannedChange::Update {
+                id: reg.id,
+                old: reg.info.clone(),
+                new: new[i].clone(),
+                sim,
+            });
+        let creates = self.creates.iter().map(|&i| PlannedChange::Create {
+            new: new[i].clone(),
+        });
+        let deletes = self.deletes.iter().map(|reg| PlannedChange::Delete {
+            id: reg.id,
+            old: reg.info.clone(),
+        });
+
+        CommandPlan {
+            unchanged: self.unchanged.len(),
+            changes: updates.chain(creates).chain(deletes).collect(),
+        }
+    }
+}
+
+/// Pair registered commands with new command descriptors
+///
+/// Identical commands are paired first, then the remaining commands are
+/// paired greedily from most to least similar.  Pairs scoring below
+/// `min_score` are not paired, and the commands involved are deleted and
+/// recreated instead.
+pub(in super::super) fn pair<'a>(
+    existing: &'a [RegisteredCommand],
+    new: &[CommandInfo],
+    weights: &SimWeights,
+    min_score: f64,
+) -> Pairing<'a> {
+    let mut out = Pairing::default();
+    let mut unpaired_existing: Vec<_> = existing.iter().map(Some).collect();
+    let mut unpaired_new = BTreeSet::new();
+
+    for (i, inf) in new.iter().enumerate() {
+        if let Some(slot) = unpaired_existing
+            .iter_mut()
+            .find(|e| e.is_some_and(|e| e.info == *inf))
+        {
+            out.unchanged
+                .push((slot.take().unwrap_or_else(|| unreachable!()), i));
+        } else {
+            unpaired_new.insert(i);
+        }
+    }
+
+    let mut sims: BinaryHeap<_> = unpaired_existing
+        .iter()
+        .enumerate()
+        .filter_map(|(e, reg)| Some((e, (*reg)?)))
+        .flat_map(|(e, reg)| {
+            unpaired_new.iter().map(move |&n| {
+                (
+                    OrderedFloat(weights.score(&reg.info, &new[n])),
+                    Reverse(e),
+                    Reverse(n),
+                )
+            })
+        })
+        .collect();
+
+    while let Some((OrderedFloat(score), Reverse(e), Reverse(n))) = sims.pop() {
+        // Scores are popped in descending order, so no later pair can pass
+        if score < min_score {
+            break;
+        }
+
+        if !unpaired_new.contains(&n) {
+            continue;
+        }
+
+        let Some(reg) = unpaired_existing[e].take() else {
+            continue;
+        };
+
+        unpaired_new.remove(&n);
+        out.updates
+            .push((reg, n, weights.explain(&reg.info, &new[n])));
+    }
+
+    out.creates = unpaired_new.into_iter().collect();
+    out.deletes = unpaired_existing.into_iter().flatten().collect();
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use serenity::model::id::{ApplicationId, CommandId, CommandVersionId};
+
+    use super::{pair, PlannedChange};
+    use crate::interaction::command::{CommandInfo, RegisteredCommand, SimWeights};
+
+    fn registered(id: u64, info: CommandInfo) -> RegisteredCommand {
+        RegisteredCommand {
+            id: CommandId::new(id),
+            app: ApplicationId::new(1),
+            guild: None,
+            version: CommandVersionId::new(1),
+            info,
+        }
+    }
+
+    fn roll(name: &str) -> CommandInfo {
+        CommandInfo::build_slash(name, "Roll some dice", |a| {
+            a.string("dice", "Dice to roll", true, ..)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn pairing() {
+        let existing = [
+            registered(1, CommandInfo::user("Poke")),
+            registered(2, roll("roll")),
+            registered(3, CommandInfo::message("Quote")),
+        ];
+        let new = [
+            roll("dice"),
+            CommandInfo::user("Poke"),
+            CommandInfo::user("Wave"),
+        ];
+
+        let pairing = pair(&existing, &new, &SimWeights::default(), 0.0);
+        assert_eq!(pairing.unchanged.len(), 1);
+        assert_eq!(pairing.unchanged[0].0.id.get(), 1);
+        assert_eq!(
+            pairing
+                .updates
+                .iter()
+                .map(|&(r, n, _)| (r.id.get(), n))
+                .collect::<Vec<_>>(),
+            [(2, 0), (3, 2)]
+        );
+        assert!(pairing.creates.is_empty());
+        assert!(pairing.deletes.is_empty());
+
+        // Raising the threshold turns the weak match into a recreate
+        let pairing = pair(&existing, &new, &SimWeights::default(), 0.5);
+        assert_eq!(pairing.updates.len(), 1);
+        assert_eq!(pairing.creates, [2]);
+        assert_eq!(pairing.deletes[0].id.get(), 3);
+
+        let plan = pairing.plan(&new);
+        assert_eq!(plan.unchanged, 1);
+        assert!(matches!(
+            plan.changes[0],
+            PlannedChange::Update { ref sim, .. } if sim.options > 0.99
+        ));
+
+        let text = plan.to_string();
+        assert!(text.starts_with("1 unchanged, 1 updated, 1 created, 1 deleted\n"));
+        assert!(text.contains("~ \"dice\" (ID 2): score"));
+        assert!(text.contains("\n    name: \"roll\" -> \"dice\""));
+        assert!(text.contains("\n+ \"Wave\""));
+        assert!(text.ends_with("\n- \"Quote\" (ID 3)"));
+    }
+}
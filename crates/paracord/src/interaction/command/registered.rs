@@ -1,11 +1,11 @@
 use serenity::model::{
-    application::{Command, CommandType},
+    application::{Command, CommandType, InstallationContext},
     id::{ApplicationId, CommandId, CommandVersionId, GuildId},
 };
 
 use super::{CommandInfo, Data, Trie, TryFromError};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(in super::super) struct RegisteredCommand {
     pub(in super::super) id: CommandId,
     pub(in super::super) app: ApplicationId,
@@ -28,6 +28,8 @@ impl TryFrom<Command> for RegisteredCommand {
             options,
             dm_permission,
             version,
+            integration_types,
+            contexts,
             ..
         } = cmd;
 
@@ -50,6 +52,12 @@ impl TryFrom<Command> for RegisteredCommand {
                 name,
                 data,
                 can_dm: dm_permission.unwrap_or(true),
+                integration_types: if integration_types.is_empty() {
+                    [InstallationContext::Guild].into()
+                } else {
+                    integration_types.into_iter().collect()
+                },
+                contexts: contexts.map(|c| c.into_iter().collect()),
             },
         })
     }
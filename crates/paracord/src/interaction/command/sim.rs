@@ -1,13 +1,167 @@
-use std::{collections::BTreeMap, hash::Hash, num::NonZeroU8};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    hash::Hash,
+    num::NonZeroU8,
+    str::FromStr,
+};
 
 use strsim::{generic_damerau_levenshtein, normalized_damerau_levenshtein};
 
 use super::{Arg, ArgType, CommandInfo, Data, Subcommand, Trie};
 
-/// Compute the similarity between two command descriptors
+/// Compute the similarity between two command descriptors using the default
+/// weights
 #[inline]
 #[must_use]
-pub fn similarity(l: &CommandInfo, r: &CommandInfo) -> f64 { l.sim(r) }
+pub fn similarity(l: &CommandInfo, r: &CommandInfo) -> f64 { SimWeights::default().score(l, r) }
+
+/// Relative weights of the parts of a command similarity score
+///
+/// A score is the weighted mean of three parts, each between 0 and 1:
+/// - `name` compares the names of the commands
+/// - `structure` compares their kinds, descriptions and where they can be used
+/// - `options` compares their subcommands and arguments
+///
+/// Negative weights are treated as zero.  Weights can be parsed from a
+/// comma-separated list such as `name=2,options=0.5`, where any weight not
+/// listed defaults to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimWeights {
+    /// The weight of the name similarity
+    pub name: f64,
+    /// The weight of the structure similarity
+    pub structure: f64,
+    /// The weight of the option similarity
+    pub options: f64,
+}
+
+impl Default for SimWeights {
+    fn default() -> Self {
+        Self {
+            name: 1.0,
+            structure: 1.0,
+            options: 1.0,
+        }
+    }
+}
+
+/// An error resulting from parsing a [`SimWeights`] string
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Error parsing similarity weights: {0}")]
+pub struct ParseSimWeightsError(pub &'static str);
+
+impl FromStr for SimWeights {
+    type Err = ParseSimWeightsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Self::default();
+
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, val) = part
+                .split_once('=')
+                .ok_or(ParseSimWeightsError("Expected a list of key=value pairs"))?;
+            let val: f64 = val
+                .trim()
+                .parse()
+                .ok()
+                .filter(|v: &f64| v.is_finite() && *v >= 0.0)
+                .ok_or(ParseSimWeightsError("Weights must be non-negative numbers"))?;
+
+            *match key.trim() {
+                "name" => &mut weights.name,
+                "structure" => &mut weights.structure,
+                "options" => &mut weights.options,
+                _ => return Err(ParseSimWeightsError("Unknown weight name")),
+            } = val;
+        }
+
+        Ok(weights)
+    }
+}
+
+impl SimWeights {
+    /// Compute the weighted similarity between two command descriptors
+    #[inline]
+    #[must_use]
+    pub fn score(&self, l: &CommandInfo, r: &CommandInfo) -> f64 { self.explain(l, r).score }
+
+    /// Compute the weighted similarity between two command descriptors,
+    /// along with the scores of its parts
+    #[must_use]
+    pub fn explain(&self, l: &CommandInfo, r: &CommandInfo) -> SimExplanation {
+        let CommandInfo {
+            name: l_name,
+            can_dm: l_dm,
+            integration_types: l_types,
+            contexts: l_ctx,
+            data: l_data,
+        } = l;
+        let CommandInfo {
+            name: r_name,
+            can_dm: r_dm,
+            integration_types: r_types,
+            contexts: r_ctx,
+            data: r_data,
+        } = r;
+
+        let availability = avg([l_dm.sim(r_dm), l_types.sim(r_types), l_ctx.sim(r_ctx)]);
+        let (desc, options) = l_data.sim_parts(r_data);
+
+        let name = l_name.sim(r_name);
+        let structure = avg([availability, desc]);
+
+        let weights = [self.name, self.structure, self.options].map(|w| w.max(0.0));
+        let total: f64 = weights.iter().sum();
+        let score = if total > 0.0 {
+            [name, structure, options]
+                .into_iter()
+                .zip(weights)
+                .map(|(s, w)| s * w)
+                .sum::<f64>()
+                / total
+        } else {
+            0.0
+        };
+
+        SimExplanation {
+            score,
+            name,
+            structure,
+            options,
+        }
+    }
+}
+
+/// A breakdown of the similarity score between two commands
+///
+/// See [`SimWeights`] for a description of each part.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimExplanation {
+    /// The weighted similarity score
+    pub score: f64,
+    /// The similarity of the command names
+    pub name: f64,
+    /// The similarity of the command kinds, descriptions and availability
+    pub structure: f64,
+    /// The similarity of the command options
+    pub options: f64,
+}
+
+impl fmt::Display for SimExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            score,
+            name,
+            structure,
+            options,
+        } = self;
+        write!(
+            f,
+            "score {score:.2} (name {name:.2}, structure {structure:.2}, options {options:.2})"
+        )
+    }
+}
 
 #[expect(
     clippy::cast_precision_loss,
@@ -52,6 +206,32 @@ impl Sim for NonZeroU8 {
     }
 }
 
+#[expect(
+    clippy::cast_precision_loss,
+    reason = "Computing the Jaccard index requires casting len() to an f64"
+)]
+impl<T: Ord> Sim for BTreeSet<T> {
+    fn sim(&self, rhs: &Self) -> f64 {
+        let union = self.union(rhs).count();
+
+        if union == 0 {
+            1.0
+        } else {
+            self.intersection(rhs).count() as f64 / union as f64
+        }
+    }
+}
+
+impl<T: Sim> Sim for Option<T> {
+    fn sim(&self, rhs: &Self) -> f64 {
+        match (self, rhs) {
+            (Some(l), Some(r)) => l.sim(r),
+            (None, None) => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
 impl<T: Clone + Eq + Hash> Sim for Vec<T> {
     fn sim(&self, rhs: &Self) -> f64 { ngdl(self, rhs) }
 }
@@ -73,27 +253,12 @@ impl<K: Ord, V: Sim> Sim for BTreeMap<K, V> {
     }
 }
 
-impl Sim for CommandInfo {
-    fn sim(&self, rhs: &Self) -> f64 {
-        let Self {
-            name: l_name,
-            can_dm: l_dm,
-            data: l_data,
-        } = self;
-        let Self {
-            name: r_name,
-            can_dm: r_dm,
-            data: r_data,
-        } = rhs;
-
-        avg([l_name.sim(r_name), l_dm.sim(r_dm), l_data.sim(r_data)])
-    }
-}
-
-impl Sim for Data {
-    fn sim(&self, rhs: &Self) -> f64 {
+impl Data {
+    /// Compute the similarity of the kind and description, and of the
+    /// options, of two commands
+    fn sim_parts(&self, rhs: &Self) -> (f64, f64) {
         match (self, rhs) {
-            (l, r) if l == r => 1.0,
+            (l, r) if l == r => (1.0, 1.0),
             (
                 Self::Slash {
                     desc: l_desc,
@@ -103,9 +268,8 @@ impl Sim for Data {
                     desc: r_desc,
                     trie: r_trie,
                 },
-            ) => avg([l_desc.sim(r_desc), l_trie.sim(r_trie)]),
-            (Self::User, Self::User) | (Self::Message, Self::Message) => 1.0,
-            _ => 0.0,
+            ) => (l_desc.sim(r_desc), l_trie.sim(r_trie)),
+            _ => (0.0, 0.0),
         }
     }
 }
@@ -182,3 +346,60 @@ impl Sim for ArgType {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{similarity, SimWeights};
+    use crate::interaction::command::CommandInfo;
+
+    fn roll(name: &str, desc: &str) -> CommandInfo {
+        CommandInfo::build_slash(name, desc, |a| a.string("dice", "Dice to roll", true, ..))
+            .unwrap()
+    }
+
+    #[test]
+    fn parts() {
+        let l = roll("roll", "Roll some dice");
+        let r = roll("dice", "Roll some dice");
+
+        let exp = SimWeights::default().explain(&l, &r);
+        assert!(exp.name < 1.0);
+        assert!((exp.structure - 1.0).abs() < f64::EPSILON);
+        assert!((exp.options - 1.0).abs() < f64::EPSILON);
+        assert!((exp.score - similarity(&l, &r)).abs() < f64::EPSILON);
+
+        let names_only = SimWeights {
+            name: 1.0,
+            structure: 0.0,
+            options: 0.0,
+        };
+        assert!((names_only.score(&l, &r) - exp.name).abs() < f64::EPSILON);
+
+        let exp = SimWeights::default().explain(&l, &CommandInfo::user("roll"));
+        assert!((exp.name - 1.0).abs() < f64::EPSILON);
+        assert!(exp.options.abs() < f64::EPSILON);
+
+        let none = SimWeights {
+            name: 0.0,
+            structure: -1.0,
+            options: 0.0,
+        };
+        assert!(none.score(&l, &l).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("".parse::<SimWeights>().unwrap(), SimWeights::default());
+        assert_eq!(
+            "name=2, options=0.5".parse::<SimWeights>().unwrap(),
+            SimWeights {
+                name: 2.0,
+                structure: 1.0,
+                options: 0.5,
+            }
+        );
+        assert!("name".parse::<SimWeights>().is_err());
+        assert!("name=-1".parse::<SimWeights>().is_err());
+        assert!("names=1".parse::<SimWeights>().is_err());
+    }
+}
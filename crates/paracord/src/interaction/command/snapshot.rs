@@ -0,0 +1,310 @@
+use std::{collections::BTreeMap, fmt, io, path::Path};
+
+use serde_json::{Map, Value};
+use serenity::builder::CreateCommand;
+
+use super::{diff::join, Change, CommandInfo};
+
+/// Version of the format produced by [`snapshot`]
+///
+/// This is bumped whenever the rendering of a command changes in a way that
+/// would cause spurious differences against older snapshots.
+pub const SNAPSHOT_VERSION: u64 = 1;
+
+/// Environment variable which, if set to a non-empty value, causes
+/// [`check_snapshot`] to overwrite the golden file instead of comparing it
+pub const UPDATE_SNAPSHOTS_VAR: &str = "PARACORD_UPDATE_SNAPSHOTS";
+
+/// An error resulting from comparing commands against a golden snapshot
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    /// The snapshot file could not be read or written
+    #[error("Error accessing snapshot file: {0}")]
+    Io(#[from] io::Error),
+    /// The snapshot file is not valid JSON or has an unexpected structure
+    #[error("Malformed snapshot file: {0}")]
+    Malformed(String),
+    /// The snapshot file was written with a different format version
+    #[error("Snapshot has format version {0}, expected {SNAPSHOT_VERSION}")]
+    Version(u64),
+    /// The commands differ from the snapshot
+    #[error("{}", Mismatch(.0))]
+    Mismatch(Vec<Change>),
+}
+
+struct Mismatch<'a>(&'a [Change]);
+
+impl fmt::Display for Mismatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Commands differ from snapshot:")?;
+        for change in self.0 {
+            writeln!(f, "  {change}")?;
+        }
+        write!(
+            f,
+            "Set {UPDATE_SNAPSHOTS_VAR}=1 to accept these changes and update the snapshot"
+        )
+    }
+}
+
+fn render(info: &CommandInfo) -> Value {
+    let value = serde_json::to_value(CreateCommand::from(info.clone()))
+        .unwrap_or_else(|e| unreachable!("Error serializing command: {e}"));
+    canonicalize(value)
+}
+
+/// Sort all object keys and drop null or empty fields, which Discord treats
+/// the same as absent ones
+///
+/// Keys are sorted explicitly since `serde_json` only does so when its
+/// `preserve_order` feature is disabled.
+fn canonicalize(value: Value) -> Value {
+    fn is_empty(value: &Value) -> bool {
+        match value {
+            Value::Null => true,
+            Value::Array(a) => a.is_empty(),
+            Value::Object(o) => o.is_empty(),
+            _ => false,
+        }
+    }
+
+    match value {
+        Value::Object(obj) => {
+            let sorted: BTreeMap<_, _> = obj
+                .into_iter()
+                .map(|(k, v)| (k, canonicalize(v)))
+                .filter(|(_, v)| !is_empty(v))
+                .collect();
+            Value::Object(sorted.into_iter().collect())
+        },
+        Value::Array(arr) => Value::Array(arr.into_iter().map(canonicalize).collect()),
+        v => v,
+    }
+}
+
+fn render_all<'a>(commands: impl IntoIterator<Item = &'a CommandInfo>) -> Map<String, Value> {
+    let sorted: BTreeMap<_, _> = commands
+        .into_iter()
+        .map(|c| (c.name().clone(), render(c)))
+        .collect();
+    sorted.into_iter().collect()
+}
+
+/// Produce a stable, canonical JSON rendering of the given commands, suitable
+/// for committing as a golden file
+///
+/// Commands are keyed by name and sorted, object keys are sorted, and the
+/// output is tagged with [`SNAPSHOT_VERSION`].  Parameter order is preserved,
+/// as it is visible to users.
+#[must_use]
+pub fn snapshot<'a>(commands: impl IntoIterator<Item = &'a CommandInfo>) -> String {
+    let mut root = Map::new();
+    root.insert("version".into(), SNAPSHOT_VERSION.into());
+    root.insert("commands".into(), Value::Object(render_all(commands)));
+
+    let mut out = serde_json::to_string_pretty(&Value::Object(root))
+        .unwrap_or_else(|e| unreachable!("Error formatting snapshot: {e}"));
+    out.push('\n');
+    out
+}
+
+/// Compare the given commands against a snapshot previously produced by
+/// [`snapshot`], returning every field that differs
+///
+/// # Errors
+/// This method returns an error if the snapshot is malformed or was produced
+/// with a different format version.
+pub fn diff_snapshot<'a>(
+    golden: &str,
+    commands: impl IntoIterator<Item = &'a CommandInfo>,
+) -> Result<Vec<Change>, SnapshotError> {
+    let golden: Value =
+        serde_json::from_str(golden).map_err(|e| SnapshotError::Malformed(e.to_string()))?;
+    let version = golden
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| SnapshotError::Malformed("Missing format version".into()))?;
+    if version != SNAPSHOT_VERSION {
+        return Err(SnapshotError::Version(version));
+    }
+
+    let Some(Value::Object(old)) = golden.get("commands") else {
+        return Err(SnapshotError::Malformed("Missing command list".into()));
+    };
+
+    let mut out = vec![];
+    diff_value(
+        "",
+        &Value::Object(old.clone()),
+        &Value::Object(render_all(commands)),
+        &mut out,
+    );
+    Ok(out)
+}
+
+/// Compare the given commands against the golden file at `path`
+///
+/// If the environment variable named by [`UPDATE_SNAPSHOTS_VAR`] is set, the
+/// golden file is instead (re)written with the current commands.
+///
+/// # Errors
+/// This method returns an error if the golden file cannot be read or written,
+/// is malformed, or does not match the given commands.  A missing golden file
+/// is reported as an I/O error.
+pub fn check_snapshot<'a>(
+    path: impl AsRef<Path>,
+    commands: impl IntoIterator<Item = &'a CommandInfo>,
+) -> Result<(), SnapshotError> {
+    let path = path.as_ref();
+
+    if std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some_and(|v| !v.is_empty()) {
+        return std::fs::write(path, snapshot(commands)).map_err(Into::into);
+    }
+
+    let changes = diff_snapshot(&std::fs::read_to_string(path)?, commands)?;
+    if changes.is_empty() {
+        Ok(())
+    } else {
+        Err(SnapshotError::Mismatch(changes))
+    }
+}
+
+/// Assert that the given commands match the golden file at `path`, for use
+/// in tests
+///
+/// See [`check_snapshot`] for how to update the golden file.
+///
+/// # Panics
+/// This function panics if [`check_snapshot`] returns an error.
+#[track_caller]
+pub fn assert_snapshot<'a>(
+    path: impl AsRef<Path>,
+    commands: impl IntoIterator<Item = &'a CommandInfo>,
+) {
+    let path = path.as_ref();
+    if let Err(e) = check_snapshot(path, commands) {
+        panic!("Snapshot {path:?} does not match:\n{e}");
+    }
+}
+
+fn diff_value(path: &str, old: &Value, new: &Value, out: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, l) in old {
+                match new.get(key) {
+                    Some(r) => diff_value(&join(path, key), l, r, out),
+                    None => out.push(Change {
+                        path: join(path, key),
+                        old: Some(l.to_string()),
+                        new: None,
+                    }),
+                }
+            }
+
+            for (key, r) in new {
+                if !old.contains_key(key) {
+                    out.push(Change {
+                        path: join(path, key),
+                        old: None,
+                        new: Some(r.to_string()),
+                    });
+                }
+            }
+        },
+        (Value::Array(old), Value::Array(new)) => {
+            for (i, (l, r)) in old.iter().zip(new).enumerate() {
+                diff_value(&format!("{path}[{i}]"), l, r, out);
+            }
+
+            for (i, l) in old.iter().enumerate().skip(new.len()) {
+                out.push(Change {
+                    path: format!("{path}[{i}]"),
+                    old: Some(l.to_string()),
+                    new: None,
+                });
+            }
+
+            for (i, r) in new.iter().enumerate().skip(old.len()) {
+                out.push(Change {
+                    path: format!("{path}[{i}]"),
+                    old: None,
+                    new: Some(r.to_string()),
+                });
+            }
+        },
+        (l, r) if l != r => out.push(Change {
+            path: path.into(),
+            old: Some(l.to_string()),
+            new: Some(r.to_string()),
+        }),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff_snapshot, snapshot, SnapshotError};
+    use crate::interaction::command::{prelude::*, CommandInfo};
+
+    fn commands() -> Vec<CommandInfo> {
+        vec![
+            CommandInfo::build_slash("roll", "Roll some dice", |a| {
+                a.string("dice", "Dice to roll", true, ..).int(
+                    "times",
+                    "Number of times",
+                    false,
+                    1..=10,
+                )
+            })
+            .unwrap(),
+            CommandInfo::user("Poke"),
+        ]
+    }
+
+    #[test]
+    fn stable() {
+        let cmds = commands();
+        let snap = snapshot(&cmds);
+        assert_eq!(snap, snapshot(cmds.iter().rev()));
+        assert!(snap.ends_with('\n'));
+
+        let json: serde_json::Value = serde_json::from_str(&snap).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["commands"]["roll"]["options"][1]["name"], "times");
+        assert!(diff_snapshot(&snap, &cmds).unwrap().is_empty());
+    }
+
+    #[test]
+    fn detects_changes() {
+        let golden = snapshot(&commands());
+        let mut cmds = commands();
+        cmds[0] = CommandInfo::build_slash("roll", "Roll dice", |a| {
+            a.string("dice", "Dice to roll", true, ..)
+        })
+        .unwrap();
+        cmds[1] = CommandInfo::message("Poke");
+        cmds.push(CommandInfo::user("Hug").can_dm(false));
+
+        let changes = diff_snapshot(&golden, &cmds).unwrap();
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.old.is_some(), c.new.is_some()))
+            .collect();
+        assert_eq!(summary, [
+            ("Poke.type", true, true),
+            ("roll.description", true, true),
+            ("roll.options[1]", true, false),
+            ("Hug", false, true),
+        ]);
+        assert_eq!(changes[0].to_string(), "Poke.type: 2 -> 3");
+        assert_eq!(
+            changes[1].to_string(),
+            "roll.description: \"Roll some dice\" -> \"Roll dice\""
+        );
+
+        assert!(matches!(
+            diff_snapshot("{\"version\": 0, \"commands\": {}}", &cmds),
+            Err(SnapshotError::Version(0))
+        ));
+    }
+}
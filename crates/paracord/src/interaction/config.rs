@@ -0,0 +1,325 @@
+//! Typed per-handler configuration, read from TOML, the environment, and
+//! command-line overrides
+//!
+//! Each handler implementing [`FromConfig`] names a section and declares the
+//! type of its options, and a [`ConfigRegistry`] collects the sections from
+//! every source before handing each handler its own.  Later sources take
+//! precedence over earlier ones, so a registry is normally built from a TOML
+//! file, then the environment, then command-line overrides.
+
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
+
+/// An error arising from reading or applying handler configuration
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The configuration file could not be read
+    #[error("Error reading handler config {0:?}")]
+    Read(PathBuf, #[source] io::Error),
+    /// The configuration file was not valid TOML
+    #[error("Error parsing handler config")]
+    Parse(#[from] toml::de::Error),
+    /// A configuration file contained a top-level value that was not a table
+    #[error("Handler config section {0:?} is not a table")]
+    NotATable(String),
+    /// An override was not of the form `section.key=value`
+    #[error("Invalid handler option {0:?}, expected section.key=value")]
+    Override(String),
+    /// A section did not match the options of the handler reading it
+    #[error("Invalid options in handler config section {0:?}")]
+    Section(&'static str, #[source] toml::de::Error),
+}
+
+/// A handler which is constructed from its own section of configuration
+pub trait FromConfig: Sized + 'static {
+    /// Options shared by every handler, passed alongside the handler's own
+    type Opts: ?Sized;
+
+    /// This handler's own options
+    ///
+    /// An absent section is read as an empty table, so fields that may be
+    /// omitted should be marked `#[serde(default)]`.
+    type Config: DeserializeOwned + Clone + Send + Sync + 'static;
+
+    /// The name of the section this handler's options are read from
+    const SECTION: &'static str;
+
+    /// Construct the handler from its options
+    fn from_config(opts: &Self::Opts, config: Self::Config) -> Self;
+}
+
+/// Parse a value given as a string, treating it as a TOML value if possible
+/// and a bare string otherwise
+fn parse_value(s: &str) -> Value {
+    toml::from_str::<Table>(&format!("v = {s}"))
+        .ok()
+        .filter(|t| t.len() == 1)
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| Value::String(s.into()))
+}
+
+/// A collection of handler configuration sections, and the options parsed
+/// from them keyed by handler type
+#[derive(Default)]
+pub struct ConfigRegistry {
+    sections: HashMap<String, Table>,
+    claimed: HashSet<&'static str>,
+    resolved: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl fmt::Debug for ConfigRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigRegistry")
+            .field("sections", &self.sections)
+            .field("claimed", &self.claimed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ConfigRegistry {
+    /// Construct a new registry with no configured sections
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self { Self::default() }
+
+    /// Construct a registry from the tables of a TOML document
+    ///
+    /// # Errors
+    /// This method returns an error if the document is invalid or contains a
+    /// top-level value that is not a table.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let sections = text
+            .parse::<Table>()?
+            .into_iter()
+            .map(|(k, v)| match v {
+                Value::Table(t) => Ok((k, t)),
+                _ => Err(ConfigError::NotATable(k)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            sections,
+            ..Self::default()
+        })
+    }
+
+    /// Read a registry from a TOML file
+    ///
+    /// # Errors
+    /// This method returns an error if the file cannot be read or is not
+    /// valid configuration.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.into(), e))?;
+        Self::from_toml(&text)
+    }
+
+    /// Set a single option, replacing any existing value
+    pub fn set(&mut self, section: impl Into<String>, key: impl Into<String>, value: Value) {
+        self.sections
+            .entry(section.into())
+            .or_default()
+            .insert(key.into(), value);
+    }
+
+    /// Apply options from variables of the form `{prefix}{SECTION}__{KEY}`,
+    /// with section and key names lowercased
+    ///
+    /// Values are parsed as TOML if possible and read as strings otherwise.
+    #[must_use]
+    pub fn with_vars(
+        mut self,
+        prefix: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        for (name, val) in vars {
+            let Some((section, key)) = name
+                .strip_prefix(prefix)
+                .and_then(|n| n.split_once("__"))
+                .filter(|(s, k)| !s.is_empty() && !k.is_empty())
+            else {
+                continue;
+            };
+
+            self.set(
+                section.to_lowercase(),
+                key.to_lowercase(),
+                parse_value(&val),
+            );
+        }
+
+        self
+    }
+
+    /// Apply options from environment variables, as with
+    /// [`with_vars`](Self::with_vars)
+    #[inline]
+    #[must_use]
+    pub fn with_env(self, prefix: &str) -> Self { self.with_vars(prefix, std::env::vars()) }
+
+    /// Apply overrides of the form `section.key=value`, such as those given
+    /// on the command line
+    ///
+    /// Values are parsed as TOML if possible and read as strings otherwise.
+    ///
+    /// # Errors
+    /// This method returns an error if an override is malformed.
+    pub fn with_overrides<S: AsRef<str>>(
+        mut self,
+        overrides: impl IntoIterator<Item = S>,
+    ) -> Result<Self, ConfigError> {
+        for o in overrides {
+            let o = o.as_ref();
+            let Some(((section, key), val)) = o
+                .split_once('=')
+                .and_then(|(k, v)| k.trim().split_once('.').map(|k| (k, v.trim())))
+                .filter(|((s, k), _)| !s.is_empty() && !k.is_empty())
+            else {
+                return Err(ConfigError::Override(o.into()));
+            };
+
+            self.set(section, key, parse_value(val));
+        }
+
+        Ok(self)
+    }
+
+    /// Parse the options for the given handler type, without constructing it
+    ///
+    /// # Errors
+    /// This method returns an error if the handler's section does not match
+    /// its options.
+    pub fn resolve<H: FromConfig>(&mut self) -> Result<&H::Config, ConfigError> {
+        let table = self.sections.get(H::SECTION).cloned().unwrap_or_default();
+        let config: H::Config = Value::Table(table)
+            .try_into()
+            .map_err(|e| ConfigError::Section(H::SECTION, e))?;
+
+        self.claimed.insert(H::SECTION);
+        self.resolved.insert(TypeId::of::<H>(), Box::new(config));
+
+        Ok(self
+            .get::<H>()
+            .unwrap_or_else(|| unreachable!("Missing resolved config")))
+    }
+
+    /// Parse the options for the given handler type and construct it
+    ///
+    /// # Errors
+    /// This method returns an error if the handler's section does not match
+    /// its options.
+    pub fn build<H: FromConfig>(&mut self, opts: &H::Opts) -> Result<H, ConfigError> {
+        let config = self.resolve::<H>()?.clone();
+        Ok(H::from_config(opts, config))
+    }
+
+    /// Get the options previously parsed for the given handler type
+    #[must_use]
+    pub fn get<H: FromConfig>(&self) -> Option<&H::Config> {
+        self.resolved
+            .get(&TypeId::of::<H>())
+            .and_then(|c| c.downcast_ref())
+    }
+
+    /// List the configured sections not read by any handler, which usually
+    /// indicate a typo
+    #[must_use]
+    pub fn unused(&self) -> Vec<&str> {
+        let mut unused: Vec<_> = self
+            .sections
+            .keys()
+            .map(String::as_str)
+            .filter(|s| !self.claimed.contains(s))
+            .collect();
+        unused.sort_unstable();
+        unused
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+    use toml::Value;
+
+    use super::{ConfigError, ConfigRegistry, FromConfig};
+
+    #[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+    #[serde(default)]
+    struct EchoConfig {
+        volume: f64,
+        prefix: String,
+        repeat: u32,
+    }
+
+    #[derive(Debug)]
+    struct Echo(String, EchoConfig);
+
+    impl FromConfig for Echo {
+        type Config = EchoConfig;
+        type Opts = str;
+
+        const SECTION: &'static str = "echo";
+
+        fn from_config(opts: &str, config: EchoConfig) -> Self { Self(opts.into(), config) }
+    }
+
+    #[test]
+    fn layering() {
+        let mut reg = ConfigRegistry::from_toml(
+            "[echo]\nvolume = 0.5\nprefix = \"file\"\n\n[ehco]\nrepeat = 1\n",
+        )
+        .unwrap()
+        .with_vars("HANDLER_", [
+            ("HANDLER_ECHO__PREFIX".into(), "env".into()),
+            ("HANDLER_ECHO__REPEAT".into(), "3".into()),
+            ("HANDLER_CONFIG".into(), "ignored".into()),
+            ("OTHER_ECHO__VOLUME".into(), "9".into()),
+        ])
+        .with_overrides(["echo.repeat = 4"])
+        .unwrap();
+
+        assert!(reg.get::<Echo>().is_none());
+        let Echo(opts, config) = reg.build::<Echo>("q").unwrap();
+        assert_eq!(opts, "q");
+        assert_eq!(config, EchoConfig {
+            volume: 0.5,
+            prefix: "env".into(),
+            repeat: 4,
+        });
+        assert_eq!(reg.get::<Echo>(), Some(&config));
+        assert_eq!(reg.unused(), ["ehco"]);
+
+        reg.set("echo", "repeat", Value::String("lots".into()));
+        assert!(matches!(
+            reg.build::<Echo>("q"),
+            Err(ConfigError::Section("echo", _))
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            ConfigRegistry::new().build::<Echo>("").unwrap().1,
+            EchoConfig::default()
+        );
+
+        assert!(matches!(
+            ConfigRegistry::from_toml("volume = 1"),
+            Err(ConfigError::NotATable(k)) if k == "volume"
+        ));
+
+        for bad in ["echo=1", ".repeat=1", "echo.=1", "echo.repeat"] {
+            assert!(matches!(
+                ConfigRegistry::new().with_overrides([bad]),
+                Err(ConfigError::Override(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Metadata describing the lifetime of a single interaction
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serenity::model::id::{InteractionId, ShardId};
+
+/// The time after an interaction is created within which it must be
+/// acknowledged
+pub const INITIAL_RESPONSE_WINDOW: TimeDelta = TimeDelta::seconds(3);
+
+/// The time after an interaction is created for which its token can be used
+/// to edit the response or create followups
+pub const TOKEN_LIFETIME: TimeDelta = TimeDelta::minutes(15);
+
+/// Context for an interaction being handled, including its response deadlines
+#[derive(Debug, Clone, Copy)]
+pub struct InteractionCtx {
+    id: InteractionId,
+    created: DateTime<Utc>,
+    shard: ShardId,
+}
+
+impl InteractionCtx {
+    /// Construct a new context for the given interaction, received on the
+    /// given shard
+    #[inline]
+    #[must_use]
+    pub fn new(id: InteractionId, shard: ShardId) -> Self {
+        Self {
+            id,
+            created: *id.created_at(),
+            shard,
+        }
+    }
+
+    /// The ID of the interaction
+    #[inline]
+    #[must_use]
+    pub fn id(&self) -> InteractionId { self.id }
+
+    /// The ID of the shard that received the interaction
+    #[inline]
+    #[must_use]
+    pub fn shard_id(&self) -> ShardId { self.shard }
+
+    /// The time the interaction was created, according to its snowflake ID
+    #[inline]
+    #[must_use]
+    pub fn created_at(&self) -> DateTime<Utc> { self.created }
+
+    /// The time by which the interaction must be acknowledged
+    #[inline]
+    #[must_use]
+    pub fn initial_deadline(&self) -> DateTime<Utc> { self.created + INITIAL_RESPONSE_WINDOW }
+
+    /// The time at which the interaction token expires
+    #[inline]
+    #[must_use]
+    pub fn token_deadline(&self) -> DateTime<Utc> { self.created + TOKEN_LIFETIME }
+
+    /// Returns true if the window for acknowledging the interaction has passed
+    /// according to the local clock
+    #[inline]
+    #[must_use]
+    pub fn is_initial_expired(&self) -> bool { Utc::now() >= self.initial_deadline() }
+
+    /// Returns true if the interaction token has expired, after which no
+    /// response calls can succeed
+    #[inline]
+    #[must_use]
+    pub fn is_expired(&self) -> bool { Utc::now() >= self.token_deadline() }
+}
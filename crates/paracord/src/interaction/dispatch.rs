@@ -0,0 +1,350 @@
+//! Bounded, prioritized dispatch of interactions to their handlers
+//!
+//! Without a dispatch queue, every incoming interaction is handled as soon as
+//! it arrives.  With one configured (see
+//! [`Registry::with_dispatch`](super::Registry::with_dispatch)), at most
+//! [`DispatchOpts::concurrency`] interactions are handled at once.  The rest
+//! wait in a queue that is drained round-robin by guild, so a burst from one
+//! server cannot starve the others.  When the queue is full, the lowest
+//! priority waiter is shed in favor of more important interactions, and shed
+//! interactions receive a standard "busy" reply.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serenity::model::id::{GuildId, UserId};
+use tokio::sync::oneshot;
+
+/// The relative importance of an interaction while the dispatch queue is
+/// under pressure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Interactions that are safe to drop, such as autocomplete requests
+    Low,
+    /// Most interactions, such as commands and components
+    Normal,
+    /// Interactions that would lose user input if dropped, such as modal
+    /// submissions
+    High,
+}
+
+impl Priority {
+    const ALL: [Self; 3] = [Self::Low, Self::Normal, Self::High];
+
+    #[inline]
+    fn index(self) -> usize { self as usize }
+}
+
+/// Limits for the dispatch queue of a [`Registry`](super::Registry)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DispatchOpts {
+    /// Maximum number of interactions handled at once
+    pub concurrency: usize,
+    /// Maximum number of interactions waiting for a free handler
+    pub max_queued: usize,
+    /// Longest an interaction may wait for a free handler before it is given
+    /// a busy reply
+    ///
+    /// Discord expects a response within three seconds, so this should be
+    /// kept well below that.
+    pub max_wait: Duration,
+}
+
+impl Default for DispatchOpts {
+    fn default() -> Self {
+        Self {
+            concurrency: 32,
+            max_queued: 128,
+            max_wait: Duration::from_secs(2),
+        }
+    }
+}
+
+/// A point-in-time snapshot of dispatch queue activity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DispatchStats {
+    /// Number of interactions currently being handled
+    pub running: usize,
+    /// Number of interactions currently waiting for a free handler
+    pub queued: usize,
+    /// The highest number of waiting interactions observed
+    pub peak_queued: usize,
+    /// Total number of interactions given a busy reply
+    pub shed: u64,
+}
+
+/// The fairness domain of an interaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Source {
+    Guild(GuildId),
+    User(UserId),
+}
+
+impl Source {
+    #[inline]
+    pub fn new(guild: Option<GuildId>, user: UserId) -> Self {
+        guild.map_or(Self::User(user), Self::Guild)
+    }
+}
+
+/// Error returned when an interaction could not be admitted to the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Busy;
+
+type Ticket = (u64, oneshot::Sender<Permit>);
+
+/// Waiters of a single priority, drained round-robin by source
+#[derive(Debug, Default)]
+struct Level {
+    order: VecDeque<Source>,
+    waiting: HashMap<Source, VecDeque<Ticket>>,
+}
+
+impl Level {
+    fn push(&mut self, src: Source, ticket: Ticket) {
+        let queue = self.waiting.entry(src).or_default();
+        if queue.is_empty() {
+            self.order.push_back(src);
+        }
+        queue.push_back(ticket);
+    }
+
+    fn take(
+        &mut self,
+        src: Source,
+        f: impl FnOnce(&mut VecDeque<Ticket>) -> Option<Ticket>,
+    ) -> Option<Ticket> {
+        let queue = self.waiting.get_mut(&src)?;
+        let ticket = f(queue);
+
+        if queue.is_empty() {
+            self.waiting.remove(&src);
+            self.order.retain(|&s| s != src);
+        }
+
+        ticket
+    }
+
+    /// Take the oldest waiter from the next source in line
+    fn pop(&mut self) -> Option<Ticket> {
+        let src = self.order.pop_front()?;
+        self.order.push_back(src);
+        self.take(src, VecDeque::pop_front)
+    }
+
+    /// Take the newest waiter from the source with the most waiters
+    fn shed(&mut self) -> Option<Ticket> {
+        let src = self
+            .waiting
+            .iter()
+            .max_by_key(|(_, q)| q.len())
+            .map(|(&s, _)| s)?;
+        self.take(src, VecDeque::pop_back)
+    }
+
+    /// Remove a specific waiter, returning whether it was found
+    fn remove(&mut self, src: Source, id: u64) -> bool {
+        self.take(src, |q| {
+            let i = q.iter().position(|&(i, _)| i == id)?;
+            q.remove(i)
+        })
+        .is_some()
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    levels: [Level; Priority::ALL.len()],
+    next_id: u64,
+    stats: DispatchStats,
+}
+
+/// A concurrency-limited queue of interactions waiting to be handled
+#[derive(Debug)]
+pub(super) struct Queue {
+    opts: DispatchOpts,
+    state: Mutex<State>,
+}
+
+/// A handler slot in a [`Queue`], released when dropped
+#[derive(Debug)]
+pub(super) struct Permit(Option<Arc<Queue>>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(queue) = self.0.take() {
+            queue.release();
+        }
+    }
+}
+
+impl Queue {
+    pub fn new(opts: DispatchOpts) -> Self {
+        Self {
+            opts,
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn stats(&self) -> DispatchStats { self.state.lock().unwrap().stats }
+
+    /// Wait for a free handler slot
+    ///
+    /// # Errors
+    /// This method returns an error if the interaction was shed, either
+    /// because the queue was full or because it waited too long.
+    pub async fn acquire(self: &Arc<Self>, src: Source, prio: Priority) -> Result<Permit, Busy> {
+        let (id, mut rx) = {
+            let mut state = self.state.lock().unwrap();
+            let State {
+                ref mut levels,
+                ref mut next_id,
+                ref mut stats,
+            } = *state;
+
+            if stats.running < self.opts.concurrency && stats.queued == 0 {
+                stats.running += 1;
+                return Ok(Permit(Some(Arc::clone(self))));
+            }
+
+            if stats.queued >= self.opts.max_queued {
+                stats.shed += 1;
+
+                // Dropping the shed ticket wakes its waiter with an error
+                if levels[..prio.index()]
+                    .iter_mut()
+                    .find_map(Level::shed)
+                    .is_none()
+                {
+                    tracing::warn!(?src, ?prio, "Dispatch queue full, rejecting interaction");
+                    return Err(Busy);
+                }
+
+                tracing::warn!(
+                    ?src,
+                    ?prio,
+                    "Dispatch queue full, shed a lower-priority interaction"
+                );
+                stats.queued -= 1;
+            }
+
+            let id = *next_id;
+            *next_id += 1;
+            let (tx, rx) = oneshot::channel();
+            levels[prio.index()].push(src, (id, tx));
+            stats.queued += 1;
+            stats.peak_queued = stats.peak_queued.max(stats.queued);
+            tracing::debug!(?src, ?prio, queued = stats.queued, "Interaction queued");
+
+            (id, rx)
+        };
+
+        if let Ok(res) = tokio::time::timeout(self.opts.max_wait, &mut rx).await {
+            return res.map_err(|_| Busy);
+        }
+
+        let removed = {
+            let mut state = self.state.lock().unwrap();
+            let removed = state.levels[prio.index()].remove(src, id);
+            if removed {
+                state.stats.queued -= 1;
+                state.stats.shed += 1;
+            }
+            removed
+        };
+
+        if removed {
+            tracing::warn!(?src, ?prio, "Interaction timed out in dispatch queue");
+            Err(Busy)
+        } else {
+            // A slot was handed over just as the timeout elapsed
+            rx.try_recv().map_err(|_| Busy)
+        }
+    }
+
+    /// Hand a released slot to the next waiter, or free it if none remain
+    fn release(self: Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            let Some((_, tx)) = state.levels.iter_mut().rev().find_map(Level::pop) else {
+                state.stats.running -= 1;
+                return;
+            };
+            state.stats.queued -= 1;
+
+            match tx.send(Permit(Some(Arc::clone(&self)))) {
+                Ok(()) => return,
+                // The waiter gave up, so disarm the permit and try the next
+                Err(mut permit) => drop(permit.0.take()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use futures_util::FutureExt;
+    use serenity::model::id::{GuildId, UserId};
+    use tokio::sync::oneshot;
+
+    use super::{DispatchOpts, Level, Priority, Queue, Source};
+
+    fn ticket(id: u64) -> super::Ticket { (id, oneshot::channel().0) }
+
+    #[test]
+    fn round_robin() {
+        let (a, b) = (Source::Guild(GuildId::new(1)), Source::User(UserId::new(2)));
+        let mut level = Level::default();
+        for id in 0..3 {
+            level.push(a, ticket(id));
+        }
+        level.push(b, ticket(3));
+
+        let order: Vec<_> = std::iter::from_fn(|| level.pop().map(|(i, _)| i)).collect();
+        assert_eq!(order, [0, 3, 1, 2]);
+        assert!(level.waiting.is_empty() && level.order.is_empty());
+    }
+
+    #[test]
+    fn shed_and_remove() {
+        let (a, b) = (
+            Source::Guild(GuildId::new(1)),
+            Source::Guild(GuildId::new(2)),
+        );
+        let mut level = Level::default();
+        level.push(a, ticket(0));
+        level.push(b, ticket(1));
+        level.push(b, ticket(2));
+
+        assert_eq!(level.shed().map(|(i, _)| i), Some(2));
+        assert!(level.remove(b, 1));
+        assert!(!level.remove(b, 1));
+        assert_eq!(level.order, [a]);
+    }
+
+    #[test]
+    fn admission() {
+        let queue = Arc::new(Queue::new(DispatchOpts {
+            concurrency: 1,
+            max_queued: 0,
+            ..DispatchOpts::default()
+        }));
+        let src = Source::new(None, UserId::new(1));
+        let acquire = || queue.acquire(src, Priority::High).now_or_never();
+
+        let permit = acquire().unwrap().unwrap();
+        assert_eq!(queue.stats().running, 1);
+        assert!(acquire().unwrap().is_err());
+        assert_eq!(queue.stats().shed, 1);
+
+        drop(permit);
+        assert_eq!(queue.stats().running, 0);
+        assert!(acquire().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,142 @@
+//! Reporting for unexpected errors raised by interaction handlers
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serenity::model::id::InteractionId;
+
+use super::ring::RingLog;
+
+/// A short identifier for a handler failure, shown to the affected user so
+/// their report can be matched against the bot's logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ErrorId(u64);
+
+impl From<InteractionId> for ErrorId {
+    #[inline]
+    fn from(id: InteractionId) -> Self { Self(id.get()) }
+}
+
+impl fmt::Display for ErrorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{:x}", self.0) }
+}
+
+impl std::str::FromStr for ErrorId {
+    type Err = std::num::ParseIntError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> { u64::from_str_radix(s, 16).map(Self) }
+}
+
+/// A record of a single unexpected error raised while handling an
+/// interaction
+#[derive(Debug, Clone)]
+pub struct HandlerFailure {
+    /// The ID reported to the user alongside the error
+    pub id: ErrorId,
+    /// The time the error occurred
+    pub time: DateTime<Utc>,
+    /// The kind of interaction being handled, e.g. `"command"`
+    pub kind: &'static str,
+    /// A human-readable description of the interaction
+    pub name: String,
+    /// A human-readable description of the user who sent the interaction
+    pub issuer: String,
+    /// The error message
+    pub error: String,
+}
+
+impl fmt::Display for HandlerFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            id,
+            time,
+            kind,
+            name,
+            issuer,
+            error,
+        } = self;
+
+        write!(
+            f,
+            "[{}] {id}: {kind} {name} from {issuer}: {error}",
+            time.format("%Y-%m-%d %H:%M:%S"),
+        )
+    }
+}
+
+/// A destination for handler failure records
+pub trait FailureSink: fmt::Debug + Send + Sync {
+    /// Record an unexpected error raised by a handler
+    fn record(&self, failure: HandlerFailure);
+}
+
+/// A [`FailureSink`] retaining a fixed number of the most recent failures in
+/// memory
+#[derive(Debug)]
+pub struct MemoryFailureLog(RingLog<HandlerFailure>);
+
+impl MemoryFailureLog {
+    /// Construct a new failure log retaining at most `cap` failures
+    #[must_use]
+    pub fn new(cap: usize) -> Self { Self(RingLog::new(cap)) }
+
+    /// Get up to `n` of the most recent failures, oldest first
+    ///
+    /// # Panics
+    /// This method panics if the log's lock is poisoned.
+    #[must_use]
+    pub fn recent(&self, n: usize) -> Vec<HandlerFailure> { self.0.recent(n) }
+
+    /// Look up a retained failure by its error ID
+    ///
+    /// # Panics
+    /// This method panics if the log's lock is poisoned.
+    #[must_use]
+    pub fn get(&self, id: ErrorId) -> Option<HandlerFailure> {
+        self.0.recent(usize::MAX).into_iter().find(|f| f.id == id)
+    }
+}
+
+impl FailureSink for MemoryFailureLog {
+    fn record(&self, failure: HandlerFailure) { self.0.push(failure); }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use serenity::model::id::InteractionId;
+
+    use super::{ErrorId, FailureSink, HandlerFailure, MemoryFailureLog};
+
+    fn failure(id: u64) -> HandlerFailure {
+        HandlerFailure {
+            id: InteractionId::new(id).into(),
+            time: Utc::now(),
+            kind: "command",
+            name: "/test".into(),
+            issuer: "@user".into(),
+            error: "oops".into(),
+        }
+    }
+
+    #[test]
+    fn error_id_round_trip() {
+        let id = ErrorId::from(InteractionId::new(0x1234_abcd));
+        assert_eq!(id.to_string(), "1234abcd");
+        assert_eq!("1234abcd".parse(), Ok(id));
+    }
+
+    #[test]
+    fn log_is_bounded() {
+        let log = MemoryFailureLog::new(3);
+        for id in 1..=5 {
+            log.record(failure(id));
+        }
+
+        let ids: Vec<_> = log.recent(10).into_iter().map(|f| f.id).collect();
+        assert_eq!(ids, [3, 4, 5].map(|i| InteractionId::new(i).into()));
+        assert!(log.get(InteractionId::new(2).into()).is_none());
+        assert!(log.get(InteractionId::new(4).into()).is_some());
+    }
+}
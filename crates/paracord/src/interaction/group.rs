@@ -0,0 +1,351 @@
+//! Support for mounting related command handlers under a shared name
+
+use std::{collections::BTreeMap, fmt, sync::Arc, time::Duration};
+
+use qcore::builder;
+use serenity::model::id::GuildId;
+use tokio::time::Instant;
+
+use super::{
+    command::{CommandInfo, TryFromError},
+    handler::{
+        AutoDefer, CommandError, CommandHandler, CommandResponder, CommandResult, CommandVisitor,
+        CompletionResult, CompletionVisitor, Handlers,
+    },
+    rpc::Schema,
+};
+use crate::client::Context;
+
+/// Logic run around the dispatch of every command in a [`CommandGroup`]
+///
+/// Middleware is not run for autocomplete interactions.
+#[async_trait::async_trait]
+pub trait CommandMiddleware<S>: fmt::Debug + Send + Sync {
+    /// Inspect a command before it is passed to its handler
+    ///
+    /// `command` is the full name of the invoked command, including the name
+    /// of its group if nested.  Returning an error (usually after responding
+    /// to the user) prevents the command handler from running.  The default
+    /// behavior of this method is to do nothing.
+    async fn before<'a, 'b>(
+        &self,
+        ctx: &Context,
+        command: &str,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'a, 'b, S>,
+    ) -> Result<CommandResponder<'a, 'b, S>, CommandError<'b, S>> {
+        let _ = (ctx, command, visitor);
+        Ok(responder)
+    }
+
+    /// Observe the result of a command after it has been dispatched
+    ///
+    /// This is called for every command whose middleware was run, including
+    /// commands rejected by other middleware.  The default behavior of this
+    /// method is to do nothing.
+    #[inline]
+    fn after(&self, command: &str, elapsed: Duration, result: &CommandResult<'_, S>) {
+        let _ = (command, elapsed, result);
+    }
+}
+
+#[derive(Debug)]
+enum Mount {
+    Prefix(String),
+    Nested { name: String, desc: String },
+}
+
+/// A set of command handlers mounted under a shared name
+///
+/// Groups can either register each handler as its own command with a common
+/// prefix added to its name, or register a single command with each handler
+/// as one of its subcommands.  Both forms run the group's middleware, in the
+/// order it was added, before dispatching to the selected handler.
+///
+/// Since group names are applied at registration time, the names seen by the
+/// [`Registry`](super::Registry) are stable between runs and regrouping
+/// commands is treated as a rename rather than a delete-and-create.
+#[derive(Debug)]
+pub struct CommandGroup<S> {
+    mount: Mount,
+    handlers: Vec<Arc<dyn CommandHandler<S>>>,
+    middleware: Vec<Arc<dyn CommandMiddleware<S>>>,
+}
+
+impl<S> CommandGroup<S> {
+    #[inline]
+    fn new(mount: Mount) -> Self {
+        Self {
+            mount,
+            handlers: vec![],
+            middleware: vec![],
+        }
+    }
+
+    /// Construct a new group registering each handler as a separate command,
+    /// with the given prefix added to its name
+    #[inline]
+    #[must_use]
+    pub fn prefixed(prefix: impl Into<String>) -> Self { Self::new(Mount::Prefix(prefix.into())) }
+
+    /// Construct a new group registering a single chat input command, with
+    /// each handler as a subcommand
+    ///
+    /// Only chat input commands with at most one level of subcommands can be
+    /// nested.
+    #[inline]
+    #[must_use]
+    pub fn nested(name: impl Into<String>, desc: impl Into<String>) -> Self {
+        Self::new(Mount::Nested {
+            name: name.into(),
+            desc: desc.into(),
+        })
+    }
+}
+
+#[builder(trait_name = CommandGroupExt)]
+/// Helper methods for mutating [`CommandGroup`]
+impl<S> CommandGroup<S> {
+    /// Add a command handler to this group
+    pub fn handler(&mut self, handler: Arc<dyn CommandHandler<S>>) { self.handlers.push(handler); }
+
+    /// Add several command handlers to this group
+    pub fn handlers(&mut self, handlers: impl IntoIterator<Item = Arc<dyn CommandHandler<S>>>) {
+        self.handlers.extend(handlers);
+    }
+
+    /// Add middleware to be run before every command in this group
+    pub fn middleware(&mut self, middleware: Arc<dyn CommandMiddleware<S>>) {
+        self.middleware.push(middleware);
+    }
+}
+
+impl<S: Schema + 'static> Handlers<S> {
+    /// Add all handlers in the given group to this set
+    ///
+    /// # Errors
+    /// This method returns an error if the group is nested and any of its
+    /// handlers cannot be registered as a subcommand.
+    pub fn mount(&mut self, group: CommandGroup<S>) -> Result<(), TryFromError> {
+        let CommandGroup {
+            mount,
+            handlers,
+            middleware,
+        } = group;
+        let middleware: Arc<[_]> = middleware.into();
+
+        match mount {
+            Mount::Prefix(prefix) => {
+                let prefix: Arc<str> = prefix.into();
+
+                self.commands.extend(handlers.into_iter().map(|inner| {
+                    Arc::new(Prefixed {
+                        prefix: Arc::clone(&prefix),
+                        inner,
+                        middleware: Arc::clone(&middleware),
+                    }) as Arc<dyn CommandHandler<S>>
+                }));
+            },
+            Mount::Nested { name, desc } => {
+                let mut children = BTreeMap::new();
+                let info = CommandInfo::nest(
+                    name,
+                    desc,
+                    handlers.into_iter().map(|h| {
+                        let info = h.register_global();
+                        children.insert(info.name().clone(), h);
+                        info
+                    }),
+                )?;
+
+                self.commands.push(Arc::new(Nested {
+                    info,
+                    children,
+                    middleware,
+                }));
+            },
+        }
+
+        Ok(())
+    }
+}
+
+async fn dispatch<'a, S: Schema>(
+    middleware: &[Arc<dyn CommandMiddleware<S>>],
+    command: &str,
+    handler: &dyn CommandHandler<S>,
+    ctx: &Context,
+    visitor: &mut CommandVisitor<'_>,
+    responder: CommandResponder<'_, 'a, S>,
+) -> CommandResult<'a, S> {
+    let start = Instant::now();
+    let res = async {
+        let mut responder = responder;
+
+        for m in middleware {
+            responder = m.before(ctx, command, visitor, responder).await?;
+        }
+
+        handler.respond(ctx, visitor, responder).await
+    }
+    .await;
+
+    let elapsed = start.elapsed();
+    for m in middleware {
+        m.after(command, elapsed, &res);
+    }
+
+    res
+}
+
+#[derive(Debug)]
+struct Prefixed<S> {
+    prefix: Arc<str>,
+    inner: Arc<dyn CommandHandler<S>>,
+    middleware: Arc<[Arc<dyn CommandMiddleware<S>>]>,
+}
+
+#[async_trait::async_trait]
+impl<S: Schema> CommandHandler<S> for Prefixed<S> {
+    fn register_global(&self) -> CommandInfo {
+        let mut info = self.inner.register_global();
+        info.prefix_name(&self.prefix);
+        info
+    }
+
+    fn register_guild(&self, id: GuildId) -> Option<CommandInfo> {
+        let mut info = self.inner.register_guild(id)?;
+        info.prefix_name(&self.prefix);
+        Some(info)
+    }
+
+    #[inline]
+    fn auto_defer(&self) -> Option<AutoDefer> { self.inner.auto_defer() }
+
+    async fn complete(
+        &self,
+        ctx: &Context,
+        visitor: &mut CompletionVisitor<'_>,
+    ) -> CompletionResult {
+        self.inner.complete(ctx, visitor).await
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a, S>,
+    ) -> CommandResult<'a, S> {
+        let command = format!("{}{}", self.prefix, visitor.int.data.name);
+        dispatch(
+            &self.middleware,
+            &command,
+            &*self.inner,
+            ctx,
+            visitor,
+            responder,
+        )
+        .await
+    }
+}
+
+#[derive(Debug)]
+struct Nested<S> {
+    info: CommandInfo,
+    children: BTreeMap<String, Arc<dyn CommandHandler<S>>>,
+    middleware: Arc<[Arc<dyn CommandMiddleware<S>>]>,
+}
+
+#[async_trait::async_trait]
+impl<S: Schema> CommandHandler<S> for Nested<S> {
+    #[inline]
+    fn register_global(&self) -> CommandInfo { self.info.clone() }
+
+    // The subcommand isn't known until the command is dispatched, so defer on
+    // the earliest schedule of any of them
+    fn auto_defer(&self) -> Option<AutoDefer> {
+        self.children
+            .values()
+            .filter_map(|c| c.auto_defer())
+            .min_by_key(|d| d.after)
+    }
+
+    async fn complete(
+        &self,
+        ctx: &Context,
+        visitor: &mut CompletionVisitor<'_>,
+    ) -> CompletionResult {
+        let name = visitor.descend()?;
+        let Some(child) = self.children.get(name) else {
+            return Ok(vec![]);
+        };
+
+        child.complete(ctx, visitor).await
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a, S>,
+    ) -> CommandResult<'a, S> {
+        let name = visitor.descend()?;
+        let child = self
+            .children
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown subcommand {name:?} in command group"))?;
+        let command = format!("{} {name}", self.info.name());
+
+        dispatch(
+            &self.middleware,
+            &command,
+            &**child,
+            ctx,
+            visitor,
+            responder,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interaction::command::CommandInfo;
+
+    fn slash(name: &str) -> CommandInfo {
+        CommandInfo::build_slash(name, "A command", |a| {
+            a.string("text", "Some text", true, 1..=10)
+        })
+        .unwrap()
+    }
+
+    fn branch(name: &str, depth: u8) -> CommandInfo {
+        CommandInfo::build_slash(name, "A command", |a| {
+            (1..depth).fold(a.build_subcmd("leaf", "A subcommand", |a| a), |a, i| {
+                a.build_subcmd(format!("branch{i}"), "A subcommand group", |a| {
+                    a.build_subcmd("leaf", "A subcommand", |a| a)
+                })
+            })
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn nest_commands() {
+        let mut prefixed = slash("b");
+        prefixed.prefix_name("q");
+        assert_eq!(prefixed.name(), "qb");
+
+        let nested = CommandInfo::nest("q", "Group", [slash("a"), branch("b", 1)]).unwrap();
+        assert_eq!(nested.name(), "q");
+        assert_eq!(
+            CommandInfo::nest("q", "Group", [branch("b", 1), slash("a")]).unwrap(),
+            nested,
+        );
+
+        assert!(CommandInfo::nest("q", "Group", [branch("b", 2)]).is_err());
+        assert!(CommandInfo::nest("q", "Group", [CommandInfo::message("b")]).is_err());
+        assert!(CommandInfo::nest("q", "Group", [slash("a"), slash("a")]).is_err());
+        assert!(CommandInfo::nest("q", "Group", []).is_err());
+    }
+}
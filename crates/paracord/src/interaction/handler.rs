@@ -1,16 +1,14 @@
 //! Traits for defining handler logic for various interactions
 
-use std::{fmt, sync::Arc};
-
-use serenity::{
-    client::Context,
-    model::{
-        application::{CommandInteraction, ComponentInteraction, ModalInteraction},
-        id::GuildId,
-    },
+use std::{collections::BTreeMap, fmt, sync::Arc, time::Duration};
+
+use serenity::model::{
+    application::{CommandInteraction, CommandType, ComponentInteraction, ModalInteraction},
+    id::GuildId,
 };
 
 use super::{command::CommandInfo, completion::Completion, response, rpc, visitor};
+use crate::client::Context;
 
 /// Helper trait for constructing an error response
 pub trait IntoErr<E> {
@@ -30,6 +28,103 @@ pub struct Handlers<S: rpc::Schema> {
     pub modals: Vec<Arc<dyn RpcHandler<S, S::ModalKey>>>,
 }
 
+/// A command name and type claimed by more than one command handler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameCollision {
+    /// The type of the conflicting commands
+    pub kind: CommandType,
+    /// The name of the conflicting commands
+    pub name: String,
+    /// The names of every handler registering a command with this name and
+    /// type, as returned by [`CommandHandler::handler_name`]
+    pub handlers: Vec<&'static str>,
+}
+
+impl fmt::Display for NameCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            CommandType::ChatInput => "slash command",
+            CommandType::User => "user command",
+            CommandType::Message => "message command",
+            _ => "command",
+        };
+
+        write!(
+            f,
+            "{kind} {:?} is registered by {}",
+            self.name,
+            self.handlers.join(", ")
+        )
+    }
+}
+
+/// An error indicating that multiple command handlers register commands that
+/// Discord would consider the same
+#[derive(Debug, thiserror::Error)]
+#[error("{}", Collisions(.0))]
+pub struct CollisionError(pub Vec<NameCollision>);
+
+struct Collisions<'a>(&'a [NameCollision]);
+
+impl fmt::Display for Collisions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Conflicting command names:")?;
+        for collision in self.0 {
+            write!(f, "\n  {collision}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: rpc::Schema> Handlers<S> {
+    /// Check that no two command handlers register a global command with the
+    /// same name and type
+    ///
+    /// This is checked by [`Registry::init`](super::registry::Registry::init)
+    /// before any commands are registered, but can also be called beforehand
+    /// (e.g. in a test) to catch conflicts between name prefixes early.
+    ///
+    /// # Errors
+    /// This method returns an error listing every conflicting name if any
+    /// are found.
+    pub fn check_names(&self) -> Result<(), CollisionError> {
+        let collisions = find_collisions(
+            self.commands
+                .iter()
+                .map(|c| (c.register_global(), c.handler_name())),
+        );
+
+        if collisions.is_empty() {
+            Ok(())
+        } else {
+            Err(CollisionError(collisions))
+        }
+    }
+}
+
+fn find_collisions(
+    commands: impl IntoIterator<Item = (CommandInfo, &'static str)>,
+) -> Vec<NameCollision> {
+    let mut names: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+    for (info, handler) in commands {
+        names
+            .entry((info.kind(), info.name().clone()))
+            .or_default()
+            .push(handler);
+    }
+
+    names
+        .into_iter()
+        .filter(|(_, h)| h.len() > 1)
+        .map(|((kind, name), handlers)| NameCollision {
+            kind,
+            name,
+            handlers,
+        })
+        .collect()
+}
+
 // TODO: Component and Modal should have dedicated visitors
 /// Visitor for command interactions
 pub type CommandVisitor<'a> = visitor::CommandVisitor<'a, CommandInteraction>;
@@ -87,6 +182,26 @@ pub enum CompletionError {
 /// Return type for the autocomplete interaction handler
 pub type CompletionResult = Result<Vec<Completion>, CompletionError>;
 
+/// Options for automatically deferring a slow command response
+#[derive(Debug, Clone, Copy)]
+pub struct AutoDefer {
+    /// How long to wait for the handler to respond before deferring
+    pub after: Duration,
+    /// Options for the deferred response message
+    pub opts: response::MessageOpts,
+}
+
+impl AutoDefer {
+    /// Defer after the given delay with the given message options
+    ///
+    /// The deferred message's visibility cannot be changed later, so `opts`
+    /// should match the options of the message the handler eventually sends,
+    /// in particular whether it is ephemeral.
+    #[inline]
+    #[must_use]
+    pub fn new(after: Duration, opts: response::MessageOpts) -> Self { Self { after, opts } }
+}
+
 /// A handler for a command interaction and its associated autocomplete
 /// interactions
 #[async_trait::async_trait]
@@ -94,6 +209,13 @@ pub trait CommandHandler<S>: fmt::Debug + Send + Sync {
     /// Provide registration data for this command within the global context
     fn register_global(&self) -> CommandInfo;
 
+    /// Get a name identifying this handler in diagnostics
+    ///
+    /// The default behavior of this method is to return the name of the
+    /// implementing type.
+    #[inline]
+    fn handler_name(&self) -> &'static str { std::any::type_name::<Self>() }
+
     /// Provide registration data for this command within the context of a guild
     #[inline]
     fn register_guild(&self, id: GuildId) -> Option<CommandInfo> {
@@ -101,6 +223,16 @@ pub trait CommandHandler<S>: fmt::Debug + Send + Sync {
         None
     }
 
+    /// Configure automatic deferral for this command's responses
+    ///
+    /// If this returns a value and the handler has not created a response
+    /// within the given delay, a deferred message response is sent on its
+    /// behalf, and any message response it creates afterwards is sent as an
+    /// edit of the deferred message.  The default behavior of this method is
+    /// to never defer automatically.
+    #[inline]
+    fn auto_defer(&self) -> Option<AutoDefer> { None }
+
     /// Respond to an autocomplete interaction
     ///
     /// The default behavior of this method is to return an empty list.
@@ -148,6 +280,18 @@ pub trait RpcHandler<S, K: rpc::Key>: fmt::Debug + Send + Sync {
     /// Register the ID type keys to which this handler can respond
     fn register_keys(&self) -> &'static [K];
 
+    /// Get the maximum age of a message whose components this handler will
+    /// respond to
+    ///
+    /// Component interactions on messages last sent or edited longer ago than
+    /// this are rejected with a standard message, and the components of the
+    /// stale message are disabled.  This is only consulted for message
+    /// components, and is checked in addition to any expiry time encoded in
+    /// the component ID itself.  The default behavior of this method is to
+    /// never expire components.
+    #[inline]
+    fn expiry(&self) -> Option<Duration> { None }
+
     /// Respond to an RPC interaction
     async fn respond<'a>(
         &self,
@@ -157,3 +301,38 @@ pub trait RpcHandler<S, K: rpc::Key>: fmt::Debug + Send + Sync {
         responder: response::BorrowingResponder<'_, 'a, S, K::Interaction>,
     ) -> ResponseResult<'a, S, K::Interaction>;
 }
+
+#[cfg(test)]
+mod test {
+    use serenity::model::application::CommandType;
+
+    use super::find_collisions;
+    use crate::interaction::command::CommandInfo;
+
+    #[test]
+    fn collisions() {
+        let slash = |n| CommandInfo::build_slash(n, "A command", |a| a).unwrap();
+        let collisions = find_collisions([
+            (CommandInfo::message("Test"), "A"),
+            (CommandInfo::user("Test"), "B"),
+            (CommandInfo::message("Test"), "C"),
+            (slash("test"), "D"),
+            (slash("q"), "E"),
+            (slash("q"), "F"),
+            (slash("q"), "G"),
+        ]);
+
+        let summary: Vec<_> = collisions
+            .iter()
+            .map(|c| (c.kind, c.name.as_str(), c.handlers.as_slice()))
+            .collect();
+        assert_eq!(summary, [
+            (CommandType::ChatInput, "q", ["E", "F", "G"].as_slice()),
+            (CommandType::Message, "Test", ["A", "C"].as_slice()),
+        ]);
+        assert_eq!(
+            collisions[1].to_string(),
+            "message command \"Test\" is registered by A, C"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Typed language tags for the locales Discord reports with interactions
+
+use std::{fmt, str::FromStr};
+
+/// An error arising from parsing a string that is not a Discord locale code
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown Discord locale {0:?}")]
+pub struct UnknownLocale(pub String);
+
+macro_rules! discord_locales {
+    ($($(#[$meta:meta])* $var:ident => $code:literal, $name:literal;)*) => {
+        /// A locale supported by the Discord client
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        pub enum DiscordLocale {
+            $($(#[$meta])* $var,)*
+        }
+
+        impl DiscordLocale {
+            /// Every locale supported by the Discord client, in the order
+            /// Discord documents them
+            pub const ALL: &'static [Self] = &[$(Self::$var),*];
+
+            /// Get the code Discord uses for this locale, e.g. `en-US`
+            #[must_use]
+            pub const fn code(self) -> &'static str {
+                match self {
+                    $(Self::$var => $code,)*
+                }
+            }
+
+            /// Get the English name of this locale, e.g. `English, US`
+            #[must_use]
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(Self::$var => $name,)*
+                }
+            }
+        }
+    };
+}
+
+discord_locales! {
+    /// `id`
+    Indonesian => "id", "Indonesian";
+    /// `da`
+    Danish => "da", "Danish";
+    /// `de`
+    German => "de", "German";
+    /// `en-GB`
+    EnglishUk => "en-GB", "English, UK";
+    /// `en-US`
+    EnglishUs => "en-US", "English, US";
+    /// `es-ES`
+    Spanish => "es-ES", "Spanish";
+    /// `es-419`
+    SpanishLatam => "es-419", "Spanish, LATAM";
+    /// `fr`
+    French => "fr", "French";
+    /// `hr`
+    Croatian => "hr", "Croatian";
+    /// `it`
+    Italian => "it", "Italian";
+    /// `lt`
+    Lithuanian => "lt", "Lithuanian";
+    /// `hu`
+    Hungarian => "hu", "Hungarian";
+    /// `nl`
+    Dutch => "nl", "Dutch";
+    /// `no`
+    Norwegian => "no", "Norwegian";
+    /// `pl`
+    Polish => "pl", "Polish";
+    /// `pt-BR`
+    PortugueseBr => "pt-BR", "Portuguese, Brazilian";
+    /// `ro`
+    Romanian => "ro", "Romanian";
+    /// `fi`
+    Finnish => "fi", "Finnish";
+    /// `sv-SE`
+    Swedish => "sv-SE", "Swedish";
+    /// `vi`
+    Vietnamese => "vi", "Vietnamese";
+    /// `tr`
+    Turkish => "tr", "Turkish";
+    /// `cs`
+    Czech => "cs", "Czech";
+    /// `el`
+    Greek => "el", "Greek";
+    /// `bg`
+    Bulgarian => "bg", "Bulgarian";
+    /// `ru`
+    Russian => "ru", "Russian";
+    /// `uk`
+    Ukrainian => "uk", "Ukrainian";
+    /// `hi`
+    Hindi => "hi", "Hindi";
+    /// `th`
+    Thai => "th", "Thai";
+    /// `zh-CN`
+    ChineseCn => "zh-CN", "Chinese, China";
+    /// `ja`
+    Japanese => "ja", "Japanese";
+    /// `zh-TW`
+    ChineseTw => "zh-TW", "Chinese, Taiwan";
+    /// `ko`
+    Korean => "ko", "Korean";
+}
+
+impl DiscordLocale {
+    /// Get the primary language subtag of this locale, e.g. `en` for `en-US`
+    #[inline]
+    #[must_use]
+    pub fn language(self) -> &'static str { primary_subtag(self.code()) }
+}
+
+impl FromStr for DiscordLocale {
+    type Err = UnknownLocale;
+
+    /// Parse a Discord locale code, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|l| l.code().eq_ignore_ascii_case(s))
+            .ok_or_else(|| UnknownLocale(s.into()))
+    }
+}
+
+impl fmt::Display for DiscordLocale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.code()) }
+}
+
+fn primary_subtag(tag: &str) -> &str { tag.split_once('-').map_or(tag, |(l, _)| l) }
+
+/// A language tag reported by Discord for a user or guild
+///
+/// Discord only sends the codes listed in [`DiscordLocale`], but new locales
+/// are added from time to time, so unrecognized tags are kept as-is rather
+/// than rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Locale(String);
+
+impl Locale {
+    /// Wrap a raw language tag
+    #[inline]
+    #[must_use]
+    pub fn new(tag: impl Into<String>) -> Self { Self(tag.into()) }
+
+    /// Get the raw language tag, e.g. `en-US`
+    #[inline]
+    #[must_use]
+    pub fn tag(&self) -> &str { &self.0 }
+
+    /// Get the primary language subtag, e.g. `en` for `en-US`
+    #[inline]
+    #[must_use]
+    pub fn language(&self) -> &str { primary_subtag(&self.0) }
+
+    /// Parse this tag as a known Discord locale, returning `None` if it is
+    /// not one
+    #[inline]
+    #[must_use]
+    pub fn discord(&self) -> Option<DiscordLocale> { self.0.parse().ok() }
+}
+
+impl From<DiscordLocale> for Locale {
+    #[inline]
+    fn from(locale: DiscordLocale) -> Self { Self::new(locale.code()) }
+}
+
+impl AsRef<str> for Locale {
+    #[inline]
+    fn as_ref(&self) -> &str { &self.0 }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.0) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DiscordLocale, Locale};
+
+    #[test]
+    fn parse() {
+        assert_eq!(DiscordLocale::ALL.len(), 32);
+        for &locale in DiscordLocale::ALL {
+            assert_eq!(locale.code().parse::<DiscordLocale>().unwrap(), locale);
+        }
+
+        assert_eq!(
+            "EN-us".parse::<DiscordLocale>().unwrap(),
+            DiscordLocale::EnglishUs
+        );
+        assert!("en".parse::<DiscordLocale>().is_err());
+        assert_eq!(DiscordLocale::SpanishLatam.language(), "es");
+
+        let locale = Locale::new("pt-BR");
+        assert_eq!(locale.language(), "pt");
+        assert_eq!(locale.discord(), Some(DiscordLocale::PortugueseBr));
+        assert_eq!(Locale::new("tlh").discord(), None);
+        assert_eq!(Locale::from(DiscordLocale::Korean).tag(), "ko");
+    }
+}
@@ -1,12 +1,26 @@
 //! Types and support traits for responding to application interaction events
 
+pub mod audit;
+pub mod collector;
 pub mod command;
 pub mod completion;
+pub mod config;
+pub mod context;
+pub mod dispatch;
+pub mod failure;
+pub mod group;
 pub mod handler;
+pub mod locale;
+pub mod observe;
+mod reconcile;
 mod registry;
 pub mod response;
+mod ring;
 pub mod rpc;
+pub mod state;
 pub mod visitor;
+#[cfg(feature = "webhook")]
+pub mod webhook;
 
 pub use registry::Registry;
 
@@ -0,0 +1,68 @@
+//! Hooks for observing the outcome and latency of handled interactions
+
+use std::{fmt, time::Duration};
+
+/// How the handling of an interaction concluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    /// The handler ran and completed successfully
+    Ok,
+    /// The handler raised an error, panicked, or its response could not be
+    /// sent
+    Error,
+    /// The interaction was turned away before reaching a handler, e.g.
+    /// because the bot was busy or the component had expired
+    Rejected,
+}
+
+impl Outcome {
+    /// Get a short lowercase name for this outcome, e.g. `"ok"`
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Error => "error",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.as_str()) }
+}
+
+/// A summary of a single handled interaction
+///
+/// Unlike the descriptions given to a
+/// [`FailureSink`](super::failure::FailureSink), the name here only
+/// identifies the handler and not its arguments, so it is suitable for use as
+/// a metric label.
+#[derive(Debug, Clone)]
+pub struct Handled {
+    /// The kind of interaction handled, e.g. `"command"`
+    pub kind: &'static str,
+    /// The command name or RPC key of the handler, or `"unknown"` if the
+    /// interaction could not be routed
+    pub name: String,
+    /// How handling concluded
+    pub outcome: Outcome,
+    /// The time taken to handle the interaction and send its response
+    pub latency: Duration,
+}
+
+impl Handled {
+    pub(super) fn new(kind: &'static str, name: impl Into<String>) -> Self {
+        Self {
+            kind,
+            name: name.into(),
+            outcome: Outcome::Ok,
+            latency: Duration::ZERO,
+        }
+    }
+}
+
+/// A destination for reports of handled interactions
+pub trait Observer: fmt::Debug + Send + Sync {
+    /// Record that an interaction was handled
+    fn handled(&self, event: &Handled);
+}
@@ -0,0 +1,400 @@
+//! Deletion of guild-scoped commands left behind by removed handlers
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use chrono::Utc;
+use serenity::model::{
+    application::Command,
+    id::{CommandId, GuildId},
+};
+use tokio::sync::Mutex;
+
+use super::{
+    audit::{AuditSink, CommandMutation, GuildReconciliation, MutationKind},
+    command::{self, RegisteredCommand},
+};
+use crate::client::Context;
+
+/// The Discord API calls made while reconciling a guild
+#[async_trait]
+pub(super) trait GuildCommandApi: Send + Sync {
+    /// Fetch the guild-scoped commands registered in a guild
+    async fn commands(&self, guild: GuildId) -> serenity::Result<Vec<Command>>;
+
+    /// Decide whether the deletions in the given plan may be made
+    async fn approve(&self, plan: &command::CommandPlan) -> bool;
+
+    /// Delete a guild-scoped command
+    async fn delete(&self, guild: GuildId, id: CommandId) -> serenity::Result<()>;
+}
+
+/// [`GuildCommandApi`] implementation backed by a client context
+#[derive(Debug)]
+pub(super) struct ContextApi {
+    pub ctx: Context,
+    pub gate: Option<Arc<dyn command::SyncGate>>,
+    pub dry_run: bool,
+}
+
+#[async_trait]
+impl GuildCommandApi for ContextApi {
+    async fn commands(&self, guild: GuildId) -> serenity::Result<Vec<Command>> {
+        guild.get_commands(&self.ctx.http).await
+    }
+
+    async fn approve(&self, plan: &command::CommandPlan) -> bool {
+        if self.dry_run {
+            false
+        } else if let Some(ref gate) = self.gate {
+            gate.approve(&self.ctx, plan).await
+        } else {
+            true
+        }
+    }
+
+    async fn delete(&self, guild: GuildId, id: CommandId) -> serenity::Result<()> {
+        guild.delete_command(&self.ctx.http, id).await
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    Done,
+}
+
+/// Tracks which guilds have had their orphaned commands deleted
+#[derive(Debug, Default)]
+pub(super) struct Reconciler {
+    keep: Option<HashSet<String>>,
+    guilds: Mutex<HashMap<GuildId, State>>,
+}
+
+impl Reconciler {
+    /// Enable reconciliation, keeping guild commands with the given names
+    pub fn new(keep: HashSet<String>) -> Self {
+        Self {
+            keep: Some(keep),
+            guilds: Mutex::default(),
+        }
+    }
+
+    /// Delete the orphaned commands of a guild, returning `None` if
+    /// reconciliation is disabled or the guild is already reconciled or
+    /// being reconciled
+    ///
+    /// A guild is only marked as reconciled once all its orphans were
+    /// deleted, so a guild whose deletions were not approved is reconciled
+    /// again the next time this is called for it.
+    pub async fn run(
+        &self,
+        api: &dyn GuildCommandApi,
+        guild: GuildId,
+        audit: Option<&dyn AuditSink>,
+    ) -> Result<Option<GuildReconciliation>, anyhow::Error> {
+        let Some(ref keep) = self.keep else {
+            return Ok(None);
+        };
+
+        {
+            let mut guilds = self.guilds.lock().await;
+            if guilds.contains_key(&guild) {
+                return Ok(None);
+            }
+            guilds.insert(guild, State::Running);
+        }
+
+        let res = Self::run_once(api, keep, guild, audit).await;
+
+        let mut guilds = self.guilds.lock().await;
+        match res {
+            Ok(ref s) if s.skipped.is_empty() => guilds.insert(guild, State::Done),
+            _ => guilds.remove(&guild),
+        };
+        drop(guilds);
+
+        let summary = res?;
+
+        if !summary.is_empty() {
+            tracing::info!("{summary}");
+        }
+
+        if let Some(audit) = audit {
+            audit.reconciled(summary.clone());
+        }
+
+        Ok(Some(summary))
+    }
+
+    async fn run_once(
+        api: &dyn GuildCommandApi,
+        keep: &HashSet<String>,
+        guild: GuildId,
+        audit: Option<&dyn AuditSink>,
+    ) -> Result<GuildReconciliation, anyhow::Error> {
+        let existing = api
+            .commands(guild)
+            .await
+            .with_context(|| format!("Error fetching commands for guild {guild}"))?;
+
+        let (kept, orphans): (Vec<_>, Vec<_>) =
+            existing.into_iter().partition(|c| keep.contains(&c.name));
+
+        let mut summary = GuildReconciliation {
+            time: Utc::now(),
+            guild,
+            kept: kept.into_iter().map(|c| c.name).collect(),
+            deleted: vec![],
+            skipped: vec![],
+        };
+
+        // Commands of unknown types cannot be described in the plan, so they
+        // are left alone rather than deleted without the gate seeing them
+        let mut described = Vec::with_capacity(orphans.len());
+        for cmd in orphans {
+            match RegisteredCommand::try_from(cmd.clone()) {
+                Ok(reg) => described.push((cmd, reg.info)),
+                Err(err) => {
+                    tracing::warn!(
+                        ?err,
+                        "Leaving guild command {:?} (ID {:?}) of unknown type",
+                        cmd.name,
+                        cmd.id,
+                    );
+                    summary.kept.push(cmd.name);
+                },
+            }
+        }
+        let orphans = described;
+
+        if orphans.is_empty() {
+            return Ok(summary);
+        }
+
+        let plan = command::CommandPlan {
+            unchanged: 0,
+            changes: orphans
+                .iter()
+                .map(|(c, info)| command::PlannedChange::Delete {
+                    id: c.id,
+                    old: info.clone(),
+                })
+                .collect(),
+        };
+
+        if !api.approve(&plan).await {
+            summary.skipped = orphans.into_iter().map(|(c, _)| c.name).collect();
+            summary.time = Utc::now();
+            return Ok(summary);
+        }
+
+        for (cmd, info) in orphans {
+            tracing::info!(
+                "Deleting orphaned guild command {:?} (ID {:?})",
+                cmd.name,
+                cmd.id,
+            );
+            api.delete(guild, cmd.id)
+                .await
+                .with_context(|| format!("Error deleting guild command {:?}", cmd.name))?;
+
+            if let Some(audit) = audit {
+                audit.record(CommandMutation {
+                    time: Utc::now(),
+                    actor: cmd.application_id,
+                    guild: Some(guild),
+                    id: cmd.id,
+                    kind: MutationKind::Delete,
+                    old: Some(info),
+                    new: None,
+                });
+            }
+            summary.deleted.push(cmd.name);
+        }
+
+        summary.time = Utc::now();
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashSet,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
+
+    use async_trait::async_trait;
+    use serenity::model::{
+        application::Command,
+        id::{CommandId, GuildId},
+    };
+
+    use super::{GuildCommandApi, Reconciler};
+    use crate::interaction::command::CommandPlan;
+
+    const GUILD: GuildId = GuildId::new(1);
+
+    #[derive(Debug, Default)]
+    struct MockApi {
+        commands: Mutex<Vec<Command>>,
+        approve: AtomicBool,
+        fetches: AtomicUsize,
+        plans: AtomicUsize,
+    }
+
+    impl MockApi {
+        fn new(names: &[&str]) -> Self {
+            let commands = names
+                .iter()
+                .zip(1_u64..)
+                .map(|(name, id)| {
+                    serde_json::from_value(serde_json::json!({
+                        "id": id.to_string(),
+                        "type": 1,
+                        "application_id": "2",
+                        "guild_id": GUILD.to_string(),
+                        "name": name,
+                        "description": "test",
+                        "default_member_permissions": null,
+                        "version": "1",
+                    }))
+                    .unwrap()
+                })
+                .collect();
+
+            Self {
+                commands: Mutex::new(commands),
+                ..Self::default()
+            }
+        }
+
+        fn push_unknown(&self, name: &str) {
+            let mut cmds = self.commands.lock().unwrap();
+            let id = cmds.len() + 1;
+            cmds.push(
+                serde_json::from_value(serde_json::json!({
+                    "id": id.to_string(),
+                    "type": 99,
+                    "application_id": "2",
+                    "guild_id": GUILD.to_string(),
+                    "name": name,
+                    "description": "",
+                    "default_member_permissions": null,
+                    "version": "1",
+                }))
+                .unwrap(),
+            );
+        }
+
+        fn names(&self) -> Vec<String> {
+            let cmds = self.commands.lock().unwrap();
+            cmds.iter().map(|c| c.name.clone()).collect()
+        }
+    }
+
+    #[async_trait]
+    impl GuildCommandApi for MockApi {
+        async fn commands(&self, guild: GuildId) -> serenity::Result<Vec<Command>> {
+            assert_eq!(guild, GUILD);
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(self.commands.lock().unwrap().clone())
+        }
+
+        async fn approve(&self, plan: &CommandPlan) -> bool {
+            assert!(!plan.changes.is_empty());
+            self.plans.fetch_add(1, Ordering::SeqCst);
+            self.approve.load(Ordering::SeqCst)
+        }
+
+        async fn delete(&self, guild: GuildId, id: CommandId) -> serenity::Result<()> {
+            assert_eq!(guild, GUILD);
+            self.commands.lock().unwrap().retain(|c| c.id != id);
+            Ok(())
+        }
+    }
+
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    fn keep(names: &[&str]) -> HashSet<String> { names.iter().map(|&n| n.into()).collect() }
+
+    #[test]
+    fn disabled() {
+        let api = MockApi::new(&["old"]);
+        let res = block_on(Reconciler::default().run(&api, GUILD, None)).unwrap();
+
+        assert!(res.is_none());
+        assert_eq!(api.fetches.load(Ordering::SeqCst), 0);
+        assert_eq!(api.names(), ["old"]);
+    }
+
+    #[test]
+    fn rejected() {
+        let api = MockApi::new(&["old", "kept"]);
+        let rec = Reconciler::new(keep(&["kept"]));
+
+        let summary = block_on(rec.run(&api, GUILD, None)).unwrap().unwrap();
+        assert_eq!(summary.kept, ["kept"]);
+        assert_eq!(summary.skipped, ["old"]);
+        assert!(summary.deleted.is_empty());
+        assert_eq!(api.names(), ["old", "kept"]);
+
+        // A rejected guild is not marked as reconciled, so it is retried
+        api.approve.store(true, Ordering::SeqCst);
+        let summary = block_on(rec.run(&api, GUILD, None)).unwrap().unwrap();
+        assert_eq!(summary.deleted, ["old"]);
+        assert!(summary.skipped.is_empty());
+        assert_eq!(api.names(), ["kept"]);
+        assert_eq!(api.plans.load(Ordering::SeqCst), 2);
+
+        assert!(block_on(rec.run(&api, GUILD, None)).unwrap().is_none());
+        assert_eq!(api.fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn nothing_orphaned() {
+        let api = MockApi::new(&["kept"]);
+        let rec = Reconciler::new(keep(&["kept"]));
+
+        let summary = block_on(rec.run(&api, GUILD, None)).unwrap().unwrap();
+        assert_eq!(summary.kept, ["kept"]);
+        assert_eq!(api.plans.load(Ordering::SeqCst), 0);
+        assert!(block_on(rec.run(&api, GUILD, None)).unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_type() {
+        let api = MockApi::new(&["old", "kept"]);
+        api.push_unknown("mystery");
+        api.approve.store(true, Ordering::SeqCst);
+        let rec = Reconciler::new(keep(&["kept"]));
+
+        let summary = block_on(rec.run(&api, GUILD, None)).unwrap().unwrap();
+        assert_eq!(summary.kept, ["kept", "mystery"]);
+        assert_eq!(summary.deleted, ["old"]);
+        assert_eq!(api.names(), ["kept", "mystery"]);
+        assert!(block_on(rec.run(&api, GUILD, None)).unwrap().is_none());
+
+        // A guild with nothing but unknown orphans never asks for approval
+        let api = MockApi::new(&[]);
+        api.push_unknown("mystery");
+        let rec = Reconciler::new(keep(&[]));
+        let summary = block_on(rec.run(&api, GUILD, None)).unwrap().unwrap();
+        assert_eq!(summary.kept, ["mystery"]);
+        assert_eq!(api.plans.load(Ordering::SeqCst), 0);
+        assert_eq!(api.names(), ["mystery"]);
+    }
+}
@@ -1,36 +1,59 @@
 use std::{
-    collections::{BinaryHeap, HashMap, HashSet},
+    any::Any,
+    collections::HashMap,
     fmt::{self, Write},
+    future::Future,
+    panic::AssertUnwindSafe,
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use anyhow::Context as _;
-use ordered_float::OrderedFloat;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures_util::{future::join_all, FutureExt};
+use serde_json::Value;
 use serenity::{
     builder::{CreateAutocompleteResponse, CreateInteractionResponse},
-    client::{Cache, Context},
+    client::Cache,
     model::{
         application::{
-            Command, CommandData, CommandInteraction, CommandType, ComponentInteraction,
+            ActionRow, Command, CommandData, CommandInteraction, CommandType, ComponentInteraction,
             ComponentInteractionDataKind, ModalInteraction, ResolvedOption, ResolvedValue,
         },
-        id::{ChannelId, CommandId, GuildId, InteractionId},
+        channel::{MessageFlags, Reaction},
+        gateway::GatewayIntents,
+        id::{ChannelId, CommandId, GuildId, InteractionId, UserId},
         user::User,
     },
+    utils::MessageBuilder,
 };
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
+use tracing::Instrument;
+use url::Url;
 
 use super::{
+    audit::{AuditSink, CommandMutation, GuildReconciliation, MutationKind},
+    collector::{self, Collectors},
     command,
     command::RegisteredCommand,
+    context::InteractionCtx,
+    dispatch::{self, DispatchOpts, DispatchStats, Priority, Source},
+    failure::{ErrorId, FailureSink, HandlerFailure},
     handler,
+    observe::{Handled, Observer, Outcome},
+    reconcile,
     response::{
-        id, prelude::*, BorrowedResponder, BorrowingResponder, InitResponder, Message, ModalSource,
-        ResponseError,
+        id, prelude::*, AllowedMentions, BorrowedResponder, BorrowingResponder, InitResponder,
+        Message, ModalSource, ResponseError,
     },
     rpc::{ComponentId, Key, ModalId, Schema},
+    state::{self, StateStore},
     visitor,
 };
+use crate::{
+    capability::{self, Capabilities},
+    client::Context,
+};
 
 #[inline]
 fn write_string(f: impl FnOnce(&mut String) -> fmt::Result) -> String {
@@ -206,7 +229,7 @@ fn aci_issuer(cache: &Cache, aci: &CommandInteraction) -> String {
 
 #[inline]
 fn write_custom_id<T: prost::Message + Default>(w: &mut impl Write, id: &str) -> fmt::Result {
-    let parsed = unsafe { id::Id::from_inner(id.into()) };
+    let parsed = id::Id::from_raw(id);
     let parsed = id::read::<T>(&parsed).ok();
 
     if let Some(parsed) = parsed {
@@ -262,6 +285,58 @@ fn ms_issuer(cache: &Cache, ms: &ModalInteraction) -> String {
     write_string(|s| write_issuer(s, cache, &ms.user, ms.guild_id, ms.channel_id))
 }
 
+/// The response sent for interactions with an expired component
+const EXPIRED_MESSAGE: &str = "This menu has expired.";
+const BUSY_MESSAGE: &str = "I'm a little busy right now, please try again in a moment.";
+
+/// Check whether a component should be rejected as expired, given the expiry
+/// encoded in its ID, the maximum age allowed by its handler, and the time its
+/// message was last sent or edited
+fn is_expired(
+    expires_at: Option<DateTime<Utc>>,
+    max_age: Option<Duration>,
+    sent: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> bool {
+    expires_at.is_some_and(|t| t <= now)
+        || max_age.is_some_and(|a| {
+            TimeDelta::from_std(a)
+                .ok()
+                .and_then(|a| sent.checked_add_signed(a))
+                .is_some_and(|t| t <= now)
+        })
+}
+
+/// Extract the message from a panic payload, if it has one
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string panic payload>")
+}
+
+/// Construct a message edit body disabling every non-link component in the
+/// given rows
+fn disabled_components(rows: &[ActionRow]) -> Value {
+    let mut rows = serde_json::json!(rows);
+
+    for component in rows
+        .as_array_mut()
+        .into_iter()
+        .flatten()
+        .filter_map(|r| r.get_mut("components")?.as_array_mut())
+        .flatten()
+        .filter_map(Value::as_object_mut)
+    {
+        if !component.contains_key("url") {
+            component.insert("disabled".into(), true.into());
+        }
+    }
+
+    serde_json::json!({ "components": rows })
+}
+
 type CommandHandler<S> = Arc<dyn handler::CommandHandler<S>>;
 type CommandHandlerMap<S> = HashMap<CommandId, CommandHandler<S>>;
 type RpcHandler<S, K> = Arc<dyn handler::RpcHandler<S, K>>;
@@ -270,6 +345,7 @@ type RpcHandlerMap<S, K> = HashMap<K, RpcHandler<S, K>>;
 type ComponentInfo<'a, S> = (
     &'a RpcHandler<S, <S as Schema>::ComponentKey>,
     <S as Schema>::ComponentPayload,
+    Option<DateTime<Utc>>,
 );
 type ModalInfo<'a, S> = (
     &'a RpcHandler<S, <S as Schema>::ModalKey>,
@@ -277,6 +353,74 @@ type ModalInfo<'a, S> = (
     <S as Schema>::ModalPayload,
 );
 
+/// The reason an interaction could not be routed to its handler
+#[derive(Debug, Clone, Copy)]
+enum Rejection {
+    /// The interaction was rejected for a reason outside the bot's control
+    Expected(&'static str),
+    /// The interaction was rejected due to a likely bug in the bot
+    Bug(&'static str),
+}
+
+/// Global commands no longer claimed by any handler, along with the plan to
+/// show the sync gate before deleting them
+#[derive(Debug)]
+struct PendingDeletes {
+    plan: command::CommandPlan,
+    commands: Vec<RegisteredCommand>,
+}
+
+impl PendingDeletes {
+    /// Delete the pending commands once the sync gate, if any, approves
+    ///
+    /// This may wait on the gate for a long time, so it is run in the
+    /// background after the handler maps are installed.
+    async fn run(
+        self,
+        ctx: Context,
+        gate: Option<Arc<dyn command::SyncGate>>,
+        audit: Option<Arc<dyn AuditSink>>,
+    ) -> Result<(), anyhow::Error> {
+        let Self { plan, commands } = self;
+
+        if let Some(gate) = gate {
+            if !gate.approve(&ctx, &plan).await {
+                tracing::warn!(
+                    commands = ?commands.iter().map(|r| r.info.name()).collect::<Vec<_>>(),
+                    "Deletions not approved, leaving unregistered commands in place"
+                );
+                return Ok(());
+            }
+        }
+
+        for reg in commands {
+            let inf = &reg.info;
+            tracing::info!(
+                "Deleting unregistered command {:?} (ID {:?})",
+                inf.name(),
+                reg.id,
+            );
+            Command::delete_global_command(&ctx.http, reg.id)
+                .await
+                .with_context(|| format!("Error deleting command {:?}", inf.name()))?;
+
+            if let Some(ref audit) = audit {
+                audit.record(CommandMutation {
+                    time: Utc::now(),
+                    actor: reg.app,
+                    guild: None,
+                    id: reg.id,
+                    kind: MutationKind::Delete,
+                    old: Some(reg.info.clone()),
+                    new: None,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// A self-contained registry of interaction handlers, which can register and
 /// dispatch response logic to each handler
 #[derive(Debug)]
@@ -285,113 +429,126 @@ pub struct Registry<S: Schema> {
     commands: RwLock<Option<CommandHandlerMap<S>>>,
     components: RwLock<Option<RpcHandlerMap<S, S::ComponentKey>>>,
     modals: RwLock<Option<RpcHandlerMap<S, S::ModalKey>>>,
+    audit: Option<Arc<dyn AuditSink>>,
+    sync_gate: Option<Arc<dyn command::SyncGate>>,
+    aliases: Option<Arc<dyn command::AliasResolver>>,
+    failures: Option<Arc<dyn FailureSink>>,
+    observer: Option<Arc<dyn Observer>>,
+    collectors: Arc<Collectors>,
+    state: Arc<StateStore>,
+    capabilities: Capabilities,
+    mentions: AllowedMentions,
+    dispatch: Option<Arc<dispatch::Queue>>,
+    support_url: Option<Url>,
+    version: Option<String>,
+    sim_weights: command::SimWeights,
+    min_similarity: f64,
+    dry_run: bool,
+    reconciler: Arc<reconcile::Reconciler>,
 }
 
 impl<S: Schema> Registry<S> {
-    #[tracing::instrument(level = "info", skip(ctx))]
-    async fn patch_commands(
-        ctx: &Context,
-        init: &handler::Handlers<S>,
-        guild: Option<GuildId>,
-    ) -> Result<CommandHandlerMap<S>, anyhow::Error> {
-        if let Some(guild) = guild {
-            todo!("handle guild {guild}");
-        }
-
-        let handler::Handlers { commands, .. } = init;
-        let mut handlers = HashMap::new();
-
-        let existing = Command::get_global_commands(&ctx.http)
+    async fn fetch_commands(ctx: &Context) -> Result<Vec<RegisteredCommand>, anyhow::Error> {
+        Command::get_global_commands(&ctx.http)
             .await
             .context("Error fetching initial command list")?
             .into_iter()
             .map(RegisteredCommand::try_from)
             .collect::<Result<Vec<_>, _>>()
-            .context("Error parsing initial command list")?;
+            .context("Error parsing initial command list")
+    }
 
-        let mut unpaired_existing: HashMap<_, _> = existing.iter().map(|r| (&r.info, r)).collect();
+    /// Apply every creation and update needed to register the current
+    /// handlers, returning the handler map along with any commands left to
+    /// delete
+    #[tracing::instrument(level = "info", skip(self, ctx))]
+    async fn patch_commands(
+        &self,
+        ctx: &Context,
+        guild: Option<GuildId>,
+    ) -> Result<(CommandHandlerMap<S>, Option<PendingDeletes>), anyhow::Error> {
+        let record = |mutation: CommandMutation| {
+            if let Some(ref audit) = self.audit {
+                audit.record(mutation);
+            }
+        };
 
-        let mut new: HashMap<_, _> = commands
-            .iter()
-            .map(|c| {
-                let inf = c.register_global();
-                (inf.name().clone(), (c, inf))
-            })
-            .collect();
-        assert_eq!(new.len(), commands.len());
+        if let Some(guild) = guild {
+            todo!("handle guild {guild}");
+        }
 
-        let mut unpaired_new = HashSet::new();
+        let commands = &self.handlers.commands;
+        let mut handlers = HashMap::new();
 
-        for (name, (cmd, inf)) in &new {
-            if let Some(reg) = unpaired_existing.remove(inf) {
-                handlers.insert(reg.id, Arc::clone(cmd));
-                continue;
-            }
+        let existing = Self::fetch_commands(ctx).await?;
+        let new: Vec<_> = commands.iter().map(|c| c.register_global()).collect();
+        let pairing = command::pair(&existing, &new, &self.sim_weights, self.min_similarity);
 
-            unpaired_new.insert(name.clone());
+        for &(reg, i) in &pairing.unchanged {
+            handlers.insert(reg.id, Arc::clone(&commands[i]));
         }
 
-        let mut sims: BinaryHeap<_> = unpaired_existing
-            .iter()
-            .flat_map(|(existing, &reg)| {
-                let new = &new;
-                unpaired_new.iter().map(move |n| {
-                    let (_, new) = new.get(n).unwrap_or_else(|| unreachable!());
-
-                    (
-                        OrderedFloat(command::similarity(existing, new)),
-                        reg,
-                        n.clone(),
-                    )
-                })
-            })
-            .collect();
-
-        while let Some((sim, existing, new_name)) = sims.pop() {
-            if !unpaired_new.remove(&new_name) || unpaired_existing.remove(&existing.info).is_none()
-            {
-                continue;
-            }
+        if self.dry_run {
+            tracing::info!(
+                "Dry run, leaving global commands unchanged:\n{}",
+                pairing.plan(&new)
+            );
+            return Ok((handlers, None));
+        }
 
-            let (cmd, inf) = new.remove(&new_name).unwrap_or_else(|| unreachable!());
+        for &(existing, i, sim) in &pairing.updates {
+            let inf = &new[i];
+            let new_name = inf.name();
             tracing::info!(
-                ?sim,
+                %sim,
                 id = ?existing.id,
                 old = ?existing.info.name(),
                 "Updating global command {new_name:?}"
             );
-            let res = Command::edit_global_command(&ctx.http, existing.id, inf.into())
+            let res = Command::edit_global_command(&ctx.http, existing.id, inf.clone().into())
                 .await
                 .with_context(|| format!("Error updating command {new_name:?}"))?;
             assert_eq!(existing.id, res.id);
-            assert!(handlers.insert(res.id, Arc::clone(cmd)).is_none());
+            record(CommandMutation {
+                time: Utc::now(),
+                actor: res.application_id,
+                guild,
+                id: res.id,
+                kind: MutationKind::Update,
+                old: Some(existing.info.clone()),
+                new: Some(inf.clone()),
+            });
+            assert!(handlers.insert(res.id, Arc::clone(&commands[i])).is_none());
         }
 
-        assert!(unpaired_new.is_empty() || unpaired_existing.is_empty());
-
-        for name in unpaired_new {
-            let (cmd, inf) = new.remove(&name).unwrap_or_else(|| unreachable!());
+        for &i in &pairing.creates {
+            let inf = &new[i];
+            let name = inf.name();
             tracing::info!("Creating global command {name:?}");
-            let res = Command::create_global_command(&ctx.http, inf.into())
+            let res = Command::create_global_command(&ctx.http, inf.clone().into())
                 .await
                 .with_context(|| format!("Error creating command {name:?}"))?;
-
-            assert!(handlers.insert(res.id, Arc::clone(cmd)).is_none());
-        }
-
-        for (inf, reg) in unpaired_existing {
-            tracing::info!(
-                "Deleting unregistered command {:?} (ID {:?})",
-                inf.name(),
-                reg.id,
-            );
-            Command::delete_global_command(&ctx.http, reg.id)
-                .await
-                .with_context(|| format!("Error deleting command {:?}", inf.name()))?;
+            record(CommandMutation {
+                time: Utc::now(),
+                actor: res.application_id,
+                guild,
+                id: res.id,
+                kind: MutationKind::Create,
+                old: None,
+                new: Some(inf.clone()),
+            });
+
+            assert!(handlers.insert(res.id, Arc::clone(&commands[i])).is_none());
         }
 
         assert_eq!(handlers.len(), commands.len());
-        Ok(handlers)
+
+        let pending = (!pairing.deletes.is_empty()).then(|| PendingDeletes {
+            plan: pairing.plan(&new),
+            commands: pairing.deletes.into_iter().cloned().collect(),
+        });
+
+        Ok((handlers, pending))
     }
 
     fn collate_rpc<K: Key>(handlers: &[RpcHandler<S, K>]) -> RpcHandlerMap<S, K> {
@@ -409,15 +566,17 @@ impl<S: Schema> Registry<S> {
     fn resolve_command<'a>(
         map: &'a tokio::sync::RwLockReadGuard<'a, Option<CommandHandlerMap<S>>>,
         id: CommandId,
-    ) -> Result<&'a CommandHandler<S>, &'static str> {
+    ) -> Result<&'a CommandHandler<S>, Rejection> {
         let Some(ref map) = **map else {
             tracing::warn!("Rejecting command due to uninitialized registry");
-            return Err("Still starting!  Please try again later.");
+            return Err(Rejection::Expected(
+                "Still starting!  Please try again later.",
+            ));
         };
 
         let Some(handler) = map.get(&id) else {
             tracing::warn!("Rejecting unknown command");
-            return Err("Unknown command - this may be a bug.");
+            return Err(Rejection::Bug("Unknown command - this may be a bug."));
         };
 
         Ok(handler)
@@ -426,42 +585,76 @@ impl<S: Schema> Registry<S> {
     fn resolve_component<'a>(
         map: &'a tokio::sync::RwLockReadGuard<'a, Option<RpcHandlerMap<S, S::ComponentKey>>>,
         id: &id::Id<'_>,
-    ) -> Result<ComponentInfo<'a, S>, &'static str> {
+    ) -> Result<ComponentInfo<'a, S>, Rejection> {
         let Some(ref map) = **map else {
             tracing::warn!("Rejecting component due to uninitialized registry");
-            return Err("Still starting!  Please try again later.");
+            return Err(Rejection::Expected(
+                "Still starting!  Please try again later.",
+            ));
         };
 
-        let payload = match id::read::<S::Component>(id)
-            .map_err(Some)
-            .and_then(|i| i.try_into_parts().ok_or(None))
-        {
+        let (payload, expires_at) = match id::read::<S::Component>(id).map_err(Some).and_then(|i| {
+            let expires_at = i.expires_at();
+            i.try_into_parts().map(|p| (p, expires_at)).ok_or(None)
+        }) {
             Ok(p) => p,
             Err(Some(err)) => {
                 tracing::error!(%err, "Unable to parse component ID");
-                return Err("Unrecognized component ID format - this is a bug.");
+                return Err(Rejection::Bug(
+                    "Unrecognized component ID format - this is a bug.",
+                ));
             },
             Err(None) => {
                 tracing::warn!("Rejecting unknown (deprecated?) component ID");
-                return Err("Invalid component ID - this feature may have been removed.");
+                return Err(Rejection::Expected(
+                    "Invalid component ID - this feature may have been removed.",
+                ));
             },
         };
 
         let Some(handler) = map.get(&(&payload).into()) else {
             tracing::warn!("Rejecting unknown component");
-            return Err("Unknown component - this may be a bug.");
+            return Err(Rejection::Bug("Unknown component - this may be a bug."));
         };
 
-        Ok((handler, payload))
+        Ok((handler, payload, expires_at))
+    }
+
+    /// Disable the components of a message whose components have expired
+    async fn disable_expired(ctx: &Context, mc: &ComponentInteraction) {
+        let msg = &mc.message;
+
+        // Ephemeral messages can only be edited through their interaction
+        if msg
+            .flags
+            .is_some_and(|f| f.contains(MessageFlags::EPHEMERAL))
+        {
+            return;
+        }
+
+        if let Err(err) = ctx
+            .http
+            .edit_message(
+                msg.channel_id,
+                msg.id,
+                &disabled_components(&msg.components),
+                vec![],
+            )
+            .await
+        {
+            tracing::warn!(%err, "Error disabling expired components");
+        }
     }
 
     fn resolve_modal<'a>(
         map: &'a tokio::sync::RwLockReadGuard<'a, Option<RpcHandlerMap<S, S::ModalKey>>>,
         id: &id::Id<'_>,
-    ) -> Result<ModalInfo<'a, S>, &'static str> {
+    ) -> Result<ModalInfo<'a, S>, Rejection> {
         let Some(ref map) = **map else {
             tracing::warn!("Rejecting modal due to uninitialized registry");
-            return Err("Still starting!  Please try again later.");
+            return Err(Rejection::Expected(
+                "Still starting!  Please try again later.",
+            ));
         };
 
         let (source, payload) = match id::read::<S::Modal>(id)
@@ -471,57 +664,150 @@ impl<S: Schema> Registry<S> {
             Ok(p) => p,
             Err(Some(err)) => {
                 tracing::error!(%err, "Unable to parse modal ID");
-                return Err("Unrecognized modal ID format - this is a bug.");
+                return Err(Rejection::Bug(
+                    "Unrecognized modal ID format - this is a bug.",
+                ));
             },
             Err(None) => {
                 tracing::warn!("Rejecting unknown (deprecated?) modal ID");
-                return Err("Invalid modal ID - this feature may have been removed.");
+                return Err(Rejection::Expected(
+                    "Invalid modal ID - this feature may have been removed.",
+                ));
             },
         };
 
         let Some(handler) = map.get(&(&payload).into()) else {
             tracing::warn!("Rejecting unknown modal");
-            return Err("Unknown modal - this may be a bug.");
+            return Err(Rejection::Bug("Unknown modal - this may be a bug."));
         };
 
         Ok((handler, source, payload))
     }
 
+    fn report_failure(
+        &self,
+        int: InteractionId,
+        desc: &'static str,
+        name: &str,
+        issuer: &str,
+        err: &impl fmt::Display,
+    ) -> ErrorId {
+        let id = int.into();
+
+        if let Some(ref sink) = self.failures {
+            sink.record(HandlerFailure {
+                id,
+                time: Utc::now(),
+                kind: desc,
+                name: name.into(),
+                issuer: issuer.into(),
+                error: err.to_string(),
+            });
+        }
+
+        id
+    }
+
+    /// Append the configured version and support link to a message
+    /// describing a likely bug
+    fn support_footer<'a>(&self, b: &'a mut MessageBuilder) -> &'a mut MessageBuilder {
+        if let Some(ref version) = self.version {
+            b.push("\nVersion: ").push_mono_safe(version.as_str());
+        }
+
+        if let Some(ref url) = self.support_url {
+            b.push("\nPlease report this at ").push(url.as_str());
+        }
+
+        b
+    }
+
+    fn rejection(&self, rejection: Rejection) -> Message<S::Component, id::Error> {
+        match rejection {
+            Rejection::Expected(reason) => Message::plain(reason),
+            Rejection::Bug(reason) => Message::rich(|b| self.support_footer(b.push(reason))),
+        }
+        .ephemeral(true)
+    }
+
     fn pretty_handler_error<I>(
+        &self,
         err: handler::HandlerError<S, I>,
         desc: &'static str,
+        int: InteractionId,
+        name: &str,
+        issuer: &str,
     ) -> Option<Message<S::Component, id::Error>> {
         match err {
             handler::HandlerError::Parse(err) => match err {
-                visitor::Error::GuildRequired => {
-                    tracing::debug!(%err, "Responding with guild error");
+                visitor::Error::GuildRequired
+                | visitor::Error::DmRequired
+                | visitor::Error::BotRequired
+                | visitor::Error::NotPoll => {
+                    tracing::debug!(%err, "Responding with unavailable {desc} error");
+                    let reason = match err {
+                        visitor::Error::GuildRequired => " must be run inside a server.",
+                        visitor::Error::DmRequired => " cannot be run inside a server.",
+                        visitor::Error::BotRequired => {
+                            " can only be used where the bot has been added."
+                        },
+                        _ => " can only be used on messages with a poll.",
+                    };
+                    Message::rich(|b| b.push_bold("ERROR:").push(" This ").push(desc).push(reason))
+                        .ephemeral(true)
+                        .into()
+                },
+                visitor::Error::BadMention(ref option, expected) => {
+                    tracing::debug!(%err, "Responding with mention parse error");
                     Message::rich(|b| {
                         b.push_bold("ERROR:")
-                            .push(" This ")
-                            .push(desc)
-                            .push(" must be run inside a server.")
+                            .push(" The ")
+                            .push_mono_safe(option.as_str())
+                            .push(" option must be ")
+                            .push(expected)
+                            .push(".")
+                    })
+                    .ephemeral(true)
+                    .into()
+                },
+                visitor::Error::Invalid(ref fields) => {
+                    tracing::debug!(%err, "Responding with validation error");
+                    Message::rich(|b| {
+                        b.push_bold("ERROR:").push(" Some fields were invalid:");
+                        for field in fields {
+                            b.push("\n- ")
+                                .push_bold_safe(field.label.as_str())
+                                .push(": ")
+                                .push_safe(field.reason.as_str());
+                        }
+                        b.push("\nPlease correct them and try again.")
                     })
                     .ephemeral(true)
                     .into()
                 },
-                visitor::Error::DmRequired => {
-                    tracing::debug!(%err, "Responding with non-guild error");
+                visitor::Error::PremiumRequired(sku) => {
+                    tracing::debug!(%err, "Responding with premium upsell");
                     Message::rich(|b| {
                         b.push_bold("ERROR:")
                             .push(" This ")
                             .push(desc)
-                            .push(" cannot be run inside a server.")
+                            .push(" requires a premium subscription.")
                     })
+                    .buttons(|b| b.premium(sku, false))
                     .ephemeral(true)
                     .into()
                 },
                 err => {
-                    tracing::error!(%err, "Unexpected error parsing {desc}");
+                    let error_id = self.report_failure(int, desc, name, issuer, &err);
+                    tracing::error!(%err, %error_id, "Unexpected error parsing {desc}");
                     Message::rich(|b| {
                         b.push("Unexpected error parsing ")
                             .push(desc)
                             .push(": ")
                             .push_mono_safe(err.to_string())
+                            .push("\nError ID: ")
+                            .push_mono(error_id.to_string());
+                        self.support_footer(b)
                     })
                     .ephemeral(true)
                     .into()
@@ -532,10 +818,17 @@ impl<S: Schema> Registry<S> {
                 None
             },
             handler::HandlerError::Other(err) => {
-                tracing::error!(?err, "Unexpected error handling {desc}");
-                Message::rich(|b| b.push("Unexpected error: ").push_mono_safe(err.to_string()))
-                    .ephemeral(true)
-                    .into()
+                let error_id = self.report_failure(int, desc, name, issuer, &format!("{err:#}"));
+                tracing::error!(?err, %error_id, "Unexpected error handling {desc}");
+                Message::rich(|b| {
+                    b.push("Unexpected error: ")
+                        .push_mono_safe(err.to_string())
+                        .push("\nError ID: ")
+                        .push_mono(error_id.to_string());
+                    self.support_footer(b)
+                })
+                .ephemeral(true)
+                .into()
             },
         }
     }
@@ -548,48 +841,471 @@ impl<S: Schema> Registry<S> {
             commands: None.into(),
             components: None.into(),
             modals: None.into(),
+            audit: None,
+            sync_gate: None,
+            aliases: None,
+            failures: None,
+            observer: None,
+            collectors: Arc::default(),
+            state: Arc::default(),
+            capabilities: Capabilities::default(),
+            mentions: AllowedMentions::NONE,
+            dispatch: None,
+            support_url: None,
+            version: None,
+            sim_weights: command::SimWeights::default(),
+            min_similarity: 0.0,
+            dry_run: false,
+            reconciler: Arc::default(),
         }
     }
 
+    /// Offer component interactions to the given collectors before routing
+    /// them, instead of a default set
+    #[must_use]
+    pub fn with_collectors(mut self, collectors: Arc<Collectors>) -> Self {
+        self.collectors = collectors;
+        self
+    }
+
+    /// Provide the given state store to handlers, instead of a default
+    /// in-memory one
+    #[must_use]
+    pub fn with_state(mut self, state: Arc<StateStore>) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Tell handlers which gateway intents the client was started with, so
+    /// lookups can avoid relying on data the cache will never receive
+    #[must_use]
+    pub fn with_intents(mut self, intents: GatewayIntents) -> Self {
+        self.capabilities = Capabilities::new(intents);
+        self
+    }
+
+    /// Record all changes made to registered commands to the given sink
+    #[must_use]
+    pub fn with_audit(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit = Some(sink);
+        self
+    }
+
+    /// Consult the given gate before deleting any registered commands on
+    /// startup
+    ///
+    /// The gate is consulted in the background, so waiting on it does not
+    /// hold up [`init`](Self::init).
+    #[must_use]
+    pub fn with_sync_gate(mut self, gate: Arc<dyn command::SyncGate>) -> Self {
+        self.sync_gate = Some(gate);
+        self
+    }
+
+    /// Consult the given resolver for command aliases before dispatching each
+    /// command
+    #[must_use]
+    pub fn with_aliases(mut self, aliases: Arc<dyn command::AliasResolver>) -> Self {
+        self.aliases = Some(aliases);
+        self
+    }
+
+    /// Record all unexpected errors raised by handlers to the given sink
+    #[must_use]
+    pub fn with_failures(mut self, sink: Arc<dyn FailureSink>) -> Self {
+        self.failures = Some(sink);
+        self
+    }
+
+    /// Report the outcome and latency of every handled interaction to the
+    /// given observer
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Apply the given mention policy to every response message that does not
+    /// specify its own
+    ///
+    /// By default no mentions are allowed to ping.
+    #[must_use]
+    pub fn with_mentions(mut self, mentions: AllowedMentions) -> Self {
+        self.mentions = mentions;
+        self
+    }
+
+    /// Direct users to the given URL, such as a support server invite, from
+    /// any error message that indicates a bug
+    #[must_use]
+    pub fn with_support_url(mut self, url: Url) -> Self {
+        self.support_url = Some(url);
+        self
+    }
+
+    /// Show the given version string in any error message that indicates a
+    /// bug
+    #[must_use]
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Weigh the parts of command similarity scores differently when pairing
+    /// registered commands with changed handlers
+    ///
+    /// See [`command::SimWeights`] for details.
+    #[must_use]
+    pub fn with_similarity(mut self, weights: command::SimWeights) -> Self {
+        self.sim_weights = weights;
+        self
+    }
+
+    /// Delete and recreate changed commands, rather than updating them in
+    /// place, if their similarity score is below the given threshold
+    ///
+    /// By default every changed command that can be paired with a registered
+    /// one is updated in place.
+    #[must_use]
+    pub fn with_min_similarity(mut self, score: f64) -> Self {
+        self.min_similarity = score;
+        self
+    }
+
+    /// Log the changes that would be made to the registered commands instead
+    /// of making them
+    ///
+    /// Only commands that are already registered unchanged will be handled.
+    #[must_use]
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Delete orphaned guild-scoped commands when reconciling guilds, leaving
+    /// commands with the given names registered
+    ///
+    /// Reconciliation is disabled unless this is called.  Note that an empty
+    /// allowlist deletes every guild command.  See
+    /// [`reconcile_guild`](Self::reconcile_guild) for details.
+    #[must_use]
+    pub fn with_guild_reconciliation<I: IntoIterator>(mut self, keep: I) -> Self
+    where I::Item: Into<String> {
+        self.reconciler = Arc::new(reconcile::Reconciler::new(
+            keep.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Limit the number of interactions handled at once, queueing the rest
+    ///
+    /// See the [`dispatch`](super::dispatch) module for details.
+    #[must_use]
+    pub fn with_dispatch(mut self, opts: DispatchOpts) -> Self {
+        self.dispatch = Some(Arc::new(dispatch::Queue::new(opts)));
+        self
+    }
+
+    /// Get the collectors component interactions are offered to
+    #[inline]
+    #[must_use]
+    pub fn collectors(&self) -> &Arc<Collectors> { &self.collectors }
+
+    /// Get the store handlers keep the state of multi-step flows in
+    #[inline]
+    #[must_use]
+    pub fn state(&self) -> &Arc<StateStore> { &self.state }
+
+    /// Get a snapshot of the dispatch queue, if one is configured
+    #[must_use]
+    pub fn dispatch_stats(&self) -> Option<DispatchStats> {
+        self.dispatch.as_deref().map(dispatch::Queue::stats)
+    }
+
+    /// Render the global commands this registry would register as a
+    /// canonical snapshot, without contacting Discord
+    ///
+    /// See [`command::snapshot`] for details on the format.
+    #[must_use]
+    pub fn command_snapshot(&self) -> String {
+        let infos: Vec<_> = self
+            .handlers
+            .commands
+            .iter()
+            .map(|c| c.register_global())
+            .collect();
+        command::snapshot(&infos)
+    }
+
+    /// Compute the changes [`init`](Self::init) would make to the registered
+    /// global commands, without making them
+    ///
+    /// # Errors
+    /// This method returns an error if the registered commands could not be
+    /// fetched.
+    pub async fn plan_commands(
+        &self,
+        ctx: &Context,
+    ) -> Result<command::CommandPlan, anyhow::Error> {
+        let existing = Self::fetch_commands(ctx).await?;
+        let new: Vec<_> = self
+            .handlers
+            .commands
+            .iter()
+            .map(|c| c.register_global())
+            .collect();
+
+        Ok(command::pair(&existing, &new, &self.sim_weights, self.min_similarity).plan(&new))
+    }
+
     /// Initialize dispatch logic and register all necessary metadata with
     /// Discord
     ///
     /// # Errors
-    /// This method returns an error if an API error response is received during
-    /// registration.
+    /// This method returns an error if any command handlers register
+    /// conflicting names (see [`Handlers::check_names`](handler::Handlers::check_names)),
+    /// or if an API error response is received during registration.
     #[inline]
     pub async fn init(&self, ctx: &Context) -> Result<(), anyhow::Error> {
+        self.handlers
+            .check_names()
+            .context("Error validating command handlers")?;
+
+        // Collectors are installed first so a sync gate can wait on them, and
+        // the handler maps are only locked once patching is done, leaving
+        // interactions that arrive in the meantime free to be rejected
+        collector::install(ctx, &self.collectors).await;
+        state::install(ctx, &self.state).await;
+        capability::install(ctx, self.capabilities).await;
+
+        let (patched, pending) = self.patch_commands(ctx, None).await?;
+
         let mut commands = self.commands.write().await;
         let mut components = self.components.write().await;
         let mut modals = self.modals.write().await;
 
-        *commands = Some(Self::patch_commands(ctx, &self.handlers, None).await?);
+        *commands = Some(patched);
         *components = Some(Self::collate_rpc(&self.handlers.components));
         *modals = Some(Self::collate_rpc(&self.handlers.modals));
 
-        // TODO: handle guild commands
+        drop(commands);
+        drop(components);
+        drop(modals);
+
+        // Approving deletions can take a while, and the commands in question
+        // have no handlers anyway, so they are dealt with off the startup path
+        if let Some(pending) = pending {
+            let run = pending.run(ctx.clone(), self.sync_gate.clone(), self.audit.clone());
+            tokio::spawn(
+                async move {
+                    if let Err(err) = run.await {
+                        tracing::error!(?err, "Error deleting unregistered commands");
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+
+        // Guilds that are not cached yet are reconciled as they are joined.
+        // Each guild may wait on the sync gate, so they are all reconciled at
+        // once in the background, and the reconciler skips any guild already
+        // being reconciled when it becomes available.
+        let guilds = ctx.cache.guilds();
+        if !guilds.is_empty() {
+            let api = self.reconcile_api(ctx);
+            let reconciler = Arc::clone(&self.reconciler);
+            let audit = self.audit.clone();
+            tokio::spawn(
+                async move {
+                    let runs = guilds.into_iter().map(|guild| {
+                        reconciler.run(&api, guild, audit.as_deref()).map(move |res| {
+                            if let Err(err) = res {
+                                tracing::warn!(%guild, ?err, "Error reconciling guild commands");
+                            }
+                        })
+                    });
+                    join_all(runs).await;
+                }
+                .in_current_span(),
+            );
+        }
 
         Ok(())
     }
 
-    #[tracing::instrument(level = "error", name = "handle_command", err, skip(self, ctx, aci))]
+    /// Delete any guild-scoped commands registered in the given guild that
+    /// are not on the allowlist
+    ///
+    /// Handlers only register global commands, so any guild command still
+    /// registered was left behind by a handler that no longer exists.  This is
+    /// called concurrently for every cached guild in the background by
+    /// [`init`](Self::init), and should also be called whenever a guild becomes
+    /// available so guilds missing from the cache at startup are covered.
+    ///
+    /// Nothing is done and `None` is returned unless reconciliation was
+    /// enabled with [`with_guild_reconciliation`](Self::with_guild_reconciliation),
+    /// or if the guild was already reconciled or is being reconciled.  A guild only counts as
+    /// reconciled once all its orphaned commands were deleted, so a guild
+    /// whose deletions were skipped by [`with_dry_run`](Self::with_dry_run) or
+    /// the configured sync gate is reconciled again the next time this is
+    /// called for it.  A summary of the outcome is reported to the audit sink,
+    /// if any.
+    ///
+    /// # Errors
+    /// This method returns an error if the guild's commands could not be
+    /// fetched or deleted.  The guild may then be reconciled again later.
+    #[tracing::instrument(level = "info", skip(self, ctx))]
+    pub async fn reconcile_guild(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+    ) -> Result<Option<GuildReconciliation>, anyhow::Error> {
+        self.reconciler
+            .run(&self.reconcile_api(ctx), guild, self.audit.as_deref())
+            .await
+    }
+
+    fn reconcile_api(&self, ctx: &Context) -> reconcile::ContextApi {
+        reconcile::ContextApi {
+            ctx: ctx.clone(),
+            gate: self.sync_gate.clone(),
+            dry_run: self.dry_run,
+        }
+    }
+
+    /// Wait for the dispatch queue, if any, to admit an interaction
+    async fn admit(
+        &self,
+        guild: Option<GuildId>,
+        user: UserId,
+        prio: Priority,
+    ) -> Result<Option<dispatch::Permit>, dispatch::Busy> {
+        match self.dispatch {
+            Some(ref queue) => queue
+                .acquire(Source::new(guild, user), prio)
+                .await
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Run a handler, converting any panic it raises into an error so the
+    /// user still receives a response
+    async fn catch_panic<'a, I: 'a>(
+        res: impl Future<Output = handler::ResponseResult<'a, S, I>>,
+    ) -> handler::ResponseResult<'a, S, I> {
+        AssertUnwindSafe(res)
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|payload| {
+                let msg = panic_message(&*payload);
+                tracing::error!(panic = msg, "Interaction handler panicked");
+                Err(anyhow::anyhow!("Handler panicked: {msg}").into())
+            })
+    }
+
+    /// Rewrite a command interaction invoking an alias into an invocation of
+    /// the aliased command
+    async fn expand_alias(
+        &self,
+        ctx: &Context,
+        aci: &mut CommandInteraction,
+    ) -> Result<(), command::AliasError> {
+        let Some(ref aliases) = self.aliases else {
+            return Ok(());
+        };
+        let Some(alias) = aliases.resolve(ctx, aci).await? else {
+            return Ok(());
+        };
+
+        let map = self.commands.read().await;
+        // Uninitialized registries are rejected when resolving the command
+        let Some(ref map) = *map else { return Ok(()) };
+        let (id, info) = map
+            .iter()
+            .map(|(id, h)| (*id, h.register_global()))
+            .find(|(_, i)| i.kind() == CommandType::ChatInput && i.name() == alias.command())
+            .ok_or_else(|| command::AliasError::UnknownCommand(alias.command().into()))?;
+
+        aci.data.options = info.expand_alias(&alias)?;
+        aci.data.id = id;
+        aci.data.name.clone_from(info.name());
+        tracing::debug!(%alias, "Expanded command alias");
+
+        Ok(())
+    }
+
+    async fn auto_defer<T>(
+        res: impl Future<Output = T>,
+        responder: &Mutex<BorrowedResponder<'_, S, CommandInteraction>>,
+        defer: handler::AutoDefer,
+    ) -> T {
+        let handler::AutoDefer { after, opts } = defer;
+
+        race_deferral(res, after, async {
+            match responder.lock().await.defer_pending(opts).await {
+                Ok(true) => tracing::debug!(?after, ?opts, "Handler timed out, response deferred"),
+                Ok(false) => (),
+                Err(err) => tracing::error!(%err, "Error auto-deferring response"),
+            }
+        })
+        .await
+    }
+
+    #[tracing::instrument(
+        level = "error",
+        name = "handle_command",
+        err,
+        skip(self, ctx, aci, obs)
+    )]
     async fn try_handle_command(
         &self,
         ctx: &Context,
-        aci: CommandInteraction,
+        mut aci: CommandInteraction,
         name: String,
         id: String,
         issuer: String,
+        obs: &mut Handled,
     ) -> Result<(), ResponseError> {
         tracing::info!("Handling application command");
 
+        let alias = self.expand_alias(ctx, &mut aci).await;
+        let responder =
+            InitResponder::new(&ctx.http, &aci, InteractionCtx::new(aci.id, ctx.shard_id))
+                .with_mentions(&self.mentions);
+        let Ok(_permit) = self
+            .admit(aci.guild_id, aci.user.id, Priority::Normal)
+            .await
+        else {
+            obs.outcome = Outcome::Rejected;
+            return responder
+                .create_message(Message::plain(BUSY_MESSAGE).ephemeral(true))
+                .await
+                .map(|_| ());
+        };
+
+        if let Err(err) = alias {
+            obs.outcome = Outcome::Rejected;
+            let msg = if let command::AliasError::Backend(ref err) = err {
+                tracing::error!(?err, "Error resolving command alias");
+                Message::plain("Couldn't look up that alias.  Please try again later.")
+            } else {
+                tracing::debug!(%err, "Rejecting invalid command alias");
+                Message::plain(format!("Couldn't run that alias: {err}"))
+            };
+            return responder
+                .create_message(msg.ephemeral(true))
+                .await
+                .map(|_| ());
+        }
+
         let map = self.commands.read().await;
-        let responder = InitResponder::new(&ctx.http, &aci);
         let handler = match Self::resolve_command(&map, aci.data.id) {
             Ok(h) => h,
             Err(e) => {
+                obs.outcome = Outcome::Rejected;
                 return responder
-                    .create_message(Message::plain(e).ephemeral(true))
+                    .create_message(self.rejection(e))
                     .await
                     .map(|_| ());
             },
@@ -597,15 +1313,34 @@ impl<S: Schema> Registry<S> {
         tracing::debug!(?handler, "Command handler selected");
 
         let mut vis = visitor::CommandVisitor::new(&aci);
-        let mut responder = BorrowedResponder::Init(responder);
-        let res = handler
-            .respond(ctx, &mut vis, BorrowingResponder::new(&mut responder))
-            .await;
+        let responder = Mutex::new(BorrowedResponder::Init(responder));
+        let res = {
+            let res = Self::catch_panic(handler.respond(
+                ctx,
+                &mut vis,
+                BorrowingResponder::new(&responder),
+            ));
+
+            match handler.auto_defer() {
+                Some(defer) => Self::auto_defer(res, &responder, defer).await,
+                None => res.await,
+            }
+        };
         let res = res.and_then(|_| vis.finish().map_err(Into::into));
+        let mut responder = responder.into_inner();
+
+        if res.is_err() {
+            obs.outcome = Outcome::Error;
+        }
+
+        if matches!(responder, BorrowedResponder::Poison) {
+            tracing::error!("Handler panicked mid-response, unable to send error");
+            return Ok(());
+        }
 
         if let Some(msg) = res
             .err()
-            .and_then(|e| Self::pretty_handler_error(e, "command"))
+            .and_then(|e| self.pretty_handler_error(e, "command", aci.id, &name, &issuer))
         {
             responder.create_or_followup(msg).await?;
         }
@@ -613,7 +1348,12 @@ impl<S: Schema> Registry<S> {
         Ok(())
     }
 
-    #[tracing::instrument(level = "error", name = "handle_component", err, skip(self, ctx, mc))]
+    #[tracing::instrument(
+        level = "error",
+        name = "handle_component",
+        err,
+        skip(self, ctx, mc, obs)
+    )]
     async fn try_handle_component(
         &self,
         ctx: &Context,
@@ -621,38 +1361,70 @@ impl<S: Schema> Registry<S> {
         name: String,
         id: String,
         issuer: String,
+        obs: &mut Handled,
     ) -> Result<(), ResponseError> {
         tracing::info!("Handling message component");
 
-        let map = self.components.read().await;
-        let responder = InitResponder::new(&ctx.http, &mc);
-        let (handler, payload) = match Self::resolve_component(&map, unsafe {
-            &id::Id::from_inner(mc.data.custom_id.as_str().into())
-        }) {
-            Ok(h) => h,
-            Err(e) => {
-                return responder
-                    .create_message(Message::plain(e).ephemeral(true))
-                    .await
-                    .map(|_| ());
-            },
+        let responder =
+            InitResponder::new(&ctx.http, &mc, InteractionCtx::new(mc.id, ctx.shard_id))
+                .with_mentions(&self.mentions);
+        let Ok(_permit) = self.admit(mc.guild_id, mc.user.id, Priority::Normal).await else {
+            obs.outcome = Outcome::Rejected;
+            return responder
+                .create_message(Message::plain(BUSY_MESSAGE).ephemeral(true))
+                .await
+                .map(|_| ());
         };
+
+        let map = self.components.read().await;
+        let (handler, payload, expires_at) =
+            match Self::resolve_component(&map, &id::Id::from_raw(mc.data.custom_id.as_str())) {
+                Ok(h) => h,
+                Err(e) => {
+                    obs.outcome = Outcome::Rejected;
+                    return responder
+                        .create_message(self.rejection(e))
+                        .await
+                        .map(|_| ());
+                },
+            };
         tracing::debug!(?handler, ?payload, "Component handler selected");
+        obs.name = format!("{:?}", S::ComponentKey::from(&payload));
+
+        let sent = mc.message.edited_timestamp.unwrap_or(mc.message.timestamp);
+        if is_expired(expires_at, handler.expiry(), *sent, Utc::now()) {
+            tracing::warn!(?expires_at, %sent, "Rejecting expired component");
+            obs.outcome = Outcome::Rejected;
+            responder
+                .create_message(Message::plain(EXPIRED_MESSAGE).ephemeral(true))
+                .await?;
+            Self::disable_expired(ctx, &mc).await;
+            return Ok(());
+        }
 
         let mut vis = visitor::BasicVisitor { int: &mc };
-        let mut responder = BorrowedResponder::Init(responder);
-        let res = handler
-            .respond(
-                ctx,
-                payload,
-                &mut vis,
-                BorrowingResponder::new(&mut responder),
-            )
-            .await;
+        let responder = Mutex::new(BorrowedResponder::Init(responder));
+        let res = Self::catch_panic(handler.respond(
+            ctx,
+            payload,
+            &mut vis,
+            BorrowingResponder::new(&responder),
+        ))
+        .await;
+        let mut responder = responder.into_inner();
+
+        if res.is_err() {
+            obs.outcome = Outcome::Error;
+        }
+
+        if matches!(responder, BorrowedResponder::Poison) {
+            tracing::error!("Handler panicked mid-response, unable to send error");
+            return Ok(());
+        }
 
         if let Some(msg) = res
             .err()
-            .and_then(|e| Self::pretty_handler_error(e, "component"))
+            .and_then(|e| self.pretty_handler_error(e, "component", mc.id, &name, &issuer))
         {
             responder.create_or_followup(msg).await?;
         }
@@ -664,7 +1436,7 @@ impl<S: Schema> Registry<S> {
         level = "error",
         name = "handle_autocomplete",
         err,
-        skip(self, ctx, ac)
+        skip(self, ctx, ac, obs)
     )]
     async fn try_handle_autocomplete(
         &self,
@@ -673,22 +1445,43 @@ impl<S: Schema> Registry<S> {
         name: String,
         id: String,
         issuer: String,
+        obs: &mut Handled,
     ) -> Result<(), serenity::Error> {
         tracing::trace!("Handling command autocomplete");
 
+        // Shed autocompletes still get an (empty) response
+        let permit = self.admit(ac.guild_id, ac.user.id, Priority::Low).await;
         let map = self.commands.read().await;
-        let handler = Self::resolve_command(&map, ac.data.id).ok();
+        let handler = permit
+            .is_ok()
+            .then(|| Self::resolve_command(&map, ac.data.id).ok())
+            .flatten();
 
         let mut vis = visitor::CommandVisitor::new(&ac);
+        obs.outcome = if handler.is_some() {
+            Outcome::Ok
+        } else {
+            Outcome::Rejected
+        };
         let choices = if let Some(handler) = handler {
-            handler
-                .complete(ctx, &mut vis)
+            AssertUnwindSafe(handler.complete(ctx, &mut vis))
+                .catch_unwind()
                 .await
-                .map_err(|err| tracing::error!(%err, "Error in command completion"))
+                .map_err(|payload| {
+                    let panic = panic_message(&*payload);
+                    tracing::error!(panic, "Completion handler panicked");
+                })
                 .ok()
+                .and_then(|r| {
+                    r.map_err(|err| tracing::error!(%err, "Error in command completion"))
+                        .ok()
+                })
         } else {
             None
         };
+        if handler.is_some() && choices.is_none() {
+            obs.outcome = Outcome::Error;
+        }
         tracing::trace!(?handler, "Command handler selected");
 
         ac.create_response(
@@ -705,7 +1498,7 @@ impl<S: Schema> Registry<S> {
         .await
     }
 
-    #[tracing::instrument(level = "error", name = "handle_modal", err, skip(self, ctx, ms))]
+    #[tracing::instrument(level = "error", name = "handle_modal", err, skip(self, ctx, ms, obs))]
     async fn try_handle_modal(
         &self,
         ctx: &Context,
@@ -713,39 +1506,59 @@ impl<S: Schema> Registry<S> {
         name: String,
         id: String,
         issuer: String,
+        obs: &mut Handled,
     ) -> Result<(), ResponseError> {
         tracing::info!("Handling modal submit");
 
-        let map = self.modals.read().await;
-        let responder = InitResponder::new(&ctx.http, &ms);
-        let (handler, src, payload) = match Self::resolve_modal(&map, unsafe {
-            &id::Id::from_inner(ms.data.custom_id.as_str().into())
-        }) {
-            Ok(p) => p,
-            Err(e) => {
-                return responder
-                    .create_message(Message::plain(e).ephemeral(true))
-                    .await
-                    .map(|_| ());
-            },
+        let responder =
+            InitResponder::new(&ctx.http, &ms, InteractionCtx::new(ms.id, ctx.shard_id))
+                .with_mentions(&self.mentions);
+        let Ok(_permit) = self.admit(ms.guild_id, ms.user.id, Priority::High).await else {
+            obs.outcome = Outcome::Rejected;
+            return responder
+                .create_message(Message::plain(BUSY_MESSAGE).ephemeral(true))
+                .await
+                .map(|_| ());
         };
+
+        let map = self.modals.read().await;
+        let (handler, src, payload) =
+            match Self::resolve_modal(&map, &id::Id::from_raw(ms.data.custom_id.as_str())) {
+                Ok(p) => p,
+                Err(e) => {
+                    obs.outcome = Outcome::Rejected;
+                    return responder
+                        .create_message(self.rejection(e))
+                        .await
+                        .map(|_| ());
+                },
+            };
         tracing::debug!(?handler, ?src, ?payload, "Modal handler selected");
-        let _ = src; // TODO: use this
+        obs.name = format!("{:?}", S::ModalKey::from(&payload));
 
         let mut vis = visitor::BasicVisitor { int: &ms };
-        let mut responder = BorrowedResponder::Init(responder);
-        let res = handler
-            .respond(
-                ctx,
-                payload,
-                &mut vis,
-                BorrowingResponder::new(&mut responder),
-            )
-            .await;
+        let responder = Mutex::new(BorrowedResponder::Init(responder));
+        let res = Self::catch_panic(handler.respond(
+            ctx,
+            payload,
+            &mut vis,
+            BorrowingResponder::new(&responder).with_modal_source(src),
+        ))
+        .await;
+        let mut responder = responder.into_inner();
+
+        if res.is_err() {
+            obs.outcome = Outcome::Error;
+        }
+
+        if matches!(responder, BorrowedResponder::Poison) {
+            tracing::error!("Handler panicked mid-response, unable to send error");
+            return Ok(());
+        }
 
         if let Some(msg) = res
             .err()
-            .and_then(|e| Self::pretty_handler_error(e, "modal"))
+            .and_then(|e| self.pretty_handler_error(e, "modal", ms.id, &name, &issuer))
         {
             responder.create_or_followup(msg).await?;
         }
@@ -753,13 +1566,32 @@ impl<S: Schema> Registry<S> {
         Ok(())
     }
 
+    /// Report a handled interaction to the observer, if any, counting a
+    /// failure to respond as an error
+    fn observe(&self, mut obs: Handled, start: Instant, responded: bool) {
+        let Some(ref observer) = self.observer else {
+            return;
+        };
+
+        if !responded {
+            obs.outcome = Outcome::Error;
+        }
+        obs.latency = start.elapsed();
+        observer.handled(&obs);
+    }
+
     /// Dispatch a command interaction to the proper handler and submit a
     /// response
     #[inline]
     pub async fn handle_command(&self, ctx: &Context, aci: CommandInteraction) {
         let cache = &ctx.cache;
         let (name, id, iss) = (aci_name(cache, &aci), aci_id(&aci), aci_issuer(cache, &aci));
-        self.try_handle_command(ctx, aci, name, id, iss).await.ok();
+        let mut obs = Handled::new("command", aci.data.name.as_str());
+        let start = Instant::now();
+        let res = self
+            .try_handle_command(ctx, aci, name, id, iss, &mut obs)
+            .await;
+        self.observe(obs, start, res.is_ok());
     }
 
     /// Dispatch a component interaction to the proper handler and submit a
@@ -768,7 +1600,31 @@ impl<S: Schema> Registry<S> {
     pub async fn handle_component(&self, ctx: &Context, mc: ComponentInteraction) {
         let cache = &ctx.cache;
         let (name, id, iss) = (mc_name::<S>(&mc), mc_id(&mc), mc_issuer(cache, &mc));
-        self.try_handle_component(ctx, mc, name, id, iss).await.ok();
+        let mut obs = Handled::new("component", "unknown");
+        let start = Instant::now();
+        let mc = match self.collectors.offer_component(mc) {
+            Ok(()) => {
+                tracing::debug!(id, "Component interaction collected");
+                obs.name = "collector".into();
+                return self.observe(obs, start, true);
+            },
+            Err(mc) => mc,
+        };
+        let res = self
+            .try_handle_component(ctx, mc, name, id, iss, &mut obs)
+            .await;
+        self.observe(obs, start, res.is_ok());
+    }
+
+    /// Hand a reaction to a waiting collector, returning true if one accepted
+    /// it
+    ///
+    /// Reactions are not interactions and do not otherwise pass through the
+    /// registry, so the event handler should call this for every reaction
+    /// added.
+    #[inline]
+    pub fn offer_reaction(&self, reaction: &Reaction) -> bool {
+        self.collectors.offer_reaction(reaction)
     }
 
     /// Dispatch an autocomplete interaction to the proper handler and submit a
@@ -777,9 +1633,12 @@ impl<S: Schema> Registry<S> {
     pub async fn handle_autocomplete(&self, ctx: &Context, ac: CommandInteraction) {
         let cache = &ctx.cache;
         let (name, id, iss) = (ac_name(cache, &ac), ac_id(&ac), ac_issuer(cache, &ac));
-        self.try_handle_autocomplete(ctx, ac, name, id, iss)
-            .await
-            .ok();
+        let mut obs = Handled::new("autocomplete", ac.data.name.as_str());
+        let start = Instant::now();
+        let res = self
+            .try_handle_autocomplete(ctx, ac, name, id, iss, &mut obs)
+            .await;
+        self.observe(obs, start, res.is_ok());
     }
 
     /// Dispatch a modal-submit interaction to the proper handler and submit a
@@ -788,6 +1647,130 @@ impl<S: Schema> Registry<S> {
     pub async fn handle_modal(&self, ctx: &Context, ms: ModalInteraction) {
         let cache = &ctx.cache;
         let (name, id, iss) = (ms_name::<S>(&ms), ms_id(&ms), ms_issuer(cache, &ms));
-        self.try_handle_modal(ctx, ms, name, id, iss).await.ok();
+        let mut obs = Handled::new("modal", "unknown");
+        let start = Instant::now();
+        let res = self
+            .try_handle_modal(ctx, ms, name, id, iss, &mut obs)
+            .await;
+        self.observe(obs, start, res.is_ok());
+    }
+}
+
+/// Run `res` to completion, running `defer` first if `res` has not finished
+/// after the given delay
+async fn race_deferral<T>(
+    res: impl Future<Output = T>,
+    after: Duration,
+    defer: impl Future<Output = ()>,
+) -> T {
+    tokio::pin!(res);
+
+    tokio::select! {
+        out = &mut res => return out,
+        () = tokio::time::sleep(after) => (),
+    }
+
+    defer.await;
+    res.await
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use chrono::{DateTime, TimeDelta, Utc};
+    use serenity::model::application::ActionRow;
+
+    use super::{disabled_components, is_expired, panic_message, race_deferral};
+
+    #[test]
+    fn expiry() {
+        let sent = DateTime::<Utc>::UNIX_EPOCH;
+        let now = sent + TimeDelta::minutes(10);
+        let mins = |m: u64| Some(Duration::from_secs(m * 60));
+
+        assert!(!is_expired(None, None, sent, now));
+        assert!(!is_expired(
+            Some(now + TimeDelta::seconds(1)),
+            None,
+            sent,
+            now
+        ));
+        assert!(is_expired(Some(now), None, sent, now));
+        assert!(!is_expired(None, mins(11), sent, now));
+        assert!(is_expired(None, mins(10), sent, now));
+        assert!(is_expired(Some(now), mins(11), sent, now));
+        assert!(!is_expired(None, Some(Duration::MAX), sent, now));
+    }
+
+    #[test]
+    fn disable() {
+        let rows: Vec<ActionRow> = serde_json::from_value(serde_json::json!([
+            {
+                "type": 1,
+                "components": [
+                    { "type": 2, "style": 1, "custom_id": "a", "label": "A" },
+                    { "type": 2, "style": 5, "url": "https://example.com", "label": "B" },
+                ],
+            },
+            {
+                "type": 1,
+                "components": [{ "type": 3, "custom_id": "c", "options": [] }],
+            },
+        ]))
+        .unwrap();
+
+        let body = disabled_components(&rows);
+        let disabled: Vec<_> = body["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|r| r["components"].as_array().unwrap())
+            .map(|c| c.get("disabled").and_then(serde_json::Value::as_bool))
+            .collect();
+
+        assert_eq!(disabled, [Some(true), Some(false), Some(true)]);
+    }
+
+    #[test]
+    fn panic_messages() {
+        let msg = |f: fn()| panic_message(&*std::panic::catch_unwind(f).unwrap_err()).to_owned();
+
+        let (fixed, formatted, other) = (
+            msg(|| panic!("oh no")),
+            msg(|| panic!("oh {}", "no")),
+            msg(|| std::panic::panic_any(42)),
+        );
+
+        assert_eq!(fixed, "oh no");
+        assert_eq!(formatted, "oh no");
+        assert_eq!(other, "<non-string panic payload>");
+    }
+
+    #[test]
+    fn auto_defer() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let ms = Duration::from_millis;
+
+        let run = |work: Duration| {
+            let deferred = AtomicBool::new(false);
+            let (out, at_finish) = rt.block_on(race_deferral(
+                async {
+                    tokio::time::sleep(work).await;
+                    (42, deferred.load(Ordering::SeqCst))
+                },
+                ms(50),
+                async { deferred.store(true, Ordering::SeqCst) },
+            ));
+            (out, at_finish, deferred.into_inner())
+        };
+
+        assert_eq!(run(ms(0)), (42, false, false));
+        assert_eq!(run(ms(200)), (42, true, true));
     }
 }
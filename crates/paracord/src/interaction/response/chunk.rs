@@ -0,0 +1,422 @@
+use std::mem;
+
+/// The longest message content Discord accepts, in characters
+pub const MAX_CONTENT_LEN: usize = 2000;
+
+/// The shortest chunk length [`chunk_content`] accepts, leaving room for the
+/// formatting it adds around each chunk
+pub const MIN_CHUNK_LEN: usize = 100;
+
+const FENCE: &str = "```";
+/// Longest code block opening line reopened in the next chunk, beyond which
+/// the language tag is dropped
+const MAX_FENCE_LEN: usize = 32;
+/// Inline formatting markers closed before a break in the middle of a line
+/// and reopened after it, longest first
+const MARKERS: [&str; 5] = ["**", "__", "~~", "||", "`"];
+/// Room reserved for closing every inline marker
+const MARKER_RESERVE: usize = 9;
+/// Longest span searched for the end of a mention or other `<...>` token
+const MAX_TOKEN_LEN: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Break {
+    /// The character is whitespace that can be replaced by a break
+    Soft,
+    /// A break can be inserted before the character
+    Hard,
+    /// The character cannot be separated from the one before it
+    None,
+}
+
+/// Find the end of a mention, custom emoji, timestamp, command mention or
+/// suppressed link starting at the given `<`
+fn token_end(chars: &[char], start: usize) -> Option<usize> {
+    let body = &chars[start + 1..chars.len().min(start + MAX_TOKEN_LEN)];
+    let len = body.iter().position(|&c| c == '>')?;
+    let body = &body[..len];
+
+    let spaces = match body {
+        ['/', ..] => true,
+        ['@' | '#' | ':', ..] | ['a' | 't', ':', ..] | ['h', 't', 't', 'p', ..] => false,
+        _ => return None,
+    };
+
+    (spaces || !body.iter().any(|c| c.is_whitespace())).then_some(start + 1 + len)
+}
+
+/// Classify the position before each character of a line, plus the end of
+/// the line
+fn break_points(chars: &[char], fenced: bool) -> Vec<Break> {
+    let mut breaks = vec![Break::Hard; chars.len() + 1];
+    let mut code = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '`' if !fenced => code = !code,
+            '<' if !fenced && !code => {
+                if let Some(end) = token_end(chars, i) {
+                    breaks[i + 1..=end].fill(Break::None);
+                    i = end + 1;
+                    continue;
+                }
+            },
+            c if c.is_whitespace() && !code => breaks[i] = Break::Soft,
+            _ => (),
+        }
+
+        if i > 0 && chars[i - 1] == chars[i] && "*_~|`".contains(chars[i]) {
+            breaks[i] = Break::None;
+        }
+
+        i += 1;
+    }
+
+    breaks
+}
+
+/// Update the list of open inline markers with those toggled in the given
+/// text
+fn scan_markers(text: &str, open: &mut Vec<&'static str>) {
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        if open.last() == Some(&"`") {
+            if c == '`' {
+                open.pop();
+            }
+        } else if c == '\\' {
+            rest = &rest[1..];
+            let Some(c) = rest.chars().next() else { break };
+            rest = &rest[c.len_utf8()..];
+            continue;
+        } else if let Some(marker) = MARKERS.into_iter().find(|m| rest.starts_with(m)) {
+            if let Some(pos) = open.iter().rposition(|&m| m == marker) {
+                open.remove(pos);
+            } else {
+                open.push(marker);
+            }
+
+            rest = &rest[marker.len()..];
+            continue;
+        }
+
+        rest = &rest[c.len_utf8()..];
+    }
+}
+
+/// Compute the code block state after a line, given the opening line of the
+/// code block it starts inside, if any
+fn next_fence(fence: Option<&str>, line: &str) -> Option<String> {
+    let count = line.matches(FENCE).count();
+    if count % 2 == 0 {
+        return fence.map(Into::into);
+    }
+
+    if fence.is_some() {
+        return None;
+    }
+
+    let lang = line.rsplit(FENCE).next().unwrap_or_default().trim();
+    Some(
+        if lang.is_empty()
+            || lang.contains(char::is_whitespace)
+            || FENCE.len() + lang.len() > MAX_FENCE_LEN
+        {
+            FENCE.into()
+        } else {
+            format!("{FENCE}{lang}")
+        },
+    )
+}
+
+#[inline]
+fn closing_len(fenced: bool, newline: bool) -> usize {
+    if fenced {
+        FENCE.len() + usize::from(!newline)
+    } else {
+        0
+    }
+}
+
+#[derive(Debug)]
+struct Chunker {
+    max_len: usize,
+    chunks: Vec<String>,
+    buf: String,
+    /// The length of the buffer, in characters
+    len: usize,
+    /// True if the buffer holds only the formatting reopened from the last
+    /// chunk
+    fresh: bool,
+    /// The opening line of the code block the buffer ends inside, if any
+    fence: Option<String>,
+    /// True if the rest of the content is inside a multi-line block quote
+    quote: bool,
+}
+
+impl Chunker {
+    fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            chunks: vec![],
+            buf: String::new(),
+            len: 0,
+            fresh: true,
+            fence: None,
+            quote: false,
+        }
+    }
+
+    fn push(&mut self, s: &str) {
+        self.buf.push_str(s);
+        self.len += s.chars().count();
+    }
+
+    /// Close any open code block and end the current chunk, starting the next
+    /// by reopening the current block quote and code block followed by the
+    /// given text
+    fn flush(&mut self, reopen: &str) {
+        if self.fence.is_some() {
+            if !self.buf.ends_with('\n') {
+                self.buf.push('\n');
+            }
+            self.buf.push_str(FENCE);
+        } else {
+            self.buf.truncate(self.buf.trim_end().len());
+        }
+
+        let buf = mem::take(&mut self.buf);
+        if !self.fresh && !buf.trim().is_empty() {
+            self.chunks.push(buf);
+        }
+
+        self.len = 0;
+        if self.quote {
+            self.push(">>> ");
+        }
+        if let Some(fence) = self.fence.clone() {
+            self.push(&fence);
+            self.push("\n");
+        }
+        self.push(reopen);
+        self.fresh = true;
+    }
+
+    fn line(&mut self, line: &str) {
+        let fenced = self.fence.is_some();
+        if self.fresh && !fenced && line.trim().is_empty() {
+            return;
+        }
+
+        let fence = next_fence(self.fence.as_deref(), line);
+        let len = line.chars().count() + closing_len(fence.is_some(), line.ends_with('\n'));
+        // Lines too long for any one chunk start in the current chunk instead,
+        // unless it is nearly full
+        if self.len + len > self.max_len
+            && !self.fresh
+            && (len <= self.max_len || self.len + MIN_CHUNK_LEN > self.max_len)
+        {
+            self.flush("");
+            return self.line(line);
+        }
+
+        if !fenced && line.starts_with(">>> ") {
+            self.quote = true;
+        }
+
+        if self.len + len <= self.max_len {
+            self.push(line);
+            self.fresh = false;
+            self.fence = fence;
+        } else {
+            self.split_line(line, fence);
+        }
+    }
+
+    /// Spread a line too long for any one chunk over several, ending each
+    /// chunk at the last break that fits
+    fn split_line(&mut self, line: &str, fence: Option<String>) {
+        let chars: Vec<_> = line.chars().collect();
+        let fenced = self.fence.is_some();
+        let mut breaks = break_points(&chars, fenced);
+        let quoted = !fenced && !self.quote && line.starts_with("> ");
+        if quoted {
+            // Keep the quote marker with the text it quotes
+            breaks[1..=2].fill(Break::None);
+        }
+        let reserve = closing_len(fenced, false) + if fenced { 0 } else { MARKER_RESERVE };
+        let mut open = vec![];
+        let mut start = 0;
+
+        loop {
+            let rest = chars.len() - start;
+            if self.len + rest + closing_len(fence.is_some(), line.ends_with('\n')) <= self.max_len
+            {
+                self.push(&chars[start..].iter().collect::<String>());
+                self.fresh = false;
+                self.fence = fence;
+                break;
+            }
+
+            let end = (start + self.max_len.saturating_sub(self.len + reserve))
+                .min(chars.len() - 1)
+                .max(start + 1);
+            let (piece_end, next) =
+                if let Some(i) = (start + 1..=end).rev().find(|&i| breaks[i] == Break::Soft) {
+                    (i, i + 1)
+                } else {
+                    let i = (start + 1..=end)
+                        .rev()
+                        .find(|&i| breaks[i] == Break::Hard)
+                        .unwrap_or(end);
+                    (i, i)
+                };
+
+            let piece: String = chars[start..piece_end].iter().collect();
+            self.push(&piece);
+            self.fresh = false;
+
+            let mut reopen = String::new();
+            if quoted {
+                reopen.push_str("> ");
+            }
+            if !fenced {
+                scan_markers(&piece, &mut open);
+                for marker in open.iter().rev() {
+                    self.push(marker);
+                }
+                reopen.extend(open.iter().copied());
+            }
+
+            self.flush(&reopen);
+            start = next;
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.flush("");
+        self.chunks
+    }
+}
+
+/// Split message content into chunks of at most `max_len` characters without
+/// breaking its formatting
+///
+/// Chunks end at line breaks where possible, and lines too long for a single
+/// chunk are broken at whitespace, never inside a mention, custom emoji,
+/// timestamp or link.  Code blocks and multi-line block quotes cut off by the
+/// end of a chunk are closed and reopened in the next, as are bold,
+/// underline, strikethrough, spoiler and inline code spans cut off in the
+/// middle of a line.  Blank lines at the edges of each chunk are dropped.
+///
+/// The first chunk is suitable as an interaction response, with the rest sent
+/// as followups.  No chunks are returned if the content is blank.  Lengths
+/// shorter than [`MIN_CHUNK_LEN`] are treated as that length.
+#[must_use]
+pub fn chunk_content(content: &str, max_len: usize) -> Vec<String> {
+    let mut chunker = Chunker::new(max_len.max(MIN_CHUNK_LEN));
+
+    for line in content.split_inclusive('\n') {
+        chunker.line(line);
+    }
+
+    chunker.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{chunk_content, MIN_CHUNK_LEN};
+
+    fn check(chunks: &[String], max_len: usize) {
+        for chunk in chunks {
+            assert!(
+                chunk.chars().count() <= max_len,
+                "Chunk too long: {chunk:?}"
+            );
+            assert_eq!(
+                chunk.matches("```").count() % 2,
+                0,
+                "Unclosed code block: {chunk:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn short() {
+        assert_eq!(chunk_content("hello\nworld\n", 2000), ["hello\nworld"]);
+        assert!(chunk_content(" \n\n", 2000).is_empty());
+    }
+
+    #[test]
+    fn lines() {
+        let content = (0..50).fold(String::new(), |s, i| s + &format!("line {i}\n\n"));
+        let chunks = chunk_content(&content, MIN_CHUNK_LEN);
+        check(&chunks, MIN_CHUNK_LEN);
+        assert!(chunks.len() > 1);
+
+        let lines: Vec<_> = chunks
+            .iter()
+            .flat_map(|c| c.lines())
+            .filter(|l| !l.is_empty())
+            .collect();
+        assert_eq!(lines.len(), 50);
+        assert!(lines
+            .iter()
+            .enumerate()
+            .all(|(i, l)| *l == format!("line {i}")));
+    }
+
+    #[test]
+    fn code_blocks() {
+        let content = format!(
+            "Here's the log:\n```rust\n{}```\ndone",
+            "let x = 1;\n".repeat(30)
+        );
+        let chunks = chunk_content(&content, MIN_CHUNK_LEN);
+        check(&chunks, MIN_CHUNK_LEN);
+        assert!(chunks[0].starts_with("Here's the log:\n```rust\n"));
+        assert!(chunks[1..chunks.len() - 1]
+            .iter()
+            .all(|c| c.starts_with("```rust\n")));
+        assert!(chunks.last().unwrap().ends_with("done"));
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|c| c.matches("let x").count())
+                .sum::<usize>(),
+            30
+        );
+    }
+
+    #[test]
+    fn long_lines() {
+        let words = "**bold <@1234567890> text** ".repeat(20);
+        let chunks = chunk_content(&words, MIN_CHUNK_LEN);
+        check(&chunks, MIN_CHUNK_LEN);
+
+        for chunk in &chunks {
+            assert_eq!(chunk.matches("**").count() % 2, 0, "Unbalanced: {chunk:?}");
+            assert_eq!(
+                chunk.matches('<').count(),
+                chunk.matches("<@1234567890>").count()
+            );
+        }
+
+        let unbroken = "x".repeat(250);
+        let chunks = chunk_content(&format!("> {unbroken}"), MIN_CHUNK_LEN);
+        check(&chunks, MIN_CHUNK_LEN);
+        assert!(chunks.iter().all(|c| c.starts_with("> ")));
+        assert_eq!(chunks.concat().matches('x').count(), 250);
+    }
+
+    #[test]
+    fn quotes() {
+        let content = format!(">>> {}", "quoted\n".repeat(40));
+        let chunks = chunk_content(&content, MIN_CHUNK_LEN);
+        check(&chunks, MIN_CHUNK_LEN);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.starts_with(">>> quoted")));
+    }
+}
@@ -4,17 +4,18 @@ use std::{
     marker::PhantomData,
 };
 
+use chrono::{DateTime, Utc};
 use qcore::{
     build_range::BuildRange,
     build_with::{BuildWith, BuilderHelpers},
     builder,
 };
 use serenity::{
-    all::{ChannelId, RoleId, UserId},
+    all::{ChannelId, RoleId, SkuId, UserId},
     builder::{
         CreateActionRow, CreateButton, CreateInputText, CreateInteractionResponseFollowup,
-        CreateInteractionResponseMessage, CreateModal, CreateSelectMenu, CreateSelectMenuKind,
-        CreateSelectMenuOption, EditInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, CreateModal, CreateSelectMenu,
+        CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse,
     },
     model::{
         application::{ButtonStyle as ButtonStyleModel, InputTextStyle},
@@ -23,7 +24,10 @@ use serenity::{
 };
 use url::Url;
 
-use super::{super::rpc::ComponentId, id, Prepare};
+use super::{
+    super::{locale::Locale, rpc::ComponentId},
+    id, Localized, Prepare,
+};
 
 /// A set of components to attach to a message
 #[derive(Debug)]
@@ -42,28 +46,6 @@ macro_rules! build_components {
     }};
 }
 
-impl<I: ComponentId> Components<MessageComponent<I, id::Error>> {
-    fn menu_parts(
-        &mut self,
-        payload: I::Payload,
-        ty: MenuType<I, id::Error>,
-        placeholder: impl Into<Option<String>>,
-        count: impl BuildRange<u8>,
-        disabled: bool,
-    ) {
-        let (min_count, max_count) = count.build_range().into_inner();
-        self.0.push(MessageComponent::Menu(Menu {
-            id: id::write(&I::from_parts(payload)),
-            ty,
-            placeholder: placeholder.into(),
-            min_count: min_count.unwrap_or(0),
-            max_count, // max allowed
-            disabled,
-            rpc_id: PhantomData,
-        }));
-    }
-}
-
 #[builder(trait_name = ComponentsExt)]
 /// Helper methods for mutating [`Components`]
 impl<R> Components<R> {
@@ -71,6 +53,15 @@ impl<R> Components<R> {
     pub fn row(&mut self, row: R) { self.0.push(row); }
 }
 
+/// Re-encode a component ID with the given expiry time
+fn stamp_expiry<I: ComponentId>(id: &mut Result<id::Id<'static>, id::Error>, at: DateTime<Utc>) {
+    let Ok(ref inner) = *id else { return };
+    *id = id::read::<I>(inner).and_then(|mut msg| {
+        msg.set_expires_at(Some(at));
+        id::write(&msg)
+    });
+}
+
 /// Helper methods for mutating [`Components`] for messages
 #[builder(trait_name = MessageComponents)]
 impl<I: ComponentId> Components<MessageComponent<I, id::Error>> {
@@ -90,35 +81,14 @@ impl<I: ComponentId> Components<MessageComponent<I, id::Error>> {
         default: impl IntoIterator<Item = usize>,
         options: impl IntoIterator<Item = (I::Payload, J)>,
     ) {
-        let (items, order) = options.into_iter().fold(
-            (HashMap::new(), vec![]),
-            |(mut items, mut order), (payload, item)| {
-                let id = id::write(&I::from_parts(payload));
-
-                if let Ok(ref id) = id {
-                    assert!(items.insert(id.clone(), item.into()).is_none());
-                }
-                order.push(id);
-
-                (items, order)
-            },
-        );
-
-        let default: HashSet<_> = default.into_iter().collect();
-        assert!(default.iter().all(|d| order.len() > *d));
-
-        self.menu_parts(
+        self.0.push(MessageComponent::Menu(Menu::string(
             payload,
-            MenuType::String {
-                items,
-                order,
-                default,
-                rpc_id: PhantomData,
-            },
             placeholder,
             count,
             disabled,
-        );
+            default,
+            options,
+        )));
     }
 
     /// Add a new row with a user handle dropdown menu
@@ -131,13 +101,13 @@ impl<I: ComponentId> Components<MessageComponent<I, id::Error>> {
         disabled: bool,
         default: impl IntoIterator<Item = UserId>,
     ) {
-        self.menu_parts(
+        self.0.push(MessageComponent::Menu(Menu::new(
             payload,
             MenuType::User(default.into_iter().collect()),
             placeholder,
             count,
             disabled,
-        );
+        )));
     }
 
     /// Add a new row with a role handle dropdown menu
@@ -150,13 +120,13 @@ impl<I: ComponentId> Components<MessageComponent<I, id::Error>> {
         disabled: bool,
         default: impl IntoIterator<Item = RoleId>,
     ) {
-        self.menu_parts(
+        self.0.push(MessageComponent::Menu(Menu::new(
             payload,
             MenuType::Role(default.into_iter().collect()),
             placeholder,
             count,
             disabled,
-        );
+        )));
     }
 
     /// Add a new row with a user or role handle dropdown menu
@@ -170,7 +140,7 @@ impl<I: ComponentId> Components<MessageComponent<I, id::Error>> {
         default_user: impl IntoIterator<Item = UserId>,
         default_role: impl IntoIterator<Item = RoleId>,
     ) {
-        self.menu_parts(
+        self.0.push(MessageComponent::Menu(Menu::new(
             payload,
             MenuType::Mention(
                 default_user.into_iter().collect(),
@@ -179,7 +149,7 @@ impl<I: ComponentId> Components<MessageComponent<I, id::Error>> {
             placeholder,
             count,
             disabled,
-        );
+        )));
     }
 
     /// Add a new row with a channel dropdown menu
@@ -193,28 +163,50 @@ impl<I: ComponentId> Components<MessageComponent<I, id::Error>> {
         tys: impl IntoIterator<Item = ChannelType>,
         default: impl IntoIterator<Item = ChannelId>,
     ) {
-        self.menu_parts(
+        self.0.push(MessageComponent::Menu(Menu::new(
             payload,
             MenuType::Channel(tys.into_iter().collect(), default.into_iter().collect()),
             placeholder,
             count,
             disabled,
-        );
+        )));
+    }
+
+    /// Mark every component added so far as expiring at the given time
+    ///
+    /// Interactions with an expired component are rejected by the
+    /// [`Registry`](crate::interaction::registry::Registry) without reaching
+    /// their handler.  Components added after calling this method are not
+    /// affected.
+    pub fn expire_at(&mut self, at: DateTime<Utc>) {
+        for row in &mut self.0 {
+            match row {
+                MessageComponent::Buttons(btns) => {
+                    for btn in btns {
+                        if let ButtonType::Custom { ref mut id, .. } = btn.ty {
+                            stamp_expiry::<I>(id, at);
+                        }
+                    }
+                },
+                MessageComponent::Menu(menu) => stamp_expiry::<I>(&mut menu.id, at),
+            }
+        }
     }
 }
 
 /// Helper methods for mutating [`Components`] for modals
 #[builder(trait_name = ModalComponents)]
-impl<I: ComponentId> Components<TextInput<I, id::Error>> {
+impl<I: ComponentId> Components<ModalComponent<I, id::Error>> {
     /// Create a row with a short textbox using the given closure
     #[inline]
     pub fn text_short(
         &mut self,
         payload: I::Payload,
-        label: impl Into<String>,
+        label: impl Into<Localized>,
         f: impl FnOnce(TextInput<I, id::Error>) -> TextInput<I, id::Error>,
     ) {
-        self.0.push(f(TextInput::short(payload, label)));
+        self.0
+            .push(ModalComponent::Text(f(TextInput::short(payload, label))));
     }
 
     /// Create a row with a paragraph textbox using the given closure
@@ -222,10 +214,30 @@ impl<I: ComponentId> Components<TextInput<I, id::Error>> {
     pub fn text_long(
         &mut self,
         payload: I::Payload,
-        label: impl Into<String>,
+        label: impl Into<Localized>,
         f: impl FnOnce(TextInput<I, id::Error>) -> TextInput<I, id::Error>,
     ) {
-        self.0.push(f(TextInput::long(payload, label)));
+        self.0
+            .push(ModalComponent::Text(f(TextInput::long(payload, label))));
+    }
+
+    /// Add a new row with a string dropdown menu
+    pub fn menu<J: Into<MenuItem>>(
+        &mut self,
+        payload: I::Payload,
+        placeholder: impl Into<Option<String>>,
+        count: impl BuildRange<u8>,
+        default: impl IntoIterator<Item = usize>,
+        options: impl IntoIterator<Item = (I::Payload, J)>,
+    ) {
+        self.0.push(ModalComponent::Menu(Menu::string(
+            payload,
+            placeholder,
+            count,
+            false,
+            default,
+            options,
+        )));
     }
 }
 
@@ -263,6 +275,13 @@ where CreateActionRow: From<R>
     fn build_with(self, value: Components<R>) -> Self { build_components!(value, self) }
 }
 
+impl<R> BuildWith<Components<R>> for CreateMessage
+where CreateActionRow: From<R>
+{
+    #[inline]
+    fn build_with(self, value: Components<R>) -> Self { build_components!(value, self) }
+}
+
 impl<R> BuildWith<Components<R>> for CreateModal
 where CreateActionRow: From<R>
 {
@@ -296,7 +315,7 @@ impl<I: ComponentId> ButtonsBuilder<I> {
         disabled: bool,
     ) {
         self.0.push(Button {
-            label: label.into(),
+            label: Some(label.into()),
             ty: ButtonType::Custom {
                 id: id::write(&I::from_parts(payload)),
                 style,
@@ -310,7 +329,19 @@ impl<I: ComponentId> ButtonsBuilder<I> {
     pub fn link(&mut self, url: impl Into<Url>, label: impl Into<ButtonLabel>, disabled: bool) {
         self.0.push(Button {
             ty: ButtonType::Link(url.into()),
-            label: label.into(),
+            label: Some(label.into()),
+            disabled,
+        });
+    }
+
+    /// Add a new premium button to this row, prompting the user to purchase
+    /// the given SKU
+    ///
+    /// Premium buttons are labeled by Discord using the SKU's name and price.
+    pub fn premium(&mut self, sku: SkuId, disabled: bool) {
+        self.0.push(Button {
+            ty: ButtonType::Premium(sku),
+            label: None,
             disabled,
         });
     }
@@ -354,7 +385,7 @@ impl<I> From<MessageComponent<I, Infallible>> for CreateActionRow {
 #[derive(Debug)]
 pub struct Button<I, E> {
     ty: ButtonType<I, E>,
-    label: ButtonLabel,
+    label: Option<ButtonLabel>,
     disabled: bool,
 }
 
@@ -386,6 +417,7 @@ impl<I> From<Button<I, Infallible>> for CreateButton {
         } = value;
         match ty {
             ButtonType::Link(l) => CreateButton::new_link(l),
+            ButtonType::Premium(s) => CreateButton::new_premium(s),
             ButtonType::Custom {
                 id,
                 style,
@@ -393,7 +425,7 @@ impl<I> From<Button<I, Infallible>> for CreateButton {
             } => CreateButton::new(id.unwrap_or_else(|_| unreachable!()).to_string())
                 .style(style.into()),
         }
-        .build_with(label)
+        .fold_opt(label, BuildWith::build_with)
         .disabled(disabled)
     }
 }
@@ -441,6 +473,8 @@ impl BuildWith<ButtonLabel> for CreateButton {
 pub enum ButtonType<I, E> {
     /// A link-style button
     Link(Url),
+    /// A premium button for purchasing the given SKU
+    Premium(SkuId),
     // TODO: make this a struct?
     /// A non-link button
     Custom {
@@ -460,6 +494,7 @@ impl<I, E> Prepare for ButtonType<I, E> {
     fn prepare(self) -> Result<Self::Output, Self::Error> {
         Ok(match self {
             Self::Link(u) => ButtonType::Link(u),
+            Self::Premium(s) => ButtonType::Premium(s),
             Self::Custom { id, style, rpc_id } => ButtonType::Custom {
                 id: Ok(id?),
                 style,
@@ -505,6 +540,66 @@ pub struct Menu<I, E> {
     rpc_id: PhantomData<fn(I)>,
 }
 
+impl<I: ComponentId> Menu<I, id::Error> {
+    fn new(
+        payload: I::Payload,
+        ty: MenuType<I, id::Error>,
+        placeholder: impl Into<Option<String>>,
+        count: impl BuildRange<u8>,
+        disabled: bool,
+    ) -> Self {
+        let (min_count, max_count) = count.build_range().into_inner();
+        Self {
+            id: id::write(&I::from_parts(payload)),
+            ty,
+            placeholder: placeholder.into(),
+            min_count: min_count.unwrap_or(0),
+            max_count, // max allowed
+            disabled,
+            rpc_id: PhantomData,
+        }
+    }
+
+    fn string<J: Into<MenuItem>>(
+        payload: I::Payload,
+        placeholder: impl Into<Option<String>>,
+        count: impl BuildRange<u8>,
+        disabled: bool,
+        default: impl IntoIterator<Item = usize>,
+        options: impl IntoIterator<Item = (I::Payload, J)>,
+    ) -> Self {
+        let (items, order) = options.into_iter().fold(
+            (HashMap::new(), vec![]),
+            |(mut items, mut order), (payload, item)| {
+                let id = id::write(&I::from_parts(payload));
+
+                if let Ok(ref id) = id {
+                    assert!(items.insert(id.clone(), item.into()).is_none());
+                }
+                order.push(id);
+
+                (items, order)
+            },
+        );
+
+        let default: HashSet<_> = default.into_iter().collect();
+        assert!(default.iter().all(|d| order.len() > *d));
+
+        Self::new(
+            payload,
+            MenuType::String {
+                items,
+                order,
+                default,
+                rpc_id: PhantomData,
+            },
+            placeholder,
+            count,
+            disabled,
+        )
+    }
+}
+
 impl<I, E> Prepare for Menu<I, E> {
     type Error = E;
     type Output = Menu<I, Infallible>;
@@ -688,23 +783,65 @@ impl From<&str> for MenuItem {
     }
 }
 
+/// A single row of components that are valid inside a modal
+#[derive(Debug)]
+pub enum ModalComponent<I, E> {
+    /// A textbox
+    Text(TextInput<I, E>),
+    /// A single menu occupying a full row
+    // TODO: serenity does not yet expose the values submitted for menus in a
+    //       modal, so these cannot be read back by a modal visitor
+    Menu(Menu<I, E>),
+}
+
+impl<I, E> ModalComponent<I, E> {
+    #[must_use]
+    pub(super) fn localize(self, locale: &Locale) -> Self {
+        match self {
+            Self::Text(t) => Self::Text(t.localize(locale)),
+            Self::Menu(m) => Self::Menu(m),
+        }
+    }
+}
+
+impl<I, E> Prepare for ModalComponent<I, E> {
+    type Error = E;
+    type Output = ModalComponent<I, Infallible>;
+
+    fn prepare(self) -> Result<Self::Output, Self::Error> {
+        match self {
+            Self::Text(t) => t.prepare().map(ModalComponent::Text),
+            Self::Menu(m) => m.prepare().map(ModalComponent::Menu),
+        }
+    }
+}
+
+impl<I> From<ModalComponent<I, Infallible>> for CreateActionRow {
+    fn from(value: ModalComponent<I, Infallible>) -> Self {
+        match value {
+            ModalComponent::Text(t) => t.into(),
+            ModalComponent::Menu(m) => Self::SelectMenu(m.into()),
+        }
+    }
+}
+
 /// A textbox component, valid only for modals
 #[derive(Debug)]
 pub struct TextInput<I, E> {
     id: Result<id::Id<'static>, E>,
     style: InputTextStyle,
-    label: String,
+    label: Localized,
     min_len: Option<u16>,
     max_len: Option<u16>,
     required: bool,
     value: String,
-    placeholder: Option<String>,
+    placeholder: Option<Localized>,
     rpc_id: PhantomData<fn(I)>,
 }
 
 impl<I: ComponentId> TextInput<I, id::Error> {
     #[inline]
-    fn new(payload: I::Payload, style: InputTextStyle, label: impl Into<String>) -> Self {
+    fn new(payload: I::Payload, style: InputTextStyle, label: impl Into<Localized>) -> Self {
         Self {
             id: id::write(&I::from_parts(payload)),
             style,
@@ -724,7 +861,7 @@ impl<I: ComponentId> TextInput<I, id::Error> {
     /// This function returns an error if the given ID payload cannot be encoded
     /// correctly.
     #[inline]
-    pub fn short(payload: I::Payload, label: impl Into<String>) -> Self {
+    pub fn short(payload: I::Payload, label: impl Into<Localized>) -> Self {
         Self::new(payload, InputTextStyle::Short, label)
     }
 
@@ -734,7 +871,7 @@ impl<I: ComponentId> TextInput<I, id::Error> {
     /// This function returns an error if the given ID payload cannot be encoded
     /// correctly.
     #[inline]
-    pub fn long(payload: I::Payload, label: impl Into<String>) -> Self {
+    pub fn long(payload: I::Payload, label: impl Into<Localized>) -> Self {
         Self::new(payload, InputTextStyle::Paragraph, label)
     }
 }
@@ -756,11 +893,39 @@ impl<I, E> TextInput<I, E> {
     pub fn value(&mut self, val: impl Into<String>) { self.value = val.into(); }
 
     /// Set the empty placeholder text for this textbox
-    pub fn placeholder(&mut self, placeholder: impl Into<String>) {
+    pub fn placeholder(&mut self, placeholder: impl Into<Localized>) {
         self.placeholder = Some(placeholder.into());
     }
 }
 
+impl<I, E> TextInput<I, E> {
+    #[must_use]
+    fn localize(self, locale: &Locale) -> Self {
+        let Self {
+            id,
+            style,
+            label,
+            min_len,
+            max_len,
+            required,
+            value,
+            placeholder,
+            rpc_id,
+        } = self;
+        Self {
+            id,
+            style,
+            label: label.localize(locale),
+            min_len,
+            max_len,
+            required,
+            value,
+            placeholder: placeholder.map(|p| p.localize(locale)),
+            rpc_id,
+        }
+    }
+}
+
 impl<I, E> Prepare for TextInput<I, E> {
     type Error = E;
     type Output = TextInput<I, Infallible>;
@@ -807,14 +972,14 @@ impl<I> From<TextInput<I, Infallible>> for CreateInputText {
         // TODO: use into_ok() for id
         Self::new(
             style,
-            label,
+            String::from(label),
             id.unwrap_or_else(|_| unreachable!()).to_string(),
         )
         .fold_opt(min_len, Self::min_length)
         .fold_opt(max_len, Self::max_length)
         .required(required)
         .value(value)
-        .fold_opt(placeholder, Self::placeholder)
+        .fold_opt(placeholder.map(String::from), Self::placeholder)
     }
 }
 
@@ -5,7 +5,7 @@ use qcore::{
 use serenity::{
     builder::{
         CreateEmbed, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
-        EditInteractionResponse,
+        CreateMessage, EditInteractionResponse,
     },
     model::Color,
     utils::MessageBuilder,
@@ -39,6 +39,11 @@ impl BuildWith<Embeds> for CreateInteractionResponseFollowup {
     fn build_with(self, value: Embeds) -> Self { build_embeds!(value, self) }
 }
 
+impl BuildWith<Embeds> for CreateMessage {
+    #[inline]
+    fn build_with(self, value: Embeds) -> Self { build_embeds!(value, self) }
+}
+
 /// A message rich content embed
 #[derive(Debug, Default)]
 pub struct Embed {
@@ -138,49 +143,49 @@ impl Embed {
 #[derive(Debug)]
 struct EmbedFooter {}
 impl BuildWith<EmbedFooter> for CreateEmbed {
-    fn build_with(self, value: EmbedFooter) -> Self {
+    fn build_with(self, _value: EmbedFooter) -> Self {
         self // TODO
     }
 }
 #[derive(Debug)]
 struct EmbedImage {}
 impl BuildWith<EmbedImage> for CreateEmbed {
-    fn build_with(self, value: EmbedImage) -> Self {
+    fn build_with(self, _value: EmbedImage) -> Self {
         self // TODO
     }
 }
 #[derive(Debug)]
 struct EmbedThumbnail {}
 impl BuildWith<EmbedThumbnail> for CreateEmbed {
-    fn build_with(self, value: EmbedThumbnail) -> Self {
+    fn build_with(self, _value: EmbedThumbnail) -> Self {
         self // TODO
     }
 }
 #[derive(Debug)]
 struct EmbedVideo {}
 impl BuildWith<EmbedVideo> for CreateEmbed {
-    fn build_with(self, value: EmbedVideo) -> Self {
+    fn build_with(self, _value: EmbedVideo) -> Self {
         self // TODO
     }
 }
 #[derive(Debug)]
 struct EmbedProvider {}
 impl BuildWith<EmbedProvider> for CreateEmbed {
-    fn build_with(self, value: EmbedProvider) -> Self {
+    fn build_with(self, _value: EmbedProvider) -> Self {
         self // TODO
     }
 }
 #[derive(Debug)]
 struct EmbedAuthor {}
 impl BuildWith<EmbedAuthor> for CreateEmbed {
-    fn build_with(self, value: EmbedAuthor) -> Self {
+    fn build_with(self, _value: EmbedAuthor) -> Self {
         self // TODO
     }
 }
 #[derive(Debug)]
 struct EmbedField {}
 impl BuildWith<EmbedField> for CreateEmbed {
-    fn build_with(self, value: EmbedField) -> Self {
+    fn build_with(self, _value: EmbedField) -> Self {
         self // TODO
     }
 }
@@ -0,0 +1,120 @@
+use std::convert::Infallible;
+
+use qcore::{
+    build_with::{BuildWith, BuilderHelpers},
+    builder,
+};
+use serenity::{
+    builder::{CreateForumPost, CreateMessage},
+    model::{channel::AutoArchiveDuration, id::ForumTagId},
+};
+
+use super::{AllowedMentions, Message, Prepare};
+
+/// A new post (i.e. thread) to create in a forum or media channel
+#[derive(Debug)]
+pub struct ForumPost<I, E = Infallible> {
+    name: String,
+    message: Message<I, E>,
+    tags: Vec<ForumTagId>,
+    auto_archive: Option<AutoArchiveDuration>,
+}
+
+impl<I, E> ForumPost<I, E> {
+    /// Construct a new forum post with the given title and starter message
+    #[inline]
+    #[must_use]
+    pub fn new(name: impl Into<String>, message: impl Into<Message<I, E>>) -> Self {
+        Self {
+            name: name.into(),
+            message: message.into(),
+            tags: vec![],
+            auto_archive: None,
+        }
+    }
+
+    /// Apply the given mention policy if the message does not specify its own
+    #[must_use]
+    pub(super) fn default_mentions(self, mentions: &AllowedMentions) -> Self {
+        Self {
+            message: self.message.default_mentions(mentions),
+            ..self
+        }
+    }
+}
+
+#[builder(trait_name = ForumPostExt)]
+/// Helper methods for mutating [`ForumPost`]
+impl<I, E> ForumPost<I, E> {
+    /// Apply one of the forum's tags to this post
+    pub fn tag(&mut self, tag: ForumTagId) { self.tags.push(tag); }
+
+    /// Set how long the post may go without activity before it is archived
+    pub fn auto_archive(&mut self, duration: AutoArchiveDuration) {
+        self.auto_archive = Some(duration);
+    }
+}
+
+impl<I, E> Prepare for ForumPost<I, E> {
+    type Error = E;
+    type Output = ForumPost<I, Infallible>;
+
+    fn prepare(self) -> Result<Self::Output, Self::Error> {
+        let Self {
+            name,
+            message,
+            tags,
+            auto_archive,
+        } = self;
+        Ok(ForumPost {
+            name,
+            message: message.prepare()?,
+            tags,
+            auto_archive,
+        })
+    }
+}
+
+impl<I> From<ForumPost<I>> for CreateForumPost<'_> {
+    fn from(value: ForumPost<I>) -> Self {
+        let ForumPost {
+            name,
+            message,
+            tags,
+            auto_archive,
+        } = value;
+        CreateForumPost::new(name, CreateMessage::new().build_with(message))
+            .set_applied_tags(tags)
+            .fold_opt(auto_archive, CreateForumPost::auto_archive_duration)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use serenity::{
+        builder::CreateForumPost,
+        model::{channel::AutoArchiveDuration, id::ForumTagId},
+    };
+
+    use super::{super::*, ForumPost, ForumPostExt};
+
+    #[test]
+    fn build() {
+        let post: ForumPost<()> = ForumPost::new(
+            "Weekly poll",
+            Message::plain("Vote below!")
+                .poll(Poll::new("Best day?", Duration::from_secs(3 * 60 * 60)).answer("Friday")),
+        )
+        .tag(ForumTagId::new(7))
+        .auto_archive(AutoArchiveDuration::OneDay);
+
+        let json = serde_json::to_value(CreateForumPost::from(post)).unwrap();
+        assert_eq!(json["name"], "Weekly poll");
+        assert_eq!(json["applied_tags"][0], "7");
+        assert_eq!(json["auto_archive_duration"], 1440);
+        assert_eq!(json["message"]["content"], "Vote below!");
+        assert_eq!(json["message"]["poll"]["duration"], 3);
+    }
+}
@@ -11,6 +11,9 @@ pub enum Error {
     /// A protobuf error originating from [`prost`]
     #[error("Error decoding message payload")]
     Protobuf(#[from] prost::DecodeError),
+    /// The ID did not belong to the expected namespace
+    #[error("Error checking ID namespace")]
+    Namespace(#[from] NamespaceError),
 }
 
 impl From<Infallible> for Error {
@@ -27,19 +30,180 @@ impl fmt::Display for Id<'_> {
 }
 
 impl<'a> Id<'a> {
-    /// Construct a new custom ID from its raw string representation
+    /// Construct a new custom ID from its raw string representation, such as
+    /// the custom ID of a received interaction
     ///
-    /// # Safety
-    /// It is up to the caller to ensure that the provided string is a valid ID
-    /// payload encoded by the [`write()`] function.
+    /// The string is not validated; strings not produced by [`write()`] are
+    /// rejected by [`read()`].  To check a namespaced ID, use
+    /// [`Namespace::parse`] instead.
+    #[inline]
     #[must_use]
-    pub unsafe fn from_inner(s: Cow<'a, str>) -> Self { Self(s) }
+    pub fn from_raw(s: impl Into<Cow<'a, str>>) -> Self { Self(s.into()) }
+
+    /// Get the raw string representation of this ID
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self) -> &str { &self.0 }
 
     /// Produce an ID that borrows from `self`
     #[must_use]
     pub fn as_ref(&self) -> Id<'_> { Id(Cow::Borrowed(self.0.as_ref())) }
 }
 
+/// Separator between the namespace segments of an ID and its payload
+const NAMESPACE_SEP: char = ':';
+
+/// A segment of an ID [`Namespace`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// The application version segment
+    App,
+    /// The handler key segment
+    Handler,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::App => "app version",
+            Self::Handler => "handler",
+        })
+    }
+}
+
+/// An error occurring from checking the namespace of a custom ID
+#[derive(Debug, thiserror::Error)]
+pub enum NamespaceError {
+    /// The ID had no segment for the given scope
+    #[error("ID is missing its {0} namespace")]
+    Missing(Scope),
+    /// The ID had a segment for the given scope that did not match
+    #[error("Expected {scope} namespace {expected:?}, found {found:?}")]
+    Mismatch {
+        /// The scope of the mismatched segment
+        scope: Scope,
+        /// The segment this namespace expected
+        expected: String,
+        /// The segment found in the ID
+        found: String,
+    },
+}
+
+/// A set of prefixes for custom IDs, used to tell apart IDs written by
+/// different application versions or handlers
+///
+/// Each segment is written as a prefix to the encoded payload, so IDs written
+/// in a namespace can be rejected by [`parse`](Self::parse) before attempting
+/// to decode them.  An empty namespace adds no prefix and accepts any ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Namespace<'a> {
+    app: Option<Cow<'a, str>>,
+    handler: Option<Cow<'a, str>>,
+}
+
+impl<'a> Namespace<'a> {
+    /// The empty namespace
+    pub const ROOT: Namespace<'static> = Namespace {
+        app: None,
+        handler: None,
+    };
+
+    fn check_segment(s: &str) {
+        assert!(
+            !s.is_empty() && !s.contains(NAMESPACE_SEP),
+            "Invalid ID namespace segment {s:?}"
+        );
+    }
+
+    /// Set the application version segment of this namespace
+    ///
+    /// # Panics
+    /// This method panics if `version` is empty or contains a `:`.
+    #[must_use]
+    pub fn with_app_version(mut self, version: impl Into<Cow<'a, str>>) -> Self {
+        let version = version.into();
+        Self::check_segment(&version);
+        self.app = Some(version);
+        self
+    }
+
+    /// Set the handler key segment of this namespace
+    ///
+    /// # Panics
+    /// This method panics if `key` is empty or contains a `:`.
+    #[must_use]
+    pub fn with_handler(mut self, key: impl Into<Cow<'a, str>>) -> Self {
+        let key = key.into();
+        Self::check_segment(&key);
+        self.handler = Some(key);
+        self
+    }
+
+    fn segments(&self) -> impl Iterator<Item = (Scope, &str)> {
+        [(Scope::App, &self.app), (Scope::Handler, &self.handler)]
+            .into_iter()
+            .filter_map(|(k, v)| Some((k, v.as_deref()?)))
+    }
+
+    /// Prefix the given ID with the segments of this namespace
+    #[must_use]
+    pub fn wrap(&self, id: &Id<'_>) -> Id<'static> {
+        let mut s = String::new();
+        for (_, seg) in self.segments() {
+            s.push_str(seg);
+            s.push(NAMESPACE_SEP);
+        }
+        s.push_str(&id.0);
+
+        Id(Cow::Owned(s))
+    }
+
+    /// Check that a raw custom ID belongs to this namespace, returning the ID
+    /// with its namespace segments removed
+    ///
+    /// # Errors
+    /// This method returns an error naming the first segment of this
+    /// namespace that is missing from or does not match the given string.
+    pub fn parse<'s>(&self, s: &'s str) -> Result<Id<'s>, NamespaceError> {
+        let mut rest = s;
+
+        for (scope, expected) in self.segments() {
+            let Some((found, tail)) = rest.split_once(NAMESPACE_SEP) else {
+                return Err(NamespaceError::Missing(scope));
+            };
+
+            if found != expected {
+                return Err(NamespaceError::Mismatch {
+                    scope,
+                    expected: expected.into(),
+                    found: found.into(),
+                });
+            }
+
+            rest = tail;
+        }
+
+        Ok(Id(Cow::Borrowed(rest)))
+    }
+
+    /// Encode the given message into an [`Id`] in this namespace
+    ///
+    /// # Errors
+    /// This method fails for the same reasons as [`write()`].
+    pub fn write(&self, msg: &impl prost::Message) -> Result<Id<'static>, Error> {
+        write(msg).map(|i| self.wrap(&i))
+    }
+
+    /// Check the namespace of a raw custom ID and decode it into a message
+    ///
+    /// # Errors
+    /// This method returns an error if the namespace does not match or for
+    /// the same reasons as [`read()`].
+    pub fn read<M: prost::Message + Default>(&self, s: &str) -> Result<M, Error> {
+        read(&self.parse(s)?)
+    }
+}
+
 const FORMAT_RAW: u8 = 0;
 const FORMAT_ZSTD: u8 = 1;
 
@@ -146,4 +310,41 @@ mod test {
         assert_eq!(s, s2);
         Ok(())
     }
+
+    #[test]
+    fn test_namespace() -> Result<(), anyhow::Error> {
+        use super::{Namespace, NamespaceError, Scope};
+
+        let ns = Namespace::ROOT.with_app_version("3").with_handler("q");
+        let msg = Msg { s: "hi".into() };
+
+        let id = ns.write(&msg).context("Error writing namespaced message")?;
+        assert!(id.as_str().starts_with("3:q:"));
+        let Msg { s } = ns
+            .read(id.as_str())
+            .context("Error reading namespaced message")?;
+        assert_eq!(s, "hi");
+
+        let old = Namespace::ROOT.with_app_version("2").with_handler("q");
+        assert!(matches!(
+            old.parse(id.as_str()),
+            Err(NamespaceError::Mismatch { scope: Scope::App, ref found, .. }) if found == "3"
+        ));
+
+        let other = Namespace::ROOT.with_app_version("3").with_handler("r");
+        assert!(matches!(
+            other.parse(id.as_str()),
+            Err(NamespaceError::Mismatch {
+                scope: Scope::Handler,
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            ns.parse("3"),
+            Err(NamespaceError::Missing(Scope::App))
+        ));
+        assert_eq!(Namespace::ROOT.parse(id.as_str())?, id);
+        Ok(())
+    }
 }
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use qcore::builder;
+
+use super::super::locale::Locale;
+
+/// A user-facing string with optional per-locale translations
+///
+/// Locales are identified by the codes Discord reports for an interaction,
+/// e.g. `en-US` or `fr`.  A translation for a bare language code (e.g. `es`)
+/// is used as a fallback for any regional variant of that language (e.g.
+/// `es-ES`) without a translation of its own.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Localized {
+    default: String,
+    locales: HashMap<String, String>,
+}
+
+impl Localized {
+    /// Construct a new string with no translations
+    #[inline]
+    #[must_use]
+    pub fn new(default: impl Into<String>) -> Self {
+        Self {
+            default: default.into(),
+            locales: HashMap::new(),
+        }
+    }
+
+    /// Get the text for the given locale, falling back to the default text if
+    /// no translation is available
+    #[must_use]
+    pub fn get(&self, locale: &str) -> &str {
+        self.locales
+            .get(locale)
+            .or_else(|| {
+                let (lang, _) = locale.split_once('-')?;
+                self.locales.get(lang)
+            })
+            .unwrap_or(&self.default)
+    }
+
+    /// Replace the default text with its translation for the given locale and
+    /// discard all other translations
+    #[must_use]
+    pub(super) fn localize(self, locale: &Locale) -> Self {
+        let default = self.get(locale.tag()).to_owned();
+        Self::new(default)
+    }
+}
+
+#[builder(trait_name = LocalizedExt)]
+/// Helper methods for mutating [`Localized`]
+impl Localized {
+    /// Add a translation of this string for the given locale
+    pub fn locale(&mut self, locale: impl Into<String>, text: impl Into<String>) {
+        self.locales.insert(locale.into(), text.into());
+    }
+}
+
+impl From<String> for Localized {
+    #[inline]
+    fn from(default: String) -> Self { Self::new(default) }
+}
+
+impl From<&str> for Localized {
+    #[inline]
+    fn from(default: &str) -> Self { Self::new(default) }
+}
+
+impl From<Localized> for String {
+    #[inline]
+    fn from(value: Localized) -> Self { value.default }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Localized, LocalizedExt};
+    use crate::interaction::locale::Locale;
+
+    #[test]
+    fn locale_fallback() {
+        let s = Localized::new("Color")
+            .locale("en-GB", "Colour")
+            .locale("es", "Color (es)");
+
+        assert_eq!(s.get("en-US"), "Color");
+        assert_eq!(s.get("en-GB"), "Colour");
+        assert_eq!(s.get("es-ES"), "Color (es)");
+        assert_eq!(String::from(s.localize(&Locale::new("en-GB"))), "Colour");
+    }
+}
@@ -0,0 +1,169 @@
+use qcore::builder;
+use serenity::{
+    builder::CreateAllowedMentions,
+    model::id::{RoleId, UserId},
+    utils::MessageBuilder,
+};
+
+/// A policy describing which mentions in a message are allowed to ping their
+/// targets
+///
+/// The default policy allows no pings at all.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each flag maps directly to a field of the Discord API object"
+)]
+pub struct AllowedMentions {
+    everyone: bool,
+    all_users: bool,
+    all_roles: bool,
+    replied_user: bool,
+    users: Vec<UserId>,
+    roles: Vec<RoleId>,
+}
+
+impl AllowedMentions {
+    /// A policy allowing no pings
+    pub const NONE: AllowedMentions = AllowedMentions {
+        everyone: false,
+        all_users: false,
+        all_roles: false,
+        replied_user: false,
+        users: Vec::new(),
+        roles: Vec::new(),
+    };
+
+    /// Construct a new policy allowing no pings
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self { Self::default() }
+}
+
+#[builder(trait_name = AllowedMentionsExt)]
+/// Helper methods for mutating [`AllowedMentions`]
+impl AllowedMentions {
+    /// Set whether `@everyone` and `@here` are allowed to ping
+    pub fn everyone(&mut self, everyone: bool) { self.everyone = everyone; }
+
+    /// Set whether any mentioned user is allowed to be pinged
+    pub fn all_users(&mut self, all_users: bool) { self.all_users = all_users; }
+
+    /// Set whether any mentioned guild role is allowed to be pinged
+    pub fn all_roles(&mut self, all_roles: bool) { self.all_roles = all_roles; }
+
+    /// Set whether the replied-to user is allowed to be pinged
+    pub fn replied_user(&mut self, replied_user: bool) { self.replied_user = replied_user; }
+
+    /// Set which users are allowed to be pinged
+    pub fn users(&mut self, users: Vec<UserId>) { self.users = users; }
+
+    /// Set which guild roles are allowed to be pinged
+    pub fn roles(&mut self, roles: Vec<RoleId>) { self.roles = roles; }
+}
+
+impl From<AllowedMentions> for CreateAllowedMentions {
+    fn from(value: AllowedMentions) -> Self {
+        let AllowedMentions {
+            everyone,
+            all_users,
+            all_roles,
+            replied_user,
+            users,
+            roles,
+        } = value;
+
+        // Discord rejects a policy that both parses a mention type and lists
+        // specific IDs for it
+        let mut out = CreateAllowedMentions::new()
+            .everyone(everyone)
+            .all_users(all_users)
+            .all_roles(all_roles)
+            .replied_user(replied_user);
+
+        if !all_users {
+            out = out.users(users);
+        }
+
+        if !all_roles {
+            out = out.roles(roles);
+        }
+
+        out
+    }
+}
+
+/// Escape all Markdown formatting characters in the given text
+///
+/// Unlike [`MessageBuilder::push_safe`], this also escapes headers, lists,
+/// quotes, spoilers, strikethrough, masked links and mention syntax.
+#[must_use]
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '<' | '#' | '-' | '[' | ']' | '(' | ')'
+        ) {
+            out.push('\\');
+        }
+
+        out.push(c);
+    }
+
+    out
+}
+
+/// Break any mentions in the given text so they render as plain text
+///
+/// This inserts a zero-width space after the `@` of `@everyone` and `@here`,
+/// and after the opening bracket of user, role and channel mentions.
+#[must_use]
+pub fn escape_mentions(text: &str) -> String {
+    text.replace("@everyone", "@\u{200b}everyone")
+        .replace("@here", "@\u{200b}here")
+        .replace("<@", "<\u{200b}@")
+        .replace("<#", "<\u{200b}#")
+}
+
+/// Helper methods for pushing untrusted text to a [`MessageBuilder`]
+pub trait MessageBuilderExt {
+    /// Push user-provided text with all formatting and mentions escaped
+    fn push_escaped(&mut self, text: impl AsRef<str>) -> &mut Self;
+}
+
+impl MessageBuilderExt for MessageBuilder {
+    fn push_escaped(&mut self, text: impl AsRef<str>) -> &mut Self {
+        self.push(escape_markdown(&escape_mentions(text.as_ref())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serenity::model::id::UserId;
+
+    use super::{escape_markdown, escape_mentions, AllowedMentions, AllowedMentionsExt};
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            escape_markdown("# hi ||*there*||"),
+            r"\# hi \|\|\*there\*\|\|"
+        );
+        assert_eq!(escape_markdown(r"[a](b) \_"), r"\[a\]\(b\) \\\_");
+        assert_eq!(
+            escape_mentions("@everyone <@123> <@&456> <#789>"),
+            "@\u{200b}everyone <\u{200b}@123> <\u{200b}@&456> <\u{200b}#789>"
+        );
+    }
+
+    #[test]
+    fn default_denies() {
+        assert_eq!(AllowedMentions::new(), AllowedMentions::NONE);
+        assert_ne!(
+            AllowedMentions::new().users(vec![UserId::new(1)]),
+            AllowedMentions::NONE
+        );
+    }
+}
@@ -3,26 +3,29 @@ use std::{
     convert::Infallible,
 };
 
-use qcore::{build_with::BuildWith, builder};
+use qcore::{
+    build_with::{BuildWith, BuilderHelpers},
+    builder,
+};
 use serenity::{
     builder::{
-        CreateAllowedMentions, CreateAttachment, CreateInteractionResponseFollowup,
-        CreateInteractionResponseMessage, EditInteractionResponse,
+        CreateAttachment, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+        CreateMessage, EditInteractionResponse,
     },
     model::id::{RoleId, UserId},
     utils::MessageBuilder,
 };
 
-use super::{Components, Embed, Embeds, MessageComponent, Prepare};
+use super::{
+    AllowedMentions, AllowedMentionsExt, Components, Embed, Embeds, MessageComponent, Poll, Prepare,
+};
 
 /// The body of a message
 #[derive(Debug, qcore::Borrow)]
 pub struct MessageBody<I, E = Infallible> {
     content: MessageBuilder,
     embeds: Embeds,
-    ping_replied: bool,
-    ping_users: Vec<UserId>,
-    ping_roles: Vec<RoleId>,
+    mentions: Option<AllowedMentions>,
     #[borrow(mut)]
     components: Components<MessageComponent<I, E>>,
 }
@@ -32,20 +35,13 @@ macro_rules! build_body {
         let MessageBody {
             mut content,
             embeds,
-            ping_replied,
-            ping_users,
-            ping_roles,
+            mentions,
             components,
         } = $self;
         $builder
             .content(content.build())
             .build_with(embeds)
-            .allowed_mentions(
-                CreateAllowedMentions::new()
-                    .replied_user(ping_replied)
-                    .users(ping_users)
-                    .roles(ping_roles),
-            )
+            .allowed_mentions(mentions.unwrap_or_default().into())
             .build_with(components)
     }};
 }
@@ -59,9 +55,7 @@ impl<I, E> MessageBody<I, E> {
         Self {
             content,
             embeds: Embeds::default(),
-            ping_replied: false,
-            ping_users: vec![],
-            ping_roles: vec![],
+            mentions: None,
             components: Components::default(),
         }
     }
@@ -71,19 +65,37 @@ impl<I, E> MessageBody<I, E> {
     pub fn plain(c: impl Into<serenity::utils::Content>) -> Self {
         Self::rich(|mb| mb.push_safe(c))
     }
+
+    /// Apply the given mention policy if this message does not specify its own
+    #[must_use]
+    pub(super) fn default_mentions(mut self, mentions: &AllowedMentions) -> Self {
+        self.mentions.get_or_insert_with(|| mentions.clone());
+        self
+    }
+
+    #[inline]
+    fn mentions_mut(&mut self) -> &mut AllowedMentions { self.mentions.get_or_insert_default() }
 }
 
 #[builder(trait_name = MessageBodyExt)]
 /// Helper methods for mutating [`MessageBody`]
 impl<I, E> MessageBody<I, E> {
+    /// Override the default mention policy for this message
+    pub fn mentions(&mut self, mentions: AllowedMentions) { self.mentions = Some(mentions); }
+
     /// Set whether the replied-to user is allowed to be pinged
-    pub fn ping_replied(&mut self, ping_replied: bool) { self.ping_replied = ping_replied; }
+    ///
+    /// This and the other `ping_*` methods override the default mention policy
+    /// with one allowing only the pings given for this message.
+    pub fn ping_replied(&mut self, ping_replied: bool) {
+        self.mentions_mut().replied_user(ping_replied);
+    }
 
     /// Set which users are allowed to be pinged
-    pub fn ping_users(&mut self, ping_users: Vec<UserId>) { self.ping_users = ping_users; }
+    pub fn ping_users(&mut self, ping_users: Vec<UserId>) { self.mentions_mut().users(ping_users); }
 
     /// Set which guild roles are allowed to be pinged
-    pub fn ping_roles(&mut self, ping_roles: Vec<RoleId>) { self.ping_roles = ping_roles; }
+    pub fn ping_roles(&mut self, ping_roles: Vec<RoleId>) { self.mentions_mut().roles(ping_roles); }
 
     /// Add an embed to this message
     pub fn embed(&mut self, embed: Embed) { self.embeds.0.push(embed); }
@@ -102,17 +114,13 @@ impl<I, E> Prepare for MessageBody<I, E> {
         let Self {
             content,
             embeds,
-            ping_replied,
-            ping_users,
-            ping_roles,
+            mentions,
             components,
         } = self;
         Ok(MessageBody {
             content,
             embeds,
-            ping_replied,
-            ping_users,
-            ping_roles,
+            mentions,
             components: components.prepare()?,
         })
     }
@@ -133,6 +141,11 @@ impl<I> BuildWith<MessageBody<I>> for CreateInteractionResponseFollowup {
     fn build_with(self, value: MessageBody<I>) -> Self { build_body!(value, self) }
 }
 
+impl<I> BuildWith<MessageBody<I>> for CreateMessage {
+    #[inline]
+    fn build_with(self, value: MessageBody<I>) -> Self { build_body!(value, self) }
+}
+
 /// Options to provide when creating (or deferring the creation of) a message
 #[derive(Debug, Clone, Copy, Default)]
 pub struct MessageOpts {
@@ -175,6 +188,7 @@ pub struct Message<I, E = Infallible> {
     #[borrow(mut)]
     opts: MessageOpts,
     attachments: Vec<CreateAttachment>,
+    poll: Option<Poll>,
 }
 
 impl<I, E> Borrow<Components<MessageComponent<I, E>>> for Message<I, E> {
@@ -193,11 +207,13 @@ macro_rules! build_msg {
             body,
             opts,
             attachments,
+            poll,
         } = $self;
         $builder
             .build_with(body)
             .build_with(opts)
             .files(attachments)
+            .fold_opt(poll, |b, p| b.poll(p.into()))
     }};
 }
 
@@ -207,6 +223,7 @@ impl<I, E> From<MessageBody<I, E>> for Message<I, E> {
             body,
             opts: MessageOpts::default(),
             attachments: vec![],
+            poll: None,
         }
     }
 }
@@ -234,6 +251,24 @@ impl<I, E> Message<I, E> {
             body,
             opts,
             attachments,
+            poll: None,
+        }
+    }
+
+    /// Apply the given mention policy if this message does not specify its own
+    #[must_use]
+    pub(super) fn default_mentions(self, mentions: &AllowedMentions) -> Self {
+        let Self {
+            body,
+            opts,
+            attachments,
+            poll,
+        } = self;
+        Self {
+            body: body.default_mentions(mentions),
+            opts,
+            attachments,
+            poll,
         }
     }
 }
@@ -245,6 +280,12 @@ impl<I, E> Message<I, E> {
     pub fn attach(&mut self, attachments: impl IntoIterator<Item = CreateAttachment>) {
         self.attachments.extend(attachments);
     }
+
+    /// Attach a poll to this message
+    ///
+    /// Polls can only be sent with new messages, and are ignored when this
+    /// message is used to edit a response.
+    pub fn poll(&mut self, poll: Poll) { self.poll = Some(poll); }
 }
 
 impl<I, E> Prepare for Message<I, E> {
@@ -256,11 +297,13 @@ impl<I, E> Prepare for Message<I, E> {
             body,
             opts,
             attachments,
+            poll,
         } = self;
         Ok(Message {
             body: body.prepare()?,
             opts,
             attachments,
+            poll,
         })
     }
 }
@@ -274,3 +317,37 @@ impl<I> BuildWith<Message<I>> for CreateInteractionResponseFollowup {
     #[inline]
     fn build_with(self, value: Message<I>) -> Self { build_msg!(value, self) }
 }
+
+// Ephemeral messages only exist as interaction responses, so only the TTS
+// option applies here
+impl<I> BuildWith<Message<I>> for CreateMessage {
+    fn build_with(self, value: Message<I>) -> Self {
+        let Message {
+            body,
+            opts: MessageOpts { tts, ephemeral: _ },
+            attachments,
+            poll,
+        } = value;
+        self.build_with(body)
+            .tts(tts)
+            .files(attachments)
+            .fold_opt(poll, |b, p| b.poll(p.into()))
+    }
+}
+
+// Message options and polls cannot be changed by an edit, so they are ignored
+// here
+impl<I> BuildWith<Message<I>> for EditInteractionResponse {
+    fn build_with(self, value: Message<I>) -> Self {
+        let Message {
+            body,
+            opts: _,
+            attachments,
+            poll: _,
+        } = value;
+        attachments.into_iter().fold(
+            self.build_with(body),
+            EditInteractionResponse::new_attachment,
+        )
+    }
+}
@@ -1,19 +1,33 @@
 //! Types for responding to interactions according to the Discord webhook
 //! protocol in a type-safe manner
 
+mod chunk;
 mod component;
 mod embed;
+mod forum;
 pub mod id;
+mod localized;
+mod mentions;
 mod message;
 mod modal;
+mod poll;
 mod prepare;
+mod progress;
+mod ratelimit;
 mod responder;
 
+pub use chunk::*;
 pub use component::*;
 pub use embed::*;
+pub use forum::*;
+pub use localized::*;
+pub use mentions::*;
 pub use message::*;
 pub use modal::*;
+pub use poll::*;
 pub use prepare::*;
+pub use progress::*;
+pub use ratelimit::*;
 pub use responder::*;
 
 /// Helper traits for working with response data
@@ -24,7 +38,11 @@ pub mod prelude {
             ModalComponents as _, TextInputExt as _,
         },
         embed::EmbedExt as _,
+        forum::ForumPostExt as _,
+        localized::LocalizedExt as _,
+        mentions::{AllowedMentionsExt as _, MessageBuilderExt as _},
         message::{MessageBodyExt as _, MessageExt as _, MessageOptsExt as _},
+        poll::PollExt as _,
         responder::ResponderExt as _,
     };
 }
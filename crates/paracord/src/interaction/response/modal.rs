@@ -4,8 +4,11 @@ use qcore::build_with::BuildWith;
 use serenity::builder::CreateModal;
 
 use super::{
-    super::rpc::{ModalId, Schema},
-    id, Components, Prepare, TextInput,
+    super::{
+        locale::Locale,
+        rpc::{ModalId, Schema},
+    },
+    id, Components, Localized, ModalComponent, Prepare,
 };
 
 /// A predetermined modal source, dictated by the interaction currently being
@@ -24,9 +27,9 @@ pub enum ModalSource {
 #[derive(Debug, qcore::Borrow)]
 pub struct Modal<S: Schema, E> {
     id: Result<id::Id<'static>, E>,
-    title: String,
+    title: Localized,
     #[borrow(mut)]
-    components: Components<TextInput<S::Component, E>>,
+    components: Components<ModalComponent<S::Component, E>>,
     key: PhantomData<fn(S)>,
 }
 
@@ -36,7 +39,7 @@ impl<S: Schema> Modal<S, id::Error> {
     pub fn new(
         source: ModalSourceHandle,
         payload: <S::Modal as ModalId>::Payload,
-        title: impl Into<String>,
+        title: impl Into<Localized>,
     ) -> Self {
         Self {
             id: id::write(&S::Modal::from_parts(source.0, payload)),
@@ -47,6 +50,25 @@ impl<S: Schema> Modal<S, id::Error> {
     }
 }
 
+impl<S: Schema, E> Modal<S, E> {
+    /// Resolve all translated text in this modal for the given locale
+    #[must_use]
+    pub(super) fn localize(self, locale: &Locale) -> Self {
+        let Self {
+            id,
+            title,
+            components: Components(rows),
+            key,
+        } = self;
+        Self {
+            id,
+            title: title.localize(locale),
+            components: Components(rows.into_iter().map(|r| r.localize(locale)).collect()),
+            key,
+        }
+    }
+}
+
 impl<S: Schema, E> Prepare for Modal<S, E> {
     type Error = E;
     type Output = Modal<S, Infallible>;
@@ -78,6 +100,10 @@ impl<S: Schema> From<Modal<S, Infallible>> for CreateModal {
             key: _,
         } = value;
 
-        Self::new(id.unwrap_or_else(|_| unreachable!()).to_string(), title).build_with(components)
+        Self::new(
+            id.unwrap_or_else(|_| unreachable!()).to_string(),
+            String::from(title),
+        )
+        .build_with(components)
     }
 }
@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use qcore::builder;
+use serenity::{
+    builder::{CreatePoll, CreatePollAnswer},
+    model::channel::PollMediaEmoji,
+};
+
+/// Maximum number of answers Discord allows on a poll
+pub const MAX_POLL_ANSWERS: usize = 10;
+/// Maximum length of a poll question, in characters
+pub const MAX_POLL_QUESTION: usize = 300;
+/// Maximum length of a poll answer, in characters
+pub const MAX_POLL_ANSWER: usize = 55;
+
+const HOUR: u64 = 60 * 60;
+
+/// A native Discord poll to attach to a message
+///
+/// Polls cannot be added to an existing message, so they are ignored when a
+/// message is sent as an edit (e.g. when completing a deferred response).
+#[derive(Debug, Clone)]
+pub struct Poll {
+    question: String,
+    answers: Vec<PollAnswer>,
+    duration: Duration,
+    multiselect: bool,
+}
+
+#[derive(Debug, Clone)]
+struct PollAnswer {
+    text: String,
+    emoji: Option<PollMediaEmoji>,
+}
+
+impl Poll {
+    /// Construct a new poll with no answers, running for the given duration
+    ///
+    /// # Panics
+    /// This method panics if the question is longer than
+    /// [`MAX_POLL_QUESTION`] characters, or if the duration is shorter than
+    /// one hour or longer than 255 hours.  Durations are rounded down to
+    /// whole hours.
+    #[must_use]
+    pub fn new(question: impl Into<String>, duration: Duration) -> Self {
+        let question = question.into();
+        assert!(question.chars().count() <= MAX_POLL_QUESTION);
+        assert!((1..=u64::from(u8::MAX)).contains(&(duration.as_secs() / HOUR)));
+
+        Self {
+            question,
+            answers: vec![],
+            duration,
+            multiselect: false,
+        }
+    }
+
+    fn push_answer(&mut self, text: String, emoji: Option<PollMediaEmoji>) {
+        assert!(self.answers.len() < MAX_POLL_ANSWERS);
+        assert!(text.chars().count() <= MAX_POLL_ANSWER);
+        self.answers.push(PollAnswer { text, emoji });
+    }
+}
+
+#[builder(trait_name = PollExt)]
+/// Helper methods for mutating [`Poll`]
+impl Poll {
+    /// Add a text answer to this poll
+    ///
+    /// # Panics
+    /// This method panics if the poll already has [`MAX_POLL_ANSWERS`] answers
+    /// or the answer is longer than [`MAX_POLL_ANSWER`] characters.
+    pub fn answer(&mut self, text: impl Into<String>) { self.push_answer(text.into(), None); }
+
+    /// Add an answer with an emoji to this poll
+    ///
+    /// # Panics
+    /// This method panics under the same conditions as
+    /// [`answer`](Self::answer).
+    pub fn emoji_answer(&mut self, emoji: impl Into<PollMediaEmoji>, text: impl Into<String>) {
+        self.push_answer(text.into(), Some(emoji.into()));
+    }
+
+    /// Set whether users may select more than one answer
+    pub fn multiselect(&mut self, multiselect: bool) { self.multiselect = multiselect; }
+}
+
+impl From<Poll> for CreatePoll<serenity::builder::create_poll::Ready> {
+    fn from(value: Poll) -> Self {
+        let Poll {
+            question,
+            answers,
+            duration,
+            multiselect,
+        } = value;
+
+        let poll = CreatePoll::new()
+            .question(question)
+            .answers(
+                answers
+                    .into_iter()
+                    .map(|PollAnswer { text, emoji }| {
+                        let answer = CreatePollAnswer::new().text(text);
+                        match emoji {
+                            Some(e) => answer.emoji(e),
+                            None => answer,
+                        }
+                    })
+                    .collect(),
+            )
+            .duration(duration);
+
+        if multiselect {
+            poll.allow_multiselect()
+        } else {
+            poll
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use serenity::builder::CreatePoll;
+
+    use super::{Poll, PollExt, MAX_POLL_ANSWERS};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[test]
+    fn build() {
+        let poll = Poll::new("Cats or dogs?", DAY + Duration::from_secs(59))
+            .emoji_answer("🐱".to_owned(), "Cats")
+            .answer("Dogs")
+            .multiselect(true);
+
+        let json = serde_json::to_value(CreatePoll::from(poll)).unwrap();
+        assert_eq!(json["question"]["text"], "Cats or dogs?");
+        assert_eq!(json["duration"], 24);
+        assert_eq!(json["allow_multiselect"], true);
+        assert_eq!(json["answers"][0]["poll_media"]["emoji"]["name"], "🐱");
+        assert_eq!(json["answers"][1]["poll_media"]["text"], "Dogs");
+    }
+
+    #[test]
+    #[should_panic = "assertion failed"]
+    fn too_many_answers() {
+        (0..=MAX_POLL_ANSWERS).fold(Poll::new("?", DAY), |p, i| p.answer(i.to_string()));
+    }
+
+    #[test]
+    #[should_panic = "assertion failed"]
+    fn too_short() { let _ = Poll::new("?", Duration::from_secs(59 * 60)); }
+}
@@ -0,0 +1,164 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{
+    super::rpc::Schema, id, prelude::*, responder::private::Interaction, CreatedResponder, Message,
+    MessageBody, ResponseError,
+};
+
+/// Number of cells in a rendered progress bar
+const BAR_WIDTH: u8 = 20;
+/// Default minimum time between progress edits
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Progress {
+    fraction: Option<f64>,
+    status: String,
+}
+
+/// Render a fraction in `[0, 1]` as a fixed-width text progress bar
+fn bar(fraction: f64) -> String {
+    let width = f64::from(BAR_WIDTH);
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "The value is clamped to [0, BAR_WIDTH]"
+    )]
+    let filled = (fraction.clamp(0.0, 1.0) * width).round() as u8;
+
+    (0..BAR_WIDTH)
+        .map(|i| if i < filled { '█' } else { '░' })
+        .collect()
+}
+
+fn render<I>(progress: &Progress) -> MessageBody<I, id::Error> {
+    let Progress { fraction, status } = progress;
+
+    MessageBody::rich(|mb| {
+        if let Some(fraction) = *fraction {
+            mb.push_mono(bar(fraction))
+                .push(format!(" {:.0}% ", fraction.clamp(0.0, 1.0) * 100.0));
+        }
+        mb.push_safe(status.as_str())
+    })
+}
+
+/// Reports the progress of a long-running task by editing the original
+/// response to an interaction
+///
+/// Updates are throttled to at most one edit per [interval](Self::with_interval),
+/// and are held back while the response's rate limit is exhausted.  Updates
+/// that are held back are coalesced, and the latest one is sent with the next
+/// update made after the throttle lifts.  Once the task completes, the
+/// progress message should be replaced using [`finish`](Self::finish) or
+/// [`fail`](Self::fail).
+///
+/// Progress updates are best-effort: errors sending them are logged rather
+/// than returned, so a failed edit never interrupts the task being reported
+/// on.
+#[derive(Debug)]
+pub struct ProgressReporter<'r, 'a, S: Schema, I> {
+    responder: &'r CreatedResponder<'a, S, I>,
+    interval: Duration,
+    last_edit: Option<Instant>,
+    pending: Option<Progress>,
+}
+
+impl<'r, 'a, S: Schema, I: Interaction> ProgressReporter<'r, 'a, S, I> {
+    /// Construct a new progress reporter editing the given response
+    #[inline]
+    #[must_use]
+    pub fn new(responder: &'r CreatedResponder<'a, S, I>) -> Self {
+        Self {
+            responder,
+            interval: DEFAULT_INTERVAL,
+            last_edit: None,
+            pending: None,
+        }
+    }
+
+    /// Set the minimum time between progress edits
+    #[inline]
+    #[must_use]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    async fn throttled(&self) -> bool {
+        if self.last_edit.is_some_and(|t| t.elapsed() < self.interval) {
+            return true;
+        }
+
+        self.responder
+            .response_rate_limit()
+            .await
+            .is_some_and(|l| l.wait_time(SystemTime::now()).is_some())
+    }
+
+    /// Report the current progress of the task
+    ///
+    /// `fraction` is the completed portion of the task between 0 and 1, or
+    /// `None` if it is unknown.
+    pub async fn update(&mut self, fraction: impl Into<Option<f64>>, status: impl Into<String>) {
+        self.pending = Some(Progress {
+            fraction: fraction.into(),
+            status: status.into(),
+        });
+
+        if self.throttled().await {
+            return;
+        }
+
+        self.flush().await;
+    }
+
+    /// Immediately send the latest held-back update, if any
+    pub async fn flush(&mut self) {
+        let Some(progress) = self.pending.take() else {
+            return;
+        };
+
+        self.last_edit = Some(Instant::now());
+        if let Err(err) = self.responder.edit(render(&progress)).await {
+            tracing::warn!(%err, "Error sending progress update");
+        }
+    }
+
+    /// Replace the progress message with the result of the completed task
+    ///
+    /// # Errors
+    /// This method returns an error if the message contains errors or an API
+    /// error is received.
+    pub async fn finish(self, msg: Message<S::Component, id::Error>) -> Result<(), ResponseError> {
+        self.responder.edit_deferred(msg).await
+    }
+
+    /// Replace the progress message with a failure notice
+    ///
+    /// # Errors
+    /// This method returns an error if an API error is received.
+    pub async fn fail(self, status: impl Into<String>) -> Result<(), ResponseError> {
+        let status = status.into();
+        self.responder
+            .edit(MessageBody::rich(|mb| {
+                mb.push_bold("Failed:").push(" ").push_safe(status.as_str())
+            }))
+            .await
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::bar;
+
+    #[test]
+    fn bars() {
+        assert_eq!(bar(0.0), "░".repeat(20));
+        assert_eq!(bar(0.5), "█".repeat(10) + &"░".repeat(10));
+        assert_eq!(bar(1.0), "█".repeat(20));
+        assert_eq!(bar(-1.0), bar(0.0));
+        assert_eq!(bar(2.0), bar(1.0));
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use serenity::http::{Http, Ratelimit, Route};
+
+use super::{Followup, ResponseError};
+
+/// A snapshot of the rate limit bucket for an interaction response route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The total number of requests allowed per reset period
+    pub limit: i64,
+    /// The number of requests remaining before the bucket is exhausted
+    pub remaining: i64,
+    /// The time at which the bucket resets, if known
+    pub reset_at: Option<SystemTime>,
+}
+
+impl RateLimit {
+    /// Returns true if no more requests can be made until the bucket resets
+    #[inline]
+    #[must_use]
+    pub fn is_exhausted(&self) -> bool { self.remaining <= 0 }
+
+    /// Get the time to wait before another request can be made, if the bucket
+    /// is currently exhausted
+    #[must_use]
+    pub fn wait_time(&self, now: SystemTime) -> Option<Duration> {
+        if !self.is_exhausted() {
+            return None;
+        }
+
+        self.reset_at
+            .and_then(|r| r.duration_since(now).ok())
+            .filter(|d| !d.is_zero())
+    }
+}
+
+impl From<&Ratelimit> for RateLimit {
+    fn from(value: &Ratelimit) -> Self {
+        Self {
+            limit: value.limit(),
+            remaining: value.remaining(),
+            reset_at: value.reset(),
+        }
+    }
+}
+
+/// Look up the current rate limit bucket data for the given route
+///
+/// This returns `None` if the HTTP client has no rate limiter or no request
+/// has been made against the route's bucket yet.
+pub(super) async fn lookup(http: &Http, route: Route<'_>) -> Option<RateLimit> {
+    let routes = http.ratelimiter.as_ref()?.routes();
+    let bucket = Arc::clone(routes.read().await.get(&route.ratelimiting_bucket())?);
+    let limit = RateLimit::from(&*bucket.lock().await);
+    Some(limit)
+}
+
+/// An error arising from sending a batch of followup messages
+#[derive(Debug, thiserror::Error)]
+#[error("Error sending followup {} of batch", sent.len() + 1)]
+pub struct BatchError {
+    /// The followups that were sent successfully before the error occurred
+    pub sent: Vec<Followup>,
+    /// The error that stopped the batch
+    #[source]
+    pub error: ResponseError,
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use super::RateLimit;
+
+    #[test]
+    fn wait_time() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let limit = |remaining, reset| RateLimit {
+            limit: 5,
+            remaining,
+            reset_at: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(reset)),
+        };
+
+        assert_eq!(limit(1, 102).wait_time(now), None);
+        assert_eq!(limit(0, 102).wait_time(now), Some(Duration::from_secs(2)));
+        assert_eq!(limit(0, 100).wait_time(now), None);
+        assert_eq!(limit(0, 99).wait_time(now), None);
+        assert_eq!(
+            RateLimit {
+                limit: 5,
+                remaining: 0,
+                reset_at: None
+            }
+            .wait_time(now),
+            None
+        );
+    }
+}
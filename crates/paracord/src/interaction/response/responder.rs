@@ -2,7 +2,8 @@
 //!
 //! # Notes
 //!
-//! After doing some fuzzing (see the `discord-pls` binary) I have determined
+//! After doing some fuzzing (see the `discord-pls` binary, or the `fuzz`
+//! module behind the `fuzz` feature to re-run it) I have determined
 //! some rules regarding the flow of interaction responses:
 //! - You **must** create **exactly one** response to acknowledge an
 //!   interaction
@@ -50,6 +51,10 @@
 //! _\*\* This only works if the initiating interaction was of type
 //! `MESSAGE_COMPONENT`_
 //!
+//! Modal-submit responders only offer the update responses once narrowed to a
+//! component-initiated modal with [`BorrowingResponder::initiator`], so both
+//! remarks are checked at compile time.
+//!
 //! ## Ping and Autocomplete
 //!
 //! As far as I could tell (and as far as I'm concerned) the only valid response
@@ -58,14 +63,14 @@
 //! response or followup calls should be made.  Additionally, as stated by the
 //! docs, gateway clients do not need to handle `PING` interactions.
 
-mod private {
+pub(super) mod private {
     use std::marker::PhantomData;
 
     use serenity::{
         builder::{
             CreateInteractionResponse, CreateInteractionResponseFollowup, EditInteractionResponse,
         },
-        http::Http,
+        http::{Http, Route},
         model::{
             application::{CommandInteraction, ComponentInteraction, ModalInteraction},
             channel::Message,
@@ -73,11 +78,19 @@ mod private {
         },
     };
 
-    use super::super::modal;
+    use super::super::{super::context::InteractionCtx, modal};
 
     // serenity why
     #[async_trait::async_trait]
     pub trait Interaction: Sync {
+        fn locale(&self) -> &str;
+
+        fn guild_locale(&self) -> Option<&str>;
+
+        fn response_route(&self) -> Route<'_>;
+
+        fn followup_route(&self) -> Route<'_>;
+
         async fn create_response(
             &self,
             http: &Http,
@@ -116,6 +129,28 @@ mod private {
         ($ty:ident) => {
             #[async_trait::async_trait]
             impl Interaction for $ty {
+                #[inline]
+                fn locale(&self) -> &str { &self.locale }
+
+                #[inline]
+                fn guild_locale(&self) -> Option<&str> { self.guild_locale.as_deref() }
+
+                #[inline]
+                fn response_route(&self) -> Route<'_> {
+                    Route::WebhookOriginalInteractionResponse {
+                        application_id: self.application_id,
+                        token: &self.token,
+                    }
+                }
+
+                #[inline]
+                fn followup_route(&self) -> Route<'_> {
+                    Route::WebhookFollowupMessages {
+                        application_id: self.application_id,
+                        token: &self.token,
+                    }
+                }
+
                 #[inline]
                 async fn create_response(
                     &self,
@@ -178,6 +213,8 @@ mod private {
     pub struct ResponderCore<'a, S, I> {
         pub(super) http: &'a Http,
         pub(super) int: &'a I,
+        pub(super) cx: InteractionCtx,
+        pub(super) mentions: &'a super::AllowedMentions,
         pub(super) schema: PhantomData<fn(S)>,
     }
 
@@ -238,16 +275,31 @@ mod private {
     impl<S, I> CreateFollowup for super::VoidResponder<'_, S, I> {}
 }
 
-use std::{future::Future, marker::PhantomData, mem};
+use std::{future::Future, marker::PhantomData, mem, time::SystemTime};
 
 use private::{Interaction, ResponderCore};
 use qcore::build_with::BuildDefault;
-use serenity::{builder::CreateInteractionResponse, http::Http};
+use serenity::{
+    builder::CreateInteractionResponse,
+    http::Http,
+    model::{
+        channel::GuildChannel,
+        id::{ChannelId, SkuId},
+    },
+};
+use tokio::sync::Mutex;
 
 use super::{
-    super::rpc::Schema, id, Message, MessageBody, MessageOpts, Modal, ModalSourceHandle, Prepare,
+    super::{context::InteractionCtx, locale::Locale, rpc::Schema},
+    id,
+    prelude::*,
+    ratelimit, AllowedMentions, BatchError, ForumPost, Message, MessageBody, MessageOpts, Modal,
+    ModalSource, ModalSourceHandle, Prepare, RateLimit,
 };
 
+/// The mention policy used by responders not given one explicitly
+static NO_MENTIONS: AllowedMentions = AllowedMentions::NONE;
+
 /// An error arising from sending an interaction response
 #[derive(Debug, thiserror::Error)]
 pub enum ResponseError {
@@ -257,6 +309,43 @@ pub enum ResponseError {
     /// An error occurred transcoding an [`Id`](id::Id)
     #[error("Custom ID error for component or modal")]
     Id(#[from] id::Error),
+    /// A modal was requested after the response had already been deferred
+    #[error("Cannot create a modal for an already-deferred interaction")]
+    Deferred,
+    /// The interaction's response window closed before the call could be made
+    #[error("Interaction response deadline has passed")]
+    Expired,
+}
+
+/// The Discord error code for an interaction that can no longer be responded
+/// to
+const UNKNOWN_INTERACTION: isize = 10062;
+
+impl ResponseError {
+    /// Convert an error from creating an initial response, recognizing
+    /// Discord's rejection of an expired interaction
+    fn from_create(err: serenity::Error) -> Self {
+        match err {
+            serenity::Error::Http(serenity::http::HttpError::UnsuccessfulRequest(ref res))
+                if res.error.code == UNKNOWN_INTERACTION =>
+            {
+                Self::Expired
+            },
+            e => Self::Serenity(e),
+        }
+    }
+}
+
+impl<S, I> ResponderCore<'_, S, I> {
+    /// Refuse to make an API call if the interaction token has expired
+    #[inline]
+    fn check_token(&self) -> Result<(), ResponseError> {
+        if self.cx.is_expired() {
+            Err(ResponseError::Expired)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 /// A followup message returned from a responder
@@ -264,9 +353,52 @@ pub enum ResponseError {
 #[repr(transparent)]
 pub struct Followup(serenity::model::channel::Message);
 
+impl Followup {
+    /// Get the message as last returned by Discord
+    #[inline]
+    #[must_use]
+    pub fn message(&self) -> &serenity::model::channel::Message { &self.0 }
+}
+
 /// Common methods for all responder types
 #[async_trait::async_trait]
 pub trait ResponderExt<S: Schema>: private::Responder {
+    /// Get the context of the interaction being responded to
+    #[inline]
+    fn interaction_ctx(&self) -> InteractionCtx { self.core().cx }
+
+    /// Get the locale of the user who invoked the interaction being responded
+    /// to
+    #[inline]
+    fn locale(&self) -> Locale { Locale::new(self.core().int.locale()) }
+
+    /// Get the preferred locale of the guild the interaction being responded
+    /// to was invoked in, if any
+    #[inline]
+    fn guild_locale(&self) -> Option<Locale> { self.core().int.guild_locale().map(Locale::new) }
+
+    /// Get the last known rate limit data for creating, editing and deleting
+    /// the original response to this interaction
+    ///
+    /// This returns `None` if no request has been made against the route's
+    /// bucket yet.
+    #[inline]
+    async fn response_rate_limit(&self) -> Option<RateLimit> {
+        let ResponderCore { http, int, .. } = self.core();
+        ratelimit::lookup(http, int.response_route()).await
+    }
+
+    /// Get the last known rate limit data for creating followup messages for
+    /// this interaction
+    ///
+    /// This returns `None` if no request has been made against the route's
+    /// bucket yet.
+    #[inline]
+    async fn followup_rate_limit(&self) -> Option<RateLimit> {
+        let ResponderCore { http, int, .. } = self.core();
+        ratelimit::lookup(http, int.followup_route()).await
+    }
+
     /// Create a followup message for this interaction
     #[inline]
     async fn create_followup(
@@ -277,17 +409,70 @@ pub trait ResponderExt<S: Schema>: private::Responder {
         Self: private::CreateFollowup,
         S::Component: 'async_trait,
     {
+        let core = self.core();
+        core.check_token()?;
         let ResponderCore {
             http,
             int,
-            schema: _,
-        } = self.core();
+            mentions,
+            ..
+        } = core;
         Ok(int
-            .create_followup_message(http, msg.prepare()?.build_default())
+            .create_followup_message(
+                http,
+                msg.default_mentions(mentions).prepare()?.build_default(),
+            )
             .await
             .map(Followup)?)
     }
 
+    /// Create a followup message for each of the given messages, in order
+    ///
+    /// Before each message is sent, the followup rate limit bucket is checked
+    /// and, if it is exhausted, this method waits for it to reset rather than
+    /// relying on the HTTP client to retry.  If the bucket would not reset
+    /// before the interaction token expires the batch is stopped early.
+    ///
+    /// # Errors
+    /// If any message fails to send, the remaining messages are skipped and
+    /// the error is returned along with all followups sent so far.
+    async fn send_all<M>(&self, msgs: M) -> Result<Vec<Followup>, BatchError>
+    where
+        Self: private::CreateFollowup,
+        M: IntoIterator<Item = Message<S::Component, id::Error>> + Send,
+        M::IntoIter: Send,
+        S::Component: 'async_trait,
+    {
+        let mut sent = vec![];
+
+        for msg in msgs {
+            if let Some(wait) = self
+                .followup_rate_limit()
+                .await
+                .and_then(|l| l.wait_time(SystemTime::now()))
+            {
+                let resume = chrono::TimeDelta::from_std(wait)
+                    .ok()
+                    .and_then(|w| chrono::Utc::now().checked_add_signed(w));
+                if resume.map_or(true, |r| r >= self.core().cx.token_deadline()) {
+                    return Err(BatchError {
+                        sent,
+                        error: ResponseError::Expired,
+                    });
+                }
+
+                tokio::time::sleep(wait).await;
+            }
+
+            match self.create_followup(msg).await {
+                Ok(fup) => sent.push(fup),
+                Err(error) => return Err(BatchError { sent, error }),
+            }
+        }
+
+        Ok(sent)
+    }
+
     /// Edit the given followup message for this interaction
     #[inline]
     async fn edit_followup(
@@ -299,14 +484,21 @@ pub trait ResponderExt<S: Schema>: private::Responder {
         Self: private::CreateFollowup,
         S::Component: 'async_trait,
     {
+        let core = self.core();
+        core.check_token()?;
         let ResponderCore {
             http,
             int,
-            schema: _,
-        } = self.core();
+            mentions,
+            ..
+        } = core;
         *fup = Followup(
-            int.edit_followup_message(http, fup.0.id, msg.build_default())
-                .await?,
+            int.edit_followup_message(
+                http,
+                fup.0.id,
+                msg.default_mentions(mentions).build_default(),
+            )
+            .await?,
         );
 
         Ok(())
@@ -314,14 +506,32 @@ pub trait ResponderExt<S: Schema>: private::Responder {
 
     /// Delete the given followup message for this interaction
     #[inline]
-    async fn delete_followup(&self, fup: Followup) -> Result<(), serenity::Error>
+    async fn delete_followup(&self, fup: Followup) -> Result<(), ResponseError>
     where Self: private::CreateFollowup {
-        let ResponderCore {
-            http,
-            int,
-            schema: _,
-        } = self.core();
-        int.delete_followup_message(http, fup.0.id).await
+        let core = self.core();
+        core.check_token()?;
+        let ResponderCore { http, int, .. } = core;
+        Ok(int.delete_followup_message(http, fup.0.id).await?)
+    }
+
+    /// Start a new post in the given forum or media channel
+    ///
+    /// Unlike the other methods on this trait, this is sent as the bot user
+    /// rather than through the interaction webhook, so it is not limited by
+    /// the interaction token's lifetime and requires the bot to have
+    /// permission to post in the channel.
+    #[inline]
+    async fn create_forum_post(
+        &self,
+        channel: ChannelId,
+        post: ForumPost<S::Component, id::Error>,
+    ) -> Result<GuildChannel, ResponseError>
+    where
+        S::Component: 'async_trait,
+    {
+        let ResponderCore { http, mentions, .. } = self.core();
+        let post = post.default_mentions(mentions).prepare()?;
+        Ok(channel.create_forum_post(http, post.into()).await?)
     }
 }
 
@@ -339,13 +549,23 @@ impl<'a, S, I> InitResponder<'a, S, I> {
     /// Wrap an HTTP client and interaction reference in a new responder
     #[inline]
     #[must_use]
-    pub fn new(http: &'a Http, int: &'a I) -> Self {
+    pub fn new(http: &'a Http, int: &'a I, cx: InteractionCtx) -> Self {
         Self(ResponderCore {
             http,
             int,
+            cx,
+            mentions: &NO_MENTIONS,
             schema: PhantomData,
         })
     }
+
+    /// Apply the given mention policy to all messages sent by this responder
+    /// that do not specify their own
+    #[inline]
+    #[must_use]
+    pub fn with_mentions(self, mentions: &'a AllowedMentions) -> Self {
+        Self(ResponderCore { mentions, ..self.0 })
+    }
 }
 
 impl<'a, S: Schema, I: private::Interaction> InitResponder<'a, S, I> {
@@ -354,18 +574,43 @@ impl<'a, S: Schema, I: private::Interaction> InitResponder<'a, S, I> {
         self,
         res: impl Into<CreateInteractionResponse> + Send,
         next: impl FnOnce(ResponderCore<'a, S, I>) -> T,
-    ) -> Result<T, serenity::Error> {
-        let Self(
-            core @ ResponderCore {
-                http,
-                int,
-                schema: _,
-            },
-        ) = self;
-        int.create_response(http, res.into()).await?;
+    ) -> Result<T, ResponseError> {
+        let Self(core @ ResponderCore { http, int, cx, .. }) = self;
+
+        // Local clock skew and gateway latency make this check unreliable, so
+        // leave the final say to Discord
+        if cx.is_initial_expired() {
+            tracing::warn!(
+                deadline = %cx.initial_deadline(),
+                "Initial response deadline may have passed, responding anyway",
+            );
+        }
+
+        int.create_response(http, res.into())
+            .await
+            .map_err(ResponseError::from_create)?;
         Ok(next(core))
     }
 
+    #[inline]
+    async fn update(
+        self,
+        msg: Message<S::Component, id::Error>,
+    ) -> Result<CreatedResponder<'a, S, I>, ResponseError> {
+        let msg = msg.default_mentions(self.0.mentions).prepare()?;
+        self.create(
+            CreateInteractionResponse::UpdateMessage(msg.build_default()),
+            CreatedResponder,
+        )
+        .await
+    }
+
+    #[inline]
+    async fn acknowledge(self) -> Result<CreatedResponder<'a, S, I>, ResponseError> {
+        self.create(CreateInteractionResponse::Acknowledge, CreatedResponder)
+            .await
+    }
+
     /// Create a channel message response
     ///
     /// # Errors
@@ -376,24 +621,45 @@ impl<'a, S: Schema, I: private::Interaction> InitResponder<'a, S, I> {
         self,
         msg: Message<S::Component, id::Error>,
     ) -> Result<CreatedResponder<'a, S, I>, ResponseError> {
-        Ok(self
-            .create(
-                CreateInteractionResponse::Message(msg.prepare()?.build_default()),
-                CreatedResponder,
-            )
-            .await?)
+        let msg = msg.default_mentions(self.0.mentions).prepare()?;
+        self.create(
+            CreateInteractionResponse::Message(msg.build_default()),
+            CreatedResponder,
+        )
+        .await
+    }
+
+    /// Create a channel message response prompting the user to purchase the
+    /// given SKU
+    ///
+    /// A premium button for the SKU is added in a new row below any
+    /// components already in the message.  This replaces Discord's deprecated
+    /// `PREMIUM_REQUIRED` response type.
+    ///
+    /// # Errors
+    /// This method returns an error if the message contains errors or an API
+    /// error is received.
+    #[inline]
+    pub async fn premium_required(
+        self,
+        sku: SkuId,
+        msg: Message<S::Component, id::Error>,
+    ) -> Result<CreatedResponder<'a, S, I>, ResponseError> {
+        self.create_message(msg.buttons(|b| b.premium(sku, false)))
+            .await
     }
 
     /// Create a deferred channel message response
     ///
     /// # Errors
-    /// This method returns an error if an API error is received.
+    /// This method returns an error if the response deadline has passed or an
+    /// API error is received.
     #[inline]
     pub async fn defer_message(
         self,
         // TODO: this is a message field now, can we send messages?
         opts: MessageOpts,
-    ) -> Result<CreatedResponder<'a, S, I>, serenity::Error> {
+    ) -> Result<CreatedResponder<'a, S, I>, ResponseError> {
         self.create(
             CreateInteractionResponse::Defer(opts.build_default()),
             CreatedResponder,
@@ -413,27 +679,20 @@ impl<'a, S: Schema, I: private::CreateUpdate> InitResponder<'a, S, I> {
         self,
         msg: Message<S::Component, id::Error>, // TODO: is opts necessary?
     ) -> Result<CreatedResponder<'a, S, I>, ResponseError> {
-        Ok(self
-            .create(
-                CreateInteractionResponse::UpdateMessage(msg.prepare()?.build_default()),
-                CreatedResponder,
-            )
-            .await?)
+        self.update(msg).await
     }
 
     /// Create a deferred message update response
     ///
     /// # Errors
-    /// This method returns an error if an API error is received.
+    /// This method returns an error if the response deadline has passed or an
+    /// API error is received.
     #[inline]
-    pub async fn defer_update(self) -> Result<CreatedResponder<'a, S, I>, serenity::Error> {
-        self.create(CreateInteractionResponse::Acknowledge, CreatedResponder)
-            .await
+    pub async fn defer_update(self) -> Result<CreatedResponder<'a, S, I>, ResponseError> {
+        self.acknowledge().await
     }
 }
 
-impl<S: Schema, I: private::TryCreateUpdate> InitResponder<'_, S, I> {}
-
 impl<'a, S: Schema, I: private::CreateModal> InitResponder<'a, S, I> {
     /// Create a modal dialog response
     ///
@@ -445,13 +704,14 @@ impl<'a, S: Schema, I: private::CreateModal> InitResponder<'a, S, I> {
         self,
         modal: impl FnOnce(ModalSourceHandle) -> Modal<S, id::Error>,
     ) -> Result<VoidResponder<'a, S, I>, ResponseError> {
-        let modal = modal(ModalSourceHandle(I::MODAL_SOURCE)).prepare()?;
-        Ok(self
-            .create(
-                CreateInteractionResponse::Modal(modal.into()),
-                VoidResponder,
-            )
-            .await?)
+        let modal = modal(ModalSourceHandle(I::MODAL_SOURCE))
+            .localize(&Locale::new(self.0.int.locale()))
+            .prepare()?;
+        self.create(
+            CreateInteractionResponse::Modal(modal.into()),
+            VoidResponder,
+        )
+        .await
     }
 }
 
@@ -479,20 +739,48 @@ impl<'a, S: Schema, I: private::Interaction> CreatedResponder<'a, S, I> {
         &self,
         res: MessageBody<S::Component, id::Error>,
     ) -> Result<serenity::model::channel::Message, ResponseError> {
+        self.0.check_token()?;
         Ok(self
             .0
             .int
-            .edit_response(self.0.http, res.prepare()?.build_default())
+            .edit_response(
+                self.0.http,
+                res.default_mentions(self.0.mentions)
+                    .prepare()?
+                    .build_default(),
+            )
             .await?)
     }
 
+    /// Replace the contents of a deferred response with the given message
+    #[inline]
+    pub(super) async fn edit_deferred(
+        &self,
+        msg: Message<S::Component, id::Error>,
+    ) -> Result<(), ResponseError> {
+        self.0.check_token()?;
+        self.0
+            .int
+            .edit_response(
+                self.0.http,
+                msg.default_mentions(self.0.mentions)
+                    .prepare()?
+                    .build_default(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
     /// Delete the interaction response message
     ///
     /// # Errors
-    /// This method returns an error if an API error is received.
+    /// This method returns an error if the interaction token has expired or an
+    /// API error is received.
     #[inline]
-    pub async fn delete(self) -> Result<(), serenity::Error> {
-        self.0.int.delete_response(self.0.http).await
+    pub async fn delete(self) -> Result<(), ResponseError> {
+        self.0.check_token()?;
+        Ok(self.0.int.delete_response(self.0.http).await?)
     }
 }
 
@@ -532,6 +820,8 @@ impl<'a, S, I> From<VoidResponder<'a, S, I>> for AckedResponder<'a, S, I> {
 pub enum BorrowedResponder<'a, S, I> {
     /// An initial-state responder
     Init(InitResponder<'a, S, I>),
+    /// A responder whose response was deferred on behalf of the handler
+    Deferred(CreatedResponder<'a, S, I>),
     /// A voided responder
     Void(VoidResponder<'a, S, I>),
     #[doc(hidden)]
@@ -542,16 +832,48 @@ impl<'a, S, I> BorrowedResponder<'a, S, I> {
     /// Wrap an HTTP client and interaction reference in a new responder
     #[inline]
     #[must_use]
-    pub fn new(http: &'a Http, int: &'a I) -> Self {
-        Self::Init(InitResponder(ResponderCore {
-            http,
-            int,
-            schema: PhantomData,
-        }))
+    pub fn new(http: &'a Http, int: &'a I, cx: InteractionCtx) -> Self {
+        Self::Init(InitResponder::new(http, int, cx))
     }
 }
 
 impl<'a, S: Schema, I: private::Interaction> BorrowedResponder<'a, S, I> {
+    /// Create a deferred channel message response if this responder is still in
+    /// its initial state, returning whether a response was sent
+    ///
+    /// Any response subsequently created through a [`BorrowingResponder`] will
+    /// be sent as an edit of the deferred message.
+    ///
+    /// # Errors
+    /// This method returns an error if the response deadline has passed or an
+    /// API error is received.
+    ///
+    /// # Panics
+    /// This method panics if the responder has entered a poisoned state.
+    pub async fn defer_pending(&mut self, opts: MessageOpts) -> Result<bool, ResponseError> {
+        match self {
+            Self::Init(_) => {
+                let Self::Init(resp) = mem::replace(self, Self::Poison) else {
+                    unreachable!()
+                };
+
+                let core = resp.0;
+                match resp.defer_message(opts).await {
+                    Ok(resp) => {
+                        *self = Self::Deferred(resp);
+                        Ok(true)
+                    },
+                    Err(e) => {
+                        *self = Self::Init(InitResponder(core));
+                        Err(e)
+                    },
+                }
+            },
+            Self::Deferred(_) | Self::Void(_) => Ok(false),
+            Self::Poison => panic!("Attempt to use poisoned responder"),
+        }
+    }
+
     /// Create a response message if this responder is in its initial state, or
     /// create a followup message if a response has already been created
     ///
@@ -578,52 +900,98 @@ impl<'a, S: Schema, I: private::Interaction> BorrowedResponder<'a, S, I> {
 
                 Ok(None)
             },
+            Self::Deferred(_) => {
+                let Self::Deferred(resp) = mem::replace(self, Self::Poison) else {
+                    unreachable!()
+                };
+
+                resp.edit_deferred(msg).await?;
+                *self = Self::Void(resp.void());
+
+                Ok(None)
+            },
             Self::Void(v) => v.create_followup(msg).await.map(Some),
             Self::Poison => panic!("Attempt to use poisoned responder"),
         }
     }
 }
 
+/// A responder whose response has not yet been created by its handler
+enum Pending<'a, S, I> {
+    Init(InitResponder<'a, S, I>),
+    Deferred(CreatedResponder<'a, S, I>),
+}
+
 /// A responder that mutates a [`BorrowedResponder`] when used, to synchronize
 /// type-states outside of a handler function
+///
+/// If the borrowed responder is deferred before the handler responds (see
+/// [`BorrowedResponder::defer_pending`]), message responses are sent as edits
+/// of the deferred message instead.
 #[derive(Debug)]
-pub struct BorrowingResponder<'a, 'b, S, I>(&'a mut BorrowedResponder<'b, S, I>);
+pub struct BorrowingResponder<'a, 'b, S, I>(
+    &'a Mutex<BorrowedResponder<'b, S, I>>,
+    InteractionCtx,
+    Option<ModalSource>,
+);
 
 impl<'a, 'b, S, I> BorrowingResponder<'a, 'b, S, I> {
     /// Borrow an existing [`BorrowedResponder`]
     ///
     /// # Panics
-    /// This function panics if the borrowed responder is not in its
-    /// [`Init`](BorrowedResponder::Init) state.
+    /// This function panics if the borrowed responder is locked or is not in
+    /// its [`Init`](BorrowedResponder::Init) state.
     #[inline]
     #[must_use]
-    pub fn new(resp: &'a mut BorrowedResponder<'b, S, I>) -> Self {
-        assert!(
-            matches!(resp, BorrowedResponder::Init(_)),
-            "BorrowingResponder::new called with a non-Init responder",
-        );
+    pub fn new(resp: &'a Mutex<BorrowedResponder<'b, S, I>>) -> Self {
+        let cx = match resp.try_lock().as_deref() {
+            Ok(BorrowedResponder::Init(i)) => i.0.cx,
+            _ => panic!("BorrowingResponder::new called with a non-Init responder"),
+        };
 
-        Self(resp)
+        Self(resp, cx, None)
     }
 
+    /// Record the kind of interaction that opened the modal being submitted
+    #[inline]
+    #[must_use]
+    pub(crate) fn with_modal_source(self, source: ModalSource) -> Self {
+        Self(self.0, self.1, Some(source))
+    }
+
+    /// Get the context of the interaction being responded to
+    #[inline]
+    #[must_use]
+    pub fn interaction_ctx(&self) -> InteractionCtx { self.1 }
+
     /// # Safety
     /// This function should only be called with a closure that invokes one of
-    /// the create response endpoints, otherwise the state update behavior is
-    /// incorrect.
+    /// the create response endpoints (or edits a deferred response), otherwise
+    /// the state update behavior is incorrect.
     async unsafe fn take<F: Future<Output = Result<T, E>>, T, E>(
         self,
-        f: impl FnOnce(InitResponder<'b, S, I>) -> F,
+        f: impl FnOnce(Pending<'b, S, I>) -> F,
     ) -> Result<T, E> {
-        let BorrowedResponder::Init(init) = mem::replace(self.0, BorrowedResponder::Poison) else {
-            unreachable!();
+        let Self(resp, ..) = self;
+        let mut resp = resp.lock().await;
+        let (pending, core, deferred) = match mem::replace(&mut *resp, BorrowedResponder::Poison) {
+            BorrowedResponder::Init(i) => {
+                let core = i.0;
+                (Pending::Init(i), core, false)
+            },
+            BorrowedResponder::Deferred(c) => {
+                let core = c.0;
+                (Pending::Deferred(c), core, true)
+            },
+            BorrowedResponder::Void(_) | BorrowedResponder::Poison => unreachable!(),
         };
 
-        let core = init.0;
-        let res = f(init).await;
+        let res = f(pending).await;
 
-        *self.0 = match res {
-            Ok(_) => BorrowedResponder::Void(VoidResponder(core)),
-            Err(_) => BorrowedResponder::Init(InitResponder(core)),
+        *resp = match (&res, deferred) {
+            (Ok(_), _) => BorrowedResponder::Void(VoidResponder(core)),
+            (Err(_), false) => BorrowedResponder::Init(InitResponder(core)),
+            (Err(_), true) => BorrowedResponder::Deferred(CreatedResponder(core)),
         };
 
         res
@@ -642,20 +1010,55 @@ impl<'a, 'b, S: Schema, I: private::Interaction> BorrowingResponder<'a, 'b, S, I
         msg: Message<S::Component, id::Error>,
     ) -> Result<CreatedResponder<'b, S, I>, ResponseError> {
         // SAFETY: this is a create response endpoint
-        unsafe { self.take(|i| i.create_message(msg)).await }
+        unsafe {
+            self.take(|p| async move {
+                match p {
+                    Pending::Init(i) => i.create_message(msg).await,
+                    Pending::Deferred(c) => c.edit_deferred(msg).await.map(|()| c),
+                }
+            })
+            .await
+        }
+    }
+
+    /// Create a channel message response prompting the user to purchase the
+    /// given SKU
+    ///
+    /// See [`InitResponder::premium_required`] for details.
+    ///
+    /// # Errors
+    /// This method returns an error if the message contains errors or an API
+    /// error is received.
+    #[inline]
+    pub async fn premium_required(
+        self,
+        sku: SkuId,
+        msg: Message<S::Component, id::Error>,
+    ) -> Result<CreatedResponder<'b, S, I>, ResponseError> {
+        self.create_message(msg.buttons(|b| b.premium(sku, false)))
+            .await
     }
 
     /// Create a deferred channel message response
     ///
     /// # Errors
-    /// This method returns an error if an API error is received.
+    /// This method returns an error if the response deadline has passed or an
+    /// API error is received.
     #[inline]
     pub async fn defer_message(
         self,
         opts: MessageOpts,
-    ) -> Result<CreatedResponder<'b, S, I>, serenity::Error> {
+    ) -> Result<CreatedResponder<'b, S, I>, ResponseError> {
         // SAFETY: this is a create response endpoint
-        unsafe { self.take(|i| i.defer_message(opts)).await }
+        unsafe {
+            self.take(|p| async move {
+                match p {
+                    Pending::Init(i) => i.defer_message(opts).await,
+                    Pending::Deferred(c) => Ok(c),
+                }
+            })
+            .await
+        }
     }
 }
 
@@ -671,21 +1074,56 @@ impl<'a, 'b, S: Schema, I: private::CreateUpdate> BorrowingResponder<'a, 'b, S,
         msg: Message<S::Component, id::Error>,
     ) -> Result<CreatedResponder<'b, S, I>, ResponseError> {
         // SAFETY: this is a create response endpoint
-        unsafe { self.take(|i| i.update_message(msg)).await }
+        unsafe {
+            self.take(|p| async move {
+                match p {
+                    Pending::Init(i) => i.update_message(msg).await,
+                    Pending::Deferred(c) => c.edit_deferred(msg).await.map(|()| c),
+                }
+            })
+            .await
+        }
     }
 
     /// Create a deferred message update response
     ///
     /// # Errors
-    /// This method returns an error if an API error is received.
+    /// This method returns an error if the response deadline has passed or an
+    /// API error is received.
     #[inline]
-    pub async fn defer_update(self) -> Result<CreatedResponder<'b, S, I>, serenity::Error> {
+    pub async fn defer_update(self) -> Result<CreatedResponder<'b, S, I>, ResponseError> {
         // SAFETY: this is a create response endpoint
-        unsafe { self.take(super::InitResponder::defer_update).await }
+        unsafe {
+            self.take(|p| async move {
+                match p {
+                    Pending::Init(i) => i.defer_update().await,
+                    Pending::Deferred(c) => Ok(c),
+                }
+            })
+            .await
+        }
     }
 }
 
-impl<S: Schema, I: private::TryCreateUpdate> BorrowingResponder<'_, '_, S, I> {}
+impl<'a, 'b, S: Schema, I: private::TryCreateUpdate> BorrowingResponder<'a, 'b, S, I> {
+    /// Narrow this responder according to the kind of interaction that opened
+    /// the modal being submitted
+    ///
+    /// Only a modal opened from a message component can be answered by
+    /// updating the message containing that component.  Modals opened by a
+    /// command, or whose source was not recorded, can only be answered with a
+    /// new message.
+    #[inline]
+    #[must_use]
+    pub fn initiator(self) -> ModalInitiator<'a, 'b, S, I> {
+        match self.2 {
+            Some(ModalSource::Component) => {
+                ModalInitiator::Component(ComponentModalResponder(self))
+            },
+            Some(ModalSource::Command) | None => ModalInitiator::Command(self),
+        }
+    }
+}
 
 impl<'a, 'b, S: Schema, I: private::CreateModal> BorrowingResponder<'a, 'b, S, I> {
     /// Create a modal dialog response
@@ -699,6 +1137,88 @@ impl<'a, 'b, S: Schema, I: private::CreateModal> BorrowingResponder<'a, 'b, S, I
         f: impl FnOnce(ModalSourceHandle) -> Modal<S, id::Error>,
     ) -> Result<VoidResponder<'b, S, I>, ResponseError> {
         // SAFETY: this is a create response endpoint
-        unsafe { self.take(|i| i.modal(f)).await }
+        unsafe {
+            self.take(|p| async move {
+                match p {
+                    Pending::Init(i) => i.modal(f).await,
+                    Pending::Deferred(_) => Err(ResponseError::Deferred),
+                }
+            })
+            .await
+        }
+    }
+}
+
+/// A modal-submit responder narrowed by the kind of interaction that opened
+/// the modal
+#[derive(Debug)]
+pub enum ModalInitiator<'a, 'b, S, I> {
+    /// The modal was opened by a command, or its source is unknown
+    Command(BorrowingResponder<'a, 'b, S, I>),
+    /// The modal was opened from a message component
+    Component(ComponentModalResponder<'a, 'b, S, I>),
+}
+
+/// A responder for a modal opened from a message component, which may update
+/// the message containing that component
+#[derive(Debug)]
+pub struct ComponentModalResponder<'a, 'b, S, I>(BorrowingResponder<'a, 'b, S, I>);
+
+impl<'a, 'b, S, I> ComponentModalResponder<'a, 'b, S, I> {
+    /// Get the context of the interaction being responded to
+    #[inline]
+    #[must_use]
+    pub fn interaction_ctx(&self) -> InteractionCtx { self.0.interaction_ctx() }
+
+    /// Unwrap the underlying responder, e.g. to create a new message instead
+    /// of updating the existing one
+    #[inline]
+    #[must_use]
+    pub fn into_inner(self) -> BorrowingResponder<'a, 'b, S, I> { self.0 }
+}
+
+impl<'a, 'b, S: Schema, I: private::TryCreateUpdate> ComponentModalResponder<'a, 'b, S, I> {
+    /// Create a message update response, editing the message containing the
+    /// component that opened the modal
+    ///
+    /// # Errors
+    /// This method returns an error if the message contains errors or an API
+    /// error is received.
+    #[inline]
+    pub async fn update_message(
+        self,
+        msg: Message<S::Component, id::Error>,
+    ) -> Result<CreatedResponder<'b, S, I>, ResponseError> {
+        // SAFETY: this is a create response endpoint
+        unsafe {
+            self.0
+                .take(|p| async move {
+                    match p {
+                        Pending::Init(i) => i.update(msg).await,
+                        Pending::Deferred(c) => c.edit_deferred(msg).await.map(|()| c),
+                    }
+                })
+                .await
+        }
+    }
+
+    /// Create a deferred message update response
+    ///
+    /// # Errors
+    /// This method returns an error if the response deadline has passed or an
+    /// API error is received.
+    #[inline]
+    pub async fn defer_update(self) -> Result<CreatedResponder<'b, S, I>, ResponseError> {
+        // SAFETY: this is a create response endpoint
+        unsafe {
+            self.0
+                .take(|p| async move {
+                    match p {
+                        Pending::Init(i) => i.acknowledge().await,
+                        Pending::Deferred(c) => Ok(c),
+                    }
+                })
+                .await
+        }
     }
 }
@@ -0,0 +1,38 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+/// A bounded, thread-safe log retaining only the most recent entries
+#[derive(Debug)]
+pub(super) struct RingLog<T> {
+    cap: usize,
+    log: Mutex<VecDeque<T>>,
+}
+
+impl<T> RingLog<T> {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            cap,
+            log: Mutex::new(VecDeque::with_capacity(cap)),
+        }
+    }
+
+    pub fn push(&self, val: T) {
+        if self.cap == 0 {
+            return;
+        }
+
+        let mut log = self.log.lock().unwrap();
+        while log.len() >= self.cap {
+            log.pop_front();
+        }
+        log.push_back(val);
+    }
+
+    pub fn recent(&self, n: usize) -> Vec<T>
+    where T: Clone {
+        let log = self.log.lock().unwrap();
+        log.iter()
+            .skip(log.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
@@ -6,13 +6,14 @@
 
 use std::fmt;
 
+use chrono::{DateTime, Utc};
 use serenity::model::application::{ComponentInteraction, ModalInteraction};
 
 use super::response::ModalSource;
 
 /// A key identifying the static handler for an RPC request (i.e. the remote
 /// procedure name)
-pub trait Key: fmt::Debug + Copy + Ord + std::hash::Hash + 'static
+pub trait Key: fmt::Debug + Copy + Ord + std::hash::Hash + Send + Sync + 'static
 where for<'a> Self: From<&'a Self::Payload>
 {
     /// The payload type for this set of keys, containing enough data to produce
@@ -34,14 +35,14 @@ pub trait Schema: fmt::Debug {
     /// message
     type ComponentKey: Key<Payload = Self::ComponentPayload, Interaction = ComponentInteraction>;
     /// The payload of a [`Component`](Self::Component) message
-    type ComponentPayload: fmt::Debug;
+    type ComponentPayload: fmt::Debug + Send;
 
     /// The message type for modal custom IDs
     type Modal: ModalId<Key = Self::ModalKey, Payload = Self::ModalPayload>;
     /// The key type identifying the kind of a [`Modal`](Self::Modal) message
     type ModalKey: Key<Payload = Self::ModalPayload, Interaction = ModalInteraction>;
     /// The payload of a [`Modal`](Self::Modal) message
-    type ModalPayload: fmt::Debug;
+    type ModalPayload: fmt::Debug + Send;
 }
 
 /// A valid message for encoding into custom component IDs
@@ -56,6 +57,12 @@ pub trait ComponentId: Default + prost::Message {
 
     /// Destructure an ID message into its inner payload
     fn try_into_parts(self) -> Option<Self::Payload>;
+
+    /// Get the time after which this ID should no longer be handled, if any
+    fn expires_at(&self) -> Option<DateTime<Utc>>;
+
+    /// Set or clear the time after which this ID should no longer be handled
+    fn set_expires_at(&mut self, at: Option<DateTime<Utc>>);
 }
 
 /// A valid message for encoding into modal custom IDs
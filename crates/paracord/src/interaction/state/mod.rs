@@ -0,0 +1,419 @@
+//! Short-lived server-side state for multi-step interaction flows
+//!
+//! Component and modal custom IDs are limited to 100 characters, which is
+//! too little to carry the accumulated answers of a wizard-style flow from
+//! one step to the next.  Instead, a handler can put the flow's state in a
+//! [`StateStore`] and place only the returned [`StateToken`] in the payload
+//! of the components it sends.  The next step reads the state back with the
+//! token from the payload.
+//!
+//! State expires along with the token of the interaction that stored it, at
+//! which point the components carrying its token can no longer be answered
+//! with a followup anyway.  Tokens are not guessable, but a flow's state is
+//! not tied to the user who started it, so handlers should store and check
+//! the user themselves where it matters.
+//!
+//! A [`Registry`](super::Registry) stores its state store in the client's data
+//! so handlers can retrieve it with [`get`].
+
+#[cfg(feature = "redis")]
+mod redis;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasher, RandomState},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serenity::prelude::TypeMapKey;
+
+#[cfg(feature = "redis")]
+pub use self::redis::RedisBackend;
+use super::context::InteractionCtx;
+use crate::client::Context;
+
+/// The default limit on entries held by a [`MemoryBackend`]
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// An opaque handle to stored state, small enough to embed in a custom ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateToken(u64);
+
+impl StateToken {
+    /// Get the raw value of this token, for storing in a payload
+    #[inline]
+    #[must_use]
+    pub const fn get(self) -> u64 { self.0 }
+}
+
+impl From<u64> for StateToken {
+    #[inline]
+    fn from(value: u64) -> Self { Self(value) }
+}
+
+impl fmt::Display for StateToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{:016x}", self.0) }
+}
+
+/// An error arising from storing or retrieving state
+#[derive(Debug, thiserror::Error)]
+pub enum StateError {
+    /// Stored state could not be decoded as the requested type
+    #[error("Error decoding stored state")]
+    Decode(#[from] prost::DecodeError),
+    /// The Redis server could not be reached
+    #[cfg(feature = "redis")]
+    #[error("Error communicating with Redis")]
+    Io(#[from] std::io::Error),
+    /// The Redis server returned an error or an unexpected reply
+    #[cfg(feature = "redis")]
+    #[error("Redis error: {0}")]
+    Redis(String),
+    /// A custom backend returned an error
+    #[error("Error accessing state backend")]
+    Backend(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Storage for encoded state, keyed by token
+#[async_trait]
+pub trait StateBackend: fmt::Debug + Send + Sync {
+    /// Store a value under the given token for the given duration, replacing
+    /// any existing value
+    ///
+    /// # Errors
+    /// This method returns an error if the backend cannot be reached.
+    async fn put(&self, token: StateToken, value: Vec<u8>, ttl: Duration)
+        -> Result<(), StateError>;
+
+    /// Get the unexpired value stored under the given token
+    ///
+    /// # Errors
+    /// This method returns an error if the backend cannot be reached.
+    async fn get(&self, token: StateToken) -> Result<Option<Vec<u8>>, StateError>;
+
+    /// Remove and return the unexpired value stored under the given token
+    ///
+    /// # Errors
+    /// This method returns an error if the backend cannot be reached.
+    async fn take(&self, token: StateToken) -> Result<Option<Vec<u8>>, StateError>;
+}
+
+/// A [`StateBackend`] holding state in memory, which is lost on restart
+#[derive(Debug)]
+pub struct MemoryBackend {
+    max_entries: usize,
+    entries: Mutex<HashMap<StateToken, (Instant, Vec<u8>)>>,
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self { Self::new(DEFAULT_MAX_ENTRIES) }
+}
+
+impl MemoryBackend {
+    /// Construct a new backend holding at most `max_entries` values, after
+    /// which the values closest to expiring are evicted first
+    #[inline]
+    #[must_use]
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// The number of values currently stored, including any that have
+    /// expired but not yet been evicted
+    ///
+    /// # Panics
+    /// This method panics if the backend's lock is poisoned.
+    #[must_use]
+    pub fn len(&self) -> usize { self.entries.lock().unwrap().len() }
+
+    /// Returns true if no values are stored
+    ///
+    /// # Panics
+    /// This method panics if the backend's lock is poisoned.
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+#[async_trait]
+impl StateBackend for MemoryBackend {
+    async fn put(
+        &self,
+        token: StateToken,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<(), StateError> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, &mut (exp, _)| exp > now);
+        entries.insert(token, (now + ttl, value));
+
+        while entries.len() > self.max_entries {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, &(exp, _))| exp)
+                .map(|(&k, _)| k)
+            else {
+                break;
+            };
+
+            tracing::warn!(%oldest, "Evicting state to make room");
+            entries.remove(&oldest);
+        }
+
+        Ok(())
+    }
+
+    async fn get(&self, token: StateToken) -> Result<Option<Vec<u8>>, StateError> {
+        let entries = self.entries.lock().unwrap();
+        Ok(entries
+            .get(&token)
+            .filter(|(exp, _)| *exp > Instant::now())
+            .map(|(_, v)| v.clone()))
+    }
+
+    async fn take(&self, token: StateToken) -> Result<Option<Vec<u8>>, StateError> {
+        let mut entries = self.entries.lock().unwrap();
+        Ok(entries
+            .remove(&token)
+            .filter(|(exp, _)| *exp > Instant::now())
+            .map(|(_, v)| v))
+    }
+}
+
+/// Typed access to a [`StateBackend`], issuing a fresh token for each new
+/// flow
+pub struct StateStore {
+    backend: Arc<dyn StateBackend>,
+    keys: RandomState,
+    next: AtomicU64,
+}
+
+impl fmt::Debug for StateStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateStore")
+            .field("backend", &self.backend)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for StateStore {
+    fn default() -> Self { Self::new(Arc::new(MemoryBackend::default())) }
+}
+
+impl StateStore {
+    /// Construct a new store over the given backend
+    #[inline]
+    #[must_use]
+    pub fn new(backend: Arc<dyn StateBackend>) -> Self {
+        Self {
+            backend,
+            keys: RandomState::new(),
+            next: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the backend this store reads from and writes to
+    #[inline]
+    #[must_use]
+    pub fn backend(&self) -> &Arc<dyn StateBackend> { &self.backend }
+
+    fn token(&self) -> StateToken {
+        StateToken(
+            self.keys
+                .hash_one(self.next.fetch_add(1, Ordering::Relaxed)),
+        )
+    }
+
+    /// Store state for a new flow until the token of the given interaction
+    /// expires, returning the token to read it back with
+    ///
+    /// # Errors
+    /// This method returns an error if the backend cannot be reached.
+    pub async fn insert<M: prost::Message>(
+        &self,
+        ctx: &InteractionCtx,
+        value: &M,
+    ) -> Result<StateToken, StateError> {
+        let ttl = (ctx.token_deadline() - Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        self.insert_for(ttl, value).await
+    }
+
+    /// Store state for a new flow for the given duration, returning the token
+    /// to read it back with
+    ///
+    /// # Errors
+    /// This method returns an error if the backend cannot be reached.
+    pub async fn insert_for<M: prost::Message>(
+        &self,
+        ttl: Duration,
+        value: &M,
+    ) -> Result<StateToken, StateError> {
+        let token = self.token();
+        self.backend.put(token, value.encode_to_vec(), ttl).await?;
+        Ok(token)
+    }
+
+    /// Replace the state of an existing flow, renewing it until the token of
+    /// the given interaction expires
+    ///
+    /// # Errors
+    /// This method returns an error if the backend cannot be reached.
+    pub async fn replace<M: prost::Message>(
+        &self,
+        ctx: &InteractionCtx,
+        token: StateToken,
+        value: &M,
+    ) -> Result<(), StateError> {
+        let ttl = (ctx.token_deadline() - Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        self.backend.put(token, value.encode_to_vec(), ttl).await
+    }
+
+    /// Read the state of a flow, returning `None` if it has expired
+    ///
+    /// # Errors
+    /// This method returns an error if the backend cannot be reached or the
+    /// state cannot be decoded.
+    pub async fn get<M: prost::Message + Default>(
+        &self,
+        token: StateToken,
+    ) -> Result<Option<M>, StateError> {
+        let Some(bytes) = self.backend.get(token).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(M::decode(&*bytes)?))
+    }
+
+    /// Remove and return the state of a finished flow, returning `None` if it
+    /// has expired
+    ///
+    /// # Errors
+    /// This method returns an error if the backend cannot be reached or the
+    /// state cannot be decoded.
+    pub async fn take<M: prost::Message + Default>(
+        &self,
+        token: StateToken,
+    ) -> Result<Option<M>, StateError> {
+        let Some(bytes) = self.backend.take(token).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(M::decode(&*bytes)?))
+    }
+}
+
+struct StateStoreKey;
+
+impl TypeMapKey for StateStoreKey {
+    type Value = Arc<StateStore>;
+}
+
+/// Store a state store in the client's data for retrieval with [`get`]
+pub(super) async fn install(ctx: &Context, state: &Arc<StateStore>) {
+    ctx.data
+        .write()
+        .await
+        .insert::<StateStoreKey>(Arc::clone(state));
+}
+
+/// Retrieve the state store of the [`Registry`](super::Registry) handling
+/// interactions for this client
+///
+/// This returns `None` until the registry has been initialized.
+pub async fn get(ctx: &Context) -> Option<Arc<StateStore>> {
+    ctx.data.read().await.get::<StateStoreKey>().map(Arc::clone)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use futures_util::FutureExt;
+
+    use super::{MemoryBackend, StateBackend, StateStore, StateToken};
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Wizard {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(uint32, tag = "2")]
+        step: u32,
+    }
+
+    #[test]
+    fn round_trip() {
+        let store = StateStore::default();
+        let state = Wizard {
+            name: "q".into(),
+            step: 1,
+        };
+        let hour = Duration::from_secs(3600);
+
+        let token = store
+            .insert_for(hour, &state)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        let other = store
+            .insert_for(hour, &state)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_ne!(token, other);
+
+        let get = |t| store.get::<Wizard>(t).now_or_never().unwrap().unwrap();
+        assert_eq!(get(token), Some(state.clone()));
+        assert_eq!(get(StateToken::from(token.get() ^ 1)), None);
+
+        let done = store.take::<Wizard>(token).now_or_never().unwrap().unwrap();
+        assert_eq!(done, Some(state));
+        assert_eq!(get(token), None);
+    }
+
+    #[test]
+    fn expiry() {
+        let backend = Arc::new(MemoryBackend::new(2));
+        let put = |t: u64, ttl| {
+            backend
+                .put(t.into(), vec![], Duration::from_secs(ttl))
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+        };
+        let has = |t: u64| {
+            backend
+                .get(t.into())
+                .now_or_never()
+                .unwrap()
+                .unwrap()
+                .is_some()
+        };
+
+        put(1, 0);
+        assert!(!has(1));
+
+        put(2, 60);
+        put(3, 30);
+        assert_eq!(backend.len(), 2);
+
+        put(4, 90);
+        assert_eq!(backend.len(), 2);
+        assert!(has(2));
+        assert!(!has(3));
+        assert!(has(4));
+    }
+}
@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use percent_encoding::percent_decode_str;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream},
+    net::TcpStream,
+    sync::Mutex,
+};
+use url::Url;
+
+use super::{StateBackend, StateError, StateToken};
+
+const DEFAULT_PORT: u16 = 6379;
+const DEFAULT_PREFIX: &str = "paracord:state:";
+
+/// A single reply from the server, excluding errors and arrays, which none of
+/// the commands sent by this backend return
+enum Reply {
+    Nil,
+    Simple,
+    Int,
+    Bulk(Vec<u8>),
+}
+
+impl Reply {
+    fn into_bulk(self) -> Result<Option<Vec<u8>>, StateError> {
+        match self {
+            Self::Nil => Ok(None),
+            Self::Bulk(b) => Ok(Some(b)),
+            Self::Simple | Self::Int => Err(StateError::Redis("Expected a bulk reply".into())),
+        }
+    }
+}
+
+fn protocol(msg: &str) -> StateError { StateError::Redis(format!("Protocol error: {msg}")) }
+
+async fn read_reply(conn: &mut BufStream<TcpStream>) -> Result<Reply, StateError> {
+    let mut line = vec![];
+    conn.read_until(b'\n', &mut line).await?;
+    let line = line
+        .strip_suffix(b"\r\n")
+        .ok_or_else(|| protocol("Unterminated reply"))?;
+    let (&kind, rest) = line.split_first().ok_or_else(|| protocol("Empty reply"))?;
+    let rest = std::str::from_utf8(rest).map_err(|_| protocol("Invalid reply header"))?;
+
+    match kind {
+        b'+' => Ok(Reply::Simple),
+        b'-' => Err(StateError::Redis(rest.into())),
+        b':' => Ok(Reply::Int),
+        b'$' if rest == "-1" => Ok(Reply::Nil),
+        b'$' => {
+            let len: usize = rest.parse().map_err(|_| protocol("Invalid bulk length"))?;
+            let mut buf = vec![0; len + 2];
+            conn.read_exact(&mut buf).await?;
+            buf.truncate(len);
+            Ok(Reply::Bulk(buf))
+        },
+        _ => Err(protocol("Unexpected reply type")),
+    }
+}
+
+async fn exec(conn: &mut BufStream<TcpStream>, args: &[&[u8]]) -> Result<Reply, StateError> {
+    conn.write_all(format!("*{}\r\n", args.len()).as_bytes())
+        .await?;
+    for arg in args {
+        conn.write_all(format!("${}\r\n", arg.len()).as_bytes())
+            .await?;
+        conn.write_all(arg).await?;
+        conn.write_all(b"\r\n").await?;
+    }
+    conn.flush().await?;
+
+    read_reply(conn).await
+}
+
+/// A [`StateBackend`] holding state on a Redis server, which survives
+/// restarts and can be shared between processes
+///
+/// Values are stored with `SET ... PX` and removed with `GETDEL`, which
+/// requires Redis 6.2 or newer.  A single connection is opened on first use
+/// and reopened after any error.
+#[derive(Debug)]
+pub struct RedisBackend {
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+    db: Option<String>,
+    prefix: String,
+    conn: Mutex<Option<BufStream<TcpStream>>>,
+}
+
+impl RedisBackend {
+    /// Construct a new backend for the server at the given
+    /// `redis://[[user]:password@]host[:port][/db]` URL
+    ///
+    /// No connection is made until the backend is first used.
+    ///
+    /// # Errors
+    /// This method returns an error if the URL is not a valid Redis URL.
+    pub fn new(url: &Url) -> Result<Self, StateError> {
+        let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+
+        if url.scheme() != "redis" {
+            return Err(StateError::Redis(format!(
+                "Unsupported URL scheme {:?}",
+                url.scheme()
+            )));
+        }
+
+        let host = url
+            .host_str()
+            .ok_or_else(|| StateError::Redis("Missing host in URL".into()))?
+            .to_owned();
+        let db = url.path().trim_start_matches('/');
+
+        Ok(Self {
+            host,
+            port: url.port().unwrap_or(DEFAULT_PORT),
+            username: Some(url.username()).filter(|u| !u.is_empty()).map(decode),
+            password: url.password().map(decode),
+            db: Some(db).filter(|d| !d.is_empty()).map(Into::into),
+            prefix: DEFAULT_PREFIX.into(),
+            conn: Mutex::default(),
+        })
+    }
+
+    /// Store values under keys beginning with the given prefix, instead of
+    /// the default of `paracord:state:`
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, token: StateToken) -> String { format!("{}{token}", self.prefix) }
+
+    async fn connect(&self) -> Result<BufStream<TcpStream>, StateError> {
+        let mut conn = BufStream::new(TcpStream::connect((&*self.host, self.port)).await?);
+
+        if let Some(ref password) = self.password {
+            let mut auth: Vec<&[u8]> = vec![b"AUTH"];
+            auth.extend(self.username.as_ref().map(String::as_bytes));
+            auth.push(password.as_bytes());
+            exec(&mut conn, &auth).await?;
+        }
+
+        if let Some(ref db) = self.db {
+            exec(&mut conn, &[b"SELECT", db.as_bytes()]).await?;
+        }
+
+        Ok(conn)
+    }
+
+    async fn query(&self, args: &[&[u8]]) -> Result<Reply, StateError> {
+        let mut conn = self.conn.lock().await;
+
+        let stream = match *conn {
+            Some(ref mut s) => s,
+            None => conn.insert(self.connect().await?),
+        };
+
+        let res = exec(stream, args).await;
+        if res.is_err() {
+            // The connection may be left mid-reply, so start over next time
+            *conn = None;
+        }
+
+        res
+    }
+}
+
+#[async_trait]
+impl StateBackend for RedisBackend {
+    async fn put(
+        &self,
+        token: StateToken,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<(), StateError> {
+        let ms = ttl.as_millis().max(1).to_string();
+        self.query(&[
+            b"SET",
+            self.key(token).as_bytes(),
+            &value,
+            b"PX",
+            ms.as_bytes(),
+        ])
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, token: StateToken) -> Result<Option<Vec<u8>>, StateError> {
+        self.query(&[b"GET", self.key(token).as_bytes()])
+            .await?
+            .into_bulk()
+    }
+
+    async fn take(&self, token: StateToken) -> Result<Option<Vec<u8>>, StateError> {
+        self.query(&[b"GETDEL", self.key(token).as_bytes()])
+            .await?
+            .into_bulk()
+    }
+}
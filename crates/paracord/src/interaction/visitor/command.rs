@@ -10,13 +10,15 @@ use serenity::{
     all::{ResolvedOption, ResolvedValue},
     model::{
         application::{CommandData, CommandDataResolved, CommandOptionType, CommandType},
-        channel::{Attachment, Message, PartialChannel},
+        channel::{Attachment, Message, PartialChannel, Poll},
         guild::{PartialMember, Role},
+        id::{ChannelId, RoleId, UserId},
+        misc::EmojiIdentifier,
         user::User,
     },
 };
 
-use super::{BasicVisitor, Describe, Error, Result};
+use super::{BasicVisitor, Describe, Error, Mention, MessageLink, Result};
 
 #[derive(Debug, Clone, Copy)]
 pub enum OptionValueType {
@@ -55,7 +57,8 @@ type OptionMap<'a> = HashMap<&'a str, ResolvedOption<'a>>;
 #[derive(Debug)]
 enum VisitorState<'a> {
     Init,
-    SlashCommand(OptionMap<'a>),
+    // The subcommand path is held until it is consumed by a visitor method
+    SlashCommand(Option<Subcommand<'a>>, OptionMap<'a>),
 }
 
 /// A visitor for extracting data from a command invocation
@@ -188,9 +191,91 @@ impl<'a, I: super::private::Interaction<Data = CommandData>> CommandVisitor<'a,
         pub fn visit_attachment() -> &'a Attachment { Attachment(a) => a }
     }
 
+    fn visit_parsed<T>(
+        &mut self,
+        name: &'a str,
+        desc: &'static str,
+        f: impl FnOnce(Mention<'a>) -> Option<T>,
+    ) -> Result<OptionVisitor<'a, T>> {
+        let resolved = &self.base.int.data().resolved;
+        let OptionVisitor(_, val) = self.visit_string(name)?;
+
+        val.map(|s| {
+            Mention::parse(s, resolved)
+                .and_then(f)
+                .ok_or_else(|| Error::BadMention(name.into(), desc))
+        })
+        .transpose()
+        .map(|v| OptionVisitor(name, v))
+    }
+
+    /// Visit a string argument containing a user, role or channel mention, a
+    /// custom emoji or a message link
+    ///
+    /// # Errors
+    /// This method returns an error if the command does not take arguments,
+    /// the named argument is not a string, or it could not be parsed
+    pub fn visit_mention(&mut self, name: &'a str) -> Result<OptionVisitor<'a, Mention<'a>>> {
+        self.visit_parsed(name, "a mention, emoji or message link", Some)
+    }
+
+    /// Visit a string argument containing a user mention
+    ///
+    /// # Errors
+    /// This method returns an error if the command does not take arguments,
+    /// the named argument is not a string, or it is not a user mention
+    pub fn visit_user_mention(
+        &mut self,
+        name: &'a str,
+    ) -> Result<OptionVisitor<'a, (UserId, Option<&'a User>)>> {
+        self.visit_parsed(name, "a user mention", Mention::user)
+    }
+
+    /// Visit a string argument containing a role mention
+    ///
+    /// # Errors
+    /// This method returns an error if the command does not take arguments,
+    /// the named argument is not a string, or it is not a role mention
+    pub fn visit_role_mention(
+        &mut self,
+        name: &'a str,
+    ) -> Result<OptionVisitor<'a, (RoleId, Option<&'a Role>)>> {
+        self.visit_parsed(name, "a role mention", Mention::role)
+    }
+
+    /// Visit a string argument containing a channel mention
+    ///
+    /// # Errors
+    /// This method returns an error if the command does not take arguments,
+    /// the named argument is not a string, or it is not a channel mention
+    pub fn visit_channel_mention(
+        &mut self,
+        name: &'a str,
+    ) -> Result<OptionVisitor<'a, (ChannelId, Option<&'a PartialChannel>)>> {
+        self.visit_parsed(name, "a channel mention", Mention::channel)
+    }
+
+    /// Visit a string argument containing a custom emoji
+    ///
+    /// # Errors
+    /// This method returns an error if the command does not take arguments,
+    /// the named argument is not a string, or it is not a custom emoji
+    pub fn visit_emoji(&mut self, name: &'a str) -> Result<OptionVisitor<'a, EmojiIdentifier>> {
+        self.visit_parsed(name, "a custom emoji", Mention::emoji)
+    }
+
+    /// Visit a string argument containing a message link
+    ///
+    /// # Errors
+    /// This method returns an error if the command does not take arguments,
+    /// the named argument is not a string, or it is not a message link
+    pub fn visit_message_link(&mut self, name: &'a str) -> Result<OptionVisitor<'a, MessageLink>> {
+        self.visit_parsed(name, "a message link", Mention::message)
+    }
+
     fn visit_opts(&mut self) -> Result<(Option<Subcommand<'a>>, &mut OptionMap<'a>)> {
-        if let VisitorState::SlashCommand(ref mut m) = self.state {
-            return Ok((None, m));
+        if let VisitorState::SlashCommand(ref mut s, ref mut m) = self.state {
+            return Ok((s.take(), m));
         }
 
         if !matches!(self.base.int.data().kind, CommandType::ChatInput) {
@@ -239,8 +324,8 @@ impl<'a, I: super::private::Interaction<Data = CommandData>> CommandVisitor<'a,
             })
             .collect::<Result<_>>()?;
 
-        self.state = VisitorState::SlashCommand(map);
-        let VisitorState::SlashCommand(ref mut m) = self.state else {
+        self.state = VisitorState::SlashCommand(None, map);
+        let VisitorState::SlashCommand(_, ref mut m) = self.state else {
             unreachable!();
         };
         Ok(((!subcmd.is_empty()).then_some(subcmd), m))
@@ -269,6 +354,23 @@ impl<'a, I: super::private::Interaction<Data = CommandData>> CommandVisitor<'a,
         subcmd.ok_or(Error::MissingSubcommand)
     }
 
+    /// Consume the first segment of the invoked subcommand path, leaving the
+    /// rest to be visited by [`visit_subcmd`](Self::visit_subcmd)
+    pub(in super::super) fn descend(&mut self) -> Result<&'a str> {
+        let (subcmd, _opts) = self.visit_opts()?;
+        let mut subcmd = subcmd.ok_or(Error::MissingSubcommand)?;
+        let head = subcmd.remove(0);
+
+        if !subcmd.is_empty() {
+            let VisitorState::SlashCommand(ref mut s, _) = self.state else {
+                unreachable!();
+            };
+            *s = Some(subcmd);
+        }
+
+        Ok(head)
+    }
+
     /// Visit the target of this context menu command
     #[inline]
     #[must_use]
@@ -294,7 +396,13 @@ impl<'a, I: super::private::Interaction<Data = CommandData>> CommandVisitor<'a,
                     ));
                 }
             },
-            VisitorState::SlashCommand(m) => {
+            VisitorState::SlashCommand(s, m) => {
+                if let Some(s) = s {
+                    return Err(Error::UnhandledSubcommand(
+                        s.into_iter().map(Into::into).collect(),
+                    ));
+                }
+
                 if !m.is_empty() {
                     return Err(Error::Trailing(m.into_keys().map(Into::into).collect()));
                 }
@@ -368,4 +476,11 @@ impl<'a> TargetVisitor<'a> {
 
         Ok(msg)
     }
+
+    /// Read the poll attached to the target message of a message command
+    ///
+    /// Discord does not send interactions for poll votes, so this is the
+    /// primary way for a command to inspect a poll's answers and (possibly
+    /// still running) vote counts.
+    pub fn poll(self) -> Result<&'a Poll> { self.message()?.poll.as_deref().ok_or(Error::NotPoll) }
 }
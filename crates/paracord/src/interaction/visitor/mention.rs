@@ -0,0 +1,243 @@
+use serenity::{
+    model::{
+        application::CommandDataResolved,
+        channel::PartialChannel,
+        guild::Role,
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        misc::EmojiIdentifier,
+        user::User,
+    },
+    utils,
+};
+
+const MESSAGE_HOSTS: &[&str] = &["discord.com", "discordapp.com"];
+
+/// A link to a message, as produced by Discord's "Copy Message Link" action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MessageLink {
+    /// The guild containing the message, or `None` for a DM
+    pub guild: Option<GuildId>,
+    /// The channel containing the message
+    pub channel: ChannelId,
+    /// The ID of the message
+    pub message: MessageId,
+}
+
+impl MessageLink {
+    /// Parse a message link, returning `None` if the input is not one
+    ///
+    /// Links from the Canary and PTB clients, links using the legacy
+    /// `discordapp.com` domain, and links wrapped in angle brackets to suppress
+    /// embeds are all accepted.
+    #[must_use]
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = s
+            .strip_prefix('<')
+            .and_then(|s| s.strip_suffix('>'))
+            .unwrap_or(s);
+        let rest = s
+            .strip_prefix("https://")
+            .or_else(|| s.strip_prefix("http://"))?;
+        let (host, path) = rest.split_once('/')?;
+        let host = host
+            .strip_prefix("canary.")
+            .or_else(|| host.strip_prefix("ptb."))
+            .unwrap_or(host);
+
+        if !MESSAGE_HOSTS.contains(&host) {
+            return None;
+        }
+
+        let mut parts = path.strip_prefix("channels/")?.split('/');
+        let guild = match parts.next()? {
+            "@me" => None,
+            g => Some(g.parse().ok()?),
+        };
+        let channel = parts.next()?.parse().ok()?;
+        let message = parts.next()?.parse().ok()?;
+
+        parts.next().is_none().then_some(Self {
+            guild,
+            channel,
+            message,
+        })
+    }
+}
+
+/// A mention, custom emoji or message link parsed from a string option
+///
+/// Mentions of users, roles and channels include the corresponding entity if
+/// Discord provided it in the interaction's resolved data.
+#[derive(Debug, Clone)]
+pub enum Mention<'a> {
+    /// A user mention, i.e. `<@id>` or `<@!id>`
+    User(UserId, Option<&'a User>),
+    /// A role mention, i.e. `<@&id>`
+    Role(RoleId, Option<&'a Role>),
+    /// A channel mention, i.e. `<#id>`
+    Channel(ChannelId, Option<&'a PartialChannel>),
+    /// A custom emoji, i.e. `<:name:id>` or `<a:name:id>`
+    Emoji(EmojiIdentifier),
+    /// A link to a message
+    Message(MessageLink),
+}
+
+impl<'a> Mention<'a> {
+    /// Parse a mention, custom emoji or message link from the given string,
+    /// looking up any mentioned entity in `resolved`
+    ///
+    /// Surrounding whitespace is ignored, but any other text causes this
+    /// function to return `None`.
+    #[must_use]
+    pub fn parse(s: &str, resolved: &'a CommandDataResolved) -> Option<Self> {
+        let s = s.trim();
+
+        if s.starts_with('<') && s.ends_with('>') {
+            if let Some(emoji) = utils::parse_emoji(s) {
+                return Some(Self::Emoji(emoji));
+            }
+
+            if let Some(id) = utils::parse_role_mention(s) {
+                return Some(Self::Role(id, resolved.roles.get(&id)));
+            }
+
+            if let Some(id) = utils::parse_user_mention(s) {
+                return Some(Self::User(id, resolved.users.get(&id)));
+            }
+
+            if let Some(id) = utils::parse_channel_mention(s) {
+                return Some(Self::Channel(id, resolved.channels.get(&id)));
+            }
+        }
+
+        MessageLink::parse(s).map(Self::Message)
+    }
+
+    /// Return the mentioned user, if this is a user mention
+    #[inline]
+    #[must_use]
+    pub fn user(self) -> Option<(UserId, Option<&'a User>)> {
+        match self {
+            Self::User(id, u) => Some((id, u)),
+            _ => None,
+        }
+    }
+
+    /// Return the mentioned role, if this is a role mention
+    #[inline]
+    #[must_use]
+    pub fn role(self) -> Option<(RoleId, Option<&'a Role>)> {
+        match self {
+            Self::Role(id, r) => Some((id, r)),
+            _ => None,
+        }
+    }
+
+    /// Return the mentioned channel, if this is a channel mention
+    #[inline]
+    #[must_use]
+    pub fn channel(self) -> Option<(ChannelId, Option<&'a PartialChannel>)> {
+        match self {
+            Self::Channel(id, c) => Some((id, c)),
+            _ => None,
+        }
+    }
+
+    /// Return the custom emoji, if this is one
+    #[inline]
+    #[must_use]
+    pub fn emoji(self) -> Option<EmojiIdentifier> {
+        match self {
+            Self::Emoji(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Return the message link, if this is one
+    #[inline]
+    #[must_use]
+    pub fn message(self) -> Option<MessageLink> {
+        match self {
+            Self::Message(m) => Some(m),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serenity::model::{
+        application::CommandDataResolved,
+        id::{ChannelId, EmojiId, GuildId, MessageId, RoleId, UserId},
+    };
+
+    use super::{Mention, MessageLink};
+
+    #[test]
+    fn mentions() {
+        let res = CommandDataResolved::default();
+        let parse = |s| Mention::parse(s, &res);
+
+        assert!(matches!(parse("<@123>"), Some(Mention::User(id, None)) if id == UserId::new(123)));
+        assert!(
+            matches!(parse(" <@!123> "), Some(Mention::User(id, None)) if id == UserId::new(123))
+        );
+        assert!(matches!(parse("<@&45>"), Some(Mention::Role(id, None)) if id == RoleId::new(45)));
+        assert!(
+            matches!(parse("<#67>"), Some(Mention::Channel(id, None)) if id == ChannelId::new(67))
+        );
+
+        let emoji = parse("<a:party:89>").and_then(Mention::emoji).unwrap();
+        assert!(emoji.animated);
+        assert_eq!(emoji.id, EmojiId::new(89));
+        assert_eq!(emoji.name, "party");
+
+        for bad in [
+            "",
+            "@123",
+            "<@123",
+            "<@abc>",
+            "<@123> hi",
+            "<@&>",
+            ":party:",
+        ] {
+            assert!(parse(bad).is_none(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn message_links() {
+        let link = MessageLink {
+            guild: Some(GuildId::new(1)),
+            channel: ChannelId::new(2),
+            message: MessageId::new(3),
+        };
+
+        assert_eq!(
+            MessageLink::parse("https://discord.com/channels/1/2/3"),
+            Some(link)
+        );
+        assert_eq!(
+            MessageLink::parse("<https://canary.discordapp.com/channels/1/2/3>"),
+            Some(link)
+        );
+        assert_eq!(
+            MessageLink::parse("https://discord.com/channels/@me/2/3"),
+            Some(MessageLink {
+                guild: None,
+                ..link
+            })
+        );
+
+        for bad in [
+            "https://discord.com/channels/1/2",
+            "https://discord.com/channels/1/2/3/4",
+            "https://example.com/channels/1/2/3",
+            "https://discord.com/channels/1/x/3",
+            "discord.com/channels/1/2/3",
+        ] {
+            assert_eq!(MessageLink::parse(bad), None, "{bad:?}");
+        }
+    }
+}
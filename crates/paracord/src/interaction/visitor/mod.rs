@@ -1,9 +1,11 @@
 //! Types for extracting data from interaction invocations in a type-safe manner
 
 mod command;
+mod mention;
+mod modal;
 
 mod private {
-    use serenity::model::{application, guild, id, user};
+    use serenity::model::{application, guild, id, monetization, permissions, user};
 
     pub trait Interaction {
         type Data;
@@ -12,9 +14,23 @@ mod private {
 
         fn guild_id(&self) -> Option<id::GuildId>;
 
+        fn channel_id(&self) -> id::ChannelId;
+
         fn member(&self) -> Option<&guild::Member>;
 
         fn user(&self) -> &user::User;
+
+        fn context(&self) -> Option<application::InteractionContext>;
+
+        fn integration_owners(&self) -> &[application::AuthorizingIntegrationOwner];
+
+        fn entitlements(&self) -> &[monetization::Entitlement];
+
+        fn app_permissions(&self) -> Option<permissions::Permissions>;
+
+        fn locale(&self) -> &str;
+
+        fn guild_locale(&self) -> Option<&str>;
     }
 
     impl Interaction for application::CommandInteraction {
@@ -26,11 +42,34 @@ mod private {
         #[inline]
         fn guild_id(&self) -> Option<id::GuildId> { self.guild_id }
 
+        #[inline]
+        fn channel_id(&self) -> id::ChannelId { self.channel_id }
+
         #[inline]
         fn member(&self) -> Option<&guild::Member> { self.member.as_deref() }
 
         #[inline]
         fn user(&self) -> &user::User { &self.user }
+
+        #[inline]
+        fn context(&self) -> Option<application::InteractionContext> { self.context }
+
+        #[inline]
+        fn integration_owners(&self) -> &[application::AuthorizingIntegrationOwner] {
+            &self.authorizing_integration_owners.0
+        }
+
+        #[inline]
+        fn entitlements(&self) -> &[monetization::Entitlement] { &self.entitlements }
+
+        #[inline]
+        fn app_permissions(&self) -> Option<permissions::Permissions> { self.app_permissions }
+
+        #[inline]
+        fn locale(&self) -> &str { &self.locale }
+
+        #[inline]
+        fn guild_locale(&self) -> Option<&str> { self.guild_locale.as_deref() }
     }
 
     impl Interaction for application::ComponentInteraction {
@@ -42,11 +81,34 @@ mod private {
         #[inline]
         fn guild_id(&self) -> Option<id::GuildId> { self.guild_id }
 
+        #[inline]
+        fn channel_id(&self) -> id::ChannelId { self.channel_id }
+
         #[inline]
         fn member(&self) -> Option<&guild::Member> { self.member.as_ref() }
 
         #[inline]
         fn user(&self) -> &user::User { &self.user }
+
+        #[inline]
+        fn context(&self) -> Option<application::InteractionContext> { self.context }
+
+        #[inline]
+        fn integration_owners(&self) -> &[application::AuthorizingIntegrationOwner] {
+            &self.authorizing_integration_owners.0
+        }
+
+        #[inline]
+        fn entitlements(&self) -> &[monetization::Entitlement] { &self.entitlements }
+
+        #[inline]
+        fn app_permissions(&self) -> Option<permissions::Permissions> { self.app_permissions }
+
+        #[inline]
+        fn locale(&self) -> &str { &self.locale }
+
+        #[inline]
+        fn guild_locale(&self) -> Option<&str> { self.guild_locale.as_deref() }
     }
 
     impl Interaction for application::ModalInteraction {
@@ -58,18 +120,52 @@ mod private {
         #[inline]
         fn guild_id(&self) -> Option<id::GuildId> { self.guild_id }
 
+        #[inline]
+        fn channel_id(&self) -> id::ChannelId { self.channel_id }
+
         #[inline]
         fn member(&self) -> Option<&guild::Member> { self.member.as_ref() }
 
         #[inline]
         fn user(&self) -> &user::User { &self.user }
+
+        // TODO: serenity does not yet deserialize these for modal submissions
+        #[inline]
+        fn context(&self) -> Option<application::InteractionContext> { None }
+
+        #[inline]
+        fn integration_owners(&self) -> &[application::AuthorizingIntegrationOwner] { &[] }
+
+        #[inline]
+        fn entitlements(&self) -> &[monetization::Entitlement] { &self.entitlements }
+
+        #[inline]
+        fn app_permissions(&self) -> Option<permissions::Permissions> { self.app_permissions }
+
+        #[inline]
+        fn locale(&self) -> &str { &self.locale }
+
+        #[inline]
+        fn guild_locale(&self) -> Option<&str> { self.guild_locale.as_deref() }
     }
 }
 
 use std::fmt;
 
 pub use command::*;
-use serenity::model::{guild::Member, id::GuildId, user::User};
+pub use mention::*;
+pub use modal::*;
+use serenity::model::{
+    application::{AuthorizingIntegrationOwner, InteractionContext},
+    guild::Member,
+    id::{ChannelId, GuildId, SkuId, UserId},
+    monetization::Entitlement,
+    permissions::Permissions,
+    user::User,
+    Timestamp,
+};
+
+use super::locale::Locale;
 
 /// An error caused by performing an invalid extraction
 #[derive(Debug, thiserror::Error)]
@@ -89,6 +185,9 @@ pub enum Error {
     /// command
     #[error("Attempted to read target message for a non-message command")]
     NotMessage,
+    /// A poll target extractor was used on a message with no poll
+    #[error("Attempted to read poll from a message without one")]
+    NotPoll,
 
     // Subcommand visitor errors
     /// The subcommand extractor was used on a command with no subcommands
@@ -107,10 +206,22 @@ pub enum Error {
     /// correct type
     #[error("Type mismatch in value of command option {0:?} - expected {1}, found {2:?}")]
     BadOptionValueType(String, &'static str, command::OptionValueType),
+    /// A string argument could not be parsed as the kind of mention or link
+    /// expected
+    #[error("Command option {0:?} is not {1}")]
+    BadMention(String, &'static str),
     /// A trailing argument was left in the visitor after the handler completed
     #[error("Trailing arguments: {0:?}")]
     Trailing(Vec<String>),
 
+    // Modal visitor errors
+    /// A textbox was expected but not present in a modal submission
+    #[error("Modal field {0:?} missing from submission")]
+    MissingField(String),
+    /// One or more textboxes in a modal submission failed validation
+    #[error("Invalid modal input: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    Invalid(Vec<modal::FieldError>),
+
     // Guild visitor errors
     /// The guild ID extractor was used on an interaction invoked outside of a
     /// guild
@@ -119,6 +230,18 @@ pub enum Error {
     /// The DM-only extractor was used on an interaction invoked within a guild
     #[error("DM-only interaction run inside guild")]
     DmRequired,
+
+    // Context visitor errors
+    /// An interaction requiring the bot user was invoked through a user
+    /// install somewhere the bot is not present
+    #[error("Interaction requiring bot user run through user install")]
+    BotRequired,
+
+    // Entitlement visitor errors
+    /// An interaction requiring an entitlement to the given SKU was invoked
+    /// by a user or guild without one
+    #[error("Interaction requiring SKU {0} run without an active entitlement")]
+    PremiumRequired(SkuId),
 }
 
 trait Describe {
@@ -156,6 +279,153 @@ impl<'a, I: private::Interaction> BasicVisitor<'a, I> {
     #[inline]
     #[must_use]
     pub fn user(&self) -> &'a User { self.int.user() }
+
+    /// Visit the ID of the channel this interaction was invoked in
+    #[inline]
+    #[must_use]
+    pub fn channel_id(&self) -> ChannelId { self.int.channel_id() }
+
+    /// Visit the installation and invocation context of this interaction
+    #[inline]
+    #[must_use]
+    pub fn context(&self) -> ContextVisitor<'a> {
+        ContextVisitor {
+            kind: self.int.context(),
+            owners: self.int.integration_owners(),
+        }
+    }
+
+    /// Visit the entitlements of the invoking user and guild for this
+    /// interaction
+    #[inline]
+    #[must_use]
+    pub fn entitlements(&self) -> EntitlementVisitor<'a> {
+        EntitlementVisitor(self.int.entitlements())
+    }
+
+    /// Visit the permissions the app has in the channel this interaction was
+    /// invoked in, if Discord provided them
+    #[inline]
+    #[must_use]
+    pub fn app_permissions(&self) -> Option<Permissions> { self.int.app_permissions() }
+
+    /// Visit the locale of the user who invoked this interaction
+    #[inline]
+    #[must_use]
+    pub fn locale(&self) -> Locale { Locale::new(self.int.locale()) }
+
+    /// Visit the preferred locale of the guild this interaction was invoked
+    /// in, if any
+    ///
+    /// Discord only sends this for guilds with the Community feature enabled.
+    #[inline]
+    #[must_use]
+    pub fn guild_locale(&self) -> Option<Locale> { self.int.guild_locale().map(Locale::new) }
+}
+
+/// Visitor for the context an interaction was invoked in
+///
+/// Interactions for commands available through user installs may be invoked
+/// in guilds and DMs the bot user itself has no access to, in which case any
+/// behavior relying on the bot user (such as reading the cache or sending
+/// non-response messages) will fail.
+#[derive(Debug, Clone, Copy)]
+pub struct ContextVisitor<'a> {
+    kind: Option<InteractionContext>,
+    owners: &'a [AuthorizingIntegrationOwner],
+}
+
+impl ContextVisitor<'_> {
+    /// Get the type of channel the interaction was invoked in, if known
+    #[inline]
+    #[must_use]
+    pub fn kind(self) -> Option<InteractionContext> { self.kind }
+
+    /// Get the ID of the user whose install of the app authorized this
+    /// interaction, if any
+    #[inline]
+    #[must_use]
+    pub fn user_install(self) -> Option<UserId> {
+        self.owners.iter().find_map(|o| match *o {
+            AuthorizingIntegrationOwner::UserInstall(u) => Some(u),
+            _ => None,
+        })
+    }
+
+    /// Get the ID of the guild whose install of the app authorized this
+    /// interaction, if any
+    #[inline]
+    #[must_use]
+    pub fn guild_install(self) -> Option<GuildId> {
+        self.owners.iter().find_map(|o| match *o {
+            AuthorizingIntegrationOwner::GuildInstall(g) => g,
+            _ => None,
+        })
+    }
+
+    /// Verify the bot user is present where the interaction was invoked
+    ///
+    /// Interactions with no context information (e.g. from older clients) are
+    /// assumed to have been invoked through a guild install.
+    ///
+    /// # Errors
+    /// This method returns an error if the interaction was invoked in a guild
+    /// without the app installed or in a DM other than the bot user's.
+    pub fn require_bot(self) -> Result<()> {
+        match self.kind {
+            Some(InteractionContext::PrivateChannel) => Err(Error::BotRequired),
+            Some(InteractionContext::Guild) if self.guild_install().is_none() => {
+                Err(Error::BotRequired)
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Visitor for the entitlements attached to an interaction
+///
+/// For monetized apps, Discord sends the entitlements of both the invoking
+/// user and the guild the interaction was invoked in, including ones which
+/// have expired or been deleted.
+#[derive(Debug, Clone, Copy)]
+#[repr(transparent)]
+pub struct EntitlementVisitor<'a>(&'a [Entitlement]);
+
+impl<'a> EntitlementVisitor<'a> {
+    /// Get every entitlement attached to the interaction, including inactive
+    /// ones
+    #[inline]
+    #[must_use]
+    pub fn all(self) -> &'a [Entitlement] { self.0 }
+
+    /// Iterate over the entitlements active at the given time
+    ///
+    /// Entitlements with no start or end time (e.g. test entitlements) are
+    /// treated as unbounded.
+    pub fn active_at(self, now: Timestamp) -> impl Iterator<Item = &'a Entitlement> {
+        self.0.iter().filter(move |e| {
+            !e.deleted
+                && e.starts_at.map_or(true, |s| s <= now)
+                && e.ends_at.map_or(true, |e| now < e)
+        })
+    }
+
+    /// Find a currently-active entitlement to the given SKU, returning `None`
+    /// if there is none
+    #[must_use]
+    pub fn optional(self, sku: SkuId) -> Option<&'a Entitlement> {
+        self.active_at(Timestamp::now()).find(|e| e.sku_id == sku)
+    }
+
+    /// Find a currently-active entitlement to the given SKU
+    ///
+    /// # Errors
+    /// This method returns an error if neither the invoking user nor guild
+    /// has an active entitlement to the SKU.
+    #[inline]
+    pub fn required(self, sku: SkuId) -> Result<&'a Entitlement> {
+        self.optional(sku).ok_or(Error::PremiumRequired(sku))
+    }
 }
 
 /// Visitor for the source guild of an interaction
@@ -187,3 +457,57 @@ impl<'a> GuildVisitor<'a> {
         self.0.is_none().then_some(()).ok_or(Error::DmRequired)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use serenity::model::{
+        id::{ApplicationId, EntitlementId, SkuId},
+        monetization::{Entitlement, EntitlementKind},
+        Timestamp,
+    };
+
+    use super::{EntitlementVisitor, Error};
+
+    fn entitlement(sku: u64, deleted: bool, starts: Option<i64>, ends: Option<i64>) -> Entitlement {
+        let ts = |s| Timestamp::from_unix_timestamp(s).unwrap();
+        Entitlement {
+            id: EntitlementId::new(sku),
+            sku_id: SkuId::new(sku),
+            application_id: ApplicationId::new(1),
+            user_id: None,
+            kind: EntitlementKind::ApplicationSubscription,
+            deleted,
+            starts_at: starts.map(ts),
+            ends_at: ends.map(ts),
+            guild_id: None,
+        }
+    }
+
+    #[test]
+    fn active() {
+        let ents = [
+            entitlement(1, false, Some(100), Some(200)),
+            entitlement(2, true, None, None),
+            entitlement(3, false, None, None),
+            entitlement(4, false, Some(300), None),
+        ];
+        let visitor = EntitlementVisitor(&ents);
+        let active = |t| {
+            visitor
+                .active_at(Timestamp::from_unix_timestamp(t).unwrap())
+                .map(|e| e.sku_id.get())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(active(50), [3]);
+        assert_eq!(active(100), [1, 3]);
+        assert_eq!(active(200), [3]);
+        assert_eq!(active(300), [3, 4]);
+
+        assert!(visitor.optional(SkuId::new(4)).is_some());
+        assert!(matches!(
+            visitor.required(SkuId::new(2)),
+            Err(Error::PremiumRequired(s)) if s.get() == 2
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+use std::{fmt, marker::PhantomData};
+
+use qcore::{build_range::BuildRange, builder};
+use serenity::model::application::{ActionRowComponent, ModalInteraction};
+use shrec::{
+    dfa::Dfa,
+    re::{
+        run::{Anchors, Matcher},
+        Regex,
+    },
+};
+
+use super::{
+    super::{
+        response::{id, Localized},
+        rpc::ComponentId,
+    },
+    BasicVisitor, Error, Result,
+};
+
+/// A single textbox in a modal submission that failed validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// The label of the textbox, localized for the submitting user
+    pub label: String,
+    /// A human-readable description of the problem with the input
+    pub reason: String,
+}
+
+impl fmt::Display for FieldError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.label, self.reason)
+    }
+}
+
+/// Validation rules for a single textbox in a modal, checked on submit
+///
+/// Rules are keyed on the same ID payload used to construct the textbox, and
+/// should be constructed alongside the modal itself so the two stay in sync.
+#[derive(Debug)]
+pub struct TextRule<I> {
+    id: std::result::Result<id::Id<'static>, id::Error>,
+    label: Localized,
+    min_len: Option<u16>,
+    max_len: Option<u16>,
+    len_message: Option<Localized>,
+    pattern: Option<(Dfa<char, u64, (), ()>, Localized)>,
+    rpc_id: PhantomData<fn(I)>,
+}
+
+impl<I: ComponentId> TextRule<I> {
+    /// Construct a new rule for the textbox with the given ID payload and
+    /// label
+    #[inline]
+    pub fn new(payload: I::Payload, label: impl Into<Localized>) -> Self {
+        Self {
+            id: id::write(&I::from_parts(payload)),
+            label: label.into(),
+            min_len: None,
+            max_len: None,
+            len_message: None,
+            pattern: None,
+            rpc_id: PhantomData,
+        }
+    }
+}
+
+#[builder(trait_name = TextRuleExt)]
+/// Helper methods for mutating [`TextRule`]
+impl<I> TextRule<I> {
+    /// Set the valid length range for the textbox, in characters
+    pub fn len(&mut self, len: impl BuildRange<u16>) {
+        let (min_len, max_len) = len.build_range().into_inner();
+        self.min_len = min_len;
+        self.max_len = max_len;
+    }
+
+    /// Set the message shown to the user if their input is too short or too
+    /// long
+    ///
+    /// If no message is set, an English message describing the violated bound
+    /// is shown regardless of the user's locale.
+    pub fn len_message(&mut self, message: impl Into<Localized>) {
+        self.len_message = Some(message.into());
+    }
+
+    /// Require the entire input to match the given regular expression
+    ///
+    /// The message is shown to the user as-is if their input does not match,
+    /// e.g. "Must be a hex color"
+    pub fn pattern<L: IntoIterator<Item = char> + Clone>(
+        &mut self,
+        re: Regex<L>,
+        message: impl Into<Localized>,
+    ) {
+        let mut nfa = re.compile();
+        nfa.simplify();
+        let (dfa, _) = nfa.compile().copied().atomize_nodes::<u64>();
+        self.pattern = Some((dfa.map_token(|_| ()), message.into()));
+    }
+}
+
+impl<I> TextRule<I> {
+    fn check(&self, value: &str, locale: &str) -> std::result::Result<(), String> {
+        let len = value.chars().count();
+
+        let len_err = |default: String| {
+            self.len_message
+                .as_ref()
+                .map_or(default, |m| m.get(locale).to_owned())
+        };
+
+        if let Some(min) = self.min_len.filter(|m| len < usize::from(*m)) {
+            return Err(len_err(format!("Must be at least {min} characters long")));
+        }
+
+        if let Some(max) = self.max_len.filter(|m| len > usize::from(*m)) {
+            return Err(len_err(format!("Must be at most {max} characters long")));
+        }
+
+        if let Some((ref dfa, ref message)) = self.pattern {
+            let mut matcher = Matcher::new(dfa, Anchors {
+                start: true,
+                end: true,
+            });
+            matcher.feed(value.chars());
+
+            if matcher.finish().is_none() {
+                return Err(message.get(locale).to_owned());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> BasicVisitor<'a, ModalInteraction> {
+    fn text_value(&self, id: &id::Id<'_>) -> Option<&'a str> {
+        let id = id.to_string();
+
+        self.int
+            .data
+            .components
+            .iter()
+            .flat_map(|r| &r.components)
+            .find_map(|c| match c {
+                ActionRowComponent::InputText(t) if t.custom_id == id => {
+                    Some(t.value.as_deref().unwrap_or_default())
+                },
+                _ => None,
+            })
+    }
+
+    /// Extract the values of the given textboxes, checking each against its
+    /// rules
+    ///
+    /// Discord does not allow responding to a modal submission with another
+    /// modal, so when validation fails the registry instead responds with a
+    /// message listing the rejected fields and asking the user to try again.
+    ///
+    /// # Errors
+    /// This method returns an error if any textbox is missing from the
+    /// submission or any value fails validation.
+    pub fn visit_text<I, const N: usize>(&self, rules: &[TextRule<I>; N]) -> Result<[&'a str; N]> {
+        let locale = self.int.locale.as_str();
+        let mut errors = vec![];
+
+        let values = rules.each_ref().map(|rule| {
+            let label = rule.label.get(locale);
+            let Some(value) = rule.id.as_ref().ok().and_then(|i| self.text_value(i)) else {
+                errors.push(Err(Error::MissingField(label.into())));
+                return "";
+            };
+
+            if let Err(reason) = rule.check(value, locale) {
+                errors.push(Ok(FieldError {
+                    label: label.into(),
+                    reason,
+                }));
+            }
+
+            value
+        });
+
+        let errors = errors.into_iter().collect::<Result<Vec<_>>>()?;
+
+        if errors.is_empty() {
+            Ok(values)
+        } else {
+            Err(Error::Invalid(errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::marker::PhantomData;
+
+    use shrec::re::Regex;
+
+    use super::{TextRule, TextRuleExt};
+    use crate::interaction::response::{id, prelude::*, Localized};
+
+    fn rule() -> TextRule<()> {
+        TextRule {
+            id: id::write(&0_u32),
+            label: "Color".into(),
+            min_len: None,
+            max_len: None,
+            len_message: None,
+            pattern: None,
+            rpc_id: PhantomData,
+        }
+    }
+
+    fn hex() -> Regex<Vec<char>> {
+        Regex::Cat(vec![
+            Regex::Lit(vec!['#']),
+            Regex::Star(
+                Regex::Alt(
+                    "0123456789abcdef"
+                        .chars()
+                        .map(|c| Regex::Lit(vec![c]))
+                        .collect(),
+                )
+                .into(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn check_len_and_pattern() {
+        let rule = rule().len(2..=7).pattern(
+            hex(),
+            Localized::new("Must be a hex color").locale("fr", "Doit être une couleur hex"),
+        );
+
+        assert_eq!(rule.check("#1a2b3c", "en-US"), Ok(()));
+        assert!(rule.check("#", "en-US").unwrap_err().contains("at least 2"));
+        assert!(rule
+            .check("#1a2b3c4", "en-US")
+            .unwrap_err()
+            .contains("at most 7"));
+        assert_eq!(
+            rule.check("red", "en-US"),
+            Err("Must be a hex color".into())
+        );
+        assert_eq!(
+            rule.check("red", "fr"),
+            Err("Doit être une couleur hex".into())
+        );
+    }
+
+    #[test]
+    fn check_len_message() {
+        let rule = rule().len(2..=7).len_message(
+            Localized::new("Must be 2 to 7 characters long")
+                .locale("fr", "Doit contenir 2 à 7 caractères"),
+        );
+
+        assert_eq!(rule.check("#1a", "fr"), Ok(()));
+        assert_eq!(
+            rule.check("#", "fr"),
+            Err("Doit contenir 2 à 7 caractères".into())
+        );
+        assert_eq!(
+            rule.check("#1a2b3c4", "en-US"),
+            Err("Must be 2 to 7 characters long".into())
+        );
+    }
+}
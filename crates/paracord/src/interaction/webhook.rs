@@ -0,0 +1,581 @@
+//! Support for receiving interactions as signed HTTP requests instead of over
+//! the gateway
+//!
+//! Discord posts each interaction to the application's configured endpoint
+//! URL, signed with the application's ed25519 key. [`Endpoint`] verifies the
+//! signature, answers pings, and feeds everything else through the same
+//! [`Registry`] dispatch used for gateway events, so handlers need no changes.
+//! Initial responses are still submitted through the REST callback route, so
+//! the HTTP request is acknowledged with `202 Accepted` as soon as the
+//! handler has been spawned.
+//!
+//! Handlers receive a [`Context`], which a client without a gateway connection
+//! can build from its HTTP client and cache with [`Context::new`] and pass to
+//! [`Endpoint::set_context`].
+
+use std::{convert::Infallible, sync::Arc};
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::{
+    body::{Body, Bytes},
+    header::{HeaderValue, CONTENT_TYPE},
+    server::conn::http1,
+    service::service_fn,
+    HeaderMap, Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serenity::model::application::Interaction;
+use tokio::{net::TcpListener, sync::RwLock};
+use tracing::Instrument;
+
+use super::{rpc::Schema, Registry};
+use crate::client::Context;
+
+/// Header carrying the hex-encoded request signature
+pub const SIGNATURE_HEADER: &str = "x-signature-ed25519";
+/// Header carrying the timestamp prepended to the body before signing
+pub const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+/// The largest request body accepted by [`respond`]
+pub const MAX_BODY_SIZE: usize = 1 << 20;
+
+/// An error arising from an incoming interaction request
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    /// The configured public key was not a valid ed25519 key
+    #[error("Invalid public key")]
+    InvalidKey,
+    /// The request did not use the `POST` method
+    #[error("Method not allowed")]
+    Method,
+    /// The request body exceeded [`MAX_BODY_SIZE`]
+    #[error("Request body too large")]
+    TooLarge,
+    /// The request body could not be read
+    #[error("Error reading request body")]
+    Body,
+    /// A required signature header was missing or not valid UTF-8
+    #[error("Missing or invalid {0} header")]
+    MissingHeader(&'static str),
+    /// The request signature did not match its body
+    #[error("Invalid request signature")]
+    BadSignature,
+    /// The request body could not be parsed as an interaction
+    #[error("Invalid interaction payload")]
+    Json(#[from] serde_json::Error),
+    /// No client context has been provided yet
+    #[error("Endpoint has no client context")]
+    NoContext,
+}
+
+impl WebhookError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::InvalidKey | Self::NoContext => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Method => StatusCode::METHOD_NOT_ALLOWED,
+            Self::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::MissingHeader(_) | Self::BadSignature => StatusCode::UNAUTHORIZED,
+            Self::Body | Self::Json(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn into_response(self) -> Response<Full<Bytes>> {
+        let mut res = Response::new(Full::new(self.to_string().into()));
+        *res.status_mut() = self.status();
+        res
+    }
+}
+
+/// Verifier for the signatures Discord attaches to interaction requests
+#[derive(Debug, Clone, Copy)]
+pub struct Verifier(VerifyingKey);
+
+impl Verifier {
+    /// Construct a new verifier from the hex-encoded public key shown in the
+    /// application's developer portal
+    ///
+    /// # Errors
+    /// This method returns an error if the key is not a valid hex-encoded
+    /// ed25519 public key.
+    pub fn new(public_key: &str) -> Result<Self, WebhookError> {
+        let mut bytes = [0_u8; 32];
+        hex::decode_to_slice(public_key.trim(), &mut bytes)
+            .map_err(|_| WebhookError::InvalidKey)?;
+
+        VerifyingKey::from_bytes(&bytes)
+            .map(Self)
+            .map_err(|_| WebhookError::InvalidKey)
+    }
+
+    /// Check the signature headers of a request against its body
+    ///
+    /// # Errors
+    /// This method returns an error if either header is missing or the
+    /// signature does not match.
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), WebhookError> {
+        let header = |name: &'static str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .ok_or(WebhookError::MissingHeader(name))
+        };
+        let sig = header(SIGNATURE_HEADER)?;
+        let timestamp = header(TIMESTAMP_HEADER)?;
+
+        let mut bytes = [0_u8; 64];
+        hex::decode_to_slice(sig, &mut bytes).map_err(|_| WebhookError::BadSignature)?;
+        let sig = Signature::from_bytes(&bytes);
+
+        let mut msg = Vec::with_capacity(timestamp.len() + body.len());
+        msg.extend_from_slice(timestamp.as_bytes());
+        msg.extend_from_slice(body);
+
+        self.0
+            .verify_strict(&msg, &sig)
+            .map_err(|_| WebhookError::BadSignature)
+    }
+}
+
+/// An HTTP interactions endpoint dispatching to a [`Registry`]
+#[derive(Debug)]
+pub struct Endpoint<S: Schema> {
+    registry: Arc<Registry<S>>,
+    verifier: Verifier,
+    context: RwLock<Option<Context>>,
+}
+
+impl<S: Schema + Send + Sync + 'static> Endpoint<S> {
+    /// Construct a new endpoint for the given registry
+    #[inline]
+    #[must_use]
+    pub fn new(registry: Arc<Registry<S>>, verifier: Verifier) -> Self {
+        Self {
+            registry,
+            verifier,
+            context: RwLock::new(None),
+        }
+    }
+
+    /// Provide the client context used to dispatch interactions
+    ///
+    /// Until this is called, only pings are answered and all other
+    /// interactions are rejected with `503 Service Unavailable`.
+    pub async fn set_context(&self, ctx: Context) { *self.context.write().await = Some(ctx); }
+
+    /// Verify and parse a single interaction request, spawning its handler in
+    /// the background
+    ///
+    /// # Errors
+    /// This method returns an error if the request fails verification, cannot
+    /// be parsed, or arrives before a context has been set.
+    pub async fn handle(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Response<Full<Bytes>>, WebhookError> {
+        self.verifier.verify(headers, body)?;
+
+        let int: Interaction = serde_json::from_slice(body)?;
+
+        if let Interaction::Ping(_) = int {
+            let mut res = Response::new(Full::new(
+                serde_json::json!({ "type": 1 }).to_string().into(),
+            ));
+            res.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            return Ok(res);
+        }
+
+        let ctx = self
+            .context
+            .read()
+            .await
+            .clone()
+            .ok_or(WebhookError::NoContext)?;
+        let registry = Arc::clone(&self.registry);
+
+        tokio::spawn(
+            async move {
+                match int {
+                    Interaction::Command(c) => registry.handle_command(&ctx, c).await,
+                    Interaction::Component(c) => registry.handle_component(&ctx, c).await,
+                    Interaction::Autocomplete(a) => registry.handle_autocomplete(&ctx, a).await,
+                    Interaction::Modal(m) => registry.handle_modal(&ctx, m).await,
+                    i => tracing::warn!(interaction = ?i, "Unknown interaction"),
+                }
+            }
+            .in_current_span(),
+        );
+
+        let mut res = Response::new(Full::default());
+        *res.status_mut() = StatusCode::ACCEPTED;
+        Ok(res)
+    }
+}
+
+/// Respond to a single HTTP request for the given endpoint
+///
+/// Only `POST` requests are accepted, and bodies larger than
+/// [`MAX_BODY_SIZE`] are rejected before being verified.  The request path is
+/// not checked.
+pub async fn respond<S: Schema + Send + Sync + 'static, B: Body>(
+    endpoint: &Endpoint<S>,
+    request: Request<B>,
+) -> Response<Full<Bytes>>
+where
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let (parts, body) = request.into_parts();
+
+    let res = if parts.method == Method::POST {
+        match Limited::new(body, MAX_BODY_SIZE).collect().await {
+            Ok(body) => endpoint.handle(&parts.headers, &body.to_bytes()).await,
+            Err(e) if e.is::<LengthLimitError>() => Err(WebhookError::TooLarge),
+            Err(e) => {
+                tracing::debug!(%e, "Error reading interaction request");
+                Err(WebhookError::Body)
+            },
+        }
+    } else {
+        Err(WebhookError::Method)
+    };
+
+    res.unwrap_or_else(|e| {
+        tracing::debug!(%e, "Rejected interaction request");
+        e.into_response()
+    })
+}
+
+/// Serve interaction requests for the given endpoint on a listener, spawning
+/// a task for each connection
+///
+/// This function only returns if the task running it is cancelled.
+pub async fn serve<S: Schema + Send + Sync + 'static>(
+    endpoint: Arc<Endpoint<S>>,
+    listener: TcpListener,
+) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                tracing::warn!(%err, "Error accepting interaction connection");
+                continue;
+            },
+        };
+
+        let endpoint = Arc::clone(&endpoint);
+        tokio::spawn(async move {
+            let svc = service_fn(|req| {
+                let endpoint = Arc::clone(&endpoint);
+                async move { Ok::<_, Infallible>(respond(&endpoint, req).await) }
+            });
+
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), svc)
+                .await
+            {
+                tracing::debug!(%err, "Error serving interaction request");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use chrono::{DateTime, Utc};
+    use ed25519_dalek::{Signer, SigningKey};
+    use http_body_util::{BodyExt, Full};
+    use hyper::{body::Bytes, HeaderMap, Method, Request, Response, StatusCode};
+    use serenity::{
+        cache::Cache,
+        http::Http,
+        model::application::{ComponentInteraction, ModalInteraction},
+        prelude::TypeMap,
+    };
+    use tokio::sync::RwLock;
+
+    use super::{
+        respond, Endpoint, Verifier, WebhookError, MAX_BODY_SIZE, SIGNATURE_HEADER,
+        TIMESTAMP_HEADER,
+    };
+    use crate::{
+        client::Context,
+        interaction::{
+            handler::Handlers,
+            response::ModalSource,
+            rpc::{self, ComponentId, ModalId},
+            Registry,
+        },
+    };
+
+    #[derive(Debug)]
+    enum Schema {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Id {
+        #[prost(uint32, tag = "1")]
+        payload: u32,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct ComponentKey;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct ModalKey;
+
+    impl From<&u32> for ComponentKey {
+        fn from(_: &u32) -> Self { Self }
+    }
+
+    impl From<&u32> for ModalKey {
+        fn from(_: &u32) -> Self { Self }
+    }
+
+    impl rpc::Key for ComponentKey {
+        type Interaction = ComponentInteraction;
+        type Payload = u32;
+    }
+
+    impl rpc::Key for ModalKey {
+        type Interaction = ModalInteraction;
+        type Payload = u32;
+    }
+
+    impl ComponentId for Id {
+        type Key = ComponentKey;
+        type Payload = u32;
+
+        fn from_parts(payload: u32) -> Self { Self { payload } }
+
+        fn try_into_parts(self) -> Option<u32> { Some(self.payload) }
+
+        fn expires_at(&self) -> Option<DateTime<Utc>> { None }
+
+        fn set_expires_at(&mut self, _: Option<DateTime<Utc>>) {}
+    }
+
+    impl ModalId for Id {
+        type Key = ModalKey;
+        type Payload = u32;
+
+        fn from_parts(_: ModalSource, payload: u32) -> Self { Self { payload } }
+
+        fn try_into_parts(self) -> Option<(ModalSource, u32)> {
+            Some((ModalSource::Command, self.payload))
+        }
+    }
+
+    impl rpc::Schema for Schema {
+        type Component = Id;
+        type ComponentKey = ComponentKey;
+        type ComponentPayload = u32;
+        type Modal = Id;
+        type ModalKey = ModalKey;
+        type ModalPayload = u32;
+    }
+
+    const PING: &[u8] = br#"{"type":1,"id":"1","application_id":"2","token":"t","version":1}"#;
+    const COMMAND: &[u8] = br#"{"type":2,"id":"1","application_id":"2","token":"t","version":1,
+        "data":{"id":"3","name":"q","type":1},
+        "channel_id":"4","user":{"id":"5","username":"u","discriminator":"0"},
+        "locale":"en-US","app_permissions":"0","entitlements":[],
+        "authorizing_integration_owners":{}}"#;
+
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    fn key() -> SigningKey { SigningKey::from_bytes(&[7; 32]) }
+
+    fn endpoint() -> Endpoint<Schema> {
+        let registry = Registry::new(Handlers {
+            commands: vec![],
+            components: vec![],
+            modals: vec![],
+        });
+        let verifier = Verifier::new(&hex::encode(key().verifying_key().to_bytes())).unwrap();
+        Endpoint::new(Arc::new(registry), verifier)
+    }
+
+    fn signed(key: &SigningKey, timestamp: &str, body: &[u8]) -> HeaderMap {
+        let mut msg = timestamp.as_bytes().to_vec();
+        msg.extend_from_slice(body);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            SIGNATURE_HEADER,
+            hex::encode(key.sign(&msg).to_bytes()).parse().unwrap(),
+        );
+        headers.insert(TIMESTAMP_HEADER, timestamp.parse().unwrap());
+        headers
+    }
+
+    fn request(method: Method, headers: HeaderMap, body: Vec<u8>) -> Request<Full<Bytes>> {
+        let mut req = Request::new(Full::new(body.into()));
+        *req.method_mut() = method;
+        *req.headers_mut() = headers;
+        req
+    }
+
+    async fn body(res: Response<Full<Bytes>>) -> (StatusCode, Vec<u8>) {
+        let status = res.status();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        (status, body.to_vec())
+    }
+
+    #[test]
+    fn verify() {
+        let key = key();
+        let verifier = Verifier::new(&hex::encode(key.verifying_key().to_bytes())).unwrap();
+        let body = br#"{"type":1}"#;
+        let headers = signed(&key, "1700000000", body);
+
+        assert!(verifier.verify(&headers, body).is_ok());
+        assert!(matches!(
+            verifier.verify(&headers, br#"{"type":2}"#),
+            Err(WebhookError::BadSignature)
+        ));
+
+        let mut retimed = headers.clone();
+        retimed.insert(TIMESTAMP_HEADER, "1700000001".parse().unwrap());
+        assert!(matches!(
+            verifier.verify(&retimed, body),
+            Err(WebhookError::BadSignature)
+        ));
+
+        let mut unsigned = headers;
+        unsigned.remove(SIGNATURE_HEADER);
+        assert!(matches!(
+            verifier.verify(&unsigned, body),
+            Err(WebhookError::MissingHeader(SIGNATURE_HEADER))
+        ));
+    }
+
+    #[test]
+    fn bad_key() {
+        assert!(matches!(Verifier::new("abcd"), Err(WebhookError::InvalidKey)));
+        assert!(matches!(
+            Verifier::new(&"zz".repeat(32)),
+            Err(WebhookError::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn ping() {
+        let endpoint = endpoint();
+        let headers = signed(&key(), "1700000000", PING);
+
+        block_on(async {
+            let res = endpoint.handle(&headers, PING).await.unwrap();
+            assert_eq!(res.headers()["content-type"], "application/json");
+            let (status, body) = body(res).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body, br#"{"type":1}"#);
+        });
+    }
+
+    #[test]
+    fn handle_errors() {
+        let endpoint = endpoint();
+
+        block_on(async {
+            let headers = signed(&key(), "1700000000", PING);
+            let tampered = br#"{"type":1,"id":"1","application_id":"3","token":"t","version":1}"#;
+            assert!(matches!(
+                endpoint.handle(&headers, tampered).await,
+                Err(WebhookError::BadSignature)
+            ));
+
+            let garbage = b"{not json";
+            let headers = signed(&key(), "1700000000", garbage);
+            assert!(matches!(
+                endpoint.handle(&headers, garbage).await,
+                Err(WebhookError::Json(_))
+            ));
+
+            let headers = signed(&key(), "1700000000", COMMAND);
+            assert!(matches!(
+                endpoint.handle(&headers, COMMAND).await,
+                Err(WebhookError::NoContext)
+            ));
+        });
+    }
+
+    #[test]
+    fn respond_status() {
+        let endpoint = endpoint();
+
+        block_on(async {
+            let status = |method, headers, body: &[u8]| {
+                let req = request(method, headers, body.to_vec());
+                let endpoint = &endpoint;
+                async move { respond(endpoint, req).await.status() }
+            };
+
+            let (code, ping) = body(
+                respond(
+                    &endpoint,
+                    request(Method::POST, signed(&key(), "1", PING), PING.to_vec()),
+                )
+                .await,
+            )
+            .await;
+            assert_eq!(code, StatusCode::OK);
+            assert_eq!(ping, br#"{"type":1}"#);
+
+            let wrong_key = SigningKey::from_bytes(&[8; 32]);
+            assert_eq!(
+                status(Method::POST, signed(&wrong_key, "1", PING), PING).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(Method::POST, HeaderMap::new(), PING).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(Method::POST, signed(&key(), "1", b"[]"), b"[]").await,
+                StatusCode::BAD_REQUEST
+            );
+            assert_eq!(
+                status(Method::GET, signed(&key(), "1", PING), PING).await,
+                StatusCode::METHOD_NOT_ALLOWED
+            );
+
+            let big = vec![b' '; MAX_BODY_SIZE + 1];
+            assert_eq!(
+                status(Method::POST, signed(&key(), "1", &big), &big).await,
+                StatusCode::PAYLOAD_TOO_LARGE
+            );
+        });
+    }
+
+    #[test]
+    fn no_context() {
+        let endpoint = endpoint();
+        let headers = signed(&key(), "1", COMMAND);
+
+        block_on(async {
+            let res = respond(
+                &endpoint,
+                request(Method::POST, headers.clone(), COMMAND.to_vec()),
+            )
+            .await;
+            assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+            endpoint
+                .set_context(Context::new(
+                    Arc::new(Http::new("")),
+                    Arc::new(Cache::new()),
+                    Arc::new(RwLock::new(TypeMap::new())),
+                ))
+                .await;
+            let res = respond(&endpoint, request(Method::POST, headers, COMMAND.to_vec())).await;
+            assert_eq!(res.status(), StatusCode::ACCEPTED);
+        });
+    }
+}
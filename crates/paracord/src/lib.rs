@@ -11,4 +11,10 @@
 #![warn(clippy::pedantic, missing_docs)]
 #![allow(clippy::module_name_repetitions)]
 
+pub mod attachment;
+pub mod capability;
+pub mod client;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 pub mod interaction;
+pub mod presence;
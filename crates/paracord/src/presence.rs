@@ -0,0 +1,389 @@
+//! Declarative management of the bot's presence, rotating between activities
+//!
+//! A [`Rotation`] lists the activities to show, each given as an
+//! [`ActivityTemplate`] whose text may refer to the bot's state, such as
+//! `listening:{guilds} servers`.  A [`Presence`] owns the rotation and should
+//! be told when each shard becomes ready or resumes, at which point it sets
+//! the shard's presence and keeps it rotating on schedule.  Templates are
+//! re-rendered on every update, so state-based text stays current even when
+//! there is only one activity.
+//!
+//! The following variables may appear in a template:
+//! - `{guilds}`: the number of guilds cached by this process
+//! - `{shards}`: the total number of shards
+//! - `{shard}`: the ID of the shard showing the activity
+
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    client::Context,
+    gateway::ActivityData,
+    model::{id::ShardId, user::OnlineStatus},
+};
+use tokio::task::AbortHandle;
+
+/// The default time each activity in a rotation is shown for
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The kind of activity shown in a presence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActivityKind {
+    /// "Playing ..."
+    Playing,
+    /// "Listening to ..."
+    Listening,
+    /// "Watching ..."
+    Watching,
+    /// "Competing in ..."
+    Competing,
+    /// A custom status with no prefix
+    Custom,
+}
+
+impl ActivityKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Playing => "playing",
+            Self::Listening => "listening",
+            Self::Watching => "watching",
+            Self::Competing => "competing",
+            Self::Custom => "custom",
+        }
+    }
+}
+
+/// An error resulting from parsing an [`ActivityTemplate`] string
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Error parsing activity: {0}")]
+pub struct ParseActivityError(pub &'static str);
+
+/// The values available to an [`ActivityTemplate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresenceVars {
+    /// The number of guilds cached by this process
+    pub guilds: usize,
+    /// The total number of shards
+    pub shards: u32,
+    /// The ID of the shard showing the activity
+    pub shard: ShardId,
+}
+
+impl PresenceVars {
+    /// Read the current values of each variable from the client
+    #[must_use]
+    pub fn read(ctx: &Context) -> Self {
+        Self {
+            guilds: ctx.cache.guild_count(),
+            shards: ctx.cache.shard_count(),
+            shard: ctx.shard_id,
+        }
+    }
+}
+
+/// An activity whose text may refer to the bot's state
+///
+/// Templates are parsed from strings of the form `kind:text`, where `kind` is
+/// one of `playing`, `listening`, `watching`, `competing` or `custom`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct ActivityTemplate {
+    kind: ActivityKind,
+    text: String,
+}
+
+impl FromStr for ActivityTemplate {
+    type Err = ParseActivityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, text) = s.split_once(':').ok_or(ParseActivityError(
+            "Expected a string of the form kind:text",
+        ))?;
+        let kind = match kind.trim() {
+            "playing" => ActivityKind::Playing,
+            "listening" => ActivityKind::Listening,
+            "watching" => ActivityKind::Watching,
+            "competing" => ActivityKind::Competing,
+            "custom" => ActivityKind::Custom,
+            _ => return Err(ParseActivityError("Unknown activity kind")),
+        };
+
+        Ok(Self::new(kind, text.trim()))
+    }
+}
+
+impl TryFrom<String> for ActivityTemplate {
+    type Error = ParseActivityError;
+
+    #[inline]
+    fn try_from(value: String) -> Result<Self, Self::Error> { value.parse() }
+}
+
+impl fmt::Display for ActivityTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind.name(), self.text)
+    }
+}
+
+impl ActivityTemplate {
+    /// Construct a new template of the given kind
+    #[inline]
+    #[must_use]
+    pub fn new(kind: ActivityKind, text: impl Into<String>) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    /// Get the kind of activity this template produces
+    #[inline]
+    #[must_use]
+    pub fn kind(&self) -> ActivityKind { self.kind }
+
+    /// Substitute the given values into this template's text
+    #[must_use]
+    pub fn render_text(&self, vars: &PresenceVars) -> String {
+        self.text
+            .replace("{guilds}", &vars.guilds.to_string())
+            .replace("{shards}", &vars.shards.to_string())
+            .replace("{shard}", &vars.shard.to_string())
+    }
+
+    /// Produce the activity described by this template
+    #[must_use]
+    pub fn render(&self, vars: &PresenceVars) -> ActivityData {
+        let text = self.render_text(vars);
+
+        match self.kind {
+            ActivityKind::Playing => ActivityData::playing(text),
+            ActivityKind::Listening => ActivityData::listening(text),
+            ActivityKind::Watching => ActivityData::watching(text),
+            ActivityKind::Competing => ActivityData::competing(text),
+            ActivityKind::Custom => ActivityData::custom(text),
+        }
+    }
+}
+
+/// A list of activities to cycle through, along with the online status to
+/// show alongside them
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    activities: Vec<ActivityTemplate>,
+    interval: Duration,
+    status: OnlineStatus,
+}
+
+impl Default for Rotation {
+    fn default() -> Self { Self::new(vec![]) }
+}
+
+impl Rotation {
+    /// Construct a new rotation showing each of the given activities in turn
+    ///
+    /// An empty rotation clears the activity instead.
+    #[inline]
+    #[must_use]
+    pub fn new(activities: Vec<ActivityTemplate>) -> Self {
+        Self {
+            activities,
+            interval: DEFAULT_INTERVAL,
+            status: OnlineStatus::Online,
+        }
+    }
+
+    /// Show each activity for the given duration, instead of
+    /// [`DEFAULT_INTERVAL`]
+    ///
+    /// Zero durations are treated as one second.
+    #[must_use]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(Duration::from_secs(1));
+        self
+    }
+
+    /// Show the given online status, instead of appearing online
+    #[must_use]
+    pub fn with_status(mut self, status: OnlineStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Get the activities in this rotation
+    #[inline]
+    #[must_use]
+    pub fn activities(&self) -> &[ActivityTemplate] { &self.activities }
+
+    /// Get the activity to show after the given time has elapsed since the
+    /// rotation started
+    #[must_use]
+    pub fn current(&self, elapsed: Duration) -> Option<&ActivityTemplate> {
+        let len = u128::try_from(self.activities.len()).ok()?;
+        let step = (elapsed.as_millis() / self.interval.as_millis()).checked_rem(len)?;
+        self.activities
+            .get(usize::try_from(step).unwrap_or_else(|_| unreachable!()))
+    }
+}
+
+/// Owner of the bot's presence, keeping every shard's activity rotating
+///
+/// All shards start the rotation at the same time, so they show the same
+/// activity at once.
+#[derive(Debug)]
+pub struct Presence {
+    rotation: Rotation,
+    started: Instant,
+    tasks: Mutex<HashMap<ShardId, AbortHandle>>,
+}
+
+impl Drop for Presence {
+    fn drop(&mut self) {
+        let tasks = self
+            .tasks
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        for (_, task) in tasks.drain() {
+            task.abort();
+        }
+    }
+}
+
+impl Presence {
+    /// Construct a new presence manager for the given rotation
+    #[inline]
+    #[must_use]
+    pub fn new(rotation: Rotation) -> Self {
+        Self {
+            rotation,
+            started: Instant::now(),
+            tasks: Mutex::default(),
+        }
+    }
+
+    /// Get the rotation this manager shows
+    #[inline]
+    #[must_use]
+    pub fn rotation(&self) -> &Rotation { &self.rotation }
+
+    /// Set the presence of the shard of the given context to the current
+    /// activity of the rotation
+    pub fn apply(&self, ctx: &Context) {
+        let activity = self
+            .rotation
+            .current(self.started.elapsed())
+            .map(|t| t.render(&PresenceVars::read(ctx)));
+        ctx.set_presence(activity, self.rotation.status);
+    }
+
+    /// Set the presence of a newly-ready shard and keep it rotating,
+    /// replacing any previous rotation task for the same shard
+    ///
+    /// This should be called from the `ready` event handler.
+    ///
+    /// # Panics
+    /// This method panics if the manager's lock is poisoned.
+    pub fn ready(&self, ctx: &Context) {
+        self.apply(ctx);
+
+        if self.rotation.activities.is_empty() {
+            return;
+        }
+
+        let ctx = ctx.clone();
+        let shard = ctx.shard_id;
+        let rotation = self.rotation.clone();
+        let started = self.started;
+        let task = tokio::task::spawn(async move {
+            let interval = rotation.interval;
+            let mut timer = tokio::time::interval_at(
+                tokio::time::Instant::from_std(started) + interval,
+                interval,
+            );
+            timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+            loop {
+                timer.tick().await;
+
+                let activity = rotation
+                    .current(started.elapsed())
+                    .map(|t| t.render(&PresenceVars::read(&ctx)));
+                tracing::trace!(%shard, ?activity, "Rotating presence");
+                ctx.set_presence(activity, rotation.status);
+            }
+        });
+
+        if let Some(old) = self
+            .tasks
+            .lock()
+            .unwrap()
+            .insert(shard, task.abort_handle())
+        {
+            old.abort();
+        }
+    }
+
+    /// Restore the presence of a resumed shard
+    ///
+    /// This should be called from the `resume` event handler.
+    #[inline]
+    pub fn resume(&self, ctx: &Context) { self.apply(ctx); }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use serenity::model::id::ShardId;
+
+    use super::{ActivityKind, ActivityTemplate, PresenceVars, Rotation};
+
+    #[test]
+    fn parse_template() {
+        let t: ActivityTemplate = "listening: {guilds} servers".parse().unwrap();
+        assert_eq!(t.kind(), ActivityKind::Listening);
+        assert_eq!(t.to_string(), "listening:{guilds} servers");
+
+        let vars = PresenceVars {
+            guilds: 12,
+            shards: 2,
+            shard: ShardId(1),
+        };
+        assert_eq!(t.render_text(&vars), "12 servers");
+        assert_eq!(
+            "custom:shard {shard}/{shards}"
+                .parse::<ActivityTemplate>()
+                .unwrap()
+                .render_text(&vars),
+            "shard 1/2"
+        );
+
+        assert!("playing".parse::<ActivityTemplate>().is_err());
+        assert!("dancing:all night".parse::<ActivityTemplate>().is_err());
+    }
+
+    #[test]
+    fn rotation() {
+        let rot = Rotation::new(
+            ["playing:a", "watching:b", "custom:c"]
+                .into_iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        )
+        .with_interval(Duration::from_secs(10));
+        let at = |s| rot.current(Duration::from_secs(s)).unwrap().to_string();
+
+        assert_eq!(at(0), "playing:a");
+        assert_eq!(at(9), "playing:a");
+        assert_eq!(at(10), "watching:b");
+        assert_eq!(at(25), "custom:c");
+        assert_eq!(at(30), "playing:a");
+
+        assert!(Rotation::default().current(Duration::ZERO).is_none());
+    }
+}
@@ -0,0 +1,302 @@
+//! On-disk cache of descriptor sets compiled from Git blobs
+//!
+//! Entries are keyed by blob OID, so identical revisions of a file are only
+//! compiled once no matter how many commits or runs they appear in.  Since
+//! the output of `protoc` may change between releases, each `protoc` version
+//! gets its own cache namespace.
+
+use std::{
+    env, fs,
+    io::{self, prelude::*},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use git2::Oid;
+
+use crate::protoc::Descriptors;
+
+/// Bumped whenever the layout of the cache directory changes
+const CACHE_VERSION: u32 = 1;
+
+const DIR_VAR: &str = "PROTOCK_CACHE_DIR";
+const ENTRY_EXT: &str = "pb";
+
+/// Get the default cache location, or `None` if no suitable directory exists
+///
+/// This is `$PROTOCK_CACHE_DIR` if set, otherwise `protock` under
+/// `$XDG_CACHE_HOME` or `$HOME/.cache`.
+#[must_use]
+pub fn default_dir() -> Option<PathBuf> {
+    let var = |k| env::var_os(k).filter(|v| !v.is_empty()).map(PathBuf::from);
+
+    var(DIR_VAR).or_else(|| {
+        var("XDG_CACHE_HOME")
+            .or_else(|| var("HOME").map(|h| h.join(".cache")))
+            .map(|d| d.join("protock"))
+    })
+}
+
+/// A directory of serialized descriptor sets, keyed by blob OID
+#[derive(Debug)]
+pub struct BlobCache {
+    root: PathBuf,
+    dir: PathBuf,
+}
+
+impl BlobCache {
+    /// Open (creating if necessary) the cache for the given `protoc` version
+    /// under `root`
+    ///
+    /// # Errors
+    /// This method returns an error if the cache directory cannot be created.
+    pub fn open(root: impl Into<PathBuf>, protoc_version: &str) -> Result<Self> {
+        let root = root.into();
+        let ns: String = protoc_version
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let dir = root.join(format!("v{CACHE_VERSION}")).join(ns);
+
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Error creating cache directory {dir:?}"))?;
+
+        Ok(Self { root, dir })
+    }
+
+    fn path(&self, oid: Oid) -> PathBuf { self.dir.join(format!("{oid}.{ENTRY_EXT}")) }
+
+    /// Look up the descriptor set compiled from the given blob
+    ///
+    /// Unreadable or corrupt entries are treated as missing.  Hits refresh
+    /// the entry's modification time, which [`gc`](Self::gc) uses to evict
+    /// the least recently used entries first.
+    #[must_use]
+    pub fn get(&self, oid: Oid) -> Option<Descriptors> {
+        let path = self.path(oid);
+        let mut file = match fs::File::options().read(true).write(true).open(&path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                tracing::warn!(?path, "Error opening cache entry: {e}");
+                return None;
+            },
+        };
+
+        let mut raw = vec![];
+        let desc = file
+            .read_to_end(&mut raw)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Descriptors::decode(raw).map_err(Into::into));
+
+        match desc {
+            Ok(d) => {
+                file.set_modified(SystemTime::now()).ok();
+                tracing::trace!(%oid, "Cache hit");
+                Some(d)
+            },
+            Err(e) => {
+                tracing::warn!(?path, "Discarding corrupt cache entry: {e}");
+                drop(file);
+                fs::remove_file(&path).ok();
+                None
+            },
+        }
+    }
+
+    /// Store the descriptor set compiled from the given blob
+    ///
+    /// The entry is written to a temporary file and moved into place, so
+    /// concurrent runs never observe a partial entry.
+    ///
+    /// # Errors
+    /// This method returns an error if the entry cannot be written.
+    pub fn put(&self, oid: Oid, desc: &Descriptors) -> Result<()> {
+        let mut tmp = tempfile::NamedTempFile::new_in(&self.dir)
+            .context("Error creating temporary cache entry")?;
+        tmp.write_all(&desc.raw)
+            .context("Error writing cache entry")?;
+        tmp.persist(self.path(oid))
+            .context("Error moving cache entry into place")?;
+
+        Ok(())
+    }
+
+    /// Delete the least recently used entries until the cache occupies at
+    /// most `max_bytes`, returning the number of entries removed
+    ///
+    /// Entries written by other `protoc` versions or cache layouts are
+    /// included, so stale namespaces are eventually cleaned up.
+    ///
+    /// # Errors
+    /// This method returns an error if the cache directory cannot be listed.
+    pub fn gc(&self, max_bytes: u64) -> Result<usize> {
+        let mut entries = vec![];
+        collect_entries(&self.root, &mut entries)
+            .with_context(|| format!("Error listing cache directory {:?}", self.root))?;
+
+        let mut total: u64 = entries.iter().map(|(_, _, s)| s).sum();
+        if total <= max_bytes {
+            return Ok(0);
+        }
+
+        entries.sort_by_key(|&(_, t, _)| t);
+
+        let mut removed = 0;
+        for (path, _, size) in entries {
+            if total <= max_bytes {
+                break;
+            }
+
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    total -= size;
+                    removed += 1;
+                },
+                Err(e) => tracing::warn!(?path, "Error removing cache entry: {e}"),
+            }
+        }
+
+        tracing::debug!(removed, total, "Cache garbage collection finished");
+        Ok(removed)
+    }
+}
+
+fn collect_entries(dir: &Path, out: &mut Vec<(PathBuf, SystemTime, u64)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        let path = entry.path();
+
+        if meta.is_dir() {
+            collect_entries(&path, out)?;
+        } else if path.extension().is_some_and(|e| e == ENTRY_EXT) {
+            out.push((path, meta.modified()?, meta.len()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use prost::Message;
+    use prost_types::{FileDescriptorProto, FileDescriptorSet};
+
+    use super::*;
+
+    fn oid(n: u8) -> Oid { Oid::from_bytes(&[n; 20]).unwrap() }
+
+    fn desc(name: &str) -> Descriptors {
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some(name.into()),
+                ..Default::default()
+            }],
+        };
+        Descriptors::decode(set.encode_to_vec()).unwrap()
+    }
+
+    fn name(desc: &Descriptors) -> &str { desc.set.file[0].name() }
+
+    /// Set the modification time of an entry to the given number of seconds
+    /// after the epoch
+    fn touch(cache: &BlobCache, oid: Oid, secs: u64) {
+        fs::File::options()
+            .write(true)
+            .open(cache.path(oid))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn hit_and_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::open(dir.path(), "libprotoc 29.2").unwrap();
+        assert!(cache.dir.ends_with("v1/libprotoc_29_2"));
+
+        assert!(cache.get(oid(1)).is_none());
+
+        cache.put(oid(1), &desc("a.proto")).unwrap();
+        assert_eq!(cache.get(oid(1)).map(|d| name(&d).to_owned()).unwrap(), "a.proto");
+        assert!(cache.get(oid(2)).is_none());
+
+        // Entries are replaced when written again
+        cache.put(oid(1), &desc("b.proto")).unwrap();
+        assert_eq!(cache.get(oid(1)).map(|d| name(&d).to_owned()).unwrap(), "b.proto");
+
+        // Entries persist across opens
+        let cache = BlobCache::open(dir.path(), "libprotoc 29.2").unwrap();
+        assert!(cache.get(oid(1)).is_some());
+    }
+
+    #[test]
+    fn versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = BlobCache::open(dir.path(), "libprotoc 29.2").unwrap();
+        let new = BlobCache::open(dir.path(), "libprotoc 30.0").unwrap();
+
+        old.put(oid(1), &desc("a.proto")).unwrap();
+        assert!(old.get(oid(1)).is_some());
+        assert!(new.get(oid(1)).is_none());
+    }
+
+    #[test]
+    fn corrupt_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::open(dir.path(), "libprotoc 29.2").unwrap();
+
+        fs::write(cache.path(oid(1)), b"\xff\xff\xff").unwrap();
+        assert!(cache.get(oid(1)).is_none());
+        assert!(!cache.path(oid(1)).exists());
+
+        cache.put(oid(1), &desc("a.proto")).unwrap();
+        assert!(cache.get(oid(1)).is_some());
+    }
+
+    #[test]
+    fn hits_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::open(dir.path(), "libprotoc 29.2").unwrap();
+
+        cache.put(oid(1), &desc("a.proto")).unwrap();
+        touch(&cache, oid(1), 1);
+        assert!(cache.get(oid(1)).is_some());
+
+        let modified = fs::metadata(cache.path(oid(1))).unwrap().modified().unwrap();
+        assert!(modified > SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn gc() {
+        let dir = tempfile::tempdir().unwrap();
+        let stale = BlobCache::open(dir.path(), "libprotoc 29.2").unwrap();
+        let cache = BlobCache::open(dir.path(), "libprotoc 30.0").unwrap();
+
+        stale.put(oid(1), &desc("a.proto")).unwrap();
+        touch(&stale, oid(1), 1);
+        for (i, secs) in [(2, 3), (3, 2), (4, 4)] {
+            cache.put(oid(i), &desc("a.proto")).unwrap();
+            touch(&cache, oid(i), secs);
+        }
+        fs::write(cache.dir.join("unrelated.txt"), [0; 1024]).unwrap();
+
+        let size = fs::metadata(cache.path(oid(2))).unwrap().len();
+        assert_eq!(cache.gc(4 * size).unwrap(), 0);
+
+        // The stale namespace and then the least recently used entry go first
+        assert_eq!(cache.gc(2 * size).unwrap(), 2);
+        assert!(stale.get(oid(1)).is_none());
+        assert!(cache.get(oid(3)).is_none());
+        assert!(cache.get(oid(2)).is_some());
+        assert!(cache.get(oid(4)).is_some());
+        assert!(cache.dir.join("unrelated.txt").exists());
+
+        assert_eq!(cache.gc(0).unwrap(), 2);
+        assert!(cache.get(oid(2)).is_none());
+    }
+}
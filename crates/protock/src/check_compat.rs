@@ -6,17 +6,37 @@ use crate::compat_pair::{CompatPair, SideInclusive};
 pub struct CompatLog {
     errors: Vec<CompatError>,
     warnings: Vec<CompatError>,
+    /// Teams whose approval errors need, if the checked file is owned by
+    /// another team
+    approvers: Option<Vec<String>>,
 }
 
 impl CompatLog {
-    pub fn run<E>(f: impl FnOnce(&mut Self), e: impl FnOnce() -> E) -> Result<(), E> {
-        let mut me = Self::default();
+    pub fn run<E>(
+        approvers: Option<&[String]>,
+        f: impl FnOnce(&mut Self),
+        e: impl FnOnce() -> E,
+    ) -> Result<(), E> {
+        let mut me = Self {
+            approvers: approvers.map(<[_]>::to_vec),
+            ..Self::default()
+        };
         f(&mut me);
         me.finish(e)
     }
 
+    #[cfg(test)]
+    pub fn errors(&self) -> &[CompatError] { &self.errors }
+
+    #[cfg(test)]
+    pub fn warnings(&self) -> &[CompatError] { &self.warnings }
+
     pub fn finish<E>(self, error: impl FnOnce() -> E) -> Result<(), E> {
-        let Self { errors, warnings } = self;
+        let Self {
+            errors,
+            warnings,
+            approvers,
+        } = self;
 
         for warn in warnings {
             tracing::warn!("{warn}");
@@ -24,7 +44,11 @@ impl CompatLog {
 
         let err = !errors.is_empty();
         for err in errors {
-            tracing::error!("{err}");
+            if let Some(ref teams) = approvers {
+                tracing::error!("Needs approval from {}: {err}", teams.join(", "));
+            } else {
+                tracing::error!("{err}");
+            }
         }
 
         err.then(|| Err(error())).unwrap_or(Ok(()))
@@ -62,6 +86,9 @@ impl CompatError {
         }
     }
 
+    #[cfg(test)]
+    pub fn message(&self) -> &str { &self.message }
+
     #[inline]
     pub fn err(self, log: &mut CompatLog) { log.errors.push(self); }
 
@@ -0,0 +1,341 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
+};
+
+use prost_types::{
+    DescriptorProto, FileDescriptorProto, FileDescriptorSet, MessageOptions, ServiceDescriptorProto,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Message,
+    MapEntry,
+    Enum,
+    Service,
+}
+
+impl NodeKind {
+    const fn var_pretty(self) -> &'static str {
+        match self {
+            Self::Message | Self::MapEntry => "message",
+            Self::Enum => "enum",
+            Self::Service => "service",
+        }
+    }
+}
+
+/// An edge from a type to a type that embeds it
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Use {
+    parent: String,
+    /// The field or method through which the parent refers to the child
+    via: String,
+}
+
+/// The graph of type references across all files in a descriptor set
+#[derive(Debug, Default)]
+pub struct DepGraph {
+    nodes: BTreeMap<String, NodeKind>,
+    uses: BTreeMap<String, BTreeSet<Use>>,
+}
+
+/// A type affected by a change, along with one shortest chain of references
+/// leading back to the changed type
+#[derive(Debug)]
+pub struct Affected<'a> {
+    pub name: &'a str,
+    pub kind: NodeKind,
+    /// Whether no other type embeds this one
+    pub top_level: bool,
+    chain: Vec<&'a Use>,
+}
+
+impl fmt::Display for Affected<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind.var_pretty(), self.name)?;
+
+        for (i, u) in self.chain.iter().enumerate() {
+            write!(
+                f,
+                "{}{}",
+                if i == 0 { " via " } else { " -> " },
+                u.via.trim_start_matches('.')
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[inline]
+fn qualify(scope: &str, name: Option<&str>) -> String {
+    format!("{scope}.{}", name.expect("Missing type name"))
+}
+
+impl DepGraph {
+    pub fn new(desc: &FileDescriptorSet) -> Self {
+        let mut me = Self::default();
+
+        for file in &desc.file {
+            me.file(file);
+        }
+
+        me
+    }
+
+    fn file(&mut self, desc: &FileDescriptorProto) {
+        let scope = desc
+            .package
+            .as_deref()
+            .map_or_else(String::new, |p| format!(".{p}"));
+
+        for msg in &desc.message_type {
+            self.message(&scope, msg);
+        }
+
+        for en in &desc.enum_type {
+            self.nodes
+                .insert(qualify(&scope, en.name.as_deref()), NodeKind::Enum);
+        }
+
+        for svc in &desc.service {
+            self.service(&scope, svc);
+        }
+    }
+
+    fn message(&mut self, scope: &str, desc: &DescriptorProto) {
+        let name = qualify(scope, desc.name.as_deref());
+        let map_entry = desc
+            .options
+            .as_ref()
+            .and_then(|MessageOptions { map_entry, .. }| *map_entry)
+            .unwrap_or(false);
+
+        self.nodes.insert(
+            name.clone(),
+            if map_entry {
+                NodeKind::MapEntry
+            } else {
+                NodeKind::Message
+            },
+        );
+
+        for field in &desc.field {
+            let Some(ref ty) = field.type_name else {
+                continue;
+            };
+
+            self.add_use(ty, &name, field.name.as_deref().unwrap_or("?"));
+        }
+
+        for msg in &desc.nested_type {
+            self.message(&name, msg);
+        }
+
+        for en in &desc.enum_type {
+            self.nodes
+                .insert(qualify(&name, en.name.as_deref()), NodeKind::Enum);
+        }
+    }
+
+    fn service(&mut self, scope: &str, desc: &ServiceDescriptorProto) {
+        let name = qualify(scope, desc.name.as_deref());
+        self.nodes.insert(name.clone(), NodeKind::Service);
+
+        for method in &desc.method {
+            let method_name = method.name.as_deref().unwrap_or("?");
+
+            for ty in [&method.input_type, &method.output_type]
+                .into_iter()
+                .flatten()
+            {
+                self.add_use(ty, &name, method_name);
+            }
+        }
+    }
+
+    fn add_use(&mut self, child: &str, parent: &str, member: &str) {
+        self.uses.entry(child.to_owned()).or_default().insert(Use {
+            parent: parent.to_owned(),
+            via: format!("{parent}.{member}"),
+        });
+    }
+
+    /// Normalize a user-provided type name into its fully-qualified form,
+    /// returning `None` if no such type exists
+    pub fn resolve<'a>(&'a self, name: &str) -> Option<&'a str> {
+        let name = format!(".{}", name.trim_start_matches('.'));
+        self.nodes.get_key_value(&name).map(|(k, _)| k.as_str())
+    }
+
+    /// Find all types that transitively embed the given type, ordered by their
+    /// distance from it
+    ///
+    /// Synthetic map entry messages are traversed but not reported.
+    pub fn impact<'a>(&'a self, name: &'a str) -> Vec<Affected<'a>> {
+        let mut prev: BTreeMap<&str, Option<(&str, &Use)>> = BTreeMap::new();
+        let mut order = vec![];
+        let mut q = VecDeque::new();
+
+        prev.insert(name, None);
+        q.push_back(name);
+
+        while let Some(child) = q.pop_front() {
+            for u in self.uses.get(child).into_iter().flatten() {
+                if prev.contains_key(u.parent.as_str()) {
+                    continue;
+                }
+
+                prev.insert(&u.parent, Some((child, u)));
+                order.push(u.parent.as_str());
+                q.push_back(&u.parent);
+            }
+        }
+
+        order
+            .into_iter()
+            .filter_map(|n| {
+                let kind = self.nodes[n];
+                if kind == NodeKind::MapEntry {
+                    return None;
+                }
+
+                let mut chain = vec![];
+                let mut curr = n;
+                while let Some(&Some((child, u))) = prev.get(curr) {
+                    chain.push(u);
+                    curr = child;
+                }
+
+                Some(Affected {
+                    name: n.trim_start_matches('.'),
+                    kind,
+                    top_level: !self.uses.contains_key(n),
+                    chain,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prost_types::{
+        DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+        FileDescriptorSet, MessageOptions, MethodDescriptorProto, ServiceDescriptorProto,
+    };
+
+    use super::{DepGraph, NodeKind};
+
+    fn field(name: &str, ty: &str) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.into()),
+            type_name: Some(ty.into()),
+            ..FieldDescriptorProto::default()
+        }
+    }
+
+    fn message(name: &str, fields: Vec<FieldDescriptorProto>) -> DescriptorProto {
+        DescriptorProto {
+            name: Some(name.into()),
+            field: fields,
+            ..DescriptorProto::default()
+        }
+    }
+
+    /// A schema equivalent to:
+    ///
+    /// ```proto
+    /// package pkg;
+    /// enum Color { RED = 0; }
+    /// message Leaf { Color color = 1; }
+    /// message Mid { Leaf leaf = 1; map<string, Leaf> by_name = 2; }
+    /// message Top { Mid mid = 1; }
+    /// service Svc { rpc Get(Top) returns (Leaf); }
+    /// ```
+    fn graph() -> DepGraph {
+        let entry = DescriptorProto {
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..MessageOptions::default()
+            }),
+            ..message("ByNameEntry", vec![field("value", ".pkg.Leaf")])
+        };
+
+        DepGraph::new(&FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                package: Some("pkg".into()),
+                enum_type: vec![EnumDescriptorProto {
+                    name: Some("Color".into()),
+                    ..EnumDescriptorProto::default()
+                }],
+                message_type: vec![
+                    message("Leaf", vec![field("color", ".pkg.Color")]),
+                    DescriptorProto {
+                        nested_type: vec![entry],
+                        ..message("Mid", vec![
+                            field("leaf", ".pkg.Leaf"),
+                            field("by_name", ".pkg.Mid.ByNameEntry"),
+                        ])
+                    },
+                    message("Top", vec![field("mid", ".pkg.Mid")]),
+                ],
+                service: vec![ServiceDescriptorProto {
+                    name: Some("Svc".into()),
+                    method: vec![MethodDescriptorProto {
+                        name: Some("Get".into()),
+                        input_type: Some(".pkg.Top".into()),
+                        output_type: Some(".pkg.Leaf".into()),
+                        ..MethodDescriptorProto::default()
+                    }],
+                    ..ServiceDescriptorProto::default()
+                }],
+                ..FileDescriptorProto::default()
+            }],
+        })
+    }
+
+    #[test]
+    fn resolve() {
+        let graph = graph();
+
+        assert_eq!(graph.resolve("pkg.Leaf"), Some(".pkg.Leaf"));
+        assert_eq!(graph.resolve(".pkg.Mid.ByNameEntry"), Some(".pkg.Mid.ByNameEntry"));
+        assert_eq!(graph.resolve("pkg.Nope"), None);
+    }
+
+    #[test]
+    fn impact() {
+        let graph = graph();
+        let name = graph.resolve("pkg.Color").unwrap();
+        let affected = graph.impact(name);
+
+        let summary: Vec<_> = affected
+            .iter()
+            .map(|a| (a.name, a.kind, a.top_level))
+            .collect();
+        assert_eq!(summary, [
+            ("pkg.Leaf", NodeKind::Message, false),
+            ("pkg.Mid", NodeKind::Message, false),
+            ("pkg.Svc", NodeKind::Service, true),
+            ("pkg.Top", NodeKind::Message, false),
+        ]);
+
+        let chains: Vec<_> = affected.iter().map(ToString::to_string).collect();
+        assert_eq!(chains, [
+            "message pkg.Leaf via pkg.Leaf.color",
+            "message pkg.Mid via pkg.Mid.leaf -> pkg.Leaf.color",
+            "service pkg.Svc via pkg.Svc.Get -> pkg.Leaf.color",
+            "message pkg.Top via pkg.Top.mid -> pkg.Mid.leaf -> pkg.Leaf.color",
+        ]);
+    }
+
+    #[test]
+    fn impact_leaf() {
+        let graph = graph();
+
+        assert!(graph.impact(".pkg.Top").iter().all(|a| a.name == "pkg.Svc"));
+        assert!(graph.impact(".pkg.Svc").is_empty());
+    }
+}
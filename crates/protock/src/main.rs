@@ -11,38 +11,68 @@
 #![warn(clippy::pedantic, missing_docs)]
 #![allow(clippy::module_name_repetitions)]
 
+mod cache;
 mod check_compat;
 mod compat_pair;
 mod git;
+mod impact;
+mod owners;
 mod protoc;
+mod remote;
 mod schema;
 
 fn main() { entry::main(); }
 
 mod entry {
-    use std::{
-        io::prelude::*,
-        path::{Path, PathBuf},
-    };
+    use std::{io::prelude::*, path::PathBuf};
 
     use anyhow::{Context, Result};
     use clap::Parser;
+    use reqwest::Url;
     use tracing_subscriber::{filter::LevelFilter, prelude::*};
 
     use crate::{
+        cache::{self, BlobCache},
         check_compat::CompatLog,
         compat_pair::CompatPair,
-        git, protoc,
-        schema::{Schema, SchemaContext},
+        git,
+        impact::DepGraph,
+        owners::{ApprovalRequired, Owners},
+        protoc::{self, Descriptors},
+        remote,
+        schema::{OptionPolicy, PolicyRule, Schema, SchemaContext},
     };
 
     #[derive(Debug, Parser)]
-    #[command(version, author, about)]
+    #[command(
+        version,
+        author,
+        about,
+        args_conflicts_with_subcommands = true,
+        subcommand_negates_reqs = true
+    )]
     struct Opts {
         /// Print more verbose logs
-        #[arg(short, long, action = clap::ArgAction::Count)]
+        #[arg(short, long, global = true, action = clap::ArgAction::Count)]
         verbose: u8,
 
+        #[command(subcommand)]
+        command: Option<Command>,
+
+        #[command(flatten)]
+        check: CheckOpts,
+    }
+
+    #[derive(Debug, clap::Subcommand)]
+    enum Command {
+        /// List all messages and services that transitively embed a type
+        Impact(ImpactOpts),
+        /// Print a JSON inventory of the structural changes to a schema
+        Diff(DiffOpts),
+    }
+
+    #[derive(Debug, clap::Args)]
+    struct CheckOpts {
         /// Compatibility check mode
         #[arg(long, default_value = "backward")]
         mode: Mode,
@@ -51,6 +81,114 @@ mod entry {
         #[arg(long)]
         old: Option<PathBuf>,
 
+        /// URL of a descriptor set or .proto file to compare against
+        ///
+        /// Supports `http(s)://` URLs and `oci://registry/repository:tag`
+        /// references.  Registry credentials are read from
+        /// `PROTOCK_REGISTRY_TOKEN`, or `PROTOCK_REGISTRY_USER` and
+        /// `PROTOCK_REGISTRY_PASSWORD`.
+        #[arg(long, conflicts_with = "old")]
+        old_url: Option<Url>,
+
+        /// Severity of changes to a descriptor option
+        ///
+        /// OPTION is an option name such as `deprecated` or
+        /// `(validate.rules)`, or `*` to set the severity for all other
+        /// options.  LEVEL is one of `ignore`, `warn` or `error`.  By default
+        /// changes to `deprecated` are ignored and changes to any other option
+        /// are warnings.
+        #[arg(long, value_name = "OPTION=LEVEL")]
+        option_policy: Vec<PolicyRule>,
+
+        /// CODEOWNERS-style file assigning proto paths to the teams that own
+        /// them
+        ///
+        /// Each line holds a path pattern followed by the teams owning
+        /// matching files, and the last matching line applies.  Patterns are
+        /// relative to the directory of the owners file.  Breaking changes to
+        /// a file owned only by teams other than `--team` are reported as
+        /// needing their approval, and exit with status 2 rather than 1.
+        #[arg(long, requires = "team")]
+        owners: Option<PathBuf>,
+
+        /// The team making the change, as named in the owners file
+        #[arg(long, requires = "owners")]
+        team: Option<String>,
+
+        #[command(flatten)]
+        cache: CacheOpts,
+
+        /// Input file
+        #[arg(required = true)]
+        file: Option<PathBuf>,
+    }
+
+    #[derive(Debug, clap::Args)]
+    struct CacheOpts {
+        /// Compile every revision from scratch instead of using the
+        /// descriptor cache
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Directory to cache compiled revisions in
+        ///
+        /// Defaults to `$PROTOCK_CACHE_DIR`, or `protock` under
+        /// `$XDG_CACHE_HOME` or `~/.cache`.
+        #[arg(long, conflicts_with = "no_cache")]
+        cache_dir: Option<PathBuf>,
+
+        /// Maximum size of the descriptor cache in MiB, beyond which the
+        /// least recently used entries are deleted
+        #[arg(long, value_name = "MIB", default_value_t = 256)]
+        cache_max_size: u64,
+    }
+
+    impl CacheOpts {
+        fn open(&self) -> Result<Option<BlobCache>> {
+            if self.no_cache {
+                return Ok(None);
+            }
+
+            let Some(dir) = self.cache_dir.clone().or_else(cache::default_dir) else {
+                tracing::warn!("No cache directory found, compiling without a cache");
+                return Ok(None);
+            };
+
+            let version = protoc::version().context("Error getting protoc version")?;
+            BlobCache::open(dir, &version).map(Some)
+        }
+    }
+
+    #[derive(Debug, clap::Args)]
+    struct ImpactOpts {
+        /// Fully-qualified name of the changed type, e.g. `my.package.Message`
+        ty: String,
+
+        /// Input files
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    }
+
+    #[derive(Debug, clap::Args)]
+    struct DiffOpts {
+        /// File to compare against
+        #[arg(long)]
+        old: Option<PathBuf>,
+
+        /// URL of a descriptor set or .proto file to compare against
+        ///
+        /// Accepts the same URLs as the compatibility check.
+        #[arg(long, conflicts_with = "old")]
+        old_url: Option<Url>,
+
+        /// Git revision to read the old version of the input file from
+        #[arg(long, default_value = "HEAD", conflicts_with_all = ["old", "old_url"])]
+        rev: String,
+
+        /// File to write the diff to instead of standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
         /// Input file
         file: PathBuf,
     }
@@ -90,7 +228,11 @@ mod entry {
         std::process::exit(run(opts).map_or_else(
             |e| {
                 tracing::error!("{e:?}");
-                1
+                if e.is::<ApprovalRequired>() {
+                    2
+                } else {
+                    1
+                }
             },
             |()| 0,
         ));
@@ -100,20 +242,182 @@ mod entry {
     fn run(
         Opts {
             verbose: _,
+            command,
+            check,
+        }: Opts,
+    ) -> Result<()> {
+        match command {
+            Some(Command::Impact(opts)) => impact(opts),
+            Some(Command::Diff(opts)) => diff(opts),
+            None => check_compat(check),
+        }
+    }
+
+    fn impact(ImpactOpts { ty, files }: ImpactOpts) -> Result<()> {
+        let desc = protoc::get_descriptor_set(&files).context("Error compiling proto files")?;
+        let graph = DepGraph::new(&desc.set);
+        let ty = graph
+            .resolve(&ty)
+            .with_context(|| format!("No type named {ty:?} found"))?;
+
+        let affected = graph.impact(ty);
+
+        if affected.is_empty() {
+            println!("No types depend on {}", ty.trim_start_matches('.'));
+            return Ok(());
+        }
+
+        for top_level in [true, false] {
+            let mut it = affected
+                .iter()
+                .filter(|a| a.top_level == top_level)
+                .peekable();
+
+            if it.peek().is_none() {
+                continue;
+            }
+
+            println!(
+                "{} affected by {}:",
+                if top_level {
+                    "Top-level types"
+                } else {
+                    "Intermediate types"
+                },
+                ty.trim_start_matches('.'),
+            );
+
+            for a in it {
+                println!("  {a}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn diff(
+        DiffOpts {
+            old,
+            old_url,
+            rev,
+            output,
+            file,
+        }: DiffOpts,
+    ) -> Result<()> {
+        let desc = protoc::get_descriptor_set([&file]).context("Error compiling proto file")?;
+        let new_schema = Schema::new(&desc)?;
+        let new_name = file.display().to_string();
+
+        let (old_desc, old_name) = if let Some(old) = old {
+            let name = old.display().to_string();
+            (protoc::get_descriptor_set([old])?, name)
+        } else if let Some(url) = old_url {
+            let desc = match remote::fetch(&url).with_context(|| format!("Error fetching {url}"))? {
+                remote::Artifact::DescriptorSet(d) => d,
+                remote::Artifact::Proto(p) => compile_blob(&p)?,
+            };
+            (desc, url.to_string())
+        } else {
+            let repo = git::open().context("Error opening Git repository")?;
+            let commit = repo
+                .revparse_single(&rev)
+                .and_then(|o| o.peel_to_commit())
+                .with_context(|| format!("Error resolving revision {rev:?}"))?;
+            let blob = git::commit_file(&repo, &commit, &file)
+                .context("Error reading file from revision")?
+                .with_context(|| format!("{} not found in revision {rev:?}", file.display()))?;
+
+            (
+                compile_blob(blob.content())?,
+                format!("{rev}:{}", file.display()),
+            )
+        };
+
+        let old_schema = Schema::new(&old_desc)?;
+        let diff = Schema::diff(&old_schema, &old_name, &new_schema, &new_name);
+
+        if let Some(output) = output {
+            let file = std::fs::File::create(&output)
+                .with_context(|| format!("Error creating {}", output.display()))?;
+            serde_json::to_writer_pretty(std::io::BufWriter::new(file), &diff)
+                .context("Error writing diff")?;
+        } else {
+            serde_json::to_writer_pretty(std::io::stdout().lock(), &diff)
+                .context("Error writing diff")?;
+            println!();
+        }
+
+        Ok(())
+    }
+
+    fn compile_blob(content: &[u8]) -> Result<Descriptors> {
+        let mut tmp =
+            tempfile::NamedTempFile::new().context("Error creating temporary proto file")?;
+        tmp.write_all(content)
+            .context("Error writing temporary proto file")?;
+
+        protoc::get_descriptor_set([tmp.path()])
+    }
+
+    fn compile_cached(blob: &git2::Blob, cache: Option<&BlobCache>) -> Result<Descriptors> {
+        let Some(cache) = cache else {
+            return compile_blob(blob.content());
+        };
+
+        if let Some(desc) = cache.get(blob.id()) {
+            return Ok(desc);
+        }
+
+        let desc = compile_blob(blob.content())?;
+        cache
+            .put(blob.id(), &desc)
+            .map_err(|e| tracing::warn!("Error caching compiled blob: {e:?}"))
+            .ok();
+        Ok(desc)
+    }
+
+    fn check_compat(
+        CheckOpts {
             mode,
             old,
+            old_url,
+            option_policy,
+            owners,
+            team,
+            cache,
             file,
-        }: Opts,
+        }: CheckOpts,
     ) -> Result<()> {
+        let file = file.unwrap_or_else(|| unreachable!());
         let desc = protoc::get_descriptor_set([&file]).context("Error compiling proto file")?;
-        let new_schema = Schema::new(&desc);
+        let new_schema = Schema::new(&desc)?;
         let new_name = file.display().to_string();
+        let policy = OptionPolicy::new(option_policy);
+        let owners = owners.map(Owners::load).transpose()?;
+        let approvers = owners
+            .as_ref()
+            .zip(team)
+            .and_then(|(o, t)| o.approvers(&file, &t));
+        let cx = CheckContext {
+            mode,
+            policy: &policy,
+            approvers,
+        };
 
         if let Some(old) = old {
             let old_name = old.display().to_string();
-            check_protos(&new_schema, &new_name, old, &old_name, mode)?;
+            let old_desc = protoc::get_descriptor_set([old])?;
+            check_protos(&new_schema, &new_name, &old_desc, &old_name, &cx)?;
+        } else if let Some(url) = old_url {
+            let old_desc =
+                match remote::fetch(&url).with_context(|| format!("Error fetching {url}"))? {
+                    remote::Artifact::DescriptorSet(d) => d,
+                    remote::Artifact::Proto(p) => compile_blob(&p)?,
+                };
+            check_protos(&new_schema, &new_name, &old_desc, url.as_str(), &cx)?;
         } else {
             let repo = git::open().context("Error opening Git repository")?;
+            let blob_cache = cache.open().context("Error opening descriptor cache")?;
 
             let diffopt = git::diff_opts(&file);
 
@@ -138,39 +442,61 @@ mod entry {
                 .entered();
                 tracing::debug!("Blob found, compiling and checking...");
 
-                let mut tmp = tempfile::NamedTempFile::new()
-                    .context("Error creating temporary proto file")?;
-                tmp.write_all(blob.content())
-                    .context("Error writing temporary proto file")?;
-
+                let old_desc = compile_cached(&blob, blob_cache.as_ref())?;
                 let old_name = format!("{}:{}", id.as_str().unwrap_or_default(), file.display());
 
-                check_protos(&new_schema, &new_name, tmp.path(), &old_name, mode)?;
+                check_protos(&new_schema, &new_name, &old_desc, &old_name, &cx)?;
+            }
+
+            if let Some(blob_cache) = blob_cache {
+                blob_cache
+                    .gc(cache.cache_max_size.saturating_mul(1024 * 1024))
+                    .context("Error cleaning up descriptor cache")?;
             }
         }
 
         Ok(())
     }
 
+    /// Settings shared by every compatibility check of a single file
+    struct CheckContext<'a> {
+        mode: Mode,
+        policy: &'a OptionPolicy,
+        /// Teams whose approval breaking changes need, if the file is owned
+        /// by another team
+        approvers: Option<&'a [String]>,
+    }
+
     fn check_protos(
         new_schema: &Schema,
         new_name: &str,
-        old: impl AsRef<Path>,
+        old_desc: &Descriptors,
         old_name: &str,
-        mode: Mode,
+        &CheckContext {
+            mode,
+            policy,
+            approvers,
+        }: &CheckContext,
     ) -> Result<()> {
-        let old_desc = protoc::get_descriptor_set([old])?;
-        let old_schema = Schema::new(&old_desc);
+        let old_schema = Schema::new(old_desc)?;
         let mut res = Ok(());
 
         if mode.is_backward() {
             let ck = CompatPair::new(new_schema, &old_schema);
-            let cx = CompatPair::new(SchemaContext { name: new_name }, SchemaContext {
-                name: old_name,
-            });
+            let cx = CompatPair::new(
+                SchemaContext {
+                    name: new_name,
+                    policy,
+                },
+                SchemaContext {
+                    name: old_name,
+                    policy,
+                },
+            );
             let (reader, writer) = cx.as_ref().map(|c| c.name).into_inner();
             let _s = tracing::error_span!("check_backward", reader, writer).entered();
             res = res.and(CompatLog::run(
+                approvers,
                 |l| ck.check(cx, l),
                 || {
                     tracing::error!(
@@ -182,12 +508,20 @@ mod entry {
 
         if mode.is_forward() {
             let ck = CompatPair::new(&old_schema, new_schema);
-            let cx = CompatPair::new(SchemaContext { name: old_name }, SchemaContext {
-                name: new_name,
-            });
+            let cx = CompatPair::new(
+                SchemaContext {
+                    name: old_name,
+                    policy,
+                },
+                SchemaContext {
+                    name: new_name,
+                    policy,
+                },
+            );
             let (reader, writer) = cx.as_ref().map(|c| c.name).into_inner();
             let _s = tracing::error_span!("check_forward", reader, writer).entered();
             res = res.and(CompatLog::run(
+                approvers,
                 |l| ck.check(cx, l),
                 || {
                     tracing::error!(
@@ -197,6 +531,17 @@ mod entry {
             ));
         }
 
-        res.map_err(|()| anyhow::anyhow!("Stopping due to failed compatibility check"))
+        res.map_err(|()| {
+            approvers.map_or_else(
+                || anyhow::anyhow!("Stopping due to failed compatibility check"),
+                |teams| {
+                    ApprovalRequired {
+                        file: new_name.into(),
+                        owners: teams.to_vec(),
+                    }
+                    .into()
+                },
+            )
+        })
     }
 }
@@ -0,0 +1,301 @@
+//! CODEOWNERS-style assignment of proto files to the teams that own them
+//!
+//! Each non-empty line of an owners file holds a path pattern followed by
+//! the teams owning the files it matches, and the last matching line wins.
+//! Lines starting with `#` are comments, and a pattern with no teams marks
+//! its files as unowned.  Patterns follow `CODEOWNERS` rules: `*` and `?`
+//! match within a path component, `**` matches any number of components, a
+//! pattern containing a `/` other than a trailing one is relative to the
+//! directory of the owners file, and any other pattern matches at any depth.
+
+use std::{
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{ensure, Context, Result};
+
+#[derive(Debug)]
+struct Rule {
+    pattern: Vec<String>,
+    /// Whether the pattern only matches directories
+    dir: bool,
+    teams: Vec<String>,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Result<Option<Self>> {
+        let mut words = line.split_whitespace();
+        let Some(pat) = words.next().filter(|w| !w.starts_with('#')) else {
+            return Ok(None);
+        };
+        let teams = words
+            .take_while(|w| !w.starts_with('#'))
+            .map(Into::into)
+            .collect();
+
+        let dir = pat.ends_with('/');
+        let pat = pat.trim_end_matches('/');
+        let anchored = pat.contains('/');
+        let pat = pat.trim_start_matches('/');
+        ensure!(!pat.is_empty(), "Empty path pattern");
+
+        let pattern = (!anchored)
+            .then(|| "**".to_owned())
+            .into_iter()
+            .chain(pat.split('/').map(Into::into))
+            .collect();
+
+        Ok(Some(Self {
+            pattern,
+            dir,
+            teams,
+        }))
+    }
+
+    /// Returns true if the pattern matches the given path or any directory
+    /// containing it
+    fn matches(&self, path: &[&str]) -> bool {
+        let len = if self.dir {
+            path.len().saturating_sub(1)
+        } else {
+            path.len()
+        };
+
+        (1..=len).any(|n| match_path(&self.pattern, &path[..n]))
+    }
+}
+
+fn match_path(pat: &[String], path: &[&str]) -> bool {
+    match pat.split_first() {
+        None => path.is_empty(),
+        Some((p, rest)) if p == "**" => (0..=path.len()).any(|i| match_path(rest, &path[i..])),
+        Some((p, rest)) => path.split_first().is_some_and(|(s, path)| {
+            match_component(p.as_bytes(), s.as_bytes()) && match_path(rest, path)
+        }),
+    }
+}
+
+fn match_component(pat: &[u8], s: &[u8]) -> bool {
+    match pat.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => (0..=s.len()).any(|i| match_component(rest, &s[i..])),
+        Some((&p, rest)) => s
+            .split_first()
+            .is_some_and(|(&c, s)| (p == b'?' || p == c) && match_component(rest, s)),
+    }
+}
+
+/// Ownership rules read from an owners file
+#[derive(Debug)]
+pub struct Owners {
+    root: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Owners {
+    /// Read an owners file, resolving its patterns against the directory it
+    /// is in
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Error reading owners file {}", path.display()))?;
+
+        let rules = text
+            .lines()
+            .enumerate()
+            .filter_map(|(i, l)| {
+                Rule::parse(l)
+                    .with_context(|| {
+                        format!("Invalid rule on line {} of {}", i + 1, path.display())
+                    })
+                    .transpose()
+            })
+            .collect::<Result<_>>()?;
+
+        let root = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+
+        Ok(Self {
+            root: root.canonicalize().unwrap_or_else(|_| root.into()),
+            rules,
+        })
+    }
+
+    /// Get the teams owning the given file, or `None` if it is unowned
+    pub fn owners(&self, file: impl AsRef<Path>) -> Option<&[String]> {
+        let file = file.as_ref();
+        let file = file
+            .canonicalize()
+            .ok()
+            .and_then(|f| f.strip_prefix(&self.root).ok().map(Path::to_path_buf))
+            .unwrap_or_else(|| file.into());
+
+        let path: Vec<_> = file
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(s) => s.to_str(),
+                _ => None,
+            })
+            .collect();
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| r.matches(&path))
+            .map(|r| &*r.teams)
+            .filter(|t| !t.is_empty())
+    }
+
+    /// Get the teams whose approval is needed for breaking changes to the
+    /// given file made by the given team, or `None` if the file is unowned or
+    /// owned by that team
+    pub fn approvers(&self, file: impl AsRef<Path>, team: &str) -> Option<&[String]> {
+        self.owners(file).filter(|t| !t.iter().any(|t| t == team))
+    }
+}
+
+/// A compatibility check failed for a file owned by other teams
+#[derive(Debug)]
+pub struct ApprovalRequired {
+    /// The file that failed the check
+    pub file: String,
+    /// The teams owning the file
+    pub owners: Vec<String>,
+}
+
+impl fmt::Display for ApprovalRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Breaking changes to {} need approval from {}",
+            self.file,
+            self.owners.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ApprovalRequired {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn load(rules: &str) -> (tempfile::TempDir, Owners) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("OWNERS");
+        fs::write(&path, rules).unwrap();
+        let owners = Owners::load(path).unwrap();
+        (dir, owners)
+    }
+
+    fn teams<'a>(owners: &'a Owners, file: &str) -> Option<Vec<&'a str>> {
+        owners.owners(file).map(|t| t.iter().map(String::as_str).collect())
+    }
+
+    #[test]
+    fn components() {
+        let m = |p: &str, s: &str| match_component(p.as_bytes(), s.as_bytes());
+
+        assert!(m("user.proto", "user.proto"));
+        assert!(m("*.proto", "user.proto"));
+        assert!(m("*", ""));
+        assert!(m("us?r.*", "user.proto"));
+        assert!(m("*_v*.proto", "user_v2.proto"));
+        assert!(!m("*.proto", "user.proto.bak"));
+        assert!(!m("us?r.proto", "usr.proto"));
+        assert!(!m("user.proto", "User.proto"));
+    }
+
+    #[test]
+    fn globs() {
+        let (_dir, owners) = load(
+            "
+            *.proto @any
+            /api/*.proto @api
+            /internal/**/secret.proto @security
+            /gen/ @build
+            ",
+        );
+
+        assert_eq!(teams(&owners, "user.proto"), Some(vec!["@any"]));
+        assert_eq!(teams(&owners, "deep/nested/user.proto"), Some(vec!["@any"]));
+        assert_eq!(teams(&owners, "api/user.proto"), Some(vec!["@api"]));
+        assert_eq!(teams(&owners, "api/v1/user.proto"), Some(vec!["@any"]));
+        assert_eq!(teams(&owners, "other/api/user.proto"), Some(vec!["@any"]));
+        assert_eq!(teams(&owners, "internal/secret.proto"), Some(vec!["@security"]));
+        assert_eq!(teams(&owners, "internal/a/b/secret.proto"), Some(vec!["@security"]));
+        assert_eq!(teams(&owners, "gen/types.proto"), Some(vec!["@build"]));
+        assert_eq!(teams(&owners, "gen/v1/types.proto"), Some(vec!["@build"]));
+        assert_eq!(teams(&owners, "README"), None);
+    }
+
+    #[test]
+    fn directories() {
+        let (_dir, owners) = load("gen/ @build\ndocs @docs");
+
+        // A trailing slash only matches directories, at any depth
+        assert_eq!(teams(&owners, "gen"), None);
+        assert_eq!(teams(&owners, "gen/types.proto"), Some(vec!["@build"]));
+        assert_eq!(teams(&owners, "a/gen/types.proto"), Some(vec!["@build"]));
+        assert_eq!(teams(&owners, "docs"), Some(vec!["@docs"]));
+        assert_eq!(teams(&owners, "a/docs/index.proto"), Some(vec!["@docs"]));
+    }
+
+    #[test]
+    fn precedence() {
+        let (_dir, owners) = load(
+            "
+            # Everything defaults to the platform team
+            * @platform @infra # trailing comment
+            /billing/ @billing
+            /billing/legacy/*.proto
+            /billing/**/tax.proto @billing @tax
+            ",
+        );
+
+        assert_eq!(teams(&owners, "user.proto"), Some(vec!["@platform", "@infra"]));
+        assert_eq!(teams(&owners, "billing/invoice.proto"), Some(vec!["@billing"]));
+        assert_eq!(teams(&owners, "billing/legacy/invoice.proto"), None);
+        assert_eq!(teams(&owners, "billing/legacy/tax.proto"), Some(vec!["@billing", "@tax"]));
+    }
+
+    #[test]
+    fn fallback() {
+        let (_dir, owners) = load("# No rules yet\n\n");
+        assert_eq!(teams(&owners, "user.proto"), None);
+        assert_eq!(owners.approvers("user.proto", "@api"), None);
+
+        let (_dir, owners) = load("/api/ @api @platform");
+        assert_eq!(owners.approvers("other.proto", "@billing"), None);
+        assert_eq!(owners.approvers("api/user.proto", "@api"), None);
+        assert_eq!(
+            owners.approvers("api/user.proto", "@billing"),
+            Some(&["@api".to_owned(), "@platform".to_owned()][..])
+        );
+    }
+
+    #[test]
+    fn absolute_paths() {
+        let (dir, owners) = load("/api/ @api");
+        let file = dir.path().join("api/user.proto");
+        fs::create_dir(dir.path().join("api")).unwrap();
+        fs::write(&file, "").unwrap();
+
+        assert_eq!(owners.owners(&file), Some(&["@api".to_owned()][..]));
+        assert_eq!(owners.owners(dir.path().join("user.proto")), None);
+    }
+
+    #[test]
+    fn invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("OWNERS");
+        fs::write(&path, "*.proto @api\n/ @root\n").unwrap();
+
+        let err = Owners::load(&path).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid rule on line 2 of "));
+        assert!(Owners::load(dir.path().join("missing")).is_err());
+    }
+}
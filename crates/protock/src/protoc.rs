@@ -4,7 +4,41 @@ use anyhow::{Context, Result};
 use prost::Message;
 use prost_types::FileDescriptorSet;
 
-pub fn get_descriptor_set<I: IntoIterator>(files: I) -> Result<FileDescriptorSet>
+/// A decoded descriptor set, along with its encoded form
+///
+/// Decoding discards extension fields, so the encoded form is kept to recover
+/// custom options (e.g. `(validate.rules)`) from.
+#[derive(Debug)]
+pub struct Descriptors {
+    pub set: FileDescriptorSet,
+    pub raw: Vec<u8>,
+}
+
+impl Descriptors {
+    pub fn decode(raw: Vec<u8>) -> Result<Self, prost::DecodeError> {
+        let set = FileDescriptorSet::decode(&*raw)?;
+        Ok(Self { set, raw })
+    }
+}
+
+/// Get the version string reported by `protoc`, e.g. `libprotoc 29.2`
+pub fn version() -> Result<String> {
+    let out = std::process::Command::new("protoc")
+        .arg("--version")
+        .output()
+        .context("Error running protoc")?;
+
+    if !out.status.success() {
+        anyhow::bail!(
+            "protoc exited with code {}",
+            out.status.code().unwrap_or(-1)
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_owned())
+}
+
+pub fn get_descriptor_set<I: IntoIterator>(files: I) -> Result<Descriptors>
 where I::Item: AsRef<Path> {
     let mut tmp = tempfile::NamedTempFile::new().context("Error creating descriptor tempfile")?;
 
@@ -41,5 +75,5 @@ where I::Item: AsRef<Path> {
         .read_to_end(&mut bytes)
         .context("Error reading descriptor set")?;
 
-    FileDescriptorSet::decode(&*bytes).context("Error decoding descriptor set")
+    Descriptors::decode(bytes).context("Error decoding descriptor set")
 }
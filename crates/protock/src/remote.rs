@@ -0,0 +1,418 @@
+//! Fetching of baseline schemas from HTTP servers and OCI artifact registries
+
+use std::{env, path::Path};
+
+use anyhow::{Context, Result};
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    header, StatusCode, Url,
+};
+
+use crate::protoc::Descriptors;
+
+const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+
+const TOKEN_VAR: &str = "PROTOCK_REGISTRY_TOKEN";
+const USER_VAR: &str = "PROTOCK_REGISTRY_USER";
+const PASSWORD_VAR: &str = "PROTOCK_REGISTRY_PASSWORD";
+
+/// A schema fetched from a remote source
+#[derive(Debug)]
+pub enum Artifact {
+    /// A compiled descriptor set
+    DescriptorSet(Descriptors),
+    /// The source text of a `.proto` file
+    Proto(Vec<u8>),
+}
+
+impl Artifact {
+    fn new(name: &str, bytes: Vec<u8>) -> Result<Self> {
+        if Path::new(name)
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("proto"))
+        {
+            Ok(Self::Proto(bytes))
+        } else {
+            Descriptors::decode(bytes)
+                .map(Self::DescriptorSet)
+                .context("Error decoding remote descriptor set")
+        }
+    }
+}
+
+/// Credentials for a remote source, read from the environment
+#[derive(Debug)]
+enum Auth {
+    None,
+    Bearer(String),
+    Basic(String, Option<String>),
+}
+
+impl Auth {
+    fn from_env() -> Self {
+        if let Ok(token) = env::var(TOKEN_VAR) {
+            Self::Bearer(token)
+        } else if let Ok(user) = env::var(USER_VAR) {
+            Self::Basic(user, env::var(PASSWORD_VAR).ok())
+        } else {
+            Self::None
+        }
+    }
+
+    fn apply(&self, req: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::None => req,
+            Self::Bearer(t) => req.bearer_auth(t),
+            Self::Basic(u, p) => req.basic_auth(u, p.as_ref()),
+        }
+    }
+}
+
+fn check(res: Response, what: &str) -> Result<Response> {
+    let status = res.status();
+    anyhow::ensure!(
+        status.is_success(),
+        "Error fetching {what}: server returned {status}"
+    );
+    Ok(res)
+}
+
+/// Fetch a baseline schema from the given URL
+///
+/// URLs with an `http` or `https` scheme are downloaded directly.  URLs of the
+/// form `oci://registry/repository:tag` (or `@digest`) are resolved using the
+/// OCI distribution API, taking the first layer of the referenced manifest.
+/// In either case the content is treated as `.proto` source if its name ends
+/// in `.proto`, and as a binary descriptor set otherwise.
+///
+/// Credentials are read from `PROTOCK_REGISTRY_TOKEN`, or from
+/// `PROTOCK_REGISTRY_USER` and `PROTOCK_REGISTRY_PASSWORD`.
+pub fn fetch(url: &Url) -> Result<Artifact> {
+    let client = Client::builder()
+        .user_agent(concat!("protock/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("Error building HTTP client")?;
+    let auth = Auth::from_env();
+
+    match url.scheme() {
+        "http" | "https" => {
+            let res = auth
+                .apply(client.get(url.clone()))
+                .send()
+                .with_context(|| format!("Error requesting {url}"))?;
+            let bytes = check(res, url.as_str())?
+                .bytes()
+                .context("Error reading response body")?;
+            Artifact::new(url.path(), bytes.into())
+        },
+        "oci" => Registry::new(client, auth, url)?.fetch(),
+        s => anyhow::bail!("Unsupported URL scheme {s:?}"),
+    }
+}
+
+#[derive(Debug)]
+struct Registry {
+    client: Client,
+    auth: Auth,
+    base: Url,
+    repo: String,
+    reference: String,
+}
+
+impl Registry {
+    fn new(client: Client, auth: Auth, url: &Url) -> Result<Self> {
+        let host = url
+            .host_str()
+            .context("OCI URL is missing a registry host")?;
+        let path = url.path().trim_start_matches('/');
+        let (repo, reference) = path
+            .rsplit_once('@')
+            .or_else(|| {
+                let (repo, tag) = path.rsplit_once(':')?;
+                (!tag.contains('/')).then_some((repo, tag))
+            })
+            .unwrap_or((path, "latest"));
+        anyhow::ensure!(!repo.is_empty(), "OCI URL is missing a repository");
+
+        let mut base: Url = format!("https://{host}/v2/")
+            .parse()
+            .context("Error building registry URL")?;
+        if let Some(port) = url.port() {
+            base.set_port(Some(port))
+                .map_err(|()| anyhow::anyhow!("Invalid registry port"))?;
+        }
+
+        Ok(Self {
+            client,
+            auth,
+            base,
+            repo: repo.into(),
+            reference: reference.into(),
+        })
+    }
+
+    fn get(&mut self, path: &str, accept: Option<&str>) -> Result<Response> {
+        let url = self
+            .base
+            .join(&format!("{}/{path}", self.repo))
+            .context("Error building registry URL")?;
+        let send = |auth: &Auth| {
+            let mut req = auth.apply(self.client.get(url.clone()));
+            if let Some(accept) = accept {
+                req = req.header(header::ACCEPT, accept);
+            }
+            req.send()
+                .with_context(|| format!("Error requesting {url}"))
+        };
+
+        let res = send(&self.auth)?;
+        if res.status() != StatusCode::UNAUTHORIZED {
+            return check(res, url.as_str());
+        }
+
+        // Most registries require exchanging credentials (or nothing, for
+        // anonymous pulls) for a token from the realm they advertise
+        let challenge = res
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .context("Registry rejected credentials")?;
+        self.auth = Auth::Bearer(self.token(challenge)?);
+
+        check(send(&self.auth)?, url.as_str())
+    }
+
+    fn token(&self, challenge: &str) -> Result<String> {
+        let mut realm = None;
+        let mut params = vec![];
+
+        for param in challenge.split(',') {
+            let Some((key, val)) = param.trim().split_once('=') else {
+                continue;
+            };
+            let val = val.trim_matches('"');
+
+            if key == "realm" {
+                realm = Some(val);
+            } else {
+                params.push((key, val));
+            }
+        }
+
+        let realm: Url = realm
+            .context("Registry auth challenge is missing a realm")?
+            .parse()
+            .context("Invalid registry auth realm")?;
+        let basic = match self.auth {
+            Auth::Basic(..) => &self.auth,
+            _ => &Auth::None,
+        };
+
+        let res = basic
+            .apply(self.client.get(realm.clone()).query(&params))
+            .send()
+            .with_context(|| format!("Error requesting token from {realm}"))?;
+        let body: serde_json::Value = check(res, realm.as_str())?
+            .json()
+            .context("Error decoding registry token")?;
+
+        body.get("token")
+            .or_else(|| body.get("access_token"))
+            .and_then(serde_json::Value::as_str)
+            .map(ToOwned::to_owned)
+            .context("Registry token response is missing a token")
+    }
+
+    fn fetch(mut self) -> Result<Artifact> {
+        let manifest: serde_json::Value = self
+            .get(&format!("manifests/{}", self.reference), Some(OCI_MANIFEST))?
+            .json()
+            .context("Error decoding OCI manifest")?;
+
+        let layer = manifest
+            .get("layers")
+            .and_then(serde_json::Value::as_array)
+            .and_then(|l| l.first())
+            .context("OCI manifest has no layers")?;
+        let digest = layer
+            .get("digest")
+            .and_then(serde_json::Value::as_str)
+            .context("OCI manifest layer is missing a digest")?
+            .to_owned();
+        let name = layer
+            .get("annotations")
+            .and_then(|a| a.get(TITLE_ANNOTATION))
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+            .to_owned();
+
+        let bytes = self
+            .get(&format!("blobs/{digest}"), None)?
+            .bytes()
+            .context("Error reading OCI blob")?;
+        Artifact::new(&name, bytes.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    use prost::Message;
+
+    use super::*;
+
+    /// Serve one canned response per request on a local port, returning the
+    /// base URL of the server and a receiver for the request lines it got
+    fn serve(responses: Vec<(u16, Vec<u8>)>) -> (Url, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                tx.send(line.trim_end().to_owned()).unwrap();
+
+                // Skip the rest of the request head
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+
+        (url.parse().unwrap(), rx)
+    }
+
+    fn registry(url: &str) -> Result<Registry> {
+        Registry::new(Client::new(), Auth::None, &url.parse().unwrap())
+    }
+
+    #[test]
+    fn references() {
+        let parts = |url| {
+            let r = registry(url).unwrap();
+            (r.base.to_string(), r.repo, r.reference)
+        };
+
+        assert_eq!(
+            parts("oci://ghcr.io/acme/schemas:v1.2"),
+            ("https://ghcr.io/v2/".into(), "acme/schemas".into(), "v1.2".into())
+        );
+        assert_eq!(
+            parts("oci://ghcr.io/acme/schemas"),
+            ("https://ghcr.io/v2/".into(), "acme/schemas".into(), "latest".into())
+        );
+        assert_eq!(
+            parts("oci://localhost:5000/schemas@sha256:abc123"),
+            (
+                "https://localhost:5000/v2/".into(),
+                "schemas".into(),
+                "sha256:abc123".into()
+            )
+        );
+    }
+
+    #[test]
+    fn bad_references() {
+        let err = |url| registry(url).unwrap_err().to_string();
+
+        assert_eq!(err("oci:acme/schemas"), "OCI URL is missing a registry host");
+        assert_eq!(err("oci://ghcr.io"), "OCI URL is missing a repository");
+        assert_eq!(err("oci://ghcr.io/:v1"), "OCI URL is missing a repository");
+    }
+
+    #[test]
+    fn artifacts() {
+        let set = prost_types::FileDescriptorSet::default().encode_to_vec();
+
+        assert!(matches!(
+            Artifact::new("schema.PROTO", b"syntax = \"proto3\";".to_vec()),
+            Ok(Artifact::Proto(b)) if b == b"syntax = \"proto3\";"
+        ));
+        assert!(matches!(
+            Artifact::new("schema.pb", set),
+            Ok(Artifact::DescriptorSet(_))
+        ));
+        assert_eq!(
+            Artifact::new("", vec![0xff]).unwrap_err().to_string(),
+            "Error decoding remote descriptor set"
+        );
+    }
+
+    #[test]
+    fn unsupported_scheme() {
+        let err = fetch(&"ftp://example.com/schema.pb".parse().unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported URL scheme \"ftp\"");
+    }
+
+    #[test]
+    fn fetch_http() {
+        let (base, rx) = serve(vec![(200, b"message Msg {}".to_vec()), (404, vec![])]);
+
+        let url = base.join("v1/schema.proto").unwrap();
+        assert!(matches!(fetch(&url), Ok(Artifact::Proto(b)) if b == b"message Msg {}"));
+        assert_eq!(rx.recv().unwrap(), "GET /v1/schema.proto HTTP/1.1");
+
+        let url = base.join("missing.pb").unwrap();
+        assert_eq!(
+            fetch(&url).unwrap_err().to_string(),
+            format!("Error fetching {url}: server returned 404 Not Found")
+        );
+    }
+
+    #[test]
+    fn tokens() {
+        let (base, rx) = serve(vec![
+            (200, br#"{"token":"abc"}"#.to_vec()),
+            (200, br#"{"access_token":"def"}"#.to_vec()),
+            (200, b"{}".to_vec()),
+            (401, vec![]),
+        ]);
+        let realm = base.join("token").unwrap();
+        let registry = registry("oci://localhost/schemas").unwrap();
+        let challenge = format!(r#"realm="{realm}",service="registry",scope="repository:a:pull""#);
+
+        assert_eq!(registry.token(&challenge).unwrap(), "abc");
+        assert_eq!(
+            rx.recv().unwrap(),
+            "GET /token?service=registry&scope=repository%3Aa%3Apull HTTP/1.1"
+        );
+        assert_eq!(registry.token(&challenge).unwrap(), "def");
+        assert_eq!(
+            registry.token(&challenge).unwrap_err().to_string(),
+            "Registry token response is missing a token"
+        );
+        assert_eq!(
+            registry.token(&challenge).unwrap_err().to_string(),
+            format!("Error fetching {realm}: server returned 401 Unauthorized")
+        );
+
+        assert_eq!(
+            registry.token(r#"service="registry""#).unwrap_err().to_string(),
+            "Registry auth challenge is missing a realm"
+        );
+        assert_eq!(
+            registry.token(r#"realm="not a url""#).unwrap_err().to_string(),
+            "Invalid registry auth realm"
+        );
+    }
+}
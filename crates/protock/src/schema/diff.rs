@@ -0,0 +1,421 @@
+//! Structural diff of two schemas, independent of compatibility mode
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::{json, Value};
+use shrec::range_set::RangeSet;
+
+use super::Schema;
+use crate::schema::{
+    field::{Field, Presence},
+    record::{Record, RecordValue},
+    ty::Type,
+    variant::Variant,
+};
+
+/// Flattened view of a single field or enum variant
+#[derive(PartialEq, Eq)]
+struct Member {
+    names: BTreeSet<String>,
+    ty: Option<String>,
+    label: Option<&'static str>,
+    presence: Option<Presence>,
+}
+
+impl Member {
+    fn field(field: &Field) -> Self {
+        Self {
+            names: [field.name().to_owned()].into(),
+            ty: Some(field.ty().to_string()),
+            label: Some(field.kind().label()),
+            presence: field.presence().cloned(),
+        }
+    }
+
+    fn variant(variant: &Variant) -> Self {
+        Self {
+            names: variant.names().map(ToOwned::to_owned).collect(),
+            ty: None,
+            label: None,
+            presence: None,
+        }
+    }
+
+    fn to_json(&self, number: i32) -> Value {
+        let mut names = self.names.iter();
+        let mut obj = json!({
+            "number": number,
+            "name": names.next(),
+        });
+
+        let aliases: Vec<_> = names.collect();
+        if !aliases.is_empty() {
+            obj["aliases"] = json!(aliases);
+        }
+
+        if let Some(ty) = &self.ty {
+            obj["type"] = json!(ty);
+        }
+
+        if let Some(label) = self.label {
+            obj["label"] = json!(label);
+        }
+
+        match &self.presence {
+            None => (),
+            Some(Presence::Implicit) => obj["presence"] = json!("implicit"),
+            Some(Presence::Explicit { default }) => {
+                obj["presence"] = json!("explicit");
+                if let Some(default) = default {
+                    obj["default"] = json!(default);
+                }
+            },
+        }
+
+        obj
+    }
+}
+
+fn members<T: for<'a> RecordValue<'a>>(
+    rec: &Record<T>,
+    f: impl Fn(&T) -> Member,
+) -> BTreeMap<i32, Member> {
+    rec.numbers().iter().map(|(&k, v)| (k, f(v))).collect()
+}
+
+fn pair(old: (i32, &Member), new: (i32, &Member)) -> Value {
+    json!({ "old": old.1.to_json(old.0), "new": new.1.to_json(new.0) })
+}
+
+/// Diff the members of a record, matching them first by name and then by
+/// number
+fn diff_members(
+    old: &BTreeMap<i32, Member>,
+    new: &BTreeMap<i32, Member>,
+    out: &mut serde_json::Map<String, Value>,
+) {
+    let by_name = |m: &BTreeMap<i32, Member>| -> BTreeMap<String, i32> {
+        m.iter()
+            .flat_map(|(&k, v)| v.names.iter().map(move |n| (n.clone(), k)))
+            .collect()
+    };
+    let (old_names, new_names) = (by_name(old), by_name(new));
+
+    let mut renumbered = vec![];
+    let mut moved_old = BTreeSet::new();
+    let mut moved_new = BTreeSet::new();
+    for (name, &old_num) in &old_names {
+        let Some(&new_num) = new_names.get(name) else {
+            continue;
+        };
+
+        if old_num != new_num && moved_old.insert(old_num) {
+            moved_new.insert(new_num);
+            renumbered.push(pair((old_num, &old[&old_num]), (new_num, &new[&new_num])));
+        }
+    }
+
+    let mut added = vec![];
+    let mut removed = vec![];
+    let mut renamed = vec![];
+    let mut retyped = vec![];
+    for (&num, old_memb) in old {
+        match new.get(&num) {
+            Some(new_memb) if !moved_old.contains(&num) && !moved_new.contains(&num) => {
+                if old_memb.names != new_memb.names {
+                    renamed.push(pair((num, old_memb), (num, new_memb)));
+                }
+
+                if (&old_memb.ty, old_memb.label, &old_memb.presence)
+                    != (&new_memb.ty, new_memb.label, &new_memb.presence)
+                {
+                    retyped.push(pair((num, old_memb), (num, new_memb)));
+                }
+            },
+            _ if moved_old.contains(&num) => (),
+            _ => removed.push(old_memb.to_json(num)),
+        }
+    }
+
+    for (&num, new_memb) in new {
+        let paired = old.contains_key(&num) && !moved_old.contains(&num);
+        if !paired && !moved_new.contains(&num) {
+            added.push(new_memb.to_json(num));
+        }
+    }
+
+    for (key, list) in [
+        ("added", added),
+        ("removed", removed),
+        ("renamed", renamed),
+        ("renumbered", renumbered),
+        ("retyped", retyped),
+    ] {
+        if !list.is_empty() {
+            out.insert(key.into(), list.into());
+        }
+    }
+}
+
+fn ranges_json(set: &RangeSet<i64>) -> Vec<Value> {
+    set.ranges()
+        .map(|p| json!({ "start": p.start, "end": p.end }))
+        .collect()
+}
+
+/// Diff the reserved numbers and names of a record
+fn diff_reserved<T: for<'a> RecordValue<'a>>(
+    old: &Record<T>,
+    new: &Record<T>,
+    out: &mut serde_json::Map<String, Value>,
+) {
+    let added = new
+        .reserved()
+        .clone()
+        .intersected(&old.reserved().clone().inverted());
+    let removed = old
+        .reserved()
+        .clone()
+        .intersected(&new.reserved().clone().inverted());
+    let (added, removed) = (ranges_json(&added), ranges_json(&removed));
+
+    if !added.is_empty() || !removed.is_empty() {
+        out.insert(
+            "reserved_ranges".into(),
+            json!({ "added": added, "removed": removed }),
+        );
+    }
+
+    let old_names: BTreeSet<_> = old.reserved_names().collect();
+    let new_names: BTreeSet<_> = new.reserved_names().collect();
+    let added: Vec<_> = new_names.difference(&old_names).collect();
+    let removed: Vec<_> = old_names.difference(&new_names).collect();
+
+    if !added.is_empty() || !removed.is_empty() {
+        out.insert(
+            "reserved_names".into(),
+            json!({ "added": added, "removed": removed }),
+        );
+    }
+}
+
+fn diff_type(name: &str, old: &Type, new: &Type) -> Option<Value> {
+    let mut out = serde_json::Map::new();
+    out.insert("name".into(), name.into());
+    out.insert("kind".into(), new.var_pretty().into());
+
+    if let (Some(old), Some(new)) = (old.as_message(), new.as_message()) {
+        diff_members(
+            &members(old, Member::field),
+            &members(new, Member::field),
+            &mut out,
+        );
+        diff_reserved(old, new, &mut out);
+    } else if let (Some(old), Some(new)) = (old.as_enum(), new.as_enum()) {
+        diff_members(
+            &members(old, Member::variant),
+            &members(new, Member::variant),
+            &mut out,
+        );
+        diff_reserved(old, new, &mut out);
+    } else {
+        out.insert("old_kind".into(), old.var_pretty().into());
+    }
+
+    (out.len() > 2).then_some(out.into())
+}
+
+fn sorted_types(schema: &Schema) -> BTreeMap<String, &Type> {
+    schema
+        .types
+        .0
+        .iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect()
+}
+
+impl Schema {
+    /// Produce a machine-readable inventory of the structural differences
+    /// between two schemas
+    ///
+    /// Fields and variants are matched by name first, so a member whose name
+    /// is kept but whose number changes is reported as renumbered; members
+    /// that keep their number but change name are reported as renamed.
+    /// Reserved ranges are half-open, with `null` bounds being unbounded.
+    /// All lists are sorted, so the output is stable across runs.
+    #[must_use]
+    pub fn diff(old: &Self, old_name: &str, new: &Self, new_name: &str) -> Value {
+        let (old_types, new_types) = (sorted_types(old), sorted_types(new));
+
+        let summary =
+            |(name, ty): (&String, &&Type)| json!({ "name": name, "kind": ty.var_pretty() });
+
+        let added: Vec<_> = new_types
+            .iter()
+            .filter(|(k, _)| !old_types.contains_key(*k))
+            .map(summary)
+            .collect();
+        let removed: Vec<_> = old_types
+            .iter()
+            .filter(|(k, _)| !new_types.contains_key(*k))
+            .map(summary)
+            .collect();
+        let changed: Vec<_> = old_types
+            .iter()
+            .filter_map(|(k, o)| diff_type(k, o, new_types.get(k)?))
+            .collect();
+
+        json!({
+            "old": old_name,
+            "new": new_name,
+            "added_types": added,
+            "removed_types": removed,
+            "changed_types": changed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::schema::{test_util::compile_str, Schema};
+
+    fn diff(old: &str, new: &str) -> Value {
+        let schema = |body| compile_str(&format!("syntax = \"proto3\";\npackage test;\n{body}"));
+        Schema::diff(&schema(old), "old", &schema(new), "new")
+    }
+
+    /// Diff two versions of a message, returning its entry in
+    /// `changed_types`
+    fn changed(old: &str, new: &str) -> Value {
+        let diff = diff(&format!("message Msg {{ {old} }}"), &format!("message Msg {{ {new} }}"));
+        assert_eq!(diff["added_types"], json!([]));
+        assert_eq!(diff["removed_types"], json!([]));
+
+        match diff["changed_types"].as_array().unwrap().as_slice() {
+            [] => Value::Null,
+            [ty] => ty.clone(),
+            t => panic!("Expected one changed type, got {t:?}"),
+        }
+    }
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(
+            diff("message Msg { int32 a = 1; }", "message Msg { int32 a = 1; }"),
+            json!({
+                "old": "old",
+                "new": "new",
+                "added_types": [],
+                "removed_types": [],
+                "changed_types": [],
+            })
+        );
+    }
+
+    #[test]
+    fn added_field() {
+        assert_eq!(
+            changed("int32 a = 1;", "int32 a = 1; optional string b = 2;"),
+            json!({
+                "name": "test.Msg",
+                "kind": "message",
+                "added": [{
+                    "number": 2,
+                    "name": "b",
+                    "type": "string",
+                    "label": "optional",
+                    "presence": "explicit",
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn removed_field() {
+        assert_eq!(
+            changed("int32 a = 1; repeated int64 b = 2;", "int32 a = 1;"),
+            json!({
+                "name": "test.Msg",
+                "kind": "message",
+                "removed": [{
+                    "number": 2,
+                    "name": "b",
+                    "type": "int64",
+                    "label": "repeated",
+                }],
+            })
+        );
+    }
+
+    /// The JSON for a singular proto3 field
+    fn field(number: i32, name: &str, ty: &str) -> Value {
+        json!({
+            "number": number,
+            "name": name,
+            "type": ty,
+            "label": "singular",
+            "presence": "implicit",
+        })
+    }
+
+    #[test]
+    fn changed_fields() {
+        assert_eq!(
+            changed(
+                "int32 a = 1; int32 b = 2; int32 c = 3;",
+                "int64 a = 1; int32 d = 2; int32 c = 4;"
+            ),
+            json!({
+                "name": "test.Msg",
+                "kind": "message",
+                "renamed": [{ "old": field(2, "b", "int32"), "new": field(2, "d", "int32") }],
+                "renumbered": [{ "old": field(3, "c", "int32"), "new": field(4, "c", "int32") }],
+                "retyped": [{ "old": field(1, "a", "int32"), "new": field(1, "a", "int64") }],
+            })
+        );
+    }
+
+    #[test]
+    fn reserved() {
+        assert_eq!(
+            changed(
+                "reserved 2 to 4; reserved \"b\";",
+                "reserved 3 to 5; reserved \"c\";"
+            ),
+            json!({
+                "name": "test.Msg",
+                "kind": "message",
+                "reserved_ranges": {
+                    "added": [{ "start": 5, "end": 6 }],
+                    "removed": [{ "start": 2, "end": 3 }],
+                },
+                "reserved_names": { "added": ["c"], "removed": ["b"] },
+            })
+        );
+    }
+
+    #[test]
+    fn types() {
+        let diff = diff(
+            "message Msg {} enum Kind { KIND_A = 0; KIND_B = 1; } message Old {}",
+            "enum Msg { MSG_A = 0; } enum Kind { KIND_A = 0; KIND_C = 2; } message New {}",
+        );
+
+        assert_eq!(diff["added_types"], json!([{ "name": "test.New", "kind": "message" }]));
+        assert_eq!(diff["removed_types"], json!([{ "name": "test.Old", "kind": "message" }]));
+        assert_eq!(
+            diff["changed_types"],
+            json!([
+                {
+                    "name": "test.Kind",
+                    "kind": "enum",
+                    "added": [{ "number": 2, "name": "KIND_C" }],
+                    "removed": [{ "number": 1, "name": "KIND_B" }],
+                },
+                { "name": "test.Msg", "kind": "enum", "old_kind": "message" },
+            ])
+        );
+    }
+}
@@ -1,22 +1,78 @@
+use std::fmt;
+
 use super::{
-    field_kind::FieldKind,
+    field_kind::{FieldKind, Syntax},
     field_type::{FieldType, FieldTypeContext},
     oneof,
-    primitive::{VarIntMode, WireType},
+    primitive::{PrimitiveType, VarIntMode, WireType},
     qual_name::MemberQualName,
     record::{RecordContext, RecordExtra, RecordValue},
     ty::{TypeCheckKind, TypeContext},
+    TypeMap,
 };
 use crate::{
     check_compat::{CheckCompat, CompatError, CompatLog},
     compat_pair::{CompatPair, Side},
 };
 
+/// Whether a singular field tracks if it was set, which decides how values
+/// equal to its default are written and read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Presence {
+    /// A proto3 scalar field, which is not written when set to its type's
+    /// zero value and reads as that value when absent
+    Implicit,
+    /// A proto2, `optional`, oneof or message field, which is written
+    /// whenever set and reads as unset when absent
+    Explicit {
+        /// The value an unset field reads as, if not its type's zero value
+        default: Option<String>,
+    },
+}
+
+impl fmt::Display for Presence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Implicit => f.write_str("implicit presence"),
+            Self::Explicit { default: None } => f.write_str("explicit presence"),
+            Self::Explicit { default: Some(d) } => write!(f, "explicit presence (default {d:?})"),
+        }
+    }
+}
+
+impl Presence {
+    /// Determine the presence of a field, returning `None` for repeated and
+    /// map fields
+    pub fn new(
+        kind: FieldKind,
+        syntax: Syntax,
+        message: bool,
+        oneof: bool,
+        default: Option<String>,
+    ) -> Option<Self> {
+        match kind {
+            FieldKind::Repeated { .. } | FieldKind::Map => None,
+            FieldKind::Singular if syntax == Syntax::Proto3 && !message && !oneof => {
+                // protoc rejects these, but other descriptor sources may not
+                if let Some(default) = default {
+                    tracing::warn!(
+                        ?default,
+                        "Ignoring default value of proto3 field without presence"
+                    );
+                }
+                Some(Self::Implicit)
+            },
+            FieldKind::Singular | FieldKind::Optional => Some(Self::Explicit { default }),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Field {
     name: String,
     ty: FieldType,
     kind: FieldKind,
+    presence: Option<Presence>,
     oneof: Option<oneof::OneofId>,
 }
 
@@ -31,22 +87,79 @@ impl Field {
         name: String,
         ty: FieldType,
         kind: FieldKind,
+        presence: Option<Presence>,
         oneof: Option<oneof::OneofId>,
     ) -> Self {
         Self {
             name,
             ty,
             kind,
+            presence,
             oneof,
         }
     }
 
+    #[inline]
+    pub fn name(&self) -> &str { &self.name }
+
+    #[inline]
+    pub const fn ty(&self) -> &FieldType { &self.ty }
+
+    #[inline]
+    pub const fn kind(&self) -> FieldKind { self.kind }
+
+    #[inline]
+    pub const fn presence(&self) -> Option<&Presence> { self.presence.as_ref() }
+
+    /// The explicit default of this field, or `None` if it reads as its
+    /// type's zero value when unset
+    fn nonzero_default<'a>(&'a self, types: &TypeMap) -> Option<&'a str> {
+        let Some(Presence::Explicit {
+            default: Some(ref default),
+        }) = self.presence
+        else {
+            return None;
+        };
+
+        let zero = match self.ty {
+            FieldType::Primitive(PrimitiveType::String | PrimitiveType::Bytes) => {
+                default.is_empty()
+            },
+            FieldType::Primitive(PrimitiveType::Bool) => default == "false",
+            // protoc normalizes numeric defaults, but floats may still be
+            // written as e.g. -0
+            FieldType::Primitive(_) => default.parse::<f64>().is_ok_and(|f| f == 0.0),
+            // Unset enum fields read as the first declared variant, which is
+            // only guaranteed to be 0 in proto3
+            FieldType::Named(ref name) => types
+                .get(name)
+                .ok()
+                .and_then(|t| t.as_enum())
+                .and_then(|e| e.numbers().get(&e.extra().first()?))
+                .is_some_and(|v| v.names().any(|n| n == default)),
+        };
+
+        (!zero).then_some(default.as_str())
+    }
+
+    /// The key type of a map field, read from its entry message
+    fn map_key<'a>(&'a self, types: &'a TypeMap) -> Option<&'a FieldType> {
+        let FieldType::Named(ref entry) = self.ty else {
+            return None;
+        };
+
+        (self.kind == FieldKind::Map)
+            .then(|| types.get(entry).ok()?.as_message()?.numbers().get(&1))
+            .flatten()
+            .map(Field::ty)
+    }
+
     fn warn_non_zigzag(&self, ctx: &TypeContext<'_>, side: Side, log: &mut CompatLog) {
         let Ok(wire) = self.ty.wire_format(self.kind, |n| ctx.types.get(n)) else {
             return;
         };
 
-        if wire == WireType::VarInt(VarIntMode::Signed) {
+        if matches!(wire, WireType::VarInt(VarIntMode::Signed(_))) {
             CompatError::new(
                 side.then(ctx.kind.type_name().member(&self.name).to_owned())
                     .into(),
@@ -71,6 +184,7 @@ impl CheckCompat for Field {
         log: &mut CompatLog,
     ) {
         let id = cx.as_ref().map(|c| c.id).unwrap_eq();
+        let type_maps = cx.as_ref().map(|c| c.ty.types);
 
         let qual_names = cx
             .as_ref()
@@ -94,11 +208,99 @@ impl CheckCompat for Field {
 
         let (types, kinds) = ck.map(|f| (&f.ty, f.kind)).unzip();
 
+        // Entry key formats are checked with the entry types, but even a
+        // compatible change can merge or reorder entries
+        if let Some(keys) = ck
+            .zip(type_maps)
+            .filter_map(|(f, t)| f.map_key(t).map(ToString::to_string))
+        {
+            if keys.as_ref().try_unwrap_eq().is_err() {
+                CompatError::new(
+                    cx.as_ref().map(|c| c.field.to_owned()).into(),
+                    format!("Map key type change ({})", keys.display()),
+                )
+                .warn(log);
+            }
+        }
+
+        if let Some(presence) = ck.filter_map(|f| f.presence.as_ref()) {
+            Self::check_presence(
+                ck,
+                presence,
+                type_maps,
+                &cx.as_ref().map(|c| c.field.borrowed()),
+                log,
+            );
+        }
+
         types.check(cx, log);
         kinds.as_ref().check(qual_names, log);
     }
 }
 
+impl Field {
+    /// Check that unset fields and fields set to their default read the same
+    /// on both sides
+    fn check_presence(
+        ck: CompatPair<&Self>,
+        presence: CompatPair<&Presence>,
+        types: CompatPair<&TypeMap>,
+        field: &CompatPair<MemberQualName<'_>>,
+        log: &mut CompatLog,
+    ) {
+        let defaults = ck.zip(types).map(|(f, t)| f.nonzero_default(t));
+        let (rd_default, wr_default) = defaults.into_inner();
+        let err = |msg: String| {
+            CompatError::new(field.as_ref().map(MemberQualName::to_owned).into(), msg)
+        };
+
+        match presence.as_ref().into_inner() {
+            (Presence::Implicit, Presence::Implicit) => (),
+            // Zero values are never written, so the reader sees them as unset
+            (Presence::Explicit { .. }, Presence::Implicit) => {
+                if let Some(default) = rd_default {
+                    err(format!(
+                        "Zero values omitted by writer read as default {default} ({})",
+                        presence.display()
+                    ))
+                    .err(log);
+                } else {
+                    err(format!(
+                        "Zero values read as unset ({})",
+                        presence.display()
+                    ))
+                    .warn(log);
+                }
+            },
+            // The reader cannot tell unset fields from ones set to zero
+            (Presence::Implicit, Presence::Explicit { .. }) => {
+                if let Some(default) = wr_default {
+                    err(format!(
+                        "Unset fields read as zero instead of default {default} ({})",
+                        presence.display()
+                    ))
+                    .err(log);
+                } else {
+                    err(format!(
+                        "Unset fields indistinguishable from zero values ({})",
+                        presence.display()
+                    ))
+                    .warn(log);
+                }
+            },
+            (Presence::Explicit { .. }, Presence::Explicit { .. }) => {
+                if rd_default != wr_default {
+                    err(format!(
+                        "Default value change ({})",
+                        defaults.map(|d| d.unwrap_or("zero")).display()
+                    ))
+                    .err(log);
+                }
+            },
+        }
+    }
+}
+
 impl RecordExtra for Field {
     type Extra = FieldExtra;
 }
@@ -175,3 +377,149 @@ impl<'a> RecordValue<'a> for Field {
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{FieldKind, Presence, Syntax};
+    use crate::schema::{
+        test_util::{check, compile, compile_str},
+        Schema,
+    };
+
+    fn fixture(version: u8) -> Schema {
+        compile(format!(
+            "{}/fixtures/presence/v{version}.proto",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+    }
+
+    /// Check `new` against `old` in both directions, returning the backward
+    /// and forward results
+    #[expect(clippy::type_complexity, reason = "Test helper")]
+    fn both(
+        old: &Schema,
+        new: &Schema,
+    ) -> ((Vec<String>, Vec<String>), (Vec<String>, Vec<String>)) {
+        (check(new, old), check(old, new))
+    }
+
+    #[test]
+    fn fixture_default_change() {
+        let (backward, forward) = both(&fixture(1), &fixture(2));
+
+        assert_eq!(backward, (
+            vec!["Settings::volume: Default value change (60 in reader, 50 in writer)".into()],
+            vec![]
+        ));
+        assert_eq!(forward, (
+            vec!["Settings::volume: Default value change (50 in reader, 60 in writer)".into()],
+            vec![]
+        ));
+    }
+
+    #[test]
+    fn fixture_proto3_migration() {
+        let (backward, forward) = both(&fixture(2), &fixture(3));
+
+        assert_eq!(backward.0, [
+            "Settings::limit: Default value change (zero in reader, 10 in writer)",
+            "Settings::volume: Unset fields read as zero instead of default 60 (implicit presence \
+             in reader, explicit presence (default \"60\") in writer)",
+        ]);
+        assert_eq!(backward.1, [
+            "Profile::name: Unset fields indistinguishable from zero values (implicit presence in \
+             reader, explicit presence in writer)",
+            "Settings::gain: Unset fields indistinguishable from zero values (implicit presence \
+             in reader, explicit presence (default \"0\") in writer)",
+            "Settings::mode: Unset fields indistinguishable from zero values (implicit presence \
+             in reader, explicit presence (default \"MODE_NORMAL\") in writer)",
+            "Settings::nick: Unset fields indistinguishable from zero values (implicit presence \
+             in reader, explicit presence (default \"\") in writer)",
+        ]);
+
+        assert_eq!(forward.0, [
+            "Settings::limit: Default value change (10 in reader, zero in writer)",
+            "Settings::volume: Zero values omitted by writer read as default 60 (explicit \
+             presence (default \"60\") in reader, implicit presence in writer)",
+        ]);
+        assert_eq!(forward.1, [
+            "Profile::name: Zero values read as unset (explicit presence in reader, implicit \
+             presence in writer)",
+            "Settings::gain: Zero values read as unset (explicit presence (default \"0\") in \
+             reader, implicit presence in writer)",
+            "Settings::mode: Zero values read as unset (explicit presence (default \
+             \"MODE_NORMAL\") in reader, implicit presence in writer)",
+            "Settings::nick: Zero values read as unset (explicit presence (default \"\") in \
+             reader, implicit presence in writer)",
+        ]);
+    }
+
+    #[test]
+    fn fixture_presence_change() {
+        let (backward, forward) = both(&fixture(3), &fixture(4));
+
+        assert!(backward.0.is_empty());
+        assert_eq!(backward.1, [
+            "Settings::mode: Zero values read as unset (explicit presence in reader, implicit \
+             presence in writer)",
+            "Settings::muted: Unset fields indistinguishable from zero values (implicit presence \
+             in reader, explicit presence in writer)",
+            "Settings::nick: Zero values read as unset (explicit presence in reader, implicit \
+             presence in writer)",
+        ]);
+
+        assert!(forward.0.is_empty());
+        assert_eq!(forward.1, [
+            "Settings::mode: Unset fields indistinguishable from zero values (implicit presence \
+             in reader, explicit presence in writer)",
+            "Settings::muted: Zero values read as unset (explicit presence in reader, implicit \
+             presence in writer)",
+            "Settings::nick: Unset fields indistinguishable from zero values (implicit presence \
+             in reader, explicit presence in writer)",
+        ]);
+    }
+
+    #[test]
+    fn proto2_enum_default() {
+        let schema = |default: &str| {
+            compile_str(&format!(
+                "syntax = \"proto2\";\n\
+                 enum Level {{ HIGH = 2; LOW = 1; NONE = 0; }}\n\
+                 message Msg {{ optional Level level = 1{default}; }}\n"
+            ))
+        };
+        let none = schema("");
+
+        // Unset proto2 enum fields read as the first declared variant, even
+        // if it is not 0
+        assert_eq!(check(&schema(" [default = HIGH]"), &none), (vec![], vec![]));
+        assert_eq!(check(&none, &schema(" [default = HIGH]")), (vec![], vec![]));
+
+        assert_eq!(check(&schema(" [default = NONE]"), &none), (
+            vec!["Msg::level: Default value change (NONE in reader, zero in writer)".into()],
+            vec![]
+        ));
+        assert_eq!(check(&none, &schema(" [default = LOW]")), (
+            vec!["Msg::level: Default value change (zero in reader, LOW in writer)".into()],
+            vec![]
+        ));
+    }
+
+    #[test]
+    fn proto3_default_ignored() {
+        assert_eq!(
+            Presence::new(
+                FieldKind::Singular,
+                Syntax::Proto3,
+                false,
+                false,
+                Some("1".into())
+            ),
+            Some(Presence::Implicit)
+        );
+        assert_eq!(
+            Presence::new(FieldKind::Optional, Syntax::Proto3, false, false, None),
+            Some(Presence::Explicit { default: None })
+        );
+    }
+}
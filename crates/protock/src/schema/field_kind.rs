@@ -6,27 +6,78 @@ use crate::{
     compat_pair::CompatPair,
 };
 
+/// The syntax version of the file declaring a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Proto2,
+    Proto3,
+}
+
+impl Syntax {
+    pub fn new(syntax: Option<&str>) -> Option<Self> {
+        match syntax {
+            None | Some("proto2") => Some(Self::Proto2),
+            Some("proto3") => Some(Self::Proto3),
+            Some(_) => None,
+        }
+    }
+
+    /// Whether repeated numeric fields without an explicit `packed` option
+    /// use packed encoding
+    #[inline]
+    pub const fn packed_by_default(self) -> bool { matches!(self, Self::Proto3) }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FieldKind {
     Singular,
-    Repeated { packed: Option<bool> },
+    /// A repeated field, and whether it is encoded packed if its type is
+    /// numeric
+    Repeated {
+        packed: bool,
+    },
+    /// A `map<K, V>` field, encoded as a repeated message of its
+    /// compiler-generated entry type
+    Map,
     Optional,
 }
 
 impl FieldKind {
-    pub fn new(label: Label, packed: Option<bool>, proto3_optional: Option<bool>) -> Self {
+    pub fn new(
+        label: Label,
+        packed: Option<bool>,
+        proto3_optional: Option<bool>,
+        syntax: Syntax,
+        map: bool,
+    ) -> Self {
         if !matches!(label, Label::Repeated) {
             assert!(packed.is_none());
+            assert!(!map);
         }
 
         match (label, proto3_optional) {
             (Label::Optional, Some(false) | None) => Self::Singular,
             (Label::Required, None) => panic!("Unsupported required label found"),
-            (Label::Repeated, None) => Self::Repeated { packed },
+            (Label::Repeated, None) if map => {
+                assert!(packed.is_none());
+                Self::Map
+            },
+            (Label::Repeated, None) => Self::Repeated {
+                packed: packed.unwrap_or(syntax.packed_by_default()),
+            },
             (Label::Optional, Some(true)) => Self::Optional,
             (l, o) => panic!("Unexpected field kind ({l:?}, optional={o:?})"),
         }
     }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Singular => "singular",
+            Self::Repeated { .. } => "repeated",
+            Self::Map => "map",
+            Self::Optional => "optional",
+        }
+    }
 }
 
 impl CheckCompat for FieldKind {
@@ -39,9 +90,11 @@ impl CheckCompat for FieldKind {
     ) {
         match ck.into_inner() {
             (a, b) if a == b => (),
-            (Self::Singular | Self::Optional, Self::Singular | Self::Optional) => (),
-            (Self::Repeated { packed: _ }, Self::Singular | Self::Optional) => {},
-            (rd @ (Self::Singular | Self::Optional), wr @ Self::Repeated { packed: _ }) => {
+            // Packedness only affects numeric fields, and is checked alongside
+            // their wire formats
+            (Self::Repeated { .. }, Self::Repeated { .. })
+            | (_, Self::Singular | Self::Optional) => (),
+            (rd @ (Self::Singular | Self::Optional), wr @ (Self::Repeated { .. } | Self::Map)) => {
                 CompatError::new(
                     cx.map(|n| n.to_owned()).into(),
                     format!(
@@ -51,14 +104,121 @@ impl CheckCompat for FieldKind {
                 )
                 .warn(log);
             },
+            // Maps are encoded as repeated entry messages, but a map reader
+            // keeps only the last entry for each key and neither side
+            // preserves the other's ordering
             (rd, wr) => CompatError::new(
                 cx.map(|n| n.to_owned()).into(),
                 format!(
-                    "Incompatible field kinds ({:?})",
-                    CompatPair::new(rd, wr).display()
+                    "Map/repeated mismatch ({})",
+                    CompatPair::new(rd, wr).map(|k| k.label()).display()
                 ),
             )
-            .err(log),
+            .warn(log),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::schema::{
+        test_util::{check, compile_str},
+        Schema,
+    };
+
+    const ENTRY: &str = "message Entry { uint32 key = 1; string value = 2; }";
+
+    fn proto2(body: &str) -> Schema { compile_str(&format!("syntax = \"proto2\";\n{body}\n")) }
+
+    fn proto3(body: &str) -> Schema { compile_str(&format!("syntax = \"proto3\";\n{body}\n")) }
+
+    fn warnings(warnings: &[&str]) -> (Vec<String>, Vec<String>) {
+        (vec![], warnings.iter().map(|&w| w.into()).collect())
+    }
+
+    #[test]
+    fn map_key() {
+        let narrow = proto3("message M { map<uint32, string> m = 1; }");
+        let wide = proto3("message M { map<uint64, string> m = 1; }");
+
+        assert_eq!(check(&narrow, &narrow), warnings(&[]));
+        assert_eq!(check(&narrow, &wide), (
+            vec![
+                "MEntry::key: Varint narrowing may truncate values (Unsigned(W32) in reader, \
+                 Unsigned(W64) in writer)"
+                    .into()
+            ],
+            vec!["M::m: Map key type change (uint32 in reader, uint64 in writer)".into()]
+        ));
+        assert_eq!(
+            check(&wide, &narrow),
+            warnings(&[
+                "M::m: Map key type change (uint64 in reader, uint32 in writer)",
+                "MEntry::key: Varint widening (Unsigned(W64) in reader, Unsigned(W32) in writer)",
+            ])
+        );
+    }
+
+    #[test]
+    fn map_repeated() {
+        let map = proto3(&format!("{ENTRY} message M {{ map<uint32, string> m = 1; }}"));
+        let repeated = proto3(&format!("{ENTRY} message M {{ repeated Entry m = 1; }}"));
+
+        assert_eq!(check(&map, &map), warnings(&[]));
+        assert_eq!(check(&repeated, &repeated), warnings(&[]));
+        assert_eq!(
+            check(&map, &repeated),
+            warnings(&["M::m: Map/repeated mismatch (map in reader, repeated in writer)"])
+        );
+        assert_eq!(
+            check(&repeated, &map),
+            warnings(&["M::m: Map/repeated mismatch (repeated in reader, map in writer)"])
+        );
+    }
+
+    #[test]
+    fn repeated_singular() {
+        let singular = proto3(&format!("{ENTRY} message M {{ Entry m = 1; }}"));
+        let repeated = proto3(&format!("{ENTRY} message M {{ repeated Entry m = 1; }}"));
+        let map = proto3(&format!("{ENTRY} message M {{ map<uint32, string> m = 1; }}"));
+
+        // Repeated readers accept a single value
+        assert_eq!(check(&repeated, &singular), warnings(&[]));
+        assert_eq!(check(&map, &singular), warnings(&[]));
+
+        // Singular readers keep only the last value
+        assert_eq!(
+            check(&singular, &repeated),
+            warnings(&["M::m: Repeated/singular mismatch (Singular in reader, Repeated { packed: \
+                        true } in writer)"])
+        );
+        assert_eq!(
+            check(&singular, &map),
+            warnings(&["M::m: Repeated/singular mismatch (Singular in reader, Map in writer)"])
+        );
+    }
+
+    #[test]
+    fn packed_default() {
+        let field = "message M { repeated sint32 m = 1";
+        let p2 = proto2(&format!("{field}; }}"));
+        let p2_packed = proto2(&format!("{field} [packed = true]; }}"));
+        let p3 = proto3(&format!("{field}; }}"));
+        let p3_unpacked = proto3(&format!("{field} [packed = false]; }}"));
+
+        // Only proto3 packs repeated numeric fields by default
+        assert_eq!(
+            check(&p2, &p3),
+            warnings(&["M::m: Packed encoding mismatch (unpacked in reader, packed in writer)"])
+        );
+        assert_eq!(
+            check(&p3, &p2),
+            warnings(&["M::m: Packed encoding mismatch (packed in reader, unpacked in writer)"])
+        );
+
+        assert_eq!(check(&p2_packed, &p3), warnings(&[]));
+        assert_eq!(check(&p3, &p2_packed), warnings(&[]));
+        assert_eq!(check(&p2, &p3_unpacked), warnings(&[]));
+        assert_eq!(check(&p3_unpacked, &p2), warnings(&[]));
+    }
+}
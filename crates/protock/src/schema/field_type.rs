@@ -30,6 +30,15 @@ impl FieldType {
     }
 }
 
+impl std::fmt::Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Primitive(p) => f.write_str(p.proto_name()),
+            Self::Named(n) => write!(f, "{n}"),
+        }
+    }
+}
+
 pub struct FieldTypeContext<'a> {
     pub field: MemberQualName<'a>,
     pub types: &'a TypeMap,
@@ -71,7 +80,10 @@ impl CheckCompat for FieldType {
                 None
             }
         }) {
-            let _wire = wire_formats.unwrap_eq();
+            // Mismatched wire formats have already been reported above
+            if wire_formats.try_unwrap_eq().is_err() {
+                return;
+            }
 
             let types = match type_names.zip(type_maps).try_map(|(n, t)| t.get(n)) {
                 Ok(t) => t,
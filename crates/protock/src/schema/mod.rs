@@ -2,6 +2,7 @@ mod field;
 mod field_kind;
 mod field_type;
 mod oneof;
+mod options;
 mod primitive;
 mod qual_name;
 mod record;
@@ -9,25 +10,87 @@ mod ty;
 mod variant;
 
 pub use imp::{Schema, SchemaContext, TypeError, TypeMap};
+pub use options::{OptionPolicy, PolicyRule};
+
+#[cfg(test)]
+pub mod test_util {
+    use std::{collections::BTreeSet, path::Path};
+
+    use super::{OptionPolicy, Schema, SchemaContext};
+    use crate::{
+        check_compat::{CompatError, CompatLog},
+        compat_pair::CompatPair,
+        protoc,
+    };
+
+    /// Compile a proto file with `protoc` from `PATH`
+    pub fn compile(path: impl AsRef<Path>) -> Schema {
+        Schema::new(&protoc::get_descriptor_set([path]).unwrap()).unwrap()
+    }
+
+    /// Compile the source of a single proto file
+    pub fn compile_str(source: &str) -> Schema {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.proto");
+        std::fs::write(&path, source).unwrap();
+        compile(path)
+    }
+
+    /// Check a reader schema against a writer schema, returning the errors
+    /// and warnings logged as `Type::member: message`, sorted and without
+    /// duplicates
+    ///
+    /// Types referenced by fields are checked both by name and through the
+    /// field, so the same problem can be logged more than once.
+    pub fn check(reader: &Schema, writer: &Schema) -> (Vec<String>, Vec<String>) {
+        let policy = OptionPolicy::new([]);
+        let cx = |name| SchemaContext {
+            name,
+            policy: &policy,
+        };
+
+        let mut log = CompatLog::default();
+        CompatPair::new(reader, writer)
+            .check(CompatPair::new(cx("reader"), cx("writer")), &mut log);
+
+        let summarize = |errs: &[CompatError]| {
+            errs.iter()
+                .map(|e| {
+                    let text = e.to_string();
+                    let cx = text[..text.len() - e.message().len()].trim_end();
+                    let subject = cx.trim_start_matches('(').split(" in ").next().unwrap();
+                    let subject = subject.rsplit('.').next().unwrap();
+                    format!("{subject}: {}", e.message())
+                })
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        };
+        (summarize(log.errors()), summarize(log.warnings()))
+    }
+}
 
 #[path = ""]
 mod imp {
+    mod diff;
     mod visitor;
 
     use std::collections::HashMap;
 
-    use prost_types::FileDescriptorSet;
+    use anyhow::{Context, Result};
 
     use super::{
+        options::{Extensions, OptionMap, OptionPolicy},
         qual_name::QualName,
         ty::{Type, TypeCheckKind, TypeContext},
     };
     use crate::{
         check_compat::{CheckCompat, CompatError, CompatLog},
         compat_pair::{CompatPair, Side},
+        protoc::Descriptors,
     };
 
-    #[derive(Debug)]
+    #[derive(Debug, Default)]
     #[repr(transparent)]
     pub struct TypeMap(HashMap<QualName<'static>, Type>);
 
@@ -53,23 +116,28 @@ mod imp {
     #[derive(Debug)]
     pub struct Schema {
         types: TypeMap,
+        options: OptionMap,
     }
 
     impl Schema {
-        pub fn new(desc: &FileDescriptorSet) -> Self {
+        pub fn new(desc: &Descriptors) -> Result<Self> {
+            let exts = Extensions::new(&desc.set);
             let mut me = Self {
                 types: TypeMap(HashMap::new()),
+                options: OptionMap::new(&desc.raw, &exts)
+                    .context("Error reading descriptor options")?,
             };
 
-            visitor::Visitor::from(&mut me).fildes_set(desc);
+            visitor::Visitor::from(&mut me).fildes_set(&desc.set);
             tracing::trace!("{me:#?}");
 
-            me
+            Ok(me)
         }
     }
 
     pub struct SchemaContext<'a> {
         pub name: &'a str,
+        pub policy: &'a OptionPolicy,
     }
 
     impl CheckCompat for Schema {
@@ -103,6 +171,9 @@ mod imp {
                     }
                 },
             );
+
+            let (policy, _) = cx.map(|c| c.policy).into_inner();
+            OptionMap::check(ck.map(|s| &s.options), policy, log);
         }
     }
 }
@@ -0,0 +1,701 @@
+//! Descriptor options, including custom options declared as extensions of the
+//! `google.protobuf.*Options` messages
+//!
+//! Decoding a descriptor set with [`prost_types`] discards extension fields, so
+//! options are instead read directly from its encoded form.
+
+use std::{borrow::Cow, collections::HashMap, fmt, str::FromStr};
+
+use anyhow::{bail, ensure, Context, Result};
+use prost::encoding::{decode_key, decode_varint, WireType};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet};
+
+use crate::{
+    check_compat::{CompatError, CompatLog},
+    compat_pair::{CompatPair, SideInclusive},
+};
+
+/// The lowest field number available to extensions of an options message
+const FIRST_EXTENSION: u32 = 1000;
+
+const DESCRIPTOR_PROTO: &str = "google/protobuf/descriptor.proto";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum OptionsKind {
+    File,
+    Message,
+    Field,
+    Enum,
+    EnumValue,
+}
+
+impl OptionsKind {
+    fn from_extendee(extendee: &str) -> Option<Self> {
+        Some(match extendee.strip_prefix(".google.protobuf.")? {
+            "FileOptions" => Self::File,
+            "MessageOptions" => Self::Message,
+            "FieldOptions" => Self::Field,
+            "EnumOptions" => Self::Enum,
+            "EnumValueOptions" => Self::EnumValue,
+            _ => return None,
+        })
+    }
+
+    /// The field number of the built-in `deprecated` option
+    const fn deprecated(self) -> u32 {
+        match self {
+            Self::File => 23,
+            Self::EnumValue => 1,
+            Self::Message | Self::Field | Self::Enum => 3,
+        }
+    }
+}
+
+fn is_option_extension(ext: &FieldDescriptorProto) -> bool {
+    ext.extendee
+        .as_deref()
+        .and_then(OptionsKind::from_extendee)
+        .is_some()
+}
+
+/// Returns true if the given file exists only to declare custom options, and
+/// thus describes no data that needs to be checked for compatibility
+pub fn defines_options(file: &FileDescriptorProto) -> bool {
+    file.name.as_deref() == Some(DESCRIPTOR_PROTO)
+        || !file.extension.is_empty() && file.extension.iter().all(is_option_extension)
+}
+
+/// Display names of all known custom options, keyed by the options message
+/// they extend and their field number
+#[derive(Debug, Default)]
+pub struct Extensions(HashMap<(OptionsKind, u32), String>);
+
+impl Extensions {
+    pub fn new(desc: &FileDescriptorSet) -> Self {
+        let mut me = Self::default();
+
+        for file in &desc.file {
+            let scope = file.package.as_deref().unwrap_or_default();
+            me.register(scope, &file.extension);
+            me.descend(scope, &file.message_type);
+        }
+
+        me
+    }
+
+    fn descend(&mut self, scope: &str, msgs: &[DescriptorProto]) {
+        for msg in msgs {
+            let scope = qualify(scope, msg.name());
+            self.register(&scope, &msg.extension);
+            self.descend(&scope, &msg.nested_type);
+        }
+    }
+
+    fn register(&mut self, scope: &str, exts: &[FieldDescriptorProto]) {
+        for ext in exts {
+            let (Some(kind), Some(number)) = (
+                ext.extendee.as_deref().and_then(OptionsKind::from_extendee),
+                ext.number.and_then(|n| u32::try_from(n).ok()),
+            ) else {
+                continue;
+            };
+
+            self.0
+                .insert((kind, number), format!("({})", qualify(scope, ext.name())));
+        }
+    }
+
+    /// Get the display name of an option, or `None` if it is a built-in option
+    /// not tracked by protock
+    fn name(&self, kind: OptionsKind, number: u32) -> Option<Cow<'_, str>> {
+        if number == kind.deprecated() {
+            Some("deprecated".into())
+        } else if number >= FIRST_EXTENSION {
+            Some(
+                self.0
+                    .get(&(kind, number))
+                    .map_or_else(|| format!("({number})").into(), Into::into),
+            )
+        } else {
+            None
+        }
+    }
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.into()
+    } else {
+        format!("{scope}.{name}")
+    }
+}
+
+/// A single encoded field of a protobuf message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionValue {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(Vec<u8>),
+    Fixed32(u32),
+}
+
+impl fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Varint(v) | Self::Fixed64(v) => write!(f, "{v}"),
+            Self::Fixed32(v) => write!(f, "{v}"),
+            Self::Bytes(b) => match std::str::from_utf8(b) {
+                Ok(s) if !s.chars().any(char::is_control) => write!(f, "{s:?}"),
+                _ => write!(f, "<{} bytes>", b.len()),
+            },
+        }
+    }
+}
+
+struct Values<'a>(&'a [OptionValue]);
+
+impl fmt::Display for Values<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, val) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+
+            write!(f, "{val}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn fields(mut buf: &[u8]) -> Result<Vec<(u32, OptionValue)>> {
+    fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+        ensure!(buf.len() >= len, "Unexpected end of message");
+        let (head, tail) = buf.split_at(len);
+        *buf = tail;
+        Ok(head)
+    }
+
+    let mut ret = vec![];
+
+    while !buf.is_empty() {
+        let (number, wire) = decode_key(&mut buf)?;
+        let val = match wire {
+            WireType::Varint => OptionValue::Varint(decode_varint(&mut buf)?),
+            WireType::SixtyFourBit => OptionValue::Fixed64(u64::from_le_bytes(
+                take(&mut buf, 8)?
+                    .try_into()
+                    .unwrap_or_else(|_| unreachable!()),
+            )),
+            WireType::LengthDelimited => {
+                let len = decode_varint(&mut buf)?
+                    .try_into()
+                    .context("Field length too large")?;
+                OptionValue::Bytes(take(&mut buf, len)?.to_vec())
+            },
+            WireType::ThirtyTwoBit => OptionValue::Fixed32(u32::from_le_bytes(
+                take(&mut buf, 4)?
+                    .try_into()
+                    .unwrap_or_else(|_| unreachable!()),
+            )),
+            WireType::StartGroup | WireType::EndGroup => {
+                bail!("Group-encoded field {number} is not supported")
+            },
+        };
+
+        ret.push((number, val));
+    }
+
+    Ok(ret)
+}
+
+fn bytes(val: &OptionValue) -> Result<&[u8]> {
+    match val {
+        OptionValue::Bytes(b) => Ok(b),
+        v => bail!("Expected length-delimited field, got {v:?}"),
+    }
+}
+
+fn name(fields: &[(u32, OptionValue)]) -> Result<&str> {
+    let val = fields
+        .iter()
+        .find_map(|(n, v)| (*n == 1).then_some(v))
+        .context("Missing element name")?;
+    std::str::from_utf8(bytes(val)?).context("Invalid element name")
+}
+
+/// The options set on a single element of a schema
+#[derive(Debug, Default)]
+#[repr(transparent)]
+pub struct OptionSet(HashMap<String, Vec<OptionValue>>);
+
+/// The options set on every element of a schema, keyed by the element's
+/// fully-qualified name
+#[derive(Debug, Default)]
+#[repr(transparent)]
+pub struct OptionMap(HashMap<String, OptionSet>);
+
+impl OptionMap {
+    /// Read all options from an encoded descriptor set
+    pub fn new(raw: &[u8], exts: &Extensions) -> Result<Self> {
+        let mut me = Self::default();
+
+        for (number, val) in fields(raw)? {
+            if number == 1 {
+                me.file(exts, bytes(&val)?)?;
+            }
+        }
+
+        Ok(me)
+    }
+
+    fn file(&mut self, exts: &Extensions, buf: &[u8]) -> Result<()> {
+        let fields = fields(buf)?;
+        let package = fields
+            .iter()
+            .find_map(|(n, v)| (*n == 2).then_some(v))
+            .map(|v| bytes(v).and_then(|b| std::str::from_utf8(b).context("Invalid package")))
+            .transpose()?
+            .unwrap_or_default();
+
+        // Files are keyed by package rather than by name, as the name of a
+        // file compiled from history is not meaningful
+        let path = format!("package {package:?}");
+        self.0.entry(path.clone()).or_default();
+
+        for (number, val) in &fields {
+            match number {
+                4 => self.message(exts, package, bytes(val)?)?,
+                5 => self.enumeration(exts, package, bytes(val)?)?,
+                8 => self.options(exts, OptionsKind::File, &path, bytes(val)?)?,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn message(&mut self, exts: &Extensions, scope: &str, buf: &[u8]) -> Result<()> {
+        let fields = fields(buf)?;
+        let path = qualify(scope, name(&fields)?);
+        self.0.entry(path.clone()).or_default();
+
+        for (number, val) in &fields {
+            match number {
+                2 => self.member(exts, OptionsKind::Field, &path, 8, bytes(val)?)?,
+                3 => self.message(exts, &path, bytes(val)?)?,
+                4 => self.enumeration(exts, &path, bytes(val)?)?,
+                7 => self.options(exts, OptionsKind::Message, &path, bytes(val)?)?,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enumeration(&mut self, exts: &Extensions, scope: &str, buf: &[u8]) -> Result<()> {
+        let fields = fields(buf)?;
+        let path = qualify(scope, name(&fields)?);
+        self.0.entry(path.clone()).or_default();
+
+        for (number, val) in &fields {
+            match number {
+                2 => self.member(exts, OptionsKind::EnumValue, &path, 3, bytes(val)?)?,
+                3 => self.options(exts, OptionsKind::Enum, &path, bytes(val)?)?,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn member(
+        &mut self,
+        exts: &Extensions,
+        kind: OptionsKind,
+        scope: &str,
+        options: u32,
+        buf: &[u8],
+    ) -> Result<()> {
+        let fields = fields(buf)?;
+        let path = qualify(scope, name(&fields)?);
+        self.0.entry(path.clone()).or_default();
+
+        for (number, val) in &fields {
+            if *number == options {
+                self.options(exts, kind, &path, bytes(val)?)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn options(
+        &mut self,
+        exts: &Extensions,
+        kind: OptionsKind,
+        path: &str,
+        buf: &[u8],
+    ) -> Result<()> {
+        let set = &mut self.0.entry(path.into()).or_default().0;
+
+        for (number, val) in fields(buf)? {
+            // deprecated = false is indistinguishable from an unset option
+            if number == kind.deprecated() && val == OptionValue::Varint(0) {
+                continue;
+            }
+
+            if let Some(name) = exts.name(kind, number) {
+                set.entry(name.into_owned()).or_default().push(val);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Report any options that differ between elements present in both
+    /// schemas, according to the given policy
+    pub fn check(ck: CompatPair<&Self>, policy: &OptionPolicy, log: &mut CompatLog) {
+        for (path, sets) in ck.map(|m| &m.0).iter_joined() {
+            // Missing elements are reported by the type checks
+            let SideInclusive::Both(sets) = sets else {
+                continue;
+            };
+
+            for (option, values) in sets.map(|s| &s.0).iter_joined() {
+                let message = match values {
+                    SideInclusive::Both(pair) => {
+                        let Err(pair) = pair.try_unwrap_eq() else {
+                            continue;
+                        };
+
+                        format!(
+                            "Option {option} changed: {}",
+                            pair.map(|v| Values(v)).display()
+                        )
+                    },
+                    SideInclusive::One(side) => {
+                        let (side, vals) = side.split();
+                        format!(
+                            "Option {option} only set on {}: {}",
+                            side.pretty(),
+                            Values(vals),
+                        )
+                    },
+                };
+
+                let err =
+                    CompatError::new(CompatPair::new(path.clone(), path.clone()).into(), message);
+                match policy.severity(option) {
+                    Severity::Ignore => (),
+                    Severity::Warn => err.warn(log),
+                    Severity::Error => err.err(log),
+                }
+            }
+        }
+    }
+}
+
+/// How a change to a descriptor option should be reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Severity {
+    /// Do not report the change
+    Ignore,
+    /// Report the change without failing the check
+    Warn,
+    /// Fail the check
+    Error,
+}
+
+/// A severity to use for changes to a particular option
+#[derive(Debug, Clone)]
+pub struct PolicyRule {
+    option: String,
+    severity: Severity,
+}
+
+impl FromStr for PolicyRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (option, severity) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Expected OPTION=LEVEL, got {s:?}"))?;
+
+        Ok(Self {
+            option: option.trim().into(),
+            severity: clap::ValueEnum::from_str(severity.trim(), true)?,
+        })
+    }
+}
+
+/// The severity to use for changes to each descriptor option
+#[derive(Debug)]
+pub struct OptionPolicy {
+    default: Severity,
+    rules: HashMap<String, Severity>,
+}
+
+impl OptionPolicy {
+    /// Construct a policy from the defaults overridden by the given rules
+    ///
+    /// By default, changes to `deprecated` are ignored and changes to any other
+    /// option are warnings.  A rule for the option `*` overrides the latter.
+    pub fn new(rules: impl IntoIterator<Item = PolicyRule>) -> Self {
+        let mut me = Self {
+            default: Severity::Warn,
+            rules: [("deprecated".into(), Severity::Ignore)]
+                .into_iter()
+                .collect(),
+        };
+
+        for PolicyRule { option, severity } in rules {
+            if option == "*" {
+                me.default = severity;
+            } else {
+                me.rules.insert(option, severity);
+            }
+        }
+
+        me
+    }
+
+    fn severity(&self, option: &str) -> Severity {
+        self.rules.get(option).copied().unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prost::Message;
+
+    use super::*;
+    use crate::protoc;
+
+    const OPTIONS: &str = r#"
+        import "google/protobuf/descriptor.proto";
+
+        package test;
+
+        extend google.protobuf.FieldOptions {
+            optional string format = 50000;
+            optional bool sensitive = 50001;
+        }
+
+        extend google.protobuf.MessageOptions {
+            optional int32 version = 50000;
+        }
+    "#;
+
+    fn descriptors(source: &str) -> protoc::Descriptors {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.proto");
+        std::fs::write(&path, format!("syntax = \"proto2\";\n{OPTIONS}\n{source}")).unwrap();
+        protoc::get_descriptor_set([path]).unwrap()
+    }
+
+    fn options(source: &str) -> OptionMap {
+        let desc = descriptors(source);
+        OptionMap::new(&desc.raw, &Extensions::new(&desc.set)).unwrap()
+    }
+
+    fn set(map: &OptionMap, path: &str) -> Vec<(String, String)> {
+        let mut set: Vec<_> = map.0[path]
+            .0
+            .iter()
+            .map(|(k, v)| (k.clone(), Values(v).to_string()))
+            .collect();
+        set.sort_unstable();
+        set
+    }
+
+    /// Check the options of a reader schema against a writer schema,
+    /// returning the messages of the errors and warnings logged
+    fn check(reader: &str, writer: &str, rules: &[&str]) -> (Vec<String>, Vec<String>) {
+        let policy = OptionPolicy::new(rules.iter().map(|r| r.parse().unwrap()));
+        let (reader, writer) = (options(reader), options(writer));
+        let mut log = CompatLog::default();
+        OptionMap::check(CompatPair::new(&reader, &writer), &policy, &mut log);
+
+        let messages = |errs: &[CompatError]| {
+            let mut msgs: Vec<_> = errs.iter().map(|e| e.message().to_owned()).collect();
+            msgs.sort_unstable();
+            msgs
+        };
+        (messages(log.errors()), messages(log.warnings()))
+    }
+
+    #[test]
+    fn parse_fields() {
+        let msg = prost_types::FieldOptions {
+            deprecated: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            fields(&msg.encode_to_vec()).unwrap(),
+            [(3, OptionValue::Varint(1))]
+        );
+
+        // Field 1 as each wire type: varint 150, fixed64, "hi", fixed32
+        let buf = [
+            0x08, 0x96, 0x01, //
+            0x09, 1, 0, 0, 0, 0, 0, 0, 0, //
+            0x0a, 2, b'h', b'i', //
+            0x0d, 2, 0, 0, 0,
+        ];
+        assert_eq!(fields(&buf).unwrap(), [
+            (1, OptionValue::Varint(150)),
+            (1, OptionValue::Fixed64(1)),
+            (1, OptionValue::Bytes(b"hi".to_vec())),
+            (1, OptionValue::Fixed32(2)),
+        ]);
+
+        assert!(fields(&[0x0a, 5, b'h']).is_err());
+        assert!(fields(&[0x09, 0]).is_err());
+        assert!(fields(&[0x0b]).is_err());
+    }
+
+    #[test]
+    fn display_values() {
+        let vals = [
+            OptionValue::Varint(7),
+            OptionValue::Bytes(b"date".to_vec()),
+            OptionValue::Bytes(b"\0\x01".to_vec()),
+            OptionValue::Fixed32(3),
+        ];
+        assert_eq!(Values(&vals).to_string(), r#"7, "date", <2 bytes>, 3"#);
+    }
+
+    #[test]
+    fn read_options() {
+        let map = options(
+            r#"
+            message Event {
+                option (version) = 2;
+                option deprecated = true;
+
+                optional string at = 1 [(format) = "date", (sensitive) = true];
+                optional string id = 2 [deprecated = false];
+
+                enum Kind {
+                    option deprecated = true;
+                    KIND_UNKNOWN = 0 [deprecated = true];
+                }
+            }
+            "#,
+        );
+
+        assert_eq!(set(&map, "test.Event"), [
+            ("(test.version)".into(), "2".into()),
+            ("deprecated".into(), "1".into()),
+        ]);
+        assert_eq!(set(&map, "test.Event.at"), [
+            ("(test.format)".into(), r#""date""#.into()),
+            ("(test.sensitive)".into(), "1".into()),
+        ]);
+        assert_eq!(set(&map, "test.Event.id"), []);
+        assert_eq!(set(&map, "test.Event.Kind"), [(
+            "deprecated".into(),
+            "1".into()
+        )]);
+        assert_eq!(set(&map, "test.Event.Kind.KIND_UNKNOWN"), [(
+            "deprecated".into(),
+            "1".into()
+        )]);
+        assert_eq!(set(&map, "package \"test\""), []);
+    }
+
+    #[test]
+    fn unknown_extension() {
+        let desc = descriptors(r#"message Event { optional string at = 1 [(format) = "date"]; }"#);
+        let map = OptionMap::new(&desc.raw, &Extensions::default()).unwrap();
+
+        assert_eq!(set(&map, "test.Event.at"), [(
+            "(50000)".into(),
+            r#""date""#.into()
+        )]);
+    }
+
+    #[test]
+    fn options_files() {
+        let desc = descriptors("");
+        let [ref descriptor, ref test] = *desc.set.file else {
+            panic!("Expected two files, got {:?}", desc.set.file);
+        };
+
+        assert!(defines_options(descriptor));
+        assert!(defines_options(test));
+        assert!(!defines_options(&FileDescriptorProto::default()));
+    }
+
+    #[test]
+    fn policy_rules() {
+        let rule: PolicyRule = " (validate.rules) = Error ".parse().unwrap();
+        assert_eq!(rule.option, "(validate.rules)");
+        assert_eq!(rule.severity, Severity::Error);
+
+        assert!("deprecated".parse::<PolicyRule>().is_err());
+        assert!("deprecated=fatal".parse::<PolicyRule>().is_err());
+
+        let policy = OptionPolicy::new([]);
+        assert_eq!(policy.severity("deprecated"), Severity::Ignore);
+        assert_eq!(policy.severity("(test.format)"), Severity::Warn);
+
+        let policy = OptionPolicy::new(
+            ["*=error", "deprecated=warn"]
+                .into_iter()
+                .map(|r| r.parse().unwrap()),
+        );
+        assert_eq!(policy.severity("deprecated"), Severity::Warn);
+        assert_eq!(policy.severity("(test.format)"), Severity::Error);
+    }
+
+    const UNCHANGED: &str = r#"message Event { optional string at = 1 [(format) = "date"]; }"#;
+    const CHANGED: &str = r#"message Event { optional string at = 1 [(format) = "time"]; }"#;
+    const UNSET: &str = "message Event { optional string at = 1; }";
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(check(UNCHANGED, UNCHANGED, &[]), (vec![], vec![]));
+    }
+
+    #[test]
+    fn changed() {
+        assert_eq!(check(UNCHANGED, CHANGED, &[]), (vec![], vec![
+            r#"Option (test.format) changed: "date" in reader, "time" in writer"#.into()
+        ]));
+    }
+
+    #[test]
+    fn one_side() {
+        assert_eq!(check(UNCHANGED, UNSET, &[]), (vec![], vec![
+            r#"Option (test.format) only set on reader: "date""#.into()
+        ]));
+        assert_eq!(check(UNSET, UNCHANGED, &[]), (vec![], vec![
+            r#"Option (test.format) only set on writer: "date""#.into()
+        ]));
+    }
+
+    #[test]
+    fn missing_element() {
+        assert_eq!(
+            check(UNCHANGED, "message Other {}", &["*=error"]),
+            (vec![], vec![])
+        );
+    }
+
+    #[test]
+    fn severities() {
+        let deprecated = "message Event { optional string at = 1 [deprecated = true]; }";
+
+        assert_eq!(check(deprecated, UNSET, &[]), (vec![], vec![]));
+        assert_eq!(check(deprecated, UNSET, &["deprecated=warn"]), (vec![], vec![
+            "Option deprecated only set on reader: 1".into()
+        ]));
+        assert_eq!(check(UNCHANGED, CHANGED, &["(test.format)=error"]), (
+            vec![r#"Option (test.format) changed: "date" in reader, "time" in writer"#.into()],
+            vec![]
+        ));
+        assert_eq!(check(UNCHANGED, CHANGED, &["*=ignore"]), (vec![], vec![]));
+    }
+}
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 use prost_types::field_descriptor_proto::Type;
 
 use super::{field_kind::FieldKind, field_type::FieldTypeContext};
@@ -47,19 +49,44 @@ impl PrimitiveType {
         })
     }
 
+    /// The name of this type in a `.proto` file
+    pub const fn proto_name(self) -> &'static str {
+        match self {
+            Self::F64 => "double",
+            Self::F32 => "float",
+            Self::VarI64 => "int64",
+            Self::VarU64 => "uint64",
+            Self::VarI32 => "int32",
+            Self::FixU64 => "fixed64",
+            Self::FixU32 => "fixed32",
+            Self::Bool => "bool",
+            Self::String => "string",
+            Self::Bytes => "bytes",
+            Self::VarU32 => "uint32",
+            Self::FixI32 => "sfixed32",
+            Self::FixI64 => "sfixed64",
+            Self::VarZ32 => "sint32",
+            Self::VarZ64 => "sint64",
+        }
+    }
+
     pub fn wire_format(self, kind: FieldKind) -> WireType {
         match self {
             Self::F64 => WireType::Fix64(FixIntMode::Float),
             Self::F32 => WireType::Fix32(FixIntMode::Float),
-            Self::VarI64 | Self::VarI32 => WireType::VarInt(VarIntMode::Signed),
-            Self::VarU64 | Self::VarU32 | Self::Bool => WireType::VarInt(VarIntMode::Unsigned),
+            Self::VarI64 => WireType::VarInt(VarIntMode::Signed(IntWidth::W64)),
+            Self::VarI32 => WireType::VarInt(VarIntMode::Signed(IntWidth::W32)),
+            Self::VarU64 => WireType::VarInt(VarIntMode::Unsigned(IntWidth::W64)),
+            Self::VarU32 => WireType::VarInt(VarIntMode::Unsigned(IntWidth::W32)),
+            Self::Bool => WireType::VarInt(VarIntMode::Bool),
             Self::FixU64 => WireType::Fix64(FixIntMode::Unsigned),
             Self::FixU32 => WireType::Fix32(FixIntMode::Unsigned),
             Self::String => WireType::Bytes(BytesMode::Utf8),
             Self::Bytes => WireType::Bytes(BytesMode::Bytes),
             Self::FixI32 => WireType::Fix32(FixIntMode::Signed),
             Self::FixI64 => WireType::Fix64(FixIntMode::Signed),
-            Self::VarZ32 | Self::VarZ64 => WireType::VarInt(VarIntMode::ZigZag),
+            Self::VarZ32 => WireType::VarInt(VarIntMode::ZigZag(IntWidth::W32)),
+            Self::VarZ64 => WireType::VarInt(VarIntMode::ZigZag(IntWidth::W64)),
         }
         .adjust_for_kind(kind)
     }
@@ -86,19 +113,45 @@ impl WireType {
         }
     }
 
+    fn is_numeric(self) -> bool { !matches!(self, Self::Bytes(_)) }
+
     pub fn adjust_for_kind(self, kind: FieldKind) -> Self {
         match (self.to_numeric(), kind) {
-            (
-                Some(n),
-                FieldKind::Repeated {
-                    packed: None | Some(true),
-                },
-            ) => Self::Bytes(BytesMode::Packed(n)),
+            (Some(n), FieldKind::Repeated { packed: true }) => Self::Bytes(BytesMode::Packed(n)),
             (..) => self,
         }
     }
 }
 
+impl NumericWireType {
+    fn into_wire(self) -> WireType {
+        match self {
+            Self::VarInt(m) => WireType::VarInt(m),
+            Self::Fix32(m) => WireType::Fix32(m),
+            Self::Fix64(m) => WireType::Fix64(m),
+            Self::Bytes(b) => match b {},
+        }
+    }
+}
+
+fn warn_packed_mismatch(
+    cx: &CompatPair<FieldTypeContext<'_>>,
+    reader_packed: bool,
+    log: &mut CompatLog,
+) {
+    let pair = if reader_packed {
+        CompatPair::new("packed", "unpacked")
+    } else {
+        CompatPair::new("unpacked", "packed")
+    };
+
+    CompatError::new(
+        cx.as_ref().map(|c| c.field.to_owned()).into(),
+        format!("Packed encoding mismatch ({})", pair.display()),
+    )
+    .warn(log);
+}
+
 impl CheckCompat for WireType {
     type Context<'a> = FieldTypeContext<'a>;
 
@@ -118,6 +171,16 @@ impl CheckCompat for WireType {
             (WireType::Bytes(ref reader), WireType::Bytes(ref writer)) => {
                 CompatPair::new(reader, writer).check(cx, log);
             },
+            // Conforming parsers accept both packed and unpacked encodings for
+            // repeated numeric fields, but older and hand-rolled ones may not
+            (&WireType::Bytes(BytesMode::Packed(reader)), writer) if writer.is_numeric() => {
+                warn_packed_mismatch(&cx, true, log);
+                CompatPair::new(&reader.into_wire(), writer).check(cx, log);
+            },
+            (reader, &WireType::Bytes(BytesMode::Packed(writer))) if reader.is_numeric() => {
+                warn_packed_mismatch(&cx, false, log);
+                CompatPair::new(reader, &writer.into_wire()).check(cx, log);
+            },
             (rd, wr) => CompatError::new(
                 cx.map(|c| c.field.to_owned()).into(),
                 format!(
@@ -129,14 +192,37 @@ impl CheckCompat for WireType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntWidth {
+    W32,
+    W64,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VarIntMode {
-    Signed,
-    Unsigned,
-    ZigZag,
+    Bool,
+    Signed(IntWidth),
+    Unsigned(IntWidth),
+    ZigZag(IntWidth),
     Enum,
 }
 
+impl VarIntMode {
+    /// The number of significant bits retained when decoding this varint
+    fn bits(self) -> u8 {
+        match self {
+            Self::Bool => 1,
+            Self::Signed(IntWidth::W32)
+            | Self::Unsigned(IntWidth::W32)
+            | Self::ZigZag(IntWidth::W32)
+            | Self::Enum => 32,
+            Self::Signed(IntWidth::W64)
+            | Self::Unsigned(IntWidth::W64)
+            | Self::ZigZag(IntWidth::W64) => 64,
+        }
+    }
+}
+
 impl CheckCompat for VarIntMode {
     type Context<'a> = FieldTypeContext<'a>;
 
@@ -145,35 +231,52 @@ impl CheckCompat for VarIntMode {
         cx: CompatPair<Self::Context<'_>>,
         log: &mut CompatLog,
     ) {
-        match ck.into_inner() {
-            (a, b) if a == b => (),
-            (rd @ (Self::Signed | Self::Unsigned), wr @ (Self::Signed | Self::Unsigned)) => {
+        let (&rd, &wr) = ck.into_inner();
+        if rd == wr {
+            return;
+        }
+
+        let pair = CompatPair::new(rd, wr);
+        let field = || cx.as_ref().map(|c| c.field.to_owned()).into();
+
+        match pair.into_inner() {
+            (Self::ZigZag(_), Self::ZigZag(_)) => (),
+            (Self::ZigZag(_), _) | (_, Self::ZigZag(_)) => {
                 CompatError::new(
-                    cx.map(|c| c.field.to_owned()).into(),
-                    format!(
-                        "Varint sign difference ({:?})",
-                        CompatPair::new(rd, wr).display()
-                    ),
+                    field(),
+                    format!("Incompatible varint formats ({:?})", pair.display()),
+                )
+                .err(log);
+                return;
+            },
+            (Self::Signed(_), Self::Unsigned(_)) | (Self::Unsigned(_), Self::Signed(_)) => {
+                CompatError::new(
+                    field(),
+                    format!("Varint sign difference ({:?})", pair.display()),
                 )
                 .warn(log);
             },
-            (rd @ (Self::Signed | Self::Unsigned), wr @ Self::Enum)
-            | (rd @ Self::Enum, wr @ (Self::Signed | Self::Unsigned)) => CompatError::new(
-                cx.map(|c| c.field.to_owned()).into(),
-                format!(
-                    "Enum type punning ({:?})",
-                    CompatPair::new(rd, wr).display()
-                ),
-            )
-            .warn(log),
-            (rd, wr) => CompatError::new(
-                cx.map(|c| c.field.to_owned()).into(),
+            (Self::Enum, _) | (_, Self::Enum) => {
+                CompatError::new(field(), format!("Enum type punning ({:?})", pair.display()))
+                    .warn(log);
+            },
+            (..) => (),
+        }
+
+        match rd.bits().cmp(&wr.bits()) {
+            Ordering::Less => CompatError::new(
+                field(),
                 format!(
-                    "Incompatible varint formats ({:?})",
-                    CompatPair::new(rd, wr).display()
+                    "Varint narrowing may truncate values ({:?})",
+                    pair.display()
                 ),
             )
             .err(log),
+            Ordering::Greater => {
+                CompatError::new(field(), format!("Varint widening ({:?})", pair.display()))
+                    .warn(log);
+            },
+            Ordering::Equal => (),
         }
     }
 }
@@ -250,6 +353,9 @@ impl CheckCompat for BytesMode {
                 )
                 .warn(log);
             },
+            (Self::Packed(rd), Self::Packed(wr)) => {
+                CompatPair::new(&rd.into_wire(), &wr.into_wire()).check(cx, log);
+            },
             (rd, wr) => CompatError::new(
                 cx.map(|c| c.field.to_owned()).into(),
                 format!(
@@ -261,3 +367,105 @@ impl CheckCompat for BytesMode {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        FieldKind, FieldTypeContext,
+        PrimitiveType::{
+            self, Bool, Bytes, FixI32, FixI64, FixU32, FixU64, String, VarI32, VarI64, VarU32,
+            VarU64, VarZ32, VarZ64, F32, F64,
+        },
+    };
+    use crate::{
+        check_compat::CompatLog,
+        compat_pair::CompatPair,
+        schema::{qual_name::QualName, TypeMap},
+    };
+
+    /// Check a reader field against a writer field, returning the number of
+    /// errors and warnings logged
+    fn check(
+        (reader, rd_kind): (PrimitiveType, FieldKind),
+        (writer, wr_kind): (PrimitiveType, FieldKind),
+    ) -> (usize, usize) {
+        let types = TypeMap::default();
+        let name = QualName::new(None, vec!["Msg".into()]);
+        let cx = |kind| FieldTypeContext {
+            field: name.member("field"),
+            types: &types,
+            kind,
+        };
+
+        let mut log = CompatLog::default();
+        CompatPair::new(&reader.wire_format(rd_kind), &writer.wire_format(wr_kind))
+            .check(CompatPair::new(cx(rd_kind), cx(wr_kind)), &mut log);
+
+        (log.errors().len(), log.warnings().len())
+    }
+
+    fn singular(reader: PrimitiveType, writer: PrimitiveType) -> (usize, usize) {
+        check((reader, FieldKind::Singular), (writer, FieldKind::Singular))
+    }
+
+    #[test]
+    fn identical() {
+        for ty in [
+            F64, F32, VarI64, VarU64, VarI32, FixU64, FixU32, Bool, String, Bytes, VarU32, FixI32,
+            FixI64, VarZ32, VarZ64,
+        ] {
+            assert_eq!(singular(ty, ty), (0, 0), "{}", ty.proto_name());
+        }
+    }
+
+    #[test]
+    fn varint_width() {
+        // Reading a wider value into a narrower field truncates it
+        assert_eq!(singular(VarI32, VarI64), (1, 0));
+        assert_eq!(singular(VarU32, VarU64), (1, 0));
+        assert_eq!(singular(VarZ32, VarZ64), (1, 0));
+
+        // Reading a narrower value into a wider field is lossless
+        assert_eq!(singular(VarI64, VarI32), (0, 1));
+        assert_eq!(singular(VarU64, VarU32), (0, 1));
+        assert_eq!(singular(VarZ64, VarZ32), (0, 1));
+    }
+
+    #[test]
+    fn varint_zigzag() {
+        assert_eq!(singular(VarZ32, VarI32), (1, 0));
+        assert_eq!(singular(VarI32, VarZ32), (1, 0));
+        assert_eq!(singular(VarZ64, VarU64), (1, 0));
+        assert_eq!(singular(VarU64, VarZ64), (1, 0));
+        assert_eq!(singular(Bool, VarZ32), (1, 0));
+    }
+
+    #[test]
+    fn varint_bool() {
+        assert_eq!(singular(Bool, VarI32), (1, 0));
+        assert_eq!(singular(VarI32, Bool), (0, 1));
+    }
+
+    #[test]
+    fn varint_sign() {
+        assert_eq!(singular(VarI32, VarU32), (0, 1));
+        assert_eq!(singular(VarU64, VarI64), (0, 1));
+        // Sign and width differences are reported separately
+        assert_eq!(singular(VarU32, VarI64), (1, 1));
+    }
+
+    #[test]
+    fn packed() {
+        let packed = FieldKind::Repeated { packed: true };
+        let unpacked = FieldKind::Repeated { packed: false };
+
+        assert_eq!(check((VarI32, packed), (VarI32, packed)), (0, 0));
+        assert_eq!(check((VarI32, packed), (VarI32, unpacked)), (0, 1));
+        assert_eq!(check((VarI32, unpacked), (VarI32, packed)), (0, 1));
+        assert_eq!(check((VarI32, packed), (VarI64, packed)), (1, 0));
+        assert_eq!(check((VarI32, unpacked), (VarZ32, packed)), (1, 1));
+        assert_eq!(check((FixU32, packed), (FixU64, unpacked)), (1, 1));
+        // Non-numeric types are never packed
+        assert_eq!(check((String, packed), (String, unpacked)), (0, 0));
+    }
+}
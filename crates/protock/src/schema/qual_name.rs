@@ -20,6 +20,23 @@ impl fmt::Debug for QualName<'_> {
     }
 }
 
+/// Formats the name as it would be written in a `.proto` file
+impl fmt::Display for QualName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+
+        for part in self.package.iter().chain(&self.path) {
+            if !first {
+                f.write_str(".")?;
+            }
+            first = false;
+            f.write_str(part)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl<'a> QualName<'a> {
     #[inline]
     pub const fn new(package: Option<Cow<'a, str>>, path: Vec<Cow<'a, str>>) -> Self {
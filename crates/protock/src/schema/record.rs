@@ -96,6 +96,21 @@ impl<T: for<'a> RecordValue<'a>> Record<T> {
 
     #[inline]
     pub const fn internal(&self) -> bool { self.internal }
+
+    #[inline]
+    pub const fn numbers(&self) -> &HashMap<i32, T> { &self.numbers }
+
+    #[inline]
+    pub const fn reserved(&self) -> &RangeSet<i64> { &self.reserved }
+
+    #[inline]
+    pub const fn extra(&self) -> &T::Extra { &self.extra }
+
+    pub fn reserved_names(&self) -> impl Iterator<Item = &str> {
+        self.names
+            .iter()
+            .filter_map(|(k, v)| v.is_none().then_some(k.as_str()))
+    }
 }
 
 impl<T: for<'a> RecordValue<'a>> CheckCompat for Record<T> {
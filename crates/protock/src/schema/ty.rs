@@ -50,6 +50,22 @@ impl Type {
         }
     }
 
+    #[inline]
+    pub const fn as_message(&self) -> Option<&Record<Field>> {
+        match self.0 {
+            Kind::Message(ref m) => Some(m),
+            Kind::Enum(_) => None,
+        }
+    }
+
+    #[inline]
+    pub const fn as_enum(&self) -> Option<&Record<Variant>> {
+        match self.0 {
+            Kind::Message(_) => None,
+            Kind::Enum(ref e) => Some(e),
+        }
+    }
+
     pub fn wire_format(&self, kind: FieldKind) -> WireType {
         match self.0 {
             Kind::Message(_) => WireType::Bytes(BytesMode::Message),
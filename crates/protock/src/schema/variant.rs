@@ -13,7 +13,20 @@ use crate::{
 #[repr(transparent)]
 pub struct Variant(BTreeSet<String>);
 
-pub type VariantExtra = ();
+/// Enum-wide information not tied to a single variant
+#[derive(Debug, Default)]
+pub struct VariantExtra {
+    /// The value of the first declared variant, which unset fields read as
+    first: Option<i32>,
+}
+
+impl VariantExtra {
+    #[inline]
+    pub const fn new(first: Option<i32>) -> Self { Self { first } }
+
+    #[inline]
+    pub const fn first(&self) -> Option<i32> { self.first }
+}
 
 impl Variant {
     #[inline]
@@ -170,7 +183,9 @@ impl<'a> RecordValue<'a> for Variant {
     ) where
         Self: Sized,
     {
-        let ((), ()) = extra.into_inner();
+        // The first variant only matters for proto2 fields without an
+        // explicit default, which are checked with the fields themselves
+        let _ = extra;
 
         for side in ck.iter() {
             let (side, (&value, var)) = side.split();
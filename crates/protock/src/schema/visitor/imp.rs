@@ -2,22 +2,24 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 
 use prost_types::{
     descriptor_proto::ReservedRange, enum_descriptor_proto::EnumReservedRange,
-    file_options::OptimizeMode, DescriptorProto, EnumDescriptorProto, EnumOptions,
-    EnumValueDescriptorProto, FieldDescriptorProto, FieldOptions, FileDescriptorProto,
-    FileDescriptorSet, FileOptions, MessageOptions, OneofDescriptorProto,
+    field_descriptor_proto, file_options::OptimizeMode, DescriptorProto, EnumDescriptorProto,
+    EnumOptions, EnumValueDescriptorProto, EnumValueOptions, FieldDescriptorProto, FieldOptions,
+    FileDescriptorProto, FileDescriptorSet, FileOptions, MessageOptions, OneofDescriptorProto,
 };
 use shrec::range_set::RangeSet;
 
 use super::{scope::GlobalScope, scope_ref::ScopeRef};
 use crate::schema::{
-    field::{Field, FieldExtra},
-    field_kind::FieldKind,
+    field::{Field, FieldExtra, Presence},
+    field_kind::{FieldKind, Syntax},
     field_type::FieldType,
     oneof::Oneof,
+    options,
     primitive::PrimitiveType,
+    qual_name::QualName,
     record::Record,
     ty::Type,
-    variant::Variant,
+    variant::{Variant, VariantExtra},
     Schema,
 };
 
@@ -35,13 +37,24 @@ impl Visitor<'_> {
 
         let FileDescriptorSet { file } = desc;
 
-        file.iter().for_each(|f| self.fildes(&scope, f));
+        // Files declaring custom options describe the options themselves
+        // rather than any data, and are typically proto2, so skip them
+        let option_files: HashSet<_> = file
+            .iter()
+            .filter(|f| options::defines_options(f))
+            .filter_map(|f| f.name.as_deref())
+            .collect();
+
+        file.iter()
+            .filter(|f| !options::defines_options(f))
+            .for_each(|f| self.fildes(&scope, &option_files, f));
     }
 
     #[inline]
     fn descend(
         &mut self,
         scope: &ScopeRef<'_>,
+        syntax: Syntax,
         msgs: &[DescriptorProto],
         enums: &[EnumDescriptorProto],
     ) {
@@ -51,6 +64,7 @@ impl Visitor<'_> {
                     .clone()
                     .child(m.name.as_deref().unwrap())
                     .expect("Missing message scope"),
+                syntax,
                 m,
             );
         }
@@ -66,7 +80,12 @@ impl Visitor<'_> {
         }
     }
 
-    fn fildes(&mut self, scope: &GlobalScope<'_>, desc: &FileDescriptorProto) {
+    fn fildes(
+        &mut self,
+        scope: &GlobalScope<'_>,
+        option_files: &HashSet<&str>,
+        desc: &FileDescriptorProto,
+    ) {
         let FileDescriptorProto {
             name: _,
             package,
@@ -82,15 +101,17 @@ impl Visitor<'_> {
             syntax,
         } = desc;
 
-        assert!(dependency.iter().all(|d| d.starts_with("google/protobuf")));
+        assert!(dependency
+            .iter()
+            .all(|d| d.starts_with("google/protobuf") || option_files.contains(&**d)));
         assert!(public_dependency.is_empty());
         assert!(weak_dependency.is_empty());
         assert!(service.is_empty());
         assert!(extension.is_empty());
         assert!(source_code_info.is_none());
-        assert_eq!(syntax.as_deref(), Some("proto3"));
+        let syntax = Syntax::new(syntax.as_deref()).expect("Unsupported syntax version");
 
-        let (optimize, deprecated) = if let Some(opts) = options {
+        let (_optimize, _deprecated) = if let Some(opts) = options {
             #[expect(
                 deprecated,
                 reason = "Explicitly ignoring java_generate_equals_and_hash"
@@ -133,10 +154,10 @@ impl Visitor<'_> {
 
         let scope = scope.package_ref(package.as_ref()).unwrap();
 
-        self.descend(&scope, message_type, enum_type);
+        self.descend(&scope, syntax, message_type, enum_type);
     }
 
-    fn desc(&mut self, scope: &ScopeRef<'_>, desc: &DescriptorProto) {
+    fn desc(&mut self, scope: &ScopeRef<'_>, syntax: Syntax, desc: &DescriptorProto) {
         let DescriptorProto {
             name,
             field,
@@ -177,11 +198,22 @@ impl Visitor<'_> {
             (false, false)
         };
 
+        // Entry types generated by the compiler for this message's map fields
+        let map_entries: HashSet<_> = nested_type
+            .iter()
+            .filter(|m| m.options.as_ref().and_then(|o| o.map_entry) == Some(true))
+            .map(|m| {
+                scope
+                    .qualify([m.name.as_deref().unwrap()])
+                    .expect("Invalid map entry name")
+            })
+            .collect();
+
         let mut numbers = HashMap::new();
         let mut oneofs = vec![];
 
         for field in field {
-            Self::field(&mut numbers, scope, field);
+            Self::field(&mut numbers, scope, syntax, &map_entries, field);
         }
 
         for oneof in oneof_decl {
@@ -220,13 +252,15 @@ impl Visitor<'_> {
             )
             .is_none());
 
-        self.descend(scope, nested_type, enum_type);
+        self.descend(scope, syntax, nested_type, enum_type);
     }
 
     #[inline]
     fn field(
         numbers: &mut HashMap<i32, Field>,
         scope: &ScopeRef<'_>,
+        syntax: Syntax,
+        map_entries: &HashSet<QualName<'_>>,
         field: &FieldDescriptorProto,
     ) {
         let FieldDescriptorProto {
@@ -236,7 +270,7 @@ impl Visitor<'_> {
             r#type,
             type_name,
             extendee,
-            default_value: _,
+            default_value,
             oneof_index,
             json_name: _,
             options,
@@ -247,6 +281,10 @@ impl Visitor<'_> {
         let number = number.unwrap();
         let label = label.and_then(|l| l.try_into().ok()).unwrap();
         let ty = r#type.and_then(|t| t.try_into().ok());
+        let message = matches!(
+            ty,
+            Some(field_descriptor_proto::Type::Message | field_descriptor_proto::Type::Group)
+        );
         let type_name = type_name.as_ref();
         assert!(extendee.is_none());
 
@@ -256,7 +294,7 @@ impl Visitor<'_> {
                 packed,
                 jstype,
                 lazy,
-                deprecated,
+                deprecated: _,
                 weak,
                 uninterpreted_option,
             } = opts;
@@ -264,7 +302,6 @@ impl Visitor<'_> {
             assert!(ctype.is_none());
             assert!(jstype.is_none());
             assert!(lazy.is_none());
-            assert!(deprecated.is_none());
             assert!(weak.is_none());
             assert!(uninterpreted_option.is_empty());
 
@@ -273,30 +310,42 @@ impl Visitor<'_> {
             None
         };
 
+        let ty = if let Some(ty) = ty.and_then(PrimitiveType::new) {
+            assert!(type_name.is_none());
+            FieldType::Primitive(ty)
+        } else {
+            let type_name = type_name.unwrap();
+
+            let qual = if let Some(type_name) = type_name.strip_prefix('.') {
+                scope
+                    .global()
+                    .resolve(type_name.split('.'))
+                    .expect("Couldn't resolve fully-qualified type name")
+                    .to_owned()
+            } else {
+                scope
+                    .search(type_name.split('.'))
+                    .expect("Couldn't find valid scope for name")
+                    .to_owned()
+            };
+
+            FieldType::Named(qual)
+        };
+
+        let map = matches!(ty, FieldType::Named(ref q) if map_entries.contains(q));
+
+        let kind = FieldKind::new(label, packed, *proto3_optional, syntax, map);
         let field = Field::new(
             name.into(),
-            if let Some(ty) = ty.and_then(PrimitiveType::new) {
-                assert!(type_name.is_none());
-                FieldType::Primitive(ty)
-            } else {
-                let type_name = type_name.unwrap();
-
-                let qual = if let Some(type_name) = type_name.strip_prefix('.') {
-                    scope
-                        .global()
-                        .resolve(type_name.split('.'))
-                        .expect("Couldn't resolve fully-qualified type name")
-                        .to_owned()
-                } else {
-                    scope
-                        .search(type_name.split('.'))
-                        .expect("Couldn't find valid scope for name")
-                        .to_owned()
-                };
-
-                FieldType::Named(qual)
-            },
-            FieldKind::new(label, packed, *proto3_optional),
+            ty,
+            kind,
+            Presence::new(
+                kind,
+                syntax,
+                message,
+                oneof_index.is_some(),
+                default_value.clone(),
+            ),
             oneof_index.map(|i| usize::try_from(i).unwrap().into()),
         );
 
@@ -343,7 +392,14 @@ impl Visitor<'_> {
 
             let name = name.as_ref().unwrap();
             let number = number.unwrap();
-            assert!(options.is_none());
+
+            if let Some(EnumValueOptions {
+                deprecated: _,
+                uninterpreted_option,
+            }) = options
+            {
+                assert!(uninterpreted_option.is_empty());
+            }
 
             if aliasing {
                 assert!(numbers.entry(number).or_default().insert(name.into()));
@@ -381,7 +437,7 @@ impl Visitor<'_> {
                     reserved,
                     reserved_names,
                     false,
-                    (),
+                    VariantExtra::new(value.first().and_then(|v| v.number)),
                 ))
             )
             .is_none());
@@ -7,10 +7,9 @@ use crate::prelude::*;
 #[derive(Default)]
 pub(super) struct Args {
     trait_name: Option<syn::Ident>,
+    typestate: Option<syn::Ident>,
 }
 
-// TODO(design): Refactor builder interface to handle errors better
-
 impl Args {
     pub fn parser(&'_ mut self) -> impl syn::parse::Parser<Output = ()> + '_ {
         syn::meta::parser(|meta| {
@@ -22,21 +21,171 @@ impl Args {
                 return Ok(());
             }
 
+            if meta.path.is_ident("typestate") {
+                let _eq: syn::token::Eq = meta.input.parse()?;
+                let ident: syn::Ident = meta.input.parse()?;
+
+                self.typestate = Some(ident);
+                return Ok(());
+            }
+
             Err(meta.error("Invalid #[builder] attribute"))
         })
     }
 
-    fn finish(self, span: Span) -> Result<(syn::Ident,), syn::Error> {
+    fn finish(self, span: Span) -> Result<(syn::Ident, Option<syn::Ident>), syn::Error> {
         let trait_name = self
             .trait_name
             .ok_or_else(|| span.error("Missing trait_name in #[builder] attribute"))?;
 
-        Ok((trait_name,))
+        Ok((trait_name, self.typestate))
+    }
+}
+
+/// A state transition declared on a builder method with `#[typestate(...)]`
+struct Transition {
+    from: Vec<syn::Path>,
+    to: syn::Path,
+}
+
+impl syn::parse::Parse for Transition {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let from =
+            syn::punctuated::Punctuated::<syn::Path, syn::Token![|]>::parse_separated_nonempty(
+                input,
+            )?;
+        let _arrow: syn::Token![=>] = input.parse()?;
+        let to = input.parse()?;
+
+        Ok(Self {
+            from: from.into_iter().collect(),
+            to,
+        })
+    }
+}
+
+/// The signature of a builder method, as needed to generate its type-state
+/// wrapper
+struct Method {
+    attrs: Vec<syn::Attribute>,
+    sig: syn::Signature,
+    transition: Option<Transition>,
+}
+
+/// If the given return type is of the form `Result<(), E>`, return the path
+/// segment containing the `Result` type
+fn fallible_output(ret: &syn::ReturnType) -> Option<&syn::PathSegment> {
+    let syn::ReturnType::Type(_, ref t) = *ret else {
+        return None;
+    };
+    let syn::Type::Path(syn::TypePath {
+        qself: None,
+        ref path,
+    }) = **t
+    else {
+        return None;
+    };
+    let seg = path.segments.last()?;
+
+    if seg.ident != "Result" {
+        return None;
+    }
+
+    let syn::PathArguments::AngleBracketed(ref args) = seg.arguments else {
+        return None;
+    };
+
+    match args.args.first() {
+        Some(syn::GenericArgument::Type(syn::Type::Tuple(t))) if t.elems.is_empty() => Some(seg),
+        _ => None,
+    }
+}
+
+/// Replace the `()` in a `Result<(), E>` type with the given type
+fn map_fallible_output(seg: &syn::PathSegment, ok: syn::Type) -> syn::PathSegment {
+    let mut seg = seg.clone();
+    let syn::PathArguments::AngleBracketed(ref mut args) = seg.arguments else {
+        unreachable!();
+    };
+    *args.args.first_mut().unwrap_or_else(|| unreachable!()) = syn::GenericArgument::Type(ok);
+    seg
+}
+
+fn take_transition(m: &mut syn::ImplItemFn) -> Result<Option<Transition>, syn::Error> {
+    let mut transition = None;
+    let mut err = None::<syn::Error>;
+
+    m.attrs.retain(|a| {
+        if !a.path().is_ident("typestate") {
+            return true;
+        }
+
+        let res = if transition.is_some() {
+            Err(a.span().error("Duplicate #[typestate] attribute"))
+        } else {
+            a.parse_args().map(|t| transition = Some(t))
+        };
+
+        if let Err(e) = res {
+            match err {
+                Some(ref mut err) => err.combine(e),
+                None => err = Some(e),
+            }
+        }
+
+        false
+    });
+
+    err.map_or(Ok(transition), Err)
+}
+
+fn collect_methods(
+    body: &mut syn::ItemImpl,
+    typestate: bool,
+    vis: &mut Option<syn::Visibility>,
+    diag: &mut TokenStream,
+) -> Vec<Method> {
+    let mut methods = vec![];
+
+    if typestate && !body.generics.params.is_empty() {
+        diag.extend(
+            body.generics
+                .span()
+                .error("#[builder] with a typestate cannot be used on a generic impl block")
+                .into_compile_error(),
+        );
+    }
+
+    for item in &mut body.items {
+        if let Err((span, err)) = is_builder_method(item, vis) {
+            diag.extend(span.error(err).into_compile_error());
+            continue;
+        }
+
+        let syn::ImplItem::Fn(m) = item else {
+            unreachable!()
+        };
+
+        match take_transition(m) {
+            Ok(Some(_)) if !typestate => diag.extend(
+                m.span()
+                    .error("#[typestate] requires a typestate name in the #[builder] attribute")
+                    .into_compile_error(),
+            ),
+            Ok(transition) => methods.push(Method {
+                attrs: m.attrs.clone(),
+                sig: m.sig.clone(),
+                transition,
+            }),
+            Err(e) => diag.extend(e.into_compile_error()),
+        }
     }
+
+    methods
 }
 
 pub(super) fn run(args: Args, arg_span: Span, mut body: syn::ItemImpl) -> TokenStream {
-    let (trait_name,) = match args.finish(arg_span) {
+    let (trait_name, typestate) = match args.finish(arg_span) {
         Ok(a) => a,
         Err(e) => return e.into_compile_error(),
     };
@@ -51,11 +200,7 @@ pub(super) fn run(args: Args, arg_span: Span, mut body: syn::ItemImpl) -> TokenS
 
     let mut diag = TokenStream::new();
     let mut vis = None;
-    for item in &body.items {
-        if let Err((span, err)) = is_builder_method(item, &mut vis) {
-            diag.extend(span.error(err).into_compile_error());
-        }
-    }
+    let sigs = collect_methods(&mut body, typestate.is_some(), &mut vis, &mut diag);
 
     if !diag.is_empty() {
         return diag;
@@ -119,6 +264,9 @@ pub(super) fn run(args: Args, arg_span: Span, mut body: syn::ItemImpl) -> TokenS
         });
     let attrs = &body.attrs;
     let ty_name = &body.self_ty;
+    let typestate = typestate.map_or_else(TokenStream::new, |t| {
+        make_typestate(&t, &trait_name, vis.as_ref(), ty_name, sigs)
+    });
     quote_spanned! { body.brace_token.span =>
         #diag
         #(#attrs)*
@@ -134,9 +282,158 @@ pub(super) fn run(args: Args, arg_span: Span, mut body: syn::ItemImpl) -> TokenS
             #[expect(non_camel_case_types, reason = "Macro-generated variable")]
             __Builder_T: ::std::borrow::BorrowMut<#ty_name> + ::std::marker::Sized
         > #trait_name <#trait_generics> for __Builder_T #impl_generic_where {}
+
+        #typestate
+    }
+}
+
+fn make_typestate(
+    name: &syn::Ident,
+    trait_name: &syn::Ident,
+    vis: Option<&syn::Visibility>,
+    ty_name: &syn::Type,
+    methods: Vec<Method>,
+) -> TokenStream {
+    let span = name.span();
+    let vis = vis.cloned().unwrap_or(syn::Visibility::Inherited);
+    let doc = format!(
+        "Type-state wrapper for [`{}`], restricting which builder methods can be called based on \
+         its state parameter",
+        ty_name.to_token_stream().to_string().replace(' ', ""),
+    );
+
+    let impls = methods
+        .into_iter()
+        .map(|m| make_typestate_method(name, trait_name, &vis, ty_name, m));
+
+    quote_spanned! { span =>
+        #[doc = #doc]
+        #vis struct #name<
+            #[expect(non_camel_case_types, reason = "Macro-generated variable")]
+            __Builder_S,
+            #[expect(non_camel_case_types, reason = "Macro-generated variable")]
+            __Builder_T = #ty_name,
+        >(__Builder_T, ::std::marker::PhantomData<fn() -> __Builder_S>);
+
+        impl<
+            #[expect(non_camel_case_types, reason = "Macro-generated variable")]
+            __Builder_S,
+            #[expect(non_camel_case_types, reason = "Macro-generated variable")]
+            __Builder_T,
+        > #name<__Builder_S, __Builder_T> {
+            /// Wrap the given builder, assuming its contents are valid for the
+            /// state `S`
+            #[inline]
+            #[must_use]
+            #vis fn new_unchecked(inner: __Builder_T) -> Self {
+                Self(inner, ::std::marker::PhantomData)
+            }
+
+            /// Unwrap the inner builder
+            #[inline]
+            #[must_use]
+            #vis fn into_inner(self) -> __Builder_T { self.0 }
+        }
+
+        impl<
+            #[expect(non_camel_case_types, reason = "Macro-generated variable")]
+            __Builder_S,
+            #[expect(non_camel_case_types, reason = "Macro-generated variable")]
+            __Builder_T: ::std::fmt::Debug,
+        > ::std::fmt::Debug for #name<__Builder_S, __Builder_T> {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.debug_tuple(::std::stringify!(#name)).field(&self.0).finish()
+            }
+        }
+
+        #(#impls)*
     }
 }
 
+fn make_typestate_method(
+    name: &syn::Ident,
+    trait_name: &syn::Ident,
+    vis: &syn::Visibility,
+    ty_name: &syn::Type,
+    m: Method,
+) -> TokenStream {
+    let span = name.span();
+    let Method {
+        attrs,
+        mut sig,
+        transition,
+    } = m;
+    let ident = &sig.ident;
+    let fallible = fallible_output(&sig.output).cloned();
+
+    let mut args = vec![];
+    for (i, arg) in sig.inputs.iter_mut().skip(1).enumerate() {
+        let syn::FnArg::Typed(arg) = arg else {
+            unreachable!()
+        };
+        let arg_name = syn::Ident::new(&format!("__builder_arg{i}"), arg.pat.span());
+        *arg.pat = syn::parse_quote! { #arg_name };
+        args.push(arg_name);
+    }
+
+    *sig.inputs.first_mut().unwrap_or_else(|| unreachable!()) =
+        syn::parse_quote_spanned! { span => self };
+
+    let call = quote_spanned! { span =>
+        <__Builder_T as #trait_name>::#ident(self.0, #(#args),*)
+    };
+
+    // Methods without a declared transition are callable in any state and
+    // preserve it
+    let (impl_params, states, to): (_, Vec<syn::Type>, syn::Type) = match transition {
+        Some(Transition { from, to }) => (
+            quote_spanned! { span => },
+            from.into_iter().map(|p| syn::parse_quote! { #p }).collect(),
+            syn::parse_quote! { #to },
+        ),
+        None => (
+            quote_spanned! { span =>
+                #[expect(non_camel_case_types, reason = "Macro-generated variable")]
+                __Builder_S,
+            },
+            vec![syn::parse_quote_spanned! { span => __Builder_S }],
+            syn::parse_quote_spanned! { span => __Builder_S },
+        ),
+    };
+
+    let out: syn::Type = syn::parse_quote_spanned! { span => #name<#to, __Builder_T> };
+    let (must_use, body) = if let Some(seg) = fallible {
+        let seg = map_fallible_output(&seg, out);
+        sig.output = syn::parse_quote_spanned! { span => -> #seg };
+        (quote_spanned! { span => }, quote_spanned! { span =>
+            ::std::result::Result::map(#call, |b| #name(b, ::std::marker::PhantomData))
+        })
+    } else {
+        sig.output = syn::parse_quote_spanned! { span => -> #out };
+        (
+            quote_spanned! { span => #[must_use] },
+            quote_spanned! { span => #name(#call, ::std::marker::PhantomData) },
+        )
+    };
+
+    states
+        .into_iter()
+        .map(|from| {
+            quote_spanned! { span =>
+                impl<
+                    #impl_params
+                    #[expect(non_camel_case_types, reason = "Macro-generated variable")]
+                    __Builder_T: ::std::borrow::BorrowMut<#ty_name>
+                > #name<#from, __Builder_T> {
+                    #(#attrs)*
+                    #must_use
+                    #vis #sig { #body }
+                }
+            }
+        })
+        .collect::<TokenStream>()
+}
+
 fn is_builder_method(
     m: &syn::ImplItem,
     vis: &mut Option<syn::Visibility>,
@@ -149,7 +446,13 @@ fn is_builder_method(
         syn::ReturnType::Default => (),
         syn::ReturnType::Type(_, ref t) => match **t {
             syn::Type::Tuple(ref t) if t.elems.is_empty() => (),
-            _ => return Err((m.span(), "Builder method must return ()".into())),
+            _ if fallible_output(&m.sig.output).is_some() => (),
+            _ => {
+                return Err((
+                    m.span(),
+                    "Builder method must return () or Result<(), E>".into(),
+                ))
+            },
         },
     }
 
@@ -212,7 +515,16 @@ fn make_builder_method(m: syn::ImplItem, diag: &mut TokenStream) -> syn::ImplIte
         )]
     });
 
-    m.sig.output = syn::parse_quote_spanned! { span => -> Self };
+    let fallible = fallible_output(&m.sig.output)
+        .map(|seg| map_fallible_output(seg, syn::parse_quote_spanned! { span => Self }));
+    let ret = if let Some(seg) = fallible {
+        let ret = m.sig.output.clone();
+        m.sig.output = syn::parse_quote_spanned! { span => -> #seg };
+        Some(ret)
+    } else {
+        m.sig.output = syn::parse_quote_spanned! { span => -> Self };
+        None
+    };
 
     let this: syn::ExprPath = syn::parse_quote_spanned! { r.span() =>
         self
@@ -222,29 +534,51 @@ fn make_builder_method(m: syn::ImplItem, diag: &mut TokenStream) -> syn::ImplIte
         __builder_self
     };
 
-    m.block = syn::Block {
-        brace_token: m.block.brace_token,
-        stmts: vec![
-            syn::parse_quote_spanned! { m.sig.ident.span() =>
-                let #repl = ::std::borrow::BorrowMut::borrow_mut(&mut self);
+    let borrow = syn::parse_quote_spanned! { m.sig.ident.span() =>
+        let #repl = ::std::borrow::BorrowMut::borrow_mut(&mut self);
+    };
+    let block = Folder {
+        diag,
+        this: this.clone(),
+        repl,
+        fallible: ret.is_some(),
+    }
+    .fold_block(m.block);
+
+    let stmts = if let Some(ret) = ret {
+        let syn::ReturnType::Type(_, ret) = ret else {
+            unreachable!()
+        };
+
+        vec![
+            borrow,
+            syn::parse_quote_spanned! { block.span() =>
+                let __builder_res: #ret = #block;
             },
-            {
-                let block = Folder {
-                    diag,
-                    this: this.clone(),
-                    repl,
-                }
-                .fold_block(m.block);
-                syn::parse_quote_spanned! { block.span() =>
-                    #[allow(
-                        clippy::unnecessary_operation,
-                        reason = "Inside macro-generated code"
-                    )]
-                    { #block; };
-                }
+            syn::Stmt::Expr(
+                syn::parse_quote_spanned! { span =>
+                    ::std::result::Result::map(__builder_res, |()| #this)
+                },
+                None,
+            ),
+        ]
+    } else {
+        vec![
+            borrow,
+            syn::parse_quote_spanned! { block.span() =>
+                #[allow(
+                    clippy::unnecessary_operation,
+                    reason = "Inside macro-generated code"
+                )]
+                { #block; };
             },
             syn::Stmt::Expr(syn::Expr::Path(this), None),
-        ],
+        ]
+    };
+
+    m.block = syn::Block {
+        brace_token: block.brace_token,
+        stmts,
     };
 
     m
@@ -254,6 +588,7 @@ struct Folder<'a> {
     diag: &'a mut TokenStream,
     this: syn::ExprPath,
     repl: syn::ExprPath,
+    fallible: bool,
 }
 
 impl Fold for Folder<'_> {
@@ -268,6 +603,24 @@ impl Fold for Folder<'_> {
     }
 
     fn fold_expr_return(&mut self, mut e: syn::ExprReturn) -> syn::ExprReturn {
+        if self.fallible {
+            let Some(x) = e.expr else {
+                self.diag.extend(
+                    e.span()
+                        .error("Fallible builder method must return a Result")
+                        .into_compile_error(),
+                );
+                return e;
+            };
+
+            let x = self.fold_expr(*x);
+            let this = &self.this;
+            e.expr = Some(syn::parse_quote_spanned! { x.span() =>
+                ::std::result::Result::map(#x, |()| #this)
+            });
+            return e;
+        }
+
         if let Some(ref x) = e.expr {
             self.diag.extend(
                 x.span()
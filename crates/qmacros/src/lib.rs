@@ -41,6 +41,114 @@ pub fn borrow(input: TokenStream1) -> TokenStream1 {
 }
 
 /// Lift an impl block for a builder struct into a helper trait
+///
+/// Methods take `&mut self` and return either `()` or `Result<(), E>`; the
+/// latter produce setters returning `Result<Self, E>`.  Passing
+/// `typestate = Name` additionally generates a wrapper type `Name<S>` whose
+/// methods are restricted by `#[typestate(A | B => C)]` annotations, allowing
+/// invalid call sequences to be rejected at compile time.
+///
+/// ```
+/// use qcore::builder;
+///
+/// #[derive(Debug, Default)]
+/// pub struct Order {
+///     size: u8,
+///     toppings: Vec<String>,
+/// }
+///
+/// pub mod state {
+///     pub struct Empty;
+///     pub struct Chosen;
+/// }
+///
+/// #[builder(trait_name = OrderExt, typestate = TypedOrder)]
+/// impl Order {
+///     #[typestate(state::Empty => state::Chosen)]
+///     pub fn size(&mut self, size: u8) -> Result<(), &'static str> {
+///         if size == 0 {
+///             return Err("Size must be nonzero");
+///         }
+///         self.size = size;
+///         Ok(())
+///     }
+///
+///     #[typestate(state::Chosen => state::Chosen)]
+///     pub fn topping(&mut self, name: &str) { self.toppings.push(name.into()); }
+/// }
+///
+/// // Fallible setters return the builder on success
+/// let order = Order::default().size(12).unwrap().topping("basil");
+/// assert_eq!((order.size, order.toppings.len()), (12, 1));
+/// assert_eq!(Order::default().size(0).unwrap_err(), "Size must be nonzero");
+///
+/// let order = TypedOrder::<state::Empty>::new_unchecked(Order::default())
+///     .size(12)
+///     .unwrap()
+///     .topping("basil")
+///     .into_inner();
+/// assert_eq!(order.toppings, ["basil"]);
+/// ```
+///
+/// Calling a method in a state it does not accept is a compile error:
+///
+/// ```compile_fail
+/// # use qcore::builder;
+/// # #[derive(Debug, Default)]
+/// # pub struct Order {
+/// #     size: u8,
+/// #     toppings: Vec<String>,
+/// # }
+/// # pub mod state {
+/// #     pub struct Empty;
+/// #     pub struct Chosen;
+/// # }
+/// # #[builder(trait_name = OrderExt, typestate = TypedOrder)]
+/// # impl Order {
+/// #     #[typestate(state::Empty => state::Chosen)]
+/// #     pub fn size(&mut self, size: u8) -> Result<(), &'static str> {
+/// #         if size == 0 {
+/// #             return Err("Size must be nonzero");
+/// #         }
+/// #         self.size = size;
+/// #         Ok(())
+/// #     }
+/// #     #[typestate(state::Chosen => state::Chosen)]
+/// #     pub fn topping(&mut self, name: &str) { self.toppings.push(name.into()); }
+/// # }
+/// TypedOrder::<state::Empty>::new_unchecked(Order::default()).topping("basil");
+/// ```
+///
+/// As is skipping a required transition entirely:
+///
+/// ```compile_fail
+/// # use qcore::builder;
+/// # #[derive(Debug, Default)]
+/// # pub struct Order {
+/// #     size: u8,
+/// #     toppings: Vec<String>,
+/// # }
+/// # pub mod state {
+/// #     pub struct Empty;
+/// #     pub struct Chosen;
+/// # }
+/// # #[builder(trait_name = OrderExt, typestate = TypedOrder)]
+/// # impl Order {
+/// #     #[typestate(state::Empty => state::Chosen)]
+/// #     pub fn size(&mut self, size: u8) -> Result<(), &'static str> {
+/// #         if size == 0 {
+/// #             return Err("Size must be nonzero");
+/// #         }
+/// #         self.size = size;
+/// #         Ok(())
+/// #     }
+/// #     #[typestate(state::Chosen => state::Chosen)]
+/// #     pub fn topping(&mut self, name: &str) { self.toppings.push(name.into()); }
+/// # }
+/// fn bake(order: TypedOrder<state::Chosen>) -> Order { order.into_inner() }
+///
+/// bake(TypedOrder::<state::Empty>::new_unchecked(Order::default()));
+/// ```
 #[proc_macro_attribute]
 pub fn builder(arg_stream: TokenStream1, body: TokenStream1) -> TokenStream1 {
     let mut args = builder::Args::default();
@@ -0,0 +1,132 @@
+//! Benchmarks for regex-to-NFA construction and determinization
+//!
+//! See [`common`] for the caveats of the harness these run on.
+
+mod common;
+
+use common::{Bench, Rng};
+use shrec::re::{
+    arena::{RegexArena, RegexId},
+    Regex, RegexBag,
+};
+
+/// Generate a list of distinct pseudo-random lowercase words
+fn words(n: usize, seed: u64) -> Vec<String> {
+    let mut rng = Rng::new(seed);
+    let mut words = std::collections::BTreeSet::new();
+
+    while words.len() < n {
+        let len = 3 + rng.below(8);
+        words.insert(
+            (0..len)
+                .map(|_| char::from(b'a' + u8::try_from(rng.below(16)).unwrap()))
+                .collect::<String>(),
+        );
+    }
+
+    words.into_iter().collect()
+}
+
+/// A keyword-style lexer with one token per word
+fn keywords(n: usize) -> RegexBag<Vec<char>, usize> {
+    words(n, 0x5eed)
+        .into_iter()
+        .enumerate()
+        .map(|(i, w)| (Regex::Lit(w.chars().collect()), i))
+        .collect()
+}
+
+/// The language `(a|b)*a(a|b){n}`, whose minimal DFA has `2^(n + 1)` states
+fn blowup(n: usize) -> Regex<Vec<char>> {
+    let ab = || Regex::Alt(vec![Regex::Lit(vec!['a']), Regex::Lit(vec!['b'])]);
+
+    Regex::Cat(
+        [Regex::Star(ab().into()), Regex::Lit(vec!['a'])]
+            .into_iter()
+            .chain((0..n).map(|_| ab()))
+            .collect(),
+    )
+}
+
+/// The same lexer as [`keywords`], stored in an arena
+fn keywords_arena(n: usize) -> (RegexArena<Vec<char>>, Vec<(RegexId, usize)>) {
+    let mut arena = RegexArena::new();
+    let roots = words(n, 0x5eed)
+        .into_iter()
+        .enumerate()
+        .map(|(i, w)| (arena.lit(w.chars().collect()), i))
+        .collect();
+
+    (arena, roots)
+}
+
+/// The same language as [`blowup`], stored in an arena
+fn blowup_arena(arena: &mut RegexArena<Vec<char>>, n: usize) -> RegexId {
+    let a = arena.lit(vec!['a']);
+    let b = arena.lit(vec!['b']);
+    let ab = arena.alt([a, b]);
+    let star = arena.star(ab);
+
+    arena.cat([star, a].into_iter().chain((0..n).map(|_| ab)))
+}
+
+fn main() {
+    let bench = Bench::new("automata");
+
+    for n in [100, 1000] {
+        let re = keywords(n);
+        bench.run(
+            &format!("nfa_build/keywords_{n}"),
+            || re.clone(),
+            RegexBag::compile,
+        );
+    }
+
+    let (arena, roots) = keywords_arena(1000);
+    bench.run(
+        "nfa_build/arena_keywords_1000",
+        || roots.clone(),
+        |r| arena.compile::<char, _>(r),
+    );
+
+    let re = blowup(12);
+    bench.run("nfa_build/blowup_12", || re.clone(), Regex::compile);
+
+    let mut arena = RegexArena::new();
+    let root = blowup_arena(&mut arena, 12);
+    bench.run(
+        "nfa_build/arena_blowup_12",
+        || (),
+        |()| arena.compile::<char, _>([(root, ())]),
+    );
+
+    bench.run("construct/tree_blowup_1000", || (), |()| blowup(1000));
+    bench.run("construct/arena_blowup_1000", RegexArena::new, |mut a| {
+        blowup_arena(&mut a, 1000)
+    });
+
+    let nfa = keywords(1000).compile();
+    bench.run(
+        "simplify/keywords_1000",
+        || nfa.clone(),
+        |mut n| n.simplify(),
+    );
+
+    let mut nfa = keywords(1000).compile();
+    nfa.simplify();
+    bench.run(
+        "determinize/keywords_1000",
+        || &nfa,
+        |n| n.compile().states().len(),
+    );
+
+    for n in [6, 10] {
+        let mut nfa = blowup(n).compile();
+        nfa.simplify();
+        bench.run(
+            &format!("determinize/blowup_{n}"),
+            || &nfa,
+            |n| n.compile().states().len(),
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Minimal benchmark harness shared by the shrec benchmarks
+//!
+//! Run with `cargo bench -p shrec [filter]`.
+//!
+//! This is a smoke harness, not a statistical benchmarking tool: it reports
+//! the median, minimum and maximum of its samples and does no outlier
+//! detection or comparison against previous runs.  Use it to spot gross
+//! regressions on a single machine, and compare numbers only between runs on
+//! that machine.
+//!
+//! Each benchmark is sampled for a fixed time budget, timing every call of the
+//! routine separately so input construction is never measured.  When a bench
+//! binary is run without `--bench` (e.g. by `cargo test --benches`) every
+//! routine is run exactly once as a smoke test.
+//!
+//! Heap allocations are counted by a wrapper around the system allocator, and
+//! the number made by one call of each routine is reported alongside its
+//! timings.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    env,
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+const WARMUP: Duration = Duration::from_millis(500);
+const MEASURE: Duration = Duration::from_secs(3);
+const MIN_SAMPLES: usize = 10;
+const MAX_SAMPLES: usize = 10_000;
+
+/// The system allocator, counting every allocation and reallocation made
+/// through it
+#[derive(Debug)]
+pub struct CountingAlloc;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) { System.dealloc(ptr, layout) }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Count the allocations made by a closure
+fn count_allocs<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let start = ALLOCS.load(Ordering::Relaxed);
+    let ret = f();
+    (ret, ALLOCS.load(Ordering::Relaxed) - start)
+}
+
+#[derive(Debug)]
+pub struct Bench {
+    group: &'static str,
+    filter: Option<String>,
+    measure: bool,
+}
+
+impl Bench {
+    /// Construct a new benchmark group, reading the name filter and mode from
+    /// the command line
+    pub fn new(group: &'static str) -> Self {
+        let mut filter = None;
+        let mut measure = false;
+
+        for arg in env::args().skip(1) {
+            match &*arg {
+                "--bench" => measure = true,
+                a if a.starts_with('-') => (),
+                a => filter = Some(a.to_owned()),
+            }
+        }
+
+        Self {
+            group,
+            filter,
+            measure,
+        }
+    }
+
+    /// Benchmark `routine`, calling `setup` to produce a fresh input for each
+    /// call
+    pub fn run<I, O>(
+        &self,
+        name: &str,
+        mut setup: impl FnMut() -> I,
+        mut routine: impl FnMut(I) -> O,
+    ) {
+        let name = format!("{}/{name}", self.group);
+        if self.filter.as_ref().is_some_and(|f| !name.contains(&**f)) {
+            return;
+        }
+
+        if !self.measure {
+            let input = setup();
+            let (out, allocs) = count_allocs(|| routine(input));
+            black_box(out);
+            println!("{name}: ok ({allocs} allocs)");
+            return;
+        }
+
+        let input = setup();
+        let (out, allocs) = count_allocs(|| routine(black_box(input)));
+        black_box(out);
+
+        let mut sample = || {
+            let input = setup();
+            let start = Instant::now();
+            black_box(routine(black_box(input)));
+            start.elapsed()
+        };
+
+        let start = Instant::now();
+        while start.elapsed() < WARMUP {
+            sample();
+        }
+
+        let mut samples = vec![];
+        let start = Instant::now();
+        while samples.len() < MIN_SAMPLES
+            || (samples.len() < MAX_SAMPLES && start.elapsed() < MEASURE)
+        {
+            samples.push(sample());
+        }
+
+        samples.sort_unstable();
+        println!(
+            "{name:<48} median {:>10.3?}  min {:>10.3?}  max {:>10.3?}  allocs {allocs:>8}  ({} \
+             samples)",
+            samples[samples.len() / 2],
+            samples[0],
+            samples[samples.len() - 1],
+            samples.len(),
+        );
+    }
+}
+
+/// Deterministic xorshift generator for building benchmark inputs
+#[derive(Debug, Clone, Copy)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self { Self(seed | 1) }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Generate a value in `0..n`
+    pub fn below(&mut self, n: u64) -> u64 { self.next() % n }
+}
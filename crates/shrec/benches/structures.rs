@@ -0,0 +1,108 @@
+//! Benchmarks for the union-find and partition map data structures
+//!
+//! See [`common`] for the caveats of the harness these run on.  The `_x4`
+//! runs share the atomic union-find between threads, so on a single core
+//! they only measure the overhead of sharing it.
+
+mod common;
+
+use common::{Bench, Rng};
+use shrec::{
+    partition_map::PartitionMap,
+    union_find::{AtomicUnionFind, UnionFind},
+};
+
+/// Number of threads sharing the unions in the parallel union-find benchmark
+const THREADS: usize = 4;
+
+/// Random unions over `n` nodes, as produced by a merge-heavy rewrite pass
+fn merges(n: usize, seed: u64) -> Vec<(usize, usize)> {
+    let mut rng = Rng::new(seed);
+    let n64 = n as u64;
+
+    (0..n)
+        .map(|_| {
+            let a = rng.below(n64);
+            let b = rng.below(n64);
+            (usize::try_from(a).unwrap(), usize::try_from(b).unwrap())
+        })
+        .collect()
+}
+
+/// Overlapping ranges of bytes with a small set of values, as produced when
+/// partitioning the alphabet of a large character-class-heavy automaton
+fn ranges(n: usize, seed: u64) -> Vec<(std::ops::Range<u32>, u8)> {
+    let mut rng = Rng::new(seed);
+
+    (0..n)
+        .map(|_| {
+            let start = u32::try_from(rng.below(0x1_0000)).unwrap();
+            let len = u32::try_from(1 + rng.below(0x400)).unwrap();
+            let val = u8::try_from(rng.below(8)).unwrap();
+            (start..start + len, val)
+        })
+        .collect()
+}
+
+fn main() {
+    let bench = Bench::new("structures");
+
+    for n in [10_000, 100_000] {
+        let merges = merges(n, 0x5eed);
+        bench.run(
+            &format!("union_find/merge_{n}"),
+            || {
+                let mut uf = UnionFind::default();
+                for _ in 0..n {
+                    uf.put();
+                }
+                uf
+            },
+            |mut uf| {
+                for &(a, b) in &merges {
+                    uf.union(a, b).unwrap();
+                }
+
+                (0..n).filter(|&i| uf.find(i).unwrap() == i).count()
+            },
+        );
+        bench.run(
+            &format!("union_find/atomic_merge_{n}"),
+            || AtomicUnionFind::new(n),
+            |uf| {
+                for &(a, b) in &merges {
+                    uf.union(a, b).unwrap();
+                }
+
+                (0..n).filter(|&i| uf.find(i).unwrap() == i).count()
+            },
+        );
+        bench.run(
+            &format!("union_find/atomic_merge_{n}_x{THREADS}"),
+            || AtomicUnionFind::new(n),
+            |uf| {
+                std::thread::scope(|s| {
+                    for chunk in merges.chunks(merges.len().div_ceil(THREADS)) {
+                        let uf = &uf;
+                        s.spawn(move || {
+                            for &(a, b) in chunk {
+                                uf.union(a, b).unwrap();
+                            }
+                        });
+                    }
+                });
+
+                (0..n).filter(|&i| uf.find(i).unwrap() == i).count()
+            },
+        );
+    }
+
+    for n in [1000, 10_000] {
+        let ranges = ranges(n, 0x5eed);
+        bench.run(
+            &format!("partition_map/extend_{n}"),
+            || ranges.clone(),
+            |r| r.into_iter().collect::<PartitionMap<u32, u8>>(),
+        );
+    }
+}
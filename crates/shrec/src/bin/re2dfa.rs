@@ -52,7 +52,8 @@ fn main() {
     //     // ]),
     // ]);
     // let dfa = token_dfa();
-    let non_dfa = re.compile();
+    let mut non_dfa = re.compile();
+    non_dfa.simplify();
     let dfa = non_dfa.compile().copied();
     let (dfa, states) = dfa.atomize_nodes::<u64>();
 
@@ -1,18 +1,36 @@
-use std::{borrow::Cow, collections::BTreeMap, hash::Hash};
+use std::{
+    borrow::{Borrow, Cow},
+    collections::{btree_map, BTreeMap},
+    hash::Hash,
+};
 
 use hashbrown::HashMap;
-pub use scanner::Scanner;
+pub use scanner::{Scanner, TrapError};
+pub use table::{Table, TableError, DEAD, NO_TOKEN};
 
 use self::atomize::DfaAtomizer;
 use crate::{dot, free::Succ};
 
 mod atomize;
+mod ops;
 mod scanner;
+mod table;
 
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct Node<I, N, E>(BTreeMap<I, (N, E)>);
 
+impl<I: Ord, N, E> Node<I, N, E> {
+    #[inline]
+    pub fn edges(&self) -> btree_map::Iter<I, (N, E)> { self.0.iter() }
+
+    #[inline]
+    pub fn get<Q: Ord + ?Sized>(&self, inp: &Q) -> Option<&(N, E)>
+    where I: Borrow<Q> {
+        self.0.get(inp)
+    }
+}
+
 #[derive(Debug)]
 pub struct Dfa<I, N, E, T> {
     states: BTreeMap<N, Node<I, N, E>>,
@@ -35,6 +53,21 @@ impl<I, N: Ord, E, T> Dfa<I, N, E, T> {
         }
     }
 
+    #[inline]
+    pub fn start(&self) -> &N { &self.start }
+
+    #[inline]
+    pub fn accept(&self) -> &BTreeMap<N, T> { &self.accept }
+
+    #[inline]
+    pub fn states(&self) -> btree_map::Iter<N, Node<I, N, E>> { self.states.iter() }
+
+    #[inline]
+    pub fn get<Q: Ord + ?Sized>(&self, node: &Q) -> Option<&Node<I, N, E>>
+    where N: Borrow<Q> {
+        self.states.get(node)
+    }
+
     pub fn map_token<U>(self, f: impl Fn(T) -> U) -> Dfa<I, N, E, U> {
         let Self {
             states,
@@ -0,0 +1,293 @@
+//! Language operations on DFAs
+//!
+//! Compiled DFAs are partial: any input without an outgoing edge is
+//! implicitly rejected.  Operations that depend on what a DFA rejects must
+//! make this explicit by routing every missing transition to a dead state,
+//! which requires knowing the full input alphabet.  For automata over
+//! [`ClassId`](crate::nfa::ClassId)s this is every class of the corresponding
+//! [`Alphabet`](crate::nfa::Alphabet).
+
+use std::collections::{btree_map, BTreeMap, BTreeSet, VecDeque};
+
+use super::{Dfa, Node};
+
+impl<I: Clone + Ord, N: Ord, E: Clone + Default, T> Dfa<I, N, E, T> {
+    /// Add an explicit dead state, represented by `None`, and route every
+    /// transition missing from this DFA to it
+    ///
+    /// The resulting DFA has an edge for every symbol of `alphabet` out of
+    /// every state.  New edges carry the default output.
+    ///
+    /// # Panics
+    /// This method panics if any existing edge is labeled with a symbol not
+    /// contained in `alphabet`.
+    #[must_use]
+    pub fn complete(self, alphabet: impl IntoIterator<Item = I>) -> Dfa<I, Option<N>, E, T> {
+        let alphabet: BTreeSet<I> = alphabet.into_iter().collect();
+        let Self {
+            states,
+            start,
+            accept,
+        } = self;
+
+        let mut states: BTreeMap<_, _> = states
+            .into_iter()
+            .map(|(state, Node(edges))| {
+                assert!(
+                    edges.keys().all(|i| alphabet.contains(i)),
+                    "DFA edge label missing from alphabet"
+                );
+
+                let mut edges: BTreeMap<_, _> = edges
+                    .into_iter()
+                    .map(|(i, (next, out))| (i, (Some(next), out)))
+                    .collect();
+                for i in &alphabet {
+                    edges
+                        .entry(i.clone())
+                        .or_insert_with(|| (None, E::default()));
+                }
+
+                (Some(state), Node(edges))
+            })
+            .collect();
+
+        states.insert(
+            None,
+            Node(
+                alphabet
+                    .into_iter()
+                    .map(|i| (i, (None, E::default())))
+                    .collect(),
+            ),
+        );
+
+        Dfa {
+            states,
+            start: Some(start),
+            accept: accept.into_iter().map(|(n, t)| (Some(n), t)).collect(),
+        }
+    }
+
+    /// Construct a DFA accepting exactly the strings over `alphabet` rejected
+    /// by this one
+    ///
+    /// The states of the complement are those of [`complete`](Self::complete),
+    /// including the dead state, which is accepting in the complement.
+    ///
+    /// # Panics
+    /// This method panics under the same conditions as
+    /// [`complete`](Self::complete).
+    #[must_use]
+    pub fn complement(self, alphabet: impl IntoIterator<Item = I>) -> Dfa<I, Option<N>, E, ()>
+    where N: Clone {
+        let Dfa {
+            states,
+            start,
+            accept,
+        } = self.complete(alphabet);
+        let accept = states
+            .keys()
+            .filter(|s| !accept.contains_key(*s))
+            .map(|s| (s.clone(), ()))
+            .collect();
+
+        Dfa {
+            states,
+            start,
+            accept,
+        }
+    }
+}
+
+impl<I: Clone + Ord, N: Clone + Ord, E: Clone, T: Clone> Dfa<I, N, E, T> {
+    /// Construct a DFA accepting the strings accepted by this DFA but not by
+    /// `other`
+    ///
+    /// Both DFAs must be defined over the same alphabet; for automata over
+    /// input classes this means both must have been compressed together, e.g.
+    /// with [`Nfa::compress_alphabets`](crate::nfa::Nfa::compress_alphabets).
+    /// Transitions missing from `other` are treated as leading to a dead
+    /// state, represented by `None`, so neither DFA needs to be completed
+    /// first.  Only states reachable from the start state are produced, and
+    /// edges and accepting states keep the outputs and tokens of this DFA.
+    #[must_use]
+    pub fn difference<M: Clone + Ord, F, U>(
+        &self,
+        other: &Dfa<I, M, F, U>,
+    ) -> Dfa<I, (N, Option<M>), E, T> {
+        let start = (self.start.clone(), Some(other.start.clone()));
+        let mut states = BTreeMap::new();
+        let mut accept = BTreeMap::new();
+        let mut queue = VecDeque::from([start.clone()]);
+
+        while let Some(state) = queue.pop_front() {
+            if states.contains_key(&state) {
+                continue;
+            }
+
+            let (ref lhs, ref rhs) = state;
+            let rhs_accepts = rhs.as_ref().is_some_and(|r| other.accept.contains_key(r));
+            if let Some(tok) = self.accept.get(lhs).filter(|_| !rhs_accepts) {
+                accept.insert(state.clone(), tok.clone());
+            }
+
+            let edges: BTreeMap<_, _> = self.states[lhs]
+                .edges()
+                .map(|(i, (lhs_next, out))| {
+                    let rhs_next = rhs
+                        .as_ref()
+                        .and_then(|r| other.states[r].get(i))
+                        .map(|(n, _)| n.clone());
+                    let next = (lhs_next.clone(), rhs_next);
+                    if !states.contains_key(&next) {
+                        queue.push_back(next.clone());
+                    }
+
+                    (i.clone(), (next, out.clone()))
+                })
+                .collect();
+
+            states.insert(state, Node(edges));
+        }
+
+        Dfa {
+            states,
+            start,
+            accept,
+        }
+    }
+}
+
+impl<I: Ord, N: Ord, E, T> Dfa<I, N, E, T> {
+    /// Find a shortest input accepted by this DFA, along with the token it
+    /// produces, or `None` if the DFA accepts nothing
+    ///
+    /// Ties are broken by input order, so the result is the least shortest
+    /// input.  Applied to a [`difference`](Self::difference), this produces a
+    /// witness string matched by one automaton but not the other.
+    #[must_use]
+    pub fn shortest_accepted(&self) -> Option<(Vec<&I>, &T)> {
+        let mut prev: BTreeMap<&N, Option<(&N, &I)>> = BTreeMap::new();
+        let mut queue = VecDeque::from([&self.start]);
+        prev.insert(&self.start, None);
+
+        while let Some(state) = queue.pop_front() {
+            if let Some(tok) = self.accept.get(state) {
+                let mut path = vec![];
+                let mut curr = state;
+                while let Some((p, i)) = prev[curr] {
+                    path.push(i);
+                    curr = p;
+                }
+                path.reverse();
+
+                return Some((path, tok));
+            }
+
+            for (i, (next, _)) in self.states[state].edges() {
+                if let btree_map::Entry::Vacant(v) = prev.entry(next) {
+                    v.insert(Some((state, i)));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::iter;
+
+    use crate::{dfa::Dfa, nfa::Nfa, range_set::RangeSet, re::Regex};
+
+    fn compile(re: Regex<std::str::Chars<'_>>) -> Dfa<char, u64, (), ()> {
+        let mut nfa = re.compile();
+        nfa.simplify();
+        let (dfa, _) = nfa.compile().copied().atomize_nodes();
+        dfa.map_token(|_| ())
+    }
+
+    fn accepts<I: Ord, N: Ord, E, T>(
+        dfa: &Dfa<I, N, E, T>,
+        input: impl IntoIterator<Item = I>,
+    ) -> bool {
+        let mut state = dfa.start();
+        for i in input {
+            match dfa.get(state).and_then(|n| n.get(&i)) {
+                Some((next, _)) => state = next,
+                None => return false,
+            }
+        }
+
+        dfa.accept().contains_key(state)
+    }
+
+    fn witness<I: Clone + Ord, N: Ord, E, T>(dfa: &Dfa<I, N, E, T>) -> Option<Vec<I>> {
+        dfa.shortest_accepted()
+            .map(|(p, _)| p.into_iter().cloned().collect())
+    }
+
+    #[test]
+    fn complement() {
+        let dfa = compile(Regex::Star(Regex::Lit("ab".chars()).into()));
+        let comp = dfa.complement(['a', 'b']);
+
+        for s in ["", "ab", "abab"] {
+            assert!(!accepts(&comp, s.chars()), "{s:?}");
+        }
+        for s in ["a", "b", "aab", "abb", "aba"] {
+            assert!(accepts(&comp, s.chars()), "{s:?}");
+        }
+
+        assert_eq!(witness(&comp), Some(vec!['a']));
+        assert!(comp.states().all(|(_, n)| n.edges().count() == 2));
+    }
+
+    #[test]
+    fn difference() {
+        let lhs = compile(Regex::Cat(vec![
+            Regex::Lit("a".chars()),
+            Regex::Star(Regex::Alt(vec![Regex::Lit("b".chars()), Regex::Lit("c".chars())]).into()),
+        ]));
+        let rhs = compile(Regex::Cat(vec![
+            Regex::Lit("a".chars()),
+            Regex::Star(Regex::Lit("b".chars()).into()),
+        ]));
+
+        let diff = lhs.difference(&rhs);
+        assert!(!accepts(&diff, "abb".chars()));
+        assert!(accepts(&diff, "abcb".chars()));
+        assert_eq!(witness(&diff), Some(vec!['a', 'c']));
+
+        assert_eq!(witness(&rhs.difference(&lhs)), None);
+        assert_eq!(witness(&lhs.difference(&lhs)), None);
+    }
+
+    #[test]
+    fn difference_over_classes() {
+        let class =
+            |r: std::ops::Range<char>| Regex::Lit(vec![iter::once(r).collect::<RangeSet<_>>()]);
+        let plus = |r: std::ops::Range<char>| {
+            Regex::Cat(vec![class(r.clone()), Regex::Star(class(r).into())])
+        };
+
+        let (nfas, alphabet) =
+            Nfa::compress_alphabets([plus('a'..'{').compile(), plus('a'..'g').compile()]);
+        let [lhs, rhs] =
+            [&nfas[0], &nfas[1]].map(|n| n.compile().copied().atomize_nodes::<u64>().0);
+
+        let diff = lhs.difference(&rhs);
+        let (path, _) = diff.shortest_accepted().unwrap();
+        let path: String = path
+            .into_iter()
+            .map(|&c| *alphabet.example(c).unwrap())
+            .collect();
+        assert_eq!(path, "g");
+
+        let comp = rhs.complement(alphabet.classes());
+        assert_eq!(comp.shortest_accepted().map(|(p, ())| p.len()), Some(0));
+    }
+}
@@ -1,6 +1,6 @@
 use super::Dfa;
 
-#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
 #[error("Scanner entered trap state with no accepting prefix")]
 pub struct TrapError;
 
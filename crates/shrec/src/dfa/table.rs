@@ -0,0 +1,458 @@
+//! Dense transition tables for embedding compiled DFAs
+//!
+//! A [`Table`] lowers a DFA to a flat array of `u32` transitions, one row per
+//! state and one column per input symbol, alongside the token accepted by
+//! each state.  Tables can be emitted as Rust source, e.g. from a build
+//! script, or as a binary blob loaded at runtime, so an application can run a
+//! compiled lexer without constructing its automaton on startup.
+//!
+//! Columns must be small, dense integers, so tables are best suited to
+//! automata over bytes or over the [`ClassId`](crate::nfa::ClassId)s of a
+//! compressed [`Alphabet`](crate::nfa::Alphabet).
+
+use std::{borrow::Cow, collections::BTreeMap, fmt::Write};
+
+use super::Dfa;
+
+/// Marker for a missing transition in [`Table::transitions`]
+pub const DEAD: u32 = u32::MAX;
+/// Marker for a non-accepting state in [`Table::accept`]
+pub const NO_TOKEN: u32 = u32::MAX;
+
+const MAGIC: [u8; 4] = *b"SHRT";
+const VERSION: u32 = 1;
+/// Size of the binary header: magic, version, columns, states and start
+const HEADER_LEN: usize = 20;
+
+/// An error arising from building or loading a [`Table`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum TableError {
+    /// The DFA has too many states or columns to index with a `u32`
+    #[error("DFA is too large to encode as a table")]
+    TooLarge,
+    /// An input symbol was mapped to a column outside the table
+    #[error("Input symbol mapped to column {0}, outside the table")]
+    Column(u32),
+    /// A token was mapped to the reserved [`NO_TOKEN`] value
+    #[error("Token mapped to the reserved non-accepting value")]
+    ReservedToken,
+    /// A binary table did not start with the expected magic number
+    #[error("Data is not a DFA table")]
+    BadMagic,
+    /// A binary table was written by an incompatible version of this crate
+    #[error("Unsupported DFA table version {0}")]
+    Version(u32),
+    /// A binary table ended early or had trailing data
+    #[error("DFA table has the wrong length")]
+    Length,
+    /// A table had a start state or transition referring to no state
+    #[error("DFA table refers to a nonexistent state")]
+    Corrupt,
+}
+
+/// A DFA lowered to a dense transition table
+///
+/// States are numbered from zero in the order of the source DFA's state IDs.
+/// The transition from state `s` on column `c` is stored at index
+/// `s * columns + c` of [`transitions`](Self::transitions), or [`DEAD`] if
+/// there is none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table<'a> {
+    columns: u32,
+    start: u32,
+    transitions: Cow<'a, [u32]>,
+    accept: Cow<'a, [u32]>,
+}
+
+impl<I: Ord, N: Ord, T> Dfa<I, N, (), T> {
+    /// Lower this DFA to a dense table with the given number of columns
+    ///
+    /// `column` maps each input symbol to its column and `token` maps each
+    /// accepted token to the value stored for it, which must not be
+    /// [`NO_TOKEN`].
+    ///
+    /// # Errors
+    /// This method returns an error if a symbol maps to a column out of range,
+    /// a token maps to [`NO_TOKEN`], or the table would not fit in memory
+    /// indexed by `u32`.
+    pub fn to_table(
+        &self,
+        columns: u32,
+        column: impl Fn(&I) -> u32,
+        token: impl Fn(&T) -> u32,
+    ) -> Result<Table<'static>, TableError> {
+        let ids: BTreeMap<&N, u32> = self
+            .states
+            .keys()
+            .enumerate()
+            .map(|(i, n)| u32::try_from(i).map(|i| (n, i)))
+            .collect::<Result<_, _>>()
+            .map_err(|_| TableError::TooLarge)?;
+        let len = usize::try_from(columns)
+            .ok()
+            .and_then(|c| c.checked_mul(ids.len()))
+            .filter(|&l| u32::try_from(l).is_ok())
+            .ok_or(TableError::TooLarge)?;
+
+        let mut transitions = vec![DEAD; len];
+        let mut accept = vec![NO_TOKEN; ids.len()];
+        for (row, (state, node)) in self.states.iter().enumerate() {
+            for (input, (next, ())) in node.edges() {
+                let col = column(input);
+                if col >= columns {
+                    return Err(TableError::Column(col));
+                }
+
+                transitions[row * columns as usize + col as usize] = ids[next];
+            }
+
+            if let Some(tok) = self.accept.get(state) {
+                accept[row] = match token(tok) {
+                    NO_TOKEN => return Err(TableError::ReservedToken),
+                    t => t,
+                };
+            }
+        }
+
+        Ok(Table {
+            columns,
+            start: ids[&self.start],
+            transitions: transitions.into(),
+            accept: accept.into(),
+        })
+    }
+}
+
+impl<'a> Table<'a> {
+    /// Construct a table from static data, such as that emitted by
+    /// [`to_rust`](Self::to_rust)
+    ///
+    /// # Panics
+    /// This function panics, at compile time if evaluated in a constant, if
+    /// the data is not a valid table.
+    #[must_use]
+    pub const fn from_static(
+        columns: u32,
+        start: u32,
+        transitions: &'a [u32],
+        accept: &'a [u32],
+    ) -> Self {
+        assert!(
+            Self::is_valid(columns, start, transitions, accept),
+            "Invalid DFA table"
+        );
+
+        Self {
+            columns,
+            start,
+            transitions: Cow::Borrowed(transitions),
+            accept: Cow::Borrowed(accept),
+        }
+    }
+
+    const fn is_valid(columns: u32, start: u32, transitions: &[u32], accept: &[u32]) -> bool {
+        let states = accept.len();
+        if start as usize >= states {
+            return false;
+        }
+        match states.checked_mul(columns as usize) {
+            Some(len) if len == transitions.len() => (),
+            _ => return false,
+        }
+
+        let mut i = 0;
+        while i < transitions.len() {
+            if transitions[i] != DEAD && transitions[i] as usize >= states {
+                return false;
+            }
+            i += 1;
+        }
+
+        true
+    }
+
+    /// The number of input columns in each row
+    #[inline]
+    #[must_use]
+    pub fn columns(&self) -> u32 { self.columns }
+
+    /// The number of states in the table
+    #[inline]
+    #[must_use]
+    pub fn state_count(&self) -> usize { self.accept.len() }
+
+    /// The start state
+    #[inline]
+    #[must_use]
+    pub fn start(&self) -> u32 { self.start }
+
+    /// The raw transition table, in row-major order
+    #[inline]
+    #[must_use]
+    pub fn transitions(&self) -> &[u32] { &self.transitions }
+
+    /// The token accepted by each state, or [`NO_TOKEN`]
+    #[inline]
+    #[must_use]
+    pub fn accept(&self) -> &[u32] { &self.accept }
+
+    /// Follow the transition from `state` on `column`, returning `None` if
+    /// there is none or either argument is out of range
+    #[inline]
+    #[must_use]
+    pub fn next(&self, state: u32, column: u32) -> Option<u32> {
+        if column >= self.columns {
+            return None;
+        }
+
+        (state as usize)
+            .checked_mul(self.columns as usize)
+            .and_then(|r| self.transitions.get(r + column as usize))
+            .copied()
+            .filter(|&n| n != DEAD)
+    }
+
+    /// Get the token accepted by `state`, if any
+    #[inline]
+    #[must_use]
+    pub fn token(&self, state: u32) -> Option<u32> {
+        self.accept
+            .get(state as usize)
+            .copied()
+            .filter(|&t| t != NO_TOKEN)
+    }
+
+    /// Run the table over some input, returning the token accepted at its end
+    #[must_use]
+    pub fn matches(&self, input: impl IntoIterator<Item = u32>) -> Option<u32> {
+        input
+            .into_iter()
+            .try_fold(self.start, |s, c| self.next(s, c))
+            .and_then(|s| self.token(s))
+    }
+
+    /// Find the longest prefix of some input accepted by the table, returning
+    /// its token and length
+    ///
+    /// This is a single step of a maximal-munch scanner.
+    #[must_use]
+    pub fn longest_prefix(&self, input: impl IntoIterator<Item = u32>) -> Option<(u32, usize)> {
+        let mut state = self.start;
+        let mut best = self.token(state).map(|t| (t, 0));
+
+        for (i, col) in input.into_iter().enumerate() {
+            let Some(next) = self.next(state, col) else {
+                break;
+            };
+
+            state = next;
+            if let Some(tok) = self.token(state) {
+                best = Some((tok, i + 1));
+            }
+        }
+
+        best
+    }
+
+    /// Copy any borrowed data, producing a table with no lifetime
+    #[must_use]
+    pub fn into_owned(self) -> Table<'static> {
+        Table {
+            columns: self.columns,
+            start: self.start,
+            transitions: Cow::Owned(self.transitions.into_owned()),
+            accept: Cow::Owned(self.accept.into_owned()),
+        }
+    }
+
+    /// Encode this table as a binary blob, readable with
+    /// [`from_bytes`](Table::from_bytes)
+    ///
+    /// The format is the magic number `SHRT`, followed by the format version,
+    /// column count, state count and start state, then the transitions and
+    /// accepted tokens, all as little-endian `u32`s.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let states = u32::try_from(self.accept.len()).unwrap_or_else(|_| unreachable!());
+        let mut out =
+            Vec::with_capacity(HEADER_LEN + 4 * (self.transitions.len() + self.accept.len()));
+
+        out.extend_from_slice(&MAGIC);
+        for word in [VERSION, self.columns, states, self.start]
+            .into_iter()
+            .chain(self.transitions.iter().copied())
+            .chain(self.accept.iter().copied())
+        {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+
+        out
+    }
+
+    /// Emit this table as Rust source defining a static named `name`
+    ///
+    /// The emitted item refers to this crate as `::shrec` and can be included
+    /// into a crate depending on it, e.g. with [`include!`].
+    #[must_use]
+    pub fn to_rust(&self, name: &str) -> String {
+        fn array(out: &mut String, words: &[u32]) {
+            out.push_str("&[");
+            for (i, &w) in words.iter().enumerate() {
+                out.push_str(if i % 8 == 0 { "\n        " } else { " " });
+                if w == u32::MAX {
+                    out.push_str("u32::MAX,");
+                } else {
+                    write!(out, "{w},").unwrap_or_else(|_| unreachable!());
+                }
+            }
+            out.push_str("\n    ]");
+        }
+
+        let mut out = format!(
+            concat!(
+                "pub static {}: ::shrec::dfa::Table<'static> = ",
+                "::shrec::dfa::Table::from_static(\n    {},\n    {},\n    ",
+            ),
+            name, self.columns, self.start
+        );
+        array(&mut out, &self.transitions);
+        out.push_str(",\n    ");
+        array(&mut out, &self.accept);
+        out.push_str(",\n);\n");
+        out
+    }
+}
+
+impl Table<'static> {
+    /// Decode a table from a binary blob written by
+    /// [`to_bytes`](Table::to_bytes)
+    ///
+    /// # Errors
+    /// This function returns an error if the data is not a complete table
+    /// written by a compatible version of this crate.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TableError> {
+        let body = bytes.strip_prefix(&MAGIC).ok_or(TableError::BadMagic)?;
+        if body.len() % 4 != 0 {
+            return Err(TableError::Length);
+        }
+
+        let mut words = body
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]));
+        let mut header = || words.next().ok_or(TableError::Length);
+
+        let version = header()?;
+        if version != VERSION {
+            return Err(TableError::Version(version));
+        }
+
+        let columns = header()?;
+        let states = header()? as usize;
+        let start = header()?;
+        let len = states
+            .checked_mul(columns as usize)
+            .ok_or(TableError::TooLarge)?;
+        if words.len() != len + states {
+            return Err(TableError::Length);
+        }
+
+        let transitions: Vec<_> = words.by_ref().take(len).collect();
+        let accept: Vec<_> = words.collect();
+        if !Self::is_valid(columns, start, &transitions, &accept) {
+            return Err(TableError::Corrupt);
+        }
+
+        Ok(Self {
+            columns,
+            start,
+            transitions: transitions.into(),
+            accept: accept.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Table, TableError, DEAD, NO_TOKEN};
+    use crate::re::{Regex, RegexBag};
+
+    fn table() -> Table<'static> {
+        let re = RegexBag::from(vec![
+            (Regex::Lit("for".bytes()), 1),
+            (Regex::Lit("foreach".bytes()), 2),
+            (Regex::Star(Regex::Lit("ab".bytes()).into()), 3),
+        ]);
+        let mut nfa = re.compile();
+        nfa.simplify();
+        let (dfa, _) = nfa.compile().copied().atomize_nodes::<u64>();
+
+        dfa.to_table(256, |&b| b.into(), |t| **t.first().unwrap())
+            .unwrap()
+    }
+
+    fn cols(s: &str) -> impl Iterator<Item = u32> + '_ { s.bytes().map(u32::from) }
+
+    #[test]
+    fn lookup() {
+        let table = table();
+        assert_eq!(table.columns(), 256);
+        assert_eq!(table.transitions().len(), table.state_count() * 256);
+
+        assert_eq!(table.matches(cols("for")), Some(1));
+        assert_eq!(table.matches(cols("foreach")), Some(2));
+        assert_eq!(table.matches(cols("abab")), Some(3));
+        assert_eq!(table.matches(cols("")), Some(3));
+        assert_eq!(table.matches(cols("fore")), None);
+        assert_eq!(table.matches(cols("x")), None);
+        assert_eq!(table.matches([1000]), None);
+
+        assert_eq!(table.longest_prefix(cols("foreacx")), Some((1, 3)));
+        assert_eq!(table.longest_prefix(cols("ababa")), Some((3, 4)));
+        assert_eq!(table.longest_prefix(cols("xyz")), Some((3, 0)));
+    }
+
+    #[test]
+    fn binary() {
+        let table = table();
+        let bytes = table.to_bytes();
+        assert_eq!(Table::from_bytes(&bytes), Ok(table));
+
+        assert_eq!(Table::from_bytes(b"nope"), Err(TableError::BadMagic));
+        assert_eq!(
+            Table::from_bytes(&bytes[..bytes.len() - 4]),
+            Err(TableError::Length)
+        );
+
+        let mut bad = bytes.clone();
+        bad[4] = 2;
+        assert_eq!(Table::from_bytes(&bad), Err(TableError::Version(2)));
+
+        let mut bad = bytes;
+        bad[20..24].copy_from_slice(&9999_u32.to_le_bytes());
+        assert_eq!(Table::from_bytes(&bad), Err(TableError::Corrupt));
+    }
+
+    static EMBEDDED: Table<'static> = Table::from_static(2, 0, &[1, DEAD, DEAD, 1], &[NO_TOKEN, 7]);
+
+    #[test]
+    fn source() {
+        assert_eq!(EMBEDDED.matches([0, 1, 1]), Some(7));
+        assert_eq!(EMBEDDED.matches([0, 0]), None);
+
+        let src = EMBEDDED.to_rust("EMBEDDED");
+        assert_eq!(
+            src,
+            concat!(
+                "pub static EMBEDDED: ::shrec::dfa::Table<'static> = ",
+                "::shrec::dfa::Table::from_static(\n",
+                "    2,\n    0,\n",
+                "    &[\n        1, u32::MAX, u32::MAX, 1,\n    ],\n",
+                "    &[\n        u32::MAX, 7,\n    ],\n",
+                ");\n",
+            )
+        );
+
+        let dfa = table();
+        assert_eq!(dfa.clone().into_owned(), dfa);
+    }
+}
@@ -0,0 +1,43 @@
+//! The error types returned by this crate
+//!
+//! Each module reports failures with its own error type, all of which convert
+//! into [`Error`] for callers working across several modules at once.
+//!
+//! Operations that do not return a [`Result`] do not fail, with one
+//! exception: the [`Extend`] and [`FromIterator`] implementations of
+//! [`PartitionMap`](crate::partition_map::PartitionMap),
+//! [`RangeMap`](crate::range_map::RangeMap) and
+//! [`RangeSet`](crate::range_set::RangeSet) cannot report errors, and so panic
+//! if given a range that starts after it ends.
+
+pub use crate::{
+    dfa::{TableError, TrapError},
+    nfa::MissingNode,
+    partition_map::InvalidRange,
+    re::syntax::ParseError,
+    union_find::NoNode,
+};
+
+/// Any error returned by this crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// A regular expression could not be parsed
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    /// A range passed to a range-keyed collection starts after it ends
+    #[error(transparent)]
+    InvalidRange(#[from] InvalidRange),
+    /// An NFA edge refers to a node not in the automaton
+    #[error(transparent)]
+    MissingNode(#[from] MissingNode),
+    /// A union-find operation refers to a node that does not exist
+    #[error(transparent)]
+    NoNode(#[from] NoNode),
+    /// A scanner reached input no token could match
+    #[error(transparent)]
+    Trap(#[from] TrapError),
+    /// A DFA table could not be built or loaded
+    #[error(transparent)]
+    Table(#[from] TableError),
+}
@@ -20,6 +20,7 @@
 pub mod closure_builder;
 pub mod dfa;
 pub mod dot;
+pub mod error;
 pub mod free;
 pub mod lex_cmp;
 pub mod memoize;
@@ -29,3 +30,5 @@ pub mod range_map;
 pub mod range_set;
 pub mod re;
 pub mod union_find;
+
+pub use error::Error;
@@ -6,11 +6,23 @@ use std::{
 };
 
 use self::dfa_builder::DfaBuilder;
+pub use self::{
+    alphabet::{Alphabet, ClassId},
+    passes::PassStats,
+};
 use crate::{dfa::Dfa, dot};
 
+pub mod alphabet;
 mod dfa_builder;
+pub mod passes;
+
+/// Error indicating an edge passed to [`Nfa::connect`] refers to a node not
+/// in the automaton
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Edge endpoint not found in NFA")]
+pub struct MissingNode;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node<I, N, E>(BTreeMap<Option<I>, BTreeMap<N, E>>);
 
 impl<I, N, E> Default for Node<I, N, E> {
@@ -28,7 +40,7 @@ impl<I: Ord, N, E> Node<I, N, E> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Nfa<I, N, E, T> {
     nodes: BTreeMap<N, Node<I, N, E>>,
     start: N,
@@ -65,24 +77,34 @@ impl<I: Ord, N: Ord, E, T: Ord> Nfa<I, N, E, T> {
         self.nodes.insert(node, Node::default())
     }
 
+    /// Add an edge between two existing nodes, returning the output of the
+    /// edge it replaced, if any
+    ///
+    /// # Errors
+    /// This method returns an error if either node has not been inserted, in
+    /// which case the automaton is left unchanged.
     pub fn connect<Q: Ord + ?Sized>(
         &mut self,
         from: &Q,
         to: N,
         by: Option<I>,
         out: E,
-    ) -> Option<E>
+    ) -> Result<Option<E>, MissingNode>
     where
         N: Borrow<Q>,
     {
-        assert!(self.nodes.contains_key::<N>(&to));
-        self.nodes
+        if !self.nodes.contains_key::<N>(&to) {
+            return Err(MissingNode);
+        }
+
+        Ok(self
+            .nodes
             .get_mut(from)
-            .unwrap()
+            .ok_or(MissingNode)?
             .0
             .entry(by)
             .or_default()
-            .insert(to, out)
+            .insert(to, out))
     }
 }
 
@@ -0,0 +1,353 @@
+//! Alphabet compression for automata whose edges are labeled with sets of
+//! input ranges
+//!
+//! Labeling each edge with a single input symbol makes large character
+//! classes (such as most Unicode categories) cost one edge per code point.
+//! Instead, an [`Nfa`] may be built over [`RangeSet`] labels and then
+//! compressed, splitting the input domain into the coarsest set of classes
+//! that no edge can tell apart.  The compressed automaton has one edge per
+//! class rather than per symbol, and input is mapped to its class with
+//! [`Alphabet::classify`] before being fed to the automaton.
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use super::{Nfa, Node};
+use crate::{
+    partition_map::{InvalidRange, PartitionMap},
+    range_set::RangeSet,
+};
+
+/// Identifier for an equivalence class of input symbols
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct ClassId(u32);
+
+impl ClassId {
+    /// Get the index of this class within its alphabet
+    #[inline]
+    #[must_use]
+    pub fn index(self) -> usize { self.0 as usize }
+}
+
+/// An automaton whose edges are labeled with input classes
+type ClassNfa<N, E, T> = Nfa<ClassId, N, E, T>;
+
+/// A partition of an input domain into classes of symbols that are never
+/// distinguished by any transition
+#[derive(Debug, Clone)]
+pub struct Alphabet<I> {
+    classes: PartitionMap<I, ClassId>,
+    class_count: u32,
+}
+
+impl<I: Clone + Ord> Alphabet<I> {
+    /// Compute the coarsest partition of the input domain under which every
+    /// set in `labels` is a union of classes
+    ///
+    /// Alongside the alphabet this returns, for each label in order, the
+    /// classes whose union makes up that label.  Symbols matched by no label
+    /// all share a single class, which no label contains.
+    pub fn new<'a>(labels: impl IntoIterator<Item = &'a RangeSet<I>>) -> (Self, Vec<Vec<ClassId>>)
+    where I: 'a {
+        // Tag each elementary range with the set of labels containing it
+        let mut sigs: PartitionMap<I, Vec<usize>> = PartitionMap::new(vec![]);
+        let mut label_count = 0;
+        for (idx, label) in labels.into_iter().enumerate() {
+            // The ranges of a valid set are always ordered
+            sigs.update_all(
+                label.ranges().map(|r| {
+                    let (start, end) = r.bounds();
+                    (start.cloned(), end.cloned())
+                }),
+                |p| {
+                    let mut sig = p.value.clone();
+                    sig.push(idx);
+                    sig
+                },
+            )
+            .unwrap_or_else(|InvalidRange| unreachable!());
+            label_count = idx + 1;
+        }
+
+        // Ranges contained in exactly the same labels can share a class, even
+        // if they aren't adjacent
+        let mut ids: BTreeMap<&[usize], ClassId> = BTreeMap::new();
+        let mut members = vec![vec![]; label_count];
+        let mut classes = vec![];
+        for part in sigs.partitions() {
+            let next = ClassId(
+                ids.len()
+                    .try_into()
+                    .unwrap_or_else(|_| panic!("Too many input classes")),
+            );
+            let id = *ids.entry(part.value.as_slice()).or_insert_with(|| {
+                for &label in part.value {
+                    members[label].push(next);
+                }
+                next
+            });
+
+            classes.push((part.bounds(), id));
+        }
+
+        let class_count = ids.len().try_into().unwrap_or_else(|_| unreachable!());
+        let classes = classes
+            .into_iter()
+            .map(|((s, e), id)| ((s.cloned(), e.cloned()), id))
+            .collect();
+
+        (
+            Self {
+                classes,
+                class_count,
+            },
+            members,
+        )
+    }
+}
+
+impl<I: Ord> Alphabet<I> {
+    /// Get the class containing the given input symbol
+    #[inline]
+    #[must_use]
+    pub fn classify<Q: ?Sized + Ord>(&self, sym: &Q) -> ClassId
+    where I: Borrow<Q> {
+        *self.classes.sample(sym)
+    }
+
+    /// Map a sequence of input symbols to their classes
+    #[inline]
+    pub fn classify_all<J: IntoIterator<Item = I>>(
+        &self,
+        it: J,
+    ) -> std::iter::Map<J::IntoIter, impl FnMut(I) -> ClassId + Clone + '_> {
+        it.into_iter().map(|i| self.classify(&i))
+    }
+}
+
+impl<I> Alphabet<I> {
+    /// Get the number of classes in this alphabet
+    #[inline]
+    #[must_use]
+    pub fn class_count(&self) -> usize { self.class_count as usize }
+
+    /// Get the number of contiguous ranges stored to represent this alphabet
+    #[inline]
+    #[must_use]
+    pub fn range_count(&self) -> usize { self.classes.partitions().count() }
+
+    /// Iterate over every class in this alphabet, e.g. to complete an
+    /// automaton over it with [`Dfa::complete`](crate::dfa::Dfa::complete)
+    #[inline]
+    pub fn classes(&self) -> impl Iterator<Item = ClassId> { (0..self.class_count).map(ClassId) }
+
+    /// Get an input symbol belonging to the given class, e.g. to display a
+    /// witness produced by an automaton over this alphabet
+    ///
+    /// Symbols are taken from the lower bound of one of the class's ranges, so
+    /// this returns `None` for a class consisting solely of a range with no
+    /// lower bound.
+    #[must_use]
+    pub fn example(&self, class: ClassId) -> Option<&I> {
+        self.classes
+            .partitions()
+            .find_map(|p| if *p.value == class { p.start } else { None })
+    }
+}
+
+impl<I: Clone + Ord, N: Clone + Ord, E: Clone, T: Ord> Nfa<RangeSet<I>, N, E, T> {
+    /// Replace every range-labeled edge of this automaton with one edge per
+    /// input class making up its label
+    ///
+    /// Epsilon edges are left untouched.  The returned alphabet must be used
+    /// to classify input before running the compressed automaton (or any
+    /// automaton compiled from it).
+    #[must_use]
+    pub fn compress_alphabet(self) -> (Nfa<ClassId, N, E, T>, Alphabet<I>) {
+        let (mut nfas, alphabet) = Self::compress_alphabets([self]);
+        let nfa = nfas.pop().unwrap_or_else(|| unreachable!());
+        (nfa, alphabet)
+    }
+
+    /// Compress several automata at once, producing a single alphabet shared
+    /// by all of them
+    ///
+    /// Automata must share an alphabet to be combined or compared, e.g. with
+    /// [`Dfa::difference`](crate::dfa::Dfa::difference), since class IDs from
+    /// separately compressed automata are unrelated.
+    #[must_use]
+    pub fn compress_alphabets(
+        nfas: impl IntoIterator<Item = Self>,
+    ) -> (Vec<ClassNfa<N, E, T>>, Alphabet<I>) {
+        let nfas: Vec<_> = nfas.into_iter().collect();
+        let labels: BTreeSet<RangeSet<I>> = nfas
+            .iter()
+            .flat_map(|n| n.nodes.values())
+            .flat_map(|n| n.0.keys().flatten().cloned())
+            .collect();
+        let (alphabet, members) = Alphabet::new(&labels);
+        let members: BTreeMap<_, _> = labels.into_iter().zip(members).collect();
+
+        let nfas = nfas
+            .into_iter()
+            .map(|nfa| nfa.classify_edges(&members))
+            .collect();
+
+        (nfas, alphabet)
+    }
+
+    fn classify_edges(
+        self,
+        members: &BTreeMap<RangeSet<I>, Vec<ClassId>>,
+    ) -> Nfa<ClassId, N, E, T> {
+        let Self {
+            nodes,
+            start,
+            accept,
+        } = self;
+
+        let nodes = nodes
+            .into_iter()
+            .map(|(id, Node(edges))| {
+                let mut new = Node::default();
+                for (label, outs) in edges {
+                    let Some(label) = label else {
+                        new.0.entry(None).or_default().extend(outs);
+                        continue;
+                    };
+
+                    for &class in &members[&label] {
+                        new.0
+                            .entry(Some(class))
+                            .or_default()
+                            .extend(outs.iter().map(|(n, e)| (n.clone(), e.clone())));
+                    }
+                }
+
+                (id, new)
+            })
+            .collect();
+
+        Nfa {
+            nodes,
+            start,
+            accept,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::iter;
+
+    use super::{Alphabet, ClassId};
+    use crate::{
+        range_set::RangeSet,
+        re::{Regex, RegexBag},
+    };
+
+    fn class(ranges: impl IntoIterator<Item = std::ops::Range<char>>) -> RangeSet<char> {
+        ranges.into_iter().collect()
+    }
+
+    #[test]
+    fn coarsest_partition() {
+        let lower = class(iter::once('a'..'{'));
+        let hex = class(['0'..':', 'a'..'g']);
+        let (alphabet, members) = Alphabet::new([&lower, &hex]);
+
+        // {g-z}, {a-f}, {0-9} and everything else
+        assert_eq!(alphabet.class_count(), 4);
+        let c = |ch| alphabet.classify(&ch);
+        assert_eq!(c('a'), c('f'));
+        assert_eq!(c('g'), c('z'));
+        assert_ne!(c('a'), c('g'));
+        assert_ne!(c('0'), c('a'));
+        assert_eq!(c(' '), c('{'));
+        assert_eq!(c(' '), c('\u{10ffff}'));
+
+        let mut lower_classes = members[0].clone();
+        lower_classes.sort_unstable();
+        let mut expected = vec![c('a'), c('g')];
+        expected.sort_unstable();
+        assert_eq!(lower_classes, expected);
+        assert!(!members.iter().flatten().any(|&m| m == c(' ')));
+    }
+
+    #[test]
+    fn unicode_classes_stay_small() {
+        let alpha = class([
+            'A'..'[',
+            '_'..'`',
+            'a'..'{',
+            '\u{c0}'..'\u{2000}',
+            '\u{3040}'..'\u{a000}',
+        ]);
+        let digit = class(iter::once('0'..':'));
+        let alnum = alpha.clone().unioned(&digit);
+        let space = class([' '..'!', '\t'..'\u{b}', '\u{3000}'..'\u{3001}']);
+
+        let bag: RegexBag<Vec<RangeSet<char>>, &str> = vec![
+            (
+                Regex::Cat(vec![
+                    Regex::Lit(vec![alpha]),
+                    Regex::Star(Regex::Lit(vec![alnum]).into()),
+                ]),
+                "ident",
+            ),
+            (
+                Regex::Cat(vec![
+                    Regex::Lit(vec![digit.clone()]),
+                    Regex::Star(Regex::Lit(vec![digit]).into()),
+                ]),
+                "num",
+            ),
+            (Regex::Lit(vec![space]), "space"),
+        ]
+        .into();
+
+        let mut nfa = bag.compile();
+        nfa.simplify();
+        let (nfa, alphabet) = nfa.compress_alphabet();
+        let (dfa, _) = nfa.compile().copied().atomize_nodes::<u64>();
+
+        // Tens of thousands of code points collapse into four classes, and the
+        // automaton stores a handful of edges per state rather than one per
+        // code point
+        assert_eq!(alphabet.class_count(), 4);
+        assert!(alphabet.range_count() < 20, "{}", alphabet.range_count());
+        let edges: usize = dfa.states().map(|(_, n)| n.edges().count()).sum();
+        assert!(edges <= dfa.states().count() * 3, "{edges}");
+        assert!(edges < 16, "{edges}");
+
+        let scan = |s: &str| {
+            crate::dfa::Scanner::new(&dfa, alphabet.classify_all(s.chars()))
+                .map(|t| t.ok().map(|t| t.iter().map(|t| **t).collect::<Vec<_>>()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(scan("héllo 42"), [
+            Some(vec!["ident"]),
+            Some(vec!["space"]),
+            Some(vec!["num"])
+        ]);
+        assert_eq!(scan("ひらがな\u{3000}x9"), [
+            Some(vec!["ident"]),
+            Some(vec!["space"]),
+            Some(vec!["ident"])
+        ]);
+        assert_eq!(scan("9a"), [Some(vec!["num"]), Some(vec!["ident"])]);
+        assert_eq!(scan("a-"), [Some(vec!["ident"]), None]);
+    }
+
+    #[test]
+    fn no_labels() {
+        let (alphabet, members) = Alphabet::<char>::new([]);
+        assert_eq!(alphabet.class_count(), 1);
+        assert_eq!(alphabet.classify(&'x'), ClassId::default());
+        assert!(members.is_empty());
+    }
+}
@@ -0,0 +1,277 @@
+//! Simplification passes for shrinking an [`Nfa`] before determinization
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::{Add, AddAssign},
+};
+
+use super::{Nfa, Node};
+use crate::closure_builder::ClosureBuilder;
+
+/// Statistics describing the changes made by a single NFA pass
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PassStats {
+    /// Number of states removed from the automaton
+    pub states_removed: usize,
+    /// Number of edges removed from the automaton
+    pub edges_removed: usize,
+    /// Number of edges added to the automaton
+    pub edges_added: usize,
+}
+
+impl Add for PassStats {
+    type Output = Self;
+
+    #[inline]
+    fn add(mut self, rhs: Self) -> Self {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for PassStats {
+    fn add_assign(&mut self, rhs: Self) {
+        let Self {
+            states_removed,
+            edges_removed,
+            edges_added,
+        } = rhs;
+        self.states_removed += states_removed;
+        self.edges_removed += edges_removed;
+        self.edges_added += edges_added;
+    }
+}
+
+impl<I: Ord, N, E> Node<I, N, E> {
+    #[inline]
+    fn edge_count(&self) -> usize { self.0.values().map(BTreeMap::len).sum() }
+
+    /// Count the edges of this node not present in `other`
+    fn edges_missing_from(&self, other: &Self) -> usize
+    where N: Ord {
+        self.0
+            .iter()
+            .map(|(inp, outs)| {
+                let Some(other) = other.0.get(inp) else {
+                    return outs.len();
+                };
+                outs.keys().filter(|n| !other.contains_key(*n)).count()
+            })
+            .sum()
+    }
+}
+
+impl<I: Ord, N: Clone + Ord, E, T: Ord> Nfa<I, N, E, T> {
+    /// Remove all states not in `keep`, along with any edges into them and any
+    /// tokens accepted by them
+    fn retain_states(&mut self, keep: &BTreeSet<N>) -> PassStats {
+        let mut stats = PassStats::default();
+
+        self.nodes.retain(|id, node| {
+            let retain = keep.contains(id);
+            if !retain {
+                stats.states_removed += 1;
+                stats.edges_removed += node.edge_count();
+            }
+            retain
+        });
+
+        for node in self.nodes.values_mut() {
+            node.0.retain(|_, outs| {
+                outs.retain(|to, _| {
+                    let retain = keep.contains(to);
+                    if !retain {
+                        stats.edges_removed += 1;
+                    }
+                    retain
+                });
+                !outs.is_empty()
+            });
+        }
+
+        self.accept.retain(|_, n| keep.contains(n));
+
+        stats
+    }
+
+    /// Remove all states that cannot be reached from the start state
+    ///
+    /// Tokens accepted only by unreachable states are removed as well, since
+    /// they can never be produced.
+    pub fn prune_unreachable(&mut self) -> PassStats {
+        let mut closure = ClosureBuilder::default();
+        closure.init([&self.start]);
+        let reachable: BTreeSet<&N> = closure.solve(BTreeSet::new(), |n| {
+            self.nodes
+                .get(n)
+                .into_iter()
+                .flat_map(|n| n.0.values())
+                .flat_map(BTreeMap::keys)
+        });
+        let reachable = reachable.into_iter().cloned().collect();
+
+        self.retain_states(&reachable)
+    }
+
+    /// Remove all states from which no accepting state can be reached
+    ///
+    /// The start state is always kept, even if it cannot reach an accepting
+    /// state.
+    pub fn prune_dead(&mut self) -> PassStats {
+        let mut preds: BTreeMap<&N, BTreeSet<&N>> = BTreeMap::new();
+        for (from, node) in &self.nodes {
+            for to in node.0.values().flat_map(BTreeMap::keys) {
+                preds.entry(to).or_default().insert(from);
+            }
+        }
+
+        let mut closure = ClosureBuilder::default();
+        closure.init(self.accept.values());
+        let live: BTreeSet<&N> = closure.solve(BTreeSet::new(), |n| {
+            preds.get(n).into_iter().flatten().copied()
+        });
+        let mut live: BTreeSet<_> = live.into_iter().cloned().collect();
+        live.insert(self.start.clone());
+
+        self.retain_states(&live)
+    }
+}
+
+impl<I: Clone + Ord, N: Clone + Ord, T: Ord> Nfa<I, N, (), T> {
+    /// Replace epsilon edges with direct edges on the inputs reachable through
+    /// them
+    ///
+    /// Because each token is accepted by exactly one state, epsilon edges
+    /// leading directly to an accepting state are kept rather than merging the
+    /// accepting state into its predecessors.  All other epsilon edges are
+    /// removed.  This pass typically leaves many states unreachable, so it
+    /// should be followed by [`prune_unreachable`](Self::prune_unreachable).
+    pub fn eliminate_epsilons(&mut self) -> PassStats {
+        let accepting: BTreeSet<&N> = self.accept.values().collect();
+        let mut closure = ClosureBuilder::default();
+        let mut stats = PassStats::default();
+
+        let nodes: BTreeMap<_, _> = self
+            .nodes
+            .iter()
+            .map(|(id, node)| {
+                closure.init([id]);
+                let eps: BTreeSet<&N> = closure.solve(BTreeSet::new(), |n| {
+                    #[expect(
+                        clippy::zero_sized_map_values,
+                        reason = "Nfa with unit edge type necessarily creates a BTreeMap \
+                                  representing a set"
+                    )]
+                    self.nodes
+                        .get(n)
+                        .into_iter()
+                        .filter_map(|n| n.0.get(&None))
+                        .flat_map(BTreeMap::keys)
+                });
+
+                let mut new = Node::default();
+                for &reached in &eps {
+                    if reached != id && accepting.contains(reached) {
+                        new.0.entry(None).or_default().insert(reached.clone(), ());
+                    }
+
+                    for (inp, outs) in &self.nodes[reached].0 {
+                        let Some(inp) = inp else { continue };
+                        new.0
+                            .entry(Some(inp.clone()))
+                            .or_default()
+                            .extend(outs.keys().map(|n| (n.clone(), ())));
+                    }
+                }
+
+                stats.edges_removed += node.edges_missing_from(&new);
+                stats.edges_added += new.edges_missing_from(node);
+
+                (id.clone(), new)
+            })
+            .collect();
+
+        self.nodes = nodes;
+
+        stats
+    }
+
+    /// Run all simplification passes in order, returning the combined
+    /// statistics
+    pub fn simplify(&mut self) -> PassStats {
+        self.eliminate_epsilons() + self.prune_unreachable() + self.prune_dead()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use crate::re::{Regex, RegexBag};
+
+    fn bag() -> RegexBag<std::str::Chars<'static>, &'static str> {
+        RegexBag::from(vec![
+            (Regex::Lit("for".chars()), "for"),
+            (Regex::Lit("each".chars()), "each"),
+            (Regex::Lit("ea".chars()), "ea"),
+            (
+                Regex::Cat(vec![
+                    Regex::Lit("x".chars()),
+                    Regex::Star(Regex::Lit("yz".chars()).into()),
+                ]),
+                "xyz",
+            ),
+        ])
+    }
+
+    fn scan(
+        nfa: &super::Nfa<char, u64, (), &'static str>,
+        s: &str,
+    ) -> Vec<Option<Vec<&'static str>>> {
+        let (dfa, _) = nfa.compile().copied().atomize_nodes::<u64>();
+
+        crate::dfa::Scanner::new(&dfa, s.chars())
+            .map(|t| t.ok().map(|t| t.iter().map(|t| **t).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn simplify_preserves_language() {
+        let orig = bag().compile();
+        let mut nfa = bag().compile();
+
+        let stats = nfa.simplify();
+        assert!(stats.states_removed > 0);
+        assert!(nfa.nodes.len() < orig.nodes.len());
+
+        for input in ["foreach", "eachfor", "eaea", "xyzyzx", "fo", "xyzq", ""] {
+            assert_eq!(scan(&orig, input), scan(&nfa, input), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn epsilons_only_reach_accepting() {
+        let mut nfa = bag().compile();
+        nfa.eliminate_epsilons();
+
+        let accepting: BTreeSet<_> = nfa.accept.values().collect();
+        for node in nfa.nodes.values() {
+            for to in node.get(&None).into_iter().flat_map(|e| e.keys()) {
+                assert!(accepting.contains(to));
+            }
+        }
+    }
+
+    #[test]
+    fn prune_dead_keeps_start() {
+        let mut nfa = super::Nfa::<char, u64, (), ()>::new(0);
+        nfa.insert(1);
+        nfa.connect(&0, 1, Some('a'), ()).unwrap();
+        assert!(nfa.connect(&0, 2, None, ()).is_err());
+
+        let stats = nfa.prune_dead();
+        assert_eq!(stats.states_removed, 1);
+        assert_eq!(stats.edges_removed, 1);
+        assert_eq!(nfa.nodes.keys().copied().collect::<Vec<_>>(), [0]);
+    }
+}
@@ -69,7 +69,13 @@ impl<T> PartitionBounds<T> for (Option<T>, Option<T>) {
     fn into_bounds(self) -> (Option<T>, Option<T>) { self }
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+/// Error indicating a range passed to a [`PartitionMap`] operation starts
+/// after it ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid range, start is greater than end")]
+pub struct InvalidRange;
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PartitionMap<K, V> {
     unbounded_start: V,
     ranges_from: BTreeMap<K, V>,
@@ -107,6 +113,43 @@ impl<K, V> PartitionMap<K, V> {
     #[inline]
     #[must_use]
     pub fn into_partitions(self) -> IntoPartitions<K, V> { IntoPartitions::new(self) }
+
+    /// Iterate over the partitions whose value is equal to the given value
+    #[inline]
+    #[must_use]
+    pub fn partitions_eq<'a>(&'a self, value: &'a V) -> PartitionsEq<'a, K, V> {
+        PartitionsEq {
+            iter: self.partitions(),
+            value,
+        }
+    }
+
+    /// Iterate over the ranges of keys whose values satisfy the given
+    /// predicate
+    ///
+    /// Adjacent partitions satisfying the predicate are merged into a single
+    /// range.
+    #[inline]
+    #[must_use]
+    pub fn ranges_where<F: FnMut(&V) -> bool>(&self, pred: F) -> RangesWhere<K, V, F> {
+        RangesWhere {
+            iter: self.partitions(),
+            pred,
+        }
+    }
+
+    /// Iterate over the ranges of keys whose values do not satisfy the given
+    /// predicate
+    ///
+    /// This is the complement of [`ranges_where`](Self::ranges_where).
+    #[inline]
+    #[must_use]
+    pub fn complement<F: FnMut(&V) -> bool>(
+        &self,
+        mut pred: F,
+    ) -> RangesWhere<K, V, impl FnMut(&V) -> bool> {
+        self.ranges_where(move |v| !pred(v))
+    }
 }
 
 #[cfg(test)]
@@ -129,26 +172,55 @@ impl<K: Ord, V> PartitionMap<K, V> {
             .next_back()
             .map_or(&self.unbounded_start, |(_, v)| v)
     }
+
+    /// Get the partition containing the given key, along with its bounds
+    pub fn containing<T: ?Sized + Ord>(&self, at: &T) -> Partition<&K, &V>
+    where K: Borrow<T> {
+        let start = self
+            .ranges_from
+            .range((Bound::Unbounded, Bound::Included(at)))
+            .next_back();
+        let end = self
+            .ranges_from
+            .range((Bound::Excluded(at), Bound::Unbounded))
+            .next();
+
+        Partition {
+            start: start.map(|(k, _)| k),
+            end: end.map(|(k, _)| k),
+            value: start.map_or(&self.unbounded_start, |(_, v)| v),
+        }
+    }
 }
 
-fn check_bounds<T: Ord, B: PartitionBounds<T>>(range: B) -> Option<(Option<T>, Option<T>)> {
+type Bounds<T> = (Option<T>, Option<T>);
+
+/// Split a range into its bounds, returning `None` if it is empty
+fn check_bounds<T: Ord, B: PartitionBounds<T>>(
+    range: B,
+) -> Result<Option<Bounds<T>>, InvalidRange> {
     let (start, end) = range.into_bounds();
 
     if let (Some(s), Some(e)) = (&start, &end) {
         match s.cmp(e) {
             Ordering::Less => (),
-            Ordering::Equal => return None,
-            Ordering::Greater => panic!("Invalid range, start is greater than end"),
+            Ordering::Equal => return Ok(None),
+            Ordering::Greater => return Err(InvalidRange),
         }
     }
 
-    Some((start, end))
+    Ok(Some((start, end)))
 }
 
 impl<K: Clone + Ord, V: Clone + PartialEq> PartitionMap<K, V> {
-    fn set_internal<B: PartitionBounds<K>>(&mut self, range: B, value: V, over: &mut Vec<K>) {
-        let Some((start, end)) = check_bounds(range) else {
-            return;
+    fn set_internal<B: PartitionBounds<K>>(
+        &mut self,
+        range: B,
+        value: V,
+        over: &mut Vec<K>,
+    ) -> Result<(), InvalidRange> {
+        let Some((start, end)) = check_bounds(range)? else {
+            return Ok(());
         };
 
         debug_assert!(over.is_empty());
@@ -164,7 +236,8 @@ impl<K: Clone + Ord, V: Clone + PartialEq> PartitionMap<K, V> {
         });
 
         for key in over.drain(..) {
-            debug_assert!(self.ranges_from.remove(&key).is_some());
+            let removed = self.ranges_from.remove(&key);
+            debug_assert!(removed.is_some());
         }
 
         let start_value = start
@@ -183,26 +256,40 @@ impl<K: Clone + Ord, V: Clone + PartialEq> PartitionMap<K, V> {
 
         if *start_value != value {
             if let Some(start) = start {
-                debug_assert!(self.ranges_from.insert(start, value).is_none());
+                let prev = self.ranges_from.insert(start, value);
+                debug_assert!(prev.is_none());
             } else {
                 self.unbounded_start = value;
             }
         }
 
         if let Some((end, value)) = end {
-            debug_assert!(self.ranges_from.insert(end, value).is_none());
+            let prev = self.ranges_from.insert(end, value);
+            debug_assert!(prev.is_none());
         }
+
+        Ok(())
     }
 
+    /// Set the value of every key in the given range
+    ///
+    /// # Errors
+    /// This method returns an error if the range starts after it ends, in
+    /// which case the map is left unchanged.
     #[inline]
-    pub fn set<B: PartitionBounds<K>>(&mut self, range: B, value: V) {
-        self.set_internal(range, value, &mut vec![]);
+    pub fn set<B: PartitionBounds<K>>(&mut self, range: B, value: V) -> Result<(), InvalidRange> {
+        self.set_internal(range, value, &mut vec![])
     }
 
+    /// Set the value of every key in the range of the given partition
+    ///
+    /// # Errors
+    /// This method returns an error if the partition starts after it ends,
+    /// in which case the map is left unchanged.
     #[inline]
-    pub fn push(&mut self, part: Partition<K, V>) {
+    pub fn push(&mut self, part: Partition<K, V>) -> Result<(), InvalidRange> {
         let Partition { start, end, value } = part;
-        self.set((start, end), value);
+        self.set((start, end), value)
     }
 
     // TODO: this could maybe be faster but like.  ghh
@@ -212,9 +299,9 @@ impl<K: Clone + Ord, V: Clone + PartialEq> PartitionMap<K, V> {
         mut f: F,
         over: &mut Vec<(K, V)>,
         set_over: &mut Vec<K>,
-    ) {
-        let Some((start, end)) = check_bounds(range) else {
-            return;
+    ) -> Result<(), InvalidRange> {
+        let Some((start, end)) = check_bounds(range)? else {
+            return Ok(());
         };
 
         debug_assert!(over.is_empty());
@@ -246,7 +333,7 @@ impl<K: Clone + Ord, V: Clone + PartialEq> PartitionMap<K, V> {
                 value: &orig_value,
             });
             if value != orig_value {
-                self.set_internal((start, end.clone()), value, set_over);
+                self.set_internal((start, end.clone()), value, set_over)?;
             }
 
             start = end;
@@ -260,32 +347,48 @@ impl<K: Clone + Ord, V: Clone + PartialEq> PartitionMap<K, V> {
                 value: &orig_value,
             });
             if value != orig_value {
-                self.set_internal((start, end), value, set_over);
+                self.set_internal((start, end), value, set_over)?;
             }
         }
+
+        Ok(())
     }
 
+    /// Replace the value of each partition overlapping the given range with
+    /// the result of `f`, applied to the overlapping part of the partition
+    ///
+    /// # Errors
+    /// This method returns an error if the range starts after it ends, in
+    /// which case the map is left unchanged.
     pub fn update<B: PartitionBounds<K>, F: FnMut(Partition<&K, &V>) -> V>(
         &mut self,
         range: B,
         f: F,
-    ) {
-        self.update_internal(range, f, &mut vec![], &mut vec![]);
+    ) -> Result<(), InvalidRange> {
+        self.update_internal(range, f, &mut vec![], &mut vec![])
     }
 
+    /// Apply [`update`](Self::update) to each of the given ranges in turn
+    ///
+    /// # Errors
+    /// This method returns an error at the first range that starts after it
+    /// ends.  Updates for the ranges preceding it are kept.
     pub fn update_all<I: IntoIterator, F: FnMut(Partition<&K, &V>) -> V>(
         &mut self,
         it: I,
         mut f: F,
-    ) where
+    ) -> Result<(), InvalidRange>
+    where
         I::Item: PartitionBounds<K>,
     {
         let mut over = vec![];
         let mut set_over = vec![];
 
         for range in it {
-            self.update_internal(range, &mut f, &mut over, &mut set_over);
+            self.update_internal(range, &mut f, &mut over, &mut set_over)?;
         }
+
+        Ok(())
     }
 
     pub fn fold<F: FnMut(Partition<&K, &V>, &V) -> V>(
@@ -297,12 +400,14 @@ impl<K: Clone + Ord, V: Clone + PartialEq> PartitionMap<K, V> {
         let mut set_over = vec![];
 
         for Partition { start, end, value } in other.partitions() {
+            // Partitions of a valid map are always ordered
             self.update_internal(
                 (start.cloned(), end.cloned()),
                 |p| f(p, value),
                 &mut over,
                 &mut set_over,
-            );
+            )
+            .unwrap_or_else(|InvalidRange| unreachable!());
         }
     }
 
@@ -333,6 +438,9 @@ fn as_closed<T>(opt: &Option<T>) -> Bound<&T> {
     opt.as_ref().map_or(Bound::Unbounded, Bound::Included)
 }
 
+/// # Panics
+/// Extending a map panics if any range starts after it ends.  Use
+/// [`PartitionMap::set`] to handle invalid ranges instead.
 impl<K: Clone + Ord, V: Clone + PartialEq, B: PartitionBounds<K>> Extend<(B, V)>
     for PartitionMap<K, V>
 {
@@ -340,7 +448,9 @@ impl<K: Clone + Ord, V: Clone + PartialEq, B: PartitionBounds<K>> Extend<(B, V)>
         let mut over = vec![];
 
         for (range, value) in it {
-            self.set_internal(range, value, &mut over);
+            if let Err(err) = self.set_internal(range, value, &mut over) {
+                panic!("{err}");
+            }
         }
 
         #[cfg(test)]
@@ -529,6 +639,52 @@ impl<'a, K, V> Iterator for Partitions<'a, K, V> {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct PartitionsEq<'a, K, V> {
+    iter: Partitions<'a, K, V>,
+    value: &'a V,
+}
+
+impl<'a, K, V: PartialEq> Iterator for PartitionsEq<'a, K, V> {
+    type Item = Partition<&'a K, &'a V>;
+
+    // Adjacent partitions never share a value, so no merging is necessary
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.value;
+        self.iter.find(|p| *p.value == *value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RangesWhere<'a, K, V, F> {
+    iter: Partitions<'a, K, V>,
+    pred: F,
+}
+
+impl<'a, K, V, F: FnMut(&V) -> bool> Iterator for RangesWhere<'a, K, V, F> {
+    type Item = Partition<&'a K, ()>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.iter.find(|p| (self.pred)(p.value))?;
+        let mut end = first.end;
+
+        // Consuming the first failing partition is fine, as the next call
+        // would skip it anyway
+        while end.is_some() {
+            match self.iter.next() {
+                Some(p) if (self.pred)(p.value) => end = p.end,
+                _ => break,
+            }
+        }
+
+        Some(Partition {
+            start: first.start,
+            end,
+            value: (),
+        })
+    }
+}
+
 #[derive(Debug)]
 struct IntoPartitionsInner<K, V> {
     start: Option<K>,
@@ -616,13 +772,55 @@ mod test {
         }
     }
 
+    #[test]
+    fn set_regression() {
+        // set_internal used to perform its edits inside debug_assert!, so this
+        // only failed when built without debug assertions
+        let mut map = Map::new('a');
+        map.set(2..8, 'b').unwrap();
+        map.set(4..6, 'c').unwrap();
+        map.set(..3, 'd').unwrap();
+
+        assert_parts(&map, &[
+            part(..3, 'd'),
+            part(3..4, 'b'),
+            part(4..6, 'c'),
+            part(6..8, 'b'),
+            part(8.., 'a'),
+        ]);
+    }
+
+    #[test]
+    #[expect(clippy::reversed_empty_ranges, reason = "Testing invalid ranges")]
+    fn invalid_range() {
+        let mut map = Map::new('a');
+        map.set(2..5, 'b').unwrap();
+        let orig = map.clone();
+
+        assert_eq!(map.set(4..1, 'c'), Err(InvalidRange));
+        assert_eq!(map.update(4..1, |_| 'c'), Err(InvalidRange));
+        assert_eq!(map, orig);
+
+        assert_eq!(map.update_all([0..1, 3..2], |_| 'c'), Err(InvalidRange));
+        assert_eq!(*map.sample(&0), 'c');
+        assert_eq!(*map.sample(&3), 'b');
+
+        assert!(map.set(3..3, 'c').is_ok());
+        assert_eq!(
+            crate::Error::from(map.push(Part::of(3..1, 'c')).unwrap_err()),
+            crate::Error::InvalidRange(InvalidRange)
+        );
+    }
+
     #[test]
     fn range_set_intersect_regression_update() {
         let mut lhs = Map::new('a');
         lhs.extend([Part::of(1.., 'b')]);
 
-        lhs.update(..1, |p| intersect_regression_op(*p.value, 'b'));
-        lhs.update(1.., |p| intersect_regression_op(*p.value, 'a'));
+        lhs.update(..1, |p| intersect_regression_op(*p.value, 'b'))
+            .unwrap();
+        lhs.update(1.., |p| intersect_regression_op(*p.value, 'a'))
+            .unwrap();
 
         assert_eq!(lhs, Map::new('a'));
     }
@@ -1054,6 +1252,52 @@ mod test {
         ]);
     }
 
+    #[test]
+    fn test_queries() {
+        let mut map = Map::new('a');
+        map.extend([(2..4, 'b'), (4..6, 'c'), (8..10, 'b')]);
+
+        assert_eq!(map.containing(&0).copied(), part(..2, 'a'));
+        assert_eq!(map.containing(&4).copied(), part(4..6, 'c'));
+        assert_eq!(map.containing(&9).copied(), part(8..10, 'b'));
+        assert_eq!(map.containing(&10).copied(), part(10.., 'a'));
+
+        assert_eq!(
+            map.partitions_eq(&'b')
+                .map(|p| p.copied())
+                .collect::<Vec<_>>(),
+            [part(2..4, 'b'), part(8..10, 'b')],
+        );
+
+        assert_eq!(
+            map.ranges_where(|&v| v != 'a')
+                .map(Partition::bounds)
+                .collect::<Vec<_>>(),
+            [(Some(&2), Some(&6)), (Some(&8), Some(&10))],
+        );
+        assert_eq!(
+            map.complement(|&v| v != 'a')
+                .map(Partition::bounds)
+                .collect::<Vec<_>>(),
+            [(None, Some(&2)), (Some(&6), Some(&8)), (Some(&10), None)],
+        );
+        assert_eq!(map.ranges_where(|_| true).count(), 1);
+        assert_eq!(map.complement(|_| true).count(), 0);
+    }
+
+    fn test_containing_impl(c: char, v: Vec<Part>, at: u64) {
+        let mut map = Map::new(c);
+        map.extend(v);
+
+        let Partition { start, end, value } = map.containing(&at);
+        assert_eq!(value, map.sample(&at));
+        assert!(start.map_or(true, |&s| s <= at));
+        assert!(end.map_or(true, |&e| at < e));
+        assert!(map
+            .partitions()
+            .any(|p| p == Partition { start, end, value }));
+    }
+
     type Part = Partition<u64, char>;
 
     fn check_part(
@@ -1140,7 +1384,8 @@ mod test {
         let mut over = vec![];
         let mut set_over = vec![];
         for Partition { start, end, value } in v {
-            upd.update_internal((start, end), |_| value, &mut over, &mut set_over);
+            upd.update_internal((start, end), |_| value, &mut over, &mut set_over)
+                .unwrap();
         }
     }
 
@@ -1184,6 +1429,18 @@ mod test {
             test_update_sanity_impl(c, v);
         }
 
+        #[test]
+        fn test_containing(
+            c in prop::char::range('a', 'z'),
+            v in prop::collection::vec(
+                prop_part(0_u64..16, prop::char::range('a', 'z')),
+                0..64
+            ),
+            at in 0_u64..20,
+        ) {
+            test_containing_impl(c, v, at);
+        }
+
         #[test]
         fn test_extend_clobber(
             c in prop::char::range('a', 'z'),
@@ -1,6 +1,6 @@
 use std::{borrow::Borrow, fmt, ops};
 
-use crate::partition_map::{Partition, PartitionBounds, PartitionMap};
+use crate::partition_map::{InvalidRange, Partition, PartitionBounds, PartitionMap};
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -46,13 +46,27 @@ impl<K: Ord, V> RangeMap<K, V> {
 }
 
 impl<K: Clone + Ord, V: Clone + PartialEq> RangeMap<K, V> {
+    /// Map every key in the given range to a value
+    ///
+    /// # Errors
+    /// This method returns an error if the range starts after it ends.
     #[inline]
-    pub fn insert<B: PartitionBounds<K>>(&mut self, range: B, value: V) {
-        self.0.set(range, Some(value));
+    pub fn insert<B: PartitionBounds<K>>(
+        &mut self,
+        range: B,
+        value: V,
+    ) -> Result<(), InvalidRange> {
+        self.0.set(range, Some(value))
     }
 
+    /// Unmap every key in the given range
+    ///
+    /// # Errors
+    /// This method returns an error if the range starts after it ends.
     #[inline]
-    pub fn remove<B: PartitionBounds<K>>(&mut self, range: B) { self.0.set(range, None); }
+    pub fn remove<B: PartitionBounds<K>>(&mut self, range: B) -> Result<(), InvalidRange> {
+        self.0.set(range, None)
+    }
 }
 impl<K, V> ops::Deref for RangeMap<K, V> {
     type Target = PartitionMap<K, Option<V>>;
@@ -66,6 +80,9 @@ impl<K, V> ops::DerefMut for RangeMap<K, V> {
     fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0 }
 }
 
+/// # Panics
+/// Extending a map panics if any range starts after it ends.  Use
+/// [`RangeMap::insert`] to handle invalid ranges instead.
 impl<K: Clone + Ord, V: Clone + PartialEq, B: PartitionBounds<K>> Extend<(B, V)>
     for RangeMap<K, V>
 {
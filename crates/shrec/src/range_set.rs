@@ -1,16 +1,16 @@
 use std::{borrow::Borrow, fmt, ops};
 
-use crate::partition_map::{Partition, PartitionBounds, PartitionMap, Partitions};
+use crate::partition_map::{
+    InvalidRange, Partition, PartitionBounds, PartitionMap, Partitions, PartitionsEq,
+};
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
 pub struct RangeSet<T>(PartitionMap<T, bool>);
 
 impl<T: fmt::Debug> fmt::Debug for RangeSet<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0
-            .partitions()
-            .filter(|p| *p.value)
+        self.ranges()
             .fold(&mut f.debug_set(), |d, s| s.debug_range(|r| d.entry(r)))
             .finish()
     }
@@ -36,10 +36,10 @@ impl<T> RangeSet<T> {
     pub fn all_ranges(&self) -> AllRanges<T> { AllRanges(self.0.partitions()) }
 
     #[must_use]
-    pub fn ranges(&self) -> Ranges<T> { Ranges(self.0.partitions(), true) }
+    pub fn ranges(&self) -> Ranges<T> { Ranges(self.0.partitions_eq(&true)) }
 
     #[must_use]
-    pub fn empty_ranges(&self) -> Ranges<T> { Ranges(self.0.partitions(), false) }
+    pub fn empty_ranges(&self) -> Ranges<T> { Ranges(self.0.partitions_eq(&false)) }
 }
 
 impl<T: Ord> RangeSet<T> {
@@ -51,11 +51,23 @@ impl<T: Ord> RangeSet<T> {
 }
 
 impl<T: Clone + Ord> RangeSet<T> {
+    /// Add every element of the given range to the set
+    ///
+    /// # Errors
+    /// This method returns an error if the range starts after it ends.
     #[inline]
-    pub fn insert<B: PartitionBounds<T>>(&mut self, range: B) { self.0.set(range, true); }
+    pub fn insert<B: PartitionBounds<T>>(&mut self, range: B) -> Result<(), InvalidRange> {
+        self.0.set(range, true)
+    }
 
+    /// Remove every element of the given range from the set
+    ///
+    /// # Errors
+    /// This method returns an error if the range starts after it ends.
     #[inline]
-    pub fn remove<B: PartitionBounds<T>>(&mut self, range: B) { self.0.set(range, false); }
+    pub fn remove<B: PartitionBounds<T>>(&mut self, range: B) -> Result<(), InvalidRange> {
+        self.0.set(range, false)
+    }
 
     pub fn union(&mut self, other: &Self) { self.0.fold(other, |p, &v| *p.value || v); }
 
@@ -75,7 +87,11 @@ impl<T: Clone + Ord> RangeSet<T> {
         self
     }
 
-    pub fn invert(&mut self) { self.0.update(.., |p| !p.value); }
+    pub fn invert(&mut self) {
+        self.0
+            .update(.., |p| !p.value)
+            .unwrap_or_else(|InvalidRange| unreachable!());
+    }
 
     #[must_use]
     #[inline]
@@ -97,6 +113,9 @@ impl<T> ops::DerefMut for RangeSet<T> {
     fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0 }
 }
 
+/// # Panics
+/// Extending a set panics if any range starts after it ends.  Use
+/// [`RangeSet::insert`] to handle invalid ranges instead.
 impl<T: Clone + Ord, B: PartitionBounds<T>> Extend<B> for RangeSet<T> {
     #[inline]
     fn extend<I: IntoIterator<Item = B>>(&mut self, it: I) {
@@ -112,20 +131,13 @@ impl<T: Clone + Ord, B: PartitionBounds<T>> FromIterator<B> for RangeSet<T> {
 }
 
 #[derive(Debug, Clone)]
-pub struct Ranges<'a, T>(Partitions<'a, T, bool>, bool);
+#[repr(transparent)]
+pub struct Ranges<'a, T>(PartitionsEq<'a, T, bool>);
 
 impl<'a, T> Iterator for Ranges<'a, T> {
     type Item = Partition<&'a T, ()>;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let partition = self.0.next()?;
-
-            if *partition.value == self.1 {
-                return Some(partition.map_value(|_| ()));
-            }
-        }
-    }
+    fn next(&mut self) -> Option<Self::Item> { Some(self.0.next()?.map_value(|_| ())) }
 }
 
 #[derive(Debug, Clone)]
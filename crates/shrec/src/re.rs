@@ -1,15 +1,23 @@
+use std::ops::RangeInclusive;
+
 use nfa_builder::NfaBuilder;
 
 use crate::nfa::Nfa;
 
+pub mod arena;
+pub mod brzozowski;
 mod nfa_builder;
+pub mod run;
+pub mod scan;
 pub mod syntax;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Regex<L> {
     Alt(Vec<Regex<L>>),
     Cat(Vec<Regex<L>>),
     Star(Box<Regex<L>>),
+    /// Between `start` and `end` (inclusive) repetitions of a regex
+    Repeat(Box<Regex<L>>, RangeInclusive<u32>),
     Lit(L),
 }
 
@@ -18,7 +26,22 @@ impl<L> Regex<L> {
     pub const TOP: Regex<L> = Regex::Cat(Vec::new());
 }
 
-impl<L: IntoIterator> Regex<L>
+impl<L: Clone> Regex<L> {
+    /// Construct a regex matching at least `min` repetitions of `re`, and at
+    /// most `max` if it is given
+    #[must_use]
+    pub fn repeat(re: Self, min: u32, max: Option<u32>) -> Self {
+        match max {
+            Some(max) => Self::Repeat(re.into(), min..=max),
+            None => Self::Cat(vec![
+                Self::Repeat(re.clone().into(), min..=min),
+                Self::Star(re.into()),
+            ]),
+        }
+    }
+}
+
+impl<L: IntoIterator + Clone> Regex<L>
 where L::Item: Ord
 {
     #[inline]
@@ -29,7 +52,7 @@ where L::Item: Ord
 pub type Token<L, T> = (Regex<L>, T);
 pub type TokenList<L, T> = Vec<Token<L, T>>;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 #[repr(transparent)]
 pub struct RegexBag<L, T>(TokenList<L, T>);
 
@@ -55,7 +78,7 @@ impl<L, T> FromIterator<Token<L, T>> for RegexBag<L, T> {
     }
 }
 
-impl<L: IntoIterator, T: Ord> RegexBag<L, T>
+impl<L: IntoIterator + Clone, T: Ord> RegexBag<L, T>
 where L::Item: Ord
 {
     #[inline]
@@ -0,0 +1,290 @@
+//! An interned, arena-backed representation of regular expressions
+//!
+//! A [`Regex`] tree owns each of its subexpressions through nested `Vec`s and
+//! `Box`es, so large machine-generated patterns spend much of their
+//! construction time allocating.  A [`RegexArena`] instead stores every
+//! distinct subexpression once and refers to it by a [`RegexId`], with the
+//! operands of alternations and concatenations stored as interned lists.
+//! Structurally equal subexpressions are therefore shared, and building an
+//! expression out of existing ones only allocates if it has not been seen
+//! before.
+
+use std::{hash::Hash, mem, ops::RangeInclusive};
+
+use indexmap::IndexSet;
+
+use super::{nfa_builder::NfaBuilder, Regex};
+use crate::nfa::Nfa;
+
+/// Identifies an expression stored in a [`RegexArena`]
+///
+/// IDs are only meaningful for the arena that created them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct RegexId(u32);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Node<L> {
+    Alt(usize),
+    Cat(usize),
+    Star(RegexId),
+    Repeat(RegexId, RangeInclusive<u32>),
+    Lit(L),
+}
+
+/// A borrowed view of an expression in a [`RegexArena`], mirroring the
+/// variants of [`Regex`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexRef<'a, L> {
+    Alt(&'a [RegexId]),
+    Cat(&'a [RegexId]),
+    Star(RegexId),
+    /// Between `start` and `end` (inclusive) repetitions of a regex
+    Repeat(RegexId, RangeInclusive<u32>),
+    Lit(&'a L),
+}
+
+/// A set of hash-consed regular expressions
+#[derive(Debug, Clone)]
+pub struct RegexArena<L> {
+    nodes: IndexSet<Node<L>>,
+    lists: IndexSet<Box<[RegexId]>>,
+    // Reused to collect operand lists before they are interned
+    scratch: Vec<RegexId>,
+}
+
+impl<L> Default for RegexArena<L> {
+    fn default() -> Self {
+        Self {
+            nodes: IndexSet::new(),
+            lists: IndexSet::new(),
+            scratch: vec![],
+        }
+    }
+}
+
+impl<L> RegexArena<L> {
+    /// Construct a new empty arena
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self { Self::default() }
+
+    /// Get the number of distinct expressions stored in the arena
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize { self.nodes.len() }
+
+    /// Returns true if the arena contains no expressions
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.nodes.is_empty() }
+
+    /// Look up an expression by its ID, returning `None` if it was not
+    /// created by this arena
+    #[must_use]
+    pub fn get(&self, id: RegexId) -> Option<RegexRef<'_, L>> {
+        let list = |l: usize| &*self.lists[l];
+
+        Some(match self.nodes.get_index(id.0.try_into().ok()?)? {
+            Node::Alt(l) => RegexRef::Alt(list(*l)),
+            Node::Cat(l) => RegexRef::Cat(list(*l)),
+            Node::Star(r) => RegexRef::Star(*r),
+            Node::Repeat(r, range) => RegexRef::Repeat(*r, range.clone()),
+            Node::Lit(l) => RegexRef::Lit(l),
+        })
+    }
+
+    /// Convert an expression back into tree form, returning `None` if it was
+    /// not created by this arena
+    #[must_use]
+    pub fn to_regex(&self, id: RegexId) -> Option<Regex<L>>
+    where L: Clone {
+        let tree = |ids: &[RegexId]| ids.iter().map(|&r| self.to_regex(r)).collect::<Option<_>>();
+
+        Some(match self.get(id)? {
+            RegexRef::Alt(a) => Regex::Alt(tree(a)?),
+            RegexRef::Cat(c) => Regex::Cat(tree(c)?),
+            RegexRef::Star(r) => Regex::Star(self.to_regex(r)?.into()),
+            RegexRef::Repeat(r, range) => Regex::Repeat(self.to_regex(r)?.into(), range),
+            RegexRef::Lit(l) => Regex::Lit(l.clone()),
+        })
+    }
+
+    /// Compile a set of expressions into an NFA accepting each with the
+    /// token paired with it
+    ///
+    /// This is equivalent to compiling the tree form of each expression with
+    /// [`RegexBag`](super::RegexBag), but shared subexpressions are read in
+    /// place rather than cloned.
+    ///
+    /// # Panics
+    /// This method panics if any of the given IDs were not created by this
+    /// arena.
+    #[must_use]
+    pub fn compile<I: Ord + Clone, T: Ord>(
+        &self,
+        roots: impl IntoIterator<Item = (RegexId, T)>,
+    ) -> Nfa<I, u64, (), T>
+    where
+        for<'a> &'a L: IntoIterator<Item = &'a I>,
+    {
+        NfaBuilder::build_arena(self, roots).finish()
+    }
+}
+
+impl<L: Hash + Eq> RegexArena<L> {
+    fn intern(&mut self, node: Node<L>) -> RegexId {
+        let (id, _) = self.nodes.insert_full(node);
+        RegexId(id.try_into().expect("Too many expressions in regex arena"))
+    }
+
+    fn intern_list(&mut self, ids: impl IntoIterator<Item = RegexId>) -> usize {
+        let mut scratch = mem::take(&mut self.scratch);
+        scratch.clear();
+        scratch.extend(ids);
+
+        let list = self
+            .lists
+            .get_index_of(&*scratch)
+            .unwrap_or_else(|| self.lists.insert_full(scratch.as_slice().into()).0);
+
+        self.scratch = scratch;
+        list
+    }
+
+    /// Add the expression matching nothing, equivalent to [`Regex::BOTTOM`]
+    #[inline]
+    pub fn bottom(&mut self) -> RegexId { self.alt([]) }
+
+    /// Add the expression matching only the empty string, equivalent to
+    /// [`Regex::TOP`]
+    #[inline]
+    pub fn top(&mut self) -> RegexId { self.cat([]) }
+
+    /// Add an expression matching any of the given expressions
+    pub fn alt(&mut self, alts: impl IntoIterator<Item = RegexId>) -> RegexId {
+        let list = self.intern_list(alts);
+        self.intern(Node::Alt(list))
+    }
+
+    /// Add an expression matching each of the given expressions in sequence
+    pub fn cat(&mut self, cats: impl IntoIterator<Item = RegexId>) -> RegexId {
+        let list = self.intern_list(cats);
+        self.intern(Node::Cat(list))
+    }
+
+    /// Add an expression matching any number of repetitions of an expression
+    #[inline]
+    pub fn star(&mut self, re: RegexId) -> RegexId { self.intern(Node::Star(re)) }
+
+    /// Add an expression matching at least `min` repetitions of `re`, and at
+    /// most `max` if it is given, as with [`Regex::repeat`]
+    pub fn repeat(&mut self, re: RegexId, min: u32, max: Option<u32>) -> RegexId {
+        if let Some(max) = max {
+            return self.intern(Node::Repeat(re, min..=max));
+        }
+
+        let head = self.intern(Node::Repeat(re, min..=min));
+        let tail = self.star(re);
+        self.cat([head, tail])
+    }
+
+    /// Add an expression matching a literal
+    #[inline]
+    pub fn lit(&mut self, lit: L) -> RegexId { self.intern(Node::Lit(lit)) }
+
+    /// Add an expression given in tree form, returning the ID of its root
+    pub fn insert(&mut self, re: Regex<L>) -> RegexId {
+        match re {
+            Regex::Alt(a) => {
+                let ids: Vec<_> = a.into_iter().map(|r| self.insert(r)).collect();
+                self.alt(ids)
+            },
+            Regex::Cat(c) => {
+                let ids: Vec<_> = c.into_iter().map(|r| self.insert(r)).collect();
+                self.cat(ids)
+            },
+            Regex::Star(r) => {
+                let id = self.insert(*r);
+                self.star(id)
+            },
+            Regex::Repeat(r, range) => {
+                let id = self.insert(*r);
+                self.intern(Node::Repeat(id, range))
+            },
+            Regex::Lit(l) => self.lit(l),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::{RegexArena, RegexRef};
+    use crate::{dfa::Dfa, re::Regex};
+
+    fn regex() -> impl Strategy<Value = Regex<Vec<char>>> {
+        let leaf =
+            prop::collection::vec(prop::sample::select(&['a', 'b'][..]), 0..3).prop_map(Regex::Lit);
+
+        leaf.prop_recursive(3, 12, 3, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..3).prop_map(Regex::Alt),
+                prop::collection::vec(inner.clone(), 0..3).prop_map(Regex::Cat),
+                inner.clone().prop_map(|r| Regex::Star(r.into())),
+                (inner, 0..3_u32, 0..3_u32)
+                    .prop_map(|(r, a, b)| Regex::Repeat(r.into(), a.min(b)..=a.max(b))),
+            ]
+        })
+    }
+
+    fn determinize(nfa: crate::nfa::Nfa<char, u64, (), ()>) -> Dfa<char, u64, (), ()> {
+        let mut nfa = nfa;
+        nfa.simplify();
+        let (dfa, _) = nfa.compile().copied().atomize_nodes();
+        dfa.map_token(|_| ())
+    }
+
+    #[test]
+    fn interning() {
+        let mut arena = RegexArena::new();
+        let ab = |arena: &mut RegexArena<Vec<char>>| {
+            let a = arena.lit(vec!['a']);
+            let b = arena.lit(vec!['b']);
+            arena.alt([a, b])
+        };
+
+        let x = ab(&mut arena);
+        let y = ab(&mut arena);
+        assert_eq!(x, y);
+        assert_eq!(arena.len(), 3);
+
+        let cat = arena.cat([x, y, x]);
+        assert_eq!(arena.get(cat), Some(RegexRef::Cat(&[x, x, x])));
+        assert_eq!(arena.len(), 4);
+
+        let top = arena.top();
+        assert_ne!(arena.bottom(), top);
+        assert!(RegexArena::<Vec<char>>::new().get(cat).is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip(re in regex()) {
+            let mut arena = RegexArena::new();
+            let id = arena.insert(re.clone());
+
+            prop_assert_eq!(
+                format!("{:?}", arena.to_regex(id).unwrap()),
+                format!("{re:?}")
+            );
+            prop_assert_eq!(arena.insert(re.clone()), id);
+
+            let lhs = determinize(re.compile());
+            let rhs = determinize(arena.compile([(id, ())]));
+            prop_assert!(lhs.difference(&rhs).shortest_accepted().is_none());
+            prop_assert!(rhs.difference(&lhs).shortest_accepted().is_none());
+        }
+    }
+}
@@ -0,0 +1,358 @@
+//! Direct DFA construction from regular expressions using Brzozowski
+//! derivatives
+//!
+//! The derivative of a language with respect to a symbol is the set of
+//! suffixes of its strings starting with that symbol.  Repeatedly taking
+//! derivatives of a regex yields the states of a DFA for it, but the number of
+//! distinct derivatives is only finite up to the associativity, commutativity
+//! and idempotence of alternation.  To guarantee the construction terminates,
+//! every [`Term`] is kept in a canonical form modulo those identities, along
+//! with the unit and annihilator laws for ∅ and ε, so that equivalent
+//! derivatives compare structurally equal.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use super::Regex;
+use crate::{dfa::Dfa, free::Free};
+
+/// A regular expression in canonical form
+///
+/// The following invariants are upheld by the smart constructors
+/// [`alt`](Self::alt), [`cat`](Self::cat) and [`star`](Self::star):
+///  - alternations and concatenations never directly contain a term of the
+///    same kind, and never have exactly one element
+///  - alternations are sorted and contain no duplicates or ∅
+///  - concatenations contain no ε, and are ∅ if any element is ∅
+///  - a Kleene star never contains ∅, ε, another star, or an alternation
+///    containing ε
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Term<I> {
+    Alt(Vec<Term<I>>),
+    Cat(Vec<Term<I>>),
+    Star(Box<Term<I>>),
+    Sym(I),
+}
+
+impl<I> Term<I> {
+    /// The term matching nothing, ∅
+    pub const BOTTOM: Term<I> = Term::Alt(Vec::new());
+    /// The term matching only the empty string, ε
+    pub const TOP: Term<I> = Term::Cat(Vec::new());
+
+    /// Returns true if this term matches the empty string
+    #[must_use]
+    pub fn nullable(&self) -> bool {
+        match self {
+            Self::Alt(a) => a.iter().any(Self::nullable),
+            Self::Cat(c) => c.iter().all(Self::nullable),
+            Self::Star(_) => true,
+            Self::Sym(_) => false,
+        }
+    }
+
+    /// Returns true if this term is ∅
+    #[inline]
+    #[must_use]
+    pub fn is_bottom(&self) -> bool { matches!(self, Self::Alt(a) if a.is_empty()) }
+
+    /// Returns true if this term is ε
+    #[inline]
+    #[must_use]
+    pub fn is_top(&self) -> bool { matches!(self, Self::Cat(c) if c.is_empty()) }
+
+    /// Collect every symbol appearing in this term
+    pub fn alphabet<'a>(&'a self, out: &mut BTreeSet<&'a I>)
+    where I: Ord {
+        match self {
+            Self::Alt(v) | Self::Cat(v) => {
+                for t in v {
+                    t.alphabet(out);
+                }
+            },
+            Self::Star(t) => t.alphabet(out),
+            Self::Sym(s) => {
+                out.insert(s);
+            },
+        }
+    }
+}
+
+impl<I: Ord> Term<I> {
+    /// Construct the canonical alternation of the given terms
+    #[must_use]
+    pub fn alt(terms: impl IntoIterator<Item = Self>) -> Self {
+        let mut out = vec![];
+
+        for term in terms {
+            match term {
+                Self::Alt(a) => out.extend(a),
+                t => out.push(t),
+            }
+        }
+
+        out.sort_unstable();
+        out.dedup();
+
+        if out.len() == 1 {
+            out.pop().unwrap_or_else(|| unreachable!())
+        } else {
+            Self::Alt(out)
+        }
+    }
+
+    /// Construct the canonical concatenation of the given terms
+    #[must_use]
+    pub fn cat(terms: impl IntoIterator<Item = Self>) -> Self {
+        let mut out = vec![];
+
+        for term in terms {
+            match term {
+                t if t.is_bottom() => return Self::BOTTOM,
+                Self::Cat(c) => out.extend(c),
+                t => out.push(t),
+            }
+        }
+
+        if out.len() == 1 {
+            out.pop().unwrap_or_else(|| unreachable!())
+        } else {
+            Self::Cat(out)
+        }
+    }
+
+    /// Construct the canonical Kleene star of the given term
+    #[must_use]
+    pub fn star(term: Self) -> Self {
+        match term {
+            t if t.is_bottom() || t.is_top() => Self::TOP,
+            Self::Star(t) => Self::Star(t),
+            // (ε|r)* = r*
+            Self::Alt(a) if a.iter().any(Self::is_top) => {
+                Self::star(Self::alt(a.into_iter().filter(|t| !t.is_top())))
+            },
+            t => Self::Star(t.into()),
+        }
+    }
+}
+
+impl<I: Clone + Ord> Term<I> {
+    /// Compute the derivative of this term with respect to the given symbol
+    #[must_use]
+    pub fn derive(&self, sym: &I) -> Self {
+        match self {
+            Self::Alt(a) => Self::alt(a.iter().map(|t| t.derive(sym))),
+            Self::Cat(c) => {
+                let mut alts = vec![];
+
+                for (i, head) in c.iter().enumerate() {
+                    alts.push(Self::cat(
+                        [head.derive(sym)]
+                            .into_iter()
+                            .chain(c[i + 1..].iter().cloned()),
+                    ));
+
+                    if !head.nullable() {
+                        break;
+                    }
+                }
+
+                Self::alt(alts)
+            },
+            Self::Star(t) => Self::cat([t.derive(sym), self.clone()]),
+            Self::Sym(s) => {
+                if s == sym {
+                    Self::TOP
+                } else {
+                    Self::BOTTOM
+                }
+            },
+        }
+    }
+
+    /// Construct a DFA recognizing this term by exploring its derivatives
+    ///
+    /// Each state of the output corresponds to one distinct canonical
+    /// derivative.  The state for ∅ is omitted, so any input without an
+    /// outgoing edge should be treated as a rejection.
+    #[must_use]
+    pub fn compile(&self) -> Dfa<I, u64, (), ()> {
+        let mut alphabet = BTreeSet::new();
+        self.alphabet(&mut alphabet);
+
+        let mut free = Free::default();
+        let start = free.fresh();
+        let mut ids = BTreeMap::new();
+        let mut states = BTreeMap::new();
+        #[expect(
+            clippy::zero_sized_map_values,
+            reason = "Dfa with unit token type necessarily creates a BTreeMap representing a set"
+        )]
+        let mut accept = BTreeMap::new();
+        let mut q = VecDeque::new();
+
+        ids.insert(self.clone(), start);
+        q.push_back((self.clone(), start));
+
+        while let Some((term, id)) = q.pop_front() {
+            let mut edges = BTreeMap::new();
+
+            for &sym in &alphabet {
+                let next = term.derive(sym);
+
+                if next.is_bottom() {
+                    continue;
+                }
+
+                let next_id = *ids.entry(next).or_insert_with_key(|next| {
+                    let next_id = free.fresh();
+                    q.push_back((next.clone(), next_id));
+                    next_id
+                });
+
+                edges.insert(sym.clone(), (next_id, ()));
+            }
+
+            if term.nullable() {
+                accept.insert(id, ());
+            }
+
+            states.insert(id, edges);
+        }
+
+        Dfa::new(states, start, accept)
+    }
+}
+
+impl<L: IntoIterator> From<Regex<L>> for Term<L::Item>
+where L::Item: Clone + Ord
+{
+    fn from(re: Regex<L>) -> Self {
+        match re {
+            Regex::Alt(a) => Self::alt(a.into_iter().map(Into::into)),
+            Regex::Cat(c) => Self::cat(c.into_iter().map(Into::into)),
+            Regex::Star(r) => Self::star((*r).into()),
+            Regex::Repeat(r, range) => {
+                let (min, max) = range.into_inner();
+                if max < min {
+                    return Self::BOTTOM;
+                }
+
+                let term = Self::from(*r);
+                // r{0,k} = (ε|r(ε|r(...)))
+                let optional = (min..max).fold(Self::TOP, |rest, _| {
+                    Self::alt([Self::TOP, Self::cat([term.clone(), rest])])
+                });
+
+                Self::cat((0..min).map(|_| term.clone()).chain([optional]))
+            },
+            Regex::Lit(l) => Self::cat(l.into_iter().map(Self::Sym)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::Term;
+    use crate::{
+        dfa::Dfa,
+        re::{
+            run::{Anchors, Matcher},
+            Regex,
+        },
+    };
+
+    fn lit(s: &str) -> Regex<Vec<char>> { Regex::Lit(s.chars().collect()) }
+
+    fn matches(dfa: &Dfa<char, u64, (), ()>, s: &str) -> bool {
+        let mut m = Matcher::new(dfa, Anchors {
+            start: true,
+            end: true,
+        });
+        m.feed(s.chars());
+        m.finish().is_some()
+    }
+
+    fn regex() -> impl Strategy<Value = Regex<Vec<char>>> {
+        let leaf = prop::collection::vec(prop::sample::select(&['a', 'b', 'c'][..]), 0..3)
+            .prop_map(Regex::Lit);
+
+        leaf.prop_recursive(4, 24, 3, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..3).prop_map(Regex::Alt),
+                prop::collection::vec(inner.clone(), 0..3).prop_map(Regex::Cat),
+                inner.clone().prop_map(|r| Regex::Star(r.into())),
+                (inner, 0_u32..3, 0_u32..3)
+                    .prop_map(|(r, min, extra)| Regex::Repeat(r.into(), min..=min + extra)),
+            ]
+        })
+    }
+
+    #[test]
+    fn canonical_identities() {
+        let a = || Term::Sym('a');
+        let b = || Term::Sym('b');
+
+        assert_eq!(
+            Term::alt([b(), Term::alt([a(), Term::BOTTOM]), a()]),
+            Term::Alt(vec![a(), b()])
+        );
+        assert_eq!(
+            Term::cat([Term::TOP, Term::cat([a(), b()]), Term::TOP]),
+            Term::Cat(vec![a(), b()])
+        );
+        assert_eq!(Term::cat([a(), Term::BOTTOM, b()]), Term::BOTTOM);
+        assert_eq!(Term::star(Term::star(a())), Term::star(a()));
+        assert_eq!(Term::star(Term::alt([Term::TOP, a()])), Term::star(a()));
+        assert_eq!(Term::<char>::star(Term::BOTTOM), Term::TOP);
+    }
+
+    #[test]
+    fn derivative_states_bounded() {
+        // (a|b)*abb has a minimal DFA of 4 states
+        let re = Regex::Cat(vec![
+            Regex::Star(Regex::Alt(vec![lit("a"), lit("b")]).into()),
+            lit("abb"),
+        ]);
+        let dfa = Term::from(re).compile();
+
+        assert_eq!(dfa.states().count(), 4);
+        assert!(matches(&dfa, "babaabb"));
+        assert!(!matches(&dfa, "abba"));
+    }
+
+    #[test]
+    fn nested_stars_terminate() {
+        let re = Regex::Star(
+            Regex::Cat(vec![
+                Regex::Star(Regex::Star(lit("ab").into()).into()),
+                Regex::Star(Regex::Alt(vec![lit("a"), Regex::TOP]).into()),
+            ])
+            .into(),
+        );
+        let dfa = Term::from(re).compile();
+
+        assert!(dfa.states().count() <= 5);
+        assert!(matches(&dfa, ""));
+        assert!(matches(&dfa, "aaabab"));
+        assert!(!matches(&dfa, "b"));
+    }
+
+    proptest! {
+        #[test]
+        fn matches_thompson_dfa(
+            re in regex(),
+            inputs in prop::collection::vec("[abc]{0,6}", 1..16),
+        ) {
+            let nfa = re.clone().compile();
+            let (expected, _) = nfa.compile().copied().atomize_nodes::<u64>();
+            let expected = expected.map_token(|_| ());
+            let actual = Term::from(re).compile();
+
+            for input in &inputs {
+                prop_assert_eq!(matches(&expected, input), matches(&actual, input), "{:?}", input);
+            }
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use std::mem;
 
-use super::Regex;
+use super::{
+    arena::{RegexArena, RegexId, RegexRef},
+    Regex,
+};
 use crate::{free::Free, nfa::Nfa};
 
 pub struct NfaBuilder<I, T> {
@@ -17,7 +20,7 @@ impl<I: Ord, T: Ord> NfaBuilder<I, T> {
         Self { nfa, free }
     }
 
-    pub fn build<B: IntoIterator<Item = (Regex<L>, T)>, L: IntoIterator<Item = I>>(
+    pub fn build<B: IntoIterator<Item = (Regex<L>, T)>, L: IntoIterator<Item = I> + Clone>(
         tok_bag: B,
     ) -> Self {
         let mut me = Self::new();
@@ -29,6 +32,23 @@ impl<I: Ord, T: Ord> NfaBuilder<I, T> {
         me
     }
 
+    pub fn build_arena<B: IntoIterator<Item = (RegexId, T)>, L>(
+        arena: &RegexArena<L>,
+        roots: B,
+    ) -> Self
+    where
+        I: Clone,
+        for<'a> &'a L: IntoIterator<Item = &'a I>,
+    {
+        let mut me = Self::new();
+        for (id, tok) in roots {
+            let accept = me.free.fresh();
+            assert!(me.nfa.insert_accept(accept, tok).is_none());
+            me.build_arena_in(arena, id, *me.nfa.start(), accept);
+        }
+        me
+    }
+
     #[inline]
     fn fresh_node(&mut self) -> u64 {
         let fresh = self.free.fresh();
@@ -38,10 +58,16 @@ impl<I: Ord, T: Ord> NfaBuilder<I, T> {
 
     #[inline]
     fn connect(&mut self, from: u64, to: u64, by: Option<I>) {
-        assert!(self.nfa.connect(&from, to, by, ()).is_none());
+        let prev = self.nfa.connect(&from, to, by, ());
+        assert!(prev.is_ok_and(|p| p.is_none()));
     }
 
-    fn build_in<L: IntoIterator<Item = I>>(&mut self, regex: Regex<L>, head: u64, tail: u64) {
+    fn build_in<L: IntoIterator<Item = I> + Clone>(
+        &mut self,
+        regex: Regex<L>,
+        head: u64,
+        tail: u64,
+    ) {
         match regex {
             Regex::Alt(a) => {
                 for re in a {
@@ -68,12 +94,105 @@ impl<I: Ord, T: Ord> NfaBuilder<I, T> {
                 self.connect(head, tail, None);
                 self.connect(t, h, None);
             },
+            Regex::Repeat(r, range) => {
+                self.build_repeat_in(range, head, tail, |s, h, t| s.build_in((*r).clone(), h, t));
+            },
             Regex::Lit(l) => {
                 self.build_cat_in(l, head, tail, |s, i, h, t| s.connect(h, t, Some(i)));
             },
         }
     }
 
+    /// Build `start` copies of a regex in sequence, followed by `end - start`
+    /// optional copies
+    ///
+    /// Rather than nesting the optional copies as `(r(r)?)?`, each one may
+    /// skip directly to the shared tail, so the NFA grows linearly with the
+    /// number of repetitions.
+    fn build_repeat_in(
+        &mut self,
+        range: std::ops::RangeInclusive<u32>,
+        head: u64,
+        tail: u64,
+        f: impl Fn(&mut Self, u64, u64),
+    ) {
+        let (min, max) = range.into_inner();
+        if max < min {
+            return;
+        }
+
+        let mut h = head;
+        for i in 0..max {
+            if i >= min {
+                self.connect(h, tail, None);
+            }
+
+            let rh = self.fresh_node();
+            let rt = self.fresh_node();
+            f(self, rh, rt);
+            self.connect(h, rh, None);
+
+            h = if i + 1 == max {
+                tail
+            } else {
+                self.fresh_node()
+            };
+            self.connect(rt, h, None);
+        }
+
+        if max == 0 {
+            self.connect(head, tail, None);
+        }
+    }
+
+    fn build_arena_in<L>(&mut self, arena: &RegexArena<L>, id: RegexId, head: u64, tail: u64)
+    where
+        I: Clone,
+        for<'a> &'a L: IntoIterator<Item = &'a I>,
+    {
+        let regex = arena
+            .get(id)
+            .unwrap_or_else(|| panic!("Regex ID {id:?} not found in arena"));
+
+        match regex {
+            RegexRef::Alt(a) => {
+                for &re in a {
+                    let h = self.fresh_node();
+                    let t = self.fresh_node();
+
+                    self.build_arena_in(arena, re, h, t);
+                    self.connect(head, h, None);
+                    self.connect(t, tail, None);
+                }
+            },
+            RegexRef::Cat(c) => {
+                self.build_cat_in(c.iter().copied(), head, tail, |s, re, h, t| {
+                    s.build_arena_in(arena, re, h, t);
+                });
+            },
+            RegexRef::Star(r) => {
+                let h = self.fresh_node();
+                let t = self.fresh_node();
+
+                self.build_arena_in(arena, r, h, t);
+                self.connect(head, h, None);
+                self.connect(t, tail, None);
+                self.connect(head, tail, None);
+                self.connect(t, h, None);
+            },
+            RegexRef::Repeat(r, range) => {
+                self.build_repeat_in(range, head, tail, |s, h, t| {
+                    s.build_arena_in(arena, r, h, t);
+                });
+            },
+            RegexRef::Lit(l) => {
+                self.build_cat_in(l, head, tail, |s, i, h, t| {
+                    s.connect(h, t, Some(i.clone()));
+                });
+            },
+        }
+    }
+
     #[inline]
     fn build_cat_in<J: IntoIterator>(
         &mut self,
@@ -102,3 +221,85 @@ impl<I: Ord, T: Ord> NfaBuilder<I, T> {
     #[inline]
     pub fn finish(self) -> Nfa<I, u64, (), T> { self.nfa }
 }
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use crate::{dfa::Dfa, re::Regex};
+
+    fn compile(re: Regex<Vec<char>>) -> Dfa<char, u64, (), ()> {
+        let mut nfa = re.compile();
+        nfa.simplify();
+        let (dfa, _) = nfa.compile().copied().atomize_nodes();
+        dfa.map_token(|_| ())
+    }
+
+    fn equivalent(lhs: Regex<Vec<char>>, rhs: Regex<Vec<char>>) -> bool {
+        let (lhs, rhs) = (compile(lhs), compile(rhs));
+        lhs.difference(&rhs).shortest_accepted().is_none()
+            && rhs.difference(&lhs).shortest_accepted().is_none()
+    }
+
+    /// Expand a repetition into an alternation of every allowed count
+    fn naive(re: &Regex<Vec<char>>, min: u32, max: u32) -> Regex<Vec<char>> {
+        Regex::Alt(
+            (min..=max)
+                .map(|n| Regex::Cat((0..n).map(|_| re.clone()).collect()))
+                .collect(),
+        )
+    }
+
+    fn regex() -> impl Strategy<Value = Regex<Vec<char>>> {
+        let leaf =
+            prop::collection::vec(prop::sample::select(&['a', 'b'][..]), 0..3).prop_map(Regex::Lit);
+
+        leaf.prop_recursive(3, 12, 3, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..3).prop_map(Regex::Alt),
+                prop::collection::vec(inner.clone(), 0..3).prop_map(Regex::Cat),
+                inner.prop_map(|r| Regex::Star(r.into())),
+            ]
+        })
+    }
+
+    #[test]
+    fn repeat_edge_cases() {
+        let a = || Regex::Lit(vec!['a']);
+
+        assert!(equivalent(Regex::Repeat(a().into(), 0..=0), Regex::TOP));
+        assert!(equivalent(Regex::Repeat(a().into(), 1..=1), a()));
+        #[expect(clippy::reversed_empty_ranges, reason = "Testing empty ranges")]
+        let empty = Regex::Repeat(a().into(), 2..=1);
+        assert!(equivalent(empty, Regex::BOTTOM));
+        assert!(equivalent(
+            Regex::Repeat(Regex::TOP.into(), 0..=3),
+            Regex::TOP
+        ));
+        assert!(equivalent(
+            Regex::repeat(a(), 2, None),
+            Regex::Cat(vec![a(), a(), Regex::Star(a().into())])
+        ));
+    }
+
+    #[test]
+    fn repeat_grows_linearly() {
+        let re = Regex::Repeat(Regex::Lit(vec!['a', 'b']).into(), 10..=100);
+        let nfa = re.compile();
+        let nodes = (0_u64..10_000).take_while(|n| nfa.get(n).is_some()).count();
+
+        // Each copy of the body adds a constant number of nodes
+        assert!(nodes <= 100 * 5, "{nodes}");
+    }
+
+    proptest! {
+        #[test]
+        fn repeat_matches_naive(re in regex(), min in 0_u32..4, extra in 0_u32..4) {
+            let max = min + extra;
+            prop_assert!(equivalent(
+                Regex::Repeat(re.clone().into(), min..=max),
+                naive(&re, min, max),
+            ));
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Incremental matching of compiled regular expressions over streamed input
+
+use crate::dfa::Dfa;
+
+/// Anchoring constraints for a [`Matcher`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Anchors {
+    /// Require the match to begin at the start of the input
+    pub start: bool,
+    /// Require the match to end at the end of the input
+    pub end: bool,
+}
+
+/// A match found by a [`Matcher`], spanning the half-open range of input
+/// positions `start..end`
+#[derive(Debug, PartialEq, Eq)]
+pub struct Match<'a, T> {
+    pub start: usize,
+    pub end: usize,
+    pub token: &'a T,
+}
+
+impl<T> Clone for Match<'_, T> {
+    #[inline]
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T> Copy for Match<'_, T> {}
+
+/// The result of feeding input to a [`Matcher`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status<'a, T> {
+    /// More input is required to determine the result
+    NeedMore,
+    /// The leftmost-longest match has been found
+    Match(Match<'a, T>),
+    /// The input can no longer match
+    NoMatch,
+}
+
+/// A streaming leftmost-longest matcher driven by a DFA
+///
+/// Input is consumed one chunk at a time without being buffered.  Unanchored
+/// searches track one DFA state per distinct candidate start position, so
+/// memory use is bounded by the number of DFA states.
+#[derive(Debug)]
+pub struct Matcher<'a, I, N, T> {
+    dfa: &'a Dfa<I, N, (), T>,
+    anchors: Anchors,
+    pos: usize,
+    // Sorted by start position, with at most one entry per state
+    threads: Vec<(usize, N)>,
+    best: Option<Match<'a, T>>,
+    done: bool,
+}
+
+impl<'a, I: Ord, N: Copy + Ord, T> Matcher<'a, I, N, T> {
+    #[must_use]
+    pub fn new(dfa: &'a Dfa<I, N, (), T>, anchors: Anchors) -> Self {
+        Self {
+            dfa,
+            anchors,
+            pos: 0,
+            threads: vec![],
+            best: None,
+            done: false,
+        }
+    }
+
+    /// The number of inputs consumed so far
+    #[inline]
+    #[must_use]
+    pub fn position(&self) -> usize { self.pos }
+
+    /// The current result of the matcher, without consuming any input
+    #[must_use]
+    pub fn status(&self) -> Status<'a, T> {
+        if self.done {
+            self.best.map_or(Status::NoMatch, Status::Match)
+        } else {
+            Status::NeedMore
+        }
+    }
+
+    /// Consume a chunk of input, returning the result if it can be determined
+    ///
+    /// Once a result other than [`Status::NeedMore`] is returned, further input
+    /// is ignored.
+    pub fn feed<J: IntoIterator<Item = I>>(&mut self, chunk: J) -> Status<'a, T> {
+        for inp in chunk {
+            if self.done {
+                break;
+            }
+
+            self.step(&inp);
+        }
+
+        self.status()
+    }
+
+    /// Signal the end of input, returning the final match, if any
+    #[must_use]
+    pub fn finish(mut self) -> Option<Match<'a, T>> {
+        if self.done {
+            return self.best;
+        }
+
+        self.spawn();
+
+        if !self.anchors.end {
+            return self.best;
+        }
+
+        self.threads.iter().find_map(|&(start, state)| {
+            Some(Match {
+                start,
+                end: self.pos,
+                token: self.dfa.accept().get(&state)?,
+            })
+        })
+    }
+
+    fn spawn(&mut self) {
+        if self.best.is_some() || (self.anchors.start && self.pos > 0) {
+            return;
+        }
+
+        let start = *self.dfa.start();
+        if self.threads.iter().all(|&(_, n)| n != start) {
+            self.threads.push((self.pos, start));
+            self.record(self.pos, start);
+        }
+    }
+
+    fn record(&mut self, start: usize, state: N) {
+        if self.anchors.end {
+            return;
+        }
+
+        let Some(token) = self.dfa.accept().get(&state) else {
+            return;
+        };
+
+        let better = self.best.map_or(true, |b| {
+            start < b.start || (start == b.start && self.pos > b.end)
+        });
+
+        if better {
+            self.best = Some(Match {
+                start,
+                end: self.pos,
+                token,
+            });
+        }
+    }
+
+    fn step(&mut self, inp: &I) {
+        self.spawn();
+
+        let mut next: Vec<(usize, N)> = Vec::with_capacity(self.threads.len());
+        for &(start, state) in &self.threads {
+            let Some(&(to, ())) = self.dfa.get(&state).and_then(|n| n.get(inp)) else {
+                continue;
+            };
+
+            if next.iter().all(|&(_, n)| n != to) {
+                next.push((start, to));
+            }
+        }
+
+        self.pos += 1;
+        self.threads = next;
+
+        for i in 0..self.threads.len() {
+            let (start, state) = self.threads[i];
+            self.record(start, state);
+        }
+
+        if let Some(best) = self.best {
+            self.threads.retain(|&(s, _)| s <= best.start);
+        }
+
+        self.done = self.threads.is_empty() && (self.best.is_some() || self.anchors.start);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use super::{Anchors, Match, Matcher, Status};
+    use crate::{
+        dfa::Dfa,
+        re::{Regex, RegexBag},
+    };
+
+    fn dfa(toks: &[&'static str]) -> Dfa<char, u64, (), BTreeSet<&'static str>> {
+        let nfa = toks
+            .iter()
+            .map(|&t| (Regex::Lit(t.chars()), t))
+            .collect::<RegexBag<_, _>>()
+            .compile();
+        let (dfa, _) = nfa.compile().copied().atomize_nodes::<u64>();
+        dfa.map_token(|t| t.iter().map(|t| **t).collect())
+    }
+
+    fn span<T>(m: Match<'_, T>) -> (usize, usize) { (m.start, m.end) }
+
+    #[test]
+    fn longest_match_across_chunks() {
+        let dfa = &dfa(&["for", "foreach"]);
+        let mut m = Matcher::new(dfa, Anchors::default());
+        assert_eq!(m.feed("xxfo".chars()), Status::NeedMore);
+        assert_eq!(m.feed("re".chars()), Status::NeedMore);
+        let Status::Match(found) = m.feed("ach!".chars()) else {
+            panic!("expected a match");
+        };
+        assert_eq!(span(found), (2, 9));
+    }
+
+    #[test]
+    fn shorter_match_on_trap() {
+        let dfa = &dfa(&["for", "foreach"]);
+        let mut m = Matcher::new(dfa, Anchors::default());
+        let Status::Match(found) = m.feed("a fore!".chars()) else {
+            panic!("expected a match");
+        };
+        assert_eq!(span(found), (2, 5));
+    }
+
+    #[test]
+    fn start_anchor() {
+        let dfa = &dfa(&["for"]);
+        let anchors = Anchors {
+            start: true,
+            end: false,
+        };
+
+        let mut m = Matcher::new(dfa, anchors);
+        assert_eq!(m.feed("xfor".chars()), Status::NoMatch);
+
+        let mut m = Matcher::new(dfa, anchors);
+        assert_eq!(m.feed("fo".chars()), Status::NeedMore);
+        assert_eq!(m.finish(), None);
+    }
+
+    #[test]
+    fn end_anchor() {
+        let dfa = &dfa(&["each", "for"]);
+        let anchors = Anchors {
+            start: false,
+            end: true,
+        };
+
+        let mut m = Matcher::new(dfa, anchors);
+        assert_eq!(m.feed("for ea".chars()), Status::NeedMore);
+        assert_eq!(m.feed("ch".chars()), Status::NeedMore);
+        assert_eq!(m.finish().map(span), Some((4, 8)));
+
+        let mut m = Matcher::new(dfa, anchors);
+        assert_eq!(m.feed("for each ".chars()), Status::NeedMore);
+        assert_eq!(m.finish(), None);
+    }
+}
@@ -0,0 +1,214 @@
+//! Scanning of input for every match of a set of tagged patterns
+
+use std::collections::BTreeSet;
+
+use super::{
+    run::{Anchors, Match, Matcher, Status},
+    RegexBag,
+};
+use crate::dfa::Dfa;
+
+/// A compiled set of tagged patterns, reporting every non-overlapping
+/// leftmost-longest match in an input
+///
+/// This is a regex-based analogue of Aho-Corasick: all patterns are searched
+/// for in a single pass per match.  Each match is tagged with every pattern
+/// that matches the same span.
+#[derive(Debug)]
+pub struct MultiScanner<I, T> {
+    dfa: Dfa<I, u64, (), BTreeSet<T>>,
+}
+
+impl<I: Copy + Ord, T: Clone + Ord + std::hash::Hash> MultiScanner<I, T> {
+    /// Compile a scanner from a bag of tagged patterns
+    #[must_use]
+    pub fn new<L: IntoIterator<Item = I> + Clone>(bag: RegexBag<L, T>) -> Self {
+        let mut nfa = bag.compile();
+        nfa.simplify();
+        let (dfa, _) = nfa.compile().copied().atomize_nodes::<u64>();
+
+        Self {
+            dfa: dfa.map_token(|t| t.iter().map(|&t| t.clone()).collect()),
+        }
+    }
+}
+
+impl<I: Copy + Ord, T> MultiScanner<I, T> {
+    /// Get the DFA recognizing the scanner's patterns
+    #[inline]
+    #[must_use]
+    pub fn dfa(&self) -> &Dfa<I, u64, (), BTreeSet<T>> { &self.dfa }
+
+    /// Find every non-overlapping match in the input, from left to right
+    ///
+    /// Empty matches are skipped.
+    #[inline]
+    #[must_use]
+    pub fn scan<'a>(&'a self, input: &'a [I]) -> Scan<'a, I, T> { Scan::new(self, input, false) }
+
+    /// Split the input into consecutive matches starting from its beginning,
+    /// stopping at the first position where no pattern matches
+    ///
+    /// Comparing the end of the last match to the length of the input tells
+    /// whether the entire input was consumed.
+    #[inline]
+    #[must_use]
+    pub fn scan_anchored<'a>(&'a self, input: &'a [I]) -> Scan<'a, I, T> {
+        Scan::new(self, input, true)
+    }
+}
+
+/// Iterator over the matches found by a [`MultiScanner`]
+#[derive(Debug)]
+pub struct Scan<'a, I, T> {
+    dfa: &'a Dfa<I, u64, (), BTreeSet<T>>,
+    input: &'a [I],
+    anchored: bool,
+    pos: usize,
+    done: bool,
+}
+
+impl<'a, I, T> Scan<'a, I, T> {
+    fn new(scanner: &'a MultiScanner<I, T>, input: &'a [I], anchored: bool) -> Self {
+        Self {
+            dfa: &scanner.dfa,
+            input,
+            anchored,
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<'a, I: Copy + Ord, T> Iterator for Scan<'a, I, T> {
+    type Item = Match<'a, BTreeSet<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done && self.pos <= self.input.len() {
+            let mut matcher = Matcher::new(self.dfa, Anchors {
+                start: self.anchored,
+                end: false,
+            });
+            let found = match matcher.feed(self.input[self.pos..].iter().copied()) {
+                Status::Match(m) => Some(m),
+                Status::NoMatch => None,
+                Status::NeedMore => matcher.finish(),
+            };
+
+            let Some(Match { start, end, token }) = found else {
+                break;
+            };
+            let (start, end) = (self.pos + start, self.pos + end);
+
+            if start == end {
+                if self.anchored {
+                    break;
+                }
+
+                self.pos = start + 1;
+                continue;
+            }
+
+            self.pos = end;
+            return Some(Match { start, end, token });
+        }
+
+        self.done = true;
+        None
+    }
+}
+
+impl<I: Copy + Ord, T> std::iter::FusedIterator for Scan<'_, I, T> {}
+
+#[cfg(test)]
+mod test {
+    use super::MultiScanner;
+    use crate::re::{syntax, Regex, RegexBag};
+
+    fn scanner(pats: &[(&str, u8)]) -> MultiScanner<char, u8> {
+        MultiScanner::new(
+            pats.iter()
+                .map(|&(p, t)| (syntax::parse(p).unwrap(), t))
+                .collect::<RegexBag<_, _>>(),
+        )
+    }
+
+    fn spans(
+        scanner: &MultiScanner<char, u8>,
+        input: &str,
+        anchored: bool,
+    ) -> Vec<(usize, usize, Vec<u8>)> {
+        let input: Vec<_> = input.chars().collect();
+        let scan = if anchored {
+            scanner.scan_anchored(&input)
+        } else {
+            scanner.scan(&input)
+        };
+
+        scan.map(|m| (m.start, m.end, m.token.iter().copied().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn leftmost_longest() {
+        let s = scanner(&[
+            ("for", 0),
+            ("foreach", 1),
+            ("(a|c|e|f|h|o|r)(a|c|e|f|h|o|r)*", 2),
+        ]);
+
+        assert_eq!(spans(&s, "for each foreach", false), [
+            (0, 3, vec![0, 2]),
+            (4, 8, vec![2]),
+            (9, 16, vec![1, 2]),
+        ]);
+    }
+
+    #[test]
+    fn non_overlapping() {
+        let s = scanner(&[("aba", 0)]);
+
+        assert_eq!(spans(&s, "ababa aba", false), [
+            (0, 3, vec![0]),
+            (6, 9, vec![0])
+        ]);
+    }
+
+    #[test]
+    fn skips_empty() {
+        let s = scanner(&[("x*", 0)]);
+
+        assert_eq!(spans(&s, "axxbx", false), [
+            (1, 3, vec![0]),
+            (4, 5, vec![0])
+        ]);
+    }
+
+    #[test]
+    fn anchored() {
+        let s = scanner(&[("(1|2|3)(1|2|3)*", 0), ("+", 1)]);
+
+        assert_eq!(spans(&s, "1+23", true), [
+            (0, 1, vec![0]),
+            (1, 2, vec![1]),
+            (2, 4, vec![0]),
+        ]);
+        assert_eq!(spans(&s, "1+ 2", true), [(0, 1, vec![0]), (1, 2, vec![1])]);
+        assert!(spans(&s, " 1", true).is_empty());
+    }
+
+    #[test]
+    fn literals() {
+        let bag: RegexBag<_, _> = [
+            (Regex::Lit("he".chars()), 'h'),
+            (Regex::Lit("she".chars()), 's'),
+        ]
+        .into_iter()
+        .collect();
+        let s = MultiScanner::new(bag);
+        let input: Vec<_> = "ushers".chars().collect();
+
+        let found: Vec<_> = s.scan(&input).map(|m| (m.start, m.end)).collect();
+        assert_eq!(found, [(1, 4)]);
+    }
+}
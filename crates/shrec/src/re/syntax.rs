@@ -1,3 +1,5 @@
+use std::iter::Peekable;
+
 use super::{Regex, RegexBag};
 use crate::dfa::Dfa;
 
@@ -7,6 +9,8 @@ pub enum Token {
     Star,
     LPar,
     RPar,
+    LBrace,
+    RBrace,
 }
 
 #[must_use]
@@ -16,13 +20,16 @@ pub fn token_re() -> RegexBag<[char; 1], Token> {
         (Regex::Lit(['*']), Token::Star),
         (Regex::Lit(['(']), Token::LPar),
         (Regex::Lit([')']), Token::RPar),
+        (Regex::Lit(['{']), Token::LBrace),
+        (Regex::Lit(['}']), Token::RBrace),
     ]
     .into()
 }
 
 #[must_use]
 pub fn token_dfa() -> Dfa<char, u64, (), Token> {
-    let non_dfa = token_re().compile();
+    let mut non_dfa = token_re().compile();
+    non_dfa.simplify();
     let (dfa, _states) = non_dfa.compile().copied().atomize_nodes::<u64>();
     dfa.try_map_token(|t| {
         let mut it = t.iter();
@@ -32,3 +39,178 @@ pub fn token_dfa() -> Dfa<char, u64, (), Token> {
     })
     .unwrap_or_else(|t| unreachable!("Found ambiguous tokens: {t:?}"))
 }
+
+/// An error encountered while parsing a regular expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    #[error("Unexpected {0:?} at position {1}")]
+    Unexpected(char, usize),
+    #[error("Unexpected end of pattern")]
+    Eof,
+    #[error("Invalid repetition bounds at position {0}")]
+    BadRepeat(usize),
+}
+
+type Input<'a> = Peekable<std::iter::Enumerate<std::str::Chars<'a>>>;
+
+/// Parse a regular expression over characters
+///
+/// Supports alternation (`a|b`), grouping (`(ab)`), Kleene star (`a*`),
+/// counted repetition (`a{2}`, `a{2,}` and `a{2,5}`), and escaping any
+/// special character with a backslash.
+pub fn parse(s: &str) -> Result<Regex<Vec<char>>, ParseError> {
+    let mut input = s.chars().enumerate().peekable();
+    let re = parse_alt(&mut input)?;
+
+    match input.next() {
+        Some((i, c)) => Err(ParseError::Unexpected(c, i)),
+        None => Ok(re),
+    }
+}
+
+fn parse_alt(input: &mut Input) -> Result<Regex<Vec<char>>, ParseError> {
+    let mut alts = vec![parse_cat(input)?];
+
+    while input.next_if(|&(_, c)| c == '|').is_some() {
+        alts.push(parse_cat(input)?);
+    }
+
+    Ok(if alts.len() == 1 {
+        alts.pop().unwrap_or_else(|| unreachable!())
+    } else {
+        Regex::Alt(alts)
+    })
+}
+
+fn parse_cat(input: &mut Input) -> Result<Regex<Vec<char>>, ParseError> {
+    let mut cat = vec![];
+
+    while let Some(&(i, c)) = input.peek() {
+        let mut atom = match c {
+            '|' | ')' => break,
+            '(' => {
+                input.next();
+                let inner = parse_alt(input)?;
+                match input.next() {
+                    Some((_, ')')) => inner,
+                    Some((i, c)) => return Err(ParseError::Unexpected(c, i)),
+                    None => return Err(ParseError::Eof),
+                }
+            },
+            '\\' => {
+                input.next();
+                Regex::Lit(vec![input.next().ok_or(ParseError::Eof)?.1])
+            },
+            '*' | '{' | '}' => return Err(ParseError::Unexpected(c, i)),
+            c => {
+                input.next();
+                Regex::Lit(vec![c])
+            },
+        };
+
+        loop {
+            if input.next_if(|&(_, c)| c == '*').is_some() {
+                atom = Regex::Star(atom.into());
+            } else if let Some((i, _)) = input.next_if(|&(_, c)| c == '{') {
+                let (min, max) = parse_bounds(input, i)?;
+                atom = Regex::repeat(atom, min, max);
+            } else {
+                break;
+            }
+        }
+
+        cat.push(atom);
+    }
+
+    Ok(if cat.len() == 1 {
+        cat.pop().unwrap_or_else(|| unreachable!())
+    } else {
+        Regex::Cat(cat)
+    })
+}
+
+fn parse_number(input: &mut Input, start: usize) -> Result<Option<u32>, ParseError> {
+    let mut num = None::<u32>;
+
+    while let Some((_, c)) = input.next_if(|&(_, c)| c.is_ascii_digit()) {
+        let digit = c.to_digit(10).unwrap_or_else(|| unreachable!());
+        num = Some(
+            num.unwrap_or(0)
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(digit))
+                .ok_or(ParseError::BadRepeat(start))?,
+        );
+    }
+
+    Ok(num)
+}
+
+/// Parse the bounds of a counted repetition, after its opening brace
+fn parse_bounds(input: &mut Input, start: usize) -> Result<(u32, Option<u32>), ParseError> {
+    let min = parse_number(input, start)?.ok_or(ParseError::BadRepeat(start))?;
+    let max = if input.next_if(|&(_, c)| c == ',').is_some() {
+        parse_number(input, start)?
+    } else {
+        Some(min)
+    };
+
+    match input.next() {
+        Some((_, '}')) => (),
+        Some((i, c)) => return Err(ParseError::Unexpected(c, i)),
+        None => return Err(ParseError::Eof),
+    }
+
+    if max.is_some_and(|m| m < min) {
+        return Err(ParseError::BadRepeat(start));
+    }
+
+    Ok((min, max))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, ParseError};
+    use crate::re::{
+        run::{Anchors, Matcher},
+        Regex,
+    };
+
+    fn matches(re: &Regex<Vec<char>>, s: &str) -> bool {
+        let mut nfa = re.clone().compile();
+        nfa.simplify();
+        let (dfa, _) = nfa.compile().copied().atomize_nodes::<u64>();
+        let dfa = dfa.map_token(|_| ());
+
+        let mut m = Matcher::new(&dfa, Anchors {
+            start: true,
+            end: true,
+        });
+        m.feed(s.chars());
+        m.finish().is_some()
+    }
+
+    #[test]
+    fn repetition() {
+        let re = parse("a(bc){1,2}d{2,}|x{3}").unwrap();
+
+        for s in ["abcdd", "abcbcddd", "xxx"] {
+            assert!(matches(&re, s), "{s:?}");
+        }
+        for s in ["add", "abcbcbcdd", "abcd", "xx", "xxxx"] {
+            assert!(!matches(&re, s), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn escapes_and_errors() {
+        let re = parse(r"\{\*").unwrap();
+        assert!(matches(&re, "{*"));
+
+        assert_eq!(parse("a{3,2}").unwrap_err(), ParseError::BadRepeat(1));
+        assert_eq!(parse("a{,2}").unwrap_err(), ParseError::BadRepeat(1));
+        assert_eq!(parse("a{2").unwrap_err(), ParseError::Eof);
+        assert_eq!(parse("(a").unwrap_err(), ParseError::Eof);
+        assert_eq!(parse("a)").unwrap_err(), ParseError::Unexpected(')', 1));
+        assert_eq!(parse("*a").unwrap_err(), ParseError::Unexpected('*', 0));
+    }
+}
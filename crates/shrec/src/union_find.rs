@@ -1,13 +1,34 @@
 //! A disjoint-set data structure and relevant support types
+//!
+//! Nodes are identified by consecutive IDs starting at zero, allocated by
+//! [`UnionFind::put`].  Each node belongs to exactly one partition, identified
+//! by the ID of its root node as returned by [`UnionFind::find`].  Root IDs
+//! are only stable until the next union, so they should not be stored across
+//! calls to [`UnionFind::union`].
+//!
+//! [`RangedUnionFind`] extends this with the ability to merge every node in a
+//! contiguous range of IDs at once, and [`AtomicUnionFind`] allows lookups and
+//! unions from multiple threads at once without a lock.
 
-use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+mod atomic;
+
+pub use atomic::AtomicUnionFind;
 
 /// Error indicating a node ID passed to a [`UnionFind`] operation does not
 /// exist.
-#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
 #[error("No disjoint-set node found with ID {0}")]
 pub struct NoNode(usize);
 
+impl NoNode {
+    /// Get the node ID that could not be found
+    #[inline]
+    #[must_use]
+    pub fn id(self) -> usize { self.0 }
+}
+
 #[derive(Debug)]
 struct UnionFindNode {
     parent: usize,
@@ -15,11 +36,27 @@ struct UnionFindNode {
 }
 
 /// A disjoint-set data structure
+///
+/// Unions are performed by size and lookups compress the paths they traverse,
+/// so any sequence of operations runs in near-constant amortized time per
+/// operation.
 #[derive(Debug, Default)]
 pub struct UnionFind(Vec<UnionFindNode>);
 
 impl UnionFind {
-    /// Add a new node to the union-find, returning its ID
+    /// Get the number of nodes in the union-find
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize { self.0.len() }
+
+    /// Returns true if the union-find contains no nodes
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Add a new node to the union-find in its own partition, returning its ID
+    ///
+    /// IDs are allocated consecutively starting from zero.
     pub fn put(&mut self) -> usize {
         let key = self.0.len();
         self.0.push(UnionFindNode {
@@ -54,6 +91,9 @@ impl UnionFind {
     /// Perform the in-place union of the partitions containing the two given
     /// node IDs
     ///
+    /// Returns the root ID of the merged partition, or `None` if both nodes
+    /// were already in the same partition.
+    ///
     /// # Errors
     /// This method first checks if both node IDs are valid, returning an error
     /// if either cannot be found.
@@ -71,13 +111,15 @@ impl UnionFind {
             b_rank = self.0.get_unchecked(b).rank;
         }
 
-        match a.cmp(&b) {
-            Ordering::Equal => return Ok(None),
-            Ordering::Greater if a_rank <= b_rank => {
-                std::mem::swap(&mut a, &mut b);
-                std::mem::swap(&mut a_rank, &mut b_rank);
-            },
-            Ordering::Less | Ordering::Greater => (),
+        if a == b {
+            return Ok(None);
+        }
+
+        // Attach the smaller partition to the larger one, preferring the lower
+        // ID as the root when both are the same size
+        if (a_rank, b) < (b_rank, a) {
+            std::mem::swap(&mut a, &mut b);
+            std::mem::swap(&mut a_rank, &mut b_rank);
         }
 
         debug_assert!((a_rank, b) > (b_rank, a));
@@ -93,3 +135,206 @@ impl UnionFind {
         Ok(Some(a))
     }
 }
+
+/// A disjoint-set data structure supporting the union of contiguous ranges of
+/// node IDs
+///
+/// Merging a range of `n` nodes with [`union_range`](Self::union_range) takes
+/// near-constant amortized time rather than the `n - 1` unions it would take
+/// with a plain [`UnionFind`], as each pair of adjacent nodes is only ever
+/// linked once.  This is useful for, e.g., merging runs of states or input
+/// classes that are known to be equivalent.
+#[derive(Debug, Default)]
+pub struct RangedUnionFind {
+    sets: UnionFind,
+    /// For each node, the ID of a node to its right such that every node in
+    /// between is known to be in the same partition, compressed like the
+    /// parent pointers of a union-find.  Nodes pointing to themselves have not
+    /// been linked to their successor.
+    next: Vec<usize>,
+}
+
+impl RangedUnionFind {
+    /// Construct a new union-find containing `len` nodes, each in its own
+    /// partition
+    #[must_use]
+    pub fn new(len: usize) -> Self {
+        let mut uf = Self::default();
+        for _ in 0..len {
+            uf.put();
+        }
+        uf
+    }
+
+    /// Get the number of nodes in the union-find
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize { self.sets.len() }
+
+    /// Returns true if the union-find contains no nodes
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.sets.is_empty() }
+
+    /// Add a new node to the union-find in its own partition, returning its ID
+    ///
+    /// See [`UnionFind::put`].
+    pub fn put(&mut self) -> usize {
+        let key = self.sets.put();
+        debug_assert_eq!(key, self.next.len());
+        self.next.push(key);
+        key
+    }
+
+    /// Find the partition root ID for the given node ID
+    ///
+    /// See [`UnionFind::find`].
+    ///
+    /// # Errors
+    /// This method returns an error if no node with the given ID exists.
+    #[inline]
+    pub fn find(&mut self, key: usize) -> Result<usize, NoNode> { self.sets.find(key) }
+
+    /// Perform the in-place union of the partitions containing the two given
+    /// node IDs
+    ///
+    /// See [`UnionFind::union`].
+    ///
+    /// # Errors
+    /// This method returns an error if either node ID cannot be found.
+    #[inline]
+    pub fn union(&mut self, a: usize, b: usize) -> Result<Option<usize>, NoNode> {
+        self.sets.union(a, b)
+    }
+
+    /// Find the last node of the run of linked nodes containing `key`, and
+    /// compress the path to it
+    fn run_end(&mut self, key: usize) -> usize {
+        let mut end = key;
+        while self.next[end] != end {
+            end = self.next[end];
+        }
+
+        let mut curr = key;
+        while curr != end {
+            curr = std::mem::replace(&mut self.next[curr], end);
+        }
+
+        end
+    }
+
+    /// Merge the partitions containing every node ID in the given range
+    ///
+    /// Returns the root ID of the merged partition, or `None` if the range is
+    /// empty.
+    ///
+    /// # Errors
+    /// This method returns an error if the range extends past the last node,
+    /// in which case no partitions are modified.
+    pub fn union_range(&mut self, range: impl RangeBounds<usize>) -> Result<Option<usize>, NoNode> {
+        let start = match range.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e.saturating_add(1),
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => self.len(),
+        };
+
+        if start >= end {
+            return Ok(None);
+        }
+
+        if end > self.len() {
+            return Err(NoNode(end - 1));
+        }
+
+        let mut curr = self.run_end(start);
+        while curr + 1 < end {
+            self.sets
+                .union(curr, curr + 1)
+                .unwrap_or_else(|_| unreachable!());
+            self.next[curr] = curr + 1;
+            curr = self.run_end(curr + 1);
+        }
+
+        self.sets.find(start).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::{RangedUnionFind, UnionFind};
+
+    const LEN: usize = 64;
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Union(usize, usize),
+        Range(usize, usize),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (0..LEN, 0..LEN).prop_map(|(a, b)| Op::Union(a, b)),
+            (0..=LEN, 0..=LEN).prop_map(|(a, b)| Op::Range(a.min(b), a.max(b))),
+        ]
+    }
+
+    #[test]
+    fn ranges() {
+        let mut uf = RangedUnionFind::new(10);
+        assert_eq!(uf.union_range(3..3).unwrap(), None);
+        assert!(uf.union_range(8..=10).is_err());
+
+        let root = uf.union_range(2..5).unwrap().unwrap();
+        assert!((2..5).all(|i| uf.find(i).unwrap() == root));
+        assert_ne!(uf.find(1).unwrap(), root);
+        assert_ne!(uf.find(5).unwrap(), root);
+
+        uf.union_range(7..).unwrap();
+        uf.union_range(4..=7).unwrap();
+        let root = uf.find(2).unwrap();
+        assert!((2..10).all(|i| uf.find(i).unwrap() == root));
+        assert_ne!(uf.find(0).unwrap(), uf.find(1).unwrap());
+    }
+
+    proptest! {
+        #[test]
+        fn matches_single_unions(ops in prop::collection::vec(op(), 0..128)) {
+            let mut ranged = RangedUnionFind::new(LEN);
+            let mut naive = UnionFind::default();
+            for _ in 0..LEN {
+                naive.put();
+            }
+
+            for op in ops {
+                match op {
+                    Op::Union(a, b) => {
+                        ranged.union(a, b).unwrap();
+                        naive.union(a, b).unwrap();
+                    },
+                    Op::Range(a, b) => {
+                        ranged.union_range(a..b).unwrap();
+                        for i in a + 1..b {
+                            naive.union(a, i).unwrap();
+                        }
+                    },
+                }
+            }
+
+            for a in 0..LEN {
+                for b in 0..LEN {
+                    prop_assert_eq!(
+                        ranged.find(a).unwrap() == ranged.find(b).unwrap(),
+                        naive.find(a).unwrap() == naive.find(b).unwrap(),
+                    );
+                }
+            }
+        }
+    }
+}
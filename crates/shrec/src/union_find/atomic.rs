@@ -0,0 +1,226 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::NoNode;
+
+/// A disjoint-set data structure supporting concurrent lookups and unions
+/// through a shared reference
+///
+/// Each node stores an atomic parent pointer.  Lookups split the paths they
+/// traverse by pointing each visited node at its grandparent, which never
+/// blocks and needs no retries, as a failed update only means another thread
+/// already shortened the path.  Unions link the root with the higher ID under
+/// the root with the lower one, retrying if either root was linked elsewhere
+/// in the meantime.  Because every parent pointer refers to a lower ID, no
+/// interleaving of concurrent unions can form a cycle.
+///
+/// Linking by ID rather than by size gives up the size bound of
+/// [`UnionFind`](super::UnionFind), so in the worst case lookups take
+/// amortized logarithmic rather than near-constant time.
+///
+/// Nodes can only be added with exclusive access to the union-find.
+#[derive(Debug, Default)]
+pub struct AtomicUnionFind(Vec<AtomicUsize>);
+
+impl AtomicUnionFind {
+    /// Construct a new union-find containing `len` nodes, each in its own
+    /// partition
+    #[must_use]
+    pub fn new(len: usize) -> Self { Self((0..len).map(AtomicUsize::new).collect()) }
+
+    /// Get the number of nodes in the union-find
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize { self.0.len() }
+
+    /// Returns true if the union-find contains no nodes
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Add a new node to the union-find in its own partition, returning its ID
+    ///
+    /// See [`UnionFind::put`](super::UnionFind::put).
+    pub fn put(&mut self) -> usize {
+        let key = self.0.len();
+        self.0.push(AtomicUsize::new(key));
+        key
+    }
+
+    #[inline]
+    fn parent(&self, key: usize) -> usize {
+        debug_assert!(key < self.0.len());
+        // Safety: all stored parent IDs are valid node IDs
+        unsafe { self.0.get_unchecked(key) }.load(Ordering::Acquire)
+    }
+
+    /// Find the partition root ID for the given node ID, and split the search
+    /// path between the node and its root
+    ///
+    /// If other threads are performing unions, the returned node may no
+    /// longer be a root by the time this method returns.
+    ///
+    /// # Errors
+    /// This method first checks if the node ID is valid, returning an error if
+    /// no associated node can be found.
+    pub fn find(&self, key: usize) -> Result<usize, NoNode> {
+        if key >= self.0.len() {
+            return Err(NoNode(key));
+        }
+
+        let mut curr = key;
+        loop {
+            let parent = self.parent(curr);
+            if parent == curr {
+                break Ok(curr);
+            }
+
+            let grandparent = self.parent(parent);
+            if grandparent != parent {
+                // Failure means another thread already moved this pointer
+                // closer to the root
+                let _ = self.0[curr].compare_exchange_weak(
+                    parent,
+                    grandparent,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                );
+            }
+
+            curr = parent;
+        }
+    }
+
+    /// Returns true if the two given node IDs are in the same partition
+    ///
+    /// # Errors
+    /// This method first checks if both node IDs are valid, returning an error
+    /// if either cannot be found.
+    pub fn equiv(&self, a: usize, b: usize) -> Result<bool, NoNode> {
+        let (mut a, mut b) = (self.find(a)?, self.find(b)?);
+
+        loop {
+            if a == b {
+                break Ok(true);
+            }
+
+            // The roots differ, but only prove the nodes are disjoint if the
+            // first root was not linked elsewhere while finding the second
+            if self.parent(a) == a {
+                break Ok(false);
+            }
+
+            a = self.find(a).unwrap_or_else(|_| unreachable!());
+            b = self.find(b).unwrap_or_else(|_| unreachable!());
+        }
+    }
+
+    /// Perform the in-place union of the partitions containing the two given
+    /// node IDs
+    ///
+    /// Returns the root ID of the merged partition at the time of the union,
+    /// or `None` if both nodes were already in the same partition.
+    ///
+    /// # Errors
+    /// This method first checks if both node IDs are valid, returning an error
+    /// if either cannot be found.
+    pub fn union(&self, a: usize, b: usize) -> Result<Option<usize>, NoNode> {
+        let (mut a, mut b) = (self.find(a)?, self.find(b)?);
+
+        loop {
+            if a == b {
+                break Ok(None);
+            }
+
+            if a > b {
+                std::mem::swap(&mut a, &mut b);
+            }
+
+            if self.0[b]
+                .compare_exchange(b, a, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                break Ok(Some(a));
+            }
+
+            // b was linked under another root first, so retry from the
+            // current roots
+            a = self.find(a).unwrap_or_else(|_| unreachable!());
+            b = self.find(b).unwrap_or_else(|_| unreachable!());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::{super::UnionFind, AtomicUnionFind};
+
+    const LEN: usize = 64;
+
+    fn same_partitions(atomic: &AtomicUnionFind, naive: &mut UnionFind) -> bool {
+        (0..atomic.len()).all(|a| {
+            (0..atomic.len()).all(|b| {
+                atomic.equiv(a, b).unwrap() == (naive.find(a).unwrap() == naive.find(b).unwrap())
+            })
+        })
+    }
+
+    #[test]
+    fn concurrent() {
+        const THREADS: usize = 4;
+        const N: usize = 1000;
+
+        let uf = AtomicUnionFind::new(N);
+        let mut naive = UnionFind::default();
+        for _ in 0..N {
+            naive.put();
+        }
+
+        // Each thread joins nodes congruent mod its stride, and together they
+        // touch every node in overlapping patterns
+        std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let uf = &uf;
+                s.spawn(move || {
+                    let stride = 6 + t;
+                    for i in (0..N - stride).rev() {
+                        uf.union(i, i + stride).unwrap();
+                        uf.find(i).unwrap();
+                    }
+                });
+            }
+        });
+
+        for t in 0..THREADS {
+            let stride = 6 + t;
+            for i in 0..N - stride {
+                naive.union(i, i + stride).unwrap();
+            }
+        }
+
+        assert!(same_partitions(&uf, &mut naive));
+        assert_eq!(uf.find(N - 1).unwrap(), 0);
+        assert!(uf.find(N).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn matches_sequential(ops in prop::collection::vec((0..LEN, 0..LEN), 0..128)) {
+            let atomic = AtomicUnionFind::new(LEN);
+            let mut naive = UnionFind::default();
+            for _ in 0..LEN {
+                naive.put();
+            }
+
+            for (a, b) in ops {
+                prop_assert_eq!(
+                    atomic.union(a, b).unwrap().is_some(),
+                    naive.union(a, b).unwrap().is_some(),
+                );
+            }
+
+            prop_assert!(same_partitions(&atomic, &mut naive));
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use paracord::{
+    client::Context,
+    interaction::{
+        collector,
+        command::{CommandPlan, SyncGate},
+    },
+};
+use serenity::{
+    builder::{
+        CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage,
+        CreateMessage, EditMessage,
+    },
+    model::{application::ButtonStyle, id::UserId},
+    utils::MessageBuilder,
+};
+
+use crate::prelude::*;
+
+const TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const MAX_PLAN_LEN: usize = 1500;
+const APPROVE_ID: &str = "sync-approve";
+const REJECT_ID: &str = "sync-reject";
+
+/// Sync gate asking the bot owner over DM to approve deleting registered
+/// commands, keeping them if no answer arrives in time
+#[derive(Debug)]
+pub struct OwnerApproval;
+
+#[async_trait]
+impl SyncGate for OwnerApproval {
+    async fn approve(&self, ctx: &Context, plan: &CommandPlan) -> bool {
+        warn!("Command sync requires deletions, asking owner for approval:\n{plan}");
+
+        match ask(ctx, plan).await {
+            Ok(approved) => approved,
+            Err(err) => {
+                error!(?err, "Error requesting approval of command deletions");
+                false
+            },
+        }
+    }
+}
+
+fn render_plan(plan: &CommandPlan) -> String {
+    let plan = plan.to_string();
+    let mut out: String = plan.chars().take(MAX_PLAN_LEN).collect();
+    if out.len() < plan.len() {
+        out.push('\u{2026}');
+    }
+
+    MessageBuilder::new()
+        .push_line("This build will delete registered commands on startup:")
+        .push_codeblock_safe(out, None)
+        .push("Approve the deletions to continue, or keep the commands registered.")
+        .build()
+}
+
+async fn owner(ctx: &Context) -> Result<UserId> {
+    let info = ctx
+        .http
+        .get_current_application_info()
+        .await
+        .context("Error fetching application info")?;
+
+    info.team
+        .map(|t| t.owner_user_id)
+        .or(info.owner.map(|o| o.id))
+        .context("Application has no owner")
+}
+
+async fn ask(ctx: &Context, plan: &CommandPlan) -> Result<bool> {
+    let collectors = collector::get(ctx)
+        .await
+        .context("Missing collectors context")?;
+    let owner = owner(ctx).await?;
+
+    let mut msg = owner
+        .direct_message(
+            &ctx.http,
+            CreateMessage::new()
+                .content(render_plan(plan))
+                .components(vec![CreateActionRow::Buttons(vec![
+                    CreateButton::new(APPROVE_ID)
+                        .label("Delete commands")
+                        .style(ButtonStyle::Danger),
+                    CreateButton::new(REJECT_ID)
+                        .label("Keep commands")
+                        .style(ButtonStyle::Secondary),
+                ])]),
+        )
+        .await
+        .context("Error sending approval request")?;
+
+    let Some(mc) = collectors
+        .await_component(None, msg.id, move |mc| mc.user.id == owner, TIMEOUT)
+        .await?
+    else {
+        warn!("No answer to approval request, keeping commands");
+        msg.edit(
+            ctx,
+            EditMessage::new()
+                .content(format!(
+                    "{}\n\n*Timed out, commands were kept.*",
+                    msg.content
+                ))
+                .components(vec![]),
+        )
+        .await
+        .context("Error expiring approval request")?;
+        return Ok(false);
+    };
+
+    let approved = mc.data.custom_id == APPROVE_ID;
+    let outcome = if approved {
+        "*Approved, deleting commands.*"
+    } else {
+        "*Rejected, commands were kept.*"
+    };
+    info!(approved, "Owner answered approval request");
+
+    mc.create_response(
+        ctx,
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(format!("{}\n\n{outcome}", msg.content))
+                .components(vec![]),
+        ),
+    )
+    .await
+    .context("Error answering approval request")?;
+
+    Ok(approved)
+}
@@ -0,0 +1,467 @@
+use std::{path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Utc};
+use prost::Message;
+use paracord::client::Context;
+use reqwest::Method;
+use serenity::{
+    client::ClientBuilder,
+    model::id::GuildId,
+    prelude::TypeMapKey,
+};
+use tokio::sync::RwLock;
+
+use super::storage::Storage;
+use crate::{prelude::*, proto::guild, util::DebugShim};
+
+mod sigv4;
+
+const NAME_PREFIX: &str = "backup-";
+const NAME_SUFFIX: &str = ".pb";
+
+#[derive(Debug, Clone, PartialEq, clap::Args)]
+#[expect(
+    clippy::struct_field_names,
+    reason = "Field names are used as CLI flags"
+)]
+pub struct BackupOpts {
+    /// Directory to write scheduled backups of guild data to
+    #[arg(long, env)]
+    backup_dir: Option<PathBuf>,
+
+    /// URL of an S3-compatible bucket or other HTTP store to upload scheduled
+    /// backups of guild data to
+    ///
+    /// Each backup is uploaded with a PUT request to a new object under this
+    /// URL.  Old backups are not deleted from the store, so retention should
+    /// be configured on the bucket itself.
+    ///
+    /// Requests are signed with AWS Signature Version 4 if S3 credentials are
+    /// given, and otherwise authenticated with the optional bearer token.
+    #[arg(long, env, conflicts_with = "backup_dir")]
+    backup_url: Option<Url>,
+
+    /// Bearer token to send with requests to the backup URL, if required
+    #[arg(long, env, conflicts_with = "backup_s3_access_key")]
+    backup_token: Option<DebugShim<String>>,
+
+    /// Access key ID used to sign requests to an S3-compatible backup URL
+    #[arg(long, env, requires = "backup_s3_secret_key")]
+    backup_s3_access_key: Option<String>,
+
+    /// Secret access key used to sign requests to an S3-compatible backup URL
+    #[arg(long, env, requires = "backup_s3_access_key")]
+    backup_s3_secret_key: Option<DebugShim<String>>,
+
+    /// Region of the S3-compatible bucket, used when signing requests
+    #[arg(long, env, default_value = "us-east-1")]
+    backup_s3_region: String,
+
+    /// Interval between scheduled backups, in hours
+    #[arg(long, env, default_value_t = 24, value_parser = clap::value_parser!(u64).range(1..))]
+    backup_interval: u64,
+
+    /// Number of backups to keep in the backup directory
+    #[arg(long, env, default_value_t = 7, value_parser = clap::value_parser!(u64).range(1..))]
+    backup_retain: u64,
+}
+
+#[derive(Debug)]
+enum Auth {
+    None,
+    Bearer(DebugShim<String>),
+    S3(sigv4::Credentials),
+}
+
+#[derive(Debug)]
+enum Target {
+    Dir(PathBuf),
+    Http { url: Url, auth: Auth },
+}
+
+/// The outcome of a single successful backup
+#[derive(Debug, Clone)]
+pub struct BackupSummary {
+    pub name: String,
+    pub guilds: usize,
+    pub bytes: usize,
+}
+
+/// The outcome of the most recent backup attempt
+#[derive(Debug, Clone)]
+pub struct BackupStatus {
+    pub at: DateTime<Utc>,
+    pub result: Result<BackupSummary, String>,
+}
+
+/// Periodically exports the stored data of every guild
+#[derive(Debug)]
+pub struct Backups {
+    target: Option<Target>,
+    interval: Duration,
+    retain: usize,
+    storage: Arc<Storage>,
+    client: reqwest::Client,
+    last: RwLock<Option<BackupStatus>>,
+}
+
+struct BackupKey;
+
+impl TypeMapKey for BackupKey {
+    type Value = Arc<Backups>;
+}
+
+pub trait BackupInit {
+    #[must_use]
+    fn register_backups(self, backups: Arc<Backups>) -> Self;
+}
+
+impl BackupInit for ClientBuilder {
+    fn register_backups(self, backups: Arc<Backups>) -> Self {
+        self.type_map_insert::<BackupKey>(backups)
+    }
+}
+
+pub async fn get(ctx: &Context) -> Option<Arc<Backups>> {
+    ctx.data.read().await.get::<BackupKey>().map(Arc::clone)
+}
+
+fn backup_name(at: DateTime<Utc>) -> String {
+    format!("{NAME_PREFIX}{}{NAME_SUFFIX}", at.format("%Y%m%dT%H%M%SZ"))
+}
+
+/// Check that a name refers to a backup written by this module, and not to
+/// an arbitrary file
+fn is_backup_name(name: &str) -> bool {
+    name.strip_prefix(NAME_PREFIX)
+        .and_then(|n| n.strip_suffix(NAME_SUFFIX))
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+/// Decode a backup, rejecting anything that does not look like one
+pub fn decode(data: &[u8]) -> Result<guild::Backup> {
+    let backup = guild::Backup::decode(data).context("Error decoding backup")?;
+    ensure!(
+        backup.created_at != 0 && backup.guilds.keys().all(|&k| k != 0),
+        "File is not a valid backup"
+    );
+    Ok(backup)
+}
+
+impl Backups {
+    pub fn new(opts: BackupOpts, storage: Arc<Storage>) -> Self {
+        let BackupOpts {
+            backup_dir,
+            backup_url,
+            backup_token,
+            backup_s3_access_key,
+            backup_s3_secret_key,
+            backup_s3_region,
+            backup_interval,
+            backup_retain,
+        } = opts;
+
+        let target = if let Some(dir) = backup_dir {
+            Some(Target::Dir(dir))
+        } else {
+            backup_url.map(|mut url| {
+                if !url.path().ends_with('/') {
+                    url.set_path(&format!("{}/", url.path()));
+                }
+
+                let auth = match (backup_s3_access_key, backup_s3_secret_key, backup_token) {
+                    (Some(access_key), Some(secret_key), _) => Auth::S3(sigv4::Credentials {
+                        access_key,
+                        secret_key,
+                        region: backup_s3_region,
+                    }),
+                    (.., Some(token)) => Auth::Bearer(token),
+                    _ => Auth::None,
+                };
+
+                Target::Http { url, auth }
+            })
+        };
+
+        Self {
+            target,
+            interval: Duration::from_secs(backup_interval.saturating_mul(3600)),
+            retain: backup_retain.try_into().unwrap_or(usize::MAX),
+            storage,
+            client: reqwest::Client::builder()
+                .user_agent("the-q")
+                .timeout(Duration::from_secs(60))
+                .build()
+                .unwrap(),
+            last: RwLock::default(),
+        }
+    }
+
+    #[inline]
+    pub fn configured(&self) -> bool { self.target.is_some() }
+
+    /// Build an authenticated request for an object in an HTTP store
+    fn request(
+        &self,
+        method: Method,
+        url: Url,
+        auth: &Auth,
+        body: Vec<u8>,
+    ) -> Result<reqwest::RequestBuilder> {
+        let req = match auth {
+            Auth::None => self.client.request(method, url),
+            Auth::Bearer(token) => self.client.request(method, url).bearer_auth(&token.0),
+            Auth::S3(creds) => {
+                let headers = creds.sign(&method, &url, &body, Utc::now())?;
+                self.client.request(method, url).headers(headers)
+            },
+        };
+
+        Ok(req.body(body))
+    }
+
+    /// Get the outcome of the most recent backup, if one has been attempted
+    pub async fn last(&self) -> Option<BackupStatus> { self.last.read().await.clone() }
+
+    /// Export the stored data of the given guilds
+    async fn snapshot(&self, guilds: &[GuildId]) -> Result<guild::Backup> {
+        // Include XP that has been awarded but not saved yet
+        super::commands::flush_xp(&self.storage).await;
+
+        let mut backup = guild::Backup {
+            created_at: Utc::now().timestamp(),
+            guilds: HashMap::new(),
+        };
+
+        for &gid in guilds {
+            let data = self
+                .storage
+                .guild(gid)
+                .await
+                .with_context(|| format!("Error reading data for guild {gid}"))?;
+            backup.guilds.insert(gid.get(), data);
+        }
+
+        Ok(backup)
+    }
+
+    /// Export the stored data of a single guild
+    pub async fn export(&self, gid: GuildId) -> Result<Vec<u8>> {
+        Ok(self.snapshot(&[gid]).await?.encode_to_vec())
+    }
+
+    /// Back up every guild to the configured target now, recording the
+    /// outcome for [`last`](Self::last)
+    pub async fn run(&self) -> Result<BackupSummary> {
+        let at = Utc::now();
+        let res = self.run_inner(at).await;
+
+        *self.last.write().await = Some(BackupStatus {
+            at,
+            result: res.as_ref().map_err(|e| format!("{e:#}")).cloned(),
+        });
+
+        res
+    }
+
+    async fn run_inner(&self, at: DateTime<Utc>) -> Result<BackupSummary> {
+        let Some(ref target) = self.target else {
+            bail!("No backup target is configured");
+        };
+
+        let guilds = self
+            .storage
+            .guild_ids()
+            .await
+            .context("Error listing stored guilds")?;
+        let data = self.snapshot(&guilds).await?.encode_to_vec();
+        let name = backup_name(at);
+        let bytes = data.len();
+
+        match target {
+            Target::Dir(dir) => {
+                tokio::fs::create_dir_all(dir)
+                    .await
+                    .with_context(|| format!("Error creating backup directory {dir:?}"))?;
+
+                let path = dir.join(&name);
+                let tmp = path.with_extension("tmp");
+                tokio::fs::write(&tmp, data)
+                    .await
+                    .with_context(|| format!("Error writing {tmp:?}"))?;
+                tokio::fs::rename(&tmp, &path)
+                    .await
+                    .with_context(|| format!("Error replacing {path:?}"))?;
+
+                self.prune(dir).await?;
+            },
+            Target::Http { url, auth } => {
+                let url = url.join(&name).context("Error building backup URL")?;
+
+                self.request(Method::PUT, url, auth, data)?
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .context("Error uploading backup")?;
+            },
+        }
+
+        Ok(BackupSummary {
+            name,
+            guilds: guilds.len(),
+            bytes,
+        })
+    }
+
+    /// Delete all but the newest backups in the given directory
+    async fn prune(&self, dir: &PathBuf) -> Result {
+        let mut names = Self::list_dir(dir).await?;
+        let excess = names.len().saturating_sub(self.retain);
+
+        for name in names.drain(..excess) {
+            let path = dir.join(&name);
+            tokio::fs::remove_file(&path)
+                .await
+                .with_context(|| format!("Error deleting old backup {path:?}"))?;
+            debug!(name, "Deleted old backup");
+        }
+
+        Ok(())
+    }
+
+    async fn list_dir(dir: &PathBuf) -> Result<Vec<String>> {
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(e) => e,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e).with_context(|| format!("Error reading {dir:?}")),
+        };
+
+        let mut names = vec![];
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .with_context(|| format!("Error reading {dir:?}"))?
+        {
+            if let Some(name) = entry.file_name().to_str().filter(|n| is_backup_name(n)) {
+                names.push(name.to_owned());
+            }
+        }
+
+        // Names are timestamped, so this sorts them oldest-first
+        names.sort_unstable();
+        Ok(names)
+    }
+
+    /// List the available backups, oldest first, or `None` if the target
+    /// does not support listing
+    pub async fn list(&self) -> Result<Option<Vec<String>>> {
+        match self.target {
+            Some(Target::Dir(ref dir)) => Self::list_dir(dir).await.map(Some),
+            Some(Target::Http { .. }) | None => Ok(None),
+        }
+    }
+
+    /// Load a backup from the configured target by name
+    pub async fn load(&self, name: &str) -> Result<guild::Backup> {
+        ensure!(is_backup_name(name), "Invalid backup name {name:?}");
+
+        let data = match self.target {
+            Some(Target::Dir(ref dir)) => {
+                let path = dir.join(name);
+                tokio::fs::read(&path)
+                    .await
+                    .with_context(|| format!("Error reading {path:?}"))?
+            },
+            Some(Target::Http { ref url, ref auth }) => {
+                let url = url.join(name).context("Error building backup URL")?;
+
+                self.request(Method::GET, url, auth, vec![])?
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .context("Error downloading backup")?
+                    .bytes()
+                    .await
+                    .context("Error downloading backup")?
+                    .into()
+            },
+            None => bail!("No backup target is configured"),
+        };
+
+        decode(&data)
+    }
+
+    /// Replace the stored data of a guild with its data from a backup,
+    /// returning false if the backup does not contain the guild
+    pub async fn restore(&self, backup: &guild::Backup, gid: GuildId) -> Result<bool> {
+        let Some(data) = backup.guilds.get(&gid.get()) else {
+            return Ok(false);
+        };
+
+        self.storage
+            .update_guild(gid, |g| *g = data.clone())
+            .await
+            .context("Error restoring guild data")?;
+        super::commands::invalidate_guild_caches(Some(gid)).await;
+        Ok(true)
+    }
+
+    /// Start running scheduled backups in the background, if a target was
+    /// configured
+    pub fn spawn(self: &Arc<Self>) {
+        if !self.configured() {
+            return;
+        }
+
+        let me = Arc::clone(self);
+        tokio::spawn(
+            async move {
+                let mut interval = tokio::time::interval_at(
+                    tokio::time::Instant::now() + me.interval,
+                    me.interval,
+                );
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+
+                    match me.run().await {
+                        Ok(s) => info!(s.name, s.guilds, s.bytes, "Backup complete"),
+                        Err(err) => error!(?err, "Scheduled backup failed"),
+                    }
+                }
+            }
+            .instrument(info_span!("backup")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let name = backup_name(at);
+
+        assert_eq!(name, "backup-20231114T221320Z.pb");
+        assert!(is_backup_name(&name));
+        assert!(!is_backup_name("backup-.pb"));
+        assert!(!is_backup_name("backup-../guilds/1.pb"));
+        assert!(!is_backup_name("settings.json"));
+        assert!(backup_name(at + chrono::TimeDelta::hours(1)) > name);
+    }
+
+    #[test]
+    fn decoding() {
+        let backup = guild::Backup {
+            created_at: 1,
+            guilds: [(1, guild::Guild::default())].into(),
+        };
+
+        assert_eq!(decode(&backup.encode_to_vec()).unwrap(), backup);
+        assert!(decode(&guild::Backup::default().encode_to_vec()).is_err());
+        assert!(decode(b"\xff\xff").is_err());
+    }
+}
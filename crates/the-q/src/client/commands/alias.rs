@@ -0,0 +1,371 @@
+use paracord::interaction::command::{Alias, AliasError, AliasResolver};
+use serenity::model::{
+    application::{CommandInteraction, ResolvedOption, ResolvedValue},
+    Permissions,
+};
+
+use super::{botlog, prelude::*};
+use crate::client::storage;
+
+/// Most aliases a single server can have
+const MAX_ALIASES: usize = 25;
+const MAX_NAME: u16 = 32;
+const MAX_TARGET: u16 = 200;
+
+/// Check an alias for problems, returning a description of the first one
+/// found
+fn validate(command: &str, name: &str, target: &str) -> Result<Alias, String> {
+    if name.is_empty() || name.chars().count() > MAX_NAME.into() {
+        return Err(format!(
+            "Alias names must be 1 to {MAX_NAME} characters long"
+        ));
+    }
+
+    let alias: Alias = target
+        .trim()
+        .trim_start_matches('/')
+        .parse()
+        .map_err(|e: AliasError| e.to_string())?;
+
+    if alias.command() == command {
+        return Err("Aliases can't run other aliases".into());
+    }
+
+    Ok(alias)
+}
+
+/// Alias lookup for invocations of the alias command's `run` subcommand
+#[derive(Debug)]
+pub struct AliasLookup {
+    command: String,
+}
+
+impl From<&CommandOpts> for AliasLookup {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            command: format!("{}alias", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl AliasResolver for AliasLookup {
+    async fn resolve(
+        &self,
+        ctx: &Context,
+        aci: &CommandInteraction,
+    ) -> Result<Option<Alias>, AliasError> {
+        let Some(gid) = aci.guild_id else {
+            return Ok(None);
+        };
+
+        if aci.data.name != self.command {
+            return Ok(None);
+        }
+
+        let opts = aci.data.options();
+        let [ResolvedOption {
+            name: "run",
+            value: ResolvedValue::SubCommand(ref args),
+            ..
+        }] = *opts
+        else {
+            return Ok(None);
+        };
+        let Some(name) = args.iter().find_map(|a| match *a {
+            ResolvedOption {
+                name: "name",
+                value: ResolvedValue::String(s),
+                ..
+            } => Some(s.trim()),
+            _ => None,
+        }) else {
+            return Ok(None);
+        };
+
+        let storage = storage::get(ctx)
+            .await
+            .context("Missing storage context")
+            .map_err(AliasError::Backend)?;
+        let target = storage
+            .guild(gid)
+            .await
+            .map_err(AliasError::Backend)?
+            .aliases
+            .remove(name)
+            .ok_or_else(|| AliasError::Unknown(name.into()))?;
+
+        target.parse().map(Some)
+    }
+}
+
+#[derive(Debug)]
+pub struct AliasCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for AliasCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}alias", opts.command_base),
+        }
+    }
+}
+
+impl AliasCommand {
+    async fn add<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let name = visitor.visit_string("name")?.required()?.trim().to_owned();
+        let target = visitor.visit_string("command")?.required()?;
+        let user = visitor.user().id;
+
+        let alias = match validate(&self.name, &name, target) {
+            Ok(a) => a.to_string(),
+            Err(e) => {
+                return Err(responder
+                    .create_message(Message::plain(e).ephemeral(true))
+                    .await
+                    .context("Error sending alias error")?
+                    .into_err("Invalid alias"));
+            },
+        };
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let saved = storage
+            .update_guild(gid, |g| {
+                if g.aliases.len() >= MAX_ALIASES && !g.aliases.contains_key(&name) {
+                    return false;
+                }
+
+                g.aliases.insert(name.clone(), alias.clone());
+                true
+            })
+            .await
+            .context("Error saving alias")?;
+
+        if !saved {
+            return Err(responder
+                .create_message(
+                    Message::plain(format!(
+                        "This server already has the maximum of {MAX_ALIASES} aliases."
+                    ))
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending limit error")?
+                .into_err("Too many aliases"));
+        }
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new("Command alias saved", user)
+                .with_target(name.clone())
+                .with_reason(format!("/{alias}")),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push("Saved alias ")
+                        .push_bold_safe(name.as_str())
+                        .push(" for ")
+                        .push_mono_safe(format!("/{alias}"))
+                        .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn remove<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let name = visitor.visit_string("name")?.required()?.trim().to_owned();
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let removed = storage
+            .update_guild(gid, |g| g.aliases.remove(&name).is_some())
+            .await
+            .context("Error removing alias")?;
+
+        if !removed {
+            return Err(responder
+                .create_message(
+                    Message::rich(|mb| {
+                        mb.push("There's no alias named ")
+                            .push_bold_safe(name.as_str())
+                            .push(".")
+                    })
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending missing alias error")?
+                .into_err("Unknown alias"));
+        }
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new("Command alias removed", user).with_target(name.clone()),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push("Removed alias ")
+                        .push_bold_safe(name.as_str())
+                        .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn list<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let mut aliases: Vec<_> = storage.guild(gid).await?.aliases.into_iter().collect();
+        aliases.sort_unstable();
+
+        let msg = if aliases.is_empty() {
+            Message::plain("This server has no command aliases.")
+        } else {
+            Message::rich(|mb| {
+                mb.push_bold_line(format!(
+                    "Command aliases ({}/{MAX_ALIASES}):",
+                    aliases.len()
+                ));
+                for (name, target) in &aliases {
+                    mb.push("- ")
+                        .push_bold_safe(name.as_str())
+                        .push(": ")
+                        .push_mono_line_safe(format!("/{target}"));
+                }
+
+                mb.push("Run an alias with ")
+                    .push_mono(format!("/{} run", self.name))
+            })
+        };
+
+        Ok(responder
+            .create_message(msg.ephemeral(true))
+            .await
+            .context("Error sending alias list")?
+            .into())
+    }
+
+    /// Respond to a `run` invocation the registry did not expand, which only
+    /// happens if an alias names this command
+    async fn run<'a>(
+        &self,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let _name = visitor.visit_string("name")?.required()?;
+
+        Err(responder
+            .create_message(Message::plain("Aliases can't run other aliases.").ephemeral(true))
+            .await
+            .context("Error sending alias error")?
+            .into_err("Unexpanded alias"))
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for AliasCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Run or manage shortcuts for commands", |a| {
+            a.build_subcmd("add", "Add or replace a command alias", |a| {
+                a.string("name", "Name of the alias", true, 1..=MAX_NAME)
+                    .string(
+                        "command",
+                        "Command to run, e.g. \"qjpeg quality:5\"",
+                        true,
+                        1..=MAX_TARGET,
+                    )
+            })
+            .build_subcmd("remove", "Remove a command alias", |a| {
+                a.string("name", "Name of the alias", true, 1..=MAX_NAME)
+            })
+            .build_subcmd("list", "List this server's command aliases", id)
+            .build_subcmd("run", "Run a command alias", |a| {
+                a.string("name", "Name of the alias", true, 1..=MAX_NAME)
+            })
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+        let admin = memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD));
+        let subcmd = visitor.visit_subcmd()?;
+
+        if matches!(*subcmd, ["add" | "remove"]) && !admin {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        match *subcmd {
+            ["add"] => self.add(ctx, visitor, responder).await,
+            ["remove"] => self.remove(ctx, visitor, responder).await,
+            ["list"] => self.list(ctx, visitor, responder).await,
+            ["run"] => self.run(visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::validate;
+
+    #[test]
+    fn validation() {
+        let alias = validate("qalias", "fry", " /qjpeg  quality:5 strip_metadata:true ").unwrap();
+        assert_eq!(alias.command(), "qjpeg");
+        assert_eq!(alias.to_string(), "qjpeg quality:5 strip_metadata:true");
+
+        assert!(validate("qalias", "", "qjpeg").is_err());
+        assert!(validate("qalias", &"a".repeat(33), "qjpeg").is_err());
+        assert!(validate("qalias", "fry", "").is_err());
+        assert!(validate("qalias", "fry", "qjpeg quality:").is_err());
+        assert!(validate("qalias", "loop", "qalias run name:loop").is_err());
+    }
+}
@@ -0,0 +1,944 @@
+use std::{
+    collections::VecDeque,
+    hash::{DefaultHasher, Hasher},
+    time::{Duration, Instant},
+};
+
+use qcore::build_with::BuildWith;
+use serenity::{
+    builder::{CreateMessage, EditMember},
+    model::{
+        channel::{Message as ChannelMessage, MessageType},
+        id::{RoleId, UserId},
+        mention::Mentionable,
+        Permissions, Timestamp,
+    },
+    utils::MessageBuilder,
+};
+use tokio::sync::Mutex;
+
+use super::{botlog, guild_cache::GuildCache, prelude::*};
+use crate::{
+    client::storage::{self, Storage},
+    proto::guild,
+};
+
+const DEFAULT_MAX_MESSAGES: u32 = 5;
+const DEFAULT_WINDOW: u32 = 5;
+const DEFAULT_MAX_DUPLICATES: u32 = 3;
+const DEFAULT_MAX_MENTIONS: u32 = 5;
+const DEFAULT_MUTE_AT: u32 = 2;
+const DEFAULT_MUTE_SECS: u32 = 10 * 60;
+const DEFAULT_KICK_AT: u32 = 3;
+const DEFAULT_STRIKE_TTL: u32 = 24 * 60 * 60;
+/// Longest window messages can be counted over, in seconds
+const MAX_WINDOW: u32 = 5 * 60;
+/// Largest message, duplicate or mention limit
+const MAX_LIMIT: u32 = 50;
+/// Largest strike count at which a penalty can be applied
+const MAX_STRIKES: u32 = 10;
+/// Longest timeout Discord allows, in seconds
+const MAX_MUTE_SECS: u32 = 28 * 24 * 60 * 60;
+const MAX_STRIKE_TTL: u32 = 30 * 24 * 60 * 60;
+const MAX_EXEMPT_ROLES: usize = 25;
+/// Number of tracked users above which idle message histories are dropped
+const MAX_TRACKED: usize = 10_000;
+
+/// Anti-spam settings for each guild whose settings have been loaded, or
+/// `None` if anti-spam is off
+static SETTINGS: GuildCache<Option<Arc<guild::AntiSpam>>> = GuildCache::new();
+
+/// Recent messages sent by each user, oldest first
+///
+/// Rates only matter over a few minutes, so this is kept in memory and lost
+/// on restart, unlike strikes.
+static HISTORY: Mutex<BTreeMap<(GuildId, UserId), VecDeque<Sent>>> =
+    Mutex::const_new(BTreeMap::new());
+
+/// A message counted towards a user's rate
+#[derive(Debug, Clone, Copy)]
+struct Sent {
+    at: Instant,
+    /// A hash of the normalized content, or `None` if the message had none
+    content: Option<u64>,
+}
+
+impl Sent {
+    fn new(at: Instant, content: &str) -> Self {
+        let content = content.split_whitespace().collect::<Vec<_>>().join(" ");
+        let content = (!content.is_empty()).then(|| {
+            let mut hasher = DefaultHasher::new();
+            hasher.write(content.to_lowercase().as_bytes());
+            hasher.finish()
+        });
+
+        Self { at, content }
+    }
+}
+
+/// A heuristic broken by a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Violation {
+    Rate(usize),
+    Duplicates(usize),
+    Mentions(usize),
+}
+
+impl Violation {
+    fn describe(self, window: u32) -> String {
+        match self {
+            Self::Rate(n) => format!("sending {n} messages in {window}s"),
+            Self::Duplicates(n) => format!("repeating a message {n} times in {window}s"),
+            Self::Mentions(n) => format!("mentioning {n} users or roles at once"),
+        }
+    }
+}
+
+/// The action taken against a user for a strike
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Penalty {
+    Warn,
+    Mute,
+    Kick,
+}
+
+fn defaults() -> guild::AntiSpam {
+    guild::AntiSpam {
+        max_messages: DEFAULT_MAX_MESSAGES,
+        window: DEFAULT_WINDOW,
+        max_duplicates: DEFAULT_MAX_DUPLICATES,
+        max_mentions: DEFAULT_MAX_MENTIONS,
+        exempt_roles: vec![],
+        mute_at: DEFAULT_MUTE_AT,
+        mute_secs: DEFAULT_MUTE_SECS,
+        kick_at: DEFAULT_KICK_AT,
+        strike_ttl: DEFAULT_STRIKE_TTL,
+    }
+}
+
+/// Returns true if `n` is over `limit`, treating a limit of zero as none
+fn exceeds(n: usize, limit: u32) -> bool {
+    limit != 0 && usize::try_from(limit).is_ok_and(|l| n > l)
+}
+
+/// Add a message to a user's history and check it against the given
+/// settings, clearing the history if it broke any of them
+fn check(
+    cfg: &guild::AntiSpam,
+    history: &mut VecDeque<Sent>,
+    sent: Sent,
+    mentions: usize,
+) -> Option<Violation> {
+    let window = Duration::from_secs(cfg.window.into());
+    while history
+        .front()
+        .is_some_and(|s| sent.at.saturating_duration_since(s.at) >= window)
+    {
+        history.pop_front();
+    }
+    history.push_back(sent);
+
+    let duplicates = sent.content.map_or(0, |c| {
+        history.iter().filter(|s| s.content == Some(c)).count()
+    });
+
+    let violation = if exceeds(mentions, cfg.max_mentions) {
+        Some(Violation::Mentions(mentions))
+    } else if exceeds(history.len(), cfg.max_messages) {
+        Some(Violation::Rate(history.len()))
+    } else if exceeds(duplicates, cfg.max_duplicates) {
+        Some(Violation::Duplicates(duplicates))
+    } else {
+        None
+    };
+
+    if violation.is_some() {
+        history.clear();
+    }
+
+    violation
+}
+
+/// Record a strike against a user, forgetting any strikes that have
+/// expired, and return the user's new strike count
+fn add_strike(
+    strikes: &mut HashMap<u64, guild::SpamStrikes>,
+    ttl: u32,
+    user: u64,
+    now: i64,
+) -> u32 {
+    strikes.retain(|_, s| now - s.last_at < i64::from(ttl));
+
+    let s = strikes.entry(user).or_default();
+    s.count += 1;
+    s.last_at = now;
+    s.count
+}
+
+fn penalty(cfg: &guild::AntiSpam, strikes: u32) -> Penalty {
+    if cfg.kick_at != 0 && strikes >= cfg.kick_at {
+        Penalty::Kick
+    } else if cfg.mute_at != 0 && strikes >= cfg.mute_at {
+        Penalty::Mute
+    } else {
+        Penalty::Warn
+    }
+}
+
+/// Check settings for problems, returning a description of the first one
+/// found
+pub(super) fn validate(cfg: &guild::AntiSpam) -> Result<(), String> {
+    if !(1..=MAX_WINDOW).contains(&cfg.window) {
+        return Err(format!(
+            "The window must be between 1 and {MAX_WINDOW} seconds"
+        ));
+    }
+
+    if [cfg.max_messages, cfg.max_duplicates, cfg.max_mentions]
+        .into_iter()
+        .any(|l| l > MAX_LIMIT)
+    {
+        return Err(format!("Limits can be at most {MAX_LIMIT}"));
+    }
+
+    if cfg.mute_at > MAX_STRIKES || cfg.kick_at > MAX_STRIKES {
+        return Err(format!("Penalties must apply within {MAX_STRIKES} strikes"));
+    }
+
+    if cfg.mute_at != 0 && cfg.kick_at != 0 && cfg.kick_at <= cfg.mute_at {
+        return Err("Kicks must take more strikes than timeouts".into());
+    }
+
+    if !(60..=MAX_MUTE_SECS).contains(&cfg.mute_secs) {
+        return Err("Timeouts must last between 1 minute and 28 days".into());
+    }
+
+    if !(60..=MAX_STRIKE_TTL).contains(&cfg.strike_ttl) {
+        return Err("Strikes must last between 1 minute and 30 days".into());
+    }
+
+    if cfg.exempt_roles.len() > MAX_EXEMPT_ROLES {
+        return Err(format!("At most {MAX_EXEMPT_ROLES} roles can be exempt"));
+    }
+
+    Ok(())
+}
+
+fn limit(n: u32) -> String {
+    if n == 0 {
+        "no limit".into()
+    } else {
+        n.to_string()
+    }
+}
+
+fn strikes(n: u32) -> String {
+    if n == 0 {
+        "never".into()
+    } else {
+        format!("{n} strikes")
+    }
+}
+
+/// Summarize anti-spam settings as a list
+fn describe<'a>(mb: &'a mut MessageBuilder, cfg: &guild::AntiSpam) -> &'a mut MessageBuilder {
+    mb.push_line(format!("- Window: {}s", cfg.window))
+        .push_line(format!(
+            "- Messages per window: {}",
+            limit(cfg.max_messages)
+        ))
+        .push_line(format!(
+            "- Repeats per window: {}",
+            limit(cfg.max_duplicates)
+        ))
+        .push_line(format!(
+            "- Mentions per message: {}",
+            limit(cfg.max_mentions)
+        ))
+        .push_line(format!(
+            "- Timeout: {} for {} minutes",
+            strikes(cfg.mute_at),
+            cfg.mute_secs / 60
+        ))
+        .push_line(format!("- Kick: {}", strikes(cfg.kick_at)))
+        .push_line(format!(
+            "- Strikes expire after: {} hours",
+            cfg.strike_ttl / 3600
+        ))
+        .push("- Exempt roles: ");
+
+    if cfg.exempt_roles.is_empty() {
+        mb.push("none");
+    }
+    for (i, &role) in cfg.exempt_roles.iter().enumerate() {
+        if i > 0 {
+            mb.push(", ");
+        }
+        mb.role(RoleId::new(role));
+    }
+
+    mb
+}
+
+async fn settings(storage: &Storage, gid: GuildId) -> Result<Option<Arc<guild::AntiSpam>>> {
+    SETTINGS.load(storage, gid, |g| g.anti_spam.map(Arc::new)).await
+}
+
+/// Drop the cached anti-spam settings for a guild, or every guild if `gid` is
+/// `None`, after they were changed outside this module
+pub(super) async fn invalidate(gid: Option<GuildId>) { SETTINGS.invalidate(gid).await; }
+
+async fn record(
+    gid: GuildId,
+    message: &ChannelMessage,
+    cfg: &guild::AntiSpam,
+) -> Option<Violation> {
+    let key = (gid, message.author.id);
+    let sent = Sent::new(Instant::now(), &message.content);
+    let mut history = HISTORY.lock().await;
+
+    if history.len() > MAX_TRACKED {
+        let max_window = Duration::from_secs(MAX_WINDOW.into());
+        history.retain(|_, h| {
+            h.back()
+                .is_some_and(|s| sent.at.saturating_duration_since(s.at) < max_window)
+        });
+    }
+
+    check(
+        cfg,
+        history.entry(key).or_default(),
+        sent,
+        message.mentions.len() + message.mention_roles.len(),
+    )
+}
+
+/// Reply to a message, pinging its author before the given text
+async fn reply(ctx: &Context, message: &ChannelMessage, text: String) -> Result {
+    let user = message.author.id;
+    let body: MessageBody<Infallible> =
+        MessageBody::rich(|mb| mb.mention(&user).push(text)).ping_users(vec![user]);
+
+    message
+        .channel_id
+        .send_message(
+            &ctx.http,
+            CreateMessage::new()
+                .build_with(body)
+                .reference_message(message),
+        )
+        .await
+        .context("Error sending anti-spam warning")?;
+
+    Ok(())
+}
+
+/// Check a message against its guild's anti-spam settings, penalizing the
+/// author if it looks like spam and returning true if so
+///
+/// Repeated messages can only be detected with the privileged message
+/// content intent; without it only rates and mentions are checked.
+pub async fn anti_spam(ctx: &Context, message: &ChannelMessage) -> Result<bool> {
+    let Some(gid) = message.guild_id else {
+        return Ok(false);
+    };
+
+    if message.author.bot
+        || !matches!(
+            message.kind,
+            MessageType::Regular | MessageType::InlineReply
+        )
+    {
+        return Ok(false);
+    }
+
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let Some(cfg) = settings(&storage, gid).await? else {
+        return Ok(false);
+    };
+
+    if message
+        .member
+        .as_ref()
+        .is_some_and(|m| m.roles.iter().any(|r| cfg.exempt_roles.contains(&r.get())))
+    {
+        return Ok(false);
+    }
+
+    let Some(violation) = record(gid, message, &cfg).await else {
+        return Ok(false);
+    };
+
+    let user = message.author.id;
+    let now = Timestamp::now().unix_timestamp();
+    let count = storage
+        .update_guild(gid, |g| {
+            add_strike(&mut g.spam_strikes, cfg.strike_ttl, user.get(), now)
+        })
+        .await
+        .context("Error saving anti-spam strike")?;
+
+    let penalty = penalty(&cfg, count);
+    let reason = format!(
+        "Anti-spam strike {count} for {}",
+        violation.describe(cfg.window)
+    );
+    let bot = ctx.cache.current_user().id;
+    debug!(%gid, %user, ?violation, count, ?penalty, "Applying anti-spam penalty");
+
+    let warning = format!(", please slow down ({})", violation.describe(cfg.window));
+    let action = match penalty {
+        Penalty::Warn => {
+            reply(ctx, message, warning + ".").await?;
+            "User warned"
+        },
+        Penalty::Mute => {
+            let until = Timestamp::from_unix_timestamp(now + i64::from(cfg.mute_secs))
+                .context("Invalid timeout end")?;
+            gid.edit_member(
+                &ctx.http,
+                user,
+                EditMember::new()
+                    .disable_communication_until_datetime(until)
+                    .audit_log_reason(&reason),
+            )
+            .await
+            .context("Error timing out spammer")?;
+
+            reply(
+                ctx,
+                message,
+                format!(
+                    "{warning}; you've been timed out for {} minutes.",
+                    cfg.mute_secs / 60
+                ),
+            )
+            .await?;
+            "User timed out"
+        },
+        Penalty::Kick => {
+            gid.kick_with_reason(&ctx.http, user, &reason)
+                .await
+                .context("Error kicking spammer")?;
+
+            "User kicked"
+        },
+    };
+
+    botlog::record(
+        ctx,
+        gid,
+        botlog::Entry::new(action, bot)
+            .with_target(user.mention().to_string())
+            .with_reason(reason),
+    )
+    .await;
+
+    Ok(true)
+}
+
+#[derive(Debug)]
+pub struct AntiSpamCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for AntiSpamCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}antispam", opts.command_base),
+        }
+    }
+}
+
+impl AntiSpamCommand {
+    /// Save new settings for a guild, or turn anti-spam off if `None`
+    async fn save(ctx: &Context, gid: GuildId, cfg: Option<guild::AntiSpam>) -> Result {
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        SETTINGS
+            .update(&storage, gid, |g| {
+                if cfg.is_none() {
+                    g.spam_strikes.clear();
+                }
+                g.anti_spam.clone_from(&cfg);
+                ((), cfg.map(Arc::new))
+            })
+            .await
+            .context("Error saving anti-spam settings")?;
+
+        Ok(())
+    }
+
+    async fn set<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let mut cfg = settings(&storage, gid)
+            .await?
+            .map_or_else(defaults, |c| (*c).clone());
+
+        for (name, field, scale) in [
+            ("max_messages", &mut cfg.max_messages, 1),
+            ("window", &mut cfg.window, 1),
+            ("max_repeats", &mut cfg.max_duplicates, 1),
+            ("max_mentions", &mut cfg.max_mentions, 1),
+            ("timeout_at", &mut cfg.mute_at, 1),
+            ("timeout_minutes", &mut cfg.mute_secs, 60),
+            ("kick_at", &mut cfg.kick_at, 1),
+            ("strike_hours", &mut cfg.strike_ttl, 3600),
+        ] {
+            if let Some(val) = visitor.visit_i64(name)?.optional() {
+                *field = u32::try_from(val)
+                    .ok()
+                    .and_then(|v| v.checked_mul(scale))
+                    .with_context(|| format!("{name} out of range"))?;
+            }
+        }
+
+        if let Err(e) = validate(&cfg) {
+            return Err(responder
+                .create_message(Message::plain(e).ephemeral(true))
+                .await
+                .context("Error sending settings error")?
+                .into_err("Invalid anti-spam settings"));
+        }
+
+        Self::save(ctx, gid, Some(cfg.clone())).await?;
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new("Anti-spam settings changed", user),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| describe(mb.push_line("Anti-spam is on:"), &cfg))
+                    .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn disable<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let user = visitor.user().id;
+
+        Self::save(ctx, gid, None).await?;
+
+        botlog::record(ctx, gid, botlog::Entry::new("Anti-spam disabled", user)).await;
+
+        Ok(responder
+            .create_message(
+                Message::plain("Anti-spam is off and all strikes have been cleared.")
+                    .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn exempt<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let role = visitor.visit_role("role")?.required()?.id;
+        let remove = visitor.visit_bool("remove")?.optional().unwrap_or(false);
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let Some(cfg) = settings(&storage, gid).await? else {
+            return Err(responder
+                .create_message(Message::plain("Anti-spam is off in this server.").ephemeral(true))
+                .await
+                .context("Error sending disabled error")?
+                .into_err("Anti-spam not enabled"));
+        };
+
+        let mut cfg = (*cfg).clone();
+        cfg.exempt_roles.retain(|&r| r != role.get());
+        if !remove {
+            cfg.exempt_roles.push(role.get());
+        }
+
+        if let Err(e) = validate(&cfg) {
+            return Err(responder
+                .create_message(Message::plain(e).ephemeral(true))
+                .await
+                .context("Error sending settings error")?
+                .into_err("Invalid anti-spam settings"));
+        }
+
+        Self::save(ctx, gid, Some(cfg)).await?;
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new(
+                if remove {
+                    "Anti-spam exemption removed"
+                } else {
+                    "Anti-spam exemption added"
+                },
+                user,
+            )
+            .with_target(role.mention().to_string()),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.role(role).push(if remove {
+                        " is no longer exempt from anti-spam."
+                    } else {
+                        " is now exempt from anti-spam."
+                    })
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn show<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild = storage.guild(gid).await?;
+
+        let msg = match guild.anti_spam {
+            None => Message::plain("Anti-spam is off in this server."),
+            Some(ref cfg) => {
+                let now = Timestamp::now().unix_timestamp();
+                let active = guild
+                    .spam_strikes
+                    .values()
+                    .filter(|s| now - s.last_at < i64::from(cfg.strike_ttl))
+                    .count();
+
+                Message::rich(|mb| {
+                    describe(mb.push_line("Anti-spam is on:"), cfg)
+                        .push(format!("\n{active} user(s) currently have strikes."))
+                })
+            },
+        };
+
+        Ok(responder
+            .create_message(msg.ephemeral(true))
+            .await
+            .context("Error sending anti-spam settings")?
+            .into())
+    }
+
+    async fn pardon<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let (target, _) = visitor.visit_user("user")?.required()?;
+        let target = target.id;
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let removed = storage
+            .update_guild(gid, |g| g.spam_strikes.remove(&target.get()))
+            .await
+            .context("Error clearing anti-spam strikes")?;
+
+        let Some(removed) = removed else {
+            return Err(responder
+                .create_message(
+                    Message::rich(|mb| mb.mention(&target).push(" has no anti-spam strikes."))
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending missing strikes error")?
+                .into_err("User has no strikes"));
+        };
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new("Anti-spam strikes cleared", user)
+                .with_target(target.mention().to_string())
+                .with_reason(format!("{} strike(s)", removed.count)),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push("Cleared the strikes of ")
+                        .mention(&target)
+                        .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for AntiSpamCommand {
+    fn register_global(&self) -> CommandInfo {
+        let max_limit = i64::from(MAX_LIMIT);
+        let max_strikes = i64::from(MAX_STRIKES);
+
+        CommandInfo::build_slash(&self.name, "Manage automatic spam detection", |a| {
+            a.build_subcmd("set", "Turn on anti-spam or change its settings", |a| {
+                a.int(
+                    "max_messages",
+                    "Most messages a user can send per window, or 0 for no limit (default: 5)",
+                    false,
+                    0..=max_limit,
+                )
+                .int(
+                    "window",
+                    "Seconds over which messages are counted (default: 5)",
+                    false,
+                    1..=i64::from(MAX_WINDOW),
+                )
+                .int(
+                    "max_repeats",
+                    "Most identical messages per window, or 0 for no limit (default: 3)",
+                    false,
+                    0..=max_limit,
+                )
+                .int(
+                    "max_mentions",
+                    "Most mentions in one message, or 0 for no limit (default: 5)",
+                    false,
+                    0..=max_limit,
+                )
+                .int(
+                    "timeout_at",
+                    "Strikes before offenders are timed out, or 0 to never (default: 2)",
+                    false,
+                    0..=max_strikes,
+                )
+                .int(
+                    "timeout_minutes",
+                    "Length of timeouts (default: 10)",
+                    false,
+                    1..=i64::from(MAX_MUTE_SECS / 60),
+                )
+                .int(
+                    "kick_at",
+                    "Strikes before offenders are kicked, or 0 to never (default: 3)",
+                    false,
+                    0..=max_strikes,
+                )
+                .int(
+                    "strike_hours",
+                    "Hours after a user's last strike before their strikes expire (default: 24)",
+                    false,
+                    1..=i64::from(MAX_STRIKE_TTL / 3600),
+                )
+            })
+            .build_subcmd("disable", "Turn off anti-spam and clear all strikes", id)
+            .build_subcmd("exempt", "Exempt a role from anti-spam", |a| {
+                a.role("role", "Role to exempt", true).bool(
+                    "remove",
+                    "Whether to remove the exemption instead (default: false)",
+                    false,
+                )
+            })
+            .build_subcmd("show", "Show this server's anti-spam settings", id)
+            .build_subcmd("pardon", "Clear a user's anti-spam strikes", |a| {
+                a.user("user", "User to pardon", true)
+            })
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        match *visitor.visit_subcmd()? {
+            ["set"] => self.set(ctx, visitor, responder).await,
+            ["disable"] => self.disable(ctx, visitor, responder).await,
+            ["exempt"] => self.exempt(ctx, visitor, responder).await,
+            ["show"] => self.show(ctx, visitor, responder).await,
+            ["pardon"] => self.pardon(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{HashMap, VecDeque},
+        time::{Duration, Instant},
+    };
+
+    use super::{add_strike, check, defaults, penalty, validate, Penalty, Sent, Violation};
+    use crate::proto::guild;
+
+    #[test]
+    fn heuristics() {
+        let cfg = defaults();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut history = VecDeque::new();
+
+        for i in 0..5 {
+            assert_eq!(
+                check(
+                    &cfg,
+                    &mut history,
+                    Sent::new(at(i * 100), &i.to_string()),
+                    0
+                ),
+                None
+            );
+        }
+        assert_eq!(
+            check(&cfg, &mut history, Sent::new(at(500), "x"), 0),
+            Some(Violation::Rate(6))
+        );
+        assert!(history.is_empty());
+
+        for i in 0..3 {
+            assert_eq!(
+                check(&cfg, &mut history, Sent::new(at(i * 2000), "Buy  NOW"), 0),
+                None
+            );
+        }
+        assert_eq!(
+            check(&cfg, &mut history, Sent::new(at(6000), "buy now"), 0),
+            None,
+            "Messages outside the window should be forgotten"
+        );
+        assert_eq!(
+            check(&cfg, &mut history, Sent::new(at(6100), "buy now"), 0),
+            Some(Violation::Duplicates(4))
+        );
+
+        for i in 0..4 {
+            assert_eq!(check(&cfg, &mut history, Sent::new(at(i), ""), 0), None);
+        }
+        assert_eq!(
+            check(&cfg, &mut history, Sent::new(at(10), ""), 6),
+            Some(Violation::Mentions(6))
+        );
+
+        let off = guild::AntiSpam {
+            max_messages: 0,
+            max_duplicates: 0,
+            max_mentions: 0,
+            ..defaults()
+        };
+        for i in 0..20 {
+            assert_eq!(check(&off, &mut history, Sent::new(at(i), "x"), 99), None);
+        }
+    }
+
+    #[test]
+    fn escalation() {
+        let cfg = defaults();
+        let mut strikes = HashMap::new();
+
+        assert_eq!(add_strike(&mut strikes, cfg.strike_ttl, 1, 0), 1);
+        assert_eq!(add_strike(&mut strikes, cfg.strike_ttl, 2, 0), 1);
+        assert_eq!(add_strike(&mut strikes, cfg.strike_ttl, 1, 100), 2);
+        assert_eq!(
+            add_strike(
+                &mut strikes,
+                cfg.strike_ttl,
+                1,
+                100 + i64::from(cfg.strike_ttl)
+            ),
+            1
+        );
+        assert_eq!(strikes.len(), 1);
+
+        assert_eq!(penalty(&cfg, 1), Penalty::Warn);
+        assert_eq!(penalty(&cfg, 2), Penalty::Mute);
+        assert_eq!(penalty(&cfg, 3), Penalty::Kick);
+        assert_eq!(penalty(&cfg, 9), Penalty::Kick);
+
+        let lenient = guild::AntiSpam {
+            mute_at: 0,
+            kick_at: 0,
+            ..defaults()
+        };
+        assert_eq!(penalty(&lenient, 9), Penalty::Warn);
+    }
+
+    #[test]
+    fn validation() {
+        assert!(validate(&defaults()).is_ok());
+
+        for bad in [
+            guild::AntiSpam {
+                window: 0,
+                ..defaults()
+            },
+            guild::AntiSpam {
+                max_mentions: 51,
+                ..defaults()
+            },
+            guild::AntiSpam {
+                kick_at: 2,
+                ..defaults()
+            },
+            guild::AntiSpam {
+                mute_secs: 30,
+                ..defaults()
+            },
+            guild::AntiSpam {
+                exempt_roles: (1..=26).collect(),
+                ..defaults()
+            },
+        ] {
+            assert!(validate(&bad).is_err(), "{bad:?} should be invalid");
+        }
+    }
+}
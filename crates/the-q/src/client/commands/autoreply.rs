@@ -0,0 +1,748 @@
+use std::time::{Duration, Instant};
+
+use paracord::interaction::command::Choice;
+use qcore::build_with::BuildWith;
+use serenity::{
+    builder::CreateMessage,
+    model::{
+        channel::{Message as ChannelMessage, MessageType, ReactionType},
+        id::{ChannelId, UserId},
+        mention::Mentionable,
+        Permissions,
+    },
+    utils::MessageBuilder,
+};
+use shrec::{
+    dfa::Dfa,
+    re::{
+        run::{Anchors, Matcher},
+        syntax,
+    },
+};
+use tokio::sync::Mutex;
+
+use super::{botlog, guild_cache::GuildCache, prelude::*};
+use crate::{
+    client::storage::{self, Storage},
+    proto::{guild, guild::auto_reply::Action},
+};
+
+/// Most rules a single server can have
+pub(super) const MAX_RULES: usize = 25;
+const MAX_NAME: u16 = 32;
+/// Longest pattern accepted, in characters, to bound the size of its DFA
+const MAX_PATTERN: usize = 100;
+const MAX_RESPONSE: usize = 500;
+const DEFAULT_COOLDOWN: i64 = 30;
+const MAX_COOLDOWN: i64 = 24 * 60 * 60;
+const DEFAULT_WARNING: &str = "{user}, please keep it civil.";
+
+type Pattern = Dfa<char, u64, (), ()>;
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    settings: guild::AutoReply,
+    pattern: Pattern,
+}
+
+/// Compiled auto-reply rules, sorted by name, for each guild whose rules
+/// have been loaded
+static RULES: GuildCache<Arc<Vec<Rule>>> = GuildCache::new();
+
+/// When each rule can next trigger, keyed by channel and rule name
+static COOLDOWNS: Mutex<BTreeMap<(ChannelId, String), Instant>> = Mutex::const_new(BTreeMap::new());
+
+pub(super) fn action_name(action: Action) -> &'static str {
+    match action {
+        Action::Unknown => "unknown",
+        Action::Reply => "reply",
+        Action::React => "react",
+        Action::Delete => "delete",
+        Action::Warn => "warn",
+    }
+}
+
+pub(super) fn parse_action(s: &str) -> Option<Action> {
+    Some(match s {
+        "reply" => Action::Reply,
+        "react" => Action::React,
+        "delete" => Action::Delete,
+        "warn" => Action::Warn,
+        _ => return None,
+    })
+}
+
+/// Lowercase a character if it has a single-character lowercase form, so
+/// that match positions line up with the original text
+fn fold(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+fn compile(pattern: &str, ignore_case: bool) -> Result<Pattern, String> {
+    if pattern.chars().count() > MAX_PATTERN {
+        return Err(format!(
+            "Patterns can be at most {MAX_PATTERN} characters long"
+        ));
+    }
+
+    let source: String = if ignore_case {
+        pattern.chars().map(fold).collect()
+    } else {
+        pattern.into()
+    };
+    let re = syntax::parse(&source).map_err(|e| format!("Invalid pattern: {e}"))?;
+
+    let mut nfa = re.compile();
+    nfa.simplify();
+    let (dfa, _) = nfa.compile().copied().atomize_nodes::<u64>();
+    let dfa = dfa.map_token(|_| ());
+
+    if find(&dfa, "", false).is_some() {
+        return Err("Patterns can't match an empty message".into());
+    }
+
+    Ok(dfa)
+}
+
+/// Find the leftmost-longest match of a pattern in some text, returning the
+/// matched text
+fn find(pattern: &Pattern, text: &str, ignore_case: bool) -> Option<String> {
+    let mut matcher = Matcher::new(pattern, Anchors::default());
+    matcher.feed(text.chars().map(|c| if ignore_case { fold(c) } else { c }));
+    let m = matcher.finish()?;
+
+    Some(text.chars().skip(m.start).take(m.end - m.start).collect())
+}
+
+/// Check a rule for problems, returning a description of the first one found
+pub(super) fn validate(name: &str, rule: &guild::AutoReply) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > MAX_NAME.into() {
+        return Err(format!(
+            "Rule names must be 1 to {MAX_NAME} characters long"
+        ));
+    }
+
+    compile(&rule.pattern, rule.ignore_case)?;
+
+    match rule.action() {
+        Action::Unknown => Err("Unknown rule action".into()),
+        Action::Reply if rule.response.trim().is_empty() => {
+            Err("Reply rules need a response".into())
+        },
+        Action::React if rule.response.parse::<ReactionType>().is_err() => {
+            Err(format!("{:?} is not a valid emoji", rule.response))
+        },
+        Action::Reply | Action::React | Action::Delete | Action::Warn
+            if rule.response.len() > MAX_RESPONSE =>
+        {
+            Err(format!(
+                "Responses can be at most {MAX_RESPONSE} characters long"
+            ))
+        },
+        Action::Reply | Action::React | Action::Delete | Action::Warn => Ok(()),
+    }
+}
+
+fn render<'a>(
+    mb: &'a mut MessageBuilder,
+    mut template: &str,
+    user: UserId,
+    matched: &str,
+) -> &'a mut MessageBuilder {
+    while let Some(start) = template.find('{') {
+        let (text, rest) = template.split_at(start);
+        mb.push(text);
+
+        if let Some(rest) = rest.strip_prefix("{user}") {
+            mb.mention(&user);
+            template = rest;
+        } else if let Some(rest) = rest.strip_prefix("{match}") {
+            mb.push_safe(matched);
+            template = rest;
+        } else {
+            mb.push("{");
+            template = &rest[1..];
+        }
+    }
+
+    mb.push(template)
+}
+
+fn template(rule: &guild::AutoReply) -> &str {
+    match rule.action() {
+        Action::Warn if rule.response.trim().is_empty() => DEFAULT_WARNING,
+        _ => &rule.response,
+    }
+}
+
+/// Find the first rule, by name, matching some text
+fn first_match<'a>(rules: &'a [Rule], text: &str) -> Option<(&'a Rule, String)> {
+    rules
+        .iter()
+        .find_map(|r| find(&r.pattern, text, r.settings.ignore_case).map(|m| (r, m)))
+}
+
+fn compile_all(rules: HashMap<String, guild::AutoReply>) -> Vec<Rule> {
+    let mut rules: Vec<_> = rules
+        .into_iter()
+        .filter_map(
+            |(name, rule)| match compile(&rule.pattern, rule.ignore_case) {
+                Ok(pattern) => Some(Rule {
+                    name,
+                    settings: rule,
+                    pattern,
+                }),
+                Err(e) => {
+                    warn!(
+                        name,
+                        pattern = rule.pattern,
+                        "Skipping invalid auto-reply rule: {e}"
+                    );
+                    None
+                },
+            },
+        )
+        .collect();
+
+    rules.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    rules
+}
+
+async fn rules(storage: &Storage, gid: GuildId) -> Result<Arc<Vec<Rule>>> {
+    RULES.load(storage, gid, |g| Arc::new(compile_all(g.auto_replies))).await
+}
+
+/// Drop the cached auto-reply rules for a guild, or every guild if `gid` is
+/// `None`, after they were changed outside this module
+pub(super) async fn invalidate(gid: Option<GuildId>) { RULES.invalidate(gid).await; }
+
+/// Returns true if the rule is not cooling down in the channel, starting its
+/// cooldown if so
+async fn try_trigger(channel: ChannelId, rule: &Rule) -> bool {
+    let now = Instant::now();
+    let mut cooldowns = COOLDOWNS.lock().await;
+    cooldowns.retain(|_, &mut ready_at| ready_at > now);
+
+    let key = (channel, rule.name.clone());
+    if cooldowns.contains_key(&key) {
+        return false;
+    }
+
+    if rule.settings.cooldown > 0 {
+        cooldowns.insert(
+            key,
+            now + Duration::from_secs(rule.settings.cooldown.into()),
+        );
+    }
+
+    true
+}
+
+/// Apply the first auto-reply rule matching the given message, if any,
+/// returning true if the message was deleted
+///
+/// Message content is only available with the privileged message content
+/// intent, so this should only be called if it was requested.
+pub async fn auto_reply(ctx: &Context, message: &ChannelMessage) -> Result<bool> {
+    let Some(gid) = message.guild_id else {
+        return Ok(false);
+    };
+
+    if message.author.bot
+        || !matches!(
+            message.kind,
+            MessageType::Regular | MessageType::InlineReply
+        )
+    {
+        return Ok(false);
+    }
+
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let rules = rules(&storage, gid).await?;
+    let Some((rule, matched)) = first_match(&rules, &message.content) else {
+        return Ok(false);
+    };
+
+    if !try_trigger(message.channel_id, rule).await {
+        trace!(%gid, channel = %message.channel_id, rule = rule.name, "Auto-reply rule cooling down");
+        return Ok(false);
+    }
+
+    let action = rule.settings.action();
+    let bot = ctx.cache.current_user().id;
+    debug!(%gid, channel = %message.channel_id, message = %message.id, rule = rule.name, action = action_name(action), "Triggering auto-reply rule");
+
+    match action {
+        Action::Unknown => Ok(false),
+        Action::Reply | Action::Warn => {
+            let user = message.author.id;
+            let body: MessageBody<Infallible> =
+                MessageBody::rich(|mb| render(mb, template(&rule.settings), user, &matched))
+                    .ping_users(vec![user]);
+
+            message
+                .channel_id
+                .send_message(
+                    &ctx.http,
+                    CreateMessage::new()
+                        .build_with(body)
+                        .reference_message(message),
+                )
+                .await
+                .context("Error sending auto-reply")?;
+
+            if action == Action::Warn {
+                botlog::record(
+                    ctx,
+                    gid,
+                    botlog::Entry::new("User warned", bot)
+                        .with_target(user.mention().to_string())
+                        .with_reason(format!("Auto-reply rule {:?}", rule.name)),
+                )
+                .await;
+            }
+
+            Ok(false)
+        },
+        Action::React => {
+            let emoji: ReactionType = rule
+                .settings
+                .response
+                .parse()
+                .context("Invalid auto-reply reaction")?;
+            message
+                .react(&ctx.http, emoji)
+                .await
+                .context("Error adding auto-reply reaction")?;
+
+            Ok(false)
+        },
+        Action::Delete => {
+            message
+                .delete(&ctx.http)
+                .await
+                .context("Error deleting message for auto-reply")?;
+
+            botlog::record(
+                ctx,
+                gid,
+                botlog::Entry::new("Message deleted", bot)
+                    .with_target(message.author.mention().to_string())
+                    .with_reason(format!("Auto-reply rule {:?}", rule.name)),
+            )
+            .await;
+
+            Ok(true)
+        },
+    }
+}
+
+#[derive(Debug)]
+pub struct AutoReplyCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for AutoReplyCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}autoreply", opts.command_base),
+        }
+    }
+}
+
+impl AutoReplyCommand {
+    async fn add<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let name = visitor.visit_string("name")?.required()?.trim().to_owned();
+        let pattern = visitor.visit_string("pattern")?.required()?.to_owned();
+        let action = visitor.visit_string("action")?.required()?;
+        let action = parse_action(action).context("Invalid rule action")?;
+        let response = visitor
+            .visit_string("response")?
+            .optional()
+            .unwrap_or_default()
+            .to_owned();
+        let cooldown = visitor
+            .visit_i64("cooldown")?
+            .optional()
+            .unwrap_or(DEFAULT_COOLDOWN);
+        let ignore_case = visitor
+            .visit_bool("ignore_case")?
+            .optional()
+            .unwrap_or(true);
+        let user = visitor.user().id;
+
+        let rule = guild::AutoReply {
+            pattern,
+            ignore_case,
+            action: action.into(),
+            response,
+            cooldown: u32::try_from(cooldown).context("Cooldown out of range")?,
+        };
+
+        if let Err(e) = validate(&name, &rule) {
+            return Err(responder
+                .create_message(Message::plain(e).ephemeral(true))
+                .await
+                .context("Error sending rule error")?
+                .into_err("Invalid auto-reply rule"));
+        }
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let saved = RULES
+            .update(&storage, gid, |g| {
+                let saved = g.auto_replies.len() < MAX_RULES || g.auto_replies.contains_key(&name);
+                if saved {
+                    g.auto_replies.insert(name.clone(), rule);
+                }
+
+                (saved, Arc::new(compile_all(g.auto_replies.clone())))
+            })
+            .await
+            .context("Error saving auto-reply rule")?;
+
+        if !saved {
+            return Err(responder
+                .create_message(
+                    Message::plain(format!(
+                        "This server already has the maximum of {MAX_RULES} rules."
+                    ))
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending limit error")?
+                .into_err("Too many auto-reply rules"));
+        }
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new("Auto-reply rule saved", user).with_target(name.clone()),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push("Saved auto-reply rule ")
+                        .push_bold_safe(name.as_str())
+                        .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn remove<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let name = visitor.visit_string("name")?.required()?.trim().to_owned();
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let removed = RULES
+            .update(&storage, gid, |g| {
+                let removed = g.auto_replies.remove(&name).is_some();
+                (removed, Arc::new(compile_all(g.auto_replies.clone())))
+            })
+            .await
+            .context("Error removing auto-reply rule")?;
+
+        if !removed {
+            return Err(responder
+                .create_message(
+                    Message::rich(|mb| {
+                        mb.push("There's no auto-reply rule named ")
+                            .push_bold_safe(name.as_str())
+                            .push(".")
+                    })
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending missing rule error")?
+                .into_err("Unknown auto-reply rule"));
+        }
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new("Auto-reply rule removed", user).with_target(name.clone()),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push("Removed auto-reply rule ")
+                        .push_bold_safe(name.as_str())
+                        .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn list<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let rules = rules(&storage, gid).await?;
+
+        let msg = if rules.is_empty() {
+            Message::plain("This server has no auto-reply rules.")
+        } else {
+            Message::rich(|mb| {
+                mb.push_bold_line(format!("Auto-reply rules ({}/{MAX_RULES}):", rules.len()));
+                for Rule {
+                    name,
+                    settings: rule,
+                    ..
+                } in &*rules
+                {
+                    mb.push("- ")
+                        .push_bold_safe(name.as_str())
+                        .push(": ")
+                        .push_mono_safe(rule.pattern.as_str())
+                        .push(format!(" \u{2192} {}", action_name(rule.action())));
+
+                    if !rule.response.is_empty() {
+                        mb.push(" ").push_mono_safe(rule.response.as_str());
+                    }
+
+                    mb.push_line(format!(
+                        " ({}{}s cooldown)",
+                        if rule.ignore_case {
+                            "ignoring case, "
+                        } else {
+                            ""
+                        },
+                        rule.cooldown
+                    ));
+                }
+
+                mb
+            })
+        };
+
+        Ok(responder
+            .create_message(msg.ephemeral(true))
+            .await
+            .context("Error sending rule list")?
+            .into())
+    }
+
+    async fn test<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let sample = visitor.visit_string("message")?.required()?;
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let rules = rules(&storage, gid).await?;
+
+        let msg = match first_match(&rules, sample) {
+            None => Message::plain("No auto-reply rules match that message."),
+            Some((
+                Rule {
+                    name,
+                    settings: rule,
+                    ..
+                },
+                matched,
+            )) => Message::rich(|mb| {
+                mb.push("Rule ")
+                    .push_bold_safe(name.as_str())
+                    .push(" matches ")
+                    .push_mono_safe(matched.as_str())
+                    .push(" and would ");
+
+                match rule.action() {
+                    Action::Unknown => mb.push("do nothing."),
+                    Action::Reply => {
+                        render(mb.push_line("reply with:"), template(rule), user, &matched)
+                    },
+                    Action::React => mb.push("react with ").push(rule.response.as_str()),
+                    Action::Delete => mb.push("delete the message."),
+                    Action::Warn => render(
+                        mb.push_line("warn the author with:"),
+                        template(rule),
+                        user,
+                        &matched,
+                    ),
+                }
+            }),
+        };
+
+        Ok(responder
+            .create_message(msg.ephemeral(true))
+            .await
+            .context("Error sending test result")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for AutoReplyCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Manage automatic responses to messages", |a| {
+            a.build_subcmd("add", "Add or replace an auto-reply rule", |a| {
+                a.string("name", "Name of the rule", true, 1..=MAX_NAME)
+                    .string(
+                        "pattern",
+                        "Text to look for, supporting |, *, (), {n,m} and \\ escapes",
+                        true,
+                        1..=100,
+                    )
+                    .string_choice("action", "What to do with matching messages", true, [
+                        Choice::new("Reply", "reply".to_owned()),
+                        Choice::new("React", "react".to_owned()),
+                        Choice::new("Delete", "delete".to_owned()),
+                        Choice::new("Warn", "warn".to_owned()),
+                    ])
+                    .string(
+                        "response",
+                        "Reply template using {user} and {match}, or an emoji to react with",
+                        false,
+                        1..=500,
+                    )
+                    .int(
+                        "cooldown",
+                        "Seconds before the rule can trigger again in a channel (default: 30)",
+                        false,
+                        0..=MAX_COOLDOWN,
+                    )
+                    .bool(
+                        "ignore_case",
+                        "Whether to ignore case when matching (default: true)",
+                        false,
+                    )
+            })
+            .build_subcmd("remove", "Remove an auto-reply rule", |a| {
+                a.string("name", "Name of the rule", true, 1..=MAX_NAME)
+            })
+            .build_subcmd("list", "List this server's auto-reply rules", id)
+            .build_subcmd("test", "Check which rule a message would trigger", |a| {
+                a.string("message", "Sample message to test", true, 1..=2000)
+            })
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        match *visitor.visit_subcmd()? {
+            ["add"] => self.add(ctx, visitor, responder).await,
+            ["remove"] => self.remove(ctx, visitor, responder).await,
+            ["list"] => self.list(ctx, visitor, responder).await,
+            ["test"] => self.test(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compile, compile_all, find, first_match, validate};
+    use crate::proto::{guild, guild::auto_reply::Action};
+
+    fn rule(pattern: &str, action: Action, response: &str) -> guild::AutoReply {
+        guild::AutoReply {
+            pattern: pattern.into(),
+            ignore_case: true,
+            action: action.into(),
+            response: response.into(),
+            cooldown: 0,
+        }
+    }
+
+    #[test]
+    fn matching() {
+        let re = compile("(hello|hi) there", false).unwrap();
+        assert_eq!(
+            find(&re, "oh hi there!", false).as_deref(),
+            Some("hi there")
+        );
+        assert_eq!(find(&re, "oh HI there!", false), None);
+
+        let re = compile("HI th(e)*re", true).unwrap();
+        assert_eq!(
+            find(&re, "oh Hi THEEre!", true).as_deref(),
+            Some("Hi THEEre")
+        );
+
+        assert!(compile("a*", false).is_err());
+        assert!(compile("(a", false).is_err());
+        assert!(compile(&"a".repeat(101), false).is_err());
+    }
+
+    #[test]
+    fn rules() {
+        let rules = compile_all(
+            [
+                ("b".to_owned(), rule("spam", Action::Delete, "")),
+                ("a".to_owned(), rule("spam|eggs", Action::React, "🥚")),
+                ("bad".to_owned(), rule("(", Action::Reply, "x")),
+            ]
+            .into(),
+        );
+        assert_eq!(rules.len(), 2);
+
+        let (first, matched) = first_match(&rules, "SPAM and eggs").unwrap();
+        assert_eq!(first.name, "a");
+        assert_eq!(matched, "SPAM");
+        assert!(first_match(&rules, "ham").is_none());
+
+        assert!(validate("ok", &rule("x", Action::Warn, "")).is_ok());
+        assert!(validate("ok", &rule("x", Action::Reply, " ")).is_err());
+        assert!(validate("ok", &rule("x", Action::Unknown, "")).is_err());
+        assert!(validate("", &rule("x", Action::Delete, "")).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use serenity::builder::CreateAttachment;
+
+use super::{super::backup, botlog, errors::is_owner, prelude::*};
+
+const MAX_FILE: u32 = 8 * 1024 * 1024;
+/// Number of backups to show in the list command
+const LIST_COUNT: usize = 20;
+
+#[derive(Debug)]
+pub struct BackupCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for BackupCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}backup", opts.command_base),
+        }
+    }
+}
+
+impl BackupCommand {
+    async fn run<'a>(
+        &self,
+        backups: &backup::Backups,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        if !backups.configured() {
+            return Err(responder
+                .create_message(Message::plain("No backup target is configured.").ephemeral(true))
+                .await
+                .context("Error sending configuration error")?
+                .into_err("Backup requested without a target"));
+        }
+
+        let responder = responder
+            .defer_message(MessageOpts::default().ephemeral(true))
+            .await
+            .context("Error sending deferred message")?;
+
+        let body = match backups.run().await {
+            Ok(s) => MessageBody::rich(|mb| {
+                mb.push("Backed up ")
+                    .push(s.guilds.to_string())
+                    .push(if s.guilds == 1 { " server" } else { " servers" })
+                    .push(" to ")
+                    .push_mono_safe(s.name)
+                    .push(format!(" ({} bytes).", s.bytes))
+            }),
+            Err(err) => {
+                warn!(?err, "Manual backup failed");
+                MessageBody::rich(|mb| {
+                    mb.push_bold("Backup failed: ")
+                        .push_mono_safe(format!("{err:#}"))
+                })
+            },
+        };
+
+        responder
+            .edit(body)
+            .await
+            .context("Error sending backup report")?;
+
+        Ok(responder.into())
+    }
+
+    async fn list<'a>(
+        &self,
+        backups: &backup::Backups,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let msg = match backups.list().await? {
+            None => {
+                Message::plain("Backups can only be listed when backing up to a local directory.")
+            },
+            Some(names) if names.is_empty() => Message::plain("No backups have been made yet."),
+            Some(names) => Message::rich(|mb| {
+                mb.push_bold(format!("{} backup(s), newest first:", names.len()));
+                for name in names.iter().rev().take(LIST_COUNT) {
+                    mb.push("\n- ").push_mono_safe(name.as_str());
+                }
+                mb
+            }),
+        };
+
+        Ok(responder
+            .create_message(msg.ephemeral(true))
+            .await
+            .context("Error sending backup list")?
+            .into())
+    }
+
+    async fn export<'a>(
+        &self,
+        backups: &backup::Backups,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let data = backups.export(gid).await?;
+
+        Ok(responder
+            .create_message(
+                Message::plain(
+                    "Here is this server's data.  Use the restore command to load it again.",
+                )
+                .attach([CreateAttachment::bytes(data, format!("{gid}.pb"))])
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending server export")?
+            .into())
+    }
+
+    async fn restore<'a>(
+        &self,
+        ctx: &Context,
+        backups: &backup::Backups,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let user = visitor.user().id;
+        let name = visitor.visit_string("name")?.optional();
+        let file = visitor.visit_attachment("file")?.optional();
+        let reason = visitor
+            .visit_string("reason")?
+            .optional()
+            .map(ToOwned::to_owned);
+
+        let source = match (name, file) {
+            (Some(n), None) => Ok(n),
+            (None, Some(f)) => Err(f),
+            _ => {
+                return Err(responder
+                    .create_message(
+                        Message::plain("Please provide either a backup name or a file.")
+                            .ephemeral(true),
+                    )
+                    .await
+                    .context("Error sending argument error")?
+                    .into_err("Restore needs exactly one source"));
+            },
+        };
+
+        let label = match source {
+            Ok(n) => n.to_owned(),
+            Err(f) => f.filename.clone(),
+        };
+
+        if source.is_err_and(|f| f.size > MAX_FILE) {
+            return Err(responder
+                .create_message(Message::plain("That file is too large.").ephemeral(true))
+                .await
+                .context("Error sending file size error")?
+                .into_err("Backup file was too large"));
+        }
+
+        let responder = responder
+            .defer_message(MessageOpts::default().ephemeral(true))
+            .await
+            .context("Error sending deferred message")?;
+
+        let loaded = match source {
+            Ok(name) => backups.load(name).await,
+            Err(file) => match file.download().await {
+                Ok(data) => backup::decode(&data),
+                Err(e) => Err(e).context("Error downloading backup file"),
+            },
+        };
+
+        let backup = match loaded {
+            Ok(b) => b,
+            Err(err) => {
+                debug!(?err, "Error loading backup");
+                responder
+                    .edit(MessageBody::rich(|mb| {
+                        mb.push_bold("Couldn't load that backup: ")
+                            .push_mono_safe(format!("{err:#}"))
+                    }))
+                    .await
+                    .context("Error sending backup load error")?;
+                return Err(responder.into_err("Backup could not be loaded"));
+            },
+        };
+
+        if !backups.restore(&backup, gid).await? {
+            responder
+                .edit(MessageBody::plain(
+                    "That backup doesn't contain any data for this server.",
+                ))
+                .await
+                .context("Error sending restore error")?;
+            return Err(responder.into_err("Backup did not contain the guild"));
+        }
+
+        let entry = botlog::Entry::new("Server data restored", user).with_target(label);
+        botlog::record(ctx, gid, match reason {
+            Some(r) => entry.with_reason(r),
+            None => entry,
+        })
+        .await;
+
+        responder
+            .edit(MessageBody::plain(
+                "Restored this server's data from the backup.",
+            ))
+            .await
+            .context("Error sending restore confirmation")?;
+
+        Ok(responder.into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for BackupCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(
+            &self.name,
+            "Back up or restore the bot's server data (owner only)",
+            |a| {
+                a.build_subcmd("run", "Back up every server now", |a| a)
+                    .build_subcmd("list", "List the available backups", |a| a)
+                    .build_subcmd("export", "Download this server's data", |a| a)
+                    .build_subcmd("restore", "Replace this server's data from a backup", |a| {
+                        a.string("name", "The name of a scheduled backup", false, ..)
+                            .attachment("file", "A file from the export command", false)
+                            .string(
+                                "reason",
+                                "The reason for the restore, for the bot log",
+                                false,
+                                1..=1000,
+                            )
+                    })
+            },
+        )
+        .unwrap()
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        if !is_owner(ctx, visitor.user()).await? {
+            return Err(responder
+                .create_message(
+                    Message::plain("This command can only be used by the bot owner.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Non-owner requested backup"));
+        }
+
+        let backups = backup::get(ctx).await.context("Missing backup context")?;
+
+        match *visitor.visit_subcmd()? {
+            ["run"] => self.run(&backups, responder).await,
+            ["list"] => self.list(&backups, responder).await,
+            ["export"] => self.export(&backups, visitor, responder).await,
+            ["restore"] => self.restore(ctx, &backups, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
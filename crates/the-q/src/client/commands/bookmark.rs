@@ -0,0 +1,577 @@
+use chrono::{DateTime, Utc};
+use paracord::interaction::{
+    state,
+    visitor::{TextRule, TextRuleExt},
+};
+use serenity::{
+    builder::{CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateMessage},
+    model::{
+        channel::Message as ChannelMessage,
+        id::{ChannelId, MessageId, UserId},
+    },
+    utils::{EmbedMessageBuilding, MessageBuilder},
+};
+
+use super::prelude::*;
+use crate::{
+    client::{prefs, storage},
+    proto::user,
+};
+
+/// Most bookmarks a single user can keep
+const MAX_BOOKMARKS: usize = 200;
+/// Most tags on a single bookmark
+const MAX_TAGS: usize = 5;
+/// Longest allowed tag, in characters
+const MAX_TAG_LEN: usize = 32;
+/// Most bookmarks shown in one list or search
+const PAGE_SIZE: usize = 10;
+/// Longest preview of a bookmark's content shown in a list, in characters
+const SNIPPET_LEN: usize = 80;
+const BOOKMARK_COLOR: u32 = 0x0034_98db;
+
+/// Split user input into a list of tags, accepting commas, spaces and leading
+/// `#`s
+fn parse_tags(input: &str) -> Result<Vec<String>, String> {
+    let mut tags = vec![];
+
+    for tag in input.split(|c: char| c == ',' || c.is_whitespace()) {
+        let tag = tag.trim_start_matches('#').to_lowercase();
+        if tag.is_empty() || tags.contains(&tag) {
+            continue;
+        }
+
+        if tag.chars().count() > MAX_TAG_LEN {
+            return Err(format!(
+                "Tags can be at most {MAX_TAG_LEN} characters long."
+            ));
+        }
+
+        if !tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "{tag:?} isn't a valid tag.  Tags can only contain letters, numbers, - and _."
+            ));
+        }
+
+        tags.push(tag);
+    }
+
+    if tags.len() > MAX_TAGS {
+        return Err(format!("A bookmark can have at most {MAX_TAGS} tags."));
+    }
+
+    Ok(tags)
+}
+
+/// Returns true if every term of the query matches the bookmark
+///
+/// Terms beginning with `#` match tags exactly, and all other terms match
+/// the content, author or attachment names case-insensitively.
+fn matches(bookmark: &user::Bookmark, query: &str) -> bool {
+    query.split_whitespace().all(|term| {
+        let term = term.to_lowercase();
+
+        if let Some(tag) = term.strip_prefix('#') {
+            return bookmark.tags.iter().any(|t| *t == tag);
+        }
+
+        bookmark.content.to_lowercase().contains(&term)
+            || bookmark.author_name.to_lowercase().contains(&term)
+            || bookmark.tags.contains(&term)
+            || bookmark
+                .attachments
+                .iter()
+                .any(|a| a.filename.to_lowercase().contains(&term))
+    })
+}
+
+/// Add a bookmark to a user's data, returning it with its assigned ID, or
+/// `None` if the user has no room for it
+fn push(data: &mut user::User, mut bookmark: user::Bookmark) -> Option<user::Bookmark> {
+    if data.bookmarks.len() >= MAX_BOOKMARKS {
+        return None;
+    }
+
+    data.next_bookmark = data.next_bookmark.max(1);
+    bookmark.id = data.next_bookmark;
+    data.next_bookmark += 1;
+    data.bookmarks.push(bookmark.clone());
+
+    Some(bookmark)
+}
+
+fn snapshot(message: &ChannelMessage, guild: Option<GuildId>) -> user::Bookmark {
+    user::Bookmark {
+        id: 0,
+        guild: message.guild_id.or(guild).map_or(0, GuildId::get),
+        channel: message.channel_id.get(),
+        message: message.id.get(),
+        author: message.author.id.get(),
+        author_name: message.author.name.clone(),
+        content: message.content.clone(),
+        attachments: message
+            .attachments
+            .iter()
+            .map(|a| user::Attachment {
+                filename: a.filename.clone(),
+                url: a.url.clone(),
+            })
+            .collect(),
+        tags: vec![],
+        sent_at: message.timestamp.unix_timestamp(),
+        saved_at: 0,
+    }
+}
+
+fn link(bookmark: &user::Bookmark) -> String {
+    MessageId::new(bookmark.message).link(
+        ChannelId::new(bookmark.channel),
+        (bookmark.guild != 0).then(|| GuildId::new(bookmark.guild)),
+    )
+}
+
+fn snippet(bookmark: &user::Bookmark) -> String {
+    let line = bookmark.content.lines().next().unwrap_or_default();
+
+    if line.is_empty() {
+        return match bookmark.attachments.len() {
+            0 => "(no text)".into(),
+            1 => "(1 attachment)".into(),
+            n => format!("({n} attachments)"),
+        };
+    }
+
+    let mut snippet: String = line.chars().take(SNIPPET_LEN).collect();
+    if snippet.len() < bookmark.content.len() {
+        snippet.push('\u{2026}');
+    }
+    snippet
+}
+
+/// The copy of a bookmark sent to its owner
+fn embed(bookmark: &user::Bookmark) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(bookmark.author_name.clone()))
+        .description(bookmark.content.clone())
+        .color(BOOKMARK_COLOR)
+        .field(
+            "Source",
+            format!("[Jump to message]({})", link(bookmark)),
+            false,
+        )
+        .footer(CreateEmbedFooter::new(format!("Bookmark #{}", bookmark.id)));
+
+    if let Some(sent) = DateTime::from_timestamp(bookmark.sent_at, 0) {
+        embed = embed.timestamp(sent);
+    }
+
+    if !bookmark.attachments.is_empty() {
+        let mut mb = MessageBuilder::new();
+        for (i, att) in bookmark.attachments.iter().enumerate() {
+            if i > 0 {
+                mb.push("\n");
+            }
+            mb.push_named_link_safe(att.filename.as_str(), att.url.as_str());
+        }
+        embed = embed.field("Attachments", mb.build(), false);
+    }
+
+    if !bookmark.tags.is_empty() {
+        let mut mb = MessageBuilder::new();
+        for (i, tag) in bookmark.tags.iter().enumerate() {
+            if i > 0 {
+                mb.push(" ");
+            }
+            mb.push_mono_safe(tag.as_str());
+        }
+        embed = embed.field("Tags", mb.build(), false);
+    }
+
+    embed
+}
+
+/// A summary of the given bookmarks, most recent first
+fn list_embed<'a>(
+    title: &str,
+    bookmarks: impl DoubleEndedIterator<Item = &'a user::Bookmark>,
+) -> Embed {
+    let found: Vec<_> = bookmarks.rev().collect();
+
+    Embed::default().title(title).desc_rich(|mb| {
+        if found.is_empty() {
+            mb.push_italic("No bookmarks found");
+        }
+
+        for (i, bookmark) in found.iter().take(PAGE_SIZE).enumerate() {
+            if i > 0 {
+                mb.push("\n");
+            }
+
+            mb.push_bold(format!("#{}", bookmark.id))
+                .push(" ")
+                .push_named_link_safe(bookmark.author_name.as_str(), link(bookmark))
+                .push(" \u{2014} ")
+                .push_safe(snippet(bookmark));
+
+            for tag in &bookmark.tags {
+                mb.push(" ").push_mono_safe(tag.as_str());
+            }
+        }
+
+        if found.len() > PAGE_SIZE {
+            mb.push("\n\n").push_italic(format!(
+                "Showing the {PAGE_SIZE} most recent of {} bookmarks.",
+                found.len()
+            ));
+        }
+
+        mb
+    })
+}
+
+fn tags_rule() -> TextRule<component::Component> {
+    TextRule::new(
+        ComponentPayload::BookmarkTags(component::BookmarkTags {}),
+        "Tags",
+    )
+    .len(0..=100)
+}
+
+#[derive(Debug)]
+pub struct BookmarkCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for BookmarkCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}bookmarks", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for BookmarkCommand {
+    fn register_global(&self) -> CommandInfo {
+        let max_id = i64::from(u32::MAX);
+
+        CommandInfo::build_slash(&self.name, "View and manage your saved messages", |a| {
+            a.build_subcmd("list", "List your most recent bookmarks", |a| {
+                a.string("tag", "Only list bookmarks with this tag", false, 1..=32)
+            })
+            .build_subcmd("search", "Search your bookmarks", |a| {
+                a.string(
+                    "query",
+                    "Words to look for, or #tag to match a tag",
+                    true,
+                    1..=100,
+                )
+            })
+            .build_subcmd("delete", "Delete a bookmark", |a| {
+                a.int(
+                    "id",
+                    "The number of the bookmark to delete",
+                    true,
+                    1..=max_id,
+                )
+            })
+        })
+        .unwrap()
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let uid = visitor.user().id;
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+
+        let msg = match *visitor.visit_subcmd()? {
+            ["list"] => {
+                let tag = visitor
+                    .visit_string("tag")?
+                    .optional()
+                    .map(|t| t.trim_start_matches('#').to_lowercase());
+                let user::User { bookmarks, .. } = storage.user(uid).await?;
+
+                Message::from(list_embed(
+                    "Your bookmarks",
+                    bookmarks
+                        .iter()
+                        .filter(|b| tag.as_ref().is_none_or(|t| b.tags.contains(t))),
+                ))
+            },
+            ["search"] => {
+                let query = visitor.visit_string("query")?.required()?;
+                let user::User { bookmarks, .. } = storage.user(uid).await?;
+
+                Message::from(list_embed(
+                    "Matching bookmarks",
+                    bookmarks.iter().filter(|b| matches(b, query)),
+                ))
+            },
+            ["delete"] => {
+                let id = visitor.visit_i64("id")?.required()?;
+                let id = u64::try_from(id).context("Invalid bookmark ID")?;
+                let removed = storage
+                    .update_user(uid, |u| {
+                        let len = u.bookmarks.len();
+                        u.bookmarks.retain(|b| b.id != id);
+                        u.bookmarks.len() < len
+                    })
+                    .await
+                    .context("Error deleting bookmark")?;
+
+                Message::plain(if removed {
+                    format!("Deleted bookmark #{id}.")
+                } else {
+                    format!("You don't have a bookmark #{id}.")
+                })
+            },
+            [..] => unreachable!(), // TODO: visitor should handle this
+        };
+
+        Ok(responder
+            .create_message(msg.ephemeral(true))
+            .await
+            .context("Error sending bookmarks response")?
+            .into())
+    }
+}
+
+#[derive(Debug)]
+pub struct BookmarkMessageCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for BookmarkMessageCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}Save message", opts.context_menu_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for BookmarkMessageCommand {
+    fn register_global(&self) -> CommandInfo { CommandInfo::message(&self.name) }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let message = visitor.target().message()?;
+        let guild = visitor.guild()?.optional().map(|(g, _)| g);
+        let uid = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        if storage.user(uid).await?.bookmarks.len() >= MAX_BOOKMARKS {
+            return Err(responder
+                .create_message(
+                    Message::plain(format!(
+                        "You already have {MAX_BOOKMARKS} bookmarks.  Delete some to make room."
+                    ))
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending quota error")?
+                .into_err("Bookmark quota reached"));
+        }
+
+        let state = state::get(ctx).await.context("Missing state context")?;
+        let token = state
+            .insert(&responder.interaction_ctx(), &snapshot(message, guild))
+            .await
+            .context("Error storing message to save")?;
+
+        Ok(responder
+            .modal(|s| {
+                Modal::new(
+                    s,
+                    ModalPayload::SaveMessage(modal::SaveMessage { state: token.get() }),
+                    "Save message",
+                )
+                .text_short(
+                    ComponentPayload::BookmarkTags(component::BookmarkTags {}),
+                    "Tags",
+                    |t| {
+                        t.required(false)
+                            .len(0..=100)
+                            .placeholder("Optional, e.g. recipes, todo")
+                    },
+                )
+            })
+            .await
+            .context("Error opening save message modal")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl RpcHandler<Schema, ModalKey> for BookmarkMessageCommand {
+    fn register_keys(&self) -> &'static [ModalKey] { &[ModalKey::SaveMessage] }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        payload: ModalPayload,
+        visitor: &mut ModalVisitor<'_>,
+        responder: ModalResponder<'_, 'a>,
+    ) -> ModalResult<'a> {
+        let ModalPayload::SaveMessage(modal::SaveMessage { state: token }) = payload else {
+            unreachable!(); // TODO: set up an error for this
+        };
+        let [tags] = visitor.visit_text(&[tags_rule()])?;
+        let uid = visitor.user().id;
+
+        let tags = match parse_tags(tags) {
+            Ok(t) => t,
+            Err(e) => {
+                return Err(responder
+                    .create_message(Message::plain(e).ephemeral(true))
+                    .await
+                    .context("Error sending tag error")?
+                    .into_err("Invalid bookmark tags"));
+            },
+        };
+
+        let state = state::get(ctx).await.context("Missing state context")?;
+        let Some(bookmark) = state
+            .take::<user::Bookmark>(token.into())
+            .await
+            .context("Error reading message to save")?
+        else {
+            return Err(responder
+                .create_message(
+                    Message::plain("This save has expired.  Please try again.").ephemeral(true),
+                )
+                .await
+                .context("Error sending expiry error")?
+                .into_err("Unknown or expired bookmark state"));
+        };
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let saved = storage
+            .update_user(uid, |u| {
+                push(u, user::Bookmark {
+                    tags,
+                    saved_at: Utc::now().timestamp(),
+                    ..bookmark
+                })
+            })
+            .await
+            .context("Error saving bookmark")?;
+
+        let Some(bookmark) = saved else {
+            return Err(responder
+                .create_message(
+                    Message::plain(format!(
+                        "You already have {MAX_BOOKMARKS} bookmarks.  Delete some to make room."
+                    ))
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending quota error")?
+                .into_err("Bookmark quota reached"));
+        };
+
+        let prefs = prefs::get(ctx).await.context("Missing prefs context")?;
+        let reply = if prefs.get(uid).await?.dm_opt_out {
+            format!(
+                "Saved as bookmark #{}.  Direct messages are off in your preferences, so no copy \
+                 was sent.",
+                bookmark.id
+            )
+        } else {
+            match UserId::direct_message(
+                uid,
+                &ctx.http,
+                CreateMessage::new().embed(embed(&bookmark)),
+            )
+            .await
+            {
+                Ok(_) => format!("Saved as bookmark #{} and sent to your DMs.", bookmark.id),
+                Err(err) => {
+                    debug!(?err, "Error sending bookmark DM");
+                    format!(
+                        "Saved as bookmark #{}, but I couldn't send you a direct message.",
+                        bookmark.id
+                    )
+                },
+            }
+        };
+
+        Ok(responder
+            .create_message(Message::plain(reply).ephemeral(true))
+            .await
+            .context("Error sending save confirmation")?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{matches, parse_tags, push, user, MAX_BOOKMARKS, MAX_TAGS};
+
+    #[test]
+    fn tags() {
+        assert_eq!(parse_tags("").unwrap(), Vec::<String>::new());
+        assert_eq!(parse_tags(" #Recipes, todo  recipes,,later ").unwrap(), [
+            "recipes", "todo", "later"
+        ]);
+        assert!(parse_tags("no/slashes").is_err());
+        assert!(parse_tags(&"x".repeat(33)).is_err());
+        assert!(parse_tags(&"t ".repeat(MAX_TAGS)).is_ok());
+        assert!(parse_tags(
+            &(0..=MAX_TAGS)
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn search() {
+        let bookmark = user::Bookmark {
+            author_name: "ray".into(),
+            content: "Pancake Recipe".into(),
+            tags: vec!["food".into()],
+            attachments: vec![user::Attachment {
+                filename: "batter.png".into(),
+                url: String::new(),
+            }],
+            ..Default::default()
+        };
+
+        assert!(matches(&bookmark, "pancake"));
+        assert!(matches(&bookmark, "RECIPE ray"));
+        assert!(matches(&bookmark, "#food batter"));
+        assert!(matches(&bookmark, "food"));
+        assert!(!matches(&bookmark, "#recipe"));
+        assert!(!matches(&bookmark, "pancake waffle"));
+    }
+
+    #[test]
+    fn quota() {
+        let mut data = user::User::default();
+
+        let first = push(&mut data, user::Bookmark::default()).unwrap();
+        assert_eq!(first.id, 1);
+        data.bookmarks.clear();
+        assert_eq!(push(&mut data, user::Bookmark::default()).unwrap().id, 2);
+
+        for _ in 1..MAX_BOOKMARKS {
+            assert!(push(&mut data, user::Bookmark::default()).is_some());
+        }
+        assert!(push(&mut data, user::Bookmark::default()).is_none());
+        assert_eq!(data.bookmarks.len(), MAX_BOOKMARKS);
+    }
+}
@@ -0,0 +1,247 @@
+use serenity::{
+    builder::{CreateAllowedMentions, CreateEmbed, CreateMessage},
+    model::{
+        channel::ChannelType,
+        id::{ChannelId, UserId},
+        mention::Mentionable,
+        Permissions, Timestamp,
+    },
+};
+
+use super::prelude::*;
+use crate::client::storage;
+
+const BOT_LOG_COLOR: u32 = 0x0034_98db;
+/// Longest reason shown in a log entry, in characters
+const MAX_REASON: usize = 1000;
+
+/// A consequential action taken by the bot on behalf of a user
+#[derive(Debug, Clone)]
+pub struct Entry {
+    action: String,
+    actor: UserId,
+    target: Option<String>,
+    reason: Option<String>,
+}
+
+impl Entry {
+    /// Describe an action performed by the given user, e.g. "Settings
+    /// imported"
+    pub fn new(action: impl Into<String>, actor: UserId) -> Self {
+        Self {
+            action: action.into(),
+            actor,
+            target: None,
+            reason: None,
+        }
+    }
+
+    /// Set the user, channel or other object the action was applied to
+    #[must_use]
+    pub fn with_target(self, target: impl Into<String>) -> Self {
+        Self {
+            target: Some(target.into()),
+            ..self
+        }
+    }
+
+    /// Set the reason given for the action
+    #[must_use]
+    pub fn with_reason(self, reason: impl Into<String>) -> Self {
+        Self {
+            reason: Some(reason.into()),
+            ..self
+        }
+    }
+
+    fn embed(&self) -> CreateEmbed {
+        let mut embed = CreateEmbed::new()
+            .title(self.action.clone())
+            .color(BOT_LOG_COLOR)
+            .timestamp(Timestamp::now())
+            .field("Actor", self.actor.mention().to_string(), true);
+
+        if let Some(ref target) = self.target {
+            embed = embed.field("Target", target.clone(), true);
+        }
+
+        if let Some(ref reason) = self.reason {
+            embed = embed.field(
+                "Reason",
+                reason.chars().take(MAX_REASON).collect::<String>(),
+                false,
+            );
+        }
+
+        embed
+    }
+}
+
+/// Post an entry to the bot log channel of the given guild, if it has one
+///
+/// This never fails; the action being logged has already happened, so
+/// errors are reported to the tracing log instead.
+pub async fn record(ctx: &Context, gid: GuildId, entry: Entry) {
+    info!(
+        %gid,
+        action = entry.action,
+        actor = %entry.actor,
+        target = entry.target,
+        reason = entry.reason,
+        "Bot action"
+    );
+
+    if let Err(err) = post(ctx, gid, &entry).await {
+        warn!(?err, %gid, "Error posting to bot log");
+    }
+}
+
+async fn post(ctx: &Context, gid: GuildId, entry: &Entry) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let channel = storage.guild(gid).await?.bot_log;
+
+    if channel == 0 {
+        return Ok(());
+    }
+
+    ChannelId::new(channel)
+        .send_message(
+            &ctx.http,
+            CreateMessage::new()
+                .embed(entry.embed())
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await
+        .context("Error sending bot log message")?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct BotLogCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for BotLogCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}botlog", opts.command_base),
+        }
+    }
+}
+
+impl BotLogCommand {
+    async fn set<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let channel = visitor.visit_channel("channel")?.required()?.id;
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        storage
+            .update_guild(gid, |g| g.bot_log = channel.get())
+            .await
+            .context("Error saving bot log settings")?;
+
+        record(
+            ctx,
+            gid,
+            Entry::new("Bot log enabled", user).with_target(channel.mention().to_string()),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push("Bot actions will now be logged in ")
+                        .channel(channel)
+                        .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn disable<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let user = visitor.user().id;
+
+        // Log first so the entry still reaches the channel being disabled
+        record(ctx, gid, Entry::new("Bot log disabled", user)).await;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let prev = storage
+            .update_guild(gid, |g| mem::take(&mut g.bot_log))
+            .await
+            .context("Error saving bot log settings")?;
+
+        Ok(responder
+            .create_message(
+                Message::plain(if prev == 0 {
+                    "The bot log was already disabled."
+                } else {
+                    "Bot log disabled."
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for BotLogCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Configure logging of bot actions", |a| {
+            a.build_subcmd("set", "Set the channel to log bot actions in", |a| {
+                a.channel("channel", "The channel to post log entries in", true, [
+                    ChannelType::Text,
+                ])
+            })
+            .build_subcmd("disable", "Stop logging bot actions", id)
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        match *visitor.visit_subcmd()? {
+            ["set"] => self.set(ctx, visitor, responder).await,
+            ["disable"] => self.disable(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use std::time::Duration;
+
+use serenity::{
+    builder::{CreateThread, EditChannel},
+    model::{
+        channel::{ChannelType, Message as ChannelMessage, MessageType},
+        Permissions,
+    },
+};
+
+use super::{guild_cache::GuildCache, prelude::*};
+use crate::{
+    client::storage::{self, Storage},
+    proto::guild,
+};
+
+/// Longest slowmode interval Discord allows
+const MAX_SLOWMODE: Duration = Duration::from_secs(6 * 60 * 60);
+const MAX_THREAD_NAME: usize = 100;
+const MAX_CONTENT_WORDS: usize = 8;
+const DEFAULT_TEMPLATE: &str = "{content}";
+const FALLBACK_THREAD_NAME: &str = "Discussion";
+
+/// Auto-thread settings, keyed by channel ID, for each guild whose settings
+/// have been loaded
+static AUTO_THREADS: GuildCache<Arc<HashMap<u64, guild::AutoThread>>> = GuildCache::new();
+
+/// Parse a duration of the form `90`, `30s`, `5m` or `1h30m`, or `off`
+fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+
+    if s.eq_ignore_ascii_case("off") {
+        return Some(Duration::ZERO);
+    }
+
+    if let Ok(secs) = s.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let mut secs = 0_u64;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        if digits == 0 {
+            return None;
+        }
+
+        let (num, tail) = rest.split_at(digits);
+        let num: u64 = num.parse().ok()?;
+        let mut chars = tail.chars();
+        let scale = match chars.next()?.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            _ => return None,
+        };
+
+        secs = secs.checked_add(num.checked_mul(scale)?)?;
+        rest = chars.as_str().trim_start();
+    }
+
+    Some(Duration::from_secs(secs))
+}
+
+fn pretty_duration(dur: Duration) -> String {
+    let secs = dur.as_secs();
+    let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    [(hours, 'h'), (mins, 'm'), (secs, 's')]
+        .into_iter()
+        .filter(|&(n, _)| n > 0)
+        .fold(String::new(), |mut s, (n, u)| {
+            s.push_str(&n.to_string());
+            s.push(u);
+            s
+        })
+}
+
+fn thread_name(template: &str, author: &str, content: &str, date: impl fmt::Display) -> String {
+    let content = content
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .take(MAX_CONTENT_WORDS)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut out = String::new();
+    let mut template = template;
+    while let Some(start) = template.find('{') {
+        let (text, rest) = template.split_at(start);
+        out.push_str(text);
+
+        if let Some(rest) = rest.strip_prefix("{author}") {
+            out.push_str(author);
+            template = rest;
+        } else if let Some(rest) = rest.strip_prefix("{content}") {
+            out.push_str(&content);
+            template = rest;
+        } else if let Some(rest) = rest.strip_prefix("{date}") {
+            out.push_str(&date.to_string());
+            template = rest;
+        } else {
+            out.push('{');
+            template = &rest[1..];
+        }
+    }
+    out.push_str(template);
+
+    let name: String = out
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_THREAD_NAME)
+        .collect();
+
+    if name.is_empty() {
+        FALLBACK_THREAD_NAME.into()
+    } else {
+        name
+    }
+}
+
+async fn auto_threads(
+    storage: &Storage,
+    gid: GuildId,
+) -> Result<Arc<HashMap<u64, guild::AutoThread>>> {
+    AUTO_THREADS.load(storage, gid, |g| Arc::new(g.auto_threads)).await
+}
+
+/// Drop the cached auto-thread settings for a guild, or every guild if `gid`
+/// is `None`, after they were changed outside this module
+pub(super) async fn invalidate_auto_threads(gid: Option<GuildId>) {
+    AUTO_THREADS.invalidate(gid).await;
+}
+
+/// Create a thread for the given message if it was posted in a channel with
+/// auto-threading enabled
+pub async fn auto_thread(ctx: &Context, message: &ChannelMessage) -> Result {
+    let Some(gid) = message.guild_id else {
+        return Ok(());
+    };
+
+    if message.author.bot
+        || !matches!(
+            message.kind,
+            MessageType::Regular | MessageType::InlineReply
+        )
+    {
+        return Ok(());
+    }
+
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let settings = auto_threads(&storage, gid).await?;
+    let Some(guild::AutoThread { template }) = settings.get(&message.channel_id.get()) else {
+        return Ok(());
+    };
+
+    // Message content is only available with the privileged intent, in which
+    // case {content} renders as empty
+    let name = thread_name(
+        template,
+        message.author.display_name(),
+        &message.content,
+        message.timestamp.format("%Y-%m-%d"),
+    );
+    debug!(%gid, channel = %message.channel_id, message = %message.id, name, "Creating auto-thread");
+
+    message
+        .channel_id
+        .create_thread_from_message(&ctx.http, message.id, CreateThread::new(name))
+        .await
+        .context("Error creating auto-thread")?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct ChannelCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for ChannelCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}channel", opts.command_base),
+        }
+    }
+}
+
+impl ChannelCommand {
+    async fn slowmode<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let input = visitor.visit_string("duration")?.required()?;
+        let channel = visitor
+            .visit_channel("channel")?
+            .optional()
+            .map_or_else(|| visitor.channel_id(), |c| c.id);
+
+        let Some(delay) = parse_duration(input).filter(|&d| d <= MAX_SLOWMODE) else {
+            return Err(responder
+                .create_message(
+                    Message::rich(|mb| {
+                        mb.push("Couldn't understand ")
+                            .push_mono_safe(input)
+                            .push(" as a slowmode interval.  Try something like ")
+                            .push_mono("30s")
+                            .push(", ")
+                            .push_mono("5m")
+                            .push(" or ")
+                            .push_mono("off")
+                            .push(", up to 6 hours.")
+                    })
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending duration error")?
+                .into_err("Invalid slowmode duration"));
+        };
+
+        let secs = u16::try_from(delay.as_secs()).context("Slowmode interval out of range")?;
+        channel
+            .edit(&ctx.http, EditChannel::new().rate_limit_per_user(secs))
+            .await
+            .context("Error setting channel slowmode")?;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    if delay.is_zero() {
+                        mb.push("Slowmode disabled in ").channel(channel).push(".")
+                    } else {
+                        mb.push("Slowmode in ")
+                            .channel(channel)
+                            .push(" set to ")
+                            .push(pretty_duration(delay))
+                            .push(".")
+                    }
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn autothread<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let enabled = visitor.visit_bool("enabled")?.required()?;
+        let channel = visitor
+            .visit_channel("channel")?
+            .optional()
+            .map_or_else(|| visitor.channel_id(), |c| c.id);
+        let template = visitor
+            .visit_string("name")?
+            .optional()
+            .unwrap_or(DEFAULT_TEMPLATE)
+            .to_owned();
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        AUTO_THREADS
+            .update(&storage, gid, |g| {
+                if enabled {
+                    g.auto_threads
+                        .insert(channel.get(), guild::AutoThread { template });
+                } else {
+                    g.auto_threads.remove(&channel.get());
+                }
+
+                ((), Arc::new(g.auto_threads.clone()))
+            })
+            .await
+            .context("Error saving auto-thread settings")?;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    if enabled {
+                        mb.push("A thread will now be created for every new message in ")
+                    } else {
+                        mb.push("Threads will no longer be created automatically in ")
+                    }
+                    .channel(channel)
+                    .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn rolls<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let verbose = visitor.visit_bool("verbose")?.required()?;
+        let channel = visitor
+            .visit_channel("channel")?
+            .optional()
+            .map_or_else(|| visitor.channel_id(), |c| c.id);
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        storage
+            .update_guild(gid, |g| {
+                g.verbose_rolls.retain(|&c| c != channel.get());
+                if verbose {
+                    g.verbose_rolls.push(channel.get());
+                }
+            })
+            .await
+            .context("Error saving dice roll settings")?;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    if verbose {
+                        mb.push("Dice rolls will now show every die rolled in ")
+                    } else {
+                        mb.push("Dice rolls will now only show their total in ")
+                    }
+                    .channel(channel)
+                    .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for ChannelCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Configure channel utilities", |a| {
+            a.build_subcmd("slowmode", "Set how often members can send messages", |a| {
+                a.string(
+                    "duration",
+                    "Minimum time between messages, e.g. 30s, 5m or off",
+                    true,
+                    1..=16,
+                )
+                .channel(
+                    "channel",
+                    "The channel to configure (default: this channel)",
+                    false,
+                    [ChannelType::Text],
+                )
+            })
+            .build_subcmd(
+                "autothread",
+                "Create a thread for every new message in a channel",
+                |a| {
+                    a.bool("enabled", "Whether to create threads automatically", true)
+                        .channel(
+                            "channel",
+                            "The channel to configure (default: this channel)",
+                            false,
+                            [ChannelType::Text],
+                        )
+                        .string(
+                            "name",
+                            "Thread name template, using {author}, {content} and {date}",
+                            false,
+                            1..=100,
+                        )
+                },
+            )
+            .build_subcmd(
+                "rolls",
+                "Choose how dice rolls are shown in a channel",
+                |a| {
+                    a.bool("verbose", "Whether to show every die rolled", true)
+                        .channel(
+                            "channel",
+                            "The channel to configure (default: this channel)",
+                            false,
+                            [ChannelType::Text],
+                        )
+                },
+            )
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_CHANNELS))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Channels permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Channels permission"));
+        }
+
+        match *visitor.visit_subcmd()? {
+            ["slowmode"] => self.slowmode(ctx, visitor, responder).await,
+            ["autothread"] => self.autothread(ctx, visitor, responder).await,
+            ["rolls"] => self.rolls(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{parse_duration, pretty_duration, thread_name};
+
+    #[test]
+    fn durations() {
+        let secs = |s| parse_duration(s).map(|d| d.as_secs());
+
+        assert_eq!(secs("off"), Some(0));
+        assert_eq!(secs("0"), Some(0));
+        assert_eq!(secs("90"), Some(90));
+        assert_eq!(secs("30s"), Some(30));
+        assert_eq!(secs("5M"), Some(300));
+        assert_eq!(secs("1h 30m"), Some(5400));
+        assert_eq!(secs(""), None);
+        assert_eq!(secs("m"), None);
+        assert_eq!(secs("5"), Some(5));
+        assert_eq!(secs("5x"), None);
+        assert_eq!(secs("5m3"), None);
+
+        assert_eq!(pretty_duration(Duration::from_secs(5400)), "1h30m");
+        assert_eq!(pretty_duration(Duration::from_secs(45)), "45s");
+    }
+
+    #[test]
+    fn thread_names() {
+        assert_eq!(
+            thread_name(
+                "{author}: {content}",
+                "june",
+                "hello  there\nsecond line",
+                "x"
+            ),
+            "june: hello there"
+        );
+        assert_eq!(
+            thread_name("{date} {oops}", "", "", "2024-01-01"),
+            "2024-01-01 {oops}"
+        );
+        assert_eq!(thread_name("{content}", "june", "", "x"), "Discussion");
+        assert_eq!(thread_name(&"a".repeat(150), "", "", "x").len(), 100);
+    }
+}
@@ -0,0 +1,1030 @@
+use std::io::prelude::*;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use paracord::interaction::command::Choice;
+use prost::Message as _;
+use serde_json::{json, Map, Value};
+use serenity::{
+    builder::CreateAttachment,
+    model::{
+        id::{ChannelId, UserId},
+        Permissions,
+    },
+    utils::MessageBuilder,
+};
+use tokio::sync::Mutex;
+
+use super::{antispam, autoreply, botlog, feed::MAX_FEEDS, level, prelude::*};
+use crate::{client::storage, proto::guild};
+
+/// Version of the JSON export format
+const FORMAT_VERSION: u64 = 1;
+/// Largest configuration file accepted for import
+const MAX_FILE: u32 = 1024 * 1024;
+/// How long an import preview can be confirmed for
+const PREVIEW_TTL: TimeDelta = TimeDelta::minutes(10);
+/// Limits matching those enforced by the individual settings commands
+const MAX_THRESHOLD: u32 = 100;
+const MAX_TEMPLATE: usize = 2000;
+
+/// An import awaiting confirmation, keyed by the ID of the interaction that
+/// previewed it
+#[derive(Debug)]
+struct Pending {
+    gid: GuildId,
+    user: UserId,
+    settings: guild::Guild,
+    reason: Option<String>,
+    expires_at: DateTime<Utc>,
+}
+
+static PENDING: Mutex<BTreeMap<u64, Pending>> = Mutex::const_new(BTreeMap::new());
+
+/// Copy the portable settings out of a guild's stored data, dropping any
+/// runtime state such as open polls or feed history
+fn settings(g: &guild::Guild) -> guild::Guild {
+    guild::Guild {
+        welcome: g.welcome.clone(),
+        starboard: g.starboard.as_ref().map(|s| guild::Starboard {
+            channel: s.channel,
+            threshold: s.threshold,
+            posts: HashMap::new(),
+        }),
+        feeds: g
+            .feeds
+            .iter()
+            .map(|f| guild::Feed {
+                url: f.url.clone(),
+                channel: f.channel,
+                ..guild::Feed::default()
+            })
+            .collect(),
+        translate: g.translate.clone(),
+        auto_threads: g.auto_threads.clone(),
+        verbose_rolls: g.verbose_rolls.clone(),
+        bot_log: g.bot_log,
+        voice_stats: g.voice_stats,
+        auto_replies: g.auto_replies.clone(),
+        anti_spam: g.anti_spam.clone(),
+        leveling: g.leveling.clone(),
+        ..guild::Guild::default()
+    }
+}
+
+/// Replace the settings in a guild's stored data with the given ones
+///
+/// Runtime state is kept for feeds and the starboard where the new settings
+/// still refer to the same channel.
+fn apply(g: &mut guild::Guild, new: guild::Guild) {
+    let guild::Guild {
+        welcome,
+        starboard,
+        feeds,
+        translate,
+        auto_threads,
+        verbose_rolls,
+        bot_log,
+        voice_stats,
+        auto_replies,
+        anti_spam,
+        leveling,
+        ..
+    } = new;
+
+    g.starboard = starboard.map(|mut s| {
+        if let Some(old) = g.starboard.take().filter(|o| o.channel == s.channel) {
+            s.posts = old.posts;
+        }
+        s
+    });
+
+    let mut old_feeds = mem::take(&mut g.feeds);
+    g.feeds = feeds
+        .into_iter()
+        .map(|f| {
+            old_feeds
+                .iter()
+                .position(|o| o.url == f.url && o.channel == f.channel)
+                .map_or(f, |i| old_feeds.swap_remove(i))
+        })
+        .collect();
+
+    g.welcome = welcome;
+    g.translate = translate;
+    g.auto_threads = auto_threads;
+    g.verbose_rolls = verbose_rolls;
+    g.bot_log = bot_log;
+    g.voice_stats = voice_stats;
+    if !voice_stats {
+        g.voice_activity.clear();
+    }
+    g.auto_replies = auto_replies;
+    if anti_spam.is_none() {
+        g.spam_strikes.clear();
+    }
+    g.anti_spam = anti_spam;
+    if leveling.is_none() {
+        g.xp.clear();
+    }
+    g.leveling = leveling;
+}
+
+fn to_json(cfg: &guild::Guild) -> Value {
+    json!({
+        "version": FORMAT_VERSION,
+        "welcome": cfg.welcome.as_ref().map(|w| json!({
+            "channel": w.channel.to_string(),
+            "template": w.template,
+        })),
+        "starboard": cfg.starboard.as_ref().map(|s| json!({
+            "channel": s.channel.to_string(),
+            "threshold": s.threshold,
+        })),
+        "feeds": cfg.feeds.iter().map(|f| json!({
+            "url": f.url,
+            "channel": f.channel.to_string(),
+        })).collect::<Vec<_>>(),
+        "translate": cfg.translate.as_ref().map(|t| json!({ "target": t.target })),
+        "auto_threads": cfg
+            .auto_threads
+            .iter()
+            .map(|(c, a)| (c.to_string(), json!({ "template": a.template })))
+            .collect::<Map<_, _>>(),
+        "verbose_rolls": cfg
+            .verbose_rolls
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        "bot_log": (cfg.bot_log != 0).then(|| cfg.bot_log.to_string()),
+        "voice_stats": cfg.voice_stats,
+        "auto_replies": cfg
+            .auto_replies
+            .iter()
+            .map(|(n, r)| (n.clone(), json!({
+                "pattern": r.pattern,
+                "ignore_case": r.ignore_case,
+                "action": autoreply::action_name(r.action()),
+                "response": r.response,
+                "cooldown": r.cooldown,
+            })))
+            .collect::<Map<_, _>>(),
+        "anti_spam": cfg.anti_spam.as_ref().map(|a| json!({
+            "max_messages": a.max_messages,
+            "window": a.window,
+            "max_duplicates": a.max_duplicates,
+            "max_mentions": a.max_mentions,
+            "exempt_roles": a.exempt_roles.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "mute_at": a.mute_at,
+            "mute_secs": a.mute_secs,
+            "kick_at": a.kick_at,
+            "strike_ttl": a.strike_ttl,
+        })),
+        "leveling": cfg.leveling.as_ref().map(|l| json!({
+            "message_xp": l.message_xp,
+            "message_cooldown": l.message_cooldown,
+            "voice_xp": l.voice_xp,
+            "base": l.base,
+            "curve": level::curve_name(l.curve()),
+            "rewards": l
+                .rewards
+                .iter()
+                .map(|(n, r)| (n.to_string(), json!(r.to_string())))
+                .collect::<Map<_, _>>(),
+            "announce_channel": (l.announce_channel != 0).then(|| l.announce_channel.to_string()),
+        })),
+    })
+}
+
+fn field<'a>(obj: &'a Value, path: &str, key: &str) -> Result<&'a Value, String> {
+    obj.get(key)
+        .ok_or_else(|| format!("{path} is missing {key:?}"))
+}
+
+fn id_value(v: &Value, path: &str) -> Result<u64, String> {
+    match v {
+        Value::String(s) => s.parse().ok(),
+        Value::Number(n) => n.as_u64(),
+        _ => None,
+    }
+    .filter(|&i| i != 0)
+    .ok_or_else(|| format!("{path} is not a valid ID"))
+}
+
+fn str_value(v: &Value, path: &str) -> Result<String, String> {
+    v.as_str()
+        .map(Into::into)
+        .ok_or_else(|| format!("{path} is not a string"))
+}
+
+fn section<T>(
+    root: &Value,
+    key: &str,
+    f: impl FnOnce(&Value) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    match root.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) if v.is_object() => f(v).map(Some),
+        Some(_) => Err(format!("{key} is not an object")),
+    }
+}
+
+fn auto_reply(v: &Value, path: &str) -> Result<guild::AutoReply, String> {
+    let action = str_value(field(v, path, "action")?, &format!("{path}.action"))?;
+    let action = autoreply::parse_action(&action)
+        .ok_or_else(|| format!("{path}.action is not a valid action"))?;
+    let cooldown = field(v, path, "cooldown")?
+        .as_u64()
+        .and_then(|c| u32::try_from(c).ok())
+        .ok_or_else(|| format!("{path}.cooldown is not a valid number"))?;
+
+    Ok(guild::AutoReply {
+        pattern: str_value(field(v, path, "pattern")?, &format!("{path}.pattern"))?,
+        ignore_case: field(v, path, "ignore_case")?
+            .as_bool()
+            .ok_or_else(|| format!("{path}.ignore_case is not true or false"))?,
+        action: action.into(),
+        response: str_value(field(v, path, "response")?, &format!("{path}.response"))?,
+        cooldown,
+    })
+}
+
+fn anti_spam(v: &Value) -> Result<guild::AntiSpam, String> {
+    let num = |key: &str| {
+        field(v, "anti_spam", key)?
+            .as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .ok_or_else(|| format!("anti_spam.{key} is not a valid number"))
+    };
+    let exempt_roles = match field(v, "anti_spam", "exempt_roles")? {
+        Value::Array(a) => a
+            .iter()
+            .enumerate()
+            .map(|(i, r)| id_value(r, &format!("anti_spam.exempt_roles[{i}]")))
+            .collect::<Result<_, String>>()?,
+        _ => return Err("anti_spam.exempt_roles is not a list".into()),
+    };
+
+    Ok(guild::AntiSpam {
+        max_messages: num("max_messages")?,
+        window: num("window")?,
+        max_duplicates: num("max_duplicates")?,
+        max_mentions: num("max_mentions")?,
+        exempt_roles,
+        mute_at: num("mute_at")?,
+        mute_secs: num("mute_secs")?,
+        kick_at: num("kick_at")?,
+        strike_ttl: num("strike_ttl")?,
+    })
+}
+
+fn leveling(v: &Value) -> Result<guild::Leveling, String> {
+    let num = |key: &str| {
+        field(v, "leveling", key)?
+            .as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .ok_or_else(|| format!("leveling.{key} is not a valid number"))
+    };
+    let curve = str_value(field(v, "leveling", "curve")?, "leveling.curve")?;
+    let curve = level::parse_curve(&curve).ok_or("leveling.curve is not a valid curve")?;
+    let rewards = match field(v, "leveling", "rewards")? {
+        Value::Object(o) => o
+            .iter()
+            .map(|(k, r)| {
+                let path = format!("leveling.rewards[{k:?}]");
+                let level = k
+                    .parse::<u32>()
+                    .map_err(|_| format!("{path} is not a valid level"))?;
+                Ok((level, id_value(r, &path)?))
+            })
+            .collect::<Result<_, String>>()?,
+        _ => return Err("leveling.rewards is not an object".into()),
+    };
+    let announce_channel = match v.get("announce_channel") {
+        None | Some(Value::Null) => 0,
+        Some(c) => id_value(c, "leveling.announce_channel")?,
+    };
+
+    Ok(guild::Leveling {
+        message_xp: num("message_xp")?,
+        message_cooldown: num("message_cooldown")?,
+        voice_xp: num("voice_xp")?,
+        base: num("base")?,
+        curve: curve.into(),
+        rewards,
+        announce_channel,
+    })
+}
+
+fn feeds(root: &Value) -> Result<Vec<guild::Feed>, String> {
+    match root.get("feeds") {
+        None | Some(Value::Null) => Ok(vec![]),
+        Some(Value::Array(a)) => a
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let path = format!("feeds[{i}]");
+                Ok(guild::Feed {
+                    url: str_value(field(f, &path, "url")?, &format!("{path}.url"))?,
+                    channel: id_value(field(f, &path, "channel")?, &format!("{path}.channel"))?,
+                    ..guild::Feed::default()
+                })
+            })
+            .collect(),
+        Some(_) => Err("feeds is not a list".into()),
+    }
+}
+
+fn auto_threads(root: &Value) -> Result<HashMap<u64, guild::AutoThread>, String> {
+    match root.get("auto_threads") {
+        None | Some(Value::Null) => Ok(HashMap::new()),
+        Some(Value::Object(o)) => o
+            .iter()
+            .map(|(k, v)| {
+                let path = format!("auto_threads[{k:?}]");
+                let channel = id_value(&Value::String(k.clone()), &path)?;
+                let template =
+                    str_value(field(v, &path, "template")?, &format!("{path}.template"))?;
+                Ok((channel, guild::AutoThread { template }))
+            })
+            .collect(),
+        Some(_) => Err("auto_threads is not an object".into()),
+    }
+}
+
+fn from_json(root: &Value) -> Result<guild::Guild, String> {
+    if !root.is_object() {
+        return Err("The file is not a JSON object".into());
+    }
+
+    match root.get("version").and_then(Value::as_u64) {
+        Some(FORMAT_VERSION) => (),
+        Some(v) => return Err(format!("Unsupported format version {v}")),
+        None => return Err("The file is missing a format version".into()),
+    }
+
+    let welcome = section(root, "welcome", |w| {
+        Ok(guild::Welcome {
+            channel: id_value(field(w, "welcome", "channel")?, "welcome.channel")?,
+            template: str_value(field(w, "welcome", "template")?, "welcome.template")?,
+        })
+    })?;
+
+    let starboard = section(root, "starboard", |s| {
+        let threshold = field(s, "starboard", "threshold")?
+            .as_u64()
+            .and_then(|t| u32::try_from(t).ok())
+            .ok_or("starboard.threshold is not a valid number")?;
+
+        Ok(guild::Starboard {
+            channel: id_value(field(s, "starboard", "channel")?, "starboard.channel")?,
+            threshold,
+            posts: HashMap::new(),
+        })
+    })?;
+
+    let translate = section(root, "translate", |t| {
+        Ok(guild::Translate {
+            target: str_value(field(t, "translate", "target")?, "translate.target")?,
+        })
+    })?;
+
+    let feeds = feeds(root)?;
+    let auto_threads = auto_threads(root)?;
+
+    let verbose_rolls = match root.get("verbose_rolls") {
+        None | Some(Value::Null) => vec![],
+        Some(Value::Array(a)) => a
+            .iter()
+            .enumerate()
+            .map(|(i, c)| id_value(c, &format!("verbose_rolls[{i}]")))
+            .collect::<Result<_, String>>()?,
+        Some(_) => return Err("verbose_rolls is not a list".into()),
+    };
+
+    let bot_log = match root.get("bot_log") {
+        None | Some(Value::Null) => 0,
+        Some(c) => id_value(c, "bot_log")?,
+    };
+
+    let voice_stats = match root.get("voice_stats") {
+        None | Some(Value::Null) => false,
+        Some(Value::Bool(b)) => *b,
+        Some(_) => return Err("voice_stats is not true or false".into()),
+    };
+
+    let auto_replies = match root.get("auto_replies") {
+        None | Some(Value::Null) => HashMap::new(),
+        Some(Value::Object(o)) => o
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), auto_reply(v, &format!("auto_replies[{k:?}]"))?)))
+            .collect::<Result<_, String>>()?,
+        Some(_) => return Err("auto_replies is not an object".into()),
+    };
+
+    let anti_spam = section(root, "anti_spam", anti_spam)?;
+    let leveling = section(root, "leveling", leveling)?;
+
+    Ok(guild::Guild {
+        welcome,
+        starboard,
+        feeds,
+        translate,
+        auto_threads,
+        verbose_rolls,
+        bot_log,
+        voice_stats,
+        auto_replies,
+        anti_spam,
+        leveling,
+        ..guild::Guild::default()
+    })
+}
+
+/// Encode settings as a compact base64k string of their protobuf form
+fn to_compact(cfg: &guild::Guild) -> String {
+    let mut enc = base64k::Encoder::<String>::default();
+    enc.write_all(&cfg.encode_to_vec())
+        .unwrap_or_else(|_| unreachable!());
+    enc.finish()
+}
+
+fn from_compact(s: &str) -> Result<guild::Guild, String> {
+    let mut raw = vec![];
+    base64k::Decoder::new(s.trim().chars())
+        .read_to_end(&mut raw)
+        .map_err(|_| "The file is not valid JSON or compact settings text")?;
+
+    guild::Guild::decode(&*raw)
+        .map(|g| settings(&g))
+        .map_err(|_| "The compact settings text is corrupt".into())
+}
+
+/// Parse an exported settings file in either format
+fn parse(data: &[u8]) -> Result<guild::Guild, String> {
+    let text = std::str::from_utf8(data).map_err(|_| "The file is not valid UTF-8")?;
+
+    if text.trim_start().starts_with('{') {
+        let root: Value =
+            serde_json::from_str(text).map_err(|e| format!("The file is not valid JSON: {e}"))?;
+        from_json(&root)
+    } else {
+        from_compact(text)
+    }
+}
+
+/// Check imported settings for problems, given the IDs of the channels in
+/// the guild they are being imported into
+fn validate(cfg: &guild::Guild, channels: &HashSet<u64>) -> Vec<String> {
+    let mut errs = vec![];
+    let mut check_channel = |id: u64, what: &str| {
+        if !channels.contains(&id) {
+            errs.push(format!("The {what} channel doesn't exist in this server"));
+        }
+    };
+
+    if let Some(ref w) = cfg.welcome {
+        check_channel(w.channel, "welcome");
+    }
+    if let Some(ref s) = cfg.starboard {
+        check_channel(s.channel, "starboard");
+    }
+    for f in &cfg.feeds {
+        check_channel(f.channel, "feed");
+    }
+    for &c in cfg.auto_threads.keys() {
+        check_channel(c, "auto-thread");
+    }
+    for &c in &cfg.verbose_rolls {
+        check_channel(c, "dice roll");
+    }
+    if cfg.bot_log != 0 {
+        check_channel(cfg.bot_log, "bot log");
+    }
+    if let Some(ref l) = cfg.leveling {
+        if l.announce_channel != 0 {
+            check_channel(l.announce_channel, "level-up");
+        }
+    }
+
+    if let Some(ref w) = cfg.welcome {
+        if w.template.trim().is_empty() || w.template.len() > MAX_TEMPLATE {
+            errs.push("The welcome message is empty or too long".into());
+        }
+    }
+
+    if let Some(ref s) = cfg.starboard {
+        if !(1..=MAX_THRESHOLD).contains(&s.threshold) {
+            errs.push(format!(
+                "The starboard threshold must be between 1 and {MAX_THRESHOLD}"
+            ));
+        }
+    }
+
+    if cfg.feeds.len() > MAX_FEEDS {
+        errs.push(format!("There are more than {MAX_FEEDS} feeds"));
+    }
+    for f in &cfg.feeds {
+        if !Url::parse(&f.url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) {
+            errs.push(format!("{:?} is not a valid feed link", f.url));
+        }
+    }
+
+    if let Some(ref t) = cfg.translate {
+        if t.target.is_empty() || !t.target.chars().all(|c| c.is_ascii_alphabetic()) {
+            errs.push(format!("{:?} is not a valid language code", t.target));
+        }
+    }
+
+    if cfg
+        .auto_threads
+        .values()
+        .any(|a| a.template.is_empty() || a.template.len() > MAX_TEMPLATE)
+    {
+        errs.push("An auto-thread name template is empty or too long".into());
+    }
+
+    if cfg.auto_replies.len() > autoreply::MAX_RULES {
+        errs.push(format!(
+            "There are more than {} auto-reply rules",
+            autoreply::MAX_RULES
+        ));
+    }
+    for (name, rule) in &cfg.auto_replies {
+        if let Err(e) = autoreply::validate(name, rule) {
+            errs.push(format!("Auto-reply rule {name:?}: {e}"));
+        }
+    }
+
+    if let Some(ref a) = cfg.anti_spam {
+        if let Err(e) = antispam::validate(a) {
+            errs.push(format!("Anti-spam: {e}"));
+        }
+    }
+
+    if let Some(ref l) = cfg.leveling {
+        if let Err(e) = level::validate(l) {
+            errs.push(format!("Leveling: {e}"));
+        }
+    }
+
+    errs
+}
+
+/// Summarize the settings that an import will apply
+fn describe<'a>(mb: &'a mut MessageBuilder, cfg: &guild::Guild) -> &'a mut MessageBuilder {
+    mb.push_line("Importing will replace this server's settings with:");
+
+    mb.push("- Welcome messages: ");
+    match cfg.welcome {
+        Some(ref w) => mb.push("in ").channel(ChannelId::new(w.channel)),
+        None => mb.push("off"),
+    };
+
+    mb.push("\n- Starboard: ");
+    match cfg.starboard {
+        Some(ref s) => mb
+            .push("in ")
+            .channel(ChannelId::new(s.channel))
+            .push(format!(" at {} stars", s.threshold)),
+        None => mb.push("off"),
+    };
+
+    mb.push("\n- Translation target: ");
+    match cfg.translate {
+        Some(ref t) => mb.push_mono_safe(t.target.as_str()),
+        None => mb.push("default"),
+    };
+
+    mb.push("\n- Bot log: ");
+    match cfg.bot_log {
+        0 => mb.push("off"),
+        c => mb.push("in ").channel(ChannelId::new(c)),
+    };
+
+    mb.push("\n- Voice stats: ")
+        .push(if cfg.voice_stats { "on" } else { "off" });
+
+    mb.push(format!("\n- Feeds: {}", cfg.feeds.len()))
+        .push(format!(
+            "\n- Auto-thread channels: {}",
+            cfg.auto_threads.len()
+        ))
+        .push(format!(
+            "\n- Verbose dice roll channels: {}",
+            cfg.verbose_rolls.len()
+        ))
+        .push(format!("\n- Auto-reply rules: {}", cfg.auto_replies.len()))
+        .push("\n- Anti-spam: ")
+        .push(if cfg.anti_spam.is_some() { "on" } else { "off" })
+        .push("\n- Leveling: ")
+        .push(if cfg.leveling.is_some() { "on" } else { "off" })
+}
+
+#[derive(Debug)]
+pub struct ConfigCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for ConfigCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}config", opts.command_base),
+        }
+    }
+}
+
+impl ConfigCommand {
+    async fn export<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let compact = visitor
+            .visit_string("format")?
+            .optional()
+            .is_some_and(|f| f == "compact");
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let cfg = settings(&storage.guild(gid).await?);
+
+        let attachment = if compact {
+            CreateAttachment::bytes(to_compact(&cfg), "settings.txt")
+        } else {
+            let json =
+                serde_json::to_vec_pretty(&to_json(&cfg)).context("Error serializing settings")?;
+            CreateAttachment::bytes(json, "settings.json")
+        };
+
+        Ok(responder
+            .create_message(
+                Message::plain(
+                    "Here are this server's settings.  Use the import command to restore them.",
+                )
+                .attach([attachment])
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending settings export")?
+            .into())
+    }
+
+    async fn import<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let file = visitor.visit_attachment("file")?.required()?;
+        let reason = visitor
+            .visit_string("reason")?
+            .optional()
+            .map(ToOwned::to_owned);
+        let user = visitor.user().id;
+
+        let parsed = if file.size > MAX_FILE {
+            Err("The file is too large".into())
+        } else {
+            let data = file
+                .download()
+                .await
+                .context("Error downloading settings file")?;
+            parse(&data)
+        };
+
+        let errs = match parsed {
+            Ok(ref cfg) => {
+                let channels = gid
+                    .channels(&ctx.http)
+                    .await
+                    .context("Error fetching guild channels")?
+                    .into_keys()
+                    .map(ChannelId::get)
+                    .collect();
+                validate(cfg, &channels)
+            },
+            Err(ref e) => vec![e.clone()],
+        };
+
+        let settings = match parsed {
+            Ok(cfg) if errs.is_empty() => cfg,
+            _ => {
+                return Err(responder
+                    .create_message(
+                        Message::rich(|mb| {
+                            mb.push("Couldn't import those settings:");
+                            for err in &errs {
+                                mb.push("\n- ").push_safe(err.as_str());
+                            }
+                            mb
+                        })
+                        .ephemeral(true),
+                    )
+                    .await
+                    .context("Error sending import error")?
+                    .into_err("Invalid settings file"));
+            },
+        };
+
+        let id = responder.interaction_ctx().id().get();
+        let now = Utc::now();
+        let expires_at = now + PREVIEW_TTL;
+        let msg = Message::rich(|mb| describe(mb, &settings))
+            .buttons(|b| {
+                b.button(
+                    ComponentPayload::ConfigImport(component::ConfigImport { id, apply: true }),
+                    ButtonStyle::Danger,
+                    "Import",
+                    false,
+                )
+                .button(
+                    ComponentPayload::ConfigImport(component::ConfigImport { id, apply: false }),
+                    ButtonStyle::Secondary,
+                    "Cancel",
+                    false,
+                )
+            })
+            .expire_at(expires_at)
+            .ephemeral(true);
+
+        {
+            let mut pending = PENDING.lock().await;
+            pending.retain(|_, p| p.expires_at > now);
+            pending.insert(id, Pending {
+                gid,
+                user,
+                settings,
+                reason,
+                expires_at,
+            });
+        }
+
+        Ok(responder
+            .create_message(msg)
+            .await
+            .context("Error sending import preview")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for ConfigCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(
+            &self.name,
+            "Back up or restore this server's settings",
+            |a| {
+                a.build_subcmd("export", "Download this server's settings", |a| {
+                    a.string_choice("format", "The format to export in", false, [
+                        Choice::new("JSON", "json".to_owned()),
+                        Choice::new("Compact text", "compact".to_owned()),
+                    ])
+                })
+                .build_subcmd(
+                    "import",
+                    "Replace this server's settings from an export",
+                    |a| {
+                        a.attachment("file", "A settings file from the export command", true)
+                            .string(
+                                "reason",
+                                "The reason for the import, for the bot log",
+                                false,
+                                1..=1000,
+                            )
+                    },
+                )
+            },
+        )
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        match *visitor.visit_subcmd()? {
+            ["export"] => self.export(ctx, visitor, responder).await,
+            ["import"] => self.import(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[async_trait]
+impl RpcHandler<Schema, ComponentKey> for ConfigCommand {
+    fn register_keys(&self) -> &'static [ComponentKey] { &[ComponentKey::ConfigImport] }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        payload: ComponentPayload,
+        visitor: &mut ComponentVisitor<'_>,
+        responder: ComponentResponder<'_, 'a>,
+    ) -> ComponentResult<'a> {
+        let ComponentPayload::ConfigImport(component::ConfigImport {
+            id,
+            apply: confirmed,
+        }) = payload
+        else {
+            unreachable!(); // TODO: set up an error for this
+        };
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let user = visitor.user().id;
+
+        let pending = {
+            let mut pending = PENDING.lock().await;
+            match pending.get(&id) {
+                Some(p) if p.gid == gid && p.user == user => pending.remove(&id),
+                _ => None,
+            }
+        };
+
+        let Some(Pending {
+            settings, reason, ..
+        }) = pending
+        else {
+            return Err(responder
+                .create_message(
+                    Message::plain("This import has expired.  Please run the command again.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending expiry error")?
+                .into_err("Unknown or expired settings import"));
+        };
+
+        if !confirmed {
+            return Ok(responder
+                .update_message(Message::plain("Import cancelled."))
+                .await
+                .context("Error sending cancellation")?
+                .into());
+        }
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        storage
+            .update_guild(gid, |g| apply(g, settings))
+            .await
+            .context("Error saving imported settings")?;
+        super::invalidate_guild_caches(Some(gid)).await;
+        let entry = botlog::Entry::new("Settings imported", user);
+        botlog::record(ctx, gid, match reason {
+            Some(r) => entry.with_reason(r),
+            None => entry,
+        })
+        .await;
+
+        Ok(responder
+            .update_message(Message::plain("Settings imported."))
+            .await
+            .context("Error sending import confirmation")?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{HashMap, HashSet};
+
+    use super::{apply, from_compact, parse, settings, to_compact, to_json, validate};
+    use crate::proto::guild;
+
+    fn example() -> guild::Guild {
+        guild::Guild {
+            welcome: Some(guild::Welcome {
+                channel: 1,
+                template: "hi {user}".into(),
+            }),
+            starboard: Some(guild::Starboard {
+                channel: 2,
+                threshold: 3,
+                posts: [(10, 11)].into(),
+            }),
+            feeds: vec![guild::Feed {
+                url: "https://example.com/feed.xml".into(),
+                channel: 1,
+                seen: vec!["a".into()],
+                ..guild::Feed::default()
+            }],
+            translate: Some(guild::Translate {
+                target: "de".into(),
+            }),
+            auto_threads: [(3, guild::AutoThread {
+                template: "{content}".into(),
+            })]
+            .into(),
+            reminders: [(5, guild::Reminder::default())].into(),
+            verbose_rolls: vec![2],
+            bot_log: 1,
+            voice_stats: true,
+            voice_activity: [(4, guild::VoiceActivity::default())].into(),
+            auto_replies: [("greet".into(), guild::AutoReply {
+                pattern: "hello|hi".into(),
+                ignore_case: true,
+                action: guild::auto_reply::Action::Reply.into(),
+                response: "hi {user}".into(),
+                cooldown: 30,
+            })]
+            .into(),
+            anti_spam: Some(guild::AntiSpam {
+                max_messages: 5,
+                window: 5,
+                max_duplicates: 3,
+                max_mentions: 5,
+                exempt_roles: vec![6],
+                mute_at: 2,
+                mute_secs: 600,
+                kick_at: 3,
+                strike_ttl: 86400,
+            }),
+            spam_strikes: [(7, guild::SpamStrikes::default())].into(),
+            leveling: Some(guild::Leveling {
+                message_xp: 15,
+                message_cooldown: 60,
+                voice_xp: 5,
+                base: 100,
+                curve: guild::leveling::Curve::Quadratic.into(),
+                rewards: [(5, 8)].into(),
+                announce_channel: 2,
+            }),
+            xp: [(9, 1000)].into(),
+            ..guild::Guild::default()
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let cfg = settings(&example());
+        assert!(cfg.reminders.is_empty());
+        assert!(cfg.voice_activity.is_empty());
+        assert!(cfg.spam_strikes.is_empty());
+        assert!(cfg.xp.is_empty());
+        assert!(cfg.feeds[0].seen.is_empty());
+        assert!(cfg.starboard.as_ref().unwrap().posts.is_empty());
+
+        let json = serde_json::to_vec(&to_json(&cfg)).unwrap();
+        assert_eq!(parse(&json).unwrap(), cfg);
+        assert_eq!(from_compact(&to_compact(&cfg)).unwrap(), cfg);
+        assert_eq!(parse(to_compact(&cfg).as_bytes()).unwrap(), cfg);
+
+        assert!(parse(b"{\"version\": 2}").is_err());
+        assert!(parse(b"{\"version\": 1, \"welcome\": {\"channel\": \"x\"}}").is_err());
+    }
+
+    #[test]
+    fn validation() {
+        let cfg = settings(&example());
+        let channels: HashSet<_> = [1, 2, 3].into();
+        assert!(validate(&cfg, &channels).is_empty());
+
+        let mut bad = cfg.clone();
+        bad.starboard.as_mut().unwrap().threshold = 0;
+        bad.feeds[0].url = "ftp://example.com".into();
+        assert_eq!(validate(&bad, &channels).len(), 2);
+        assert_eq!(validate(&cfg, &[1, 2].into()).len(), 1);
+    }
+
+    #[test]
+    fn apply_keeps_state() {
+        let mut g = example();
+        let mut cfg = settings(&g);
+        cfg.feeds.push(guild::Feed {
+            url: "https://example.com/other.xml".into(),
+            channel: 1,
+            ..guild::Feed::default()
+        });
+        cfg.welcome = None;
+
+        apply(&mut g, cfg);
+
+        assert_eq!(g.welcome, None);
+        assert_eq!(g.feeds.len(), 2);
+        assert_eq!(g.feeds[0].seen, ["a"]);
+        assert_eq!(g.starboard.unwrap().posts, HashMap::from([(10, 11)]));
+        assert_eq!(g.reminders.len(), 1);
+        assert_eq!(g.voice_activity.len(), 1);
+        assert_eq!(g.spam_strikes.len(), 1);
+        assert_eq!(g.xp.len(), 1);
+    }
+}
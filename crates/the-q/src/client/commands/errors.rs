@@ -0,0 +1,180 @@
+use paracord::interaction::failure::{ErrorId, HandlerFailure, MemoryFailureLog};
+use serenity::utils::MessageBuilder;
+
+use super::prelude::*;
+
+const DEFAULT_COUNT: i64 = 10;
+const MAX_SUMMARY_LEN: usize = 120;
+
+fn truncate(s: &str, len: usize) -> String {
+    if s.chars().count() <= len {
+        return s.into();
+    }
+
+    let mut out: String = s.chars().take(len).collect();
+    out.push('\u{2026}');
+    out
+}
+
+pub(super) async fn is_owner(ctx: &Context, user: &User) -> Result<bool> {
+    let info = ctx
+        .http
+        .get_current_application_info()
+        .await
+        .context("Error fetching application info")?;
+
+    Ok(info.owner.is_some_and(|o| o.id == user.id)
+        || info
+            .team
+            .is_some_and(|t| t.members.iter().any(|m| m.user.id == user.id)))
+}
+
+fn summary<'a>(mb: &'a mut MessageBuilder, failures: &[HandlerFailure]) -> &'a mut MessageBuilder {
+    if failures.is_empty() {
+        return mb.push_italic("No recent errors.");
+    }
+
+    for failure in failures.iter().rev() {
+        let mut line = MessageBuilder::new();
+        line.push(if mb.0.is_empty() { "" } else { "\n" })
+            .push_mono(failure.id.to_string())
+            .push(format!(" <t:{}:R> ", failure.time.timestamp()))
+            .push(failure.kind)
+            .push(" ")
+            .push_mono_safe(truncate(&failure.name, MAX_SUMMARY_LEN))
+            .push(": ")
+            .push_safe(truncate(&failure.error, MAX_SUMMARY_LEN));
+
+        if mb.0.len() + line.0.len() > response::MAX_CONTENT_LEN {
+            break;
+        }
+
+        mb.push(line.0);
+    }
+
+    mb
+}
+
+fn detail<'a>(mb: &'a mut MessageBuilder, failure: &HandlerFailure) -> &'a mut MessageBuilder {
+    let HandlerFailure {
+        id,
+        time,
+        kind,
+        name,
+        issuer,
+        error,
+    } = failure;
+
+    mb.push_bold("Error ")
+        .push_mono(id.to_string())
+        .push(format!(" <t:{}:F>\n", time.timestamp()))
+        .push(*kind)
+        .push(" ")
+        .push_mono_safe(truncate(name, MAX_SUMMARY_LEN))
+        .push(" from ")
+        .push_safe(issuer.as_str())
+        .push("\n")
+        .push_codeblock_safe(error, None)
+}
+
+#[derive(Debug)]
+pub struct ErrorsCommand {
+    name: String,
+    log: Arc<MemoryFailureLog>,
+}
+
+impl ErrorsCommand {
+    pub fn new(opts: &CommandOpts, log: Arc<MemoryFailureLog>) -> Self {
+        Self {
+            name: format!("{}errors", opts.command_base),
+            log,
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for ErrorsCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "List recent bot errors (owner only)", |a| {
+            a.int(
+                "count",
+                "How many errors to list (default 10)",
+                false,
+                1..=20,
+            )
+            .string("id", "Show the details of a single error", false, 1..=16)
+        })
+        .unwrap()
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let count = visitor
+            .visit_i64("count")?
+            .optional()
+            .unwrap_or(DEFAULT_COUNT);
+        let id = visitor.visit_string("id")?.optional();
+
+        if !is_owner(ctx, visitor.user()).await? {
+            return Err(responder
+                .create_message(
+                    Message::plain("This command can only be used by the bot owner.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Non-owner requested error log"));
+        }
+
+        let Some(id) = id else {
+            let failures = self.log.recent(count.try_into().unwrap_or(0));
+            return Ok(responder
+                .create_message(Message::rich(|mb| summary(mb, &failures)).ephemeral(true))
+                .await
+                .context("Error sending error log")?
+                .into());
+        };
+
+        let failure = id
+            .trim()
+            .parse::<ErrorId>()
+            .ok()
+            .and_then(|id| self.log.get(id));
+
+        let Some(failure) = failure else {
+            return Err(responder
+                .create_message(
+                    Message::rich(|mb| {
+                        mb.push("No recent error found with ID ").push_mono_safe(id)
+                    })
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending unknown ID error")?
+                .into_err("Unknown error ID"));
+        };
+
+        let mut chunks = response::chunk_content(
+            &detail(&mut MessageBuilder::new(), &failure).build(),
+            response::MAX_CONTENT_LEN,
+        )
+        .into_iter();
+        let responder = responder
+            .create_message(Message::plain(chunks.next().unwrap_or_default()).ephemeral(true))
+            .await
+            .context("Error sending error log")?;
+
+        for chunk in chunks {
+            responder
+                .create_followup(Message::plain(chunk).ephemeral(true))
+                .await
+                .context("Error sending error log continuation")?;
+        }
+
+        Ok(responder.into())
+    }
+}
@@ -0,0 +1,479 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use paracord::interaction::visitor::Autocomplete;
+use serenity::{
+    builder::{CreateScheduledEvent, EditScheduledEvent},
+    model::{
+        channel::ChannelType,
+        guild::{ScheduledEvent, ScheduledEventStatus, ScheduledEventType},
+        id::{ChannelId, ScheduledEventId},
+        Permissions,
+    },
+};
+
+use super::{prelude::*, remind::describe_time_error, sound};
+use crate::{
+    client::{prefs, storage},
+    proto::guild,
+    util::time::{self, TimeError},
+};
+
+/// Furthest ahead an event can be scheduled, in days
+const MAX_DAYS: i64 = 365;
+/// Maximum number of suggestions Discord accepts for an autocomplete
+const MAX_COMPLETIONS: usize = 25;
+
+async fn forget(ctx: &Context, gid: GuildId, id: ScheduledEventId) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    storage
+        .update_guild(gid, |g| g.events.remove(&id.get()))
+        .await
+        .context("Error removing scheduled event")?;
+
+    Ok(())
+}
+
+/// Start an event created through the bot once its start time arrives
+///
+/// Discord does not start voice channel events on its own, so without this
+/// an event would sit in the scheduled state until someone started it by
+/// hand.
+async fn start(ctx: &Context, gid: GuildId, id: ScheduledEventId) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    if !storage.guild(gid).await?.events.contains_key(&id.get()) {
+        return Ok(());
+    }
+
+    let event = gid
+        .scheduled_event(&ctx.http, id, false)
+        .await
+        .context("Error fetching scheduled event")?;
+
+    if event.status != ScheduledEventStatus::Scheduled {
+        return Ok(());
+    }
+
+    if *event.start_time > Utc::now() {
+        debug!(%gid, %id, "Scheduled event was moved, rescheduling");
+        schedule(ctx.clone(), gid, id, event.start_time.unix_timestamp());
+        return Ok(());
+    }
+
+    gid.edit_scheduled_event(
+        &ctx.http,
+        id,
+        EditScheduledEvent::new().status(ScheduledEventStatus::Active),
+    )
+    .await
+    .context("Error starting scheduled event")?;
+
+    Ok(())
+}
+
+fn schedule(ctx: Context, gid: GuildId, id: ScheduledEventId, starts_at: i64) {
+    let delay = DateTime::from_timestamp(starts_at, 0)
+        .and_then(|t| (t - Utc::now()).to_std().ok())
+        .unwrap_or_default();
+
+    tokio::task::spawn(
+        async move {
+            tokio::time::sleep(delay).await;
+
+            start(&ctx, gid, id)
+                .await
+                .map_err(|err| error!(?err, "Error starting scheduled event"))
+                .ok();
+        }
+        .instrument(error_span!(parent: None, "scheduled_event", %gid, %id)),
+    );
+}
+
+/// Schedule any pending events in the given guilds to be started
+pub async fn resume(ctx: &Context, guilds: impl IntoIterator<Item = GuildId>) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+
+    for gid in guilds {
+        let guild::Guild { events, .. } = storage.guild(gid).await?;
+
+        for (id, event) in events {
+            debug!(%gid, id, "Resuming scheduled event");
+            schedule(ctx.clone(), gid, ScheduledEventId::new(id), event.starts_at);
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle a scheduled event being updated, playing its sound if it has just
+/// started
+pub async fn update(ctx: &Context, event: &ScheduledEvent) -> Result {
+    let ScheduledEvent {
+        id,
+        guild_id: gid,
+        channel_id,
+        status,
+        ..
+    } = *event;
+
+    match status {
+        ScheduledEventStatus::Active => (),
+        ScheduledEventStatus::Completed | ScheduledEventStatus::Canceled => {
+            return forget(ctx, gid, id).await;
+        },
+        _ => return Ok(()),
+    }
+
+    // Events may be updated several times while active, so the entry is
+    // removed to make sure the sound only plays once
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let Some(guild::ScheduledEvent { channel, sound, .. }) = storage
+        .update_guild(gid, |g| g.events.remove(&id.get()))
+        .await
+        .context("Error removing scheduled event")?
+    else {
+        return Ok(());
+    };
+
+    if sound.is_empty() {
+        return Ok(());
+    }
+
+    let chan = channel_id.unwrap_or(ChannelId::new(channel));
+    let path = sound::sample_path(&sound).context("Invalid sample name for event")?;
+
+    if !sound::play_in(ctx, gid, chan, path).await? {
+        warn!(%gid, %id, "Voice connection busy, skipping event sound");
+    }
+
+    Ok(())
+}
+
+/// Handle a scheduled event being deleted
+#[inline]
+pub async fn delete(ctx: &Context, event: &ScheduledEvent) -> Result {
+    forget(ctx, event.guild_id, event.id).await
+}
+
+#[derive(Debug)]
+pub struct EventCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for EventCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}event", opts.command_base),
+        }
+    }
+}
+
+impl EventCommand {
+    async fn create<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let name = visitor.visit_string("name")?.required()?;
+        let when = visitor.visit_string("start")?.required()?;
+        let channel = visitor.visit_channel("channel")?.required()?.id;
+        let description = visitor.visit_string("description")?.optional();
+        let sound = visitor.visit_string("sound")?.optional();
+        let user = visitor.user().id;
+
+        let prefs = prefs::get(ctx).await.context("Missing prefs context")?;
+        let tz = prefs.get(user).await?.utc_offset();
+        let now = Utc::now();
+
+        let start = time::parse_time(when, now, tz).and_then(|t| {
+            if t - now <= TimeDelta::days(MAX_DAYS) {
+                Ok(t)
+            } else {
+                Err(TimeError::OutOfRange(when.trim().into()))
+            }
+        });
+        let start = match start {
+            Ok(t) => t,
+            Err(err) => {
+                return Err(responder
+                    .create_message(
+                        Message::rich(|mb| describe_time_error(mb, &err)).ephemeral(true),
+                    )
+                    .await
+                    .context("Error sending time error")?
+                    .into_err("Invalid event start time"));
+            },
+        };
+
+        if let Some(sound) = sound {
+            let exists = match sound::sample_path(sound) {
+                Some(p) => tokio::fs::metadata(p).await.is_ok_and(|m| m.is_file()),
+                None => false,
+            };
+
+            if !exists {
+                return Err(responder
+                    .create_message(
+                        Message::rich(|mb| {
+                            mb.push("There's no sound called ")
+                                .push_mono_safe(sound)
+                                .push(".")
+                        })
+                        .ephemeral(true),
+                    )
+                    .await
+                    .context("Error sending sound error")?
+                    .into_err("Invalid event sound"));
+            }
+        }
+
+        let mut builder =
+            CreateScheduledEvent::new(ScheduledEventType::Voice, name, start).channel_id(channel);
+        if let Some(description) = description {
+            builder = builder.description(description);
+        }
+
+        let event = gid
+            .create_scheduled_event(&ctx.http, builder)
+            .await
+            .context("Error creating scheduled event")?;
+
+        let starts_at = start.timestamp();
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        storage
+            .update_guild(gid, |g| {
+                g.events.insert(event.id.get(), guild::ScheduledEvent {
+                    channel: channel.get(),
+                    sound: sound.unwrap_or_default().into(),
+                    starts_at,
+                });
+            })
+            .await
+            .context("Error saving scheduled event")?;
+
+        schedule(ctx.clone(), gid, event.id, starts_at);
+
+        Ok(responder
+            .create_message(
+                Message::plain(format!(
+                    "Scheduled https://discord.com/events/{gid}/{} for <t:{starts_at}:f>.",
+                    event.id
+                ))
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn list<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+
+        let mut events = gid
+            .scheduled_events(&ctx.http, false)
+            .await
+            .context("Error fetching scheduled events")?;
+        events.sort_by_key(|e| e.start_time);
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild::Guild {
+            events: behaviors, ..
+        } = storage.guild(gid).await?;
+
+        let msg = if events.is_empty() {
+            Message::plain("There are no upcoming events.")
+        } else {
+            Message::rich(|mb| {
+                mb.push_line("Upcoming events:");
+
+                for event in &events {
+                    mb.push("- ").push_bold_safe(event.name.as_str());
+
+                    if event.status == ScheduledEventStatus::Active {
+                        mb.push(" (happening now)");
+                    } else {
+                        mb.push(format!(" <t:{}:R>", event.start_time.unix_timestamp()));
+                    }
+
+                    if let Some(chan) = event.channel_id {
+                        mb.push(" in ").channel(chan);
+                    }
+
+                    if let Some(b) = behaviors.get(&event.id.get()) {
+                        if !b.sound.is_empty() {
+                            mb.push(", playing ").push_mono_safe(b.sound.as_str());
+                        }
+                    }
+
+                    mb.push_line("");
+                }
+
+                mb
+            })
+        };
+
+        Ok(responder
+            .create_message(msg.ephemeral(true))
+            .await
+            .context("Error sending event list")?
+            .into())
+    }
+
+    async fn cancel<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let query = visitor.visit_string("event")?.required()?;
+
+        let events = gid
+            .scheduled_events(&ctx.http, false)
+            .await
+            .context("Error fetching scheduled events")?;
+
+        // Autocompleted values are event IDs, but users may also type a name
+        let id = query.parse().ok();
+        let Some(event) = events
+            .iter()
+            .find(|e| Some(e.id.get()) == id || e.name.trim().eq_ignore_ascii_case(query.trim()))
+        else {
+            return Err(responder
+                .create_message(
+                    Message::rich(|mb| {
+                        mb.push("Couldn't find an event called ")
+                            .push_mono_safe(query)
+                            .push(".")
+                    })
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending lookup error")?
+                .into_err("Unknown scheduled event"));
+        };
+
+        gid.delete_scheduled_event(&ctx.http, event.id)
+            .await
+            .context("Error deleting scheduled event")?;
+        forget(ctx, gid, event.id).await?;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push("Cancelled ")
+                        .push_bold_safe(event.name.as_str())
+                        .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for EventCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Manage scheduled events", |a| {
+            a.build_subcmd("create", "Schedule an event in a voice channel", |a| {
+                a.string("name", "The name of the event", true, 1..=100)
+                    .string(
+                        "start",
+                        "When the event starts, e.g. in 2h, tomorrow 9pm or next friday",
+                        true,
+                        1..=100,
+                    )
+                    .channel("channel", "The voice channel to hold the event in", true, [
+                        ChannelType::Voice,
+                    ])
+                    .string("description", "What the event is about", false, 1..=1000)
+                    .string(
+                        "sound",
+                        "A sound for the bot to play when the event starts",
+                        false,
+                        1..=200,
+                    )
+            })
+            .build_subcmd("list", "List this server's upcoming events", id)
+            .build_subcmd("cancel", "Cancel a scheduled event", |a| {
+                a.string("event", "The event to cancel", true, 1..=100)
+                    .autocomplete(true, ["event"])
+            })
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn complete(
+        &self,
+        ctx: &Context,
+        visitor: &mut CompletionVisitor<'_>,
+    ) -> CompletionResult {
+        let ["cancel"] = *visitor.visit_subcmd()? else {
+            return Err(anyhow!("Unexpected subcommand").into());
+        };
+        let Some((gid, _memb)) = visitor.guild()?.optional() else {
+            return Ok(vec![]);
+        };
+
+        let query = visitor
+            .visit_string_autocomplete("event")?
+            .optional()
+            .map_or_else(String::new, |a| match a {
+                Autocomplete::Complete(s) | Autocomplete::Partial(s) => s.to_lowercase(),
+            });
+
+        let events = gid
+            .scheduled_events(&ctx.http, false)
+            .await
+            .context("Error fetching scheduled events")?;
+
+        Ok(events
+            .into_iter()
+            .filter(|e| e.name.to_lowercase().contains(&query))
+            .take(MAX_COMPLETIONS)
+            .map(|e| Completion {
+                name: e.name,
+                value: e.id.to_string().into(),
+            })
+            .collect())
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+        let can_manage = memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_EVENTS));
+
+        let subcmd = visitor.visit_subcmd()?;
+        if !can_manage && !matches!(*subcmd, ["list"]) {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Events permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Events permission"));
+        }
+
+        match *subcmd {
+            ["create"] => self.create(ctx, visitor, responder).await,
+            ["list"] => self.list(ctx, visitor, responder).await,
+            ["cancel"] => self.cancel(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
@@ -1,4 +1,35 @@
+use std::io::Cursor;
+
+use jpeggr::image::{
+    self,
+    codecs::{
+        gif::{GifDecoder, GifEncoder, Repeat},
+        png::PngDecoder,
+        webp::WebPDecoder,
+    },
+    imageops::FilterType,
+    AnimationDecoder, DynamicImage, Frame, Frames, ImageFormat, RgbaImage,
+};
+use paracord::attachment::{DownloadError, Downloader, Format};
+use serenity::{
+    builder::CreateAttachment,
+    model::{channel::Message as ChannelMessage, sticker::StickerItem},
+    utils::parse_emoji,
+};
+
 use super::prelude::*;
+use crate::util::liquid;
+
+/// Largest image the bot will download, in bytes
+const MAX_DOWNLOAD: u64 = 8 * 1024 * 1024;
+/// Largest width or height to carve at; bigger images are scaled down first
+const MAX_SIZE: u32 = 256;
+/// Most frames of an animation to explode
+const MAX_FRAMES: usize = 64;
+/// Fraction of each dimension kept by seam carving
+const SCALE: f64 = 0.5;
+/// GIF quantization speed, from 1 (best) to 30 (fastest)
+const GIF_SPEED: i32 = 10;
 
 #[derive(Debug)]
 pub struct ExplodeCommand {
@@ -35,3 +66,187 @@ impl CommandHandler<Schema> for ExplodeCommand {
             .into())
     }
 }
+
+/// Locate the first custom emoji, sticker or image attachment in a message,
+/// in that order
+fn find_source(message: &ChannelMessage) -> Option<Url> {
+    let content = &message.content;
+    let emoji = content.match_indices('<').find_map(|(start, _)| {
+        let len = content[start..].find('>')?;
+        parse_emoji(&content[start..=start + len])
+    });
+
+    emoji
+        .map(|e| e.url())
+        .into_iter()
+        .chain(
+            message
+                .sticker_items
+                .iter()
+                .filter_map(StickerItem::image_url),
+        )
+        .chain(
+            message
+                .attachments
+                .iter()
+                .filter(|a| {
+                    a.content_type
+                        .as_deref()
+                        .is_some_and(|t| t.starts_with("image/"))
+                })
+                .map(|a| a.url.clone()),
+        )
+        .find_map(|u| u.parse().ok())
+}
+
+/// Decode every frame of an animated image, or the only frame of a still one
+fn frames(data: &[u8], format: ImageFormat) -> Result<Option<Frames<'_>>> {
+    Ok(match format {
+        ImageFormat::Gif => Some(GifDecoder::new(Cursor::new(data))?.into_frames()),
+        ImageFormat::Png => {
+            let dec = PngDecoder::new(Cursor::new(data))?;
+            if dec.is_apng()? {
+                Some(dec.apng()?.into_frames())
+            } else {
+                None
+            }
+        },
+        ImageFormat::WebP => {
+            let dec = WebPDecoder::new(Cursor::new(data))?;
+            dec.has_animation().then(|| dec.into_frames())
+        },
+        _ => None,
+    })
+}
+
+fn explode_frame(image: RgbaImage) -> RgbaImage {
+    let (w, h) = image.dimensions();
+    let image = if w > MAX_SIZE || h > MAX_SIZE {
+        DynamicImage::ImageRgba8(image)
+            .resize(MAX_SIZE, MAX_SIZE, FilterType::Triangle)
+            .into_rgba8()
+    } else {
+        image
+    };
+
+    liquid::explode(&image, SCALE)
+}
+
+/// Explode an image, returning the encoded result and its file extension
+fn explode(data: &[u8], format: ImageFormat) -> Result<(Vec<u8>, &'static str)> {
+    let mut out = vec![];
+
+    if let Some(frames) = frames(data, format).context("Error reading animation")? {
+        // Frames are exploded as they are decoded to avoid holding every
+        // full-size frame in memory at once
+        let frames = frames
+            .take(MAX_FRAMES)
+            .map(|f| {
+                f.map(|f| {
+                    let delay = f.delay();
+                    Frame::from_parts(explode_frame(f.into_buffer()), 0, 0, delay)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .context("Error decoding animation frame")?;
+
+        let mut enc = GifEncoder::new_with_speed(&mut out, GIF_SPEED);
+        enc.set_repeat(Repeat::Infinite)
+            .context("Error encoding animation")?;
+        enc.encode_frames(frames)
+            .context("Error encoding animation")?;
+        drop(enc);
+
+        return Ok((out, "gif"));
+    }
+
+    let image = image::load_from_memory_with_format(data, format)
+        .context("Error reading image data")?
+        .into_rgba8();
+    DynamicImage::ImageRgba8(explode_frame(image))
+        .write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+        .context("Error encoding image")?;
+
+    Ok((out, "png"))
+}
+
+#[derive(Debug)]
+pub struct ExplodeMessageCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for ExplodeMessageCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}Blender Explode", opts.context_menu_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for ExplodeMessageCommand {
+    fn register_global(&self) -> CommandInfo { CommandInfo::message(&self.name) }
+
+    async fn respond<'a>(
+        &self,
+        _: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let message = visitor.target().message()?;
+
+        let Some(url) = find_source(message) else {
+            return Err(responder
+                .create_message(
+                    Message::plain("Target message must contain a custom emoji, sticker or image!")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending missing input error")?
+                .into_err("Target message had no emoji, sticker or image"));
+        };
+
+        let responder = responder
+            .defer_message(MessageOpts::default())
+            .await
+            .context("Error sending deferred message")?;
+        let mut progress = CommandProgress::new(&responder);
+
+        progress.update(None, "Downloading image...").await;
+        let downloader =
+            Downloader::new(http_client(None), Format::IMAGES).with_max_size(MAX_DOWNLOAD);
+        let download = match downloader.fetch(url).await {
+            Ok(d) => d,
+            Err(DownloadError::Http(e)) => {
+                return Err(anyhow::Error::new(e)
+                    .context("Error downloading input image")
+                    .into());
+            },
+            Err(err) => {
+                progress
+                    .fail(format!("Couldn't download that image: {err}"))
+                    .await
+                    .context("Error sending download error")?;
+                return Err(responder.into_err("Input image was rejected"));
+            },
+        };
+        let format = ImageFormat::from_mime_type(download.format().mime())
+            .context("Unsupported format of input image")?;
+        let data = download.into_data();
+
+        progress.update(None, "Exploding...").await;
+        let (bytes, ext) = tokio::task::spawn_blocking(move || explode(&data, format))
+            .await
+            .context("Error running image task")??;
+
+        progress
+            .finish(
+                Message::plain("")
+                    .attach([CreateAttachment::bytes(bytes, format!("explode.{ext}"))]),
+            )
+            .await
+            .context("Error sending exploded image")?;
+
+        Ok(responder.into())
+    }
+}
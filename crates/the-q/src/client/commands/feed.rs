@@ -0,0 +1,810 @@
+use std::time::Duration;
+
+use paracord::capability;
+use reqwest::{header, StatusCode};
+use serenity::{
+    builder::{CreateAllowedMentions, CreateEmbed, CreateEmbedFooter, CreateMessage},
+    model::{channel::ChannelType, id::ChannelId, Permissions},
+};
+use tokio::sync::OnceCell;
+
+use super::prelude::*;
+use crate::{client::storage, proto::guild};
+
+/// Maximum number of feed subscriptions per guild
+pub(super) const MAX_FEEDS: usize = 10;
+/// Largest feed document the bot will download, in bytes
+const MAX_DOWNLOAD: usize = 2 * 1024 * 1024;
+/// Maximum number of new entries posted for a single feed per poll
+const MAX_POSTS: usize = 5;
+/// Maximum number of entry IDs remembered per feed
+const MAX_SEEN: usize = 200;
+const SUMMARY_LEN: usize = 300;
+const FEED_COLOR: u32 = 0x00ee_802f;
+const ACCEPT_FEED: &str =
+    "application/rss+xml, application/atom+xml, application/xml;q=0.9, text/xml;q=0.9, */*;q=0.1";
+
+/// A single entry of an RSS or Atom feed
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Entry {
+    id: String,
+    title: String,
+    link: Option<String>,
+    summary: String,
+    published: Option<String>,
+}
+
+/// The parsed contents of an RSS or Atom feed
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Feed {
+    title: String,
+    entries: Vec<Entry>,
+}
+
+/// Decode XML entities and CDATA sections in element text
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text.trim();
+
+    while !rest.is_empty() {
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let (data, tail) = cdata.split_once("]]>").unwrap_or((cdata, ""));
+            out.push_str(data);
+            rest = tail;
+            continue;
+        }
+
+        let Some(amp) = rest.find(['&', '<']) else {
+            out.push_str(rest);
+            break;
+        };
+
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        if rest.starts_with('<') {
+            out.push('<');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let decoded = rest.find(';').and_then(|semi| {
+            let c = match &rest[1..semi] {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                e => e
+                    .strip_prefix("#x")
+                    .or_else(|| e.strip_prefix("#X"))
+                    .map_or_else(
+                        || e.strip_prefix('#').and_then(|d| d.parse().ok()),
+                        |h| u32::from_str_radix(h, 16).ok(),
+                    )
+                    .and_then(char::from_u32)?,
+            };
+            Some((c, semi))
+        });
+
+        if let Some((c, semi)) = decoded {
+            out.push(c);
+            rest = &rest[semi + 1..];
+        } else {
+            out.push('&');
+            rest = &rest[1..];
+        }
+    }
+
+    out
+}
+
+/// Crudely strip HTML tags from feed summaries and collapse whitespace
+fn strip_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                out.push(' ');
+            },
+            c if !in_tag => out.push(c),
+            _ => (),
+        }
+    }
+
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A single XML element found by [`elements`]
+#[derive(Debug, Clone, Copy)]
+struct Element<'a> {
+    attrs: &'a str,
+    body: &'a str,
+}
+
+impl<'a> Element<'a> {
+    fn attr(&self, name: &str) -> Option<&'a str> {
+        let mut rest = self.attrs;
+
+        while let Some(eq) = rest.find('=') {
+            let key = rest[..eq].trim();
+            let val = rest[eq + 1..].trim_start();
+            let quote = val.chars().next()?;
+            if quote != '"' && quote != '\'' {
+                return None;
+            }
+
+            let end = val[1..].find(quote)?;
+            if key == name {
+                return Some(&val[1..=end]);
+            }
+
+            rest = &val[end + 2..];
+        }
+
+        None
+    }
+
+    fn text(&self) -> String { unescape(self.body) }
+}
+
+/// Iterate over all elements with the given tag name in `xml`, without
+/// descending into matched elements
+fn elements<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = Element<'a>> + 'a {
+    let mut rest = xml;
+
+    std::iter::from_fn(move || loop {
+        let start = rest.find('<')?;
+        rest = &rest[start + 1..];
+
+        let Some(tail) = rest.strip_prefix(name) else {
+            continue;
+        };
+        if !tail.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) {
+            continue;
+        }
+
+        let close = tail.find('>')?;
+        let (attrs, self_closing) = match tail[..close].strip_suffix('/') {
+            Some(a) => (a, true),
+            None => (&tail[..close], false),
+        };
+        rest = &tail[close + 1..];
+
+        if self_closing {
+            return Some(Element { attrs, body: "" });
+        }
+
+        let end_tag = format!("</{name}>");
+        let end = rest.find(&end_tag)?;
+        let body = &rest[..end];
+        rest = &rest[end + end_tag.len()..];
+        return Some(Element { attrs, body });
+    })
+}
+
+fn child_text(xml: &str, names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|n| elements(xml, n).next())
+        .map(|e| e.text())
+        .filter(|t| !t.is_empty())
+}
+
+/// Parse an RSS 2.0 or Atom feed document
+fn parse(xml: &str) -> Result<Feed> {
+    let (entries, head) = if let Some(pos) = xml.find("<item") {
+        (elements(xml, "item").collect::<Vec<_>>(), &xml[..pos])
+    } else if let Some(pos) = xml.find("<entry") {
+        (elements(xml, "entry").collect(), &xml[..pos])
+    } else if xml.contains("<rss") || xml.contains("<feed") {
+        (vec![], xml)
+    } else {
+        bail!("Document is not an RSS or Atom feed");
+    };
+
+    let entries = entries
+        .into_iter()
+        .filter_map(|e| {
+            let xml = e.body;
+            let link = elements(xml, "link")
+                .find(|l| l.attr("rel").map_or(true, |r| r == "alternate"))
+                .and_then(|l| l.attr("href").map(unescape).or_else(|| Some(l.text())))
+                .filter(|l| !l.is_empty());
+            let title = child_text(xml, &["title"]).unwrap_or_default();
+            let id = child_text(xml, &["guid", "id"])
+                .or_else(|| link.clone())
+                .or_else(|| (!title.is_empty()).then(|| title.clone()))?;
+
+            Some(Entry {
+                id,
+                title,
+                link,
+                summary: child_text(xml, &["description", "summary", "content"])
+                    .map(|s| strip_html(&s))
+                    .unwrap_or_default(),
+                published: child_text(xml, &["pubDate", "published", "updated"]),
+            })
+        })
+        .collect();
+
+    Ok(Feed {
+        title: child_text(head, &["title"]).unwrap_or_default(),
+        entries,
+    })
+}
+
+/// Result of a conditional feed request
+enum Fetched {
+    NotModified,
+    Modified {
+        feed: Feed,
+        etag: String,
+        last_modified: String,
+    },
+}
+
+async fn fetch(url: &str, etag: &str, last_modified: &str) -> Result<Fetched> {
+    let mut request = http_client(None)
+        .get(url)
+        .header(header::ACCEPT, ACCEPT_FEED);
+
+    if !etag.is_empty() {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+
+    if !last_modified.is_empty() {
+        request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let mut res = request.send().await.context("Error requesting feed")?;
+
+    if res.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+
+    ensure!(
+        res.status().is_success(),
+        "Feed request returned {}",
+        res.status()
+    );
+
+    let header = |name| {
+        res.headers()
+            .get(name)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_owned()
+    };
+    let etag = header(header::ETAG);
+    let last_modified = header(header::LAST_MODIFIED);
+
+    let mut data = vec![];
+    while let Some(chunk) = res.chunk().await.context("Error downloading feed")? {
+        ensure!(
+            data.len() + chunk.len() <= MAX_DOWNLOAD,
+            "Feed document is too large"
+        );
+        data.extend_from_slice(&chunk);
+    }
+
+    let xml = String::from_utf8_lossy(&data);
+    Ok(Fetched::Modified {
+        feed: parse(&xml)?,
+        etag,
+        last_modified,
+    })
+}
+
+fn truncate(s: &str, len: usize) -> String {
+    match s.char_indices().nth(len) {
+        Some((i, _)) => format!("{}\u{2026}", &s[..i]),
+        None => s.into(),
+    }
+}
+
+fn embed(feed_title: &str, entry: &Entry) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .title(truncate(
+            if entry.title.is_empty() {
+                "(untitled)"
+            } else {
+                &entry.title
+            },
+            250,
+        ))
+        .description(truncate(&entry.summary, SUMMARY_LEN))
+        .color(FEED_COLOR);
+
+    if let Some(ref link) = entry.link {
+        embed = embed.url(link);
+    }
+
+    let mut footer = truncate(feed_title, 100);
+    if let Some(ref published) = entry.published {
+        if !footer.is_empty() {
+            footer.push_str(" \u{2022} ");
+        }
+        footer.push_str(published);
+    }
+
+    if !footer.is_empty() {
+        embed = embed.footer(CreateEmbedFooter::new(footer));
+    }
+
+    embed
+}
+
+/// Fetch a single subscription and post any unseen entries
+async fn poll_feed(ctx: &Context, gid: GuildId, sub: &guild::Feed) -> Result {
+    let (feed, etag, last_modified) = match fetch(&sub.url, &sub.etag, &sub.last_modified).await? {
+        Fetched::NotModified => return Ok(()),
+        Fetched::Modified {
+            feed,
+            etag,
+            last_modified,
+        } => (feed, etag, last_modified),
+    };
+
+    let seen: HashSet<_> = sub.seen.iter().map(String::as_str).collect();
+    // Feeds list entries newest-first, so post the oldest unseen ones first.
+    // Subscriptions without any history (e.g. from an imported config) are
+    // seeded instead, so they don't flood the channel with old entries.
+    let fresh: Vec<_> = feed
+        .entries
+        .iter()
+        .filter(|e| !seen.is_empty() && !seen.contains(&*e.id))
+        .take(MAX_POSTS)
+        .collect();
+
+    let channel = ChannelId::new(sub.channel);
+    for entry in fresh.iter().rev() {
+        debug!(%gid, url = sub.url, id = entry.id, "Posting feed entry");
+        channel
+            .send_message(
+                &ctx.http,
+                CreateMessage::new()
+                    .embed(embed(&feed.title, entry))
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await
+            .context("Error posting feed entry")?;
+    }
+
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    storage
+        .update_guild(gid, |g| {
+            let Some(sub) = g
+                .feeds
+                .iter_mut()
+                .find(|f| f.url == sub.url && f.channel == sub.channel)
+            else {
+                return;
+            };
+
+            sub.etag = etag;
+            sub.last_modified = last_modified;
+            sub.title.clone_from(&feed.title);
+            mark_seen(sub, &feed);
+        })
+        .await
+        .context("Error saving feed state")
+}
+
+/// Record every entry currently in the feed as seen, forgetting the oldest
+/// remembered IDs past [`MAX_SEEN`]
+fn mark_seen(sub: &mut guild::Feed, feed: &Feed) {
+    let known: HashSet<_> = sub.seen.iter().cloned().collect();
+    sub.seen.extend(
+        feed.entries
+            .iter()
+            .rev()
+            .filter(|e| !known.contains(&e.id))
+            .map(|e| e.id.clone()),
+    );
+
+    let excess = sub.seen.len().saturating_sub(MAX_SEEN);
+    sub.seen.drain(..excess);
+}
+
+async fn poll_all(ctx: &Context) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+
+    let guilds = capability::guilds(ctx)
+        .await
+        .context("Error listing guilds")?;
+
+    for gid in guilds {
+        let guild::Guild { feeds, .. } = storage.guild(gid).await?;
+
+        for sub in &feeds {
+            poll_feed(ctx, gid, sub)
+                .await
+                .map_err(|err| warn!(%gid, url = sub.url, ?err, "Error polling feed"))
+                .ok();
+        }
+    }
+
+    Ok(())
+}
+
+/// Background task posting new entries of subscribed feeds
+#[derive(Debug)]
+pub struct FeedPoller {
+    interval: Duration,
+    started: OnceCell<()>,
+}
+
+impl From<&CommandOpts> for FeedPoller {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            interval: Duration::from_secs(opts.feed_interval),
+            started: OnceCell::new(),
+        }
+    }
+}
+
+impl FeedPoller {
+    /// Start polling feeds, if the poller is not already running
+    pub fn start(&self, ctx: &Context) {
+        if self.started.set(()).is_err() {
+            return;
+        }
+
+        let ctx = ctx.clone();
+        let interval = self.interval;
+        tokio::task::spawn(
+            async move {
+                let mut timer = tokio::time::interval(interval);
+                timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    timer.tick().await;
+
+                    poll_all(&ctx)
+                        .await
+                        .map_err(|err| error!(?err, "Error polling feeds"))
+                        .ok();
+                }
+            }
+            .instrument(error_span!(parent: None, "poll_feeds")),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct FeedCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for FeedCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}feed", opts.command_base),
+        }
+    }
+}
+
+impl FeedCommand {
+    async fn add<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let url = visitor.visit_string("url")?.required()?;
+        let channel = visitor.visit_channel("channel")?.required()?.id;
+
+        let url = match Url::parse(url) {
+            Ok(u) if matches!(u.scheme(), "http" | "https") => u.to_string(),
+            _ => {
+                return Err(responder
+                    .create_message(
+                        Message::plain("Please provide an http:// or https:// link.")
+                            .ephemeral(true),
+                    )
+                    .await
+                    .context("Error sending URL error")?
+                    .into_err("Feed URL was malformed"));
+            },
+        };
+
+        let responder = responder
+            .defer_message(MessageOpts::default().ephemeral(true))
+            .await
+            .context("Error sending deferred message")?;
+
+        let (feed, etag, last_modified) = match fetch(&url, "", "").await {
+            Ok(Fetched::Modified {
+                feed,
+                etag,
+                last_modified,
+            }) => (feed, etag, last_modified),
+            res => {
+                if let Err(err) = res {
+                    debug!(?err, "Error fetching new feed");
+                }
+
+                responder
+                    .edit(MessageBody::plain(
+                        "Couldn't read a feed from that link.  Make sure it points to an RSS or \
+                         Atom feed.",
+                    ))
+                    .await
+                    .context("Error sending feed error")?;
+                return Err(responder.into_err("Feed could not be fetched"));
+            },
+        };
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let added = storage
+            .update_guild(gid, |g| {
+                if g.feeds
+                    .iter()
+                    .any(|f| f.url == url && f.channel == channel.get())
+                {
+                    return Err("That channel is already subscribed to this feed.");
+                }
+
+                if g.feeds.len() >= MAX_FEEDS {
+                    return Err("This server has reached its feed limit.");
+                }
+
+                // Existing entries are marked as seen so subscribing doesn't
+                // flood the channel with old posts
+                let mut sub = guild::Feed {
+                    url: url.clone(),
+                    channel: channel.get(),
+                    title: feed.title.clone(),
+                    etag,
+                    last_modified,
+                    seen: vec![],
+                };
+                mark_seen(&mut sub, &feed);
+                g.feeds.push(sub);
+                Ok(())
+            })
+            .await
+            .context("Error saving feed subscription")?;
+
+        if let Err(msg) = added {
+            responder
+                .edit(MessageBody::plain(msg))
+                .await
+                .context("Error sending subscription error")?;
+            return Err(responder.into_err("Feed subscription was rejected"));
+        }
+
+        responder
+            .edit(MessageBody::rich(|mb| {
+                mb.push("Subscribed ")
+                    .channel(channel)
+                    .push(" to ")
+                    .push_bold_safe(if feed.title.is_empty() {
+                        &url
+                    } else {
+                        &feed.title
+                    })
+                    .push(".")
+            }))
+            .await
+            .context("Error sending confirmation")?;
+
+        Ok(responder.into())
+    }
+
+    async fn remove<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let url = visitor.visit_string("url")?.required()?;
+        let channel = visitor.visit_channel("channel")?.optional().map(|c| c.id);
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let removed = storage
+            .update_guild(gid, |g| {
+                let len = g.feeds.len();
+                g.feeds
+                    .retain(|f| f.url != url || channel.is_some_and(|c| c.get() != f.channel));
+                len - g.feeds.len()
+            })
+            .await
+            .context("Error saving feed subscriptions")?;
+
+        Ok(responder
+            .create_message(
+                Message::plain(match removed {
+                    0 => "No matching feed subscriptions were found.".into(),
+                    1 => "Removed 1 feed subscription.".into(),
+                    n => format!("Removed {n} feed subscriptions."),
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn list<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild::Guild { feeds, .. } = storage.guild(gid).await?;
+
+        let msg = if feeds.is_empty() {
+            Message::plain("This server isn't subscribed to any feeds.")
+        } else {
+            Message::rich(|mb| {
+                mb.push_line(format!("Feed subscriptions ({}/{MAX_FEEDS}):", feeds.len()));
+
+                for feed in &feeds {
+                    mb.push("- ");
+                    if !feed.title.is_empty() {
+                        mb.push_safe(&feed.title).push(" ");
+                    }
+                    mb.push("<")
+                        .push_safe(&feed.url)
+                        .push("> in ")
+                        .channel(ChannelId::new(feed.channel))
+                        .push_line("");
+                }
+
+                mb
+            })
+        };
+
+        Ok(responder
+            .create_message(msg.ephemeral(true))
+            .await
+            .context("Error sending feed list")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for FeedCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Manage RSS and Atom feed subscriptions", |a| {
+            a.build_subcmd("add", "Post new entries of a feed to a channel", |a| {
+                a.string("url", "A link to the RSS or Atom feed", true, 1..=2000)
+                    .channel("channel", "The channel to post new entries in", true, [
+                        ChannelType::Text,
+                        ChannelType::News,
+                    ])
+            })
+            .build_subcmd("remove", "Unsubscribe from a feed", |a| {
+                a.string("url", "The link to the feed", true, 1..=2000)
+                    .channel("channel", "Only unsubscribe this channel", false, [
+                        ChannelType::Text,
+                        ChannelType::News,
+                    ])
+            })
+            .build_subcmd("list", "List this server's feed subscriptions", id)
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        match *visitor.visit_subcmd()? {
+            ["add"] => self.add(ctx, visitor, responder).await,
+            ["remove"] => self.remove(ctx, visitor, responder).await,
+            ["list"] => self.list(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, unescape, Entry};
+
+    #[test]
+    fn entities() {
+        assert_eq!(unescape("a &amp; b &lt;c&gt; &#65;&#x42;"), "a & b <c> AB");
+        assert_eq!(
+            unescape("<![CDATA[<b>&amp;</b>]]> &bogus"),
+            "<b>&amp;</b> &bogus"
+        );
+    }
+
+    #[test]
+    fn rss() {
+        let feed = parse(
+            r#"<?xml version="1.0"?>
+            <rss version="2.0"><channel>
+              <title>Example &amp; Co</title>
+              <atom:link href="https://example.com/rss" rel="self"/>
+              <item>
+                <title>Second</title>
+                <link>https://example.com/2</link>
+                <guid isPermaLink="false">post-2</guid>
+                <description><![CDATA[<p>Hello <b>world</b></p>]]></description>
+                <pubDate>Tue, 02 Jan 2024 00:00:00 GMT</pubDate>
+              </item>
+              <item><title>First</title><link>https://example.com/1</link></item>
+            </channel></rss>"#,
+        )
+        .unwrap();
+
+        assert_eq!(feed.title, "Example & Co");
+        assert_eq!(feed.entries, [
+            Entry {
+                id: "post-2".into(),
+                title: "Second".into(),
+                link: Some("https://example.com/2".into()),
+                summary: "Hello world".into(),
+                published: Some("Tue, 02 Jan 2024 00:00:00 GMT".into()),
+            },
+            Entry {
+                id: "https://example.com/1".into(),
+                title: "First".into(),
+                link: Some("https://example.com/1".into()),
+                ..Entry::default()
+            },
+        ]);
+    }
+
+    #[test]
+    fn atom() {
+        let feed = parse(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom">
+              <title type="text">Atom</title>
+              <entry>
+                <id>urn:1</id>
+                <title>Entry</title>
+                <link rel="edit" href="https://example.com/edit"/>
+                <link href="https://example.com/1?a=1&amp;b=2"/>
+                <updated>2024-01-01T00:00:00Z</updated>
+                <summary>Text</summary>
+              </entry>
+            </feed>"#,
+        )
+        .unwrap();
+
+        assert_eq!(feed.title, "Atom");
+        assert_eq!(feed.entries, [Entry {
+            id: "urn:1".into(),
+            title: "Entry".into(),
+            link: Some("https://example.com/1?a=1&b=2".into()),
+            summary: "Text".into(),
+            published: Some("2024-01-01T00:00:00Z".into()),
+        }]);
+
+        assert!(parse("<html></html>").is_err());
+    }
+}
@@ -0,0 +1,66 @@
+//! Caching for values derived from each guild's stored settings
+
+use tokio::sync::RwLock;
+
+use super::prelude::*;
+use crate::{client::storage::Storage, proto::guild};
+
+/// Values derived from each guild's stored settings, loaded on first use
+///
+/// Changes to the settings must be made through [`Self::update`], which saves
+/// them while holding the write lock.  A concurrent [`Self::load`] therefore
+/// either finishes before the change is saved or reads the changed settings,
+/// so the cache can never hold stale values.
+pub(super) struct GuildCache<V>(RwLock<BTreeMap<GuildId, V>>);
+
+impl<V: Clone + Send + Sync> GuildCache<V> {
+    pub const fn new() -> Self { Self(RwLock::const_new(BTreeMap::new())) }
+
+    /// Get the cached value for a guild, deriving it from the stored settings
+    /// with `f` if it is not yet cached
+    pub async fn load(
+        &self,
+        storage: &Storage,
+        gid: GuildId,
+        f: impl FnOnce(guild::Guild) -> V,
+    ) -> Result<V> {
+        if let Some(val) = self.0.read().await.get(&gid) {
+            return Ok(val.clone());
+        }
+
+        let mut cache = self.0.write().await;
+        if let Some(val) = cache.get(&gid) {
+            return Ok(val.clone());
+        }
+
+        let val = f(storage.guild(gid).await?);
+        cache.insert(gid, val.clone());
+        Ok(val)
+    }
+
+    /// Atomically apply `f` to the stored settings for a guild, caching the
+    /// value it returns alongside its result
+    pub async fn update<T: Send>(
+        &self,
+        storage: &Storage,
+        gid: GuildId,
+        f: impl FnOnce(&mut guild::Guild) -> (T, V) + Send,
+    ) -> Result<T> {
+        let mut cache = self.0.write().await;
+        let (ret, val) = storage.update_guild(gid, f).await?;
+        cache.insert(gid, val);
+        Ok(ret)
+    }
+
+    /// Drop the cached value for a guild, or every guild if `gid` is `None`,
+    /// so it is re-read from storage on next use
+    pub async fn invalidate(&self, gid: Option<GuildId>) {
+        let mut cache = self.0.write().await;
+
+        if let Some(gid) = gid {
+            cache.remove(&gid);
+        } else {
+            cache.clear();
+        }
+    }
+}
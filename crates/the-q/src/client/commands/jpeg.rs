@@ -1,75 +1,389 @@
-use std::path::PathBuf;
-
-use jpeggr::image::{self, ImageFormat};
-use serenity::builder::CreateAttachment;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+};
+
+use jpeggr::image::ImageFormat;
+use paracord::attachment::{Download, DownloadError, Downloader, Format};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect,
+};
+use serenity::{
+    builder::{CreateAttachment, GetMessages},
+    model::channel::Message as ChannelMessage,
+};
+use url::Host;
 
 use super::prelude::*;
 
+/// Largest image the bot will download, in bytes
+const MAX_DOWNLOAD: u64 = 16 * 1024 * 1024;
+/// Number of recent messages to search for an image when none is given
+const HISTORY_LIMIT: u8 = 20;
+
 enum JpegInput<'a> {
     Attachment(&'a Attachment),
     Url(Url),
 }
 
-async fn jpeg(input: JpegInput<'_>, quality: Option<i64>) -> Result<Vec<u8>> {
-    let quality @ 0..=100 = quality.unwrap_or(1) else {
-        unreachable!()
+/// A problem with the input image that should be reported to the user
+#[derive(Debug)]
+enum Rejected {
+    BadScheme,
+    PrivateAddress,
+    Status(reqwest::StatusCode),
+    NotImage(String),
+    TooLarge,
+    Timeout,
+    Dimensions,
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadScheme => f.write_str("Only http:// and https:// links are supported."),
+            Self::PrivateAddress => f.write_str("That link doesn't point to a public address."),
+            Self::Status(s) => write!(f, "Couldn't fetch that link ({s})."),
+            Self::NotImage(t) => write!(f, "That doesn't look like an image (got {t})."),
+            Self::TooLarge => write!(
+                f,
+                "That image is too large (the limit is {} MiB).",
+                MAX_DOWNLOAD / 1024 / 1024
+            ),
+            Self::Timeout => f.write_str("That image took too long to download."),
+            Self::Dimensions => f.write_str("That image's dimensions are too large."),
+        }
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+impl Rejected {
+    /// Report problems with the input to the user, and anything else as an
+    /// internal error
+    fn from_download(err: DownloadError) -> anyhow::Error {
+        match err {
+            DownloadError::TooLarge(_) => Self::TooLarge.into(),
+            DownloadError::Status(s) => Self::Status(s).into(),
+            DownloadError::Rejected(f) => Self::NotImage(f.mime().into()).into(),
+            DownloadError::Unrecognized => Self::NotImage("an unrecognized format".into()).into(),
+            DownloadError::Timeout => Self::Timeout.into(),
+            DownloadError::Http(e) if PrivateAddress::caused(&e) => Self::PrivateAddress.into(),
+            DownloadError::Http(e) => {
+                anyhow::Error::new(e).context("Error downloading input image")
+            },
+        }
+    }
+}
+
+/// The error produced when a link points to an address that isn't publicly
+/// routable
+#[derive(Debug)]
+struct PrivateAddress;
+
+impl fmt::Display for PrivateAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Refusing to connect to a non-public address")
+    }
+}
+
+impl std::error::Error for PrivateAddress {}
+
+impl PrivateAddress {
+    /// Returns true if this error appears anywhere in the sources of `err`
+    fn caused(err: &(dyn std::error::Error + 'static)) -> bool {
+        std::iter::successors(Some(err), |e| e.source()).any(<dyn std::error::Error>::is::<Self>)
+    }
+}
+
+/// Returns true if `ip` can be reached on the public internet, i.e. it is
+/// not a loopback, private, link-local or otherwise reserved address
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or_else(|| is_public_v6(ip), is_public_v4),
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // Shared address space (100.64.0.0/10)
+        || (a == 100 && b & 0xc0 == 64)
+        // Benchmarking (198.18.0.0/15)
+        || (a == 198 && b & 0xfe == 18)
+        // Reserved (240.0.0.0/4) and "this network" (0.0.0.0/8)
+        || a >= 240
+        || a == 0)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let [a, ..] = ip.segments();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local (fc00::/7)
+        || a & 0xfe00 == 0xfc00
+        // Link-local (fe80::/10)
+        || a & 0xffc0 == 0xfe80
+        // Documentation (2001:db8::/32)
+        || (a == 0x2001 && ip.segments()[1] == 0xdb8))
+}
+
+/// Check that a URL does not name a non-public IP address directly
+///
+/// Host names are checked when they are resolved, by [`PublicResolver`].
+fn check_host(url: &Url) -> Result<(), PrivateAddress> {
+    let public = match url.host() {
+        Some(Host::Domain(_)) => true,
+        Some(Host::Ipv4(ip)) => is_public_v4(ip),
+        Some(Host::Ipv6(ip)) => is_public(ip.into()),
+        None => false,
     };
-    let quality = u8::try_from(quality).unwrap_or_else(|_| unreachable!());
 
-    let image_data;
-    let content_type;
-    let filename;
+    if public {
+        Ok(())
+    } else {
+        Err(PrivateAddress)
+    }
+}
+
+/// A DNS resolver that discards any non-public addresses, so a host name
+/// can't be used to reach the bot's own network
+#[derive(Debug)]
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+
+        Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((host, 0))
+                .await?
+                .filter(|a| is_public(a.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(PrivateAddress.into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Construct an HTTP client for fetching user-provided links, which can only
+/// connect to public addresses, including when following redirects
+fn public_http_client() -> reqwest::Client {
+    let redirects = redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("Too many redirects")
+        } else if let Err(e) = check_host(attempt.url()) {
+            attempt.error(e)
+        } else {
+            attempt.follow()
+        }
+    });
+
+    http_client_builder(None)
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(redirects)
+        .build()
+        .unwrap()
+}
+
+async fn download(input: JpegInput<'_>) -> Result<Download> {
     match input {
         JpegInput::Attachment(a) => {
-            image_data = a
-                .download()
+            Downloader::new(http_client(None), Format::IMAGES)
+                .with_max_size(MAX_DOWNLOAD)
+                .download(a)
                 .await
-                .context("Error downloading attachment from discord")?;
-            content_type = a.content_type.clone();
-            filename = Some(a.filename.clone());
         },
         JpegInput::Url(u) => {
-            let res = http_client(None)
-                .get(u)
-                .send()
-                .await
-                .context("Error fetching input URL")?;
-            content_type = res
-                .headers()
-                .get("Content-Type")
-                .and_then(|h| h.to_str().ok())
-                .map(ToOwned::to_owned);
-            image_data = res
-                .bytes()
+            if !matches!(u.scheme(), "http" | "https") {
+                return Err(Rejected::BadScheme.into());
+            }
+
+            if check_host(&u).is_err() {
+                return Err(Rejected::PrivateAddress.into());
+            }
+
+            Downloader::new(public_http_client(), Format::IMAGES)
+                .with_max_size(MAX_DOWNLOAD)
+                .fetch(u)
                 .await
-                .context("Error downloading image response")?
-                .to_vec();
-            filename = None;
         },
     }
+    .map_err(Rejected::from_download)
+}
 
-    tokio::task::spawn_blocking(move || {
-        let format = content_type
-            .as_ref()
-            .and_then(ImageFormat::from_mime_type)
-            .or_else(|| filename.and_then(|f| ImageFormat::from_path(f).ok()))
-            .or_else(|| image::guess_format(&image_data).ok())
-            .context("Error determining format of input image")?;
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .context("Error running image task")?
+}
+
+/// Report oversized images to the user, and anything else as an internal
+/// error
+fn from_jpeggr(err: jpeggr::Error, context: &'static str) -> anyhow::Error {
+    match err {
+        jpeggr::Error::Dimensions { .. } | jpeggr::Error::TooLarge { .. } => {
+            Rejected::Dimensions.into()
+        },
+        e => anyhow::Error::new(e).context(context),
+    }
+}
+
+/// The output of the JPEG effect
+struct Jpegged {
+    bytes: Vec<u8>,
+    /// The estimated quality of the input, if it was already a JPEG saved at
+    /// or below the requested quality
+    fried: Option<u8>,
+}
+
+async fn jpeg(
+    input: JpegInput<'_>,
+    quality: Option<i64>,
+    strip_metadata: bool,
+    progress: &mut CommandProgress<'_, '_>,
+) -> Result<Jpegged> {
+    let quality @ 0..=100 = quality.unwrap_or(1) else {
+        unreachable!()
+    };
+    let quality = u8::try_from(quality).unwrap_or_else(|_| unreachable!());
+
+    progress.update(0.0, "Downloading image...").await;
+    let download = download(input).await?;
+    let format = ImageFormat::from_mime_type(download.format().mime())
+        .context("Unsupported format of input image")?;
+    let source_quality = (download.format() == Format::Jpeg)
+        .then(|| jpeggr::estimate_quality(download.data()))
+        .flatten();
+    let image_data = download.into_data();
+
+    progress.update(0.25, "Decoding image...").await;
+    let (image, metadata) = blocking(move || {
+        jpeggr::decode(&image_data, format, jpeggr::DEFAULT_MEMORY_BUDGET)
+            .map_err(|e| from_jpeggr(e, "Error reading image data"))
+    })
+    .await?;
+    let metadata = if strip_metadata {
+        jpeggr::Metadata::default()
+    } else {
+        metadata
+    };
 
-        let image = image::load_from_memory_with_format(&image_data, format)
-            .context("Error reading image data")?;
-        let jpegged_image = jpeggr::jpeg_dynamic_image(image, 1, quality)
-            .context("Error applying JPEG effect to image")?;
+    progress.update(0.5, "Applying JPEG effect...").await;
+    let jpegged_image = blocking(move || {
+        jpeggr::jpeg_dynamic_image(image, 1, quality, jpeggr::DEFAULT_MEMORY_BUDGET)
+            .map_err(|e| from_jpeggr(e, "Error applying JPEG effect to image"))
+    })
+    .await?;
 
-        let mut bytes = Vec::new();
-        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality)
-            .encode_image(&jpegged_image)
-            .context("Error encoding image")?;
+    progress.update(0.75, "Encoding image...").await;
+    let bytes = blocking(move || {
+        jpeggr::encode(&jpegged_image, quality, &metadata).context("Error encoding image")
+    })
+    .await?;
 
-        Ok(bytes)
+    Ok(Jpegged {
+        bytes,
+        fried: source_quality.map(|q| q.quality).filter(|&q| q <= quality),
     })
-    .await
-    .context("Error running image task")?
+}
+
+async fn send_jpeg<'a>(
+    responder: CreatedCommandResponder<'a>,
+    input: JpegInput<'_>,
+    quality: Option<i64>,
+    strip_metadata: bool,
+    filename: &str,
+) -> CommandResult<'a> {
+    let mut progress = CommandProgress::new(&responder);
+    let Jpegged { bytes, fried } = match jpeg(input, quality, strip_metadata, &mut progress).await {
+        Ok(b) => b,
+        Err(err) => {
+            let Some(rejected) = err.downcast_ref::<Rejected>() else {
+                return Err(err.into());
+            };
+
+            progress
+                .fail(rejected.to_string())
+                .await
+                .context("Error sending input error")?;
+            return Err(responder.into_err("Input image was rejected"));
+        },
+    };
+
+    // TODO: post file size difference
+    let attachment = CreateAttachment::bytes(
+        bytes,
+        PathBuf::from(filename)
+            .with_extension("jpg")
+            .display()
+            .to_string(),
+    );
+    let note = fried.map_or_else(String::new, |q| {
+        format!("This image was already saved at quality {q}, so it may not look much different.")
+    });
+    progress
+        .finish(Message::plain(note).attach([attachment]))
+        .await
+        .context("Error sending jpegged image")?;
+
+    Ok(responder.into())
+}
+
+/// Locate the image in a message, if it has exactly one attachment or embed
+fn find_image(message: &ChannelMessage) -> Option<(JpegInput<'_>, &str)> {
+    if let [ref attachment] = *message.attachments {
+        return Some((JpegInput::Attachment(attachment), &*attachment.filename));
+    }
+
+    // TODO: when let-chains
+    if let [ref embed] = *message.embeds {
+        if let Some(ref image) = embed.image {
+            if let Ok(url) = image.url.parse() {
+                return Some((JpegInput::Url(url), "output.jpg"));
+            }
+        }
+
+        if let Some(ref thumbnail) = embed.thumbnail {
+            if let Ok(url) = thumbnail.url.parse() {
+                return Some((JpegInput::Url(url), "thumb.jpg"));
+            }
+        }
+
+        if let Some(ref author) = embed.author {
+            if let Some(ref icon) = author.icon_url {
+                if let Ok(url) = icon.parse() {
+                    return Some((JpegInput::Url(url), "icon.jpg"));
+                }
+            }
+        }
+
+        if let Some(ref url) = embed.url {
+            if let Ok(url) = url.parse::<Url>() {
+                if ImageFormat::from_path(url.path()).is_ok() {
+                    return Some((JpegInput::Url(url), "embed.jpg"));
+                }
+            }
+        }
+    }
+
+    None
 }
 
 #[derive(Debug)]
@@ -89,45 +403,111 @@ impl From<&CommandOpts> for JpegCommand {
 impl CommandHandler<Schema> for JpegCommand {
     fn register_global(&self) -> CommandInfo {
         CommandInfo::build_slash(&self.name, "Applies a JPEG effect to an image", |a| {
-            a.attachment("image", "The input image", true).int(
-                "quality",
-                "The compression quality",
-                false,
-                1..=100,
-            )
+            a.attachment("image", "The input image", false)
+                .string("url", "A link to the input image", false, 1..=2000)
+                .int("quality", "The compression quality", false, 1..=100)
+                .bool(
+                    "strip_metadata",
+                    "Remove the color profile and EXIF data from the output",
+                    false,
+                )
         })
         .unwrap()
     }
 
     async fn respond<'a>(
         &self,
-        _ctx: &Context,
+        ctx: &Context,
         visitor: &mut CommandVisitor<'_>,
         responder: CommandResponder<'_, 'a>,
     ) -> CommandResult<'a> {
-        let attachment = visitor.visit_attachment("image")?.required()?;
+        let attachment = visitor.visit_attachment("image")?.optional();
+        let url = visitor.visit_string("url")?.optional();
         let quality = visitor.visit_i64("quality")?.optional();
+        let strip_metadata = visitor
+            .visit_bool("strip_metadata")?
+            .optional()
+            .unwrap_or(false);
+
+        let url = match url.map(Url::parse) {
+            Some(Ok(u)) => Some(u),
+            Some(Err(_)) => {
+                return Err(responder
+                    .create_message(
+                        Message::plain("That doesn't look like a link.").ephemeral(true),
+                    )
+                    .await
+                    .context("Error sending URL error")?
+                    .into_err("Input URL was malformed"));
+            },
+            None => None,
+        };
+
+        if attachment.is_some() && url.is_some() {
+            return Err(responder
+                .create_message(
+                    Message::plain("Please provide either an image or a link, not both.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending input count error")?
+                .into_err("Both attachment and URL were provided"));
+        }
 
         let responder = responder
             .defer_message(MessageOpts::default())
             .await
             .context("Error sending deferred message")?;
 
-        let bytes = jpeg(JpegInput::Attachment(attachment), quality).await?;
-
-        let attachment = CreateAttachment::bytes(
-            bytes,
-            PathBuf::from(&attachment.filename)
-                .with_extension("jpg")
-                .display()
-                .to_string(),
-        );
-        responder
-            .create_followup(Message::plain("").attach([attachment]))
+        if let Some(attachment) = attachment {
+            return send_jpeg(
+                responder,
+                JpegInput::Attachment(attachment),
+                quality,
+                strip_metadata,
+                &attachment.filename,
+            )
+            .await;
+        }
+
+        if let Some(url) = url {
+            let filename = url
+                .path_segments()
+                .and_then(Iterator::last)
+                .filter(|s| !s.is_empty())
+                .unwrap_or("output.jpg")
+                .to_owned();
+
+            return send_jpeg(
+                responder,
+                JpegInput::Url(url),
+                quality,
+                strip_metadata,
+                &filename,
+            )
+            .await;
+        }
+
+        // Slash commands can't reply to a message, so fall back to the most
+        // recent image posted in the channel
+        let history = visitor
+            .channel_id()
+            .messages(ctx, GetMessages::new().limit(HISTORY_LIMIT))
             .await
-            .context("Error sending jpegged image")?;
+            .context("Error fetching channel history")?;
+
+        let Some((input, filename)) = history.iter().find_map(find_image) else {
+            responder
+                .edit(MessageBody::plain(
+                    "Please provide an image or a link, or use this command after an image is \
+                     posted.",
+                ))
+                .await
+                .context("Error sending missing input error")?;
+            return Err(responder.into_err("No input image found"));
+        };
 
-        Ok(responder.into())
+        send_jpeg(responder, input, quality, strip_metadata, filename).await
     }
 }
 
@@ -148,7 +528,6 @@ impl From<&CommandOpts> for JpegMessageCommand {
 impl CommandHandler<Schema> for JpegMessageCommand {
     fn register_global(&self) -> CommandInfo { CommandInfo::message(&self.name) }
 
-    // TODO: simplify error handling
     async fn respond<'a>(
         &self,
         _ctx: &Context,
@@ -157,46 +536,7 @@ impl CommandHandler<Schema> for JpegMessageCommand {
     ) -> CommandResult<'a> {
         let message = visitor.target().message()?;
 
-        let input = 'found: {
-            if let [ref attachment] = *message.attachments {
-                break 'found Some((JpegInput::Attachment(attachment), &*attachment.filename));
-            }
-
-            // TODO: when let-chains
-            if let [ref embed] = *message.embeds {
-                if let Some(ref image) = embed.image {
-                    if let Ok(url) = image.url.parse() {
-                        break 'found Some((JpegInput::Url(url), "output.jpg"));
-                    }
-                }
-
-                if let Some(ref thumbnail) = embed.thumbnail {
-                    if let Ok(url) = thumbnail.url.parse() {
-                        break 'found Some((JpegInput::Url(url), "thumb.jpg"));
-                    }
-                }
-
-                if let Some(ref author) = embed.author {
-                    if let Some(ref icon) = author.icon_url {
-                        if let Ok(url) = icon.parse() {
-                            break 'found Some((JpegInput::Url(url), "icon.jpg"));
-                        }
-                    }
-                }
-
-                if let Some(ref url) = embed.url {
-                    if let Ok(url) = url.parse::<Url>() {
-                        if ImageFormat::from_path(url.path()).is_ok() {
-                            break 'found Some((JpegInput::Url(url), "embed.jpg"));
-                        }
-                    }
-                }
-            }
-
-            None
-        };
-
-        let Some((input, filename)) = input else {
+        let Some((input, filename)) = find_image(message) else {
             return Err(responder
                 .create_message(
                     Message::plain("Target message must have exactly one attachment!")
@@ -212,21 +552,73 @@ impl CommandHandler<Schema> for JpegMessageCommand {
             .await
             .context("Error sending deferred message")?;
 
-        let bytes = jpeg(input, None).await?;
-
-        // TODO: post file size difference
-        let attachment = CreateAttachment::bytes(
-            bytes,
-            PathBuf::from(filename)
-                .with_extension("jpg")
-                .display()
-                .to_string(),
-        );
-        responder
-            .create_followup(Message::plain("").attach([attachment]))
-            .await
-            .context("Error sending jpegged image")?;
+        send_jpeg(responder, input, None, false, filename).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_host, is_public, public_http_client, PrivateAddress, Url};
+
+    #[test]
+    fn public_addresses() {
+        let public = |s: &str| is_public(s.parse().unwrap());
+
+        assert!(public("1.1.1.1"));
+        assert!(public("162.159.128.233"));
+        assert!(public("2606:4700:4700::1111"));
+
+        assert!(!public("0.0.0.0"));
+        assert!(!public("127.0.0.1"));
+        assert!(!public("10.1.2.3"));
+        assert!(!public("172.16.0.1"));
+        assert!(!public("192.168.1.1"));
+        assert!(!public("169.254.169.254"));
+        assert!(!public("100.64.0.1"));
+        assert!(!public("198.19.0.1"));
+        assert!(!public("224.0.0.1"));
+        assert!(!public("255.255.255.255"));
+        assert!(!public("::"));
+        assert!(!public("::1"));
+        assert!(!public("fd00::1"));
+        assert!(!public("fe80::1"));
+        assert!(!public("::ffff:127.0.0.1"));
+        assert!(!public("::ffff:10.0.0.1"));
+    }
+
+    #[test]
+    fn hosts() {
+        let check = |s: &str| check_host(&s.parse::<Url>().unwrap()).is_ok();
+
+        assert!(check("https://cdn.discordapp.com/image.png"));
+        assert!(check("http://1.1.1.1/image.png"));
+        assert!(check("http://localhost/image.png"));
+
+        assert!(!check("http://127.0.0.1/image.png"));
+        assert!(!check("http://0x7f000001/image.png"));
+        assert!(!check("http://[::1]:8080/image.png"));
+        assert!(!check("http://[::ffff:169.254.169.254]/latest/meta-data"));
+    }
+
+    #[test]
+    fn resolve_private() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let err = rt
+            .block_on(async { public_http_client().get("http://localhost:1/").send().await })
+            .unwrap_err();
+
+        assert!(PrivateAddress::caused(&err));
+    }
+
+    #[test]
+    fn caused() {
+        let err = anyhow::Error::new(PrivateAddress).context("Error resolving host");
+        assert!(PrivateAddress::caused(err.as_ref()));
 
-        Ok(responder.into())
+        let err = std::io::Error::other("unrelated");
+        assert!(!PrivateAddress::caused(&err));
     }
 }
@@ -0,0 +1,1238 @@
+use std::{
+    cmp::Reverse,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use jpeggr::image::{
+    imageops::{self, FilterType},
+    DynamicImage, ImageFormat, Rgb, RgbImage,
+};
+use paracord::{
+    attachment::{Downloader, Format},
+    interaction::command::Choice,
+};
+use qcore::build_with::BuildWith;
+use serenity::{
+    builder::{CreateAttachment, CreateMessage},
+    model::{
+        channel::{ChannelType, Message as ChannelMessage, MessageType},
+        id::{ChannelId, RoleId, UserId},
+        mention::Mentionable,
+        Permissions,
+    },
+    utils::MessageBuilder,
+};
+use tokio::sync::{Mutex, RwLock};
+
+use super::{botlog, guild_cache::GuildCache, prelude::*};
+use crate::{
+    client::storage::{self, Storage},
+    proto::guild::{self, leveling::Curve},
+};
+
+const DEFAULT_MESSAGE_XP: u32 = 15;
+const DEFAULT_MESSAGE_COOLDOWN: u32 = 60;
+const DEFAULT_VOICE_XP: u32 = 5;
+const DEFAULT_BASE: u32 = 100;
+/// Highest level a member can reach
+const MAX_LEVEL: u32 = 1000;
+/// Most XP a single message or minute in voice can award
+const MAX_XP: u32 = 1000;
+/// Longest cooldown between messages that earn XP, in seconds
+const MAX_COOLDOWN: u32 = 60 * 60;
+const MAX_BASE: u32 = 10_000;
+const MAX_REWARDS: usize = 25;
+/// Number of members per leaderboard page
+const PAGE_SIZE: usize = 10;
+/// Number of tracked cooldowns above which expired ones are dropped
+const MAX_TRACKED: usize = 10_000;
+/// Largest avatar the bot will download for a rank card, in bytes
+const MAX_AVATAR: u64 = 4 * 1024 * 1024;
+/// How often XP awarded since the last save is written to storage
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+const CARD_WIDTH: u32 = 600;
+const CARD_HEIGHT: u32 = 160;
+const CARD_PADDING: u32 = 16;
+const AVATAR_SIZE: u32 = CARD_HEIGHT - 2 * CARD_PADDING;
+const BAR_HEIGHT: u32 = 24;
+const CARD_BACKGROUND: Rgb<u8> = Rgb([0x2b, 0x2d, 0x31]);
+const CARD_PLACEHOLDER: Rgb<u8> = Rgb([0x4e, 0x50, 0x58]);
+const BAR_TRACK: Rgb<u8> = Rgb([0x1e, 0x1f, 0x22]);
+const BAR_FILL: Rgb<u8> = Rgb([0x58, 0x65, 0xf2]);
+
+/// Leveling settings for each guild whose settings have been loaded, or
+/// `None` if leveling is off
+static SETTINGS: GuildCache<Option<Arc<guild::Leveling>>> = GuildCache::new();
+
+/// When each member last earned XP for a message
+///
+/// Cooldowns are short, so these are kept in memory and lost on restart
+/// rather than written to storage for every message.
+static LAST_AWARD: Mutex<BTreeMap<(GuildId, UserId), Instant>> =
+    Mutex::const_new(BTreeMap::new());
+
+/// The XP of a guild's members as tracked in memory
+#[derive(Debug, Default)]
+struct GuildXp {
+    /// Each member's XP as of the last save, or `None` if it has not been
+    /// loaded since the guild's data was last changed outside this module
+    stored: Option<HashMap<u64, u64>>,
+    /// XP awarded to each member since the last save
+    pending: HashMap<u64, u64>,
+}
+
+impl GuildXp {
+    fn total(&self, user: u64) -> u64 {
+        let stored = self.stored.as_ref().and_then(|s| s.get(&user)).copied();
+        let pending = self.pending.get(&user).copied();
+        stored.unwrap_or(0).saturating_add(pending.unwrap_or(0))
+    }
+}
+
+/// XP for each guild that has had any awarded since startup
+///
+/// Saving a guild rewrites all of its data, so awards are collected here and
+/// written by [`flush`] every [`FLUSH_INTERVAL`] instead of once per message.
+/// This lock is never held across storage reads or writes.
+static XP: Mutex<BTreeMap<GuildId, GuildXp>> = Mutex::const_new(BTreeMap::new());
+
+/// Held for writing while XP is being saved or cached XP is dropped, and for
+/// reading while stored XP is loaded, so a load never sees a save half-done
+static SAVING: RwLock<()> = RwLock::const_new(());
+
+fn defaults() -> guild::Leveling {
+    guild::Leveling {
+        message_xp: DEFAULT_MESSAGE_XP,
+        message_cooldown: DEFAULT_MESSAGE_COOLDOWN,
+        voice_xp: DEFAULT_VOICE_XP,
+        base: DEFAULT_BASE,
+        curve: Curve::Linear.into(),
+        rewards: HashMap::new(),
+        announce_channel: 0,
+    }
+}
+
+pub(super) fn curve_name(curve: Curve) -> &'static str {
+    match curve {
+        Curve::Flat => "flat",
+        Curve::Unknown | Curve::Linear => "linear",
+        Curve::Quadratic => "quadratic",
+    }
+}
+
+pub(super) fn parse_curve(s: &str) -> Option<Curve> {
+    match s {
+        "flat" => Some(Curve::Flat),
+        "linear" => Some(Curve::Linear),
+        "quadratic" => Some(Curve::Quadratic),
+        _ => None,
+    }
+}
+
+/// Get the total XP needed to reach the given level
+fn threshold(cfg: &guild::Leveling, level: u32) -> u64 {
+    let (base, l) = (u64::from(cfg.base.max(1)), u64::from(level));
+
+    match cfg.curve() {
+        Curve::Flat => base * l,
+        Curve::Unknown | Curve::Linear => base * l * (l + 1) / 2,
+        Curve::Quadratic => base * l * (l + 1) * (2 * l + 1) / 6,
+    }
+}
+
+/// Get the level reached with the given total XP
+fn level(cfg: &guild::Leveling, xp: u64) -> u32 {
+    (1..=MAX_LEVEL)
+        .take_while(|&l| threshold(cfg, l) <= xp)
+        .last()
+        .unwrap_or(0)
+}
+
+/// Get the XP earned towards the next level and the XP that level takes in
+/// total, or `None` at the maximum level
+fn progress(cfg: &guild::Leveling, xp: u64) -> Option<(u64, u64)> {
+    let level = level(cfg, xp);
+    (level < MAX_LEVEL).then(|| {
+        let start = threshold(cfg, level);
+        (xp - start, threshold(cfg, level + 1) - start)
+    })
+}
+
+/// Get the reward roles for levels after `from` up to and including `to`,
+/// lowest level first
+fn rewards(cfg: &guild::Leveling, from: u32, to: u32) -> Vec<RoleId> {
+    let mut rewards: Vec<_> = cfg
+        .rewards
+        .iter()
+        .filter(|&(&l, _)| l > from && l <= to)
+        .map(|(&l, &r)| (l, RoleId::new(r)))
+        .collect();
+    rewards.sort_unstable();
+    rewards.into_iter().map(|(_, r)| r).collect()
+}
+
+/// Sort members by XP, most first, leaving out anyone without any
+fn ranking(xp: &HashMap<u64, u64>) -> Vec<(u64, u64)> {
+    let mut ranking: Vec<_> = xp
+        .iter()
+        .filter(|&(_, &x)| x > 0)
+        .map(|(&u, &x)| (u, x))
+        .collect();
+    ranking.sort_unstable_by_key(|&(user, xp)| (Reverse(xp), user));
+    ranking
+}
+
+/// Returns true if a member whose last award was at `last` can earn XP for
+/// a message again
+fn off_cooldown(last: Option<Instant>, now: Instant, cooldown: u32) -> bool {
+    last.map_or(true, |l| {
+        now.saturating_duration_since(l).as_secs() >= u64::from(cooldown)
+    })
+}
+
+/// Check settings for problems, returning a description of the first one
+/// found
+pub(super) fn validate(cfg: &guild::Leveling) -> Result<(), String> {
+    if !(1..=MAX_BASE).contains(&cfg.base) {
+        return Err(format!("The base XP must be between 1 and {MAX_BASE}"));
+    }
+
+    if cfg.message_xp > MAX_XP || cfg.voice_xp > MAX_XP {
+        return Err(format!("XP awards can be at most {MAX_XP}"));
+    }
+
+    if cfg.message_cooldown > MAX_COOLDOWN {
+        return Err(format!(
+            "The message cooldown can be at most {MAX_COOLDOWN} seconds"
+        ));
+    }
+
+    if cfg.rewards.len() > MAX_REWARDS {
+        return Err(format!("At most {MAX_REWARDS} levels can have rewards"));
+    }
+
+    if cfg.rewards.keys().any(|l| !(1..=MAX_LEVEL).contains(l)) {
+        return Err(format!("Rewards must be for levels 1 to {MAX_LEVEL}"));
+    }
+
+    Ok(())
+}
+
+/// Summarize leveling settings as a list
+fn describe<'a>(mb: &'a mut MessageBuilder, cfg: &guild::Leveling) -> &'a mut MessageBuilder {
+    mb.push_line(format!(
+        "- Messages: {} XP, at most once every {}s",
+        cfg.message_xp, cfg.message_cooldown
+    ))
+    .push_line(format!("- Voice: {} XP per minute", cfg.voice_xp))
+    .push_line(format!(
+        "- Curve: {}, starting at {} XP for level 1",
+        curve_name(cfg.curve()),
+        cfg.base
+    ))
+    .push("- Announcements: ");
+
+    match cfg.announce_channel {
+        0 => mb.push("in reply to the message that earned them"),
+        c => mb.push("in ").channel(ChannelId::new(c)),
+    };
+
+    mb.push("\n- Rewards: ");
+    if cfg.rewards.is_empty() {
+        mb.push("none");
+    }
+    let mut rewards: Vec<_> = cfg.rewards.iter().collect();
+    rewards.sort_unstable();
+    for (&level, &role) in rewards {
+        mb.push(format!("\n  - Level {level}: "))
+            .role(RoleId::new(role));
+    }
+
+    mb
+}
+
+/// Draw a rank card showing the member's avatar and their progress towards
+/// the next level, from 0 to 1
+fn render_card(avatar: Option<&DynamicImage>, fraction: f64) -> RgbImage {
+    let mut card = RgbImage::from_pixel(CARD_WIDTH, CARD_HEIGHT, CARD_BACKGROUND);
+
+    let avatar = avatar.map_or_else(
+        || RgbImage::from_pixel(AVATAR_SIZE, AVATAR_SIZE, CARD_PLACEHOLDER),
+        |a| {
+            a.resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Triangle)
+                .to_rgb8()
+        },
+    );
+    imageops::overlay(
+        &mut card,
+        &avatar,
+        CARD_PADDING.into(),
+        CARD_PADDING.into(),
+    );
+
+    let bar_x = AVATAR_SIZE + 2 * CARD_PADDING;
+    let bar_y = (CARD_HEIGHT - BAR_HEIGHT) / 2;
+    let bar_width = CARD_WIDTH - bar_x - CARD_PADDING;
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "The fill is clamped to the bar width"
+    )]
+    let fill = (fraction.clamp(0.0, 1.0) * f64::from(bar_width)).round() as u32;
+
+    for x in 0..bar_width {
+        let color = if x < fill { BAR_FILL } else { BAR_TRACK };
+        for y in 0..BAR_HEIGHT {
+            card.put_pixel(bar_x + x, bar_y + y, color);
+        }
+    }
+
+    card
+}
+
+/// Download and decode a user's avatar, or return `None` if it could not be
+/// fetched
+async fn avatar(user: &User) -> Option<DynamicImage> {
+    let url = Url::parse(&user.static_face()).ok()?;
+    let download = Downloader::new(http_client(None), Format::IMAGES)
+        .with_max_size(MAX_AVATAR)
+        .fetch(url)
+        .await
+        .map_err(|err| debug!(%err, "Error downloading avatar"))
+        .ok()?;
+    let format = ImageFormat::from_mime_type(download.format().mime())?;
+    let data = download.into_data();
+
+    tokio::task::spawn_blocking(move || {
+        jpeggr::decode(&data, format, jpeggr::DEFAULT_MEMORY_BUDGET)
+            .map_err(|err| debug!(%err, "Error decoding avatar"))
+            .ok()
+    })
+    .await
+    .ok()
+    .flatten()
+    .map(|(image, _)| image)
+}
+
+async fn settings(storage: &Storage, gid: GuildId) -> Result<Option<Arc<guild::Leveling>>> {
+    SETTINGS.load(storage, gid, |g| g.leveling.map(Arc::new)).await
+}
+
+/// Drop the cached leveling settings and XP for a guild, or every guild if
+/// `gid` is `None`, after they were changed outside this module
+///
+/// XP that has not been saved yet is kept, and is added to whatever is stored
+/// by the next flush.
+pub(super) async fn invalidate(gid: Option<GuildId>) {
+    SETTINGS.invalidate(gid).await;
+
+    // Anything loaded before this point may predate the change
+    let _saving = SAVING.write().await;
+    let mut xp = XP.lock().await;
+    match gid {
+        Some(gid) => {
+            if let Some(guild) = xp.get_mut(&gid) {
+                guild.stored = None;
+            }
+        },
+        None => xp.values_mut().for_each(|g| g.stored = None),
+    }
+}
+
+/// Add XP awards to each member's total
+fn add_xp(xp: &mut HashMap<u64, u64>, awards: &HashMap<u64, u64>) {
+    for (&user, &amount) in awards {
+        let xp = xp.entry(user).or_default();
+        *xp = xp.saturating_add(amount);
+    }
+}
+
+/// Write all XP awarded since the last save to storage
+///
+/// Unsaved XP is moved into each guild's cached stored XP before writing, so
+/// awards made while this runs still see the right totals.
+pub async fn flush(storage: &Storage) {
+    let _saving = SAVING.write().await;
+    let batch: Vec<_> = XP
+        .lock()
+        .await
+        .iter_mut()
+        .filter(|(_, g)| !g.pending.is_empty())
+        .map(|(&gid, guild)| {
+            let pending = mem::take(&mut guild.pending);
+            if let Some(ref mut stored) = guild.stored {
+                add_xp(stored, &pending);
+            }
+            (gid, pending)
+        })
+        .collect();
+
+    for (gid, pending) in batch {
+        let res = storage
+            .update_guild(gid, |g| {
+                // XP earned before leveling was turned off is dropped
+                let on = g.leveling.is_some();
+                if on {
+                    add_xp(&mut g.xp, &pending);
+                }
+                on
+            })
+            .await;
+
+        let mut xp = XP.lock().await;
+        let guild = xp.entry(gid).or_default();
+        match res {
+            Ok(true) => (),
+            Ok(false) => guild.stored = None,
+            Err(err) => {
+                error!(%gid, ?err, "Error saving XP");
+                // The cached stored XP already counts these awards, so it is
+                // reloaded rather than trusted
+                guild.stored = None;
+                add_xp(&mut guild.pending, &pending);
+            },
+        }
+    }
+}
+
+/// Start saving awarded XP to storage in the background
+pub fn spawn_flush(storage: Arc<Storage>) {
+    tokio::spawn(
+        async move {
+            let mut interval = tokio::time::interval_at(
+                tokio::time::Instant::now() + FLUSH_INTERVAL,
+                FLUSH_INTERVAL,
+            );
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+                flush(&storage).await;
+            }
+        }
+        .instrument(info_span!("xp_flush")),
+    );
+}
+
+/// Load the stored data for a guild, including any XP not yet saved
+async fn load_guild(storage: &Storage, gid: GuildId) -> Result<guild::Guild> {
+    let _saving = SAVING.read().await;
+    let mut guild = storage.guild(gid).await?;
+
+    if let Some(pending) = XP.lock().await.get(&gid).map(|g| &g.pending) {
+        add_xp(&mut guild.xp, pending);
+    }
+
+    Ok(guild)
+}
+
+/// Record that a member is earning XP for a message, returning false if they
+/// already did within the cooldown
+async fn start_cooldown(gid: GuildId, user: UserId, cooldown: u32) -> bool {
+    let now = Instant::now();
+    let mut last = LAST_AWARD.lock().await;
+
+    if last.len() > MAX_TRACKED {
+        last.retain(|_, &mut l| !off_cooldown(Some(l), now, MAX_COOLDOWN));
+    }
+
+    if !off_cooldown(last.get(&(gid, user)).copied(), now, cooldown) {
+        return false;
+    }
+
+    last.insert((gid, user), now);
+    true
+}
+
+/// Give a member XP, handing out reward roles and announcing the new level
+/// if they leveled up
+///
+/// Announcements without a configured channel are sent as a reply to
+/// `source`, or skipped if there is none.
+async fn award(
+    ctx: &Context,
+    storage: &Storage,
+    cfg: &guild::Leveling,
+    gid: GuildId,
+    user: UserId,
+    amount: u64,
+    source: Option<&ChannelMessage>,
+) -> Result {
+    let (from, to) = loop {
+        let mut xp = XP.lock().await;
+        let guild = xp.entry(gid).or_default();
+
+        if guild.stored.is_some() {
+            let old = guild.total(user.get());
+            let pending = guild.pending.entry(user.get()).or_default();
+            *pending = pending.saturating_add(amount);
+            break (level(cfg, old), level(cfg, old.saturating_add(amount)));
+        }
+
+        drop(xp);
+        let _saving = SAVING.read().await;
+        let stored = storage.guild(gid).await.context("Error loading XP")?.xp;
+        XP.lock()
+            .await
+            .entry(gid)
+            .or_default()
+            .stored
+            .get_or_insert(stored);
+    };
+
+    if to <= from {
+        return Ok(());
+    }
+
+    debug!(%gid, %user, from, to, "Member leveled up");
+
+    let reason = format!("Reached level {to}");
+    for role in rewards(cfg, from, to) {
+        if let Err(err) = ctx
+            .http
+            .add_member_role(gid, user, role, Some(reason.as_str()))
+            .await
+        {
+            warn!(%gid, %user, %role, %err, "Error giving level reward role");
+        }
+    }
+
+    let body: MessageBody<Infallible> = MessageBody::rich(|mb| {
+        mb.push("\u{1f389} ")
+            .mention(&user)
+            .push(format!(" reached level {to}!"))
+    })
+    .ping_users(vec![user]);
+
+    let sent = match (cfg.announce_channel, source) {
+        (0, None) => return Ok(()),
+        (0, Some(message)) => {
+            message
+                .channel_id
+                .send_message(
+                    &ctx.http,
+                    CreateMessage::new()
+                        .build_with(body)
+                        .reference_message(message),
+                )
+                .await
+        },
+        (channel, _) => {
+            ChannelId::new(channel)
+                .send_message(&ctx.http, CreateMessage::new().build_with(body))
+                .await
+        },
+    };
+    sent.context("Error announcing level-up")?;
+
+    Ok(())
+}
+
+/// Award XP for a message if its guild has leveling on and its author is
+/// off cooldown
+pub async fn award_message(ctx: &Context, message: &ChannelMessage) -> Result {
+    let Some(gid) = message.guild_id else {
+        return Ok(());
+    };
+
+    if message.author.bot
+        || !matches!(
+            message.kind,
+            MessageType::Regular | MessageType::InlineReply
+        )
+    {
+        return Ok(());
+    }
+
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let Some(cfg) = settings(&storage, gid).await? else {
+        return Ok(());
+    };
+
+    if cfg.message_xp == 0
+        || !start_cooldown(gid, message.author.id, cfg.message_cooldown).await
+    {
+        return Ok(());
+    }
+
+    award(
+        ctx,
+        &storage,
+        &cfg,
+        gid,
+        message.author.id,
+        cfg.message_xp.into(),
+        Some(message),
+    )
+    .await
+}
+
+/// Award XP for a finished voice session if its guild has leveling on
+pub async fn award_voice(
+    ctx: &Context,
+    gid: GuildId,
+    user: UserId,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result {
+    let Ok(minutes) = u64::try_from((end - start).num_minutes()) else {
+        return Ok(());
+    };
+
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let Some(cfg) = settings(&storage, gid).await? else {
+        return Ok(());
+    };
+
+    let amount = minutes.saturating_mul(cfg.voice_xp.into());
+    if amount == 0 {
+        return Ok(());
+    }
+
+    award(ctx, &storage, &cfg, gid, user, amount, None).await
+}
+
+/// Render a page of the leaderboard
+fn leaderboard_page(cfg: &guild::Leveling, xp: &HashMap<u64, u64>, page: u32) -> MessageBody {
+    let ranking = ranking(xp);
+    if ranking.is_empty() {
+        return MessageBody::plain("Nobody has earned any XP yet.");
+    }
+
+    let pages = ranking.len().div_ceil(PAGE_SIZE);
+    let page = usize::try_from(page).map_or(pages - 1, |p| p.min(pages - 1));
+
+    let body = MessageBody::rich(|mb| {
+        mb.push_bold_line(format!("Leaderboard (page {}/{pages}):", page + 1));
+
+        for (i, &(user, xp)) in ranking
+            .iter()
+            .enumerate()
+            .skip(page * PAGE_SIZE)
+            .take(PAGE_SIZE)
+        {
+            mb.push(format!("{}. ", i + 1))
+                .user(UserId::new(user))
+                .push_line(format!(
+                    " \u{2014} level {} ({xp} XP)",
+                    level(cfg, xp)
+                ));
+        }
+
+        mb
+    });
+
+    let page = u32::try_from(page).unwrap_or_else(|_| unreachable!());
+    let button = |page| ComponentPayload::LeaderboardPage(component::LeaderboardPage { page });
+    let last = u32::try_from(pages - 1).unwrap_or(u32::MAX);
+    body.buttons(|b| {
+        b.button(
+            button(page.saturating_sub(1)),
+            ButtonStyle::Secondary,
+            "Previous",
+            page == 0,
+        )
+        .button(
+            button(page.saturating_add(1)),
+            ButtonStyle::Secondary,
+            "Next",
+            page >= last,
+        )
+    })
+}
+
+#[derive(Debug)]
+pub struct RankCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for RankCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}rank", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for RankCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Show a member's level and XP", |a| {
+            a.user("user", "Whose rank to show (default: you)", false)
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let target = match visitor.visit_user("user")?.optional() {
+            Some((u, _)) => u,
+            None => visitor.user(),
+        }
+        .clone();
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild = load_guild(&storage, gid).await?;
+        let Some(cfg) = guild.leveling else {
+            return Err(responder
+                .create_message(
+                    Message::plain(
+                        "Leveling isn't enabled in this server.  Someone with the Manage Server \
+                         permission can turn it on.",
+                    )
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending disabled error")?
+                .into_err("Leveling disabled"));
+        };
+
+        let responder = responder
+            .defer_message(MessageOpts::default())
+            .await
+            .context("Error sending deferred message")?;
+
+        let xp = guild.xp.get(&target.id.get()).copied().unwrap_or(0);
+        let rank = ranking(&guild.xp)
+            .iter()
+            .position(|&(u, _)| u == target.id.get())
+            .map(|r| r + 1);
+        let current = level(&cfg, xp);
+        let next = progress(&cfg, xp);
+
+        let avatar = avatar(&target).await;
+        #[expect(
+            clippy::cast_precision_loss,
+            reason = "The fraction only sets the width of a progress bar"
+        )]
+        let fraction = next.map_or(1.0, |(into, needed)| into as f64 / needed as f64);
+        let bytes = tokio::task::spawn_blocking(move || {
+            let card = render_card(avatar.as_ref(), fraction);
+            jpeggr::encode(
+                &DynamicImage::ImageRgb8(card),
+                90,
+                &jpeggr::Metadata::default(),
+            )
+            .context("Error encoding rank card")
+        })
+        .await
+        .context("Error running image task")??;
+
+        let msg = Message::rich(|mb| {
+            mb.user(target.id)
+                .push(format!(" \u{2014} level {current}"));
+            match rank {
+                Some(r) => mb.push(format!(", rank #{r}")),
+                None => mb.push(", unranked"),
+            };
+            match next {
+                Some((into, needed)) => mb.push(format!(
+                    "\n{into}/{needed} XP towards level {} ({xp} XP total)",
+                    current + 1
+                )),
+                None => mb.push(format!("\nMaximum level reached ({xp} XP total)")),
+            }
+        })
+        .attach([CreateAttachment::bytes(bytes, "rank.jpg")]);
+
+        let progress = CommandProgress::new(&responder);
+        progress
+            .finish(msg)
+            .await
+            .context("Error sending rank card")?;
+
+        Ok(responder.into())
+    }
+}
+
+#[derive(Debug)]
+pub struct LeaderboardCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for LeaderboardCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}leaderboard", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for LeaderboardCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Show the members with the most XP", |a| {
+            a.int("page", "Which page to start on (default: 1)", false, 1..=1000)
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let page = visitor.visit_i64("page")?.optional().unwrap_or(1);
+        let page = u32::try_from(page - 1).unwrap_or_else(|_| unreachable!());
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild::Guild { leveling, xp, .. } = load_guild(&storage, gid).await?;
+        let Some(cfg) = leveling else {
+            return Err(responder
+                .create_message(
+                    Message::plain("Leveling isn't enabled in this server.").ephemeral(true),
+                )
+                .await
+                .context("Error sending disabled error")?
+                .into_err("Leveling disabled"));
+        };
+
+        Ok(responder
+            .create_message(leaderboard_page(&cfg, &xp, page).into())
+            .await
+            .context("Error sending leaderboard")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl RpcHandler<Schema, ComponentKey> for LeaderboardCommand {
+    fn register_keys(&self) -> &'static [ComponentKey] { &[ComponentKey::LeaderboardPage] }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        payload: ComponentPayload,
+        visitor: &mut ComponentVisitor<'_>,
+        responder: ComponentResponder<'_, 'a>,
+    ) -> ComponentResult<'a> {
+        let ComponentPayload::LeaderboardPage(component::LeaderboardPage { page }) = payload else {
+            unreachable!(); // TODO: set up an error for this
+        };
+        let (gid, _memb) = visitor.guild()?.required()?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild::Guild { leveling, xp, .. } = load_guild(&storage, gid).await?;
+        let body = match leveling {
+            Some(cfg) => leaderboard_page(&cfg, &xp, page),
+            None => MessageBody::plain("Leveling isn't enabled in this server."),
+        };
+
+        Ok(responder
+            .update_message(body.into())
+            .await
+            .context("Error updating leaderboard")?
+            .into())
+    }
+}
+
+#[derive(Debug)]
+pub struct LevelsCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for LevelsCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}levels", opts.command_base),
+        }
+    }
+}
+
+impl LevelsCommand {
+    /// Save new settings for a guild, or turn leveling off if `None`
+    async fn save(ctx: &Context, gid: GuildId, cfg: Option<guild::Leveling>) -> Result {
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let enabled = SETTINGS
+            .update(&storage, gid, |g| {
+                // Earned XP is deleted rather than kept around for when
+                // leveling is turned back on
+                if cfg.is_none() {
+                    g.xp.clear();
+                }
+                g.leveling.clone_from(&cfg);
+                (cfg.is_some(), cfg.map(Arc::new))
+            })
+            .await
+            .context("Error saving leveling settings")?;
+        if !enabled {
+            XP.lock().await.remove(&gid);
+        }
+
+        Ok(())
+    }
+
+    async fn set<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let mut cfg = settings(&storage, gid)
+            .await?
+            .map_or_else(defaults, |c| (*c).clone());
+
+        for (name, field) in [
+            ("message_xp", &mut cfg.message_xp),
+            ("cooldown", &mut cfg.message_cooldown),
+            ("voice_xp", &mut cfg.voice_xp),
+            ("base", &mut cfg.base),
+        ] {
+            if let Some(val) = visitor.visit_i64(name)?.optional() {
+                *field = u32::try_from(val).with_context(|| format!("{name} out of range"))?;
+            }
+        }
+
+        if let Some(curve) = visitor.visit_string("curve")?.optional() {
+            cfg.set_curve(parse_curve(curve).context("Invalid curve")?);
+        }
+
+        if let Some(channel) = visitor.visit_channel("announce_channel")?.optional() {
+            cfg.announce_channel = channel.id.get();
+        }
+
+        if visitor
+            .visit_bool("announce_in_place")?
+            .optional()
+            .unwrap_or(false)
+        {
+            cfg.announce_channel = 0;
+        }
+
+        if let Err(e) = validate(&cfg) {
+            return Err(responder
+                .create_message(Message::plain(e).ephemeral(true))
+                .await
+                .context("Error sending settings error")?
+                .into_err("Invalid leveling settings"));
+        }
+
+        Self::save(ctx, gid, Some(cfg.clone())).await?;
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new("Leveling settings changed", user),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| describe(mb.push_line("Leveling is on:"), &cfg))
+                    .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn disable<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let user = visitor.user().id;
+
+        Self::save(ctx, gid, None).await?;
+
+        botlog::record(ctx, gid, botlog::Entry::new("Leveling disabled", user)).await;
+
+        Ok(responder
+            .create_message(
+                Message::plain("Leveling is off and all earned XP has been deleted.")
+                    .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn reward<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let level = u32::try_from(visitor.visit_i64("level")?.required()?)
+            .unwrap_or_else(|_| unreachable!());
+        let role = visitor.visit_role("role")?.optional().map(|r| r.id);
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let Some(cfg) = settings(&storage, gid).await? else {
+            return Err(responder
+                .create_message(Message::plain("Leveling is off in this server.").ephemeral(true))
+                .await
+                .context("Error sending disabled error")?
+                .into_err("Leveling not enabled"));
+        };
+
+        let mut cfg = (*cfg).clone();
+        match role {
+            Some(role) => cfg.rewards.insert(level, role.get()),
+            None => cfg.rewards.remove(&level),
+        };
+
+        if let Err(e) = validate(&cfg) {
+            return Err(responder
+                .create_message(Message::plain(e).ephemeral(true))
+                .await
+                .context("Error sending settings error")?
+                .into_err("Invalid leveling settings"));
+        }
+
+        Self::save(ctx, gid, Some(cfg)).await?;
+
+        let entry = botlog::Entry::new(
+            if role.is_some() {
+                "Level reward set"
+            } else {
+                "Level reward removed"
+            },
+            user,
+        )
+        .with_reason(format!("Level {level}"));
+        botlog::record(ctx, gid, match role {
+            Some(r) => entry.with_target(r.mention().to_string()),
+            None => entry,
+        })
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| match role {
+                    Some(r) => mb
+                        .push(format!("Members reaching level {level} will now get "))
+                        .role(r)
+                        .push("."),
+                    None => mb.push(format!("Level {level} no longer has a reward.")),
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for LevelsCommand {
+    fn register_global(&self) -> CommandInfo {
+        let max_xp = i64::from(MAX_XP);
+
+        CommandInfo::build_slash(&self.name, "Manage member levels and XP", |a| {
+            a.build_subcmd("set", "Turn on leveling or change its settings", |a| {
+                a.int(
+                    "message_xp",
+                    "XP awarded per message, or 0 for none (default: 15)",
+                    false,
+                    0..=max_xp,
+                )
+                .int(
+                    "cooldown",
+                    "Seconds before a member's messages earn XP again (default: 60)",
+                    false,
+                    0..=i64::from(MAX_COOLDOWN),
+                )
+                .int(
+                    "voice_xp",
+                    "XP awarded per minute in voice, or 0 for none (default: 5)",
+                    false,
+                    0..=max_xp,
+                )
+                .int(
+                    "base",
+                    "XP needed for level 1 (default: 100)",
+                    false,
+                    1..=i64::from(MAX_BASE),
+                )
+                .string_choice("curve", "How fast later levels get harder", false, [
+                    Choice::new("Flat", curve_name(Curve::Flat).to_owned()),
+                    Choice::new("Linear (default)", curve_name(Curve::Linear).to_owned()),
+                    Choice::new("Quadratic", curve_name(Curve::Quadratic).to_owned()),
+                ])
+                .channel(
+                    "announce_channel",
+                    "Channel to announce level-ups in",
+                    false,
+                    [ChannelType::Text],
+                )
+                .bool(
+                    "announce_in_place",
+                    "Announce level-ups in reply to the message that earned them instead",
+                    false,
+                )
+            })
+            .build_subcmd("disable", "Turn off leveling and delete all XP", id)
+            .build_subcmd("reward", "Give members a role on reaching a level", |a| {
+                a.int(
+                    "level",
+                    "Level to give the role at",
+                    true,
+                    1..=i64::from(MAX_LEVEL),
+                )
+                .role("role", "Role to give, or leave out to remove the reward", false)
+            })
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        match *visitor.visit_subcmd()? {
+            ["set"] => self.set(ctx, visitor, responder).await,
+            ["disable"] => self.disable(ctx, visitor, responder).await,
+            ["reward"] => self.reward(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use serenity::model::id::RoleId;
+
+    use super::{
+        add_xp, defaults, level, off_cooldown, progress, ranking, render_card, rewards,
+        threshold, validate, Curve, GuildXp, AVATAR_SIZE, BAR_FILL, BAR_TRACK, CARD_HEIGHT,
+        CARD_PADDING, CARD_PLACEHOLDER, CARD_WIDTH, MAX_LEVEL,
+    };
+
+    #[test]
+    fn curves() {
+        let mut cfg = defaults();
+        cfg.base = 10;
+
+        cfg.set_curve(Curve::Flat);
+        assert_eq!((0..4).map(|l| threshold(&cfg, l)).collect::<Vec<_>>(), [
+            0, 10, 20, 30
+        ]);
+        cfg.set_curve(Curve::Linear);
+        assert_eq!((0..4).map(|l| threshold(&cfg, l)).collect::<Vec<_>>(), [
+            0, 10, 30, 60
+        ]);
+        cfg.set_curve(Curve::Quadratic);
+        assert_eq!((0..4).map(|l| threshold(&cfg, l)).collect::<Vec<_>>(), [
+            0, 10, 50, 140
+        ]);
+
+        cfg.set_curve(Curve::Linear);
+        assert_eq!(level(&cfg, 0), 0);
+        assert_eq!(level(&cfg, 29), 1);
+        assert_eq!(level(&cfg, 30), 2);
+        assert_eq!(progress(&cfg, 45), Some((15, 30)));
+        assert_eq!(level(&cfg, u64::MAX), MAX_LEVEL);
+        assert_eq!(progress(&cfg, u64::MAX), None);
+    }
+
+    #[test]
+    fn pending_xp() {
+        let mut xp = GuildXp {
+            stored: Some([(1, 100), (2, u64::MAX)].into()),
+            pending: [(1, 15), (2, 15), (3, 15)].into(),
+        };
+        assert_eq!(xp.total(1), 115);
+        assert_eq!(xp.total(2), u64::MAX);
+        assert_eq!(xp.total(3), 15);
+        assert_eq!(xp.total(4), 0);
+
+        let pending = std::mem::take(&mut xp.pending);
+        add_xp(xp.stored.as_mut().unwrap(), &pending);
+        assert_eq!(xp.total(1), 115);
+        assert_eq!(xp.total(2), u64::MAX);
+        assert_eq!(xp.total(3), 15);
+    }
+
+    #[test]
+    fn role_rewards() {
+        let mut cfg = defaults();
+        cfg.rewards = [(1, 10), (5, 50), (3, 30)].into();
+
+        assert_eq!(rewards(&cfg, 0, 5), [10, 30, 50].map(RoleId::new));
+        assert_eq!(rewards(&cfg, 1, 4), [RoleId::new(30)]);
+        assert!(rewards(&cfg, 5, 9).is_empty());
+
+        assert_eq!(validate(&cfg), Ok(()));
+        cfg.rewards.insert(0, 1);
+        assert!(validate(&cfg).is_err());
+        cfg.rewards.remove(&0);
+        cfg.base = 0;
+        assert!(validate(&cfg).is_err());
+    }
+
+    #[test]
+    fn leaderboard() {
+        let xp = [(1, 50), (2, 200), (3, 0), (4, 50)].into();
+        assert_eq!(ranking(&xp), [(2, 200), (1, 50), (4, 50)]);
+    }
+
+    #[test]
+    fn cooldown() {
+        let start = Instant::now();
+        let at = |s| start + Duration::from_secs(s);
+
+        assert!(off_cooldown(None, start, 60));
+        assert!(!off_cooldown(Some(start), at(59), 60));
+        assert!(off_cooldown(Some(start), at(60), 60));
+        assert!(off_cooldown(Some(at(5)), start, 0));
+    }
+
+    #[test]
+    fn card() {
+        let card = render_card(None, 0.5);
+        assert_eq!(card.dimensions(), (CARD_WIDTH, CARD_HEIGHT));
+        assert_eq!(*card.get_pixel(20, 20), CARD_PLACEHOLDER);
+
+        let y = CARD_HEIGHT / 2;
+        assert_eq!(
+            *card.get_pixel(AVATAR_SIZE + 2 * CARD_PADDING, y),
+            BAR_FILL
+        );
+        assert_eq!(*card.get_pixel(CARD_WIDTH - 20, y), BAR_TRACK);
+        assert_eq!(
+            *render_card(None, 1.0).get_pixel(CARD_WIDTH - 20, y),
+            BAR_FILL
+        );
+    }
+}
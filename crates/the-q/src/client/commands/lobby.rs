@@ -0,0 +1,645 @@
+use rand::{seq::SliceRandom, Rng};
+use serenity::{
+    builder::{CreateChannel, CreateEmbed, EditMessage},
+    model::{
+        channel::ChannelType,
+        id::{ChannelId, MessageId, UserId},
+        Permissions,
+    },
+};
+
+use super::{botlog, prelude::*};
+use crate::{client::storage, proto::guild};
+
+const DEFAULT_TEAMS: u32 = 2;
+const MAX_TEAMS: u32 = 10;
+/// Largest team size, which keeps a full lobby within one message
+const MAX_TEAM_SIZE: u32 = 25;
+/// Longest channel name Discord allows
+const MAX_CHANNEL_NAME: usize = 100;
+
+/// Reasons a lobby request is turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Refusal {
+    NoLobby,
+    Joined,
+    NotJoined,
+    Full,
+    NotHost,
+    TooFew(u32),
+}
+
+impl Refusal {
+    fn message(self) -> String {
+        match self {
+            Self::NoLobby => "There's no open lobby in this channel.".into(),
+            Self::Joined => "You've already joined this lobby.".into(),
+            Self::NotJoined => "You haven't joined this lobby.".into(),
+            Self::Full => "This lobby is full.".into(),
+            Self::NotHost => "Only the host or a member with Manage Server can do that.".into(),
+            Self::TooFew(n) => format!("This lobby needs at least {n} players to start."),
+        }
+    }
+}
+
+fn is_manager(memb: &serenity::model::guild::Member) -> bool {
+    memb.permissions
+        .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+}
+
+/// The most players a lobby can hold, or `None` if it has no limit
+fn capacity(lobby: &guild::Lobby) -> Option<usize> {
+    (lobby.team_size != 0)
+        .then(|| usize::try_from(lobby.teams * lobby.team_size).unwrap_or_else(|_| unreachable!()))
+}
+
+fn join(lobby: &mut guild::Lobby, user: u64) -> Result<(), Refusal> {
+    if lobby.players.contains(&user) {
+        return Err(Refusal::Joined);
+    }
+
+    if capacity(lobby).is_some_and(|c| lobby.players.len() >= c) {
+        return Err(Refusal::Full);
+    }
+
+    lobby.players.push(user);
+    Ok(())
+}
+
+fn leave(lobby: &mut guild::Lobby, user: u64) -> Result<(), Refusal> {
+    let idx = lobby
+        .players
+        .iter()
+        .position(|&p| p == user)
+        .ok_or(Refusal::NotJoined)?;
+    lobby.players.remove(idx);
+    Ok(())
+}
+
+/// Shuffle the players of a lobby and deal them into teams, whose sizes
+/// differ by at most one
+fn split(mut players: Vec<u64>, teams: u32, rng: &mut impl Rng) -> Vec<Vec<u64>> {
+    let mut out = vec![vec![]; usize::try_from(teams.max(1)).unwrap_or_else(|_| unreachable!())];
+    let len = out.len();
+
+    players.shuffle(rng);
+    for (i, player) in players.into_iter().enumerate() {
+        out[i % len].push(player);
+    }
+
+    out
+}
+
+fn embed(lobby: &guild::Lobby) -> Embed {
+    let count = lobby.players.len();
+    let size = match capacity(lobby) {
+        Some(cap) => format!("{count}/{cap} players"),
+        None => format!("{count} player{}", if count == 1 { "" } else { "s" }),
+    };
+
+    Embed::default()
+        .title(format!("{} lobby", lobby.game))
+        .desc_rich(|mb| {
+            mb.push("Hosted by ")
+                .mention(&UserId::new(lobby.host))
+                .push(format!(" \u{2014} {} teams, {size}\n", lobby.teams));
+
+            if lobby.players.is_empty() {
+                mb.push_italic("No players yet");
+            }
+
+            for (i, &player) in lobby.players.iter().enumerate() {
+                mb.push(format!("\n{}. ", i + 1))
+                    .mention(&UserId::new(player));
+            }
+
+            mb
+        })
+}
+
+fn body(lobby: &guild::Lobby) -> MessageBody {
+    let button = |action| {
+        ComponentPayload::LobbyButton(component::LobbyButton {
+            lobby: lobby.id,
+            action: action as i32,
+        })
+    };
+
+    MessageBody::from(embed(lobby)).buttons(|b| {
+        b.button(
+            button(component::lobby_button::Action::Join),
+            ButtonStyle::Success,
+            "Join",
+            false,
+        )
+        .button(
+            button(component::lobby_button::Action::Leave),
+            ButtonStyle::Secondary,
+            "Leave",
+            false,
+        )
+    })
+}
+
+fn teams_embed(game: &str, teams: &[Vec<u64>], unmoved: usize) -> Embed {
+    Embed::default()
+        .title(format!("{game} teams"))
+        .desc_rich(|mb| {
+            for (i, team) in teams.iter().enumerate() {
+                if i > 0 {
+                    mb.push("\n\n");
+                }
+
+                mb.push_bold(format!("Team {}", i + 1));
+                for &player in team {
+                    mb.push("\n").mention(&UserId::new(player));
+                }
+            }
+
+            if unmoved > 0 {
+                mb.push("\n\n").push_italic(format!(
+                    "{unmoved} player{} could not be moved to their team's voice channel.",
+                    if unmoved == 1 { "" } else { "s" }
+                ));
+            }
+
+            mb
+        })
+}
+
+fn channel_name(game: &str, team: usize) -> String {
+    let suffix = format!(" \u{2014} Team {team}");
+    let game: String = game
+        .chars()
+        .take(MAX_CHANNEL_NAME - suffix.chars().count())
+        .collect();
+    format!("{game}{suffix}")
+}
+
+/// Edit a lobby's message to match its current state, for changes not made
+/// through its buttons
+async fn refresh(ctx: &Context, channel: ChannelId, lobby: &guild::Lobby) -> Result {
+    channel
+        .edit_message(
+            &ctx.http,
+            MessageId::new(lobby.message),
+            EditMessage::new().embed(CreateEmbed::from(embed(lobby))),
+        )
+        .await
+        .context("Error updating lobby message")?;
+
+    Ok(())
+}
+
+/// Replace a lobby's message with a note that it is no longer open
+async fn close(ctx: &Context, channel: ChannelId, lobby: &guild::Lobby, note: &str) -> Result {
+    channel
+        .edit_message(
+            &ctx.http,
+            MessageId::new(lobby.message),
+            EditMessage::new()
+                .embed(CreateEmbed::from(
+                    Embed::default()
+                        .title(format!("{} lobby", lobby.game))
+                        .desc_plain(note),
+                ))
+                .components(vec![]),
+        )
+        .await
+        .context("Error closing lobby message")?;
+
+    Ok(())
+}
+
+/// Create a voice channel for each team next to the lobby's channel and move
+/// the players into it, returning the number of players that could not be
+/// moved
+async fn create_voice(
+    ctx: &Context,
+    gid: GuildId,
+    channel: ChannelId,
+    game: &str,
+    teams: &[Vec<u64>],
+) -> Result<usize> {
+    let parent = channel
+        .to_channel(ctx)
+        .await
+        .context("Error getting lobby channel")?
+        .guild()
+        .and_then(|c| c.parent_id);
+    let mut unmoved = 0;
+
+    for (i, team) in teams.iter().enumerate() {
+        let mut create = CreateChannel::new(channel_name(game, i + 1)).kind(ChannelType::Voice);
+        if let Some(parent) = parent {
+            create = create.category(parent);
+        }
+
+        let voice = gid
+            .create_channel(&ctx.http, create)
+            .await
+            .context("Error creating team voice channel")?;
+
+        for &player in team {
+            // Members not already in a voice channel cannot be moved
+            if let Err(err) = gid
+                .move_member(&ctx.http, UserId::new(player), voice.id)
+                .await
+            {
+                debug!(%gid, player, ?err, "Error moving player to team channel");
+                unmoved += 1;
+            }
+        }
+    }
+
+    Ok(unmoved)
+}
+
+#[derive(Debug)]
+pub struct LobbyCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for LobbyCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}lobby", opts.command_base),
+        }
+    }
+}
+
+impl LobbyCommand {
+    async fn refuse<'a>(
+        responder: CommandResponder<'_, 'a>,
+        refusal: Refusal,
+    ) -> CommandResult<'a> {
+        Err(responder
+            .create_message(Message::plain(refusal.message()).ephemeral(true))
+            .await
+            .context("Error sending lobby error")?
+            .into_err("Lobby request refused"))
+    }
+
+    async fn create<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, memb) = visitor.guild()?.required()?;
+        let manager = is_manager(memb);
+        let game = visitor.visit_string("game")?.required()?.to_owned();
+        let teams = visitor
+            .visit_i64("teams")?
+            .optional()
+            .map_or(Ok(DEFAULT_TEAMS), u32::try_from)
+            .context("Invalid team count")?;
+        let team_size = visitor
+            .visit_i64("team_size")?
+            .optional()
+            .map_or(Ok(0), u32::try_from)
+            .context("Invalid team size")?;
+        let voice = visitor.visit_bool("voice")?.optional().unwrap_or(false);
+        let channel = visitor.channel_id();
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let old = storage.guild(gid).await?.lobbies.remove(&channel.get());
+        if old
+            .as_ref()
+            .is_some_and(|l| l.host != user.get() && !manager)
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain(concat!(
+                        "There's already an open lobby in this channel, and only its host can ",
+                        "replace it.",
+                    ))
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending existing lobby error")?
+                .into_err("Lobby already open"));
+        }
+
+        let responder = responder
+            .defer_message(MessageOpts::default())
+            .await
+            .context("Error sending deferred message")?;
+
+        let mut lobby = guild::Lobby {
+            id: responder.interaction_ctx().id().get(),
+            message: 0,
+            host: user.get(),
+            game,
+            teams,
+            team_size,
+            players: vec![user.get()],
+            voice,
+        };
+
+        let msg = responder
+            .edit(body(&lobby))
+            .await
+            .context("Error sending lobby message")?;
+        lobby.message = msg.id.get();
+
+        let old = storage
+            .update_guild(gid, |g| g.lobbies.insert(channel.get(), lobby))
+            .await
+            .context("Error saving lobby")?;
+
+        if let Some(old) = old {
+            close(ctx, channel, &old, "This lobby was replaced by a new one.")
+                .await
+                .map_err(|err| warn!(?err, "Error closing replaced lobby"))
+                .ok();
+        }
+
+        Ok(responder.into())
+    }
+
+    async fn join_or_leave<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+        joining: bool,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let channel = visitor.channel_id();
+        let user = visitor.user().id.get();
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let res = storage
+            .update_guild(gid, |g| {
+                let lobby = g.lobbies.get_mut(&channel.get()).ok_or(Refusal::NoLobby)?;
+
+                if joining {
+                    join(lobby, user)?;
+                } else {
+                    leave(lobby, user)?;
+                }
+
+                Ok(lobby.clone())
+            })
+            .await
+            .context("Error updating lobby")?;
+
+        let lobby = match res {
+            Ok(l) => l,
+            Err(r) => return Self::refuse(responder, r).await,
+        };
+
+        refresh(ctx, channel, &lobby).await?;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push(if joining { "Joined" } else { "Left" })
+                        .push(" the lobby for ")
+                        .push_bold_safe(lobby.game.as_str())
+                        .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending lobby confirmation")?
+            .into())
+    }
+
+    async fn start<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, memb) = visitor.guild()?.required()?;
+        let channel = visitor.channel_id();
+        let user = visitor.user().id;
+        let manager = is_manager(memb);
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let res = storage
+            .update_guild(gid, |g| {
+                let lobby = g.lobbies.get(&channel.get()).ok_or(Refusal::NoLobby)?;
+
+                if lobby.host != user.get() && !manager {
+                    return Err(Refusal::NotHost);
+                }
+
+                if lobby.players.len() < usize::try_from(lobby.teams).unwrap_or(usize::MAX) {
+                    return Err(Refusal::TooFew(lobby.teams));
+                }
+
+                Ok(g.lobbies
+                    .remove(&channel.get())
+                    .unwrap_or_else(|| unreachable!()))
+            })
+            .await
+            .context("Error removing started lobby")?;
+
+        let lobby = match res {
+            Ok(l) => l,
+            Err(r) => return Self::refuse(responder, r).await,
+        };
+
+        let responder = responder
+            .defer_message(MessageOpts::default())
+            .await
+            .context("Error sending deferred message")?;
+
+        let teams = split(lobby.players.clone(), lobby.teams, &mut rand::thread_rng());
+
+        close(ctx, channel, &lobby, "This lobby has started.")
+            .await
+            .map_err(|err| warn!(?err, "Error closing started lobby"))
+            .ok();
+
+        let unmoved = if lobby.voice {
+            let unmoved = create_voice(ctx, gid, channel, &lobby.game, &teams).await?;
+
+            botlog::record(
+                ctx,
+                gid,
+                botlog::Entry::new("Lobby voice channels created", user)
+                    .with_target(lobby.game.clone())
+                    .with_reason(format!("{} team(s)", teams.len())),
+            )
+            .await;
+
+            unmoved
+        } else {
+            0
+        };
+
+        responder
+            .edit(teams_embed(&lobby.game, &teams, unmoved).into())
+            .await
+            .context("Error sending lobby teams")?;
+
+        Ok(responder.into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for LobbyCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Gather players for a game", |a| {
+            a.build_subcmd("create", "Open a lobby in this channel", |a| {
+                a.string("game", "The game being played", true, 1..=80)
+                    .int(
+                        "teams",
+                        "Number of teams to split players into (default: 2)",
+                        false,
+                        1..=i64::from(MAX_TEAMS),
+                    )
+                    .int(
+                        "team_size",
+                        "Most players per team (default: no limit)",
+                        false,
+                        1..=i64::from(MAX_TEAM_SIZE),
+                    )
+                    .bool(
+                        "voice",
+                        "Create a voice channel for each team on start (default: false)",
+                        false,
+                    )
+            })
+            .build_subcmd("join", "Join the lobby in this channel", id)
+            .build_subcmd("leave", "Leave the lobby in this channel", id)
+            .build_subcmd("start", "Split the lobby in this channel into teams", id)
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        match *visitor.visit_subcmd()? {
+            ["create"] => self.create(ctx, visitor, responder).await,
+            ["join"] => self.join_or_leave(ctx, visitor, responder, true).await,
+            ["leave"] => self.join_or_leave(ctx, visitor, responder, false).await,
+            ["start"] => self.start(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[async_trait]
+impl RpcHandler<Schema, ComponentKey> for LobbyCommand {
+    fn register_keys(&self) -> &'static [ComponentKey] { &[ComponentKey::LobbyButton] }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        payload: ComponentPayload,
+        visitor: &mut ComponentVisitor<'_>,
+        responder: ComponentResponder<'_, 'a>,
+    ) -> ComponentResult<'a> {
+        let ComponentPayload::LobbyButton(component::LobbyButton { lobby: id, action }) = payload
+        else {
+            unreachable!(); // TODO: set up an error for this
+        };
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let channel = visitor.channel_id();
+        let user = visitor.user().id.get();
+        let action = component::lobby_button::Action::try_from(action).ok();
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let res = storage
+            .update_guild(gid, |g| {
+                let lobby = g
+                    .lobbies
+                    .get_mut(&channel.get())
+                    .filter(|l| l.id == id)
+                    .ok_or(Refusal::NoLobby)?;
+
+                match action {
+                    Some(component::lobby_button::Action::Join) => join(lobby, user)?,
+                    Some(component::lobby_button::Action::Leave) => leave(lobby, user)?,
+                    Some(component::lobby_button::Action::Unknown) | None => {
+                        warn!(id, "Unknown lobby button action");
+                        return Err(Refusal::NoLobby);
+                    },
+                }
+
+                Ok(lobby.clone())
+            })
+            .await
+            .context("Error updating lobby")?;
+
+        match res {
+            Ok(lobby) => Ok(responder
+                .update_message(body(&lobby).into())
+                .await
+                .context("Error updating lobby message")?
+                .into()),
+            Err(r) => Err(responder
+                .create_message(Message::plain(r.message()).ephemeral(true))
+                .await
+                .context("Error sending lobby error")?
+                .into_err("Lobby button refused")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{channel_name, join, leave, split, Refusal, MAX_CHANNEL_NAME};
+    use crate::proto::guild;
+
+    #[test]
+    fn membership() {
+        let mut lobby = guild::Lobby {
+            teams: 2,
+            team_size: 1,
+            players: vec![1],
+            ..guild::Lobby::default()
+        };
+
+        assert_eq!(join(&mut lobby, 1), Err(Refusal::Joined));
+        assert_eq!(join(&mut lobby, 2), Ok(()));
+        assert_eq!(join(&mut lobby, 3), Err(Refusal::Full));
+        assert_eq!(leave(&mut lobby, 1), Ok(()));
+        assert_eq!(leave(&mut lobby, 1), Err(Refusal::NotJoined));
+        assert_eq!(join(&mut lobby, 3), Ok(()));
+        assert_eq!(lobby.players, [2, 3]);
+
+        lobby.team_size = 0;
+        for user in 4..100 {
+            assert_eq!(join(&mut lobby, user), Ok(()));
+        }
+    }
+
+    #[test]
+    fn teams() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let players: Vec<u64> = (1..=7).collect();
+        let teams = split(players.clone(), 3, &mut rng);
+
+        assert_eq!(teams.len(), 3);
+        assert_eq!(teams.iter().map(Vec::len).collect::<Vec<_>>(), [3, 2, 2]);
+
+        let mut all: Vec<_> = teams.concat();
+        all.sort_unstable();
+        assert_eq!(all, players);
+
+        assert_eq!(split(vec![], 2, &mut rng), [Vec::<u64>::new(), vec![]]);
+    }
+
+    #[test]
+    fn channel_names() {
+        assert_eq!(channel_name("Chess", 2), "Chess \u{2014} Team 2");
+        assert_eq!(
+            channel_name(&"\u{e9}".repeat(200), 10).chars().count(),
+            MAX_CHANNEL_NAME
+        );
+    }
+}
@@ -1,11 +1,36 @@
+mod alias;
+mod antispam;
+mod autoreply;
+mod backup;
+mod bookmark;
+mod botlog;
+mod channel;
+mod config;
+mod errors;
+mod event;
 mod explode;
+mod feed;
+mod guild_cache;
 mod jpeg;
+mod level;
+mod lobby;
 mod point;
+mod poll;
+mod prefs;
+mod quote;
 mod re;
+mod reload;
+mod remind;
+mod roll;
 mod rpc;
 mod say;
 mod sound;
+mod starboard;
+mod status;
 mod test;
+mod translate;
+mod voice;
+mod welcome;
 
 mod prelude {
     #![expect(unused_imports, reason = "Some exports may not yet be used")]
@@ -25,8 +50,8 @@ mod prelude {
         },
         rpc, visitor,
     };
+    pub(super) use paracord::client::Context;
     pub(super) use serenity::{
-        client::Context,
         model::{channel::Attachment, id::GuildId, user::User},
     };
 
@@ -43,68 +68,403 @@ mod prelude {
     pub type CommandError<'a> = handler::CommandError<'a, Schema>;
     pub type CommandResult<'a> = handler::CommandResult<'a, Schema>;
     pub type CommandResponder<'a, 'b> = handler::CommandResponder<'a, 'b, Schema>;
+    pub type CreatedCommandResponder<'a> =
+        response::CreatedResponder<'a, Schema, serenity::model::application::CommandInteraction>;
+    pub type CommandProgress<'r, 'a> = response::ProgressReporter<
+        'r,
+        'a,
+        Schema,
+        serenity::model::application::CommandInteraction,
+    >;
     // pub type ComponentError<'a> = handler::ComponentError<'a, Schema>;
     pub type ComponentResult<'a> = handler::ComponentResult<'a, Schema>;
     pub type ComponentResponder<'a, 'b> = handler::ComponentResponder<'a, 'b, Schema>;
     // pub type ModalError<'a> = handler::ModalError<'a, Schema>;
-    // pub type ModalResult<'a> = handler::ModalResult<'a, Schema>;
-    // pub type ModalResponder<'a, 'b> = handler::ModalResponder<'a, 'b, Schema>;
+    pub type ModalResult<'a> = handler::ModalResult<'a, Schema>;
+    pub type ModalResponder<'a, 'b> = handler::ModalResponder<'a, 'b, Schema>;
 
     #[inline]
     pub fn id<T>(t: T) -> T { t }
 
+    pub const MAX_REDIRECTS: usize = 5;
+
     pub fn http_client(timeout: Option<std::time::Duration>) -> reqwest::Client {
+        http_client_builder(timeout).build().unwrap()
+    }
+
+    /// Configure an HTTP client like [`http_client`], for callers that need to
+    /// customize it further
+    pub fn http_client_builder(timeout: Option<std::time::Duration>) -> reqwest::ClientBuilder {
         let timeout = timeout.unwrap_or(std::time::Duration::from_secs(10));
-        let client = reqwest::Client::builder()
+        reqwest::Client::builder()
             .user_agent("the-q")
+            .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
             .gzip(true)
             .brotli(true)
             .deflate(true)
             .timeout(timeout)
-            .connect_timeout(timeout);
-        client.build().unwrap()
+            .connect_timeout(timeout)
     }
 }
 
+pub use alias::AliasLookup;
+pub use antispam::anti_spam;
+pub use autoreply::auto_reply;
+pub use channel::auto_thread;
+pub use event::{
+    delete as delete_scheduled_event, resume as resume_events, update as update_scheduled_event,
+};
+pub use feed::FeedPoller;
+pub use level::{
+    award_message as award_message_xp, award_voice as award_voice_xp, flush as flush_xp,
+    spawn_flush as spawn_xp_flush,
+};
+pub use poll::resume as resume_polls;
+pub use remind::resume as resume_reminders;
 pub use rpc::*;
+pub use starboard::{is_star as is_starboard_reaction, update as update_starboard};
+pub use welcome::greet as greet_member;
 
 pub type Handlers = prelude::handler::Handlers<Schema>;
+use paracord::interaction::config::ConfigRegistry;
+pub use paracord::interaction::failure::MemoryFailureLog;
+
+/// Drop every cached copy of a guild's stored settings, or of every guild's
+/// if `gid` is `None`, so they are re-read from storage on next use
+///
+/// This must be called whenever guild data is replaced outside the commands
+/// that own it, e.g. by a config import or a backup restore.
+pub async fn invalidate_guild_caches(gid: Option<prelude::GuildId>) {
+    antispam::invalidate(gid).await;
+    autoreply::invalidate(gid).await;
+    channel::invalidate_auto_threads(gid).await;
+    level::invalidate(gid).await;
+}
 
 // TODO: set up command names
-#[derive(Debug, clap::Args)]
+#[derive(Debug, Clone, PartialEq, clap::Args)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each flag maps directly to a command-line switch"
+)]
 pub struct CommandOpts {
     #[arg(long, env, default_value = "q")]
     command_base: String,
 
     #[arg(long, env, default_value = "")]
     context_menu_base: String,
+
+    /// Interval between checks of subscribed RSS/Atom feeds, in seconds
+    #[arg(long, env, default_value_t = 900, value_parser = clap::value_parser!(u64).range(60..))]
+    feed_interval: u64,
+
+    /// Base URL of a `LibreTranslate` server, enabling the translate commands
+    #[arg(long, env)]
+    translate_url: Option<url::Url>,
+
+    /// API key for the translation server, if it requires one
+    #[arg(long, env)]
+    translate_api_key: Option<String>,
+
+    /// Maximum number of interactions handled at once, or 0 for no limit
+    #[arg(long, env, default_value_t = 32)]
+    max_concurrent_interactions: usize,
+
+    /// Maximum number of interactions waiting for a free handler before
+    /// low-priority ones are turned away
+    #[arg(long, env, default_value_t = 128)]
+    max_queued_interactions: usize,
+
+    /// Link to a support server or issue tracker, shown to users alongside
+    /// errors caused by bugs
+    #[arg(long, env)]
+    support_url: Option<url::Url>,
+
+    /// Relative weights of the name, structure and options of changed
+    /// commands when pairing them with registered ones, e.g. "name=2"
+    #[arg(long, env, default_value = "name=1,structure=1,options=1")]
+    command_sim_weights: paracord::interaction::command::SimWeights,
+
+    /// Similarity score, between 0 and 1, below which changed commands are
+    /// deleted and recreated rather than updated in place
+    #[arg(long, env, default_value_t = 0.0)]
+    min_command_similarity: f64,
+
+    /// Log the changes that would be made to the registered commands on
+    /// startup without making them
+    #[arg(long, env)]
+    command_dry_run: bool,
+
+    /// Ask the bot owner over DM to approve deleting registered commands on
+    /// startup, keeping them until approved
+    #[arg(long, env)]
+    confirm_command_deletes: bool,
+
+    /// Approve deleting registered commands on startup without asking the
+    /// owner
+    #[arg(long)]
+    yes: bool,
+
+    /// Delete guild-scoped commands left behind by removed handlers on
+    /// startup and when joining a guild
+    ///
+    /// Every guild command not named with --keep-guild-command is deleted.
+    #[arg(long, env)]
+    reconcile_guild_commands: bool,
+
+    /// Name of a guild-scoped command to leave registered when deleting
+    /// orphaned guild commands
+    #[arg(long = "keep-guild-command", env = "KEEP_GUILD_COMMANDS", value_delimiter = ',')]
+    keep_guild_commands: Vec<String>,
+
+    /// Request the privileged message content intent, enabling auto-reply
+    /// rules
+    #[arg(long, env)]
+    message_content: bool,
+
+    /// Request the privileged server members intent, enabling welcome
+    /// messages
+    #[arg(long, env)]
+    guild_members: bool,
+
+    /// Activity to show in the bot's presence, of the form KIND:TEXT, e.g.
+    /// "listening:{guilds} servers"
+    ///
+    /// Multiple activities, separated by semicolons in the environment, are
+    /// shown in turn.
+    #[arg(long = "presence", env = "PRESENCE", value_delimiter = ';')]
+    presence: Vec<paracord::presence::ActivityTemplate>,
+
+    /// Time to show each presence activity for before moving to the next, in
+    /// seconds
+    #[arg(long, env, default_value_t = 300, value_parser = clap::value_parser!(u64).range(10..))]
+    presence_interval: u64,
+
+    /// Path to a TOML file of options for individual commands, with a table
+    /// per command
+    ///
+    /// Options can also be set with variables of the form
+    /// `HANDLER_<COMMAND>__<OPTION>`, which take precedence over the file.
+    #[arg(long, env)]
+    handler_config: Option<std::path::PathBuf>,
+
+    /// Set an option for an individual command, overriding the config file
+    /// and environment
+    #[arg(long = "handler-opt", value_name = "COMMAND.OPTION=VALUE")]
+    handler_opts: Vec<String>,
+}
+
+impl CommandOpts {
+    #[inline]
+    pub fn support_url(&self) -> Option<&url::Url> { self.support_url.as_ref() }
+
+    #[inline]
+    pub fn sim_weights(&self) -> paracord::interaction::command::SimWeights {
+        self.command_sim_weights
+    }
+
+    #[inline]
+    pub fn min_similarity(&self) -> f64 { self.min_command_similarity }
+
+    #[inline]
+    pub fn dry_run(&self) -> bool { self.command_dry_run }
+
+    #[inline]
+    pub fn reconcile_guild_commands(&self) -> bool { self.reconcile_guild_commands }
+
+    #[inline]
+    pub fn keep_guild_commands(&self) -> &[String] { &self.keep_guild_commands }
+
+    /// Get the activities to rotate through in the bot's presence
+    pub fn presence(&self) -> paracord::presence::Rotation {
+        paracord::presence::Rotation::new(self.presence.clone())
+            .with_interval(std::time::Duration::from_secs(self.presence_interval))
+    }
+
+    /// Returns true if command deletions on startup should wait for the
+    /// owner's approval
+    #[inline]
+    pub fn confirm_deletes(&self) -> bool { self.confirm_command_deletes && !self.yes }
+
+    #[inline]
+    pub fn message_content(&self) -> bool { self.message_content }
+
+    #[inline]
+    pub fn guild_members(&self) -> bool { self.guild_members }
+
+    /// Get the gateway intents to request, depending on which commands are
+    /// enabled
+    pub fn intents(&self) -> serenity::model::gateway::GatewayIntents {
+        use serenity::model::gateway::GatewayIntents;
+
+        let mut intents = GatewayIntents::non_privileged();
+        if self.message_content() {
+            intents |= GatewayIntents::MESSAGE_CONTENT;
+        }
+        if self.guild_members() {
+            intents |= GatewayIntents::GUILD_MEMBERS;
+        }
+        intents
+    }
+
+    /// Collect the options for individual commands from the config file,
+    /// environment and command line
+    pub fn handler_config(&self) -> prelude::Result<ConfigRegistry> {
+        let config = match self.handler_config {
+            Some(ref path) => ConfigRegistry::load(path)?,
+            None => ConfigRegistry::new(),
+        };
+
+        config
+            .with_env("HANDLER_")
+            .with_overrides(&self.handler_opts)
+            .map_err(Into::into)
+    }
+
+    /// Get the interaction dispatch limits, or `None` if dispatch is
+    /// unlimited
+    pub fn dispatch(&self) -> Option<paracord::interaction::dispatch::DispatchOpts> {
+        (self.max_concurrent_interactions != 0).then(|| {
+            paracord::interaction::dispatch::DispatchOpts {
+                concurrency: self.max_concurrent_interactions,
+                max_queued: self.max_queued_interactions,
+                ..Default::default()
+            }
+        })
+    }
+}
+
+/// Add the handlers that depend on privileged intents or other optional
+/// configuration
+fn optional_handlers(opts: &CommandOpts, handlers: &mut Handlers) {
+    use prelude::*;
+
+    if opts.message_content() {
+        handlers
+            .commands
+            .push(Arc::new(autoreply::AutoReplyCommand::from(opts)));
+    }
+
+    if opts.guild_members() {
+        handlers
+            .commands
+            .push(Arc::new(welcome::WelcomeCommand::from(opts)));
+    } else {
+        warn!("Server members intent not requested, welcome messages are disabled");
+    }
+
+    if let Some(backend) = translate::backend(opts) {
+        handlers
+            .commands
+            .push(Arc::new(translate::TranslateCommand::from(opts)));
+        handlers
+            .commands
+            .push(Arc::new(translate::TranslateMessageCommand::new(
+                opts, backend,
+            )));
+    }
 }
 
 // TODO: can this be attribute-macro-ified?
-pub fn handlers(opts: &CommandOpts) -> Handlers {
+pub fn handlers(
+    opts: &CommandOpts,
+    failures: &std::sync::Arc<MemoryFailureLog>,
+) -> prelude::Result<Handlers> {
     use prelude::*;
 
+    let mut handler_config = opts.handler_config()?;
+
+    let alias = Arc::new(alias::AliasCommand::from(opts));
+    let antispam = Arc::new(antispam::AntiSpamCommand::from(opts));
+    let backup = Arc::new(backup::BackupCommand::from(opts));
+    let bookmark = Arc::new(bookmark::BookmarkCommand::from(opts));
+    let bookmark_message = Arc::new(bookmark::BookmarkMessageCommand::from(opts));
+    let botlog = Arc::new(botlog::BotLogCommand::from(opts));
+    let channel = Arc::new(channel::ChannelCommand::from(opts));
+    let config = Arc::new(config::ConfigCommand::from(opts));
+    let errors = Arc::new(errors::ErrorsCommand::new(opts, Arc::clone(failures)));
+    let event = Arc::new(event::EventCommand::from(opts));
     let explode = Arc::new(explode::ExplodeCommand::from(opts));
+    let explode_message = Arc::new(explode::ExplodeMessageCommand::from(opts));
+    let feed = Arc::new(feed::FeedCommand::from(opts));
     let jpeg = Arc::new(jpeg::JpegCommand::from(opts));
     let jpeg_message = Arc::new(jpeg::JpegMessageCommand::from(opts));
+    let leaderboard = Arc::new(level::LeaderboardCommand::from(opts));
+    let levels = Arc::new(level::LevelsCommand::from(opts));
+    let lobby = Arc::new(lobby::LobbyCommand::from(opts));
     let point = Arc::new(point::PointCommand::from(opts));
+    let poll = Arc::new(poll::PollCommand::from(opts));
+    let prefs = Arc::new(prefs::PrefsCommand::from(opts));
+    let quote = Arc::new(quote::QuoteCommand::from(opts));
+    let rank = Arc::new(level::RankCommand::from(opts));
     let re = Arc::new(re::ReCommand::from(opts));
-    let say = Arc::new(say::SayCommand::from(opts));
-    let sound = Arc::new(sound::SoundCommand::from(opts));
+    let reload = Arc::new(reload::ReloadCommand::from(opts));
+    let remind = Arc::new(remind::RemindCommand::from(opts));
+    let roll = Arc::new(roll::RollCommand::from(opts));
+    let say = Arc::new(handler_config.build::<say::SayCommand>(opts)?);
+    let sound = Arc::new(handler_config.build::<sound::SoundCommand>(opts)?);
+    let starboard = Arc::new(starboard::StarboardCommand::from(opts));
+    let status = Arc::new(status::StatusCommand::from(opts));
     let test = Arc::new(test::TestCommand::from(opts));
+    let voice = Arc::new(voice::VoiceCommand::from(opts));
 
-    Handlers {
+    let mut handlers = Handlers {
         commands: vec![
+            alias,
+            antispam,
+            backup,
+            bookmark,
+            botlog,
+            channel,
+            errors,
+            event,
             explode,
+            explode_message,
+            feed,
             jpeg,
             jpeg_message,
+            levels,
             point,
+            prefs,
+            rank,
             re,
+            reload,
+            remind,
+            roll,
             say,
-            test,
+            starboard,
+            status,
+            voice,
+            Arc::clone(&bookmark_message) as Arc<dyn CommandHandler<Schema>>,
+            Arc::clone(&config) as Arc<dyn CommandHandler<Schema>>,
+            Arc::clone(&leaderboard) as Arc<dyn CommandHandler<Schema>>,
+            Arc::clone(&lobby) as Arc<dyn CommandHandler<Schema>>,
+            Arc::clone(&poll) as Arc<dyn CommandHandler<Schema>>,
+            Arc::clone(&quote) as Arc<dyn CommandHandler<Schema>>,
             Arc::clone(&sound) as Arc<dyn CommandHandler<Schema>>,
+            Arc::clone(&test) as Arc<dyn CommandHandler<Schema>>,
+        ],
+        components: vec![
+            config,
+            leaderboard,
+            lobby,
+            poll,
+            quote,
+            sound,
+            Arc::clone(&test) as Arc<dyn RpcHandler<Schema, ComponentKey>>,
+        ],
+        modals: vec![
+            bookmark_message,
+            Arc::clone(&test) as Arc<dyn RpcHandler<Schema, ModalKey>>,
         ],
-        components: vec![sound],
-        modals: vec![],
+    };
+
+    optional_handlers(opts, &mut handlers);
+
+    let unused = handler_config.unused();
+    if !unused.is_empty() {
+        warn!(?unused, "Handler config has sections for unknown commands");
     }
+
+    Ok(handlers)
 }
@@ -0,0 +1,378 @@
+use std::{
+    io::Cursor,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use jpeggr::image::{ImageFormat, Rgb, RgbImage};
+use serenity::{
+    builder::{CreateAttachment, EditMessage},
+    model::id::{ChannelId, MessageId, UserId},
+    utils::MessageBuilder,
+};
+
+use super::{prelude::*, remind::describe_time_error};
+use crate::{
+    client::{prefs, storage},
+    proto::guild,
+    util::time::{self, TimeError},
+};
+
+const OPTIONS: [&str; 10] = [
+    "option1", "option2", "option3", "option4", "option5", "option6", "option7", "option8",
+    "option9", "option10",
+];
+const BUTTONS_PER_ROW: usize = 5;
+const DEFAULT_DURATION_MINS: i64 = 60;
+/// Longest a poll can be kept open, in days
+const MAX_DAYS: i64 = 7;
+
+const CHART_WIDTH: u32 = 480;
+const CHART_BAR_HEIGHT: u32 = 24;
+const CHART_PADDING: u32 = 8;
+const CHART_BACKGROUND: Rgb<u8> = Rgb([0x2b, 0x2d, 0x31]);
+const CHART_PALETTE: [Rgb<u8>; OPTIONS.len()] = [
+    Rgb([0xe6, 0x4b, 0x3c]),
+    Rgb([0xf3, 0x9c, 0x12]),
+    Rgb([0xf1, 0xc4, 0x0f]),
+    Rgb([0x2e, 0xcc, 0x71]),
+    Rgb([0x1a, 0xbc, 0x9c]),
+    Rgb([0x34, 0x98, 0xdb]),
+    Rgb([0x9b, 0x59, 0xb6]),
+    Rgb([0xe9, 0x1e, 0x63]),
+    Rgb([0x95, 0xa5, 0xa6]),
+    Rgb([0xec, 0xf0, 0xf1]),
+];
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs().try_into().unwrap_or(i64::MAX))
+}
+
+fn tally(poll: &guild::Poll) -> Vec<u64> {
+    let mut counts = vec![0; poll.options.len()];
+
+    for &opt in poll.votes.values() {
+        if let Some(count) = usize::try_from(opt).ok().and_then(|o| counts.get_mut(o)) {
+            *count += 1;
+        }
+    }
+
+    counts
+}
+
+fn render_chart(counts: &[u64]) -> Result<Vec<u8>> {
+    let rows = u32::try_from(counts.len()).context("Too many poll options")?;
+    let height = CHART_PADDING + rows * (CHART_BAR_HEIGHT + CHART_PADDING);
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    let span = u64::from(CHART_WIDTH - 2 * CHART_PADDING);
+
+    let mut img = RgbImage::from_pixel(CHART_WIDTH, height, CHART_BACKGROUND);
+
+    for ((row, &count), color) in (0..rows).zip(counts).zip(CHART_PALETTE.iter().cycle()) {
+        let width = u32::try_from(count * span / max).unwrap_or_else(|_| unreachable!());
+        let top = CHART_PADDING + row * (CHART_BAR_HEIGHT + CHART_PADDING);
+
+        for y in top..top + CHART_BAR_HEIGHT {
+            for x in CHART_PADDING..CHART_PADDING + width {
+                img.put_pixel(x, y, *color);
+            }
+        }
+    }
+
+    let mut bytes = Cursor::new(vec![]);
+    img.write_to(&mut bytes, ImageFormat::Png)
+        .context("Error encoding poll chart")?;
+
+    Ok(bytes.into_inner())
+}
+
+fn results(poll: &guild::Poll, counts: &[u64]) -> String {
+    let total: u64 = counts.iter().sum();
+    let mut mb = MessageBuilder::new();
+
+    mb.push_bold("Poll closed: ")
+        .push_safe(poll.question.as_str())
+        .push("\n");
+
+    for (i, (opt, &count)) in poll.options.iter().zip(counts).enumerate() {
+        let pct = (count * 100).checked_div(total).unwrap_or(0);
+        mb.push(format!("{}. ", i + 1))
+            .push_safe(opt.as_str())
+            .push(format!(
+                " \u{2014} {count} vote{} ({pct}%)\n",
+                if count == 1 { "" } else { "s" }
+            ));
+    }
+
+    mb.push_italic(format!(
+        "{total} vote{} total",
+        if total == 1 { "" } else { "s" }
+    ));
+
+    mb.build()
+}
+
+async fn close(ctx: &Context, gid: GuildId, id: u64) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let Some(poll) = storage
+        .update_guild(gid, |g| g.polls.remove(&id))
+        .await
+        .context("Error removing closed poll")?
+    else {
+        return Ok(());
+    };
+
+    let counts = tally(&poll);
+    let mut edit = EditMessage::new()
+        .content(results(&poll, &counts))
+        .components(vec![]);
+
+    if poll.chart {
+        let chart = render_chart(&counts)?;
+        edit = edit.new_attachment(CreateAttachment::bytes(chart, "results.png"));
+    }
+
+    ChannelId::new(poll.channel)
+        .edit_message(&ctx.http, MessageId::new(poll.message), edit)
+        .await
+        .context("Error posting poll results")?;
+
+    Ok(())
+}
+
+fn schedule_close(ctx: Context, gid: GuildId, id: u64, closes_at: i64) {
+    let delay = Duration::from_secs(closes_at.saturating_sub(now_secs()).try_into().unwrap_or(0));
+
+    tokio::task::spawn(
+        async move {
+            tokio::time::sleep(delay).await;
+
+            close(&ctx, gid, id)
+                .await
+                .map_err(|err| error!(?err, "Error closing poll"))
+                .ok();
+        }
+        .instrument(error_span!(parent: None, "close_poll", %gid, id)),
+    );
+}
+
+/// Parse the closing time given by the poll's creator, returning a Unix
+/// timestamp
+async fn closes_at(
+    ctx: &Context,
+    user: UserId,
+    closes: Option<&str>,
+) -> Result<Result<i64, TimeError>> {
+    let Some(closes) = closes else {
+        return Ok(Ok(now_secs().saturating_add(DEFAULT_DURATION_MINS * 60)));
+    };
+
+    let prefs = prefs::get(ctx).await.context("Missing prefs context")?;
+    let tz = prefs.get(user).await?.utc_offset();
+    let now = Utc::now();
+
+    Ok(time::parse_time(closes, now, tz).and_then(|t| {
+        if t - now <= TimeDelta::days(MAX_DAYS) {
+            Ok(t.timestamp())
+        } else {
+            Err(TimeError::OutOfRange(closes.trim().into()))
+        }
+    }))
+}
+
+/// Schedule the closing of any polls left open in the given guilds
+pub async fn resume(ctx: &Context, guilds: impl IntoIterator<Item = GuildId>) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+
+    for gid in guilds {
+        let guild::Guild { polls, .. } = storage.guild(gid).await?;
+
+        for (id, poll) in polls {
+            debug!(%gid, id, "Resuming open poll");
+            schedule_close(ctx.clone(), gid, id, poll.closes_at);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct PollCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for PollCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}poll", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for PollCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Start a poll", |a| {
+            let mut a = a.string("question", "The question to ask", true, 1..=200);
+
+            for (i, name) in OPTIONS.into_iter().enumerate() {
+                a = a.string(name, format!("Choice #{}", i + 1), i < 2, 1..=80);
+            }
+
+            a.string(
+                "closes",
+                "When to close the poll, e.g. in 2h or tomorrow 9am (default: in 1 hour)",
+                false,
+                1..=100,
+            )
+            .bool("chart", "Attach a bar chart to the results", false)
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let question = visitor.visit_string("question")?.required()?.to_owned();
+        let options = OPTIONS
+            .into_iter()
+            .map(|name| {
+                visitor
+                    .visit_string(name)
+                    .map(|o| o.optional().map(ToOwned::to_owned))
+            })
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>, _>>()?;
+        let closes = visitor.visit_string("closes")?.optional();
+        let chart = visitor.visit_bool("chart")?.optional().unwrap_or(false);
+
+        let closes_at = match closes_at(ctx, visitor.user().id, closes).await? {
+            Ok(t) => t,
+            Err(err) => {
+                return Err(responder
+                    .create_message(
+                        Message::rich(|mb| describe_time_error(mb, &err)).ephemeral(true),
+                    )
+                    .await
+                    .context("Error sending time error")?
+                    .into_err("Invalid poll closing time"));
+            },
+        };
+
+        let responder = responder
+            .defer_message(MessageOpts::default())
+            .await
+            .context("Error sending deferred message")?;
+
+        let id = responder.interaction_ctx().id().get();
+
+        let mut body = MessageBody::rich(|mb| {
+            mb.push_bold_safe(question.as_str())
+                .push(format!("\nCloses <t:{closes_at}:R>"))
+        });
+
+        for (row, chunk) in options.chunks(BUTTONS_PER_ROW).enumerate() {
+            body = body.buttons(|mut b| {
+                for (i, opt) in chunk.iter().enumerate() {
+                    let option =
+                        u32::try_from(row * BUTTONS_PER_ROW + i).unwrap_or_else(|_| unreachable!());
+                    b = b.button(
+                        ComponentPayload::PollVote(component::PollVote { poll: id, option }),
+                        ButtonStyle::Secondary,
+                        opt.as_str(),
+                        false,
+                    );
+                }
+
+                b
+            });
+        }
+
+        if let Some(at) = DateTime::from_timestamp(closes_at, 0) {
+            body = body.expire_at(at);
+        }
+
+        let msg = responder
+            .edit(body)
+            .await
+            .context("Error sending poll message")?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        storage
+            .update_guild(gid, |g| {
+                g.polls.insert(id, guild::Poll {
+                    channel: msg.channel_id.get(),
+                    message: msg.id.get(),
+                    question,
+                    options,
+                    closes_at,
+                    votes: HashMap::new(),
+                    chart,
+                });
+            })
+            .await
+            .context("Error saving poll")?;
+
+        schedule_close(ctx.clone(), gid, id, closes_at);
+
+        Ok(responder.into())
+    }
+}
+
+#[async_trait]
+impl RpcHandler<Schema, ComponentKey> for PollCommand {
+    fn register_keys(&self) -> &'static [ComponentKey] { &[ComponentKey::PollVote] }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        payload: ComponentPayload,
+        visitor: &mut ComponentVisitor<'_>,
+        responder: ComponentResponder<'_, 'a>,
+    ) -> ComponentResult<'a> {
+        let ComponentPayload::PollVote(component::PollVote { poll: id, option }) = payload else {
+            unreachable!(); // TODO: set up an error for this
+        };
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let user = visitor.user();
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let choice = storage
+            .update_guild(gid, |g| {
+                let poll = g.polls.get_mut(&id)?;
+
+                if poll.closes_at <= now_secs() {
+                    return None;
+                }
+
+                let choice = poll.options.get(usize::try_from(option).ok()?)?.clone();
+                poll.votes.insert(user.id.get(), option);
+                Some(choice)
+            })
+            .await
+            .context("Error recording vote")?;
+
+        let Some(choice) = choice else {
+            return Err(responder
+                .create_message(Message::plain("This poll has closed.").ephemeral(true))
+                .await
+                .context("Error sending closed poll error")?
+                .into_err("Vote for closed poll"));
+        };
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| mb.push("Voted for ").push_bold_safe(choice)).ephemeral(true),
+            )
+            .await
+            .context("Error sending vote confirmation")?
+            .into())
+    }
+}
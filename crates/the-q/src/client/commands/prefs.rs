@@ -0,0 +1,142 @@
+use paracord::interaction::locale::DiscordLocale;
+
+use super::prelude::*;
+use crate::client::prefs::{self, UserPrefs};
+
+fn describe(prefs: &UserPrefs) -> String {
+    let UserPrefs {
+        timezone,
+        locale,
+        dm_opt_out,
+        voice_stats_opt_out,
+    } = prefs;
+
+    format!(
+        "**Timezone:** {}\n**Language:** {}\n**Direct messages:** {}\n**Voice stats:** {}",
+        timezone.map_or_else(|| "not set (UTC)".into(), |t| format!("UTC{t}")),
+        locale
+            .as_deref()
+            .unwrap_or("not set (uses your Discord language)"),
+        if *dm_opt_out { "off" } else { "on" },
+        if *voice_stats_opt_out { "off" } else { "on" },
+    )
+}
+
+#[derive(Debug)]
+pub struct PrefsCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for PrefsCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}prefs", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for PrefsCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "View or change your preferences", |a| {
+            a.build_subcmd("show", "Show your current preferences", id)
+                .build_subcmd("timezone", "Set your timezone", |a| {
+                    a.string(
+                        "offset",
+                        "Your offset from UTC, e.g. UTC-8 or +05:30",
+                        true,
+                        1..=16,
+                    )
+                })
+                .build_subcmd("language", "Set the language used for bot responses", |a| {
+                    a.string("locale", "A Discord locale code, e.g. en-US", true, 2..=6)
+                })
+                .build_subcmd(
+                    "dms",
+                    "Set whether the bot may send you direct messages",
+                    |a| a.bool("allow", "Whether to allow direct messages", true),
+                )
+                .build_subcmd(
+                    "voicestats",
+                    "Set whether your time in voice channels counts toward voice stats",
+                    |a| a.bool("allow", "Whether to track your time in voice", true),
+                )
+                .build_subcmd("reset", "Reset all of your preferences", id)
+        })
+        .unwrap()
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let user = visitor.user().id;
+        let prefs = prefs::get(ctx).await.context("Missing prefs context")?;
+
+        let updated = match *visitor.visit_subcmd()? {
+            ["show"] => prefs.get(user).await?,
+            ["timezone"] => {
+                let offset = visitor.visit_string("offset")?.required()?;
+                let Some(offset) = prefs::parse_offset(offset) else {
+                    return Err(responder
+                        .create_message(
+                            Message::plain(
+                                "That doesn't look like a UTC offset.  Try something like UTC-8 \
+                                 or +05:30.",
+                            )
+                            .ephemeral(true),
+                        )
+                        .await
+                        .context("Error sending offset error")?
+                        .into_err("Invalid UTC offset"));
+                };
+
+                prefs.update(user, |p| p.timezone = Some(offset)).await?
+            },
+            ["language"] => {
+                let locale = visitor.visit_string("locale")?.required()?;
+                let Ok(locale) = locale.parse::<DiscordLocale>() else {
+                    return Err(responder
+                        .create_message(
+                            Message::plain(format!(
+                                "Unknown locale.  Supported locales are: {}",
+                                DiscordLocale::ALL
+                                    .iter()
+                                    .map(|l| l.code())
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            ))
+                            .ephemeral(true),
+                        )
+                        .await
+                        .context("Error sending locale error")?
+                        .into_err("Invalid locale"));
+                };
+
+                prefs
+                    .update(user, |p| p.locale = Some(locale.code().into()))
+                    .await?
+            },
+            ["dms"] => {
+                let allow = visitor.visit_bool("allow")?.required()?;
+                prefs.update(user, |p| p.dm_opt_out = !allow).await?
+            },
+            ["voicestats"] => {
+                let allow = visitor.visit_bool("allow")?.required()?;
+                prefs
+                    .update(user, |p| p.voice_stats_opt_out = !allow)
+                    .await?
+            },
+            ["reset"] => prefs.update(user, |p| *p = UserPrefs::default()).await?,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        };
+
+        Ok(responder
+            .create_message(Message::plain(describe(&updated)).ephemeral(true))
+            .await
+            .context("Error sending preferences")?
+            .into())
+    }
+}
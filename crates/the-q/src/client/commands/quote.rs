@@ -0,0 +1,437 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use rand::seq::IteratorRandom;
+use serenity::{
+    model::{id::UserId, Permissions},
+    utils::MessageBuilder,
+};
+
+use super::prelude::*;
+use crate::{client::storage, proto::guild};
+
+/// Most quotes a single server can store
+const MAX_QUOTES: usize = 1000;
+/// Longest quote that can be added, in characters
+const MAX_QUOTE_LEN: u16 = 1000;
+/// Longest a quote is shown in search results, in characters
+const MAX_RESULT_LEN: usize = 300;
+/// Number of search results per page
+const PAGE_SIZE: usize = 5;
+/// Fraction of the query's trigrams a quote must contain to match
+const MIN_SCORE: f64 = 0.6;
+
+fn truncate(s: &str, len: usize) -> String {
+    match s.char_indices().nth(len) {
+        Some((i, _)) => format!("{}\u{2026}", &s[..i]),
+        None => s.into(),
+    }
+}
+
+/// Collect the case-folded trigrams of each word in a string, padded so that
+/// word boundaries and one- and two-letter words are represented
+fn trigrams(s: &str) -> HashSet<[char; 3]> {
+    let mut grams = HashSet::new();
+
+    for word in s
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        let chars: Vec<_> = std::iter::once(' ')
+            .chain(word.chars().flat_map(char::to_lowercase))
+            .chain([' '])
+            .collect();
+
+        grams.extend(chars.windows(3).map(|w| [w[0], w[1], w[2]]));
+    }
+
+    grams
+}
+
+/// Find the quotes matching a query, best match first
+fn search<'a>(quotes: &'a HashMap<u32, guild::Quote>, query: &str) -> Vec<(u32, &'a guild::Quote)> {
+    let query = trigrams(query);
+    if query.is_empty() {
+        return vec![];
+    }
+
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "Trigram counts are far below the precision limit"
+    )]
+    let mut hits: Vec<_> = quotes
+        .iter()
+        .filter_map(|(&num, quote)| {
+            let text = trigrams(&quote.text);
+            let score = query.intersection(&text).count() as f64 / query.len() as f64;
+            (score >= MIN_SCORE).then_some((score, num, quote))
+        })
+        .collect();
+
+    hits.sort_by(|(a, a_num, _), (b, b_num, _)| b.total_cmp(a).then(a_num.cmp(b_num)));
+    hits.into_iter()
+        .map(|(_, num, quote)| (num, quote))
+        .collect()
+}
+
+fn push_quote<'a>(
+    mb: &'a mut MessageBuilder,
+    num: u32,
+    quote: &guild::Quote,
+    max_len: Option<usize>,
+) -> &'a mut MessageBuilder {
+    let text = max_len.map_or_else(|| quote.text.clone(), |l| truncate(&quote.text, l));
+    for line in text.lines() {
+        mb.push_quote_line_safe(line);
+    }
+
+    mb.push("\u{2014} ");
+    if quote.author == 0 {
+        mb.push("Unknown");
+    } else {
+        mb.mention(&UserId::new(quote.author));
+    }
+    mb.push(format!(" (#{num}, <t:{}:d>)", quote.added_at))
+}
+
+/// Render a page of search results
+fn results_page(quotes: &HashMap<u32, guild::Quote>, query: &str, page: u32) -> MessageBody {
+    let hits = search(quotes, query);
+    if hits.is_empty() {
+        return MessageBody::rich(|mb| {
+            mb.push("No quotes found matching ")
+                .push_mono_safe(query)
+                .push(".")
+        });
+    }
+
+    let pages = hits.len().div_ceil(PAGE_SIZE);
+    let page = usize::try_from(page).map_or(pages - 1, |p| p.min(pages - 1));
+
+    let body = MessageBody::rich(|mb| {
+        mb.push(format!("{} quotes matching ", hits.len()))
+            .push_mono_safe(query)
+            .push_line(format!(" (page {}/{pages}):", page + 1));
+
+        for &(num, quote) in hits.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE) {
+            mb.push_line("");
+            push_quote(mb, num, quote, Some(MAX_RESULT_LEN));
+        }
+
+        mb
+    });
+
+    let page = u32::try_from(page).unwrap_or_else(|_| unreachable!());
+    let button = |page| {
+        ComponentPayload::QuotePage(component::QuotePage {
+            query: query.into(),
+            page,
+        })
+    };
+    let last = u32::try_from(pages - 1).unwrap_or(u32::MAX);
+    body.buttons(|b| {
+        b.button(
+            button(page.saturating_sub(1)),
+            ButtonStyle::Secondary,
+            "Previous",
+            page == 0,
+        )
+        .button(
+            button(page.saturating_add(1)),
+            ButtonStyle::Secondary,
+            "Next",
+            page >= last,
+        )
+    })
+}
+
+#[derive(Debug)]
+pub struct QuoteCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for QuoteCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}quote", opts.command_base),
+        }
+    }
+}
+
+impl QuoteCommand {
+    async fn add<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let text = visitor.visit_string("text")?.required()?.trim().to_owned();
+        let author = visitor
+            .visit_user("author")?
+            .optional()
+            .map_or(0, |(u, _)| u.id.get());
+        let user = visitor.user().id;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let num = storage
+            .update_guild(gid, |g| {
+                if g.quotes.len() >= MAX_QUOTES {
+                    return None;
+                }
+
+                g.next_quote = g.next_quote.max(1);
+                let num = g.next_quote;
+                g.next_quote += 1;
+                g.quotes.insert(num, guild::Quote {
+                    text,
+                    author,
+                    added_by: user.get(),
+                    added_at: Utc::now().timestamp(),
+                });
+                Some(num)
+            })
+            .await
+            .context("Error saving quote")?;
+
+        let Some(num) = num else {
+            return Err(responder
+                .create_message(
+                    Message::plain(format!(
+                        "This server already has the maximum of {MAX_QUOTES} quotes."
+                    ))
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending quote limit error")?
+                .into_err("Too many quotes"));
+        };
+
+        Ok(responder
+            .create_message(Message::plain(format!("Added quote #{num}.")))
+            .await
+            .context("Error sending quote confirmation")?
+            .into())
+    }
+
+    async fn random<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let author = visitor.visit_user("author")?.optional().map(|(u, _)| u.id);
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild::Guild { quotes, .. } = storage.guild(gid).await?;
+
+        let pick = quotes
+            .iter()
+            .filter(|(_, q)| author.map_or(true, |a| q.author == a.get()))
+            .choose(&mut rand::thread_rng());
+
+        let Some((&num, quote)) = pick else {
+            return Err(responder
+                .create_message(Message::plain("No quotes found.").ephemeral(true))
+                .await
+                .context("Error sending empty quote error")?
+                .into_err("No quotes to choose from"));
+        };
+
+        Ok(responder
+            .create_message(Message::rich(|mb| push_quote(mb, num, quote, None)))
+            .await
+            .context("Error sending quote")?
+            .into())
+    }
+
+    async fn search<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let query = visitor.visit_string("query")?.required()?.trim();
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild::Guild { quotes, .. } = storage.guild(gid).await?;
+        let body = results_page(&quotes, query, 0);
+
+        Ok(responder
+            .create_message(Message::from(body).ephemeral(true))
+            .await
+            .context("Error sending search results")?
+            .into())
+    }
+
+    async fn delete<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, memb) = visitor.guild()?.required()?;
+        let moderator = memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_MESSAGES));
+        let num = u32::try_from(visitor.visit_i64("number")?.required()?)
+            .unwrap_or_else(|_| unreachable!());
+        let user = visitor.user().id.get();
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let res = storage
+            .update_guild(gid, |g| {
+                let quote = g
+                    .quotes
+                    .get(&num)
+                    .ok_or("There's no quote with that number.")?;
+
+                if !(moderator || quote.added_by == user || quote.author == user) {
+                    return Err(
+                        "Only the person quoted, the person who added the quote, or someone with \
+                         the Manage Messages permission can delete it.",
+                    );
+                }
+
+                g.quotes.remove(&num);
+                Ok(())
+            })
+            .await
+            .context("Error deleting quote")?;
+
+        if let Err(msg) = res {
+            return Err(responder
+                .create_message(Message::plain(msg).ephemeral(true))
+                .await
+                .context("Error sending quote deletion error")?
+                .into_err("Quote could not be deleted"));
+        }
+
+        Ok(responder
+            .create_message(Message::plain(format!("Deleted quote #{num}.")).ephemeral(true))
+            .await
+            .context("Error sending deletion confirmation")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for QuoteCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Save and recall memorable quotes", |a| {
+            a.build_subcmd("add", "Add a quote to this server's collection", |a| {
+                a.string("text", "What was said", true, 1..=MAX_QUOTE_LEN)
+                    .user("author", "Who said it", false)
+            })
+            .build_subcmd("random", "Show a random quote", |a| {
+                a.user("author", "Only pick quotes by this person", false)
+            })
+            .build_subcmd("search", "Search this server's quotes", |a| {
+                a.string("query", "Words to look for", true, 1..=50)
+            })
+            .build_subcmd("delete", "Remove a quote", |a| {
+                a.int(
+                    "number",
+                    "The quote's number",
+                    true,
+                    1..=i64::from(u32::MAX),
+                )
+            })
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        match *visitor.visit_subcmd()? {
+            ["add"] => self.add(ctx, visitor, responder).await,
+            ["random"] => self.random(ctx, visitor, responder).await,
+            ["search"] => self.search(ctx, visitor, responder).await,
+            ["delete"] => self.delete(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[async_trait]
+impl RpcHandler<Schema, ComponentKey> for QuoteCommand {
+    fn register_keys(&self) -> &'static [ComponentKey] { &[ComponentKey::QuotePage] }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        payload: ComponentPayload,
+        visitor: &mut ComponentVisitor<'_>,
+        responder: ComponentResponder<'_, 'a>,
+    ) -> ComponentResult<'a> {
+        let ComponentPayload::QuotePage(component::QuotePage { query, page }) = payload else {
+            unreachable!(); // TODO: set up an error for this
+        };
+        let (gid, _memb) = visitor.guild()?.required()?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild::Guild { quotes, .. } = storage.guild(gid).await?;
+        let body = results_page(&quotes, &query, page);
+
+        Ok(responder
+            .update_message(body.into())
+            .await
+            .context("Error updating search results")?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{search, trigrams};
+    use crate::proto::guild;
+
+    #[test]
+    fn trigram_sets() {
+        let grams = trigrams("Hi, Bob");
+        assert_eq!(grams.len(), 5);
+        assert!(grams.contains(&[' ', 'h', 'i']));
+        assert!(grams.contains(&['o', 'b', ' ']));
+        assert!(trigrams(" ,.! ").is_empty());
+    }
+
+    #[test]
+    fn ranking() {
+        let quotes: HashMap<_, _> = [
+            (1, "I am the walrus"),
+            (2, "The walrus was Paul"),
+            (3, "Nothing to see here"),
+            (4, "WALRUS"),
+        ]
+        .into_iter()
+        .map(|(n, t)| {
+            (n, guild::Quote {
+                text: t.into(),
+                ..guild::Quote::default()
+            })
+        })
+        .collect();
+
+        let nums = |q| {
+            search(&quotes, q)
+                .into_iter()
+                .map(|(n, _)| n)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(nums("walrus"), [1, 2, 4]);
+        assert_eq!(nums("the walrus"), [1, 2, 4]);
+        assert_eq!(nums("walrs"), [1, 2, 4]);
+        assert_eq!(nums("paul walrus"), [2, 1, 4]);
+        assert!(nums("giraffe").is_empty());
+        assert!(nums("!!").is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+use serenity::utils::MessageBuilder;
+
+use super::{
+    super::reload::{self, ReloadReport},
+    errors::is_owner,
+    prelude::*,
+};
+
+fn summary<'a>(mb: &'a mut MessageBuilder, report: &ReloadReport) -> &'a mut MessageBuilder {
+    mb.push_bold("Configuration reloaded.");
+
+    for (label, settings) in [
+        ("Applied: ", report.applied.join(", ")),
+        ("Requires restart: ", report.restart.join(", ")),
+    ] {
+        if !settings.is_empty() {
+            mb.push("\n").push_bold(label).push(settings);
+        }
+    }
+
+    mb
+}
+
+#[derive(Debug)]
+pub struct ReloadCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for ReloadCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}reload", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for ReloadCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(
+            &self.name,
+            "Reload the bot's configuration (owner only)",
+            |a| a,
+        )
+        .unwrap()
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        if !is_owner(ctx, visitor.user()).await? {
+            return Err(responder
+                .create_message(
+                    Message::plain("This command can only be used by the bot owner.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Non-owner requested reload"));
+        }
+
+        let reloader = reload::get(ctx).await.context("Missing reload context")?;
+
+        let msg = match reloader.reload().await {
+            Ok(report) => {
+                report.log();
+                Message::rich(|mb| summary(mb, &report))
+            },
+            Err(err) => {
+                warn!(?err, "Error reloading configuration");
+                Message::rich(|mb| {
+                    mb.push_bold("Reload failed: ")
+                        .push_mono_safe(format!("{err:#}"))
+                        .push("\nThe running configuration was left unchanged.")
+                })
+            },
+        };
+
+        Ok(responder
+            .create_message(msg.ephemeral(true))
+            .await
+            .context("Error sending reload report")?
+            .into())
+    }
+}
@@ -0,0 +1,217 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serenity::{
+    builder::{CreateAllowedMentions, CreateMessage},
+    model::id::{ChannelId, UserId},
+    utils::MessageBuilder,
+};
+
+use super::prelude::*;
+use crate::{
+    client::{prefs, storage},
+    proto::guild,
+    util::time::{self, TimeError},
+};
+
+/// Furthest ahead a reminder can be set, in days
+const MAX_DAYS: i64 = 365;
+
+/// Describe an error from [`time::parse_time`] to the user who entered the
+/// time
+pub(super) fn describe_time_error<'a>(
+    mb: &'a mut MessageBuilder,
+    err: &TimeError,
+) -> &'a mut MessageBuilder {
+    match err {
+        TimeError::Unrecognized { input, suggestion } => {
+            mb.push("Couldn't understand ")
+                .push_mono_safe(input.as_str())
+                .push(" as a time.");
+
+            if let Some(suggestion) = suggestion {
+                mb.push("  Did you mean ")
+                    .push_mono_safe(suggestion.as_str())
+                    .push("?");
+            }
+
+            mb.push("  Try something like ");
+            for (i, example) in time::EXAMPLES.iter().enumerate() {
+                match i {
+                    0 => (),
+                    i if i + 1 == time::EXAMPLES.len() => {
+                        mb.push(" or ");
+                    },
+                    _ => {
+                        mb.push(", ");
+                    },
+                }
+                mb.push_mono(*example);
+            }
+            mb.push(".")
+        },
+        TimeError::Past(input) => mb.push_mono_safe(input.as_str()).push(" is in the past."),
+        TimeError::OutOfRange(input) => mb
+            .push_mono_safe(input.as_str())
+            .push(" is too far in the future."),
+    }
+}
+
+async fn fire(ctx: &Context, gid: GuildId, id: u64) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let Some(guild::Reminder {
+        channel,
+        user,
+        text,
+        ..
+    }) = storage
+        .update_guild(gid, |g| g.reminders.remove(&id))
+        .await
+        .context("Error removing reminder")?
+    else {
+        return Ok(());
+    };
+
+    let user = UserId::new(user);
+    let content = MessageBuilder::new()
+        .mention(&user)
+        .push(" Reminder: ")
+        .push_safe(text)
+        .build();
+
+    ChannelId::new(channel)
+        .send_message(
+            &ctx.http,
+            CreateMessage::new()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new().users([user])),
+        )
+        .await
+        .context("Error sending reminder")?;
+
+    Ok(())
+}
+
+fn schedule(ctx: Context, gid: GuildId, id: u64, due_at: i64) {
+    let delay = DateTime::from_timestamp(due_at, 0)
+        .and_then(|t| (t - Utc::now()).to_std().ok())
+        .unwrap_or_default();
+
+    tokio::task::spawn(
+        async move {
+            tokio::time::sleep(delay).await;
+
+            fire(&ctx, gid, id)
+                .await
+                .map_err(|err| error!(?err, "Error sending reminder"))
+                .ok();
+        }
+        .instrument(error_span!(parent: None, "reminder", %gid, id)),
+    );
+}
+
+/// Schedule any pending reminders in the given guilds
+pub async fn resume(ctx: &Context, guilds: impl IntoIterator<Item = GuildId>) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+
+    for gid in guilds {
+        let guild::Guild { reminders, .. } = storage.guild(gid).await?;
+
+        for (id, reminder) in reminders {
+            debug!(%gid, id, "Resuming pending reminder");
+            schedule(ctx.clone(), gid, id, reminder.due_at);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct RemindCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for RemindCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}remindme", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for RemindCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Set a reminder in this channel", |a| {
+            a.string(
+                "when",
+                "When to remind you, e.g. in 2h, tomorrow 9am or next friday",
+                true,
+                1..=100,
+            )
+            .string("message", "What to remind you about", true, 1..=1000)
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let when = visitor.visit_string("when")?.required()?;
+        let text = visitor.visit_string("message")?.required()?.to_owned();
+        let user = visitor.user().id;
+        let channel = visitor.channel_id();
+
+        let prefs = prefs::get(ctx).await.context("Missing prefs context")?;
+        let tz = prefs.get(user).await?.utc_offset();
+        let now = Utc::now();
+
+        let due = time::parse_time(when, now, tz).and_then(|t| {
+            if t - now <= TimeDelta::days(MAX_DAYS) {
+                Ok(t)
+            } else {
+                Err(TimeError::OutOfRange(when.trim().into()))
+            }
+        });
+        let due = match due {
+            Ok(t) => t.timestamp(),
+            Err(err) => {
+                return Err(responder
+                    .create_message(
+                        Message::rich(|mb| describe_time_error(mb, &err)).ephemeral(true),
+                    )
+                    .await
+                    .context("Error sending time error")?
+                    .into_err("Invalid reminder time"));
+            },
+        };
+
+        let id = responder.interaction_ctx().id().get();
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        storage
+            .update_guild(gid, |g| {
+                g.reminders.insert(id, guild::Reminder {
+                    channel: channel.get(),
+                    user: user.get(),
+                    text,
+                    due_at: due,
+                });
+            })
+            .await
+            .context("Error saving reminder")?;
+
+        schedule(ctx.clone(), gid, id, due);
+
+        Ok(responder
+            .create_message(
+                Message::plain(format!("Okay, I'll remind you <t:{due}:R> (<t:{due}:f>)."))
+                    .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt::Write;
+
+use super::prelude::*;
+use crate::{
+    client::storage,
+    util::dice::{self, Die, Roll},
+};
+
+/// Longest per-die breakdown shown before it is left out
+const MAX_BREAKDOWN: usize = 1500;
+
+fn write_die(s: &mut String, die: Die) {
+    let Die {
+        value,
+        kept,
+        exploded,
+    } = die;
+    let bang = if exploded { "!" } else { "" };
+
+    if kept {
+        write!(s, "{value}{bang}")
+    } else {
+        write!(s, "~~{value}{bang}~~")
+    }
+    .unwrap_or_else(|_| unreachable!());
+}
+
+/// Describe every die rolled for each dice term, or return `None` if the
+/// description would be too long
+fn breakdown(roll: &Roll) -> Option<String> {
+    let mut s = String::new();
+
+    for term in roll.terms.iter().filter(|t| !t.dice.is_empty()) {
+        write!(s, "\n`{}`: [", term.term).unwrap_or_else(|_| unreachable!());
+        for (i, &die) in term.dice.iter().enumerate() {
+            if i != 0 {
+                s.push_str(", ");
+            }
+            write_die(&mut s, die);
+        }
+        write!(s, "] = {}", term.total).unwrap_or_else(|_| unreachable!());
+
+        if s.len() > MAX_BREAKDOWN {
+            return None;
+        }
+    }
+
+    Some(s)
+}
+
+#[derive(Debug)]
+pub struct RollCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for RollCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}roll", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for RollCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Roll some dice", |a| {
+            a.string(
+                "dice",
+                "What to roll, e.g. 3d6+2, 4d6kh3, adv+5 or 2d10!",
+                true,
+                1..=100,
+            )
+        })
+        .unwrap()
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let input = visitor.visit_string("dice")?.required()?;
+        let gid = visitor.guild()?.optional().map(|(g, _)| g);
+        let channel = visitor.channel_id();
+
+        let expr = match dice::parse(input) {
+            Ok(e) => e,
+            Err(err) => {
+                return Err(responder
+                    .create_message(
+                        Message::rich(|mb| {
+                            mb.push_safe(err.to_string()).push(".  Try something like ");
+                            for (i, example) in dice::EXAMPLES.iter().enumerate() {
+                                if i != 0 {
+                                    mb.push(", ");
+                                }
+                                mb.push_mono(*example);
+                            }
+                            mb.push(".")
+                        })
+                        .ephemeral(true),
+                    )
+                    .await
+                    .context("Error sending dice error")?
+                    .into_err("Invalid dice expression"));
+            },
+        };
+
+        // Rolls outside servers have no one else to spam, so are always verbose
+        let verbose = match gid {
+            Some(gid) => {
+                let storage = storage::get(ctx).await.context("Missing storage context")?;
+                storage
+                    .guild(gid)
+                    .await?
+                    .verbose_rolls
+                    .contains(&channel.get())
+            },
+            None => true,
+        };
+
+        let roll = expr.roll(&mut rand::thread_rng());
+
+        Ok(responder
+            .create_message(Message::rich(|mb| {
+                mb.push("🎲 ")
+                    .push_mono_safe(expr.to_string())
+                    .push(": ")
+                    .push_bold(roll.total.to_string());
+
+                if verbose {
+                    match breakdown(&roll) {
+                        Some(b) => mb.push(b),
+                        None => mb.push("\n(Too many dice to show each one.)"),
+                    };
+                }
+
+                mb
+            }))
+            .await
+            .context("Error sending roll")?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::breakdown;
+    use crate::util::dice::{self, Dice, Die, Keep, Roll, Term, TermRoll};
+
+    #[test]
+    fn breakdowns() {
+        let die = |value, kept, exploded| Die {
+            value,
+            kept,
+            exploded,
+        };
+        let roll = Roll {
+            terms: vec![
+                TermRoll {
+                    negative: false,
+                    term: Term::Dice(Dice {
+                        count: 2,
+                        sides: 6,
+                        explode: true,
+                        keep: Some(Keep::Highest(2)),
+                    }),
+                    dice: vec![
+                        die(6, true, true),
+                        die(3, true, false),
+                        die(1, false, false),
+                    ],
+                    total: 9,
+                },
+                TermRoll {
+                    negative: true,
+                    term: Term::Const(2),
+                    dice: vec![],
+                    total: 2,
+                },
+            ],
+            total: 7,
+        };
+
+        assert_eq!(breakdown(&roll).unwrap(), "\n`2d6!kh2`: [6!, 3, ~~1~~] = 9");
+
+        let big = dice::parse(&["100d1000"; dice::MAX_TERMS].join("+")).unwrap();
+        assert_eq!(breakdown(&big.roll(&mut rand::thread_rng())), None);
+    }
+}
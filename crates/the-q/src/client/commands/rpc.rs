@@ -1,7 +1,37 @@
+use chrono::{DateTime, Utc};
 use serenity::model::application::{ComponentInteraction, ModalInteraction};
 
 use super::prelude::*;
 
+/// The current schema version of [`component::Component`] IDs
+const COMPONENT_VERSION: u32 = 1;
+/// The current schema version of [`modal::Modal`] IDs
+const MODAL_VERSION: u32 = 1;
+
+/// Check the schema version of an incoming custom ID, returning whether its
+/// payload can be read as the current version
+///
+/// Version 0 IDs were created before the version field was introduced, and
+/// share the payload layout of version 1.
+fn check_version(kind: &'static str, version: u32, current: u32) -> bool {
+    match version {
+        0 => {
+            trace!(kind, "Upgrading unversioned custom ID");
+            true
+        },
+        v if v == current => true,
+        v => {
+            warn!(
+                kind,
+                version = v,
+                current,
+                "Rejecting custom ID with unknown version"
+            );
+            false
+        },
+    }
+}
+
 #[derive(Debug)]
 pub enum Schema {}
 
@@ -20,13 +50,31 @@ impl rpc::ComponentId for component::Component {
 
     fn from_parts(payload: Self::Payload) -> Self {
         Self {
+            version: COMPONENT_VERSION,
+            expires_at: 0,
             payload: Some(payload),
         }
     }
 
     fn try_into_parts(self) -> Option<Self::Payload> {
-        let Self { payload } = self;
-        payload
+        let Self {
+            version,
+            expires_at: _,
+            payload,
+        } = self;
+        check_version("component", version, COMPONENT_VERSION)
+            .then_some(payload)
+            .flatten()
+    }
+
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        (self.expires_at != 0)
+            .then(|| DateTime::from_timestamp(self.expires_at, 0))
+            .flatten()
+    }
+
+    fn set_expires_at(&mut self, at: Option<DateTime<Utc>>) {
+        self.expires_at = at.map_or(0, |t| t.timestamp());
     }
 }
 
@@ -34,6 +82,13 @@ impl rpc::ComponentId for component::Component {
 pub enum ComponentKey {
     Role,
     Soundboard,
+    PollVote,
+    ConfigImport,
+    Diagnostic,
+    QuotePage,
+    LobbyButton,
+    BookmarkTags,
+    LeaderboardPage,
 }
 
 impl From<&ComponentPayload> for ComponentKey {
@@ -41,6 +96,13 @@ impl From<&ComponentPayload> for ComponentKey {
         match value {
             ComponentPayload::Role(_) => Self::Role,
             ComponentPayload::Soundboard(_) => Self::Soundboard,
+            ComponentPayload::PollVote(_) => Self::PollVote,
+            ComponentPayload::ConfigImport(_) => Self::ConfigImport,
+            ComponentPayload::Diagnostic(_) => Self::Diagnostic,
+            ComponentPayload::QuotePage(_) => Self::QuotePage,
+            ComponentPayload::LobbyButton(_) => Self::LobbyButton,
+            ComponentPayload::BookmarkTags(_) => Self::BookmarkTags,
+            ComponentPayload::LeaderboardPage(_) => Self::LeaderboardPage,
         }
     }
 }
@@ -55,13 +117,23 @@ impl rpc::ModalId for modal::Modal {
             ModalSource::Component => modal::ModalSource::Component,
         };
         Self {
+            version: MODAL_VERSION,
             source: src as i32,
             payload: Some(payload),
         }
     }
 
     fn try_into_parts(self) -> Option<(ModalSource, Self::Payload)> {
-        let Self { source, payload } = self;
+        let Self {
+            version,
+            source,
+            payload,
+        } = self;
+
+        if !check_version("modal", version, MODAL_VERSION) {
+            return None;
+        }
+
         source
             .try_into()
             .ok()
@@ -82,12 +154,16 @@ impl rpc::Key for ComponentKey {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ModalKey {
     Rename,
+    Diagnostic,
+    SaveMessage,
 }
 
 impl From<&ModalPayload> for ModalKey {
     fn from(value: &ModalPayload) -> Self {
         match value {
             ModalPayload::Rename(_) => Self::Rename,
+            ModalPayload::Diagnostic(_) => Self::Diagnostic,
+            ModalPayload::SaveMessage(_) => Self::SaveMessage,
         }
     }
 }
@@ -96,3 +172,83 @@ impl rpc::Key for ModalKey {
     type Interaction = ModalInteraction;
     type Payload = ModalPayload;
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::DateTime;
+    use paracord::interaction::{
+        response::id,
+        rpc::{ComponentId, ModalId},
+    };
+
+    use super::{component, modal, ComponentPayload, ModalPayload, ModalSource};
+
+    fn roundtrip<M: prost::Message + Default>(msg: &M) -> M {
+        id::read(&id::write(msg).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn component_roundtrip() {
+        let payload = ComponentPayload::Soundboard(component::Soundboard {
+            file: "foo.mp3".into(),
+        });
+        let msg = component::Component::from_parts(payload.clone());
+
+        assert_eq!(roundtrip(&msg).try_into_parts(), Some(payload));
+    }
+
+    #[test]
+    fn component_expiry() {
+        let mut msg = component::Component::from_parts(ComponentPayload::Role(component::Role {}));
+        assert_eq!(msg.expires_at(), None);
+
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        msg.set_expires_at(Some(at));
+        let mut msg = roundtrip(&msg);
+        assert_eq!(msg.expires_at(), Some(at));
+
+        msg.set_expires_at(None);
+        assert_eq!(roundtrip(&msg).expires_at(), None);
+    }
+
+    #[test]
+    fn modal_roundtrip() {
+        let payload = ModalPayload::Rename(modal::Rename {});
+        let msg = modal::Modal::from_parts(ModalSource::Component, payload);
+
+        let (src, out) = roundtrip(&msg).try_into_parts().unwrap();
+        assert!(matches!(src, ModalSource::Component));
+        assert_eq!(out, payload);
+    }
+
+    #[test]
+    fn unversioned_ids() {
+        let payload = ComponentPayload::Role(component::Role {});
+        let msg = component::Component {
+            version: 0,
+            expires_at: 0,
+            payload: Some(payload.clone()),
+        };
+        assert_eq!(roundtrip(&msg).try_into_parts(), Some(payload));
+
+        let payload = ModalPayload::Rename(modal::Rename {});
+        let msg = modal::Modal {
+            version: 0,
+            source: modal::ModalSource::Command as i32,
+            payload: Some(payload),
+        };
+        let (src, out) = roundtrip(&msg).try_into_parts().unwrap();
+        assert!(matches!(src, ModalSource::Command));
+        assert_eq!(out, payload);
+    }
+
+    #[test]
+    fn future_ids() {
+        let msg = component::Component {
+            version: u32::MAX,
+            expires_at: 0,
+            payload: Some(ComponentPayload::Role(component::Role {})),
+        };
+        assert_eq!(roundtrip(&msg).try_into_parts(), None);
+    }
+}
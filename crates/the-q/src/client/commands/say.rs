@@ -1,14 +1,30 @@
+use paracord::{capability, interaction::config::FromConfig};
+
 use super::prelude::*;
 
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SayConfig {
+    /// Maximum length of a message, if shorter than Discord's limit
+    max_length: Option<u16>,
+}
+
 #[derive(Debug)]
 pub struct SayCommand {
     name: String,
+    config: SayConfig,
 }
 
-impl From<&CommandOpts> for SayCommand {
-    fn from(opts: &CommandOpts) -> Self {
+impl FromConfig for SayCommand {
+    type Config = SayConfig;
+    type Opts = CommandOpts;
+
+    const SECTION: &'static str = "say";
+
+    fn from_config(opts: &CommandOpts, config: SayConfig) -> Self {
         Self {
             name: format!("{}say", opts.command_base),
+            config,
         }
     }
 }
@@ -17,7 +33,11 @@ impl From<&CommandOpts> for SayCommand {
 impl CommandHandler<Schema> for SayCommand {
     fn register_global(&self) -> CommandInfo {
         CommandInfo::build_slash(&self.name, "Say something!", |a| {
-            a.string("message", "The message to send", true, ..)
+            let (name, desc) = ("message", "The message to send");
+            match self.config.max_length {
+                Some(max) => a.string(name, desc, true, 1..=max),
+                None => a.string(name, desc, true, ..),
+            }
         })
         .unwrap()
     }
@@ -31,7 +51,12 @@ impl CommandHandler<Schema> for SayCommand {
         let msg = visitor.visit_string("message")?.required()?;
         let guild = visitor.guild()?.optional();
 
-        let color = guild.and_then(|(_, m)| m.colour(&ctx.cache));
+        let color = match guild {
+            Some((_, member)) => capability::member_colour(ctx, member)
+                .await
+                .context("Error getting member colour")?,
+            None => None,
+        };
 
         Ok(responder
             .create_message(Embed::default().desc_plain(msg).color_opt(color).into())
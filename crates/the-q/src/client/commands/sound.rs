@@ -1,14 +1,80 @@
-use std::{collections::BinaryHeap, path::PathBuf};
+use std::{
+    collections::BinaryHeap,
+    path::{Component, Path, PathBuf},
+};
 
 use ordered_float::OrderedFloat;
-use paracord::interaction::visitor::Autocomplete;
+use paracord::{
+    capability,
+    interaction::{config::FromConfig, visitor::Autocomplete},
+};
+use serenity::model::id::ChannelId;
 use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
 
 use super::prelude::*;
+use crate::client::voice;
 
 // TODO: make this configurable
 const SAMPLE_DIR: &str = "etc/samples";
 
+/// Resolve the name of a sample to its path, rejecting names which would
+/// escape the sample directory or refer to hidden files
+pub(super) fn sample_path(name: &str) -> Option<PathBuf> {
+    let rel = Path::new(name);
+    let valid = rel.components().all(|c| match c {
+        Component::Normal(s) => s.to_str().is_some_and(|s| !s.starts_with('.')),
+        _ => false,
+    });
+
+    (valid && !name.is_empty()).then(|| Path::new(SAMPLE_DIR).join(rel))
+}
+
+/// Play a sample in the given voice channel without following any user
+///
+/// Returns `false` without playing anything if the guild's voice connection
+/// is already in use.
+pub(super) async fn play_in(
+    ctx: &Context,
+    gid: GuildId,
+    chan: ChannelId,
+    path: PathBuf,
+) -> Result<bool> {
+    let sb = voice::songbird(ctx)
+        .await
+        .context("Missing songbird context")?;
+    let voice = voice::get(ctx).await.context("Missing voice context")?;
+
+    let input = songbird::input::Input::from(songbird::input::File::new(path.clone()))
+        .make_live_async()
+        .await
+        .with_context(|| format!("Error opening sample {path:?}"))?;
+
+    let Some(lock) = voice.try_lock(gid).await else {
+        return Ok(false);
+    };
+
+    let call = voice
+        .join(&sb, &lock, gid, chan)
+        .await
+        .context("Error joining voice channel")?;
+
+    call.lock()
+        .await
+        .play_input(input)
+        .add_event(
+            songbird::Event::Track(songbird::TrackEvent::End),
+            SongbirdHandler {
+                _lock: lock,
+                voice,
+                sb,
+                gid,
+            },
+        )
+        .context("Error hooking track stop")?;
+
+    Ok(true)
+}
+
 #[derive(Debug)]
 struct FileMap {
     files: RwLock<HashMap<String, PathBuf>>,
@@ -18,17 +84,38 @@ struct FileMap {
 #[derive(Debug)]
 pub struct SoundCommand {
     name: String,
+    config: SoundConfig,
     files: Mutex<std::sync::Weak<FileMap>>,
-    songbird_handle: Mutex<HashMap<GuildId, std::sync::Weak<()>>>,
     _notify_handle: RwLock<Option<oneshot::Sender<()>>>,
 }
 
-impl From<&CommandOpts> for SoundCommand {
-    fn from(opts: &CommandOpts) -> Self {
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoundConfig {
+    /// Minimum similarity, between 0 and 1, of a sample name to the text
+    /// being completed for it to be suggested
+    min_similarity: f64,
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        Self {
+            min_similarity: 0.07,
+        }
+    }
+}
+
+impl FromConfig for SoundCommand {
+    type Config = SoundConfig;
+    type Opts = CommandOpts;
+
+    const SECTION: &'static str = "sound";
+
+    fn from_config(opts: &CommandOpts, config: SoundConfig) -> Self {
         Self {
             name: format!("{}sound", opts.command_base),
+            config,
             files: Mutex::default(),
-            songbird_handle: Mutex::default(),
             _notify_handle: RwLock::default(),
         }
     }
@@ -144,11 +231,9 @@ impl SoundCommand {
     ) -> Result<X, E> {
         const PATH_ERR: &str = "That isn't a valid file.";
 
-        let voice_chan = {
-            // gay baby jail to keep rustc from freaking out
-            let guild = gid.to_guild_cached(&ctx.cache).context("Missing guild")?;
-            guild.voice_states.get(&user.id).and_then(|s| s.channel_id)
-        };
+        let voice_chan = capability::voice_channel(ctx, gid, user.id)
+            .await
+            .context("Error getting user voice state")?;
 
         let Some(voice_chan) = voice_chan else {
             return Err(fail(
@@ -159,9 +244,10 @@ impl SoundCommand {
             .await);
         };
 
-        let sb = songbird::get(ctx)
+        let sb = voice::songbird(ctx)
             .await
             .context("Missing songbird context")?;
+        let voice = voice::get(ctx).await.context("Missing voice context")?;
 
         let files = self.files().await.context("Error getting sample list")?;
         let files = files.files.read().await;
@@ -185,7 +271,16 @@ impl SoundCommand {
             .await
             .with_context(|| format!("Error opening sample {path:?}"))?;
 
-        let call = match sb.join(gid, voice_chan).await {
+        let Some(lock) = voice.try_lock(gid).await else {
+            return Err(fail(
+                extra,
+                MessageBody::plain("Calm down, buddy"),
+                "Sound already running",
+            )
+            .await);
+        };
+
+        let call = match voice.join(&sb, &lock, gid, voice_chan).await {
             Ok(l) => l,
             Err(err) => {
                 warn!(?err, "Unable to join voice channel");
@@ -198,30 +293,19 @@ impl SoundCommand {
             },
         };
 
-        let mut call_lock = call.lock().await;
-        let mut handles = self.songbird_handle.lock().await;
+        voice.follow(gid, user.id).await;
 
-        if handles
-            .get(&gid)
-            .and_then(std::sync::Weak::upgrade)
-            .is_some()
-        {
-            return Err(fail(
-                extra,
-                MessageBody::plain("Calm down, buddy"),
-                "Sound already running",
-            )
-            .await);
-        }
-
-        let handle = Arc::new(());
-        handles.insert(gid, Arc::downgrade(&handle));
-
-        call_lock
+        call.lock()
+            .await
             .play_input(input)
             .add_event(
                 songbird::Event::Track(songbird::TrackEvent::End),
-                SongbirdHandler(handle, Arc::clone(&call)),
+                SongbirdHandler {
+                    _lock: lock,
+                    voice,
+                    sb,
+                    gid,
+                },
             )
             .context("Error hooking track stop")?;
 
@@ -337,7 +421,7 @@ impl CommandHandler<Schema> for SoundCommand {
                             )
                         })
                         .filter(|(s, _)| {
-                            let matching = s.0 >= 0.07;
+                            let matching = s.0 >= self.config.min_similarity;
                             *all.get_or_init(|| !matching) || matching
                         })
                         .collect()
@@ -381,10 +465,6 @@ impl RpcHandler<Schema, ComponentKey> for SoundCommand {
         visitor: &mut ComponentVisitor<'_>,
         responder: ComponentResponder<'_, 'a>,
     ) -> ComponentResult<'a> {
-        #[expect(
-            clippy::match_wildcard_for_single_variants,
-            reason = "This will have more variants in the future"
-        )]
         match payload {
             ComponentPayload::Soundboard(s) => {
                 let component::Soundboard { file } = s;
@@ -412,10 +492,12 @@ impl RpcHandler<Schema, ComponentKey> for SoundCommand {
     }
 }
 
-struct SongbirdHandler(
-    #[expect(dead_code, reason = "This field is used as a drop canary")] Arc<()>,
-    Arc<Mutex<songbird::Call>>,
-);
+struct SongbirdHandler {
+    _lock: voice::GuildLock,
+    voice: Arc<voice::Voice>,
+    sb: Arc<songbird::Songbird>,
+    gid: GuildId,
+}
 
 #[async_trait]
 impl songbird::EventHandler for SongbirdHandler {
@@ -423,13 +505,7 @@ impl songbird::EventHandler for SongbirdHandler {
         match *ctx {
             songbird::EventContext::Track(t) => {
                 if t.iter().all(|(s, _)| s.playing.is_done()) {
-                    self.1
-                        .lock()
-                        .await
-                        .leave()
-                        .await
-                        .map_err(|err| error!(%err, "Error leaving call"))
-                        .ok();
+                    self.voice.start_idle(Arc::clone(&self.sb), self.gid).await;
                 }
 
                 None
@@ -438,3 +514,22 @@ impl songbird::EventHandler for SongbirdHandler {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{sample_path, SAMPLE_DIR};
+
+    #[test]
+    fn sample_paths() {
+        assert_eq!(
+            sample_path("memes/BUDDY.flac").as_deref(),
+            Some(Path::new(SAMPLE_DIR).join("memes/BUDDY.flac").as_path())
+        );
+        assert_eq!(sample_path(""), None);
+        assert_eq!(sample_path("../secret.flac"), None);
+        assert_eq!(sample_path("/etc/passwd"), None);
+        assert_eq!(sample_path("memes/.hidden"), None);
+    }
+}
@@ -0,0 +1,310 @@
+use serenity::{
+    builder::{CreateAllowedMentions, CreateEmbed, CreateEmbedAuthor, CreateMessage, EditMessage},
+    model::{
+        channel::{ChannelType, Message as ChannelMessage, ReactionType},
+        id::{ChannelId, MessageId},
+        mention::Mentionable,
+        Permissions,
+    },
+    utils::MessageBuilder,
+};
+use tokio::sync::Mutex;
+
+use super::{botlog, prelude::*};
+use crate::{client::storage, proto::guild};
+
+const STAR: &str = "\u{2b50}";
+const DEFAULT_THRESHOLD: u32 = 3;
+const STARBOARD_COLOR: u32 = 0x00f1_c40f;
+
+// Serializes starboard updates so that a burst of reactions can't post the
+// same message twice
+static UPDATE_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Returns true if the given reaction counts towards the starboard
+#[inline]
+pub fn is_star(reaction: &ReactionType) -> bool {
+    matches!(reaction, ReactionType::Unicode(s) if s == STAR)
+}
+
+fn star_count(message: &ChannelMessage) -> u64 {
+    message
+        .reactions
+        .iter()
+        .find(|r| is_star(&r.reaction_type))
+        .map_or(0, |r| r.count)
+}
+
+fn header(count: u64, channel: ChannelId) -> String {
+    MessageBuilder::new()
+        .push(format!("{STAR} **{count}** "))
+        .channel(channel)
+        .build()
+}
+
+fn embed(message: &ChannelMessage) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(message.author.name.clone()).icon_url(message.author.face()))
+        .description(message.content.clone())
+        .timestamp(message.timestamp)
+        .color(STARBOARD_COLOR)
+        .field(
+            "Source",
+            format!("[Jump to message]({})", message.link()),
+            false,
+        );
+
+    if let Some(image) = message.attachments.iter().find(|a| {
+        a.content_type
+            .as_ref()
+            .is_some_and(|t| t.starts_with("image/"))
+    }) {
+        embed = embed.image(image.url.clone());
+    }
+
+    embed
+}
+
+/// Bring the starboard post for the given message up to date with its current
+/// star count, posting, editing or removing it as needed
+pub async fn update(
+    ctx: &Context,
+    gid: Option<GuildId>,
+    channel: ChannelId,
+    message: MessageId,
+) -> Result {
+    let Some(gid) = gid else { return Ok(()) };
+
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let _lock = UPDATE_LOCK.lock().await;
+    let guild::Guild { starboard, .. } = storage.guild(gid).await?;
+
+    let Some(starboard) = starboard else {
+        return Ok(());
+    };
+
+    let board = ChannelId::new(starboard.channel);
+    if channel == board {
+        return Ok(());
+    }
+
+    let message = channel
+        .message(&ctx.http, message)
+        .await
+        .context("Error fetching starred message")?;
+    let count = star_count(&message);
+    let post = starboard.posts.get(&message.id.get()).copied();
+
+    match post {
+        Some(post) if count < u64::from(starboard.threshold) => {
+            debug!(%gid, message = %message.id, count, "Removing starboard post");
+            board
+                .delete_message(&ctx.http, MessageId::new(post))
+                .await
+                .context("Error deleting starboard post")?;
+            storage
+                .update_guild(gid, |g| {
+                    if let Some(s) = &mut g.starboard {
+                        s.posts.remove(&message.id.get());
+                    }
+                })
+                .await
+                .context("Error removing starboard post")?;
+        },
+        Some(post) => {
+            board
+                .edit_message(
+                    &ctx.http,
+                    MessageId::new(post),
+                    EditMessage::new().content(header(count, channel)),
+                )
+                .await
+                .context("Error updating starboard post")?;
+        },
+        None if count >= u64::from(starboard.threshold) => {
+            debug!(%gid, message = %message.id, count, "Posting to starboard");
+            let post = board
+                .send_message(
+                    &ctx.http,
+                    CreateMessage::new()
+                        .content(header(count, channel))
+                        .embed(embed(&message))
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                )
+                .await
+                .context("Error sending starboard post")?;
+            storage
+                .update_guild(gid, |g| {
+                    if let Some(s) = &mut g.starboard {
+                        s.posts.insert(message.id.get(), post.id.get());
+                    }
+                })
+                .await
+                .context("Error saving starboard post")?;
+        },
+        None => (),
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct StarboardCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for StarboardCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}starboard", opts.command_base),
+        }
+    }
+}
+
+impl StarboardCommand {
+    async fn set<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let channel = visitor.visit_channel("channel")?.required()?.id;
+        let threshold = visitor
+            .visit_i64("threshold")?
+            .optional()
+            .map_or(Ok(DEFAULT_THRESHOLD), u32::try_from)
+            .context("Invalid starboard threshold")?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        storage
+            .update_guild(gid, |g| {
+                let starboard = g.starboard.get_or_insert_with(Default::default);
+
+                // Existing posts belong to the old channel, so forget them
+                // rather than editing messages that may no longer exist
+                if starboard.channel != channel.get() {
+                    starboard.posts.clear();
+                }
+
+                starboard.channel = channel.get();
+                starboard.threshold = threshold;
+            })
+            .await
+            .context("Error saving starboard settings")?;
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new("Starboard set", visitor.user().id)
+                .with_target(format!("{} at {threshold} {STAR}", channel.mention())),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push(format!(
+                        "Messages with {threshold} or more {STAR} will now be posted to "
+                    ))
+                    .channel(channel)
+                    .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn disable<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let prev = storage
+            .update_guild(gid, |g| g.starboard.take())
+            .await
+            .context("Error saving starboard settings")?;
+
+        if prev.is_some() {
+            botlog::record(
+                ctx,
+                gid,
+                botlog::Entry::new("Starboard disabled", visitor.user().id),
+            )
+            .await;
+        }
+
+        Ok(responder
+            .create_message(
+                Message::plain(if prev.is_some() {
+                    "Starboard disabled."
+                } else {
+                    "The starboard was already disabled."
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for StarboardCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Configure the starboard", |a| {
+            a.build_subcmd("set", "Set the starboard channel and star threshold", |a| {
+                a.channel(
+                    "channel",
+                    "The channel to post starred messages in",
+                    true,
+                    [ChannelType::Text],
+                )
+                .int(
+                    "threshold",
+                    "The number of stars needed to be posted",
+                    false,
+                    1..=100,
+                )
+            })
+            .build_subcmd("disable", "Stop posting starred messages", id)
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        match *visitor.visit_subcmd()? {
+            ["set"] => self.set(ctx, visitor, responder).await,
+            ["disable"] => self.disable(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
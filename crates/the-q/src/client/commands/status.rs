@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serenity::utils::MessageBuilder;
+
+use super::{
+    super::{backup, status},
+    prelude::*,
+};
+
+fn pretty_duration(dur: Duration) -> String {
+    let secs = dur.as_secs();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    let (hours, secs) = (secs / 3600, secs % 3600);
+    let (mins, secs) = (secs / 60, secs % 60);
+
+    if days > 0 {
+        format!("{days}d {hours}h {mins}m")
+    } else if hours > 0 {
+        format!("{hours}h {mins}m")
+    } else if mins > 0 {
+        format!("{mins}m {secs}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+fn backup_status<'a>(
+    mb: &'a mut MessageBuilder,
+    last: Option<&backup::BackupStatus>,
+    now: DateTime<Utc>,
+) -> &'a mut MessageBuilder {
+    mb.push_bold("Last backup: ");
+
+    let Some(last) = last else {
+        return mb.push("never\n");
+    };
+
+    mb.push(pretty_duration(
+        (now - last.at).to_std().unwrap_or_default(),
+    ))
+    .push(" ago");
+
+    match last.result {
+        Ok(ref s) => mb.push(format!(
+            ", {} guild{}\n",
+            s.guilds,
+            if s.guilds == 1 { "" } else { "s" }
+        )),
+        Err(ref e) => mb.push(", failed: ").push_mono_safe(e.as_str()).push("\n"),
+    }
+}
+
+fn report<'a>(
+    mb: &'a mut MessageBuilder,
+    uptime: Duration,
+    backups_configured: bool,
+    last_backup: Option<&backup::BackupStatus>,
+    shards: &[status::ShardReport],
+) -> &'a mut MessageBuilder {
+    mb.push_bold("Uptime: ")
+        .push(pretty_duration(uptime))
+        .push("\n");
+
+    if backups_configured {
+        backup_status(mb, last_backup, Utc::now());
+    }
+
+    if shards.is_empty() {
+        return mb.push_italic("No shards have connected yet.");
+    }
+
+    for shard in shards {
+        mb.push_bold(format!("Shard {}: ", shard.id))
+            .push(shard.stage.to_string());
+
+        if let Some(latency) = shard.latency {
+            mb.push(format!(", {}ms", latency.as_millis()));
+        }
+
+        if let Some(ready) = shard.ready_for {
+            mb.push(format!(", up {}", pretty_duration(ready)));
+        }
+
+        mb.push(format!(
+            ", {} guild{}",
+            shard.guilds,
+            if shard.guilds == 1 { "" } else { "s" }
+        ));
+
+        if shard.reconnects > 0 {
+            mb.push(format!(", {} reconnect(s)", shard.reconnects));
+        }
+
+        if shard.storming {
+            mb.push(" \u{26a0}\u{fe0f} reconnecting frequently");
+        }
+
+        mb.push("\n");
+    }
+
+    mb
+}
+
+#[derive(Debug)]
+pub struct StatusCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for StatusCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}status", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for StatusCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Show the bot's connection status", |a| a).unwrap()
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        _: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let status = status::get(ctx).await.context("Missing status context")?;
+        let shards = status.report().await;
+        let backups = backup::get(ctx).await.context("Missing backup context")?;
+        let last_backup = backups.last().await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    report(
+                        mb,
+                        status.uptime(),
+                        backups.configured(),
+                        last_backup.as_ref(),
+                        &shards,
+                    )
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending status")?
+            .into())
+    }
+}
@@ -1,4 +1,264 @@
-use super::prelude::*;
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use paracord::interaction::{
+    rpc::ComponentId,
+    visitor::{TextRule, TextRuleExt},
+};
+use serenity::{
+    builder::CreateAttachment,
+    model::{id::UserId, Permissions},
+    utils::MessageBuilder,
+};
+use tokio::sync::Mutex;
+
+use super::{errors::is_owner, prelude::*};
+use crate::proto::component::diagnostic::Step;
+
+/// How long the interactive checks of a run can be completed for
+const RUN_TTL: TimeDelta = TimeDelta::minutes(15);
+/// Contents of the file uploaded by the attachment check
+const ATTACHMENT: &[u8] = b"the-q diagnostics\n";
+const ATTACHMENT_NAME: &str = "diagnostics.txt";
+/// Permissions the bot's features rely on in an ordinary text channel
+const REQUIRED_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::SEND_MESSAGES)
+    .union(Permissions::SEND_MESSAGES_IN_THREADS)
+    .union(Permissions::EMBED_LINKS)
+    .union(Permissions::ATTACH_FILES)
+    .union(Permissions::READ_MESSAGE_HISTORY)
+    .union(Permissions::ADD_REACTIONS)
+    .union(Permissions::CREATE_PUBLIC_THREADS);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Check {
+    Defer,
+    ComponentId,
+    Followup,
+    Attachment,
+    Permissions,
+    Component,
+    ModalOpen,
+    ModalSubmit,
+}
+
+impl Check {
+    const ALL: [Self; 8] = [
+        Self::Defer,
+        Self::ComponentId,
+        Self::Followup,
+        Self::Attachment,
+        Self::Permissions,
+        Self::Component,
+        Self::ModalOpen,
+        Self::ModalSubmit,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Defer => "Deferred response",
+            Self::ComponentId => "Component ID encoding",
+            Self::Followup => "Followup create/edit/delete",
+            Self::Attachment => "Attachment upload",
+            Self::Permissions => "Channel permissions",
+            Self::Component => "Component round-trip",
+            Self::ModalOpen => "Modal open",
+            Self::ModalSubmit => "Modal submit",
+        }
+    }
+
+    /// Instructions for checks that need the user to do something
+    fn prompt(self) -> Option<&'static str> {
+        match self {
+            Self::Component => Some("press **Component**"),
+            Self::ModalOpen | Self::ModalSubmit => Some("press **Modal** and submit it"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Pass,
+    Fail(String),
+    Skip(String),
+}
+
+impl<E: std::fmt::Display> From<Result<(), E>> for Outcome {
+    fn from(res: Result<(), E>) -> Self {
+        match res {
+            Ok(()) => Self::Pass,
+            Err(err) => Self::Fail(err.to_string()),
+        }
+    }
+}
+
+type Results = BTreeMap<Check, Outcome>;
+
+/// A diagnostics run, keyed by the ID of the interaction that started it
+#[derive(Debug)]
+struct Run {
+    user: UserId,
+    results: Results,
+    expires_at: DateTime<Utc>,
+}
+
+static RUNS: Mutex<BTreeMap<u64, Run>> = Mutex::const_new(BTreeMap::new());
+
+/// Record the outcome of a check, returning the updated results, or `None`
+/// if the run is unknown, expired or belongs to someone else
+async fn record(id: u64, user: UserId, check: Check, outcome: Outcome) -> Option<Results> {
+    let mut runs = RUNS.lock().await;
+    let run = runs
+        .get_mut(&id)
+        .filter(|r| r.user == user && r.expires_at > Utc::now())?;
+    run.results.insert(check, outcome);
+    Some(run.results.clone())
+}
+
+/// Write the pass/fail matrix for a run, including pending checks
+fn describe<'a>(mb: &'a mut MessageBuilder, results: &Results) -> &'a mut MessageBuilder {
+    let passed = results.values().filter(|o| **o == Outcome::Pass).count();
+    mb.push_bold("Diagnostics")
+        .push(format!(" ({passed}/{} passed)", Check::ALL.len()));
+
+    for check in Check::ALL {
+        mb.push("\n");
+        match results.get(&check) {
+            Some(Outcome::Pass) => mb.push("✅ ").push(check.label()),
+            Some(Outcome::Fail(why)) => mb
+                .push("❌ ")
+                .push(check.label())
+                .push(": ")
+                .push_safe(why.as_str()),
+            Some(Outcome::Skip(why)) => mb
+                .push("➖ ")
+                .push(check.label())
+                .push(": ")
+                .push_safe(why.as_str()),
+            None => mb.push("⏳ ").push(check.label()).push(
+                check
+                    .prompt()
+                    .map_or_else(String::new, |p| format!(": {p}")),
+            ),
+        };
+    }
+
+    mb
+}
+
+/// Build the report for a run, with buttons for the interactive checks
+fn report(id: u64, results: &Results, echo: Option<&str>) -> MessageBody {
+    let button = |step: Step| {
+        ComponentPayload::Diagnostic(component::Diagnostic {
+            run: id,
+            step: step.into(),
+        })
+    };
+
+    MessageBody::rich(|mb| {
+        describe(mb, results);
+        if let Some(echo) = echo {
+            mb.push("\nModal received ").push_mono_safe(echo);
+        }
+        mb
+    })
+    .buttons(|b| {
+        b.button(
+            button(Step::Component),
+            ButtonStyle::Primary,
+            "Component",
+            results.contains_key(&Check::Component),
+        )
+        .button(
+            button(Step::Modal),
+            ButtonStyle::Primary,
+            "Modal",
+            results.contains_key(&Check::ModalSubmit),
+        )
+    })
+    .expire_at(Utc::now() + RUN_TTL)
+}
+
+fn field_rule(id: u64) -> TextRule<component::Component> {
+    TextRule::new(
+        ComponentPayload::Diagnostic(component::Diagnostic {
+            run: id,
+            step: Step::Field.into(),
+        }),
+        "Anything",
+    )
+    .len(1..=100)
+}
+
+fn check_component_id(id: u64) -> Outcome {
+    let msg =
+        component::Component::from_parts(ComponentPayload::Diagnostic(component::Diagnostic {
+            run: id,
+            step: Step::Component.into(),
+        }));
+
+    match response::id::write(&msg).and_then(|i| response::id::read::<component::Component>(&i)) {
+        Ok(m) if m == msg => Outcome::Pass,
+        Ok(_) => Outcome::Fail("decoded ID does not match".into()),
+        Err(err) => Outcome::Fail(err.to_string()),
+    }
+}
+
+fn check_permissions(perms: Option<Permissions>) -> Outcome {
+    let Some(perms) = perms else {
+        return Outcome::Skip("not provided by Discord".into());
+    };
+
+    let missing = REQUIRED_PERMISSIONS - perms;
+    if missing.is_empty() {
+        Outcome::Pass
+    } else {
+        Outcome::Fail(format!(
+            "missing {}",
+            missing.get_permission_names().join(", ")
+        ))
+    }
+}
+
+async fn check_followup(responder: &CreatedCommandResponder<'_>) -> Outcome {
+    let res = async {
+        let mut fup = responder
+            .create_followup(Message::plain("Diagnostic followup").ephemeral(true))
+            .await?;
+        responder
+            .edit_followup(&mut fup, Message::plain("Diagnostic followup (edited)"))
+            .await?;
+        responder.delete_followup(fup).await
+    };
+
+    res.await.into()
+}
+
+async fn check_attachment(responder: &CreatedCommandResponder<'_>) -> Outcome {
+    let fup = match responder
+        .create_followup(
+            Message::plain("")
+                .attach([CreateAttachment::bytes(ATTACHMENT, ATTACHMENT_NAME)])
+                .ephemeral(true),
+        )
+        .await
+    {
+        Ok(f) => f,
+        Err(err) => return Outcome::Fail(err.to_string()),
+    };
+
+    let uploaded =
+        fup.message().attachments.iter().any(|a| {
+            a.filename == ATTACHMENT_NAME && usize::try_from(a.size) == Ok(ATTACHMENT.len())
+        });
+
+    match responder.delete_followup(fup).await {
+        Err(err) => Outcome::Fail(format!("uploaded, but {err}")),
+        Ok(()) if uploaded => Outcome::Pass,
+        Ok(()) => Outcome::Fail("attachment missing from sent message".into()),
+    }
+}
 
 #[derive(Debug)]
 pub struct TestCommand {
@@ -8,31 +268,211 @@ pub struct TestCommand {
 impl From<&CommandOpts> for TestCommand {
     fn from(opts: &CommandOpts) -> Self {
         Self {
-            name: format!("{}Test", opts.context_menu_base),
+            name: format!("{}test", opts.command_base),
         }
     }
 }
 
 #[async_trait]
 impl CommandHandler<Schema> for TestCommand {
-    fn register_global(&self) -> CommandInfo { CommandInfo::user(&self.name) }
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(
+            &self.name,
+            "Run interaction diagnostics in this channel (owner only)",
+            |a| a,
+        )
+        .unwrap()
+    }
 
     async fn respond<'a>(
         &self,
-        _: &Context,
-        _: &mut CommandVisitor<'_>,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
         responder: CommandResponder<'_, 'a>,
     ) -> CommandResult<'a> {
+        if !is_owner(ctx, visitor.user()).await? {
+            return Err(responder
+                .create_message(
+                    Message::plain("This command can only be used by the bot owner.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Non-owner requested diagnostics"));
+        }
+
+        let responder = responder
+            .defer_message(MessageOpts::default().ephemeral(true))
+            .await
+            .context("Error deferring diagnostics")?;
+        let id = responder.interaction_ctx().id().get();
+
+        let mut results = Results::new();
+        results.insert(Check::Defer, Outcome::Pass);
+        results.insert(Check::ComponentId, check_component_id(id));
+        results.insert(Check::Followup, check_followup(&responder).await);
+        results.insert(Check::Attachment, check_attachment(&responder).await);
+        results.insert(
+            Check::Permissions,
+            check_permissions(visitor.app_permissions()),
+        );
+
+        {
+            let now = Utc::now();
+            let mut runs = RUNS.lock().await;
+            runs.retain(|_, r| r.expires_at > now);
+            runs.insert(id, Run {
+                user: visitor.user().id,
+                results: results.clone(),
+                expires_at: now + RUN_TTL,
+            });
+        }
+
+        responder
+            .edit(report(id, &results, None))
+            .await
+            .context("Error sending diagnostics report")?;
+
+        Ok(responder.into())
+    }
+}
+
+#[async_trait]
+impl RpcHandler<Schema, ComponentKey> for TestCommand {
+    fn register_keys(&self) -> &'static [ComponentKey] { &[ComponentKey::Diagnostic] }
+
+    async fn respond<'a>(
+        &self,
+        _: &Context,
+        payload: ComponentPayload,
+        visitor: &mut ComponentVisitor<'_>,
+        responder: ComponentResponder<'_, 'a>,
+    ) -> ComponentResult<'a> {
+        let ComponentPayload::Diagnostic(component::Diagnostic { run: id, step }) = payload else {
+            unreachable!(); // TODO: set up an error for this
+        };
+        let user = visitor.user().id;
+
+        if step == i32::from(Step::Modal) {
+            let res = responder
+                .modal(|s| {
+                    Modal::new(
+                        s,
+                        ModalPayload::Diagnostic(modal::Diagnostic { run: id }),
+                        "Diagnostics",
+                    )
+                    .text_short(
+                        ComponentPayload::Diagnostic(component::Diagnostic {
+                            run: id,
+                            step: Step::Field.into(),
+                        }),
+                        "Anything",
+                        |t| t.value("ok").len(1..=100),
+                    )
+                })
+                .await;
+
+            // The response is spent either way, so report through the
+            // submission or not at all
+            let outcome = res.as_ref().map(|_| ()).map_err(ToString::to_string).into();
+            record(id, user, Check::ModalOpen, outcome).await;
+            return Ok(res.context("Error opening diagnostic modal")?.into());
+        }
+
+        let Some(results) = record(id, user, Check::Component, Outcome::Pass).await else {
+            return Err(responder
+                .create_message(
+                    Message::plain("This diagnostics run has expired.  Please run it again.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending expiry error")?
+                .into_err("Unknown or expired diagnostics run"));
+        };
+
         Ok(responder
-            .modal(|s| {
-                Modal::new(s, ModalPayload::Rename(modal::Rename {}), "Hi!").text_long(
-                    ComponentPayload::Role(component::Role {}),
-                    "foo",
-                    id,
+            .update_message(report(id, &results, None).into())
+            .await
+            .context("Error updating diagnostics report")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl RpcHandler<Schema, ModalKey> for TestCommand {
+    fn register_keys(&self) -> &'static [ModalKey] { &[ModalKey::Diagnostic] }
+
+    async fn respond<'a>(
+        &self,
+        _: &Context,
+        payload: ModalPayload,
+        visitor: &mut ModalVisitor<'_>,
+        responder: ModalResponder<'_, 'a>,
+    ) -> ModalResult<'a> {
+        let ModalPayload::Diagnostic(modal::Diagnostic { run: id }) = payload else {
+            unreachable!(); // TODO: set up an error for this
+        };
+        let [value] = visitor.visit_text(&[field_rule(id)])?;
+
+        let Some(results) = record(id, visitor.user().id, Check::ModalSubmit, Outcome::Pass).await
+        else {
+            return Err(responder
+                .create_message(
+                    Message::plain("This diagnostics run has expired.  Please run it again.")
+                        .ephemeral(true),
                 )
-            })
+                .await
+                .context("Error sending expiry error")?
+                .into_err("Unknown or expired diagnostics run"));
+        };
+
+        Ok(responder
+            .create_message(Message::from(report(id, &results, Some(value))).ephemeral(true))
             .await
-            .context("Error creating modal")?
+            .context("Error sending diagnostics report")?
             .into())
     }
 }
+
+#[cfg(test)]
+#[expect(
+    clippy::module_inception,
+    reason = "Test modules are always named test"
+)]
+mod test {
+    use serenity::model::Permissions;
+
+    use super::{check_component_id, check_permissions, describe, Check, Outcome, Results};
+
+    #[test]
+    fn matrix() {
+        let mut results = Results::new();
+        results.insert(Check::Defer, Outcome::Pass);
+        results.insert(Check::Attachment, Outcome::Fail("nope".into()));
+        results.insert(Check::Permissions, Outcome::Skip("DM".into()));
+
+        let mut mb = serenity::utils::MessageBuilder::new();
+        describe(&mut mb, &results);
+        let text = mb.build();
+        let lines: Vec<_> = text.lines().collect();
+
+        assert_eq!(lines.len(), Check::ALL.len() + 1);
+        assert_eq!(lines[0], "**Diagnostics** (1/8 passed)");
+        assert_eq!(lines[1], "✅ Deferred response");
+        assert_eq!(lines[2], "⏳ Component ID encoding");
+        assert_eq!(lines[4], "❌ Attachment upload: nope");
+        assert_eq!(lines[5], "➖ Channel permissions: DM");
+        assert_eq!(lines[6], "⏳ Component round-trip: press **Component**");
+    }
+
+    #[test]
+    fn checks() {
+        assert_eq!(check_component_id(1234), Outcome::Pass);
+        assert_eq!(check_permissions(Some(Permissions::all())), Outcome::Pass);
+        assert!(matches!(check_permissions(None), Outcome::Skip(_)));
+        assert!(matches!(
+            check_permissions(Some(Permissions::VIEW_CHANNEL)),
+            Outcome::Fail(_)
+        ));
+    }
+}
@@ -0,0 +1,396 @@
+use paracord::interaction::locale::Locale;
+use reqwest::header;
+use serde_json::{json, Value};
+use serenity::model::{id::UserId, Permissions};
+
+use super::prelude::*;
+use crate::{
+    client::{prefs, storage},
+    proto::guild,
+};
+
+/// Language translations are made into when no other preference is set and
+/// the user's Discord language isn't recognized
+const DEFAULT_TARGET: &str = "en";
+
+/// The result of translating a piece of text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Translation {
+    /// The translated text
+    pub text: String,
+    /// The detected source language, if the backend reported one
+    pub source: Option<String>,
+    /// The backend's confidence in the detected language, from 0 to 1
+    pub confidence: Option<f64>,
+}
+
+/// A backend capable of translating text between languages
+#[async_trait]
+pub trait Translator: fmt::Debug + Send + Sync {
+    /// Translate `text` into the language with the ISO 639 code `target`,
+    /// detecting its source language
+    async fn translate(&self, text: &str, target: &str) -> Result<Translation>;
+}
+
+/// A translator backed by a (possibly self-hosted) `LibreTranslate` server
+#[derive(Debug)]
+pub struct LibreTranslate {
+    url: Url,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl LibreTranslate {
+    pub fn new(url: Url, api_key: Option<String>) -> Self {
+        Self {
+            url,
+            api_key,
+            client: http_client(Some(std::time::Duration::from_secs(30))),
+        }
+    }
+
+    fn parse(body: &Value) -> Result<Translation> {
+        if let Some(err) = body["error"].as_str() {
+            bail!("Translation server returned an error: {err}");
+        }
+
+        let text = body["translatedText"]
+            .as_str()
+            .context("Missing translated text in response")?
+            .to_owned();
+        let detected = &body["detectedLanguage"];
+
+        Ok(Translation {
+            text,
+            source: detected["language"].as_str().map(ToOwned::to_owned),
+            confidence: detected["confidence"].as_f64().map(|c| c / 100.0),
+        })
+    }
+}
+
+#[async_trait]
+impl Translator for LibreTranslate {
+    async fn translate(&self, text: &str, target: &str) -> Result<Translation> {
+        let url = self
+            .url
+            .join("translate")
+            .context("Error building translation URL")?;
+
+        let request = json!({
+            "q": text,
+            "source": "auto",
+            "target": target,
+            "format": "text",
+            "api_key": self.api_key,
+        });
+
+        let body = self
+            .client
+            .post(url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(request.to_string())
+            .send()
+            .await
+            .context("Error sending translation request")?
+            .bytes()
+            .await
+            .context("Error reading translation response")?;
+        let body: Value =
+            serde_json::from_slice(&body).context("Error parsing translation response")?;
+
+        Self::parse(&body)
+    }
+}
+
+/// Construct the configured translation backend, if any
+pub fn backend(opts: &CommandOpts) -> Option<Arc<dyn Translator>> {
+    let url = opts.translate_url.clone()?;
+    Some(Arc::new(LibreTranslate::new(
+        url,
+        opts.translate_api_key.clone(),
+    )))
+}
+
+/// Convert a Discord locale or language code into an ISO 639 language code,
+/// returning `None` if it doesn't look like one
+fn language_code(locale: &str) -> Option<String> {
+    let locale = locale.trim();
+
+    // LibreTranslate distinguishes Traditional Chinese by its own code
+    if locale.eq_ignore_ascii_case("zh-TW") {
+        return Some("zt".into());
+    }
+
+    let lang = locale.split_once('-').map_or(locale, |(l, _)| l);
+    ((2..=3).contains(&lang.len()) && lang.bytes().all(|b| b.is_ascii_alphabetic()))
+        .then(|| lang.to_ascii_lowercase())
+}
+
+/// Determine the language a user's translations should be made into, falling
+/// back to the language of their Discord client
+async fn target_language(
+    ctx: &Context,
+    gid: Option<GuildId>,
+    user: UserId,
+    locale: &Locale,
+) -> Result<String> {
+    if let Some(gid) = gid {
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        if let Some(guild::Translate { target }) = storage.guild(gid).await?.translate {
+            return Ok(target);
+        }
+    }
+
+    let prefs = prefs::get(ctx).await.context("Missing prefs context")?;
+    Ok(prefs
+        .get(user)
+        .await?
+        .locale
+        .as_deref()
+        .and_then(language_code)
+        .or_else(|| language_code(locale.tag()))
+        .unwrap_or_else(|| DEFAULT_TARGET.into()))
+}
+
+/// Format a translation for display, split into messages short enough to send
+fn describe(translation: &Translation, target: &str) -> Vec<String> {
+    let Translation {
+        text,
+        source,
+        confidence,
+    } = translation;
+
+    let header = match (source, confidence) {
+        (Some(s), Some(c)) => format!(
+            "**Translated from `{s}` to `{target}`** ({:.0}% sure)",
+            c * 100.0
+        ),
+        (Some(s), None) => format!("**Translated from `{s}` to `{target}`**"),
+        (None, _) => format!("**Translated to `{target}`**"),
+    };
+
+    response::chunk_content(&format!("{header}\n{text}"), response::MAX_CONTENT_LEN)
+}
+
+#[derive(Debug)]
+pub struct TranslateMessageCommand {
+    name: String,
+    backend: Arc<dyn Translator>,
+}
+
+impl TranslateMessageCommand {
+    pub fn new(opts: &CommandOpts, backend: Arc<dyn Translator>) -> Self {
+        Self {
+            name: format!("{}Translate", opts.context_menu_base),
+            backend,
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for TranslateMessageCommand {
+    fn register_global(&self) -> CommandInfo { CommandInfo::message(&self.name) }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let message = visitor.target().message()?;
+        let gid = visitor.guild()?.optional().map(|(g, _)| g);
+        let user = visitor.user().id;
+        let locale = visitor.locale();
+
+        if message.content.trim().is_empty() {
+            return Err(responder
+                .create_message(
+                    Message::plain("That message has no text to translate.").ephemeral(true),
+                )
+                .await
+                .context("Error sending empty message error")?
+                .into_err("Target message had no text"));
+        }
+
+        let responder = responder
+            .defer_message(MessageOpts::default().ephemeral(true))
+            .await
+            .context("Error sending deferred message")?;
+
+        let target = target_language(ctx, gid, user, &locale).await?;
+        let translation = self.backend.translate(&message.content, &target).await?;
+
+        let mut chunks = describe(&translation, &target).into_iter();
+        responder
+            .edit(MessageBody::plain(chunks.next().unwrap_or_default()))
+            .await
+            .context("Error sending translation")?;
+
+        for chunk in chunks {
+            responder
+                .create_followup(Message::plain(chunk).ephemeral(true))
+                .await
+                .context("Error sending translation continuation")?;
+        }
+
+        Ok(responder.into())
+    }
+}
+
+#[derive(Debug)]
+pub struct TranslateCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for TranslateCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}translate", opts.command_base),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for TranslateCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Configure message translation", |a| {
+            a.build_subcmd(
+                "language",
+                "Set the language messages in this server are translated into",
+                |a| a.string("code", "A language code, e.g. en or pt-BR", true, 2..=6),
+            )
+            .build_subcmd(
+                "reset",
+                "Translate into each member's preferred language instead",
+                id,
+            )
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+
+        let reply = match *visitor.visit_subcmd()? {
+            ["language"] => {
+                let code = visitor.visit_string("code")?.required()?;
+                let Some(target) = language_code(code) else {
+                    return Err(responder
+                        .create_message(
+                            Message::plain(
+                                "That doesn't look like a language code.  Try something like en \
+                                 or pt-BR.",
+                            )
+                            .ephemeral(true),
+                        )
+                        .await
+                        .context("Error sending language error")?
+                        .into_err("Invalid language code"));
+                };
+
+                let reply = format!("Messages will now be translated into `{target}`.");
+                storage
+                    .update_guild(gid, |g| g.translate = Some(guild::Translate { target }))
+                    .await
+                    .context("Error saving translation language")?;
+                reply
+            },
+            ["reset"] => {
+                storage
+                    .update_guild(gid, |g| g.translate = None)
+                    .await
+                    .context("Error saving translation language")?;
+                "Messages will now be translated into each member's preferred language.".into()
+            },
+            [..] => unreachable!(), // TODO: visitor should handle this
+        };
+
+        Ok(responder
+            .create_message(Message::plain(reply).ephemeral(true))
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use paracord::interaction::response;
+    use serde_json::json;
+
+    use super::{describe, language_code, LibreTranslate, Translation};
+
+    #[test]
+    fn language_codes() {
+        assert_eq!(language_code("en-US").as_deref(), Some("en"));
+        assert_eq!(language_code("pt-BR").as_deref(), Some("pt"));
+        assert_eq!(language_code("zh-TW").as_deref(), Some("zt"));
+        assert_eq!(language_code(" FR ").as_deref(), Some("fr"));
+        assert_eq!(language_code("es-419").as_deref(), Some("es"));
+        assert_eq!(language_code("e"), None);
+        assert_eq!(language_code("english"), None);
+        assert_eq!(language_code("e1"), None);
+    }
+
+    #[test]
+    fn libre_response() {
+        let res = LibreTranslate::parse(&json!({
+            "translatedText": "Hello",
+            "detectedLanguage": { "confidence": 90.0, "language": "fr" },
+        }))
+        .unwrap();
+        assert_eq!(res, Translation {
+            text: "Hello".into(),
+            source: Some("fr".into()),
+            confidence: Some(0.9),
+        });
+
+        assert!(LibreTranslate::parse(&json!({ "error": "Invalid target" })).is_err());
+        assert!(LibreTranslate::parse(&json!({})).is_err());
+    }
+
+    #[test]
+    fn long_replies() {
+        let text = "word ".repeat(1000);
+        let chunks = describe(
+            &Translation {
+                text: text.clone(),
+                source: None,
+                confidence: None,
+            },
+            "en",
+        );
+
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].starts_with("**Translated to `en`**\n"));
+        assert!(chunks
+            .iter()
+            .all(|c| c.chars().count() <= response::MAX_CONTENT_LEN));
+        assert_eq!(
+            chunks.join(" ").split_whitespace().count(),
+            text.split_whitespace().count() + 3
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use std::cmp::Reverse;
+
+use chrono::{DateTime, Utc};
+use serenity::model::{id::UserId, Permissions};
+
+use super::{botlog, prelude::*};
+use crate::{
+    client::{
+        prefs, storage,
+        voice::{self, ACTIVITY_RETENTION_DAYS},
+    },
+    proto::guild,
+};
+
+const DEFAULT_DAYS: i64 = 7;
+/// Number of users shown in the most active list
+const TOP_USERS: usize = 10;
+/// Number of users shown in the quietest list
+const QUIET_USERS: usize = 5;
+
+/// A user ID and the seconds that user spent in voice
+type Total = (u64, u64);
+
+fn pretty_duration(secs: u64) -> String {
+    let (hours, mins) = (secs / 3600, secs % 3600 / 60);
+
+    if hours > 0 {
+        format!("{hours}h {mins}m")
+    } else {
+        format!("{mins}m")
+    }
+}
+
+/// Total the seconds each tracked user spent in voice since the given day,
+/// including sessions still in progress, sorted from most to least
+fn totals(
+    activity: &HashMap<u64, guild::VoiceActivity>,
+    live: &[(UserId, DateTime<Utc>)],
+    since: i64,
+    now: DateTime<Utc>,
+) -> Vec<Total> {
+    fn sum(days: impl IntoIterator<Item = (i64, u64)>, since: i64) -> u64 {
+        days.into_iter()
+            .filter(|&(d, _)| d >= since)
+            .map(|(_, s)| s)
+            .sum()
+    }
+
+    let mut totals: HashMap<_, _> = activity
+        .iter()
+        .map(|(&user, a)| (user, sum(a.days.iter().map(|(&d, &s)| (d, s)), since)))
+        .collect();
+
+    for &(user, start) in live {
+        *totals.entry(user.get()).or_default() += sum(voice::split_days(start, now), since);
+    }
+
+    let mut totals: Vec<_> = totals.into_iter().collect();
+    totals.sort_unstable_by_key(|&(user, secs)| (Reverse(secs), user));
+    totals
+}
+
+/// Split sorted totals into the most active users and the quietest of the
+/// remaining users, quietest first
+fn leaderboard(totals: &[Total]) -> (&[Total], Vec<Total>) {
+    let (top, rest) = totals.split_at(totals.len().min(TOP_USERS));
+    let quiet = rest.iter().rev().take(QUIET_USERS).copied().collect();
+    (top, quiet)
+}
+
+#[derive(Debug)]
+pub struct VoiceCommand {
+    name: String,
+    prefs_name: String,
+}
+
+impl From<&CommandOpts> for VoiceCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}voice", opts.command_base),
+            prefs_name: format!("{}prefs", opts.command_base),
+        }
+    }
+}
+
+impl VoiceCommand {
+    async fn stats<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let days = visitor
+            .visit_i64("days")?
+            .optional()
+            .unwrap_or(DEFAULT_DAYS);
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild = storage.guild(gid).await?;
+
+        if !guild.voice_stats {
+            return Err(responder
+                .create_message(
+                    Message::plain(
+                        "Voice stats aren't enabled in this server.  Someone with the Manage \
+                         Server permission can turn them on.",
+                    )
+                    .ephemeral(true),
+                )
+                .await
+                .context("Error sending disabled error")?
+                .into_err("Voice stats disabled"));
+        }
+
+        let voice = voice::get(ctx).await.context("Missing voice context")?;
+        let prefs = prefs::get(ctx).await.context("Missing prefs context")?;
+        let now = Utc::now();
+        let since = voice::epoch_day(now) - (days - 1);
+
+        let mut tracked = vec![];
+        for (user, secs) in totals(
+            &guild.voice_activity,
+            &voice.sessions(gid).await,
+            since,
+            now,
+        ) {
+            if !prefs.get(UserId::new(user)).await?.voice_stats_opt_out {
+                tracked.push((user, secs));
+            }
+        }
+
+        let (top, quiet) = leaderboard(&tracked);
+        let period = if days == 1 {
+            "today".into()
+        } else {
+            format!("in the last {days} days")
+        };
+
+        let msg = if top.is_empty() {
+            Message::plain(format!("Nobody has spent time in voice {period}."))
+        } else {
+            Message::rich(|mb| {
+                mb.push_bold_line(format!("Most time in voice {period}:"));
+                for (i, &(user, secs)) in top.iter().enumerate() {
+                    mb.push(format!("{}. ", i + 1))
+                        .user(UserId::new(user))
+                        .push_line(format!(" \u{2014} {}", pretty_duration(secs)));
+                }
+
+                if !quiet.is_empty() {
+                    mb.push_bold_line("\nQuietest:");
+                    for &(user, secs) in &quiet {
+                        mb.push("- ")
+                            .user(UserId::new(user))
+                            .push_line(format!(" \u{2014} {}", pretty_duration(secs)));
+                    }
+                }
+
+                mb
+            })
+        };
+
+        Ok(responder
+            .create_message(msg)
+            .await
+            .context("Error sending voice stats")?
+            .into())
+    }
+
+    async fn tracking<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, memb) = visitor.guild()?.required()?;
+        let can_manage = memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD));
+        let enabled = visitor.visit_bool("enabled")?.required()?;
+        let user = visitor.user().id;
+
+        if !can_manage {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        storage
+            .update_guild(gid, |g| {
+                g.voice_stats = enabled;
+
+                // Collected activity is deleted rather than kept around
+                // for when tracking is turned back on
+                if !enabled {
+                    g.voice_activity.clear();
+                }
+            })
+            .await
+            .context("Error saving voice stats settings")?;
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new(
+                if enabled {
+                    "Voice stats enabled"
+                } else {
+                    "Voice stats disabled"
+                },
+                user,
+            ),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::plain(if enabled {
+                    format!(
+                        "Time spent in voice channels will now be tracked for up to \
+                         {ACTIVITY_RETENTION_DAYS} days.  Members can opt out with `/{} \
+                         voicestats`.",
+                        self.prefs_name
+                    )
+                } else {
+                    "Voice stats disabled, and all tracked activity deleted.".into()
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for VoiceCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Voice channel statistics", |a| {
+            a.build_subcmd("stats", "Show who has spent the most time in voice", |a| {
+                a.int(
+                    "days",
+                    "How many days back to look, including today",
+                    false,
+                    1..=ACTIVITY_RETENTION_DAYS,
+                )
+            })
+            .build_subcmd(
+                "tracking",
+                "Set whether time spent in voice is tracked in this server",
+                |a| a.bool("enabled", "Whether to track time in voice", true),
+            )
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        match *visitor.visit_subcmd()? {
+            ["stats"] => self.stats(ctx, visitor, responder).await,
+            ["tracking"] => self.tracking(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Utc};
+    use serenity::model::id::UserId;
+
+    use super::{leaderboard, pretty_duration, totals};
+    use crate::proto::guild;
+
+    fn at(secs: i64) -> DateTime<Utc> { DateTime::from_timestamp(secs, 0).unwrap() }
+
+    #[test]
+    fn stats() {
+        let activity = [
+            (1, guild::VoiceActivity {
+                days: [(0, 500), (1, 100)].into(),
+            }),
+            (2, guild::VoiceActivity {
+                days: [(1, 300)].into(),
+            }),
+            (3, guild::VoiceActivity {
+                days: [(0, 1000)].into(),
+            }),
+        ]
+        .into();
+        let live = [
+            (UserId::new(2), at(86_400 * 2 - 50)),
+            (UserId::new(4), at(86_400 * 2)),
+        ];
+        let now = at(86_400 * 2 + 20);
+
+        assert_eq!(totals(&activity, &live, 1, now), [
+            (2, 370),
+            (1, 100),
+            (4, 20),
+            (3, 0)
+        ]);
+        assert_eq!(totals(&activity, &[], 0, now), [
+            (3, 1000),
+            (1, 600),
+            (2, 300)
+        ]);
+
+        let many: Vec<_> = (0..14).map(|u| (u, 100 - u)).collect();
+        let (top, quiet) = leaderboard(&many);
+        assert_eq!(top.len(), 10);
+        assert_eq!(quiet, [(13, 87), (12, 88), (11, 89), (10, 90)]);
+        assert!(leaderboard(&many[..3]).1.is_empty());
+
+        assert_eq!(pretty_duration(59), "0m");
+        assert_eq!(pretty_duration(3 * 3600 + 12 * 60 + 5), "3h 12m");
+    }
+}
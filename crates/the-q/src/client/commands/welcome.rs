@@ -0,0 +1,249 @@
+use paracord::capability;
+use qcore::build_with::BuildWith;
+use serenity::{
+    builder::CreateMessage,
+    model::{
+        channel::ChannelType,
+        guild::Member,
+        id::{ChannelId, UserId},
+        mention::Mentionable,
+        Permissions,
+    },
+    utils::MessageBuilder,
+};
+
+use super::{botlog, prelude::*};
+use crate::{client::storage, proto::guild};
+
+fn render<'a>(
+    mb: &'a mut MessageBuilder,
+    mut template: &str,
+    user: UserId,
+    members: u64,
+) -> &'a mut MessageBuilder {
+    while let Some(start) = template.find('{') {
+        let (text, rest) = template.split_at(start);
+        mb.push(text);
+
+        if let Some(rest) = rest.strip_prefix("{user}") {
+            mb.mention(&user);
+            template = rest;
+        } else if let Some(rest) = rest.strip_prefix("{members}") {
+            mb.push(members.to_string());
+            template = rest;
+        } else {
+            mb.push("{");
+            template = &rest[1..];
+        }
+    }
+
+    mb.push(template)
+}
+
+fn welcome_body<E>(template: &str, user: UserId, members: u64) -> MessageBody<E> {
+    MessageBody::rich(|mb| render(mb, template, user, members)).ping_users(vec![user])
+}
+
+pub async fn greet(ctx: &Context, member: &Member) -> Result {
+    let storage = storage::get(ctx).await.context("Missing storage context")?;
+    let guild::Guild { welcome, .. } = storage.guild(member.guild_id).await?;
+
+    let Some(guild::Welcome { channel, template }) = welcome else {
+        return Ok(());
+    };
+
+    let members = capability::member_count(ctx, member.guild_id)
+        .await
+        .context("Error getting guild member count")?;
+    let body: MessageBody<Infallible> = welcome_body(&template, member.user.id, members);
+
+    ChannelId::new(channel)
+        .send_message(&ctx.http, CreateMessage::new().build_with(body))
+        .await
+        .context("Error sending welcome message")?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct WelcomeCommand {
+    name: String,
+}
+
+impl From<&CommandOpts> for WelcomeCommand {
+    fn from(opts: &CommandOpts) -> Self {
+        Self {
+            name: format!("{}welcome", opts.command_base),
+        }
+    }
+}
+
+impl WelcomeCommand {
+    async fn set<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let channel = visitor.visit_channel("channel")?.required()?.id;
+        let template = visitor.visit_string("message")?.required()?.to_owned();
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        storage
+            .update_guild(gid, |g| {
+                g.welcome = Some(guild::Welcome {
+                    channel: channel.get(),
+                    template,
+                });
+            })
+            .await
+            .context("Error saving welcome message")?;
+
+        botlog::record(
+            ctx,
+            gid,
+            botlog::Entry::new("Welcome message set", visitor.user().id)
+                .with_target(channel.mention().to_string()),
+        )
+        .await;
+
+        Ok(responder
+            .create_message(
+                Message::rich(|mb| {
+                    mb.push("New members will now be welcomed in ")
+                        .channel(channel)
+                        .push(".")
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+
+    async fn preview<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+        let user = visitor.user();
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let guild::Guild { welcome, .. } = storage.guild(gid).await?;
+
+        let Some(guild::Welcome { template, .. }) = welcome else {
+            return Err(responder
+                .create_message(
+                    Message::plain("No welcome message is set up for this server.").ephemeral(true),
+                )
+                .await
+                .context("Error sending missing welcome error")?
+                .into_err("No welcome message configured"));
+        };
+
+        let members = capability::member_count(ctx, gid)
+            .await
+            .context("Error getting guild member count")?;
+
+        Ok(responder
+            .create_message(
+                Message::from(welcome_body(&template, user.id, members)).ephemeral(true),
+            )
+            .await
+            .context("Error sending welcome preview")?
+            .into())
+    }
+
+    async fn disable<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (gid, _memb) = visitor.guild()?.required()?;
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        let prev = storage
+            .update_guild(gid, |g| g.welcome.take())
+            .await
+            .context("Error saving welcome message")?;
+
+        if prev.is_some() {
+            botlog::record(
+                ctx,
+                gid,
+                botlog::Entry::new("Welcome messages disabled", visitor.user().id),
+            )
+            .await;
+        }
+
+        Ok(responder
+            .create_message(
+                Message::plain(if prev.is_some() {
+                    "Welcome messages disabled."
+                } else {
+                    "Welcome messages were already disabled."
+                })
+                .ephemeral(true),
+            )
+            .await
+            .context("Error sending confirmation")?
+            .into())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Schema> for WelcomeCommand {
+    fn register_global(&self) -> CommandInfo {
+        CommandInfo::build_slash(&self.name, "Configure messages for new members", |a| {
+            a.build_subcmd("set", "Set the welcome message and channel", |a| {
+                a.channel("channel", "The channel to post in", true, [
+                    ChannelType::Text,
+                ])
+                .string(
+                    "message",
+                    "Message template ({user} = new member, {members} = member count)",
+                    true,
+                    1..=2000,
+                )
+            })
+            .build_subcmd("preview", "Preview the welcome message", id)
+            .build_subcmd("disable", "Stop sending welcome messages", id)
+        })
+        .unwrap()
+        .can_dm(false)
+    }
+
+    async fn respond<'a>(
+        &self,
+        ctx: &Context,
+        visitor: &mut CommandVisitor<'_>,
+        responder: CommandResponder<'_, 'a>,
+    ) -> CommandResult<'a> {
+        let (_gid, memb) = visitor.guild()?.required()?;
+
+        if !memb
+            .permissions
+            .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD))
+        {
+            return Err(responder
+                .create_message(
+                    Message::plain("You need the Manage Server permission to do that.")
+                        .ephemeral(true),
+                )
+                .await
+                .context("Error sending permission error")?
+                .into_err("Missing Manage Server permission"));
+        }
+
+        match *visitor.visit_subcmd()? {
+            ["set"] => self.set(ctx, visitor, responder).await,
+            ["preview"] => self.preview(ctx, visitor, responder).await,
+            ["disable"] => self.disable(ctx, visitor, responder).await,
+            [..] => unreachable!(), // TODO: visitor should handle this
+        }
+    }
+}
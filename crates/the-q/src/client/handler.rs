@@ -1,21 +1,78 @@
-use paracord::interaction;
+use paracord::{client, interaction, presence::Presence};
 use serenity::{
-    model::{application::Interaction, gateway::Ready},
+    gateway::ShardStageUpdateEvent,
+    model::{
+        application::Interaction,
+        channel::{Message, Reaction},
+        event::ResumedEvent,
+        gateway::Ready,
+        guild::{Guild, Member, ScheduledEvent},
+        id::{ChannelId, GuildId, MessageId, ShardId},
+        voice::VoiceState,
+    },
     prelude::*,
 };
+use tokio::sync::OnceCell;
 
-use super::commands;
+use super::{approval, commands, metrics::Metrics, status, voice};
 use crate::prelude::*;
 
+const FAILURE_LOG_CAP: usize = 100;
+
 pub struct Handler {
     registry: interaction::Registry<commands::Schema>,
+    // Shards may reconnect (and re-send Ready) many times over the bot's
+    // lifetime, so one-time setup is tracked here rather than redone per event
+    registry_init: OnceCell<()>,
+    resumed_guilds: Mutex<HashSet<GuildId>>,
+    feeds: commands::FeedPoller,
+    presence: Presence,
+    // Auto-reply rules can only see message content with the privileged
+    // intent, so they are skipped entirely without it
+    auto_reply: bool,
 }
 
 impl Handler {
-    pub fn new_rc(command_opts: &commands::CommandOpts) -> Arc<Self> {
-        Arc::new(Self {
-            registry: interaction::Registry::new(commands::handlers(command_opts)),
-        })
+    pub fn new_rc(
+        command_opts: &commands::CommandOpts,
+        metrics: &Arc<Metrics>,
+    ) -> Result<Arc<Self>> {
+        let failures = Arc::new(commands::MemoryFailureLog::new(FAILURE_LOG_CAP));
+        let mut registry = interaction::Registry::new(commands::handlers(command_opts, &failures)?)
+            .with_failures(failures)
+            .with_observer(Arc::clone(metrics) as Arc<_>)
+            .with_version(env!("CARGO_PKG_VERSION"))
+            .with_intents(command_opts.intents())
+            .with_aliases(Arc::new(commands::AliasLookup::from(command_opts)))
+            .with_similarity(command_opts.sim_weights())
+            .with_min_similarity(command_opts.min_similarity())
+            .with_dry_run(command_opts.dry_run());
+
+        if command_opts.reconcile_guild_commands() {
+            registry = registry
+                .with_guild_reconciliation(command_opts.keep_guild_commands().iter().cloned());
+        }
+
+        if let Some(opts) = command_opts.dispatch() {
+            registry = registry.with_dispatch(opts);
+        }
+
+        if let Some(url) = command_opts.support_url() {
+            registry = registry.with_support_url(url.clone());
+        }
+
+        if command_opts.confirm_deletes() {
+            registry = registry.with_sync_gate(Arc::new(approval::OwnerApproval));
+        }
+
+        Ok(Arc::new(Self {
+            registry,
+            registry_init: OnceCell::new(),
+            resumed_guilds: Mutex::default(),
+            feeds: commands::FeedPoller::from(command_opts),
+            presence: Presence::new(command_opts.presence()),
+            auto_reply: command_opts.message_content(),
+        }))
     }
 }
 
@@ -30,6 +87,7 @@ async fn handler(method: &'static str, f: impl Future<Output = Result>) {
 #[async_trait]
 impl serenity::client::EventHandler for Handler {
     async fn interaction_create(&self, ctx: Context, int: Interaction) {
+        let ctx = client::Context::from(ctx);
         match int {
             Interaction::Ping(_) => (),
 
@@ -42,11 +100,190 @@ impl serenity::client::EventHandler for Handler {
         }
     }
 
-    async fn ready(&self, ctx: Context, _: Ready) {
+    async fn guild_create(&self, ctx: Context, guild: Guild, _: Option<bool>) {
+        let ctx = client::Context::from(ctx);
+        handler("guild_create", async move {
+            self.registry
+                .reconcile_guild(&ctx, guild.id)
+                .await
+                .map(|_| ())
+        })
+        .await;
+    }
+
+    async fn guild_member_addition(&self, ctx: Context, member: Member) {
+        let ctx = client::Context::from(ctx);
+        handler("guild_member_addition", async move {
+            commands::greet_member(&ctx, &member).await
+        })
+        .await;
+    }
+
+    async fn guild_scheduled_event_update(&self, ctx: Context, event: ScheduledEvent) {
+        let ctx = client::Context::from(ctx);
+        handler("guild_scheduled_event_update", async move {
+            commands::update_scheduled_event(&ctx, &event).await
+        })
+        .await;
+    }
+
+    async fn guild_scheduled_event_delete(&self, ctx: Context, event: ScheduledEvent) {
+        let ctx = client::Context::from(ctx);
+        handler("guild_scheduled_event_delete", async move {
+            commands::delete_scheduled_event(&ctx, &event).await
+        })
+        .await;
+    }
+
+    async fn message(&self, ctx: Context, message: Message) {
+        let ctx = client::Context::from(ctx);
+        handler("message", async move {
+            if commands::anti_spam(&ctx, &message).await? {
+                return Ok(());
+            }
+
+            if let Err(err) = commands::award_message_xp(&ctx, &message).await {
+                warn!(?err, "Error awarding message XP");
+            }
+
+            if self.auto_reply && commands::auto_reply(&ctx, &message).await? {
+                return Ok(());
+            }
+
+            commands::auto_thread(&ctx, &message).await
+        })
+        .await;
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        let ctx = client::Context::from(ctx);
+        self.registry.offer_reaction(&reaction);
+
+        if !commands::is_starboard_reaction(&reaction.emoji) {
+            return;
+        }
+
+        handler("reaction_add", async move {
+            let Reaction {
+                guild_id,
+                channel_id,
+                message_id,
+                ..
+            } = reaction;
+            commands::update_starboard(&ctx, guild_id, channel_id, message_id).await
+        })
+        .await;
+    }
+
+    async fn reaction_remove(&self, ctx: Context, reaction: Reaction) {
+        let ctx = client::Context::from(ctx);
+        if !commands::is_starboard_reaction(&reaction.emoji) {
+            return;
+        }
+
+        handler("reaction_remove", async move {
+            let Reaction {
+                guild_id,
+                channel_id,
+                message_id,
+                ..
+            } = reaction;
+            commands::update_starboard(&ctx, guild_id, channel_id, message_id).await
+        })
+        .await;
+    }
+
+    async fn reaction_remove_all(&self, ctx: Context, channel: ChannelId, message: MessageId) {
+        let ctx = client::Context::from(ctx);
+        handler("reaction_remove_all", async move {
+            let guild = channel
+                .to_channel(&ctx)
+                .await
+                .context("Error fetching reaction channel")?
+                .guild()
+                .map(|c| c.guild_id);
+            commands::update_starboard(&ctx, guild, channel, message).await
+        })
+        .await;
+    }
+
+    async fn reaction_remove_emoji(&self, ctx: Context, reaction: Reaction) {
+        let ctx = client::Context::from(ctx);
+        if !commands::is_starboard_reaction(&reaction.emoji) {
+            return;
+        }
+
+        handler("reaction_remove_emoji", async move {
+            let Reaction {
+                guild_id,
+                channel_id,
+                message_id,
+                ..
+            } = reaction;
+            commands::update_starboard(&ctx, guild_id, channel_id, message_id).await
+        })
+        .await;
+    }
+
+    async fn ready(&self, gateway: Context, ready: Ready) {
         handler("ready", async move {
-            self.registry.init(&ctx).await?;
+            let ctx = client::Context::from(&gateway);
+            let shard = ready.shard.map_or(ShardId(0), |s| s.id);
+            let status = status::get(&ctx).await.context("Missing status context")?;
+            status.ready(shard, ready.guilds.len()).await;
+            info!(%shard, guilds = ready.guilds.len(), "Shard ready");
+            self.presence.ready(&gateway);
+
+            self.registry_init
+                .get_or_try_init(|| self.registry.init(&ctx))
+                .await?;
+
+            let guilds: Vec<_> = {
+                let mut resumed = self.resumed_guilds.lock().await;
+                ready
+                    .guilds
+                    .iter()
+                    .map(|g| g.id)
+                    .filter(|&g| resumed.insert(g))
+                    .collect()
+            };
+            commands::resume_polls(&ctx, guilds.iter().copied()).await?;
+            commands::resume_events(&ctx, guilds.iter().copied()).await?;
+            commands::resume_reminders(&ctx, guilds).await?;
+            self.feeds.start(&ctx);
+            Ok(())
+        })
+        .await;
+    }
+
+    async fn resume(&self, ctx: Context, _: ResumedEvent) {
+        debug!(shard = %ctx.shard_id, "Shard resumed");
+        self.presence.resume(&ctx);
+    }
+
+    async fn shard_stage_update(&self, ctx: Context, event: ShardStageUpdateEvent) {
+        let ctx = client::Context::from(ctx);
+        handler("shard_stage_update", async move {
+            let ShardStageUpdateEvent { new, old, shard_id } = event;
+            let status = status::get(&ctx).await.context("Missing status context")?;
+
+            if status.stage_update(shard_id, new).await {
+                warn!(shard = %shard_id, %old, %new, "Shard is reconnecting frequently");
+            } else {
+                debug!(shard = %shard_id, %old, %new, "Shard stage changed");
+            }
+
             Ok(())
         })
         .await;
     }
+
+    async fn voice_state_update(&self, ctx: Context, _: Option<VoiceState>, new: VoiceState) {
+        let ctx = client::Context::from(ctx);
+        handler("voice_state_update", async move {
+            let voice = voice::get(&ctx).await.context("Missing voice context")?;
+            voice.state_update(&ctx, &new).await
+        })
+        .await;
+    }
 }
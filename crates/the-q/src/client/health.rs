@@ -0,0 +1,137 @@
+use std::{convert::Infallible, net::SocketAddr};
+
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+use super::{
+    metrics::Metrics,
+    status::{ShardReport, Status},
+};
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, clap::Args)]
+pub struct HealthOpts {
+    /// Address to serve the health check and Prometheus metrics endpoints
+    /// on, if any
+    #[arg(long, env)]
+    health_addr: Option<SocketAddr>,
+}
+
+async fn respond(
+    status: &Status,
+    metrics: &Metrics,
+    request: &Request<Incoming>,
+) -> Response<Full<Bytes>> {
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/health") => health(status).await,
+        (&Method::GET, "/metrics") => prometheus(metrics, &status.report().await),
+        _ => {
+            let mut res = Response::new(Full::default());
+            *res.status_mut() = StatusCode::NOT_FOUND;
+            res
+        },
+    }
+}
+
+fn prometheus(metrics: &Metrics, shards: &[ShardReport]) -> Response<Full<Bytes>> {
+    let mut res = Response::new(Full::new(metrics.render(shards).into()));
+    res.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    res
+}
+
+async fn health(status: &Status) -> Response<Full<Bytes>> {
+    let shards = status.report().await;
+    let healthy = !shards.is_empty() && shards.iter().all(ShardReport::healthy);
+
+    let body = serde_json::json!({
+        "healthy": healthy,
+        "uptime_secs": status.uptime().as_secs(),
+        "shards": shards
+            .iter()
+            .map(|s| {
+                serde_json::json!({
+                    "id": s.id,
+                    "stage": s.stage.to_string(),
+                    "latency_ms": s.latency.map(|l| l.as_millis()),
+                    "ready_secs": s.ready_for.map(|r| r.as_secs()),
+                    "guilds": s.guilds,
+                    "reconnects": s.reconnects,
+                    "storming": s.storming,
+                })
+            })
+            .collect::<Vec<_>>(),
+    });
+
+    let mut res = Response::new(Full::new(body.to_string().into()));
+    *res.status_mut() = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    res.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    res
+}
+
+async fn serve(listener: TcpListener, status: Arc<Status>, metrics: Arc<Metrics>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                warn!(%err, "Error accepting health check connection");
+                continue;
+            },
+        };
+
+        let status = Arc::clone(&status);
+        let metrics = Arc::clone(&metrics);
+        tokio::spawn(async move {
+            let svc = service_fn(|req| {
+                let status = Arc::clone(&status);
+                let metrics = Arc::clone(&metrics);
+                async move { Ok::<_, Infallible>(respond(&status, &metrics, &req).await) }
+            });
+
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), svc)
+                .await
+            {
+                debug!(%err, "Error serving health check");
+            }
+        });
+    }
+}
+
+impl HealthOpts {
+    /// Start serving the health check and metrics endpoints in the
+    /// background, if an address was configured
+    pub async fn spawn(&self, status: &Arc<Status>, metrics: &Arc<Metrics>) -> Result {
+        let Some(addr) = self.health_addr else {
+            return Ok(());
+        };
+
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Error binding health check endpoint to {addr}"))?;
+        info!(%addr, "Serving health check and metrics endpoints");
+
+        tokio::spawn(
+            serve(listener, Arc::clone(status), Arc::clone(metrics))
+                .instrument(info_span!("health")),
+        );
+
+        Ok(())
+    }
+}
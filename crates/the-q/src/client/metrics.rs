@@ -0,0 +1,269 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use paracord::interaction::observe::{Handled, Observer, Outcome};
+
+use super::status::ShardReport;
+use crate::prelude::*;
+
+/// Upper bounds, in seconds, of the interaction latency histogram buckets
+const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 15.0, 30.0];
+
+const OUTCOMES: [Outcome; 3] = [Outcome::Ok, Outcome::Error, Outcome::Rejected];
+
+/// Counters and latencies for a single interaction handler
+#[derive(Debug, Default)]
+struct Series {
+    outcomes: [u64; OUTCOMES.len()],
+    /// Non-cumulative counts for each bucket, followed by the `+Inf` bucket
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    latency: Duration,
+}
+
+impl Series {
+    fn record(&mut self, outcome: Outcome, latency: Duration) {
+        self.outcomes[match outcome {
+            Outcome::Ok => 0,
+            Outcome::Error => 1,
+            Outcome::Rejected => 2,
+        }] += 1;
+
+        let secs = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&b| secs <= b)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.latency += latency;
+    }
+}
+
+/// The result of an attempt to join a voice channel
+#[derive(Debug, Clone, Copy)]
+pub enum VoiceResult {
+    Ok,
+    Timeout,
+    Error,
+}
+
+/// Process-wide counters exported in the Prometheus text format
+#[derive(Debug, Default)]
+pub struct Metrics {
+    interactions: Mutex<BTreeMap<(&'static str, String), Series>>,
+    voice_ok: AtomicU64,
+    voice_timeout: AtomicU64,
+    voice_error: AtomicU64,
+    storage_read_errors: AtomicU64,
+    storage_write_errors: AtomicU64,
+}
+
+impl Observer for Metrics {
+    fn handled(&self, event: &Handled) {
+        self.interactions
+            .lock()
+            .unwrap()
+            .entry((event.kind, event.name.clone()))
+            .or_default()
+            .record(event.outcome, event.latency);
+    }
+}
+
+/// Escape a string for use as a label value
+fn label(s: &str) -> String {
+    s.replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+fn header(out: &mut String, name: &str, ty: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} {ty}")
+}
+
+impl Metrics {
+    /// Count an attempt to join a voice channel
+    pub fn voice_join(&self, res: VoiceResult) {
+        match res {
+            VoiceResult::Ok => &self.voice_ok,
+            VoiceResult::Timeout => &self.voice_timeout,
+            VoiceResult::Error => &self.voice_error,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a failed read from the data directory
+    pub fn storage_read_error(&self) { self.storage_read_errors.fetch_add(1, Ordering::Relaxed); }
+
+    /// Count a failed write to the data directory
+    pub fn storage_write_error(&self) { self.storage_write_errors.fetch_add(1, Ordering::Relaxed); }
+
+    fn render_interactions(&self, out: &mut String) -> fmt::Result {
+        let interactions = self.interactions.lock().unwrap();
+
+        header(
+            out,
+            "theq_interactions_total",
+            "counter",
+            "Interactions handled, by handler and outcome",
+        )?;
+        for ((kind, name), series) in &*interactions {
+            for (outcome, count) in OUTCOMES.iter().zip(series.outcomes) {
+                writeln!(
+                    out,
+                    "theq_interactions_total{{kind=\"{kind}\",name=\"{}\",outcome=\"{outcome}\"}} \
+                     {count}",
+                    label(name),
+                )?;
+            }
+        }
+
+        header(
+            out,
+            "theq_interaction_duration_seconds",
+            "histogram",
+            "Time taken to handle and respond to interactions",
+        )?;
+        for ((kind, name), series) in &*interactions {
+            let labels = format!("kind=\"{kind}\",name=\"{}\"", label(name));
+            let mut total = 0;
+            for (i, count) in series.buckets.iter().enumerate() {
+                total += count;
+                let le = LATENCY_BUCKETS
+                    .get(i)
+                    .map_or_else(|| "+Inf".into(), ToString::to_string);
+                writeln!(
+                    out,
+                    "theq_interaction_duration_seconds_bucket{{{labels},le=\"{le}\"}} {total}"
+                )?;
+            }
+            writeln!(
+                out,
+                "theq_interaction_duration_seconds_sum{{{labels}}} {}",
+                series.latency.as_secs_f64()
+            )?;
+            writeln!(
+                out,
+                "theq_interaction_duration_seconds_count{{{labels}}} {total}"
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn render_to(&self, out: &mut String, shards: &[ShardReport]) -> fmt::Result {
+        self.render_interactions(out)?;
+
+        header(
+            out,
+            "theq_gateway_reconnects_total",
+            "counter",
+            "Gateway reconnects, by shard",
+        )?;
+        for shard in shards {
+            writeln!(
+                out,
+                "theq_gateway_reconnects_total{{shard=\"{}\"}} {}",
+                shard.id, shard.reconnects
+            )?;
+        }
+
+        header(
+            out,
+            "theq_voice_connections_total",
+            "counter",
+            "Attempts to join a voice channel, by result",
+        )?;
+        for (result, count) in [
+            ("ok", &self.voice_ok),
+            ("timeout", &self.voice_timeout),
+            ("error", &self.voice_error),
+        ] {
+            writeln!(
+                out,
+                "theq_voice_connections_total{{result=\"{result}\"}} {}",
+                count.load(Ordering::Relaxed)
+            )?;
+        }
+
+        header(
+            out,
+            "theq_storage_errors_total",
+            "counter",
+            "Errors reading or writing persistent data, by operation",
+        )?;
+        for (op, count) in [
+            ("read", &self.storage_read_errors),
+            ("write", &self.storage_write_errors),
+        ] {
+            writeln!(
+                out,
+                "theq_storage_errors_total{{op=\"{op}\"}} {}",
+                count.load(Ordering::Relaxed)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self, shards: &[ShardReport]) -> String {
+        let mut out = String::new();
+        self.render_to(&mut out, shards)
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use paracord::interaction::observe::{Handled, Observer, Outcome};
+
+    use super::{label, Metrics, VoiceResult};
+
+    fn handled(name: &str, outcome: Outcome, ms: u64) -> Handled {
+        Handled {
+            kind: "command",
+            name: name.into(),
+            outcome,
+            latency: Duration::from_millis(ms),
+        }
+    }
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::default();
+        metrics.handled(&handled("ping", Outcome::Ok, 20));
+        metrics.handled(&handled("ping", Outcome::Ok, 300));
+        metrics.handled(&handled("ping", Outcome::Rejected, 60_000));
+        metrics.voice_join(VoiceResult::Timeout);
+        metrics.storage_write_error();
+
+        let out = metrics.render(&[]);
+        let lines: Vec<_> = out.lines().collect();
+        for line in [
+            r#"theq_interactions_total{kind="command",name="ping",outcome="ok"} 2"#,
+            r#"theq_interactions_total{kind="command",name="ping",outcome="rejected"} 1"#,
+            r#"theq_interaction_duration_seconds_bucket{kind="command",name="ping",le="0.05"} 1"#,
+            r#"theq_interaction_duration_seconds_bucket{kind="command",name="ping",le="0.5"} 2"#,
+            r#"theq_interaction_duration_seconds_bucket{kind="command",name="ping",le="30"} 2"#,
+            r#"theq_interaction_duration_seconds_bucket{kind="command",name="ping",le="+Inf"} 3"#,
+            r#"theq_interaction_duration_seconds_sum{kind="command",name="ping"} 60.32"#,
+            r#"theq_interaction_duration_seconds_count{kind="command",name="ping"} 3"#,
+            r#"theq_voice_connections_total{result="timeout"} 1"#,
+            r#"theq_storage_errors_total{op="write"} 1"#,
+            r#"theq_storage_errors_total{op="read"} 0"#,
+        ] {
+            assert!(lines.contains(&line), "Missing {line:?} in:\n{out}");
+        }
+
+        assert_eq!(label("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+}
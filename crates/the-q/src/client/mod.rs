@@ -1,33 +1,124 @@
-use serenity::{model::gateway::GatewayIntents, Client};
+use backup::BackupInit;
+use prefs::PrefsInit;
+use reload::ReloadInit;
+use serenity::Client;
 use songbird::SerenityInit;
+use status::StatusInit;
+use storage::StorageInit;
+use voice::VoiceInit;
 
 use crate::{prelude::*, util::DebugShim};
 
+mod approval;
+mod backup;
 mod commands;
 mod handler;
+mod health;
+mod metrics;
+mod prefs;
+mod reload;
+mod status;
+mod storage;
+mod voice;
 
-#[derive(Debug, clap::Args)]
+pub use reload::{ConfigSource, ReloadReport};
+pub use storage::StorageOpts;
+
+#[derive(Debug, Clone, PartialEq, clap::Args)]
 pub struct ClientOpts {
     /// The Discord API token to use
-    #[arg(long, env)]
-    discord_token: DebugShim<String>,
+    #[arg(long, env, required_unless_present = "migrate_only")]
+    discord_token: Option<DebugShim<String>>,
+
+    #[command(flatten)]
+    storage: storage::StorageOpts,
+
+    #[command(flatten)]
+    backup: backup::BackupOpts,
 
     #[command(flatten)]
     commands: commands::CommandOpts,
+
+    #[command(flatten)]
+    shards: status::ShardOpts,
+
+    #[command(flatten)]
+    health: health::HealthOpts,
+}
+
+impl ClientOpts {
+    /// The options for the bot's persistent data directory
+    #[inline]
+    #[must_use]
+    pub fn storage(&self) -> &StorageOpts { &self.storage }
 }
 
-pub async fn build(opts: ClientOpts) -> Result<Client> {
+pub struct Bot {
+    pub client: Client,
+    pub reloader: Arc<reload::Reloader>,
+    shards: status::ShardOpts,
+    storage: Arc<storage::Storage>,
+}
+
+impl Bot {
+    /// Connect all configured shards and run until they disconnect
+    pub async fn start(&mut self) -> Result<(), serenity::Error> {
+        self.shards.start(&mut self.client).await
+    }
+
+    /// Save any data still held in memory, before exiting
+    pub async fn flush(&self) { commands::flush_xp(&self.storage).await; }
+}
+
+/// Apply any pending data migrations without connecting to Discord
+pub async fn migrate(opts: StorageOpts) -> Result {
+    storage::Storage::open(opts, Arc::default())
+        .await
+        .map(drop)
+}
+
+pub async fn build(opts: ClientOpts, config: Box<dyn ConfigSource>) -> Result<Bot> {
     let ClientOpts {
         discord_token,
+        storage,
+        backup,
         commands,
+        shards,
+        health,
     } = opts;
 
-    let intents = GatewayIntents::non_privileged(); // TODO
-    let handler = handler::Handler::new_rc(&commands);
+    let discord_token = discord_token.context("No Discord token provided")?;
+    let intents = commands.intents();
+    let metrics = Arc::new(metrics::Metrics::default());
+    let handler = handler::Handler::new_rc(&commands, &metrics)?;
+    let status = Arc::new(status::Status::new());
+    let storage = Arc::new(storage::Storage::open(storage, Arc::clone(&metrics)).await?);
+    let voice = Arc::new(voice::Voice::new(Arc::clone(&metrics)));
+    let backups = Arc::new(backup::Backups::new(backup, Arc::clone(&storage)));
+    let prefs = Arc::new(prefs::Prefs::new(Arc::clone(&storage)));
+    let reloader = Arc::new(reload::Reloader::new(config, Arc::clone(&prefs)));
 
-    Client::builder(discord_token.0, intents)
+    let client = Client::builder(discord_token.0, intents)
         .event_handler_arc(handler)
         .register_songbird()
+        .register_voice(voice)
+        .register_storage(Arc::clone(&storage))
+        .register_backups(Arc::clone(&backups))
+        .register_prefs(prefs)
+        .register_reloader(Arc::clone(&reloader))
+        .register_status(Arc::clone(&status))
         .await
-        .context("Error constructing Serenity client")
+        .context("Error constructing Serenity client")?;
+
+    status.set_manager(Arc::clone(&client.shard_manager));
+    health.spawn(&status, &metrics).await?;
+    backups.spawn();
+    commands::spawn_xp_flush(Arc::clone(&storage));
+
+    Ok(Bot {
+        client,
+        reloader,
+        shards,
+        storage,
+    })
 }
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use chrono::{FixedOffset, Offset, Utc};
+use paracord::client::Context;
+use serenity::{
+    client::ClientBuilder,
+    model::id::UserId,
+    prelude::TypeMapKey,
+};
+use tokio::sync::RwLock;
+
+use super::storage::Storage;
+use crate::{prelude::*, proto::user};
+
+/// Largest accepted UTC offset, in minutes
+const MAX_OFFSET: i32 = 14 * 60;
+
+/// The preferences of a single user
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserPrefs {
+    /// The user's UTC offset, if they have set one
+    pub timezone: Option<FixedOffset>,
+    /// The user's preferred locale, if they have set one
+    pub locale: Option<String>,
+    /// Whether the user has opted out of unprompted DMs from the bot
+    pub dm_opt_out: bool,
+    /// Whether the user has opted out of voice activity tracking
+    pub voice_stats_opt_out: bool,
+}
+
+impl UserPrefs {
+    /// Get the user's UTC offset, or UTC itself if they have not set one
+    #[must_use]
+    pub fn utc_offset(&self) -> FixedOffset { self.timezone.unwrap_or_else(|| Utc.fix()) }
+}
+
+impl From<user::Prefs> for UserPrefs {
+    fn from(prefs: user::Prefs) -> Self {
+        let user::Prefs {
+            utc_offset,
+            locale,
+            dm_opt_out,
+            voice_stats_opt_out,
+        } = prefs;
+
+        Self {
+            timezone: utc_offset.and_then(|o| FixedOffset::east_opt(o.checked_mul(60)?)),
+            locale: (!locale.is_empty()).then_some(locale),
+            dm_opt_out,
+            voice_stats_opt_out,
+        }
+    }
+}
+
+impl From<UserPrefs> for user::Prefs {
+    fn from(prefs: UserPrefs) -> Self {
+        let UserPrefs {
+            timezone,
+            locale,
+            dm_opt_out,
+            voice_stats_opt_out,
+        } = prefs;
+
+        Self {
+            utc_offset: timezone.map(|t| t.local_minus_utc() / 60),
+            locale: locale.unwrap_or_default(),
+            dm_opt_out,
+            voice_stats_opt_out,
+        }
+    }
+}
+
+/// Parse a UTC offset of the form `UTC`, `+5`, `UTC-8`, `+05:30` or `-0330`
+#[must_use]
+pub fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let s = s.trim();
+    let s = ["UTC", "GMT", "utc", "gmt"]
+        .into_iter()
+        .find_map(|p| s.strip_prefix(p))
+        .unwrap_or(s)
+        .trim_start();
+
+    if s.is_empty() {
+        return Some(Utc.fix());
+    }
+
+    let (sign, s) = match s.as_bytes()[0] {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+
+    let (hours, mins) = match s.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if s.len() > 2 => s.split_at(s.len() - 2),
+        None => (s, "0"),
+    };
+
+    if hours.is_empty()
+        || mins.is_empty()
+        || !hours
+            .bytes()
+            .chain(mins.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let hours: i32 = hours.parse().ok()?;
+    let mins: i32 = mins.parse().ok()?;
+    if mins >= 60 {
+        return None;
+    }
+
+    let offset = hours * 60 + mins;
+    if offset > MAX_OFFSET {
+        return None;
+    }
+
+    FixedOffset::east_opt(sign * offset * 60)
+}
+
+/// Storage-backed service for reading and updating user preferences
+#[derive(Debug)]
+pub struct Prefs {
+    storage: Arc<Storage>,
+    cache: RwLock<HashMap<UserId, UserPrefs>>,
+}
+
+struct PrefsKey;
+
+impl TypeMapKey for PrefsKey {
+    type Value = Arc<Prefs>;
+}
+
+pub trait PrefsInit {
+    #[must_use]
+    fn register_prefs(self, prefs: Arc<Prefs>) -> Self;
+}
+
+impl PrefsInit for ClientBuilder {
+    fn register_prefs(self, prefs: Arc<Prefs>) -> Self { self.type_map_insert::<PrefsKey>(prefs) }
+}
+
+pub async fn get(ctx: &Context) -> Option<Arc<Prefs>> {
+    ctx.data.read().await.get::<PrefsKey>().map(Arc::clone)
+}
+
+impl Prefs {
+    pub fn new(storage: Arc<Storage>) -> Self {
+        Self {
+            storage,
+            cache: RwLock::default(),
+        }
+    }
+
+    /// Get the preferences of the given user
+    pub async fn get(&self, user: UserId) -> Result<UserPrefs> {
+        if let Some(prefs) = self.cache.read().await.get(&user) {
+            return Ok(prefs.clone());
+        }
+
+        let prefs: UserPrefs = self
+            .storage
+            .user(user)
+            .await
+            .context("Error loading user preferences")?
+            .prefs
+            .unwrap_or_default()
+            .into();
+
+        self.cache.write().await.insert(user, prefs.clone());
+        Ok(prefs)
+    }
+
+    /// Discard all cached preferences, forcing them to be re-read from storage
+    pub async fn clear_cache(&self) { self.cache.write().await.clear(); }
+
+    /// Atomically apply `f` to the preferences of the given user, returning
+    /// the updated preferences
+    pub async fn update(
+        &self,
+        user: UserId,
+        f: impl FnOnce(&mut UserPrefs) + Send,
+    ) -> Result<UserPrefs> {
+        // Holding the cache lock across the write keeps a concurrent get()
+        // from caching a stale value
+        let mut cache = self.cache.write().await;
+        let prefs = self
+            .storage
+            .update_user(user, |u| {
+                let mut prefs = u.prefs.take().unwrap_or_default().into();
+                f(&mut prefs);
+                u.prefs = Some(prefs.clone().into());
+                prefs
+            })
+            .await
+            .context("Error saving user preferences")?;
+
+        cache.insert(user, prefs.clone());
+        Ok(prefs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_offset;
+
+    #[test]
+    fn offsets() {
+        let mins = |s| parse_offset(s).map(|o| o.local_minus_utc() / 60);
+
+        assert_eq!(mins("UTC"), Some(0));
+        assert_eq!(mins("+5"), Some(300));
+        assert_eq!(mins("UTC-8"), Some(-480));
+        assert_eq!(mins("gmt +05:30"), Some(330));
+        assert_eq!(mins("-0330"), Some(-210));
+        assert_eq!(mins("+14:00"), Some(840));
+        assert_eq!(mins("+14:01"), None);
+        assert_eq!(mins("+5:60"), None);
+        assert_eq!(mins("5"), None);
+        assert_eq!(mins("+"), None);
+        assert_eq!(mins("+a"), None);
+    }
+}
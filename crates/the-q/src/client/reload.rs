@@ -0,0 +1,95 @@
+use paracord::client::Context;
+use serenity::{
+    client::ClientBuilder,
+    prelude::TypeMapKey,
+};
+use tokio::sync::Mutex;
+
+use super::prefs::Prefs;
+use crate::prelude::*;
+
+/// The outcome of a configuration reload
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+    /// Settings whose new values were applied
+    pub applied: Vec<&'static str>,
+    /// Environment variables that changed but will only take effect after a
+    /// restart
+    pub restart: Vec<String>,
+}
+
+impl ReloadReport {
+    /// Log the outcome of the reload
+    pub fn log(&self) {
+        info!(applied = ?self.applied, "Configuration reloaded");
+
+        if !self.restart.is_empty() {
+            warn!(settings = ?self.restart, "Some changed settings require a restart");
+        }
+    }
+}
+
+/// A source of process-level configuration, such as command-line options and
+/// `.env` files, that can be re-read while the bot is running
+pub trait ConfigSource: fmt::Debug + Send + 'static {
+    /// Re-read the configuration, applying any changes that are safe to make
+    /// at runtime and recording them in `report`
+    ///
+    /// # Errors
+    /// This method returns an error if the new configuration could not be
+    /// read or is invalid, in which case the running configuration is left
+    /// unchanged.
+    fn reload(&mut self, report: &mut ReloadReport) -> Result;
+}
+
+/// Service for re-reading configuration and flushing cached settings
+#[derive(Debug)]
+pub struct Reloader {
+    source: Mutex<Box<dyn ConfigSource>>,
+    prefs: Arc<Prefs>,
+}
+
+struct ReloaderKey;
+
+impl TypeMapKey for ReloaderKey {
+    type Value = Arc<Reloader>;
+}
+
+pub trait ReloadInit {
+    #[must_use]
+    fn register_reloader(self, reloader: Arc<Reloader>) -> Self;
+}
+
+impl ReloadInit for ClientBuilder {
+    fn register_reloader(self, reloader: Arc<Reloader>) -> Self {
+        self.type_map_insert::<ReloaderKey>(reloader)
+    }
+}
+
+pub async fn get(ctx: &Context) -> Option<Arc<Reloader>> {
+    ctx.data.read().await.get::<ReloaderKey>().map(Arc::clone)
+}
+
+impl Reloader {
+    pub fn new(source: Box<dyn ConfigSource>, prefs: Arc<Prefs>) -> Self {
+        Self {
+            source: Mutex::new(source),
+            prefs,
+        }
+    }
+
+    /// Re-read all configuration and apply whatever can be changed without a
+    /// restart
+    pub async fn reload(&self) -> Result<ReloadReport> {
+        let mut report = ReloadReport::default();
+        self.source.lock().await.reload(&mut report)?;
+
+        self.prefs.clear_cache().await;
+        report.applied.push("cached user preferences");
+
+        super::commands::invalidate_guild_caches(None).await;
+        report.applied.push("cached guild settings");
+
+        Ok(report)
+    }
+}
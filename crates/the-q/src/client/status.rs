@@ -0,0 +1,199 @@
+use std::time::{Duration, Instant};
+
+use paracord::client::Context;
+use serenity::{
+    client::ClientBuilder,
+    gateway::{ConnectionStage, ShardManager},
+    model::id::ShardId,
+    prelude::TypeMapKey,
+};
+use tokio::sync::{OnceCell, RwLock};
+
+use crate::prelude::*;
+
+/// Number of stage changes within [`STORM_WINDOW`] after which a shard is
+/// considered to be in a reconnect storm
+const STORM_THRESHOLD: usize = 5;
+const STORM_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+enum ShardMode {
+    /// Use the shard count recommended by Discord
+    #[default]
+    Auto,
+    /// Use the number of shards given by --shard-count
+    Fixed,
+}
+
+#[derive(Debug, Clone, PartialEq, clap::Args)]
+pub struct ShardOpts {
+    /// Whether to use the recommended shard count or a fixed one
+    #[arg(long, env, default_value = "auto")]
+    shard_mode: ShardMode,
+
+    /// The number of shards to start when using fixed sharding
+    #[arg(long, env, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    shard_count: u32,
+}
+
+impl ShardOpts {
+    /// Connect all shards of the given client and run until they disconnect
+    pub async fn start(&self, client: &mut serenity::Client) -> Result<(), serenity::Error> {
+        match self.shard_mode {
+            ShardMode::Auto => client.start_autosharded().await,
+            ShardMode::Fixed => client.start_shards(self.shard_count).await,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ShardState {
+    stage: ConnectionStage,
+    ready_at: Option<Instant>,
+    guilds: usize,
+    reconnects: u32,
+    recent_changes: Vec<Instant>,
+}
+
+impl Default for ShardState {
+    fn default() -> Self {
+        Self {
+            stage: ConnectionStage::Disconnected,
+            ready_at: None,
+            guilds: 0,
+            reconnects: 0,
+            recent_changes: vec![],
+        }
+    }
+}
+
+/// A point-in-time report of the state of a single shard
+#[derive(Debug, Clone)]
+pub struct ShardReport {
+    pub id: u32,
+    pub stage: ConnectionStage,
+    pub latency: Option<Duration>,
+    pub ready_for: Option<Duration>,
+    pub guilds: usize,
+    pub reconnects: u32,
+    pub storming: bool,
+}
+
+impl ShardReport {
+    #[inline]
+    pub fn healthy(&self) -> bool { self.stage == ConnectionStage::Connected }
+}
+
+/// Tracks the connection state of every shard run by this process
+#[derive(Debug)]
+pub struct Status {
+    started: Instant,
+    manager: OnceCell<Arc<ShardManager>>,
+    shards: RwLock<BTreeMap<u32, ShardState>>,
+}
+
+struct StatusKey;
+
+impl TypeMapKey for StatusKey {
+    type Value = Arc<Status>;
+}
+
+pub trait StatusInit {
+    #[must_use]
+    fn register_status(self, status: Arc<Status>) -> Self;
+}
+
+impl StatusInit for ClientBuilder {
+    fn register_status(self, status: Arc<Status>) -> Self {
+        self.type_map_insert::<StatusKey>(status)
+    }
+}
+
+pub async fn get(ctx: &Context) -> Option<Arc<Status>> {
+    ctx.data.read().await.get::<StatusKey>().map(Arc::clone)
+}
+
+impl Status {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            manager: OnceCell::new(),
+            shards: RwLock::default(),
+        }
+    }
+
+    pub fn set_manager(&self, manager: Arc<ShardManager>) {
+        if self.manager.set(manager).is_err() {
+            warn!("Shard manager registered twice");
+        }
+    }
+
+    #[inline]
+    pub fn uptime(&self) -> Duration { self.started.elapsed() }
+
+    /// Record a shard receiving a Ready event
+    pub async fn ready(&self, shard: ShardId, guilds: usize) {
+        let mut shards = self.shards.write().await;
+        let state = shards.entry(shard.0).or_default();
+
+        state.stage = ConnectionStage::Connected;
+        state.ready_at = Some(Instant::now());
+        state.guilds = guilds;
+    }
+
+    /// Record a change in connection stage for a shard, returning true if the
+    /// shard is reconnecting unusually often
+    pub async fn stage_update(&self, shard: ShardId, next: ConnectionStage) -> bool {
+        let now = Instant::now();
+        let mut shards = self.shards.write().await;
+        let state = shards.entry(shard.0).or_default();
+
+        if state.stage == ConnectionStage::Connected && next != ConnectionStage::Connected {
+            state.reconnects += 1;
+            state.ready_at = None;
+        }
+
+        state.stage = next;
+        state
+            .recent_changes
+            .retain(|t| now.duration_since(*t) < STORM_WINDOW);
+        state.recent_changes.push(now);
+
+        state.recent_changes.len() >= STORM_THRESHOLD
+    }
+
+    /// Get a report of the current state of every known shard
+    pub async fn report(&self) -> Vec<ShardReport> {
+        let latencies: HashMap<u32, Option<Duration>> = match self.manager.get() {
+            Some(m) => m
+                .runners
+                .lock()
+                .await
+                .iter()
+                .map(|(id, info)| (id.0, info.latency))
+                .collect(),
+            None => HashMap::new(),
+        };
+
+        let now = Instant::now();
+        self.shards
+            .read()
+            .await
+            .iter()
+            .map(|(&id, state)| ShardReport {
+                id,
+                stage: state.stage,
+                latency: latencies.get(&id).copied().flatten(),
+                ready_for: state.ready_at.map(|t| now.duration_since(t)),
+                guilds: state.guilds,
+                reconnects: state.reconnects,
+                storming: state
+                    .recent_changes
+                    .iter()
+                    .filter(|t| now.duration_since(**t) < STORM_WINDOW)
+                    .count()
+                    >= STORM_THRESHOLD,
+            })
+            .collect()
+    }
+}
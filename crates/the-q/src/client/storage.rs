@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+
+use prost::Message;
+use paracord::client::Context;
+use serenity::{
+    client::ClientBuilder,
+    model::id::{GuildId, UserId},
+    prelude::TypeMapKey,
+};
+use tokio::sync::Mutex;
+
+use super::metrics::Metrics;
+use crate::{
+    prelude::*,
+    proto::{guild, user},
+};
+
+mod migrate;
+
+#[derive(Debug, Clone, PartialEq, clap::Args)]
+pub struct StorageOpts {
+    /// Directory to store persistent bot data in
+    #[arg(long, env, default_value = "etc/data")]
+    data_dir: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct Storage {
+    dir: PathBuf,
+    lock: Mutex<()>,
+    metrics: Arc<Metrics>,
+}
+
+struct StorageKey;
+
+impl TypeMapKey for StorageKey {
+    type Value = Arc<Storage>;
+}
+
+pub trait StorageInit {
+    #[must_use]
+    fn register_storage(self, storage: Arc<Storage>) -> Self;
+}
+
+impl StorageInit for ClientBuilder {
+    fn register_storage(self, storage: Arc<Storage>) -> Self {
+        self.type_map_insert::<StorageKey>(storage)
+    }
+}
+
+pub async fn get(ctx: &Context) -> Option<Arc<Storage>> {
+    ctx.data.read().await.get::<StorageKey>().map(Arc::clone)
+}
+
+impl Storage {
+    /// Open the data directory, applying any pending migrations
+    ///
+    /// Any errors reading or writing stored data are counted in `metrics`.
+    pub async fn open(opts: StorageOpts, metrics: Arc<Metrics>) -> Result<Self> {
+        let StorageOpts { data_dir } = opts;
+
+        migrate::run(data_dir.clone())
+            .await
+            .context("Error migrating data directory")?;
+
+        Ok(Self {
+            dir: data_dir,
+            lock: Mutex::default(),
+            metrics,
+        })
+    }
+
+    fn guild_path(&self, gid: GuildId) -> PathBuf {
+        self.dir.join("guilds").join(format!("{gid}.pb"))
+    }
+
+    fn user_path(&self, uid: UserId) -> PathBuf { self.dir.join("users").join(format!("{uid}.pb")) }
+
+    async fn read_file<T: Message + Default>(path: PathBuf) -> Result<T> {
+        match tokio::fs::read(&path).await {
+            Ok(b) => T::decode(&*b).with_context(|| format!("Error decoding {path:?}")),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+            Err(e) => Err(e).with_context(|| format!("Error reading {path:?}")),
+        }
+    }
+
+    async fn write_file<T: Message>(path: PathBuf, val: &T) -> Result {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Error creating directory {parent:?}"))?;
+        }
+
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, val.encode_to_vec())
+            .await
+            .with_context(|| format!("Error writing {tmp:?}"))?;
+        tokio::fs::rename(&tmp, &path)
+            .await
+            .with_context(|| format!("Error replacing {path:?}"))
+    }
+
+    async fn read<T: Message + Default>(&self, path: PathBuf) -> Result<T> {
+        Self::read_file(path)
+            .await
+            .inspect_err(|_| self.metrics.storage_read_error())
+    }
+
+    async fn write<T: Message>(&self, path: PathBuf, val: &T) -> Result {
+        Self::write_file(path, val)
+            .await
+            .inspect_err(|_| self.metrics.storage_write_error())
+    }
+
+    /// Load the stored data for the given guild, or the default if none has
+    /// been stored yet
+    pub async fn guild(&self, gid: GuildId) -> Result<guild::Guild> {
+        let _lock = self.lock.lock().await;
+        self.read(self.guild_path(gid)).await
+    }
+
+    /// Atomically apply `f` to the stored data for the given guild
+    pub async fn update_guild<T>(
+        &self,
+        gid: GuildId,
+        f: impl FnOnce(&mut guild::Guild) -> T + Send,
+    ) -> Result<T> {
+        let _lock = self.lock.lock().await;
+        let path = self.guild_path(gid);
+        let mut data = self.read(path.clone()).await?;
+        let ret = f(&mut data);
+        self.write(path, &data).await?;
+        Ok(ret)
+    }
+
+    /// List the IDs of all guilds with stored data
+    pub async fn guild_ids(&self) -> Result<Vec<GuildId>> {
+        let dir = self.dir.join("guilds");
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(e) => e,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e).with_context(|| format!("Error reading {dir:?}")),
+        };
+
+        let mut ids = vec![];
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .with_context(|| format!("Error reading {dir:?}"))?
+        {
+            let name = entry.file_name();
+            let id = name
+                .to_str()
+                .and_then(|n| n.strip_suffix(".pb"))
+                .and_then(|n| n.parse().ok())
+                .filter(|&n| n != 0);
+
+            if let Some(id) = id {
+                ids.push(GuildId::new(id));
+            }
+        }
+
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Load the stored data for the given user, or the default if none has
+    /// been stored yet
+    pub async fn user(&self, uid: UserId) -> Result<user::User> {
+        let _lock = self.lock.lock().await;
+        self.read(self.user_path(uid)).await
+    }
+
+    /// Atomically apply `f` to the stored data for the given user
+    pub async fn update_user<T>(
+        &self,
+        uid: UserId,
+        f: impl FnOnce(&mut user::User) -> T + Send,
+    ) -> Result<T> {
+        let _lock = self.lock.lock().await;
+        let path = self.user_path(uid);
+        let mut data = self.read(path.clone()).await?;
+        let ret = f(&mut data);
+        self.write(path, &data).await?;
+        Ok(ret)
+    }
+}
@@ -0,0 +1,168 @@
+//! Forward-only schema migrations for the data directory
+//!
+//! The current schema version is recorded in a file at the root of the data
+//! directory.  Before a migration runs a marker file is written next to it,
+//! and it is only removed once the new version has been recorded, so a
+//! migration interrupted partway through leaves the directory flagged as
+//! dirty and the bot refuses to start until an operator intervenes.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::prelude::*;
+
+const VERSION_FILE: &str = "schema_version";
+const DIRTY_FILE: &str = "schema_version.dirty";
+
+struct Migration {
+    name: &'static str,
+    run: fn(&Path) -> Result,
+}
+
+/// Every known migration, in order.  Applying the migration at index `i`
+/// upgrades the data directory from version `i` to version `i + 1`.
+///
+/// Entries must never be reordered or removed once released.
+const MIGRATIONS: &[Migration] = &[Migration {
+    name: "create guild and user directories",
+    run: create_layout,
+}];
+
+fn create_layout(dir: &Path) -> Result {
+    for sub in ["guilds", "users"] {
+        let path = dir.join(sub);
+        fs::create_dir_all(&path).with_context(|| format!("Error creating directory {path:?}"))?;
+    }
+
+    Ok(())
+}
+
+/// The schema version this build of the bot expects
+#[inline]
+fn latest() -> u32 {
+    MIGRATIONS
+        .len()
+        .try_into()
+        .unwrap_or_else(|_| unreachable!())
+}
+
+fn read_opt(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(Some(s)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Error reading {path:?}")),
+    }
+}
+
+fn write_atomic(path: &Path, contents: &str) -> Result {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, contents).with_context(|| format!("Error writing {tmp:?}"))?;
+    fs::rename(&tmp, path).with_context(|| format!("Error replacing {path:?}"))
+}
+
+/// Read the current schema version of the data directory, failing if it was
+/// left dirty or is newer than this build understands
+fn check(dir: &Path) -> Result<u32> {
+    let dirty = dir.join(DIRTY_FILE);
+    if let Some(target) = read_opt(&dirty)? {
+        bail!(
+            "Data directory {dir:?} was left partially migrated while upgrading to schema version \
+             {}.  Restore it from a backup, or repair it manually and delete {dirty:?}, before \
+             starting the bot.",
+            target.trim(),
+        );
+    }
+
+    let path = dir.join(VERSION_FILE);
+    let version = read_opt(&path)?.map_or(Ok(0), |s| {
+        s.trim()
+            .parse()
+            .with_context(|| format!("Invalid schema version in {path:?}"))
+    })?;
+
+    ensure!(
+        version <= latest(),
+        "Data directory {dir:?} has schema version {version}, but this build only supports up to \
+         version {}.  Refusing to start, as an older build could corrupt newer data.",
+        latest(),
+    );
+
+    Ok(version)
+}
+
+/// Apply all pending migrations to the given data directory, returning its
+/// final schema version
+fn run_blocking(dir: &Path) -> Result<u32> {
+    fs::create_dir_all(dir).with_context(|| format!("Error creating data directory {dir:?}"))?;
+
+    let current = check(dir)?;
+    let dirty = dir.join(DIRTY_FILE);
+    let version_path = dir.join(VERSION_FILE);
+
+    for (from, Migration { name, run }) in (current..).zip(&MIGRATIONS[current as usize..]) {
+        let to = from + 1;
+        info!(from, to, name, "Applying data migration");
+
+        write_atomic(&dirty, &to.to_string())?;
+        run(dir).with_context(|| {
+            format!(
+                "Data migration {to} ({name}) failed; {dirty:?} has been left in place to prevent \
+                 the bot from starting against partially migrated data"
+            )
+        })?;
+        write_atomic(&version_path, &to.to_string())?;
+        fs::remove_file(&dirty).with_context(|| format!("Error removing {dirty:?}"))?;
+    }
+
+    Ok(latest())
+}
+
+/// Bring the given data directory up to date with this build's schema
+pub async fn run(dir: PathBuf) -> Result {
+    let version = tokio::task::spawn_blocking(move || run_blocking(&dir))
+        .await
+        .context("Data migration task panicked")??;
+    debug!(version, "Data directory is up to date");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{latest, run_blocking, DIRTY_FILE, VERSION_FILE};
+
+    #[test]
+    fn migrate() {
+        let dir = std::env::temp_dir().join(format!("the-q-migrate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(run_blocking(&dir).unwrap(), latest());
+        assert!(dir.join("guilds").is_dir());
+        assert_eq!(
+            fs::read_to_string(dir.join(VERSION_FILE)).unwrap(),
+            latest().to_string()
+        );
+        assert!(!dir.join(DIRTY_FILE).exists());
+
+        // Running again is a no-op
+        assert_eq!(run_blocking(&dir).unwrap(), latest());
+
+        fs::write(dir.join(DIRTY_FILE), "1").unwrap();
+        let err = run_blocking(&dir).unwrap_err().to_string();
+        assert!(err.contains("partially migrated"), "{err}");
+        fs::remove_file(dir.join(DIRTY_FILE)).unwrap();
+
+        fs::write(dir.join(VERSION_FILE), (latest() + 1).to_string()).unwrap();
+        let err = run_blocking(&dir).unwrap_err().to_string();
+        assert!(err.contains("only supports up to"), "{err}");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,423 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use paracord::client::Context;
+use serenity::{
+    client::ClientBuilder,
+    model::{
+        id::{ChannelId, GuildId, UserId},
+        voice::VoiceState,
+    },
+    prelude::TypeMapKey,
+};
+use songbird::{Call, Songbird, SongbirdKey};
+use tokio::sync::{
+    oneshot::{self, error::TryRecvError},
+    Mutex, OwnedMutexGuard,
+};
+
+use super::{
+    commands,
+    metrics::{Metrics, VoiceResult},
+    prefs, storage,
+};
+use crate::{prelude::*, proto::guild};
+
+// TODO: make these configurable
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+/// How many days of voice activity are kept for voice stats
+pub const ACTIVITY_RETENTION_DAYS: i64 = 90;
+const SECS_PER_DAY: i64 = 86_400;
+
+/// Get the number of days since the Unix epoch of the given time
+#[must_use]
+pub fn epoch_day(t: DateTime<Utc>) -> i64 { t.timestamp().div_euclid(SECS_PER_DAY) }
+
+/// Split the time between two instants into the seconds spent in each UTC
+/// day, keyed by [`epoch_day`]
+#[must_use]
+pub fn split_days(start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<(i64, u64)> {
+    let end = end.timestamp();
+    let mut t = start.timestamp();
+    let mut days = vec![];
+
+    while t < end {
+        let day = t.div_euclid(SECS_PER_DAY);
+        let next = ((day + 1) * SECS_PER_DAY).min(end);
+        days.push((day, next.abs_diff(t)));
+        t = next;
+    }
+
+    days
+}
+
+/// Credit the time between two instants to a user's voice activity,
+/// discarding any days older than the retention period
+pub fn record_activity(
+    activity: &mut guild::VoiceActivity,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) {
+    for (day, secs) in split_days(start, end) {
+        *activity.days.entry(day).or_default() += secs;
+    }
+
+    let oldest = epoch_day(end) - ACTIVITY_RETENTION_DAYS;
+    activity.days.retain(|&d, _| d > oldest);
+}
+
+#[derive(Debug)]
+pub enum JoinError {
+    Timeout,
+    Join(songbird::error::JoinError),
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "Timed out joining voice channel"),
+            Self::Join(e) => write!(f, "Error joining voice channel: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Timeout => None,
+            Self::Join(e) => Some(e),
+        }
+    }
+}
+
+/// Exclusive access to the voice connection of a single guild
+#[derive(Debug)]
+#[must_use]
+pub struct GuildLock(
+    #[expect(dead_code, reason = "This field is used as a drop guard")] OwnedMutexGuard<()>,
+);
+
+#[derive(Debug, Default)]
+struct GuildVoice {
+    lock: Arc<Mutex<()>>,
+    follow: Option<UserId>,
+    idle: Option<oneshot::Sender<Infallible>>,
+}
+
+#[derive(Debug)]
+pub struct Voice {
+    guilds: Mutex<HashMap<GuildId, GuildVoice>>,
+    /// When each user currently in voice joined
+    sessions: Mutex<HashMap<(GuildId, UserId), DateTime<Utc>>>,
+    metrics: Arc<Metrics>,
+}
+
+struct VoiceKey;
+
+impl TypeMapKey for VoiceKey {
+    type Value = Arc<Voice>;
+}
+
+pub trait VoiceInit {
+    #[must_use]
+    fn register_voice(self, voice: Arc<Voice>) -> Self;
+}
+
+impl VoiceInit for ClientBuilder {
+    fn register_voice(self, voice: Arc<Voice>) -> Self { self.type_map_insert::<VoiceKey>(voice) }
+}
+
+pub async fn get(ctx: &Context) -> Option<Arc<Voice>> {
+    ctx.data.read().await.get::<VoiceKey>().map(Arc::clone)
+}
+
+/// Get the voice manager registered with the client, which is only present
+/// when connected to the gateway
+pub async fn songbird(ctx: &Context) -> Option<Arc<Songbird>> {
+    ctx.data.read().await.get::<SongbirdKey>().map(Arc::clone)
+}
+
+impl Voice {
+    /// Construct a new voice tracker counting connection attempts in
+    /// `metrics`
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self {
+            guilds: Mutex::default(),
+            sessions: Mutex::default(),
+            metrics,
+        }
+    }
+
+    async fn guild_lock(&self, gid: GuildId) -> Arc<Mutex<()>> {
+        Arc::clone(&self.guilds.lock().await.entry(gid).or_default().lock)
+    }
+
+    /// Acquire exclusive access to the given guild's voice connection, or
+    /// return `None` if another command is already using it
+    pub async fn try_lock(&self, gid: GuildId) -> Option<GuildLock> {
+        self.guild_lock(gid)
+            .await
+            .try_lock_owned()
+            .ok()
+            .map(GuildLock)
+    }
+
+    /// Join the given voice channel, reusing the current call if the bot is
+    /// already connected to it
+    ///
+    /// This cancels any pending idle disconnect for the guild.
+    pub async fn join(
+        &self,
+        sb: &Songbird,
+        _lock: &GuildLock,
+        gid: GuildId,
+        chan: ChannelId,
+    ) -> Result<Arc<Mutex<Call>>, JoinError> {
+        if let Some(state) = self.guilds.lock().await.get_mut(&gid) {
+            state.idle = None;
+        }
+
+        if let Some(call) = sb.get(gid) {
+            if call.lock().await.current_channel() == Some(chan.into()) {
+                return Ok(call);
+            }
+        }
+
+        join_timeout(sb, &self.metrics, gid, chan).await
+    }
+
+    /// Move the guild's call along with the given user whenever their voice
+    /// channel changes
+    pub async fn follow(&self, gid: GuildId, user: UserId) {
+        self.guilds.lock().await.entry(gid).or_default().follow = Some(user);
+    }
+
+    /// Disconnect from the guild's call if it is not used again within the
+    /// idle timeout
+    pub async fn start_idle(self: &Arc<Self>, sb: Arc<Songbird>, gid: GuildId) {
+        let (tx, mut rx) = oneshot::channel();
+
+        self.guilds.lock().await.entry(gid).or_default().idle = Some(tx);
+
+        let this = Arc::clone(self);
+        tokio::task::spawn(
+            async move {
+                tokio::select! {
+                    () = tokio::time::sleep(IDLE_TIMEOUT) => (),
+                    _ = &mut rx => return,
+                }
+
+                // Hold the guild lock so a concurrent join can't set up a call
+                // for us to tear down, then make sure this timer wasn't
+                // cancelled or replaced while we waited for it
+                let lock = this.guild_lock(gid).await;
+                let _guard = lock.lock().await;
+                if !matches!(rx.try_recv(), Err(TryRecvError::Empty)) {
+                    return;
+                }
+
+                debug!("Voice connection idle, leaving call");
+                this.reset(gid).await;
+                sb.remove(gid)
+                    .await
+                    .map_err(|err| error!(%err, "Error leaving idle call"))
+                    .ok();
+            }
+            .instrument(error_span!(parent: None, "voice_idle", %gid)),
+        );
+    }
+
+    async fn reset(&self, gid: GuildId) {
+        if let Some(state) = self.guilds.lock().await.get_mut(&gid) {
+            state.follow = None;
+            state.idle = None;
+        }
+    }
+
+    /// List the users in voice in the given guild, along with when they
+    /// joined
+    pub async fn sessions(&self, gid: GuildId) -> Vec<(UserId, DateTime<Utc>)> {
+        self.sessions
+            .lock()
+            .await
+            .iter()
+            .filter(|&(&(g, _), _)| g == gid)
+            .map(|(&(_, u), &t)| (u, t))
+            .collect()
+    }
+
+    /// Start or end the voice session of the user whose state changed,
+    /// awarding XP for it and saving the time spent if the guild and user
+    /// have voice stats enabled
+    async fn track_activity(&self, ctx: &Context, gid: GuildId, state: &VoiceState) -> Result {
+        if state.member.as_ref().is_some_and(|m| m.user.bot) {
+            return Ok(());
+        }
+
+        let afk = ctx
+            .cache
+            .guild(gid)
+            .and_then(|g| g.afk_metadata.as_ref().map(|a| a.afk_channel_id));
+        let active = state.channel_id.is_some_and(|c| Some(c) != afk);
+        let key = (gid, state.user_id);
+        let now = Utc::now();
+
+        let start = {
+            let mut sessions = self.sessions.lock().await;
+
+            if active {
+                // Moving between channels continues the current session
+                sessions.entry(key).or_insert(now);
+                return Ok(());
+            }
+
+            sessions.remove(&key)
+        };
+
+        let Some(start) = start else {
+            return Ok(());
+        };
+
+        if let Err(err) = commands::award_voice_xp(ctx, gid, state.user_id, start, now).await {
+            warn!(?err, "Error awarding voice XP");
+        }
+
+        let prefs = prefs::get(ctx).await.context("Missing prefs context")?;
+        if prefs.get(state.user_id).await?.voice_stats_opt_out {
+            return Ok(());
+        }
+
+        let storage = storage::get(ctx).await.context("Missing storage context")?;
+        if !storage.guild(gid).await?.voice_stats {
+            return Ok(());
+        }
+
+        storage
+            .update_guild(gid, |g| {
+                // Tracking may have been disabled since the check above
+                if g.voice_stats {
+                    let activity = g.voice_activity.entry(state.user_id.get()).or_default();
+                    record_activity(activity, start, now);
+                }
+            })
+            .await
+            .context("Error saving voice activity")
+    }
+
+    pub async fn state_update(&self, ctx: &Context, state: &VoiceState) -> Result {
+        let Some(gid) = state.guild_id else {
+            return Ok(());
+        };
+
+        if let Err(err) = self.track_activity(ctx, gid, state).await {
+            warn!(?err, "Error tracking voice activity");
+        }
+
+        if state.user_id == ctx.cache.current_user().id {
+            if state.channel_id.is_none() {
+                self.reset(gid).await;
+            }
+
+            return Ok(());
+        }
+
+        let following = self
+            .guilds
+            .lock()
+            .await
+            .get(&gid)
+            .is_some_and(|s| s.follow == Some(state.user_id));
+
+        if !following {
+            return Ok(());
+        }
+
+        let sb = songbird(ctx)
+            .await
+            .context("Missing songbird context")?;
+        let Some(call) = sb.get(gid) else {
+            return Ok(());
+        };
+
+        let Some(chan) = state.channel_id else {
+            debug!("Followed user left voice, leaving call");
+            self.reset(gid).await;
+            return call
+                .lock()
+                .await
+                .leave()
+                .await
+                .context("Error leaving call");
+        };
+
+        let curr = call.lock().await.current_channel();
+        if curr.is_none() || curr == Some(chan.into()) {
+            return Ok(());
+        }
+
+        debug!(%chan, "Following user to new voice channel");
+        join_timeout(&sb, &self.metrics, gid, chan)
+            .await
+            .context("Error following user")
+            .map(|_| ())
+    }
+}
+
+async fn join_timeout(
+    sb: &Songbird,
+    metrics: &Metrics,
+    gid: GuildId,
+    chan: ChannelId,
+) -> Result<Arc<Mutex<Call>>, JoinError> {
+    let res = match tokio::time::timeout(JOIN_TIMEOUT, sb.join(gid, chan)).await {
+        Ok(Ok(c)) => Ok(c),
+        Ok(Err(e)) => Err(JoinError::Join(e)),
+        Err(_) => Err(JoinError::Timeout),
+    };
+
+    metrics.voice_join(match res {
+        Ok(_) => VoiceResult::Ok,
+        Err(JoinError::Timeout) => VoiceResult::Timeout,
+        Err(JoinError::Join(_)) => VoiceResult::Error,
+    });
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, TimeDelta, Utc};
+
+    use super::{epoch_day, record_activity, split_days, ACTIVITY_RETENTION_DAYS};
+    use crate::proto::guild;
+
+    fn at(secs: i64) -> DateTime<Utc> { DateTime::from_timestamp(secs, 0).unwrap() }
+
+    #[test]
+    fn days() {
+        assert_eq!(epoch_day(at(0)), 0);
+        assert_eq!(epoch_day(at(-1)), -1);
+        assert_eq!(epoch_day(at(86_400 * 3 + 5)), 3);
+
+        assert_eq!(split_days(at(100), at(160)), [(0, 60)]);
+        assert_eq!(split_days(at(86_000), at(86_400 * 2 + 10)), [
+            (0, 400),
+            (1, 86_400),
+            (2, 10)
+        ]);
+        assert!(split_days(at(50), at(50)).is_empty());
+        assert!(split_days(at(50), at(10)).is_empty());
+    }
+
+    #[test]
+    fn activity() {
+        let mut activity = guild::VoiceActivity::default();
+        record_activity(&mut activity, at(86_000), at(86_500));
+        record_activity(&mut activity, at(86_600), at(86_700));
+        assert_eq!(activity.days, [(0, 400), (1, 200)].into());
+
+        let later = at(86_400) + TimeDelta::days(ACTIVITY_RETENTION_DAYS);
+        record_activity(&mut activity, later, later + TimeDelta::seconds(30));
+        assert_eq!(activity.days, [(ACTIVITY_RETENTION_DAYS + 1, 30)].into());
+    }
+}
@@ -1,12 +1,34 @@
-use tracing_subscriber::{layer::Layered, EnvFilter};
+use std::ffi::{OsStr, OsString};
 
-use crate::prelude::*;
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches};
+use tracing_subscriber::{layer::Layered, reload, EnvFilter, Registry};
 
-#[derive(Debug, clap::Parser)]
+use crate::{
+    client::{ConfigSource, ReloadReport},
+    prelude::*,
+};
+
+const DEFAULT_LOG_FILTER: &str = "info";
+const LOG_FILTER_VAR: &str = "RUST_LOG";
+
+/// Environment files to load, in order of decreasing priority
+const ENV_FILES: [&str; 3] = [
+    ".env.local",
+    if cfg!(debug_assertions) {
+        ".env.dev"
+    } else {
+        ".env.prod"
+    },
+    ".env",
+];
+
+type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+#[derive(Debug, Clone, clap::Parser)]
 #[command(version, author, about)]
 struct Opts {
     /// Log filter, using env_logger-like syntax
-    #[arg(long, env = "RUST_LOG")]
+    #[arg(long, env = LOG_FILTER_VAR)]
     log_filter: Option<String>,
 
     /// Grafana Loki endpoint to use
@@ -17,6 +39,11 @@ struct Opts {
     #[arg(short = 'j', long, env)]
     threads: Option<usize>,
 
+    /// Apply any pending data migrations, then exit without connecting to
+    /// Discord.  No Discord token is required in this mode.
+    #[arg(long)]
+    migrate_only: bool,
+
     #[command(flatten)]
     client: crate::client::ClientOpts,
 }
@@ -38,20 +65,150 @@ fn init_subscriber<
     S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
 >(
     log_filter: impl AsRef<str>,
-    f: impl FnOnce(Layered<EnvFilter, tracing_subscriber::Registry>) -> S,
-) where
+    f: impl FnOnce(Layered<reload::Layer<EnvFilter, Registry>, Registry>) -> S,
+) -> LogFilterHandle
+where
     Layered<tracing_subscriber::fmt::Layer<S>, S>: Into<tracing::Dispatch>,
 {
     let log_filter = log_filter.as_ref();
-    let reg = tracing_subscriber::registry().with(
+    let (filter, handle) = reload::Layer::new(
         EnvFilter::try_new(log_filter)
             .unwrap_or_else(|e| init_error!("Invalid log filter {log_filter:?}: {e}")),
     );
 
-    f(reg)
+    f(tracing_subscriber::registry().with(filter))
         .with(fmt_layer())
         .try_init()
         .unwrap_or_else(|e| init_error!("Error initializing logger: {e}"));
+
+    handle
+}
+
+/// The variables sourced from `.env` files at startup, kept so the files can
+/// be re-read without modifying the process environment
+#[derive(Debug)]
+struct EnvFiles {
+    /// Variables already set in the process environment before the files
+    /// were loaded, which take priority over the files
+    external: HashSet<OsString>,
+    /// The values loaded from the files at startup
+    loaded: HashMap<String, String>,
+}
+
+impl EnvFiles {
+    /// Read the variables defined by all environment files, keeping only the
+    /// highest-priority definition of each
+    fn read_files() -> Result<Vec<(String, String)>> {
+        let mut seen = HashSet::new();
+        let mut vars = vec![];
+
+        for path in ENV_FILES {
+            let iter = match dotenvy::from_filename_iter(path) {
+                Ok(i) => i,
+                Err(dotenvy::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).with_context(|| format!("Error opening {path:?}")),
+            };
+
+            for var in iter {
+                let (key, val) = var.with_context(|| format!("Error loading env from {path:?}"))?;
+                if seen.insert(key.clone()) {
+                    vars.push((key, val));
+                }
+            }
+
+            trace!("Loaded env from {path:?}");
+        }
+
+        Ok(vars)
+    }
+
+    /// Load all environment files into the process environment, without
+    /// overriding any variables already set in it
+    ///
+    /// This must be called before any other threads are started.
+    fn load() -> Result<Self> {
+        let external: HashSet<_> = std::env::vars_os().map(|(k, _)| k).collect();
+        let loaded: HashMap<_, _> = Self::read_files()?
+            .into_iter()
+            .filter(|(key, _)| !external.contains(OsStr::new(key)))
+            .collect();
+
+        for (key, val) in &loaded {
+            std::env::set_var(key, val);
+        }
+
+        Ok(Self { external, loaded })
+    }
+
+    /// Returns true if the given variable was set in the process environment
+    /// before the environment files were loaded
+    fn is_external(&self, key: &str) -> bool { self.external.contains(OsStr::new(key)) }
+
+    /// Re-read all environment files, returning the variables they now define
+    /// that are not overridden by the process environment
+    fn reread(&self) -> Result<HashMap<String, String>> {
+        Ok(Self::read_files()?
+            .into_iter()
+            .filter(|(key, _)| !self.is_external(key))
+            .collect())
+    }
+
+    /// List the variables whose values in `vars` differ from those loaded at
+    /// startup
+    fn changed<'a>(&'a self, vars: &'a HashMap<String, String>) -> BTreeSet<&'a str> {
+        self.loaded
+            .keys()
+            .chain(vars.keys())
+            .filter(|key| self.loaded.get(*key) != vars.get(*key))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// The process-level configuration of the running bot
+#[derive(Debug)]
+struct Config {
+    env: EnvFiles,
+    opts: Opts,
+    /// Whether the log filter was given on the command line or in the process
+    /// environment, and so cannot be changed by editing the environment files
+    log_filter_fixed: bool,
+    log_filter: LogFilterHandle,
+}
+
+impl ConfigSource for Config {
+    fn reload(&mut self, report: &mut ReloadReport) -> Result {
+        let vars = self.env.reread().context("Error reloading .env files")?;
+
+        if !self.log_filter_fixed {
+            let log_filter = vars.get(LOG_FILTER_VAR);
+
+            if log_filter != self.opts.log_filter.as_ref() {
+                let filter = log_filter.map_or(DEFAULT_LOG_FILTER, String::as_str);
+                let filter = EnvFilter::try_new(filter)
+                    .with_context(|| format!("Invalid log filter {filter:?}"))?;
+                self.log_filter
+                    .reload(filter)
+                    .context("Error updating log filter")?;
+                self.opts.log_filter = log_filter.cloned();
+                report.applied.push("log filter");
+            }
+        }
+
+        // Compare against the values the bot was started with, so settings
+        // that have not been applied yet are reported on every reload
+        let cmd = Opts::command();
+        let settings: HashSet<_> = cmd.get_arguments().filter_map(clap::Arg::get_env).collect();
+        report.restart.extend(
+            self.env
+                .changed(&vars)
+                .into_iter()
+                .filter(|&key| key != LOG_FILTER_VAR && settings.contains(OsStr::new(key)))
+                .map(str::to_owned),
+        );
+
+        Ok(())
+    }
 }
 
 #[inline]
@@ -60,27 +217,12 @@ pub fn main() {
         tracing::subscriber::set_default(tracing_subscriber::registry().with(fmt_layer()));
     let span = error_span!("boot").entered();
 
-    [
-        ".env.local",
-        if cfg!(debug_assertions) {
-            ".env.dev"
-        } else {
-            ".env.prod"
-        },
-        ".env",
-    ]
-    .into_iter()
-    .try_for_each(|p| match dotenvy::from_filename(p) {
-        Ok(p) => {
-            trace!("Loaded env from {p:?}");
-            Ok(())
-        },
-        Err(dotenvy::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
-        Err(e) => Err(e).with_context(|| format!("Error loading env from {p:?}")),
-    })
-    .unwrap_or_else(|e| init_error!("Error loading .env files: {e:?}"));
+    let env = EnvFiles::load().unwrap_or_else(|e| init_error!("Error loading .env files: {e:?}"));
 
-    let opts: Opts = clap::Parser::parse();
+    let matches = Opts::command().get_matches();
+    let opts = Opts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let log_filter_fixed = matches.value_source("log_filter") == Some(ValueSource::CommandLine)
+        || env.is_external(LOG_FILTER_VAR);
     drop(span);
     let span = error_span!("boot", ?opts).entered();
 
@@ -92,9 +234,9 @@ pub fn main() {
         })
         .unwrap_or_else(|e| init_error!("Error getting system hostname: {e}"));
 
-    let log_filter = opts.log_filter.as_deref().unwrap_or("info");
+    let log_filter = opts.log_filter.as_deref().unwrap_or(DEFAULT_LOG_FILTER);
 
-    let loki_task = if let Some(endpoint) = &opts.loki_endpoint {
+    let (log_filter, loki_task) = if let Some(endpoint) = &opts.loki_endpoint {
         let (layer, task) = tracing_loki::layer(
             endpoint.clone(),
             [
@@ -107,11 +249,9 @@ pub fn main() {
         )
         .unwrap_or_else(|err| init_error!(%err, "Error initializing Loki exporter"));
 
-        init_subscriber(log_filter, |r| r.with(layer));
-        Some(task)
+        (init_subscriber(log_filter, |r| r.with(layer)), Some(task))
     } else {
-        init_subscriber(log_filter, |r| r);
-        None
+        (init_subscriber(log_filter, |r| r), None)
     };
 
     drop((span, tmp_logger));
@@ -158,13 +298,20 @@ pub fn main() {
 
     loki_task.map(|t| rt.spawn(t));
 
-    std::process::exit(match rt.block_on(run(opts)) {
-        Ok(()) => 0,
-        Err(e) => {
-            error!("{e:?}");
-            1
+    std::process::exit(
+        match rt.block_on(run(Config {
+            env,
+            opts,
+            log_filter_fixed,
+            log_filter,
+        })) {
+            Ok(()) => 0,
+            Err(e) => {
+                error!("{e:?}");
+                1
+            },
         },
-    });
+    );
 }
 
 enum StopType<S> {
@@ -173,16 +320,19 @@ enum StopType<S> {
 }
 
 #[inline]
-#[instrument(level = "error", skip(opts))]
-async fn run(opts: Opts) -> Result {
-    let Opts {
-        log_filter: _,
-        loki_endpoint: _,
-        threads: _,
-        client,
-    } = opts;
-
-    let mut client = crate::client::build(client).await?;
+#[instrument(level = "error", skip(config))]
+async fn run(config: Config) -> Result {
+    if config.opts.migrate_only {
+        crate::client::migrate(config.opts.client.storage().clone()).await?;
+        info!("Migrations complete, exiting");
+        return Ok(());
+    }
+
+    // The client consumes its options, but the originals are kept around to
+    // detect changes on reload
+    let client = config.opts.client.clone();
+
+    let mut bot = crate::client::build(client, Box::new(config)).await?;
     let signal;
 
     #[cfg(unix)]
@@ -190,8 +340,23 @@ async fn run(opts: Opts) -> Result {
         use futures_util::stream::FuturesUnordered;
         use tokio::signal::unix::SignalKind;
 
+        let mut hangup = tokio::signal::unix::signal(SignalKind::hangup())
+            .context("Error hooking signal SIGHUP")?;
+        let reloader = Arc::clone(&bot.reloader);
+        tokio::spawn(
+            async move {
+                while hangup.recv().await.is_some() {
+                    info!("SIGHUP received, reloading configuration...");
+                    match reloader.reload().await {
+                        Ok(report) => report.log(),
+                        Err(err) => error!(?err, "Error reloading configuration"),
+                    }
+                }
+            }
+            .instrument(error_span!("reload")),
+        );
+
         let mut stream = [
-            SignalKind::hangup(),
             SignalKind::interrupt(),
             SignalKind::quit(),
             SignalKind::terminate(),
@@ -229,7 +394,7 @@ async fn run(opts: Opts) -> Result {
 
     let ret = tokio::select! {
         s = signal => StopType::Signal(s),
-        r = client.start() => StopType::Closed(r),
+        r = bot.start() => StopType::Closed(r),
     };
 
     let shutdown = !matches!(ret, StopType::Closed(Err(_)));
@@ -246,8 +411,10 @@ async fn run(opts: Opts) -> Result {
     };
 
     if shutdown {
-        client.shard_manager.shutdown_all().await;
+        bot.client.shard_manager.shutdown_all().await;
     }
 
+    bot.flush().await;
+
     ret
 }
@@ -16,3 +16,5 @@ macro_rules! proto_mod {
 
 proto_mod!(pub modal, "modal");
 proto_mod!(pub component, "component");
+proto_mod!(pub guild, "guild");
+proto_mod!(pub user, "user");
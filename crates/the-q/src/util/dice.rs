@@ -0,0 +1,544 @@
+//! Parsing and evaluation of dice expressions, e.g. `3d6+2`, `4d6kh3` or
+//! `adv+5`
+
+use rand::Rng;
+
+use crate::prelude::*;
+
+/// Most terms allowed in one expression
+pub const MAX_TERMS: usize = 20;
+/// Most dice allowed in one term
+pub const MAX_DICE: u32 = 100;
+/// Most sides allowed on one die
+pub const MAX_SIDES: u32 = 1000;
+/// Largest constant allowed in an expression
+pub const MAX_CONST: i64 = 1_000_000;
+/// Most extra dice an exploding term can roll
+pub const MAX_EXPLOSIONS: usize = 100;
+
+/// Example inputs accepted by [`parse`], suitable for showing to users
+pub const EXAMPLES: &[&str] = &["d20", "3d6+2", "4d6kh3", "adv+5", "2d10!"];
+
+/// Which dice of a term count towards its total
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Highest(u32),
+    Lowest(u32),
+}
+
+/// A group of identical dice, e.g. `3d6`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u32,
+    /// Whether each die showing its highest face is rolled again
+    pub explode: bool,
+    pub keep: Option<Keep>,
+}
+
+impl Dice {
+    /// Roll twice and keep the higher (or lower) result
+    const fn d20_pair(keep: Keep) -> Self {
+        Self {
+            count: 2,
+            sides: 20,
+            explode: false,
+            keep: Some(keep),
+        }
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            count,
+            sides,
+            explode,
+            keep,
+        } = *self;
+
+        write!(f, "{count}d{sides}")?;
+        if explode {
+            f.write_str("!")?;
+        }
+        match keep {
+            Some(Keep::Highest(n)) => write!(f, "kh{n}"),
+            Some(Keep::Lowest(n)) => write!(f, "kl{n}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A single summand of an expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Term {
+    Dice(Dice),
+    Const(i64),
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dice(d) => d.fmt(f),
+            Self::Const(c) => c.fmt(f),
+        }
+    }
+}
+
+/// A parsed dice expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr {
+    /// Each term, paired with whether it is subtracted
+    pub terms: Vec<(bool, Term)>,
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, &(neg, term)) in self.terms.iter().enumerate() {
+            match (i, neg) {
+                (0, false) => (),
+                (0, true) => f.write_str("-")?,
+                (_, false) => f.write_str(" + ")?,
+                (_, true) => f.write_str(" - ")?,
+            }
+            term.fmt(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An error arising from parsing a dice expression with [`parse`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiceError {
+    /// The input was blank
+    Empty,
+    /// An unexpected character was found at the given byte offset
+    Unexpected(usize, char),
+    /// The input ended partway through a term
+    UnexpectedEnd,
+    /// The expression has more than [`MAX_TERMS`] terms
+    TooManyTerms,
+    /// A term rolls no dice or more than [`MAX_DICE`] dice
+    DiceCount(u64),
+    /// A die has no sides or more than [`MAX_SIDES`] sides
+    Sides(u64),
+    /// A term keeps no dice or more dice than it rolls
+    Keep(u64),
+    /// A constant is larger than [`MAX_CONST`]
+    TooLarge,
+}
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("No dice to roll"),
+            Self::Unexpected(pos, c) => write!(f, "Unexpected {c:?} at position {}", pos + 1),
+            Self::UnexpectedEnd => f.write_str("Unexpected end of expression"),
+            Self::TooManyTerms => write!(f, "Too many terms (the limit is {MAX_TERMS})"),
+            Self::DiceCount(n) => write!(f, "Can't roll {n} dice (the limit is {MAX_DICE})"),
+            Self::Sides(n) => write!(f, "Can't roll a {n}-sided die (the limit is {MAX_SIDES})"),
+            Self::Keep(n) => write!(f, "Can't keep {n} dice from that roll"),
+            Self::TooLarge => write!(f, "Number too large (the limit is {MAX_CONST})"),
+        }
+    }
+}
+
+impl std::error::Error for DiceError {}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> { self.s[self.pos..].chars().next() }
+
+    fn skip_ws(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, tok: &str) -> bool {
+        let found = self.s[self.pos..]
+            .get(..tok.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(tok));
+        if found {
+            self.pos += tok.len();
+        }
+        found
+    }
+
+    fn unexpected(&self) -> DiceError {
+        self.peek().map_or(DiceError::UnexpectedEnd, |c| {
+            DiceError::Unexpected(self.pos, c)
+        })
+    }
+
+    fn number(&mut self) -> Result<Option<u64>, DiceError> {
+        let rest = &self.s[self.pos..];
+        let len = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if len == 0 {
+            return Ok(None);
+        }
+
+        self.pos += len;
+        rest[..len]
+            .parse()
+            .map(Some)
+            .map_err(|_| DiceError::TooLarge)
+    }
+
+    fn term(&mut self) -> Result<Term, DiceError> {
+        if self.eat("adv") {
+            return Ok(Term::Dice(Dice::d20_pair(Keep::Highest(1))));
+        }
+        if self.eat("dis") {
+            return Ok(Term::Dice(Dice::d20_pair(Keep::Lowest(1))));
+        }
+
+        let count = self.number()?;
+        if !self.eat("d") {
+            let n = count.ok_or_else(|| self.unexpected())?;
+            return i64::try_from(n)
+                .ok()
+                .filter(|&n| n <= MAX_CONST)
+                .map(Term::Const)
+                .ok_or(DiceError::TooLarge);
+        }
+
+        let count = count.unwrap_or(1);
+        let count = u32::try_from(count)
+            .ok()
+            .filter(|c| (1..=MAX_DICE).contains(c))
+            .ok_or(DiceError::DiceCount(count))?;
+
+        let sides = if self.eat("%") {
+            100
+        } else {
+            self.number()?.ok_or_else(|| self.unexpected())?
+        };
+        let sides = u32::try_from(sides)
+            .ok()
+            .filter(|s| (1..=MAX_SIDES).contains(s))
+            .ok_or(DiceError::Sides(sides))?;
+
+        let explode = self.eat("!");
+
+        let keep: Option<fn(u32) -> Keep> = if self.eat("kl") {
+            Some(Keep::Lowest)
+        } else if self.eat("kh") || self.eat("k") {
+            Some(Keep::Highest)
+        } else {
+            None
+        };
+        let keep = keep
+            .map(|k| {
+                let n = self.number()?.unwrap_or(1);
+                u32::try_from(n)
+                    .ok()
+                    .filter(|n| (1..=count).contains(n))
+                    .map(k)
+                    .ok_or(DiceError::Keep(n))
+            })
+            .transpose()?;
+
+        Ok(Term::Dice(Dice {
+            count,
+            sides,
+            explode,
+            keep,
+        }))
+    }
+}
+
+/// Parse a dice expression, consisting of dice (e.g. `3d6`, `d%`) and
+/// constants joined by `+` or `-`
+///
+/// Dice may be followed by `!` to explode them and `khN` or `klN` to keep
+/// only the highest or lowest `N`.  `adv` and `dis` are shorthand for
+/// `2d20kh1` and `2d20kl1`.
+///
+/// # Errors
+/// This function returns an error if the input is malformed or exceeds any of
+/// the limits defined in this module.
+pub fn parse(s: &str) -> Result<Expr, DiceError> {
+    let mut p = Parser { s, pos: 0 };
+    let mut terms = vec![];
+
+    p.skip_ws();
+    if p.peek().is_none() {
+        return Err(DiceError::Empty);
+    }
+
+    let mut neg = p.eat("-");
+    loop {
+        p.skip_ws();
+        terms.push((neg, p.term()?));
+        if terms.len() > MAX_TERMS {
+            return Err(DiceError::TooManyTerms);
+        }
+
+        p.skip_ws();
+        neg = match p.peek() {
+            None => break,
+            Some('+') => false,
+            Some('-') => true,
+            Some(c) => return Err(DiceError::Unexpected(p.pos, c)),
+        };
+        p.pos += 1;
+    }
+
+    Ok(Expr { terms })
+}
+
+/// The outcome of rolling a single die
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Die {
+    pub value: u32,
+    /// Whether this die counts towards its term's total
+    pub kept: bool,
+    /// Whether this die caused another to be rolled
+    pub exploded: bool,
+}
+
+/// The outcome of evaluating a single term
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermRoll {
+    pub negative: bool,
+    pub term: Term,
+    /// Every die rolled, in the order rolled
+    pub dice: Vec<Die>,
+    pub total: i64,
+}
+
+/// The outcome of evaluating a whole expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Roll {
+    pub terms: Vec<TermRoll>,
+    pub total: i64,
+}
+
+fn roll_dice(dice: Dice, rng: &mut impl Rng) -> Vec<Die> {
+    let Dice {
+        count,
+        sides,
+        explode,
+        keep,
+    } = dice;
+    let mut out = Vec::with_capacity(count.try_into().unwrap_or(0));
+    let mut explosions = 0;
+
+    for _ in 0..count {
+        loop {
+            let value = rng.gen_range(1..=sides);
+            let exploded = explode && value == sides && explosions < MAX_EXPLOSIONS;
+            out.push(Die {
+                value,
+                kept: true,
+                exploded,
+            });
+
+            if !exploded {
+                break;
+            }
+            explosions += 1;
+        }
+    }
+
+    if let Some(keep) = keep {
+        let mut order: Vec<_> = (0..out.len()).collect();
+        let n = match keep {
+            Keep::Highest(n) => {
+                order.sort_by_key(|&i| std::cmp::Reverse(out[i].value));
+                n
+            },
+            Keep::Lowest(n) => {
+                order.sort_by_key(|&i| out[i].value);
+                n
+            },
+        };
+
+        for &i in order.iter().skip(n.try_into().unwrap_or(usize::MAX)) {
+            out[i].kept = false;
+        }
+    }
+
+    out
+}
+
+impl Expr {
+    /// Roll every die in this expression and sum the results
+    pub fn roll(&self, rng: &mut impl Rng) -> Roll {
+        let terms: Vec<_> = self
+            .terms
+            .iter()
+            .map(|&(negative, term)| {
+                let (dice, total) = match term {
+                    Term::Dice(d) => {
+                        let dice = roll_dice(d, rng);
+                        let total = dice
+                            .iter()
+                            .filter(|d| d.kept)
+                            .map(|d| i64::from(d.value))
+                            .sum();
+                        (dice, total)
+                    },
+                    Term::Const(c) => (vec![], c),
+                };
+
+                TermRoll {
+                    negative,
+                    term,
+                    dice,
+                    total,
+                }
+            })
+            .collect();
+
+        let total = terms
+            .iter()
+            .map(|t| if t.negative { -t.total } else { t.total })
+            .sum();
+
+        Roll { terms, total }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn prop_term() -> impl Strategy<Value = Term> {
+        prop_oneof![
+            (0..=MAX_CONST).prop_map(Term::Const),
+            (
+                1..=MAX_DICE,
+                1..=MAX_SIDES,
+                any::<bool>(),
+                any::<Option<(bool, u32)>>()
+            )
+                .prop_map(|(count, sides, explode, keep)| {
+                    let keep = keep.map(|(high, n)| {
+                        let n = n % count + 1;
+                        if high {
+                            Keep::Highest(n)
+                        } else {
+                            Keep::Lowest(n)
+                        }
+                    });
+
+                    Term::Dice(Dice {
+                        count,
+                        sides,
+                        explode,
+                        keep,
+                    })
+                }),
+        ]
+    }
+
+    fn prop_expr() -> impl Strategy<Value = Expr> {
+        prop::collection::vec((any::<bool>(), prop_term()), 1..=MAX_TERMS)
+            .prop_map(|terms| Expr { terms })
+    }
+
+    /// The smallest and largest totals a term can produce
+    fn bounds(term: Term) -> (i64, i64) {
+        match term {
+            Term::Const(c) => (c, c),
+            Term::Dice(Dice {
+                count,
+                sides,
+                explode,
+                keep,
+            }) => {
+                let kept = match keep {
+                    Some(Keep::Highest(n) | Keep::Lowest(n)) => n,
+                    None => count,
+                };
+                let rolled = if explode {
+                    u64::from(count) + MAX_EXPLOSIONS as u64
+                } else {
+                    u64::from(count)
+                };
+                let max = if explode { rolled } else { u64::from(kept) };
+
+                (
+                    i64::from(kept),
+                    i64::try_from(max * u64::from(sides)).unwrap(),
+                )
+            },
+        }
+    }
+
+    #[test]
+    fn examples() {
+        for ex in EXAMPLES {
+            parse(ex).unwrap();
+        }
+
+        assert_eq!(parse("3d6 + 2").unwrap().to_string(), "3d6 + 2");
+        assert_eq!(parse("-d%").unwrap().to_string(), "-1d100");
+        assert_eq!(parse("4D6K3").unwrap().to_string(), "4d6kh3");
+        assert_eq!(parse("adv").unwrap().to_string(), "2d20kh1");
+        assert_eq!(parse("2d10!kl").unwrap().to_string(), "2d10!kl1");
+
+        assert_eq!(parse("  "), Err(DiceError::Empty));
+        assert_eq!(parse("3d"), Err(DiceError::UnexpectedEnd));
+        assert_eq!(parse("3d6x"), Err(DiceError::Unexpected(3, 'x')));
+        assert_eq!(parse("0d6"), Err(DiceError::DiceCount(0)));
+        assert_eq!(parse("1d0"), Err(DiceError::Sides(0)));
+        assert_eq!(parse("2d6kh3"), Err(DiceError::Keep(3)));
+        assert_eq!(parse("99999999999999999999"), Err(DiceError::TooLarge));
+    }
+
+    #[test]
+    fn keep() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let roll = parse("10d6kl3").unwrap().roll(&mut rng);
+        let dice = &roll.terms[0].dice;
+        let max_kept = dice.iter().filter(|d| d.kept).map(|d| d.value).max();
+        let min_dropped = dice.iter().filter(|d| !d.kept).map(|d| d.value).min();
+
+        assert_eq!(dice.iter().filter(|d| d.kept).count(), 3);
+        assert!(max_kept <= min_dropped);
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip(expr in prop_expr()) {
+            prop_assert_eq!(parse(&expr.to_string()), Ok(expr));
+        }
+
+        #[test]
+        fn never_panics(s in ".{0,64}") {
+            parse(&s).ok();
+        }
+
+        #[test]
+        fn totals_in_bounds(expr in prop_expr(), seed in any::<u64>()) {
+            let roll = expr.roll(&mut StdRng::seed_from_u64(seed));
+
+            let mut total = 0;
+            for (t, &(neg, term)) in roll.terms.iter().zip(&expr.terms) {
+                let (min, max) = bounds(term);
+                prop_assert!((min..=max).contains(&t.total));
+                prop_assert_eq!(t.negative, neg);
+
+                if let Term::Dice(d) = term {
+                    let extra = t.dice.len() - usize::try_from(d.count).unwrap();
+                    prop_assert!(extra <= MAX_EXPLOSIONS);
+                    prop_assert!(d.explode || extra == 0);
+                }
+
+                total += if neg { -t.total } else { t.total };
+            }
+
+            prop_assert_eq!(roll.total, total);
+        }
+    }
+}
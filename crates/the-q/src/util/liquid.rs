@@ -0,0 +1,171 @@
+//! Content-aware image scaling by seam carving, as popularized by GIMP's
+//! "liquid rescale" plugin
+
+use jpeggr::image::{imageops, imageops::FilterType, Rgba, RgbaImage};
+
+/// Perceived brightness of a pixel, premultiplied by its opacity
+fn luma(Rgba([r, g, b, a]): Rgba<u8>) -> u32 {
+    (u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114) * u32::from(a) / 255_000
+}
+
+/// Compute the gradient magnitude of every pixel, in row-major order
+fn energy(image: &RgbaImage) -> Vec<u32> {
+    let (w, h) = image.dimensions();
+    let lum: Vec<_> = image.pixels().map(|&p| luma(p)).collect();
+    let at = |x: u32, y: u32| lum[(y * w + x) as usize];
+
+    (0..h)
+        .flat_map(|y| (0..w).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            // Compare against each neighbor separately so that details one
+            // pixel wide still stand out
+            let p = at(x, y);
+            p.abs_diff(at(x.saturating_sub(1), y))
+                + p.abs_diff(at((x + 1).min(w - 1), y))
+                + p.abs_diff(at(x, y.saturating_sub(1)))
+                + p.abs_diff(at(x, (y + 1).min(h - 1)))
+        })
+        .collect()
+}
+
+/// Find the connected top-to-bottom path of pixels with the least total
+/// energy, returning its column in each row
+fn find_seam(image: &RgbaImage) -> Vec<u32> {
+    let (w, h) = image.dimensions();
+    let (wu, hu) = (w as usize, h as usize);
+    let mut cost = energy(image);
+
+    for y in 1..hu {
+        for x in 0..wu {
+            let above = &cost[(y - 1) * wu..y * wu];
+            let best = above[x.saturating_sub(1)..=(x + 1).min(wu - 1)]
+                .iter()
+                .min()
+                .copied()
+                .unwrap_or(0);
+            cost[y * wu + x] += best;
+        }
+    }
+
+    let row = |y: usize| &cost[y * wu..(y + 1) * wu];
+    let mut x = row(hu - 1)
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, c)| c)
+        .map_or(0, |(x, _)| x);
+    let mut seam = vec![0; hu];
+    seam[hu - 1] = x;
+
+    for y in (0..hu - 1).rev() {
+        let lo = x.saturating_sub(1);
+        x = lo
+            + row(y)[lo..=(x + 1).min(wu - 1)]
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, c)| c)
+                .map_or(0, |(i, _)| i);
+        seam[y] = x;
+    }
+
+    seam.into_iter()
+        .map(|x| u32::try_from(x).unwrap_or_else(|_| unreachable!()))
+        .collect()
+}
+
+/// Remove the vertical seam with the least energy from an image
+fn carve_column(image: &RgbaImage) -> RgbaImage {
+    let (w, h) = image.dimensions();
+    let seam = find_seam(image);
+
+    RgbaImage::from_fn(w - 1, h, |x, y| {
+        let skip = u32::from(x >= seam[y as usize]);
+        *image.get_pixel(x + skip, y)
+    })
+}
+
+/// Shrink an image to the given size by repeatedly removing the seams of
+/// pixels with the least detail
+///
+/// Sizes larger than the image are clamped to its size, and sizes of zero
+/// to one pixel.
+#[must_use]
+pub fn carve(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let (w, h) = image.dimensions();
+    let mut out = image.clone();
+    if w == 0 || h == 0 {
+        return out;
+    }
+
+    for _ in width.clamp(1, w)..w {
+        out = carve_column(&out);
+    }
+
+    // Carve rows by carving the columns of the transposed image
+    out = imageops::rotate90(&out);
+    for _ in height.clamp(1, h)..h {
+        out = carve_column(&out);
+    }
+
+    imageops::rotate270(&out)
+}
+
+/// Seam-carve an image down by the given factor, then stretch it back to its
+/// original size, distorting everything but its most detailed features
+#[must_use]
+pub fn explode(image: &RgbaImage, scale: f64) -> RgbaImage {
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "The scaled size is clamped to the image size"
+    )]
+    let scaled = |n: u32| (f64::from(n) * scale.clamp(0.0, 1.0)).round() as u32;
+
+    let (w, h) = image.dimensions();
+    let carved = carve(image, scaled(w), scaled(h));
+    imageops::resize(&carved, w, h, FilterType::Triangle)
+}
+
+#[cfg(test)]
+mod test {
+    use jpeggr::image::{Rgba, RgbaImage};
+
+    use super::{carve, explode, find_seam};
+
+    /// A black image with a white vertical stripe at the given column
+    fn stripe(w: u32, h: u32, col: u32) -> RgbaImage {
+        RgbaImage::from_fn(w, h, |x, _| {
+            if x == col {
+                Rgba([255; 4])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn seams_avoid_detail() {
+        let image = stripe(12, 8, 6);
+        let seam = find_seam(&image);
+        assert_eq!(seam.len(), 8);
+        assert!(seam.iter().all(|&x| x.abs_diff(6) > 1));
+        assert!(seam.windows(2).all(|w| w[0].abs_diff(w[1]) <= 1));
+    }
+
+    #[test]
+    fn carving() {
+        let image = stripe(12, 8, 6);
+        let carved = carve(&image, 6, 5);
+        assert_eq!(carved.dimensions(), (6, 5));
+
+        // The stripe survives carving intact
+        let white: Vec<_> = (0..6)
+            .filter(|&x| carved.get_pixel(x, 0).0 == [255; 4])
+            .collect();
+        assert_eq!(white.len(), 1);
+        assert!((0..5).all(|y| carved.get_pixel(white[0], y).0 == [255; 4]));
+
+        assert_eq!(carve(&image, 0, 100).dimensions(), (1, 8));
+        assert_eq!(explode(&image, 0.5).dimensions(), (12, 8));
+        assert_eq!(carve(&RgbaImage::new(1, 1), 1, 1).dimensions(), (1, 1));
+    }
+}
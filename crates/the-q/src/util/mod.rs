@@ -1,3 +1,7 @@
+pub mod dice;
+pub mod liquid;
+pub mod time;
+
 use crate::prelude::*;
 
 #[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
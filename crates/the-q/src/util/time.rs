@@ -0,0 +1,487 @@
+//! Parsing of human-friendly relative and absolute times, e.g. `in 2h`,
+//! `tomorrow 9am` or `next friday`
+
+use chrono::{
+    DateTime, Datelike, Days, FixedOffset, NaiveDate, NaiveTime, TimeDelta, Timelike, Utc, Weekday,
+};
+
+use crate::prelude::*;
+
+/// Hour of the day used for day expressions without a time, e.g. `tomorrow`
+const DEFAULT_HOUR: u32 = 9;
+/// Hour of the day meant by `tonight` without a time
+const TONIGHT_HOUR: u32 = 20;
+/// Largest edit distance at which an unknown word is corrected to a known one
+const MAX_TYPO_DISTANCE: usize = 2;
+
+const UNITS: &[(&[&str], i64)] = &[
+    (&["s", "sec", "secs", "second", "seconds"], 1),
+    (&["m", "min", "mins", "minute", "minutes"], 60),
+    (&["h", "hr", "hrs", "hour", "hours"], 60 * 60),
+    (&["d", "day", "days"], 24 * 60 * 60),
+    (&["w", "wk", "wks", "week", "weeks"], 7 * 24 * 60 * 60),
+];
+
+const KEYWORDS: &[&str] = &[
+    "in", "at", "on", "and", "next", "this", "today", "tonight", "tomorrow", "noon", "midnight",
+];
+
+const WEEKDAYS: &[&str] = &[
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// Example inputs accepted by [`parse_time`], suitable for showing to users
+pub const EXAMPLES: &[&str] = &[
+    "in 2h",
+    "in 1 hour 30 minutes",
+    "tomorrow 9am",
+    "next friday",
+    "friday at 5:30pm",
+    "tonight at 11",
+];
+
+/// An error arising from parsing a time with [`parse_time`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeError {
+    /// The input was not understood
+    Unrecognized {
+        /// The input as given
+        input: String,
+        /// A corrected version of the input that would have been understood,
+        /// if one could be found
+        suggestion: Option<String>,
+    },
+    /// The input refers to a time that has already passed
+    Past(String),
+    /// The input refers to a time too far away to be represented
+    OutOfRange(String),
+}
+
+impl fmt::Display for TimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unrecognized {
+                input,
+                suggestion: None,
+            } => write!(f, "Couldn't understand {input:?} as a time"),
+            Self::Unrecognized {
+                input,
+                suggestion: Some(s),
+            } => write!(
+                f,
+                "Couldn't understand {input:?} as a time (did you mean {s:?}?)"
+            ),
+            Self::Past(i) => write!(f, "{i:?} is in the past"),
+            Self::OutOfRange(i) => write!(f, "{i:?} is too far in the future"),
+        }
+    }
+}
+
+impl std::error::Error for TimeError {}
+
+fn tokenize(s: &str) -> Vec<String> {
+    s.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn unit(s: &str) -> Option<i64> {
+    UNITS
+        .iter()
+        .find(|(names, _)| names.contains(&s))
+        .map(|&(_, secs)| secs)
+}
+
+/// Parse a token of the form `30m` or `1h30m`
+fn compound(mut s: &str) -> Option<i64> {
+    let mut secs = 0_i64;
+
+    while !s.is_empty() {
+        let digits = s.find(|c: char| !c.is_ascii_digit())?;
+        let (num, rest) = s.split_at(digits);
+        let letters = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let (name, rest) = rest.split_at(letters);
+
+        let num: i64 = num.parse().ok()?;
+        secs = secs.saturating_add(num.saturating_mul(unit(name)?));
+        s = rest;
+    }
+
+    Some(secs)
+}
+
+/// Parse a duration such as `2h`, `1h30m`, `an hour` or `3 days and 4 hours`,
+/// returning its length in seconds
+fn parse_duration(toks: &[&str]) -> Option<i64> {
+    let mut secs = 0_i64;
+    let mut toks = toks;
+
+    loop {
+        match *toks {
+            [tok, ref rest @ ..] if tok.starts_with(|c: char| c.is_ascii_digit()) => {
+                if let Some(s) = compound(tok) {
+                    secs = secs.saturating_add(s);
+                    toks = rest;
+                } else {
+                    let num: i64 = tok.parse().ok()?;
+                    let (name, rest) = rest.split_first()?;
+                    secs = secs.saturating_add(num.saturating_mul(unit(name)?));
+                    toks = rest;
+                }
+            },
+            ["a" | "an", name, ref rest @ ..] => {
+                secs = secs.saturating_add(unit(name)?);
+                toks = rest;
+            },
+            _ => return None,
+        }
+
+        match *toks {
+            [] => break Some(secs),
+            ["and", ref rest @ ..] => toks = rest,
+            _ => (),
+        }
+    }
+}
+
+/// Parse a time of day such as `9`, `9am`, `9:30 pm` or `21:30`
+fn clock(s: &str, pm: Option<bool>) -> Option<NaiveTime> {
+    let (s, pm) = match pm {
+        Some(pm) => (s, Some(pm)),
+        None => s
+            .strip_suffix("am")
+            .map(|s| (s, Some(false)))
+            .or_else(|| s.strip_suffix("pm").map(|s| (s, Some(true))))
+            .unwrap_or((s, None)),
+    };
+
+    let (hour, min) = s.split_once(':').unwrap_or((s, "00"));
+    if !(1..=2).contains(&hour.len())
+        || min.len() != 2
+        || !hour.bytes().chain(min.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let mut hour: u32 = hour.parse().ok()?;
+    let min: u32 = min.parse().ok()?;
+
+    if let Some(pm) = pm {
+        if !(1..=12).contains(&hour) {
+            return None;
+        }
+
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    }
+
+    NaiveTime::from_hms_opt(hour, min, 0)
+}
+
+/// Parse a time of day, optionally preceded by `at`
+fn parse_clock(toks: &[&str]) -> Option<NaiveTime> {
+    let toks = match toks {
+        ["at", rest @ ..] if !rest.is_empty() => rest,
+        t => t,
+    };
+
+    match *toks {
+        ["noon"] => NaiveTime::from_hms_opt(12, 0, 0),
+        ["midnight"] => Some(NaiveTime::MIN),
+        [t] => clock(t, None),
+        [t, m @ ("am" | "pm")] => clock(t, Some(m == "pm")),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Day {
+    Today,
+    Tonight,
+    Tomorrow,
+    Weekday { day: Weekday, next: bool },
+    Date(NaiveDate),
+}
+
+/// Parse a day, optionally preceded by `on`
+fn parse_day(toks: &[&str]) -> Option<Day> {
+    let toks = match toks {
+        ["on", rest @ ..] if !rest.is_empty() => rest,
+        t => t,
+    };
+
+    Some(match *toks {
+        ["today"] => Day::Today,
+        ["tonight"] => Day::Tonight,
+        ["tomorrow" | "tmrw" | "tmr"] => Day::Tomorrow,
+        ["next", day] => Day::Weekday {
+            day: day.parse().ok()?,
+            next: true,
+        },
+        ["this", day] => Day::Weekday {
+            day: day.parse().ok()?,
+            next: false,
+        },
+        [day] => day.parse().map_or_else(
+            |_| {
+                NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                    .ok()
+                    .map(Day::Date)
+            },
+            |day| Some(Day::Weekday { day, next: false }),
+        )?,
+        _ => return None,
+    })
+}
+
+/// Resolve a day and time of day to a point in time relative to `now`,
+/// returning `None` if the result is out of range
+fn resolve(
+    day: Option<Day>,
+    time: Option<NaiveTime>,
+    now: DateTime<FixedOffset>,
+) -> Option<DateTime<FixedOffset>> {
+    let today = now.date_naive();
+    let at = |date: NaiveDate, time: NaiveTime| {
+        date.and_time(time)
+            .and_local_timezone(*now.offset())
+            .single()
+    };
+    let or_default = |hour| time.map_or_else(|| NaiveTime::from_hms_opt(hour, 0, 0), Some);
+
+    match day {
+        None => {
+            let time = time?;
+            at(today, time)
+                .filter(|&t| t > now)
+                .or_else(|| at(today.succ_opt()?, time))
+        },
+        Some(Day::Today) => at(today, or_default(DEFAULT_HOUR)?),
+        Some(Day::Tonight) => {
+            let time = or_default(TONIGHT_HOUR)?;
+            let time = if time.hour() < 12 {
+                time.overflowing_add_signed(TimeDelta::hours(12)).0
+            } else {
+                time
+            };
+            at(today, time)
+        },
+        Some(Day::Tomorrow) => at(today.succ_opt()?, or_default(DEFAULT_HOUR)?),
+        Some(Day::Date(date)) => at(date, or_default(DEFAULT_HOUR)?),
+        Some(Day::Weekday { day, next }) => {
+            let time = or_default(DEFAULT_HOUR)?;
+            let ahead =
+                (7 + day.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+            let ahead = if next && ahead == 0 { 7 } else { ahead };
+
+            at(today.checked_add_days(Days::new(ahead.into()))?, time)
+                .filter(|&t| t > now)
+                .or_else(|| at(today.checked_add_days(Days::new((ahead + 7).into()))?, time))
+        },
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Invalid {
+    Unrecognized,
+    OutOfRange,
+}
+
+fn parse(toks: &[&str], now: DateTime<FixedOffset>) -> Result<DateTime<FixedOffset>, Invalid> {
+    let rel = match toks {
+        ["in", rest @ ..] => rest,
+        t => t,
+    };
+
+    if let Some(secs) = parse_duration(rel) {
+        return TimeDelta::try_seconds(secs)
+            .and_then(|d| now.checked_add_signed(d))
+            .ok_or(Invalid::OutOfRange);
+    }
+
+    // Try every split of the input into a day and a time of day, in either
+    // order, either of which may be omitted
+    let resolved = (0..=toks.len()).find_map(|i| {
+        let (l, r) = toks.split_at(i);
+        [(l, r), (r, l)].into_iter().find_map(|(day, time)| {
+            let day = if day.is_empty() {
+                None
+            } else {
+                Some(parse_day(day)?)
+            };
+            let time = if time.is_empty() {
+                None
+            } else {
+                Some(parse_clock(time)?)
+            };
+
+            (day.is_some() || time.is_some()).then(|| resolve(day, time, now))
+        })
+    });
+
+    resolved
+        .ok_or(Invalid::Unrecognized)?
+        .ok_or(Invalid::OutOfRange)
+}
+
+/// Find the known word closest to an unknown one, if any is close enough
+fn correct(tok: &str) -> Option<&'static str> {
+    if tok.len() < 3 || !tok.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let words = KEYWORDS
+        .iter()
+        .chain(WEEKDAYS)
+        .chain(UNITS.iter().flat_map(|(n, _)| *n))
+        .copied();
+
+    if words.clone().any(|w| w == tok) {
+        return None;
+    }
+
+    words
+        .map(|w| (strsim::levenshtein(tok, w), w))
+        .filter(|&(d, _)| d <= MAX_TYPO_DISTANCE && d * 2 <= tok.len())
+        .min_by_key(|&(d, w)| (d, w.len().abs_diff(tok.len())))
+        .map(|(_, w)| w)
+}
+
+/// Correct any misspelled words in the input, returning the result only if it
+/// can then be understood
+fn suggest(toks: &[&str], now: DateTime<FixedOffset>) -> Option<String> {
+    let fixed: Vec<_> = toks.iter().map(|&t| correct(t).unwrap_or(t)).collect();
+
+    (fixed != toks && parse(&fixed, now).is_ok()).then(|| fixed.join(" "))
+}
+
+/// Parse a time such as `in 2h`, `tomorrow 9am` or `next friday`, relative to
+/// `now`
+///
+/// Days and times of day are interpreted in the timezone `tz`.  Days given
+/// without a time of day refer to 9am (or 8pm for `tonight`), and a time of
+/// day given on its own refers to its next occurrence.
+///
+/// # Errors
+/// This function returns an error if the input cannot be understood or does
+/// not refer to a time after `now`.
+pub fn parse_time(
+    s: &str,
+    now: DateTime<Utc>,
+    tz: FixedOffset,
+) -> Result<DateTime<Utc>, TimeError> {
+    let toks = tokenize(s);
+    let toks: Vec<_> = toks.iter().map(String::as_str).collect();
+    let now_local = now.with_timezone(&tz);
+
+    match parse(&toks, now_local) {
+        Ok(t) if t > now => Ok(t.to_utc()),
+        Ok(_) => Err(TimeError::Past(s.trim().into())),
+        Err(Invalid::OutOfRange) => Err(TimeError::OutOfRange(s.trim().into())),
+        Err(Invalid::Unrecognized) => Err(TimeError::Unrecognized {
+            input: s.trim().into(),
+            suggestion: suggest(&toks, now_local),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, FixedOffset, Utc};
+
+    use super::{parse_time, TimeError};
+
+    fn now() -> DateTime<Utc> {
+        // A Wednesday afternoon
+        "2024-06-12T15:00:00Z".parse().unwrap()
+    }
+
+    fn at(s: &str) -> Result<String, TimeError> {
+        parse_time(s, now(), FixedOffset::east_opt(0).unwrap())
+            .map(|t| t.format("%m-%d %H:%M").to_string())
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(at("in 2h").unwrap(), "06-12 17:00");
+        assert_eq!(at("2 hours 30 minutes").unwrap(), "06-12 17:30");
+        assert_eq!(at("in an hour and 15 mins").unwrap(), "06-12 16:15");
+        assert_eq!(at("In 1h30m").unwrap(), "06-12 16:30");
+        assert_eq!(at("in 3 days, 4h").unwrap(), "06-15 19:00");
+        assert_eq!(at("in a week").unwrap(), "06-19 15:00");
+    }
+
+    #[test]
+    fn days() {
+        assert_eq!(at("tomorrow").unwrap(), "06-13 09:00");
+        assert_eq!(at("tomorrow 9:30am").unwrap(), "06-13 09:30");
+        assert_eq!(at("9am tomorrow").unwrap(), "06-13 09:00");
+        assert_eq!(at("next friday").unwrap(), "06-14 09:00");
+        assert_eq!(at("on friday at 5pm").unwrap(), "06-14 17:00");
+        assert_eq!(at("wednesday 5pm").unwrap(), "06-12 17:00");
+        assert_eq!(at("wed 2pm").unwrap(), "06-19 14:00");
+        assert_eq!(at("next wednesday").unwrap(), "06-19 09:00");
+        assert_eq!(at("tonight").unwrap(), "06-12 20:00");
+        assert_eq!(at("tonight at 11").unwrap(), "06-12 23:00");
+        assert_eq!(at("2024-07-01 14:00").unwrap(), "07-01 14:00");
+    }
+
+    #[test]
+    fn times_of_day() {
+        assert_eq!(at("at 4:30pm").unwrap(), "06-12 16:30");
+        assert_eq!(at("18:00").unwrap(), "06-12 18:00");
+        assert_eq!(at("noon").unwrap(), "06-13 12:00");
+        assert_eq!(at("12am").unwrap(), "06-13 00:00");
+        assert_eq!(at("midnight").unwrap(), "06-13 00:00");
+
+        let pacific = FixedOffset::west_opt(8 * 60 * 60).unwrap();
+        assert_eq!(
+            parse_time("9am", now(), pacific).unwrap(),
+            "2024-06-12T17:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(
+            parse_time("tomorrow", now(), pacific).unwrap(),
+            "2024-06-13T17:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let unrecognized = |s: &str, suggestion: Option<&str>| TimeError::Unrecognized {
+            input: s.trim().into(),
+            suggestion: suggestion.map(Into::into),
+        };
+
+        assert_eq!(at(""), Err(unrecognized("", None)));
+        assert_eq!(at("in 5"), Err(unrecognized("in 5", None)));
+        assert_eq!(at("13pm"), Err(unrecognized("13pm", None)));
+        assert_eq!(at("9:5"), Err(unrecognized("9:5", None)));
+        assert_eq!(at("tomorrow at"), Err(unrecognized("tomorrow at", None)));
+        assert_eq!(at("2h and"), Err(unrecognized("2h and", None)));
+        assert_eq!(
+            at(" Tommorow 9am"),
+            Err(unrecognized("Tommorow 9am", Some("tomorrow 9am")))
+        );
+        assert_eq!(
+            at("next fridya"),
+            Err(unrecognized("next fridya", Some("next friday")))
+        );
+        assert_eq!(
+            at("in 2 horus"),
+            Err(unrecognized("in 2 horus", Some("in 2 hours")))
+        );
+        assert_eq!(at("today 9am"), Err(TimeError::Past("today 9am".into())));
+        assert_eq!(
+            at("in 9999999999999 weeks"),
+            Err(TimeError::OutOfRange("in 9999999999999 weeks".into()))
+        );
+    }
+}